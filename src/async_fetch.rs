@@ -0,0 +1,150 @@
+//! Non-blocking variants of the registry queries in `fetch`.
+//!
+//! Reading the registry index is blocking I/O, so every query runs on a worker thread of its own
+//! and the returned [`Query`] future completes once it is done. The futures only rely on
+//! `std::future`, so they can be awaited from any executor.
+use crate::errors::*;
+use crate::fetch::{get_compatible_dependency, get_latest_dependency};
+use crate::Dependency;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use url::Url;
+
+/// A registry query running in the background.
+///
+/// Dropping the future does not cancel the query, its result is discarded instead.
+#[derive(Debug)]
+pub struct Query<T> {
+    state: Arc<Mutex<QueryState<T>>>,
+}
+
+#[derive(Debug)]
+struct QueryState<T> {
+    result: Option<Result<T>>,
+    waker: Option<Waker>,
+}
+
+impl<T: Send + 'static> Query<T> {
+    fn spawn<F>(query: F) -> Self
+    where
+        F: FnOnce() -> Result<T> + Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(QueryState {
+            result: None,
+            waker: None,
+        }));
+        let shared = Arc::clone(&state);
+        thread::spawn(move || {
+            let result = query();
+            let mut state = shared.lock().unwrap_or_else(|e| e.into_inner());
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        Query { state }
+    }
+}
+
+impl<T> Future for Query<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Query latest version from a registry index without blocking the calling task
+///
+/// See [`get_latest_dependency`] for details.
+pub fn get_latest_dependency_async(
+    crate_name: &str,
+    flag_allow_prerelease: bool,
+    manifest_path: &Path,
+    registry: &Option<Url>,
+) -> Query<Dependency> {
+    let crate_name = crate_name.to_owned();
+    let manifest_path = manifest_path.to_owned();
+    let registry = registry.clone();
+    Query::spawn(move || {
+        get_latest_dependency(
+            &crate_name,
+            flag_allow_prerelease,
+            &manifest_path,
+            &registry,
+        )
+    })
+}
+
+/// Query the newest version satisfying `version_req` without blocking the calling task
+///
+/// See [`get_compatible_dependency`] for details.
+pub fn get_compatible_dependency_async(
+    crate_name: &str,
+    version_req: &semver::VersionReq,
+    manifest_path: &Path,
+    registry: &Option<Url>,
+) -> Query<Dependency> {
+    let crate_name = crate_name.to_owned();
+    let version_req = version_req.clone();
+    let manifest_path = manifest_path.to_owned();
+    let registry = registry.clone();
+    Query::spawn(move || {
+        get_compatible_dependency(&crate_name, &version_req, &manifest_path, &registry)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::task::Wake;
+
+    /// Wakes the test by sending on a channel.
+    struct ChannelWaker(Mutex<mpsc::Sender<()>>);
+
+    impl Wake for ChannelWaker {
+        fn wake(self: Arc<Self>) {
+            let _ = self.0.lock().unwrap().send(());
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let (sender, receiver) = mpsc::channel();
+        let waker = Waker::from(Arc::new(ChannelWaker(Mutex::new(sender))));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            receiver.recv().unwrap();
+        }
+    }
+
+    #[test]
+    fn query_completes() {
+        let query = Query::spawn(|| {
+            thread::sleep(std::time::Duration::from_millis(10));
+            Ok(42)
+        });
+        assert_eq!(block_on(query).unwrap(), 42);
+    }
+
+    #[test]
+    fn query_reports_errors() {
+        let query = get_latest_dependency_async("", false, Path::new("Cargo.toml"), &None);
+        assert!(block_on(query).is_err());
+    }
+}
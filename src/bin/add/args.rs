@@ -1,12 +1,28 @@
 //! Handle `cargo add` arguments
 
-use cargo_edit::{find, registry_url, Dependency};
-use cargo_edit::{get_latest_dependency, CrateName};
+use cargo_edit::{
+    cache_ttl, find, find_workspace_root, registry_names, registry_url, req_with_precision,
+    set_cargo_flags, set_color_preference, set_fix_duplicates, set_generated_manifest_policy,
+    set_offline_cache_ttl, set_verbosity, ColorPreference, Config, Dependency,
+    GeneratedManifestPolicy, Manifest, OutputFormat, Platform, ReqPrecision, Verbosity,
+};
+use cargo_edit::{
+    get_git_revision, get_latest_dependency, get_latest_dependency_candidates,
+    get_latest_dependency_with_fallback, validate_crate_name, CrateName, NamingRules,
+};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
-use structopt::{clap::AppSettings, StructOpt};
+use std::time::Duration;
+use structopt::{
+    clap::{AppSettings, Shell},
+    StructOpt,
+};
 
 use crate::errors::*;
 
+/// The name cargo configuration knows crates.io by.
+const CRATES_IO_REGISTRY: &str = "crates-io";
+
 #[derive(Debug, StructOpt)]
 #[structopt(bin_name = "cargo")]
 pub enum Command {
@@ -15,20 +31,30 @@ pub enum Command {
     #[structopt(after_help = "\
 This command allows you to add a dependency to a Cargo.toml manifest file. If <crate> is a github \
 or gitlab repository URL, or a local path, `cargo add` will try to automatically get the crate \
-name and set the appropriate `--git` or `--path` value.
+name and set the appropriate `--git` or `--path` value. Package ID specs as cargo prints them \
+(e.g. `registry+https://github.com/rust-lang/crates.io-index#regex@1.4.3` or \
+`git+https://github.com/x/y?branch=dev#foo`) are accepted too.
 
 Please note that Cargo treats versions like '1.2.3' as '^1.2.3' (and that '^1.2.3' is specified \
 as '>=1.2.3 and <2.0.0'). By default, `cargo add` will use this format, as it is the one that the \
 crates.io registry suggests. One goal of `cargo add` is to prevent you from using wildcard \
-dependencies (version set to '*').")]
+dependencies (version set to '*').
+
+Inside a workspace, a crate listed in the `[workspace.dependencies]` of its root is inherited \
+with `workspace = true` rather than looked up, with any features given added next to it, unless \
+its source, version, registry or new name is given or `--no-workspace-inherit` is passed.")]
     Add(Args),
 }
 
 #[derive(Debug, StructOpt)]
 #[structopt(setting = AppSettings::ColoredHelp)]
 pub struct Args {
-    /// Crates to be added.
-    #[structopt(name = "crate", required = true)]
+    /// Crates to be added, each optionally followed by features to enable, e.g.
+    /// `serde+derive` or `tokio@1+full,macros`.
+    #[structopt(
+        name = "crate",
+        required_unless_one = &["completions", "man", "json-request"]
+    )]
     pub crates: Vec<String>,
 
     /// Rename a dependency in Cargo.toml,
@@ -69,13 +95,28 @@ pub struct Args {
     )]
     pub branch: Option<String>,
 
+    /// Pin a git dependency to the commit its branch, tag or default branch points to now, with
+    /// `rev`.
+    #[structopt(long = "pin-rev", conflicts_with = "vers", conflicts_with = "path")]
+    pub pin_rev: bool,
+
+    /// Add crates listed in `[workspace.dependencies]` of the workspace root with their own
+    /// version rather than inheriting them with `workspace = true`.
+    #[structopt(long = "no-workspace-inherit")]
+    pub no_workspace_inherit: bool,
+
     /// Specify the path the crate should be loaded from.
     #[structopt(long = "path", conflicts_with = "git")]
     pub path: Option<PathBuf>,
 
     /// Add as dependency to the given target platform.
-    #[structopt(long = "target", conflicts_with = "dev", conflicts_with = "build")]
-    pub target: Option<String>,
+    #[structopt(
+        long = "target",
+        conflicts_with = "dev",
+        conflicts_with = "build",
+        parse(try_from_str = Platform::parse_argument)
+    )]
+    pub target: Option<Platform>,
 
     /// Add as an optional dependency (for use in features).
     #[structopt(long = "optional", conflicts_with = "dev", conflicts_with = "build")]
@@ -123,14 +164,57 @@ pub struct Args {
     #[structopt(long = "no-default-features")]
     pub no_default_features: bool,
 
+    /// Pick the features of each crate from those its release offers, with the default features
+    /// checked, before adding it.
+    #[structopt(long = "interactive")]
+    pub interactive: bool,
+
     /// Do not print any output in case of success.
     #[structopt(long = "quiet", short = "q")]
     pub quiet: bool,
 
+    /// How to print the changes made: as `plain` progress lines, or as a `json` or `markdown`
+    /// report.
+    #[structopt(long = "output", value_name = "format", possible_values = OutputFormat::NAMES)]
+    pub output: Option<OutputFormat>,
+
+    /// Exit with status 2 if the manifest is left unchanged, or 3 if a dry run would change it.
+    #[structopt(long = "exit-code")]
+    pub exit_code: bool,
+
+    /// Print the changes to the manifest as a diff without writing it.
+    #[structopt(long = "dry-run")]
+    pub dry_run: bool,
+
     /// Run without accessing the network
     #[structopt(long = "offline")]
     pub offline: bool,
 
+    /// Fail rather than change the lock file
+    #[structopt(long = "locked")]
+    pub locked: bool,
+
+    /// Both `--locked` and `--offline`
+    #[structopt(long = "frozen")]
+    pub frozen: bool,
+
+    /// Update the registry index even if it was updated recently (see `CARGO_EDIT_CACHE_TTL`).
+    #[structopt(
+        long = "refresh",
+        conflicts_with = "offline",
+        conflicts_with = "frozen"
+    )]
+    pub refresh: bool,
+
+    /// Offline, only use cached index files fetched less than this many seconds ago, rather than
+    /// however old they are.
+    #[structopt(long = "offline-cache-ttl", value_name = "seconds")]
+    pub offline_cache_ttl: Option<u64>,
+
+    /// Ignore proxy settings and connect directly.
+    #[structopt(long = "no-proxy")]
+    pub no_proxy: bool,
+
     /// Sort dependencies even if currently unsorted
     #[structopt(long = "sort", short = "s")]
     pub sort: bool,
@@ -138,6 +222,67 @@ pub struct Args {
     /// Registry to use
     #[structopt(long = "registry", conflicts_with = "git", conflicts_with = "path")]
     pub registry: Option<String>,
+
+    /// How long a registry index update is good for, as configured.
+    #[structopt(skip)]
+    pub cache_ttl: Option<Duration>,
+
+    /// The registries to look crates up in, in order, as configured in `registries`.
+    #[structopt(skip)]
+    pub registry_order: Vec<String>,
+
+    /// The registries to look up some crates in instead, as configured in `crate-registries`.
+    #[structopt(skip)]
+    pub crate_registries: BTreeMap<String, Vec<String>>,
+
+    /// The registries configured for cargo besides crates.io, where crates are looked for too
+    /// when no registry is preferred, so that a name taken on several isn't silently picked from
+    /// crates.io.
+    #[structopt(skip)]
+    pub alternative_registries: Vec<String>,
+
+    /// How many parts of the latest version to require, as configured in `precision`.
+    #[structopt(skip)]
+    pub precision: ReqPrecision,
+
+    /// Crates whose pre-release versions are included, as configured in `prerelease-crates`.
+    #[structopt(skip)]
+    pub prerelease_crates: Vec<String>,
+
+    /// Print the crates queried from the index and cache hits, and with `-vv` HTTP requests too.
+    #[structopt(
+        long = "verbose",
+        short = "v",
+        parse(from_occurrences),
+        conflicts_with = "quiet"
+    )]
+    pub verbose: u8,
+
+    /// Merge dependencies listed more than once in a table, e.g. after a bad merge, instead of
+    /// failing to read the manifest.
+    #[structopt(long = "fix-duplicates")]
+    pub fix_duplicates: bool,
+
+    /// Edit `Cargo.toml.orig` instead of a manifest cargo generated when packaging the crate, as
+    /// found in an extracted `.crate`.
+    #[structopt(long = "orig")]
+    pub orig: bool,
+
+    /// Print the completion script of the command for the given shell, and exit.
+    #[structopt(long = "completions", value_name = "shell", possible_values = &Shell::variants())]
+    pub completions: Option<Shell>,
+
+    /// Print the man page of the command, in roff, and exit.
+    #[structopt(long = "man", conflicts_with = "completions")]
+    pub man: bool,
+
+    /// Read the other options as one JSON object from stdin, and print the report or error as JSON.
+    #[structopt(
+        long = "json-request",
+        conflicts_with = "completions",
+        conflicts_with = "man"
+    )]
+    pub json_request: bool,
 }
 
 fn parse_version_req(s: &str) -> Result<&str> {
@@ -146,6 +291,89 @@ fn parse_version_req(s: &str) -> Result<&str> {
 }
 
 impl Args {
+    /// Fill in the settings not given on the command line from the configuration.
+    pub fn apply_config(&mut self) -> Result<()> {
+        if self.fix_duplicates {
+            set_fix_duplicates();
+        }
+        let config = Config::load(&find(&self.manifest_path)?, "add")?;
+        self.allow_prerelease = config.flag("allow-prerelease", self.allow_prerelease)?;
+        self.quiet = config.flag("quiet", self.quiet)?;
+        self.offline = config.flag("offline", self.offline)? || self.frozen;
+        self.sort = config.flag("sort", self.sort)?;
+        self.no_workspace_inherit =
+            config.flag("no-workspace-inherit", self.no_workspace_inherit)?;
+        self.precision = config.get("precision")?.unwrap_or_default();
+        self.prerelease_crates = config.get("prerelease-crates")?.unwrap_or_default();
+        set_color_preference(config.color()?);
+        if self.output.is_none() {
+            self.output = config.get("output")?;
+        }
+        if self.registry.is_none() && self.git.is_none() && self.path.is_none() {
+            self.registry = config.get("registry")?;
+            self.registry_order = config.get("registries")?.unwrap_or_default();
+            self.crate_registries = config.get("crate-registries")?.unwrap_or_default();
+            if self.registry.is_none() && self.registry_order.is_empty() && self.vers.is_none() {
+                self.alternative_registries = registry_names(&find(&self.manifest_path)?)?;
+            }
+        }
+        self.cache_ttl = Some(config.cache_ttl()?);
+        if self.json_request {
+            // Nothing is printed but the one JSON document, and nothing is asked.
+            self.output = Some(OutputFormat::Json);
+            self.quiet = true;
+            set_color_preference(ColorPreference::Never);
+        }
+        set_verbosity(Verbosity::new(self.quiet, self.verbose));
+        set_cargo_flags(self.offline, self.locked, self.frozen);
+        if self.offline_cache_ttl.is_none() {
+            self.offline_cache_ttl = config.get("offline-cache-ttl")?;
+        }
+        if let Some(ttl) = self.offline_cache_ttl {
+            set_offline_cache_ttl(Duration::from_secs(ttl));
+        }
+        set_generated_manifest_policy(if self.orig {
+            GeneratedManifestPolicy::Original
+        } else {
+            GeneratedManifestPolicy::Ask
+        });
+        Ok(())
+    }
+
+    /// How long a previous registry index update is good for.
+    pub fn index_max_age(&self) -> Duration {
+        if self.refresh {
+            Duration::from_secs(0)
+        } else {
+            self.cache_ttl.unwrap_or_else(cache_ttl)
+        }
+    }
+
+    /// The format to report changes in.
+    pub fn output(&self) -> OutputFormat {
+        self.output.unwrap_or_default()
+    }
+
+    /// Whether progress lines are left out, because of `--quiet` or a report being printed.
+    pub fn is_quiet(&self) -> bool {
+        self.quiet || self.output() != OutputFormat::Plain
+    }
+
+    /// Whether pre-release versions of `crate_name` are included.
+    pub fn allows_prerelease(&self, crate_name: &str) -> bool {
+        self.allow_prerelease || self.prerelease_crates.iter().any(|c| c == crate_name)
+    }
+
+    /// The requirement to write for the latest version of a crate, with the prefix of the upgrade
+    /// method and spelled out to the configured precision.
+    fn version_req(&self, version: &str) -> String {
+        let version = match semver::Version::parse(version) {
+            Ok(version) => req_with_precision(&version, self.precision),
+            Err(_) => version.to_owned(),
+        };
+        format!("{}{}", self.get_upgrade_prefix(), version)
+    }
+
     /// Get dependency section
     pub fn get_section(&self) -> Vec<String> {
         if self.dev {
@@ -153,12 +381,9 @@ impl Args {
         } else if self.build {
             vec!["build-dependencies".to_owned()]
         } else if let Some(ref target) = self.target {
-            if target.is_empty() {
-                panic!("Target specification may not be empty");
-            }
             vec![
                 "target".to_owned(),
-                target.clone(),
+                target.clone().normalize().to_string(),
                 "dependencies".to_owned(),
             ]
         } else {
@@ -166,11 +391,108 @@ impl Args {
         }
     }
 
+    /// Get dependency section, using the existing `[target]` table of `manifest` for the target
+    /// if it has one under a different spelling
+    pub fn get_section_in(&self, manifest: &Manifest) -> Vec<String> {
+        let mut section = self.get_section();
+        if let Some(ref target) = self.target {
+            section[1] = manifest.target_key(target);
+        }
+        section
+    }
+
+    /// The registries to look `crate_name` up in, in order, with `None` for crates.io: those
+    /// configured for the crate or for all crates, or else the one given.
+    pub fn registries_for(&self, crate_name: &str) -> Vec<Option<String>> {
+        let order = self
+            .crate_registries
+            .get(crate_name)
+            .unwrap_or(&self.registry_order);
+        if order.is_empty() {
+            if self.registry.is_none() {
+                return Some(None)
+                    .into_iter()
+                    .chain(self.alternative_registries.iter().cloned().map(Some))
+                    .collect();
+            }
+            return vec![self.registry.clone()];
+        }
+        order
+            .iter()
+            .map(|registry| Some(registry.clone()).filter(|r| r != CRATES_IO_REGISTRY))
+            .collect()
+    }
+
+    /// The registries any of the crates may be looked up in, e.g. to update their indices.
+    pub fn registries(&self) -> Vec<Option<String>> {
+        let mut registries = Vec::new();
+        for registry in self
+            .crates
+            .iter()
+            .flat_map(|c| self.registries_for(split_features(c).0))
+        {
+            if !registries.contains(&registry) {
+                registries.push(registry);
+            }
+        }
+        registries
+    }
+
+    /// Whether `crate_name` is looked up in every configured registry, as none is preferred, to
+    /// make sure only one of them has it.
+    fn probes_registries(&self, crate_name: &str) -> bool {
+        self.registry.is_none()
+            && self.registry_order.is_empty()
+            && !self.crate_registries.contains_key(crate_name)
+            && !self.alternative_registries.is_empty()
+    }
+
+    /// Whether `crate_name` is looked up in several registries, in which case the one it comes
+    /// from is worth telling.
+    pub fn falls_back(&self, crate_name: &str) -> bool {
+        self.registries_for(crate_name).len() > 1
+    }
+
+    /// The naming rules of the registry the dependencies come from; git and path dependencies
+    /// only have to follow cargo's.
+    fn naming_rules(&self) -> NamingRules {
+        if self.git.is_some() || self.path.is_some() {
+            NamingRules::Cargo
+        } else {
+            NamingRules::for_registry(self.registry.as_deref())
+        }
+    }
+
     fn parse_single_dependency(&self, crate_name: &str) -> Result<Dependency> {
         let crate_name = CrateName::new(crate_name);
 
-        if let Some(mut dependency) = crate_name.parse_as_version()? {
+        if crate_name.is_package_id_spec() {
+            // crate specifier copied from cargo (e.g. `registry+https://...#docopt@0.8`)
+            let manifest_path = find(&self.manifest_path)?;
+            let mut dependency = crate_name
+                .parse_as_package_id_spec()?
+                .to_dependency(&manifest_path)?;
+            if dependency.version().is_none()
+                && dependency.git_repo().is_none()
+                && dependency.path().is_none()
+            {
+                let registry_url = match dependency.registry() {
+                    Some(registry) => Some(registry_url(&manifest_path, Some(registry))?),
+                    None => None,
+                };
+                let dep = get_latest_dependency(
+                    &dependency.name,
+                    self.allows_prerelease(&dependency.name),
+                    &manifest_path,
+                    &registry_url,
+                )?;
+                let v = self.version_req(dep.version().unwrap_or_else(|| unreachable!()));
+                dependency = dependency.set_version(&v);
+            }
+            Ok(dependency)
+        } else if let Some(mut dependency) = crate_name.parse_as_version()? {
             // crate specifier includes a version (e.g. `docopt@0.8`)
+            validate_crate_name(&dependency.name, self.naming_rules())?;
             if let Some(ref url) = self.git {
                 let url = url.clone();
                 let version = dependency.version().unwrap().to_string();
@@ -185,11 +507,13 @@ impl Args {
         } else if crate_name.is_url_or_path() {
             Ok(crate_name.parse_crate_name_from_uri()?)
         } else {
-            assert_eq!(self.git.is_some() && self.vers.is_some(), false);
-            assert_eq!(self.git.is_some() && self.path.is_some(), false);
-            assert_eq!(self.git.is_some() && self.registry.is_some(), false);
-            assert_eq!(self.path.is_some() && self.registry.is_some(), false);
+            assert!(!(self.git.is_some() && self.vers.is_some()));
+            assert!(!(self.git.is_some() && self.path.is_some()));
+            assert!(!(self.git.is_some() && self.registry.is_some()));
+            assert!(!(self.path.is_some() && self.registry.is_some()));
 
+            // Fail early on names the registry can't have, rather than with a failed lookup.
+            validate_crate_name(crate_name.name(), self.naming_rules())?;
             let mut dependency = Dependency::new(crate_name.name());
 
             if let Some(repo) = &self.git {
@@ -201,40 +525,73 @@ impl Args {
             if let Some(version) = &self.vers {
                 dependency = dependency.set_version(parse_version_req(version)?);
             }
-            let registry_url = if let Some(registry) = &self.registry {
-                Some(registry_url(&find(&self.manifest_path)?, Some(registry))?)
-            } else {
-                None
-            };
-
+            let mut registry = self.registry.clone();
             if self.git.is_none() && self.path.is_none() && self.vers.is_none() {
-                let dep = get_latest_dependency(
-                    crate_name.name(),
-                    self.allow_prerelease,
-                    &find(&self.manifest_path)?,
-                    &registry_url,
-                )?;
-                let v = format!(
-                    "{prefix}{version}",
-                    prefix = self.get_upgrade_prefix(),
-                    // If version is unavailable `get_latest_dependency` must have
-                    // returned `Err(FetchVersionError::GetVersion)`
-                    version = dep.version().unwrap_or_else(|| unreachable!())
-                );
+                let manifest_path = find(&self.manifest_path)?;
+                let registries = self.registries_for(crate_name.name());
+                let registry_urls = registries
+                    .iter()
+                    .map(|registry| match registry {
+                        Some(registry) => registry_url(&manifest_path, Some(registry)).map(Some),
+                        None => Ok(None),
+                    })
+                    .collect::<cargo_edit::Result<Vec<_>>>()?;
+                let allow_prerelease = self.allows_prerelease(crate_name.name());
+                let (position, dep) = if self.probes_registries(crate_name.name()) {
+                    let mut candidates = get_latest_dependency_candidates(
+                        crate_name.name(),
+                        allow_prerelease,
+                        &manifest_path,
+                        &registry_urls,
+                    )?;
+                    if candidates.len() > 1 {
+                        let found = candidates
+                            .iter()
+                            .map(|(position, dep)| {
+                                format!(
+                                    "`{}` ({})",
+                                    registries[*position]
+                                        .as_deref()
+                                        .unwrap_or(CRATES_IO_REGISTRY),
+                                    dep.version().unwrap_or_default()
+                                )
+                            })
+                            .collect();
+                        return Err(ErrorKind::AmbiguousRegistry(
+                            crate_name.name().to_owned(),
+                            found,
+                        )
+                        .into());
+                    }
+                    candidates.remove(0)
+                } else {
+                    get_latest_dependency_with_fallback(
+                        crate_name.name(),
+                        allow_prerelease,
+                        &manifest_path,
+                        &registry_urls,
+                    )?
+                };
+                registry = registries[position].clone();
+                // If version is unavailable `get_latest_dependency` must have
+                // returned `Err(FetchVersionError::GetVersion)`
+                let v = self.version_req(dep.version().unwrap_or_else(|| unreachable!()));
                 dependency = dep.set_version(&v);
             }
 
             // Set the registry after getting the latest version as
             // get_latest_dependency returns a registry-less Dependency
-            if let Some(registry) = &self.registry {
+            if let Some(registry) = &registry {
                 dependency = dependency.set_registry(registry);
             }
             Ok(dependency)
         }
     }
 
-    /// Build dependencies from arguments
-    pub fn parse_dependencies(&self) -> Result<Vec<Dependency>> {
+    /// Build dependencies from arguments, to be added to `manifest`: a crate named by the key of
+    /// a renamed entry there, like `alias` for `alias = { package = "dep" }`, is the package the
+    /// entry is renamed from, and keeps its key.
+    pub fn parse_dependencies(&self, manifest: &Manifest) -> Result<Vec<Dependency>> {
         if self.crates.len() > 1
             && (self.git.is_some() || self.path.is_some() || self.vers.is_some())
         {
@@ -249,23 +606,96 @@ impl Args {
             return Err(ErrorKind::MultipleCratesWithFeatures.into());
         }
 
-        self.crates
+        let section = self.get_section_in(manifest);
+        let workspace_root = self.workspace_root();
+        let crates: Vec<(&str, Vec<String>)> =
+            self.crates.iter().map(|c| split_features(c)).collect();
+        let renamed: BTreeMap<&str, String> = crates
             .iter()
-            .map(|crate_name| {
-                self.parse_single_dependency(crate_name).map(|x| {
+            .map(|(name, _)| *name)
+            .filter(|name| {
+                let name = CrateName::new(name);
+                !name.is_package_id_spec() && !name.is_url_or_path()
+            })
+            .filter_map(|name| {
+                let key = name.split('@').next()?;
+                let dep = manifest.get_dependency(&section, key).ok()?;
+                if dep.rename() == Some(key) {
+                    Some((key, dep.name))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        crates
+            .into_iter()
+            .map(|(crate_name, crate_features)| {
+                let key = crate_name.split('@').next().unwrap_or_default();
+                let (crate_name, rename) = match renamed.get(key) {
+                    Some(package) if self.rename.is_none() => (
+                        format!("{}{}", package, &crate_name[key.len()..]),
+                        Some(key),
+                    ),
+                    _ => (crate_name.to_owned(), self.rename.as_deref()),
+                };
+                let features = match (&self.features, crate_features.is_empty()) {
+                    (None, true) => None,
+                    (features, _) => Some(
+                        features
+                            .iter()
+                            .flatten()
+                            .cloned()
+                            .chain(crate_features)
+                            .collect(),
+                    ),
+                };
+                let inherited = rename.is_none()
+                    && workspace_root
+                        .as_ref()
+                        .is_some_and(|root| inherits(root, &crate_name));
+                let dependency = if inherited {
+                    Ok(Dependency::new(&crate_name).set_workspace())
+                } else {
+                    self.parse_single_dependency(&crate_name)
+                };
+                dependency.and_then(|x| {
                     let mut x = x
                         .set_optional(self.optional)
-                        .set_features(self.features.clone())
+                        .set_features(features)
                         .set_default_features(!self.no_default_features);
-                    if let Some(ref rename) = self.rename {
+                    if let Some(rename) = rename {
                         x = x.set_rename(rename);
                     }
-                    x
+                    if self.pin_rev {
+                        x = pin_rev(x)?;
+                    }
+                    Ok(x)
                 })
             })
             .collect()
     }
 
+    /// The workspace root to inherit crates from, unless `--no-workspace-inherit` is given or the
+    /// source, version, registry or name of the crates is.
+    ///
+    /// A root which can't be read has nothing to inherit.
+    fn workspace_root(&self) -> Option<Manifest> {
+        if self.no_workspace_inherit
+            || self.git.is_some()
+            || self.path.is_some()
+            || self.vers.is_some()
+            || self.registry.is_some()
+            || self.rename.is_some()
+            || self.pin_rev
+        {
+            return None;
+        }
+        let root = find(&self.manifest_path)
+            .and_then(|manifest_path| find_workspace_root(&manifest_path))
+            .ok()?;
+        Manifest::open(&Some(root)).ok()
+    }
+
     fn get_upgrade_prefix(&self) -> &'static str {
         match self.upgrade.as_ref() {
             "default" => "",
@@ -278,6 +708,53 @@ impl Args {
     }
 }
 
+/// Whether `crate_name`, as given on the command line, is a crate listed under its own name in
+/// `[workspace.dependencies]` of the workspace `root`.
+fn inherits(root: &Manifest, crate_name: &str) -> bool {
+    let name = CrateName::new(crate_name);
+    if name.is_package_id_spec() || name.is_url_or_path() || crate_name.contains('@') {
+        return false;
+    }
+    let table = ["workspace".to_owned(), "dependencies".to_owned()];
+    root.get_dependency(&table, crate_name)
+        .is_ok_and(|dependency| dependency.rename().is_none())
+}
+
+/// Pin the git dependency `dependency` to the commit its branch, tag or default branch points
+/// to, see `--pin-rev`.
+fn pin_rev(dependency: Dependency) -> Result<Dependency> {
+    let repo = match dependency.git_repo() {
+        Some(repo) => repo.to_owned(),
+        None => return Err(ErrorKind::PinRevWithoutGit(dependency.name).into()),
+    };
+    let rev = get_git_revision(&repo, dependency.git_reference().as_ref())
+        .chain_err(|| format!("Failed to get the commit to pin `{}` to", dependency.name))?;
+    Ok(dependency.set_git_rev(&repo, &rev))
+}
+
+/// Split the features off a crate given as `name[@version]+feature,...`, e.g. `tokio+full,macros`.
+///
+/// Package ID specs, URLs and paths are taken as they are. As `+` starts the features, a version
+/// with build metadata has to be given with `--vers` instead.
+fn split_features(spec: &str) -> (&str, Vec<String>) {
+    let name = CrateName::new(spec);
+    if name.is_package_id_spec() || name.is_url_or_path() {
+        return (spec, vec![]);
+    }
+    match spec.split_once('+') {
+        Some((spec, features)) => (
+            spec,
+            features
+                .split(',')
+                .map(str::trim)
+                .filter(|f| !f.is_empty())
+                .map(ToOwned::to_owned)
+                .collect(),
+        ),
+        None => (spec, vec![]),
+    }
+}
+
 #[cfg(test)]
 impl Default for Args {
     fn default() -> Args {
@@ -289,6 +766,7 @@ impl Default for Args {
             vers: None,
             git: None,
             branch: None,
+            pin_rev: false,
             path: None,
             target: None,
             optional: false,
@@ -298,10 +776,32 @@ impl Default for Args {
             allow_prerelease: false,
             features: None,
             no_default_features: false,
+            interactive: false,
             quiet: false,
+            verbose: 0,
+            output: None,
+            exit_code: false,
+            dry_run: false,
             offline: true,
+            locked: false,
+            frozen: false,
+            refresh: false,
+            no_workspace_inherit: false,
+            offline_cache_ttl: None,
+            no_proxy: false,
             sort: false,
             registry: None,
+            cache_ttl: None,
+            registry_order: vec![],
+            crate_registries: BTreeMap::new(),
+            alternative_registries: vec![],
+            precision: ReqPrecision::default(),
+            prerelease_crates: vec![],
+            completions: None,
+            man: false,
+            fix_duplicates: false,
+            orig: false,
+            json_request: false,
         }
     }
 }
@@ -311,6 +811,10 @@ mod tests {
     use super::*;
     use cargo_edit::Dependency;
 
+    fn manifest() -> Manifest {
+        "[package]\nname = \"demo\"".parse().unwrap()
+    }
+
     #[test]
     fn test_dependency_parsing() {
         let args = Args {
@@ -319,11 +823,36 @@ mod tests {
         };
 
         assert_eq!(
-            args.parse_dependencies().unwrap(),
+            args.parse_dependencies(&manifest()).unwrap(),
             vec![Dependency::new("demo").set_version("0.4.2")]
         );
     }
 
+    #[test]
+    fn test_features_per_crate() {
+        let args = Args {
+            crates: vec![
+                "serde@1+derive".to_owned(),
+                "tokio@1+full,macros".to_owned(),
+                "anyhow@1".to_owned(),
+            ],
+            ..Args::default()
+        };
+
+        assert_eq!(
+            args.parse_dependencies(&manifest()).unwrap(),
+            vec![
+                Dependency::new("serde")
+                    .set_version("1")
+                    .set_features(Some(vec!["derive".to_owned()])),
+                Dependency::new("tokio")
+                    .set_version("1")
+                    .set_features(Some(vec!["full".to_owned(), "macros".to_owned()])),
+                Dependency::new("anyhow").set_version("1"),
+            ]
+        );
+    }
+
     #[test]
     #[cfg(feature = "test-external-apis")]
     fn test_repo_as_arg_parsing() {
@@ -333,7 +862,7 @@ mod tests {
             ..Args::default()
         };
         assert_eq!(
-            args_github.parse_dependencies().unwrap(),
+            args_github.parse_dependencies(&manifest()).unwrap(),
             vec![Dependency::new("cargo-edit").set_git(github_url, None)]
         );
 
@@ -343,7 +872,7 @@ mod tests {
             ..Args::default()
         };
         assert_eq!(
-            args_gitlab.parse_dependencies().unwrap(),
+            args_gitlab.parse_dependencies(&manifest()).unwrap(),
             vec![Dependency::new("polly").set_git(gitlab_url, None)]
         );
     }
@@ -356,7 +885,7 @@ mod tests {
             ..Args::default()
         };
         assert_eq!(
-            args_path.parse_dependencies().unwrap(),
+            args_path.parse_dependencies(&manifest()).unwrap(),
             vec![Dependency::new("cargo-edit").set_path(self_path)]
         );
     }
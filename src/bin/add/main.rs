@@ -16,13 +16,19 @@ extern crate error_chain;
 
 use crate::args::{Args, Command};
 use cargo_edit::{
-    find, manifest_from_pkgid, registry_url, update_registry_index, Dependency, Manifest,
+    can_ask, cancel_on_ctrl_c, default_feature_names, disable_proxy, ensure_package, feature_names,
+    find, get_crate_versions, init_tracing, json_error_response, manifest_from_pkgid,
+    read_json_request, registry_url, select, stdout_color_choice, update_registry_index_if_stale,
+    verbosity, warn_metadata_violations, write_completions, write_man_page, write_manifest_diff,
+    Dependency, DynamicCompletions, ExitCode, Manifest, ManifestLock, ManifestTransaction,
+    OutputFormat, Report, StyleConfig,
 };
 use std::borrow::Cow;
-use std::io::Write;
+use std::io::{self, Write};
+use std::path::Path;
 use std::process;
-use structopt::StructOpt;
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+use structopt::{clap::Shell, StructOpt};
+use termcolor::{BufferWriter, Color, ColorSpec, StandardStream, WriteColor};
 use toml_edit::Item as TomlItem;
 
 mod args;
@@ -45,10 +51,23 @@ mod errors {
                 description("Specified multiple crates with rename")
                 display("Cannot specify multiple crates with rename")
             }
+            /// No registry was preferred, and the crate is on several of the configured ones.
+            AmbiguousRegistry(name: String, found: Vec<String>) {
+                description("The crate is on several registries")
+                display("The crate `{}` is on several registries: {}. Pick one with `--registry`, \
+                         or prefer some with `registries` in the configuration.",
+                        name, found.join(", "))
+            }
+            /// Asked to pin the commit of a dependency which isn't from git.
+            PinRevWithoutGit(name: String) {
+                description("Asked to pin the commit of a non-git dependency")
+                display("Cannot pin `{}` to a commit, as it is not a git dependency. Give its \
+                         repository with `--git`.", name)
+            }
             /// Specified multiple crates with features.
             MultipleCratesWithFeatures {
                 description("Specified multiple crates with features")
-                display("Cannot specify multiple crates with features")
+                display("Cannot specify multiple crates with features; give them per crate instead, like `serde+derive`")
             }
         }
         links {
@@ -62,22 +81,25 @@ mod errors {
 
 use crate::errors::*;
 
-fn print_msg(dep: &Dependency, section: &[String], optional: bool) -> Result<()> {
-    let colorchoice = if atty::is(atty::Stream::Stdout) {
-        ColorChoice::Auto
-    } else {
-        ColorChoice::Never
-    };
+fn print_msg(dep: &Dependency, section: &[String], optional: bool, fell_back: bool) -> Result<()> {
+    let colorchoice = stdout_color_choice();
     let mut output = StandardStream::stdout(colorchoice);
     output.set_color(ColorSpec::new().set_fg(Some(Color::Green)).set_bold(true))?;
     write!(output, "{:>12}", "Adding")?;
     output.reset()?;
-    write!(output, " {}", dep.name)?;
-    if let Some(version) = dep.version() {
+    write!(output, " {}", dep.display_name())?;
+    if dep.is_workspace() {
+        write!(output, " from the workspace")?;
+    } else if let Some(version) = dep.version() {
         write!(output, " v{}", version)?;
     } else {
         write!(output, " (unknown version)")?;
     }
+    match dep.registry() {
+        Some(registry) => write!(output, " from registry `{}`", registry)?,
+        None if fell_back => write!(output, " from crates.io")?,
+        None => {}
+    }
     write!(output, " to")?;
     if optional {
         write!(output, " optional")?;
@@ -113,40 +135,61 @@ fn is_sorted(mut it: impl Iterator<Item = impl PartialOrd>) -> bool {
     true
 }
 
-fn handle_add(args: &Args) -> Result<()> {
+fn handle_add(args: &Args) -> Result<ExitCode> {
+    init_tracing()?;
+    cancel_on_ctrl_c()?;
+
     let manifest_path = if let Some(ref pkgid) = args.pkgid {
         let pkg = manifest_from_pkgid(pkgid)?;
         Cow::Owned(Some(pkg.manifest_path))
     } else {
         Cow::Borrowed(&args.manifest_path)
     };
+    // Held until the manifest is written, so concurrent edits can't overwrite each other.
+    let _lock = if args.dry_run {
+        None
+    } else {
+        Some(ManifestLock::acquire(&find(&manifest_path)?)?)
+    };
+    ensure_package(&find(&manifest_path)?)?;
     let mut manifest = Manifest::open(&manifest_path)?;
-    let deps = &args.parse_dependencies()?;
+    let style = StyleConfig::for_manifest(&find(&manifest_path)?)?;
+    let mut deps = args.parse_dependencies(&manifest)?;
 
-    if !args.offline && std::env::var("CARGO_IS_TEST").is_err() {
-        let url = registry_url(
-            &find(&manifest_path)?,
-            args.registry.as_ref().map(String::as_ref),
-        )?;
-        update_registry_index(&url, args.quiet)?;
+    // Crates inherited from the workspace aren't looked up.
+    let looks_up = !deps.iter().all(Dependency::is_workspace);
+    if looks_up && !args.offline && std::env::var("CARGO_IS_TEST").is_err() {
+        let manifest_path = find(&manifest_path)?;
+        for registry in args.registries() {
+            let url = registry_url(&manifest_path, registry.as_deref())?;
+            update_registry_index_if_stale(&url, args.is_quiet(), args.index_max_age())?;
+        }
+    }
+    if args.interactive {
+        deps = choose_features(deps, &find(&manifest_path)?)?;
     }
 
+    if args.dry_run && args.output() == OutputFormat::Plain && !args.is_quiet() {
+        dry_run_message()?;
+    }
+
+    let section = args.get_section_in(&manifest);
     let was_sorted = manifest
-        .get_table(&args.get_section())
+        .get_table(&section)
         .map(TomlItem::as_table_mut)
         .map_or(true, |table_option| {
-            table_option.map_or(true, |table| is_sorted(table.iter().map(|(name, _)| name)))
+            table_option.is_none_or(|table| is_sorted(table.iter().map(|(name, _)| name)))
         });
     deps.iter()
         .map(|dep| {
-            if !args.quiet {
-                print_msg(dep, &args.get_section(), args.optional)?;
+            if !args.is_quiet() {
+                print_msg(dep, &section, args.optional, args.falls_back(&dep.name))?;
             }
             manifest
-                .insert_into_table(&args.get_section(), dep)
+                .insert_into_table_with_style(&section, dep, &style)
                 .map(|_| {
                     manifest
-                        .get_table(&args.get_section())
+                        .get_table(&section)
                         .map(TomlItem::as_table_mut)
                         .map(|table_option| {
                             table_option.map(|table| {
@@ -164,27 +207,192 @@ fn handle_add(args: &Args) -> Result<()> {
             err
         })?;
 
-    let mut file = Manifest::find_file(&manifest_path)?;
-    manifest.write_to_file(&mut file)?;
+    let manifest_path = find(&manifest_path)?;
+    let original = std::fs::read_to_string(&manifest_path)?;
+    let new = manifest.to_string_with_style(&style)?;
+    if args.dry_run {
+        if args.output() == OutputFormat::Plain {
+            print_diff(&manifest_path, &original, &new)?;
+        }
+    } else {
+        // Written through a transaction, so a failure or Ctrl-C leaves the manifest as it was.
+        let mut transaction = ManifestTransaction::new();
+        transaction.stage_with_style(&manifest_path, &manifest, &style)?;
+        transaction.commit()?;
+        warn_metadata_violations(&manifest, &manifest_path);
+    }
+
+    if args.output() != OutputFormat::Plain {
+        let mut report = Report::new("add");
+        report.set_dry_run(args.dry_run);
+        report.add_diff(&manifest_path, &original, &new)?;
+        print!("{}", report.render(args.output()));
+    }
+
+    if args.exit_code {
+        Ok(ExitCode::for_changes(original != new, args.dry_run))
+    } else {
+        Ok(ExitCode::Success)
+    }
+}
+
+/// Let the user pick the features of the crates from registries, see `--interactive`.
+fn choose_features(deps: Vec<Dependency>, manifest_path: &Path) -> Result<Vec<Dependency>> {
+    if !can_ask() {
+        if !verbosity().is_quiet() {
+            eprintln!("WARN: `--interactive` needs a terminal; adding the features as given");
+        }
+        return Ok(deps);
+    }
+    deps.into_iter()
+        .map(|dep| {
+            let req = match dep.version().map(semver::VersionReq::parse) {
+                Some(Ok(req)) if dep.path().is_none() && dep.git_repo().is_none() => req,
+                _ => return Ok(dep),
+            };
+            let registry = dep
+                .registry()
+                .map(|registry| registry_url(manifest_path, Some(registry)))
+                .transpose()?;
+            let versions = get_crate_versions(&dep.name, manifest_path, &registry)?;
+            let release = match versions
+                .iter()
+                .filter(|release| !release.yanked && req.matches(&release.version))
+                .max_by(|a, b| a.version.cmp(&b.version))
+            {
+                Some(release) => release,
+                None => return Ok(dep),
+            };
+            let options = feature_names(release);
+            if options.is_empty() {
+                return Ok(dep);
+            }
+
+            let defaults = default_feature_names(release);
+            let requested = dep.features.clone().unwrap_or_default();
+            let checked: Vec<bool> = options
+                .iter()
+                .map(|option| {
+                    (dep.default_features() && defaults.contains(option))
+                        || requested.contains(option)
+                })
+                .collect();
+            let title = format!("Features of `{}` {}:", dep.name, release.version);
+            let checked = select(&title, &options, &checked)?;
+            let chosen: Vec<&String> = options
+                .iter()
+                .zip(checked)
+                .filter_map(|(option, checked)| Some(option).filter(|_| checked))
+                .collect();
 
+            // Unchecking a default feature takes turning the default features off.
+            let default_features =
+                dep.default_features() && defaults.iter().all(|d| chosen.contains(&d));
+            let features: Vec<String> = chosen
+                .into_iter()
+                .filter(|option| !default_features || !defaults.contains(option))
+                .cloned()
+                // Features of its dependencies, like `serde/std`, aren't listed but kept.
+                .chain(requested.into_iter().filter(|f| !options.contains(f)))
+                .collect();
+            Ok(dep
+                .set_default_features(default_features)
+                .set_features(Some(features).filter(|features| !features.is_empty())))
+        })
+        .collect()
+}
+
+fn dry_run_message() -> Result<()> {
+    let bufwtr = BufferWriter::stdout(stdout_color_choice());
+    let mut buffer = bufwtr.buffer();
+    buffer
+        .set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true))
+        .chain_err(|| "Failed to set output colour")?;
+    write!(&mut buffer, "Starting dry run. ").chain_err(|| "Failed to write dry run message")?;
+    buffer
+        .set_color(&ColorSpec::new())
+        .chain_err(|| "Failed to clear output colour")?;
+    writeln!(&mut buffer, "Changes will not be saved.")
+        .chain_err(|| "Failed to write dry run message")?;
+    bufwtr
+        .print(&buffer)
+        .chain_err(|| "Failed to print dry run message")
+}
+
+/// Print the changes to the manifest at `path` as a diff.
+fn print_diff(path: &Path, old: &str, new: &str) -> Result<()> {
+    let colorchoice = stdout_color_choice();
+    let mut output = StandardStream::stdout(colorchoice);
+    write_manifest_diff(&mut output, path, old, new)?;
+    Ok(())
+}
+
+/// Print the completion script of `cargo add` for `shell`.
+fn print_completions(shell: Shell) -> Result<()> {
+    let dynamic = DynamicCompletions {
+        members: true,
+        dependencies: false,
+    };
+    write_completions(Command::clap(), "add", shell, dynamic, &mut io::stdout())?;
     Ok(())
 }
 
+/// Print the man page of `cargo add`.
+fn print_man_page() -> Result<()> {
+    write_man_page(Command::clap(), "add", &mut io::stdout())?;
+    Ok(())
+}
+
+/// Add the dependencies the JSON request read from stdin describes, see `--json-request`.
+fn handle_json_request() -> Result<ExitCode> {
+    let Command::Add(mut args) = read_json_request("add", &["crates"])?;
+    if args.no_proxy {
+        disable_proxy();
+    }
+    args.apply_config()?;
+    handle_add(&args)
+}
+
+/// The exit code for `err`, telling network failures, unparsable manifests and the like apart.
+fn exit_code(err: &Error) -> ExitCode {
+    match err.kind() {
+        ErrorKind::CargoEditLib(kind) => ExitCode::for_error_kind(kind),
+        _ => None,
+    }
+    .unwrap_or_else(|| ExitCode::for_error(err))
+}
+
 fn main() {
     let args: Command = Command::from_args();
-    let Command::Add(args) = args;
-
-    if let Err(err) = handle_add(&args) {
-        eprintln!("Command failed due to unhandled error: {}\n", err);
+    let Command::Add(mut args) = args;
+    if args.no_proxy {
+        disable_proxy();
+    }
 
-        for e in err.iter().skip(1) {
-            eprintln!("Caused by: {}", e);
+    let result = match args.completions {
+        Some(shell) => print_completions(shell).map(|()| ExitCode::Success),
+        None if args.man => print_man_page().map(|()| ExitCode::Success),
+        None if args.json_request => handle_json_request(),
+        None => args.apply_config().and_then(|()| handle_add(&args)),
+    };
+    match result {
+        Ok(code) => process::exit(code.code()),
+        Err(err) if args.json_request => {
+            print!("{}", json_error_response("add", &err, exit_code(&err)));
+            process::exit(exit_code(&err).code());
         }
+        Err(err) => {
+            eprintln!("Command failed due to unhandled error: {}\n", err);
 
-        if let Some(backtrace) = err.backtrace() {
-            eprintln!("Backtrace: {:?}", backtrace);
-        }
+            for e in err.iter().skip(1) {
+                eprintln!("Caused by: {}", e);
+            }
 
-        process::exit(1);
+            if let Some(backtrace) = err.backtrace() {
+                eprintln!("Backtrace: {:?}", backtrace);
+            }
+
+            process::exit(exit_code(&err).code());
+        }
     }
 }
@@ -0,0 +1,294 @@
+//! `cargo edit`
+#![warn(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+#[macro_use]
+extern crate error_chain;
+
+use cargo_edit::{
+    cache_ttl, find, init_tracing, is_offline, registry_url, set_cargo_flags, set_verbosity,
+    update_registry_index_if_stale, verbosity, write_completions, CompletionNames,
+    DynamicCompletions, ExitCode, IndexSnapshot, Manifest, Service, Verbosity,
+};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+use structopt::{
+    clap::{AppSettings, Shell},
+    StructOpt,
+};
+
+mod errors {
+    error_chain! {
+        links {
+            CargoEditLib(::cargo_edit::Error, ::cargo_edit::ErrorKind);
+        }
+        foreign_links {
+            Io(::std::io::Error);
+        }
+    }
+}
+use crate::errors::*;
+
+#[derive(Debug, StructOpt)]
+#[structopt(bin_name = "cargo")]
+enum Command {
+    /// Edit manifests on behalf of other tools.
+    #[structopt(name = "edit")]
+    Edit(Args),
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(setting = AppSettings::ColoredHelp)]
+struct Args {
+    /// Do not print any output in case of success.
+    #[structopt(long = "quiet", short = "q")]
+    quiet: bool,
+
+    /// Print the crates queried from the index and cache hits, and with `-vv` HTTP requests too.
+    #[structopt(
+        long = "verbose",
+        short = "v",
+        parse(from_occurrences),
+        conflicts_with = "quiet"
+    )]
+    verbose: u8,
+
+    /// Run without accessing the network
+    #[structopt(long = "offline")]
+    offline: bool,
+
+    /// Fail rather than change the lock file
+    #[structopt(long = "locked")]
+    locked: bool,
+
+    /// Both `--locked` and `--offline`
+    #[structopt(long = "frozen")]
+    frozen: bool,
+
+    #[structopt(subcommand)]
+    command: EditCommand,
+}
+
+#[derive(Debug, StructOpt)]
+enum EditCommand {
+    /// Answer JSON-RPC requests read from stdin, one per line, on stdout.
+    #[structopt(name = "serve")]
+    #[structopt(after_help = "\
+Each request is a JSON-RPC 2.0 object on a line of its own, like
+
+    {\"jsonrpc\": \"2.0\", \"id\": 1, \"method\": \"query-latest\", \"params\": {\"name\": \"serde\"}}
+
+and is answered with a response on a line of its own. The methods are `query-latest`, `add`, \
+`remove`, `upgrade-preview` and `shutdown`; see the README for their parameters. Registry indices \
+stay open between requests, so only the first query of a registry pays for opening it.")]
+    Serve {
+        /// Never update git registry indices.
+        #[structopt(long = "offline")]
+        offline: bool,
+    },
+
+    /// Carry registry index entries over to hosts without network access.
+    #[structopt(name = "snapshot")]
+    Snapshot(SnapshotCommand),
+
+    /// Print the completion script of `cargo edit` for the given shell.
+    #[structopt(name = "completions")]
+    #[structopt(after_help = "\
+Every other command prints its own script with `--completions <shell>`, like \
+`cargo upgrade --completions zsh`.")]
+    Completions {
+        /// The shell to complete in
+        #[structopt(possible_values = &Shell::variants())]
+        shell: Shell,
+    },
+
+    /// List names for completion scripts to complete arguments with.
+    #[structopt(name = "complete", setting = AppSettings::Hidden)]
+    Complete {
+        /// The names to list
+        #[structopt(possible_values = CompletionNames::NAMES)]
+        names: CompletionNames,
+
+        /// Path to the manifest to list the names of
+        #[structopt(long = "manifest-path", value_name = "path")]
+        manifest_path: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum SnapshotCommand {
+    /// Write the index entries of crates and their dependencies to a tar archive.
+    #[structopt(name = "export")]
+    #[structopt(after_help = "\
+Without crates, the dependencies of the manifest are exported, each from its registry. The \
+archive lists its content in `snapshot.json`, so it can be reviewed before it is carried over.")]
+    Export {
+        /// Crates to export, along with their dependencies
+        crates: Vec<String>,
+
+        /// The archive to write
+        #[structopt(long = "output", short = "o", parse(from_os_str))]
+        output: PathBuf,
+
+        /// Path to the manifest whose registries, or dependencies, to export
+        #[structopt(long = "manifest-path", value_name = "path")]
+        manifest_path: Option<PathBuf>,
+
+        /// Registry to export the crates from
+        #[structopt(long = "registry")]
+        registry: Option<String>,
+
+        /// Export the index as it is, without updating it first
+        #[structopt(long = "offline")]
+        offline: bool,
+    },
+
+    /// Install an archive written by `snapshot export`, for registries to fall back on while
+    /// they can't be reached.
+    #[structopt(name = "import")]
+    Import {
+        /// The archive to install
+        #[structopt(parse(from_os_str))]
+        archive: PathBuf,
+    },
+}
+
+fn handle_edit(args: &Args) -> Result<()> {
+    set_verbosity(Verbosity::new(args.quiet, args.verbose));
+    set_cargo_flags(args.offline, args.locked, args.frozen);
+    init_tracing()?;
+
+    match args.command {
+        EditCommand::Serve { offline } => {
+            let stdin = io::stdin();
+            let stdout = io::stdout();
+            Service::new()
+                .set_offline(offline || is_offline())
+                .run(stdin.lock(), stdout.lock())?;
+        }
+        EditCommand::Snapshot(ref command) => handle_snapshot(command)?,
+        EditCommand::Completions { shell } => write_completions(
+            Command::clap(),
+            "edit",
+            shell,
+            DynamicCompletions::default(),
+            &mut io::stdout(),
+        )?,
+        EditCommand::Complete {
+            names,
+            ref manifest_path,
+        } => {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            for name in names.list(manifest_path)? {
+                writeln!(stdout, "{}", name)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_snapshot(command: &SnapshotCommand) -> Result<()> {
+    match command {
+        SnapshotCommand::Export {
+            crates,
+            output,
+            manifest_path,
+            registry,
+            offline,
+        } => {
+            let manifest_path = find(manifest_path)?;
+            let mut snapshot = IndexSnapshot::new();
+            if crates.is_empty() {
+                if !offline {
+                    let manifest = Manifest::open(&Some(manifest_path.clone()))?;
+                    let registries: BTreeSet<Option<String>> = manifest
+                        .dependency_entries()
+                        .filter(|e| e.path().is_none() && !e.is_git() && !e.is_inherited())
+                        .map(|e| e.registry().map(ToOwned::to_owned))
+                        .collect();
+                    for registry in registries {
+                        let url = registry_url(&manifest_path, registry.as_deref())?;
+                        update_registry_index_if_stale(&url, false, cache_ttl())?;
+                    }
+                }
+                snapshot.add_manifest_closure(&manifest_path)?;
+            } else {
+                let url = registry_url(&manifest_path, registry.as_deref())?;
+                if !offline {
+                    update_registry_index_if_stale(&url, false, cache_ttl())?;
+                }
+                let crates: Vec<&str> = crates.iter().map(String::as_str).collect();
+                snapshot.add_registry_closure(&manifest_path, &Some(url), &crates)?;
+            }
+            let mut file = BufWriter::new(File::create(output)?);
+            snapshot.write_to(&mut file)?;
+            file.flush()?;
+            print_summary("Exported", &snapshot)
+        }
+        SnapshotCommand::Import { archive } => {
+            let snapshot = IndexSnapshot::read_from(BufReader::new(File::open(archive)?))?;
+            snapshot.install()?;
+            print_summary("Imported", &snapshot)
+        }
+    }
+}
+
+fn print_summary(action: &str, snapshot: &IndexSnapshot) -> Result<()> {
+    if verbosity().is_quiet() {
+        return Ok(());
+    }
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for registry in snapshot.registries() {
+        let crates = snapshot.crates(&registry);
+        writeln!(
+            stdout,
+            "{:>12} {} crates of {}",
+            action,
+            crates.len(),
+            registry
+        )?;
+    }
+    Ok(())
+}
+
+/// The exit code for `err`, telling network failures, unparsable manifests and the like apart.
+fn exit_code(err: &Error) -> ExitCode {
+    match err.kind() {
+        ErrorKind::CargoEditLib(kind) => ExitCode::for_error_kind(kind),
+        _ => None,
+    }
+    .unwrap_or_else(|| ExitCode::for_error(err))
+}
+
+fn main() {
+    let args: Command = Command::from_args();
+    let Command::Edit(args) = args;
+
+    if let Err(err) = handle_edit(&args) {
+        eprintln!("Command failed due to unhandled error: {}\n", err);
+
+        for e in err.iter().skip(1) {
+            eprintln!("Caused by: {}", e);
+        }
+
+        if let Some(backtrace) = err.backtrace() {
+            eprintln!("Backtrace: {:?}", backtrace);
+        }
+
+        process::exit(exit_code(&err).code());
+    }
+}
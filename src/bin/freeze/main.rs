@@ -0,0 +1,405 @@
+//! `cargo freeze`
+#![warn(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+#[macro_use]
+extern crate error_chain;
+
+use crate::errors::*;
+use cargo_edit::{
+    cancel_on_ctrl_c, find, init_tracing, manifest_from_pkgid, set_cargo_flags,
+    set_color_preference, set_fix_duplicates, set_generated_manifest_policy, set_verbosity,
+    stdout_color_choice, warn_metadata_violations, write_completions, write_man_page,
+    write_manifest_diff, BrokenManifest, Config, DynamicCompletions, ExitCode,
+    GeneratedManifestPolicy, Lockfile, Manifest, ManifestLock, ManifestTransaction, StyleConfig,
+    Verbosity, Workspace,
+};
+use semver::VersionReq;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use structopt::{
+    clap::{AppSettings, Shell},
+    StructOpt,
+};
+use termcolor::{BufferWriter, Color, ColorSpec, StandardStream, WriteColor};
+
+mod errors {
+    error_chain! {
+        errors {
+            /// There is no lock file to read locked versions from.
+            MissingLockfile(path: ::std::path::PathBuf) {
+                description("Missing Cargo.lock")
+                display("No lock file found at `{}`. Run `cargo generate-lockfile` first.",
+                        path.display())
+            }
+        }
+        links {
+            CargoEditLib(::cargo_edit::Error, ::cargo_edit::ErrorKind);
+        }
+        foreign_links {
+            CargoMetadata(::failure::Compat<::cargo_metadata::Error>);
+            Io(::std::io::Error);
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(bin_name = "cargo")]
+enum Command {
+    /// Pin dependencies to the exact versions recorded in the lock file.
+    #[structopt(name = "freeze")]
+    #[structopt(after_help = "\
+Every direct registry dependency requirement of the selected packages is replaced with an exact \
+`=` requirement on the version currently recorded in Cargo.lock, e.g. `rand = \"0.3\"` becomes \
+`rand = \"=0.3.23\"`. Git and path dependencies are left untouched.
+
+If `<dependency>`(s) are provided, only the specified dependencies will be pinned.
+
+Use `--reason` to record why the dependencies are pinned in `[package.metadata.cargo-edit.pins]`. \
+`cargo thaw` relaxes the pins again.
+
+The lock file must be up-to-date; run `cargo update` or `cargo generate-lockfile` first if \
+needed.")]
+    Freeze(Args),
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(setting = AppSettings::ColoredHelp)]
+struct Args {
+    /// Crates to be pinned.
+    dependency: Vec<String>,
+
+    /// Path to the manifest to pin dependencies in.
+    #[structopt(long = "manifest-path", value_name = "path", conflicts_with = "pkgid")]
+    manifest_path: Option<PathBuf>,
+
+    /// Package id of the crate to pin dependencies in.
+    #[structopt(
+        long = "package",
+        short = "p",
+        value_name = "pkgid",
+        conflicts_with = "workspace"
+    )]
+    pkgid: Option<String>,
+
+    /// Pin the dependencies of all packages in the workspace.
+    #[structopt(long = "workspace", conflicts_with = "pkgid")]
+    workspace: bool,
+
+    /// Print changes to be made without making them.
+    #[structopt(long = "dry-run")]
+    dry_run: bool,
+
+    /// Exit with status 2 if no requirement is pinned, or 3 if a dry run finds some to pin.
+    #[structopt(long = "exit-code")]
+    exit_code: bool,
+
+    /// Crates to exclude and not pin.
+    #[structopt(long)]
+    exclude: Vec<String>,
+
+    /// Record why the dependencies are pinned, so that `cargo thaw --keep-reasoned` leaves them
+    /// alone.
+    #[structopt(long, value_name = "reason")]
+    reason: Option<String>,
+
+    /// Do not print any output in case of success.
+    #[structopt(long = "quiet", short = "q")]
+    quiet: bool,
+
+    /// Print the crates queried from the index and cache hits, and with `-vv` HTTP requests too.
+    #[structopt(
+        long = "verbose",
+        short = "v",
+        parse(from_occurrences),
+        conflicts_with = "quiet"
+    )]
+    verbose: u8,
+
+    /// Run without accessing the network
+    #[structopt(long = "offline")]
+    offline: bool,
+
+    /// Fail rather than change the lock file
+    #[structopt(long = "locked")]
+    locked: bool,
+
+    /// Both `--locked` and `--offline`
+    #[structopt(long = "frozen")]
+    frozen: bool,
+
+    /// Merge dependencies listed more than once in a table, e.g. after a bad merge, instead of
+    /// failing to read the manifest.
+    #[structopt(long = "fix-duplicates")]
+    fix_duplicates: bool,
+
+    /// Edit `Cargo.toml.orig` instead of a manifest cargo generated when packaging the crate, as
+    /// found in an extracted `.crate`.
+    #[structopt(long = "orig")]
+    orig: bool,
+
+    /// Print the completion script of the command for the given shell, and exit.
+    #[structopt(long = "completions", value_name = "shell", possible_values = &Shell::variants())]
+    completions: Option<Shell>,
+
+    /// Print the man page of the command, in roff, and exit.
+    #[structopt(long = "man", conflicts_with = "completions")]
+    man: bool,
+}
+
+impl Args {
+    /// Fill in the settings not given on the command line from the configuration.
+    fn apply_config(&mut self) -> Result<()> {
+        if self.fix_duplicates {
+            set_fix_duplicates();
+        }
+        let config = Config::load(&find(&self.manifest_path)?, "freeze")?;
+        self.quiet = config.flag("quiet", self.quiet)?;
+        // Crates excluded in the configuration stay excluded.
+        let exclude: Vec<String> = config.get("exclude")?.unwrap_or_default();
+        self.exclude.extend(exclude);
+        set_color_preference(config.color()?);
+        set_verbosity(Verbosity::new(self.quiet, self.verbose));
+        set_cargo_flags(self.offline, self.locked, self.frozen);
+        set_generated_manifest_policy(if self.orig {
+            GeneratedManifestPolicy::Original
+        } else {
+            GeneratedManifestPolicy::Ask
+        });
+        Ok(())
+    }
+}
+
+/// Read the workspace lock file, which freezing needs to exist.
+fn open_lockfile(path: &Path) -> Result<Lockfile> {
+    if !path.is_file() {
+        return Err(ErrorKind::MissingLockfile(path.to_owned()).into());
+    }
+    Ok(Lockfile::open(path)?)
+}
+
+/// The manifests to edit, the member manifests skipped as they can't be parsed, and the location of
+/// the workspace lock file.
+type Manifests = (Vec<(PathBuf, Manifest)>, Vec<BrokenManifest>, PathBuf);
+
+/// Read the manifests to edit, and resolve the location of the workspace lock file.
+fn get_manifests(args: &Args) -> Result<Manifests> {
+    let workspace = Workspace::load_partial(&find(&args.manifest_path)?)?;
+    let lockfile = workspace.root_dir().join("Cargo.lock");
+
+    if args.workspace {
+        let manifests = workspace
+            .member_documents()
+            .map(|(member, manifest)| (member.manifest_path.clone(), manifest.clone()))
+            .collect();
+        return Ok((manifests, workspace.broken_manifests().to_vec(), lockfile));
+    }
+    let path = match args.pkgid {
+        Some(ref pkgid) => manifest_from_pkgid(pkgid)?.manifest_path,
+        None => find(&args.manifest_path)?,
+    };
+    let manifest = Manifest::open(&Some(path.clone()))?;
+    Ok((vec![(path, manifest)], Vec::new(), lockfile))
+}
+
+fn dry_run_message() -> Result<()> {
+    let bufwtr = BufferWriter::stdout(stdout_color_choice());
+    let mut buffer = bufwtr.buffer();
+    buffer
+        .set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true))
+        .chain_err(|| "Failed to set output colour")?;
+    write!(&mut buffer, "Starting dry run. ").chain_err(|| "Failed to write dry run message")?;
+    buffer
+        .set_color(&ColorSpec::new())
+        .chain_err(|| "Failed to clear output colour")?;
+    writeln!(&mut buffer, "Changes will not be saved.")
+        .chain_err(|| "Failed to write dry run message")?;
+    bufwtr
+        .print(&buffer)
+        .chain_err(|| "Failed to print dry run message")
+}
+
+/// Print the changes to the manifest at `path` as a diff.
+fn print_diff(path: &Path, old: &str, new: &str) -> Result<()> {
+    let colorchoice = stdout_color_choice();
+    let mut output = StandardStream::stdout(colorchoice);
+    write_manifest_diff(&mut output, path, old, new)?;
+    Ok(())
+}
+
+/// A dependency entry whose requirement will be replaced by an exact pin.
+struct Pin {
+    table_path: Vec<String>,
+    key: String,
+    new_req: String,
+}
+
+/// Work out the pins for every registry dependency of a manifest.
+fn get_pins(manifest: &Manifest, lockfile: &Lockfile, args: &Args) -> Result<Vec<Pin>> {
+    let mut pins = Vec::new();
+    for entry in manifest.dependency_entries() {
+        if entry.table.workspace {
+            continue;
+        }
+        let (key, name) = (entry.key.as_str(), entry.name());
+        let is_registry_dep = entry.path().is_none() && !entry.is_git();
+        let old_req = match entry.version_req() {
+            Some(req) if is_registry_dep => req,
+            _ => continue,
+        };
+        let selected =
+            args.dependency.is_empty() || args.dependency.iter().any(|d| d == name || d == key);
+        let excluded = args.exclude.iter().any(|d| d == name || d == key);
+        if !selected || excluded {
+            continue;
+        }
+
+        let req = VersionReq::parse(old_req).chain_err(|| {
+            ::cargo_edit::ErrorKind::ParseVersion(old_req.to_owned(), name.to_owned())
+        })?;
+        let version = match lockfile.locked_version(name, &req) {
+            Some(version) => version,
+            None => {
+                eprintln!(
+                    "WARN: `{}` ({}) is not in the lock file, skipping",
+                    name, old_req
+                );
+                continue;
+            }
+        };
+        let new_req = format!("={}", version);
+        if new_req != old_req {
+            pins.push(Pin {
+                table_path: entry.table.path(),
+                key: key.to_owned(),
+                new_req,
+            });
+        }
+    }
+    Ok(pins)
+}
+
+fn process(args: &Args) -> Result<ExitCode> {
+    init_tracing()?;
+    cancel_on_ctrl_c()?;
+
+    // Held until the manifests are written, so concurrent edits can't overwrite each other.
+    let _lock = if args.dry_run {
+        None
+    } else {
+        Some(ManifestLock::acquire(&find(&args.manifest_path)?)?)
+    };
+    let (manifests, broken, lockfile_path) = get_manifests(args)?;
+    let lockfile = open_lockfile(&lockfile_path)?;
+
+    if args.dry_run {
+        dry_run_message()?;
+    }
+
+    let mut count = 0;
+    let mut transaction = ManifestTransaction::new();
+    for (manifest_path, mut manifest) in manifests {
+        let pins = get_pins(&manifest, &lockfile, args)?;
+
+        let original = std::fs::read_to_string(&manifest_path)?;
+        for pin in &pins {
+            manifest.set_table_entry_version(&pin.table_path, &pin.key, &pin.new_req)?;
+            if let Some(ref reason) = args.reason {
+                manifest.set_pin_reason(&pin.key, reason)?;
+            }
+        }
+        count += pins.len();
+        if pins.is_empty() {
+            continue;
+        }
+
+        let style = StyleConfig::for_manifest(&manifest_path)?;
+        if !args.quiet {
+            print_diff(
+                &manifest_path,
+                &original,
+                &manifest.to_string_with_style(&style)?,
+            )?;
+        }
+        if !args.dry_run {
+            transaction.stage_with_style(&manifest_path, &manifest, &style)?;
+            warn_metadata_violations(&manifest, &manifest_path);
+        }
+    }
+    // Write all manifests at once, so a failure can't leave the workspace half-edited.
+    transaction.commit()?;
+
+    if !args.quiet {
+        println!("Pinned {} dependency requirement(s)", count);
+    }
+    if !broken.is_empty() {
+        return Err(cargo_edit::Error::from(cargo_edit::ErrorKind::BrokenManifests(broken)).into());
+    }
+    Ok(if args.exit_code {
+        ExitCode::for_changes(count > 0, args.dry_run)
+    } else {
+        ExitCode::Success
+    })
+}
+
+/// Print the completion script of `cargo freeze` for `shell`.
+fn print_completions(shell: Shell) -> Result<()> {
+    let dynamic = DynamicCompletions {
+        members: true,
+        dependencies: true,
+    };
+    write_completions(Command::clap(), "freeze", shell, dynamic, &mut io::stdout())?;
+    Ok(())
+}
+
+/// Print the man page of `cargo freeze`.
+fn print_man_page() -> Result<()> {
+    write_man_page(Command::clap(), "freeze", &mut io::stdout())?;
+    Ok(())
+}
+
+/// The exit code for `err`, telling network failures, unparsable manifests and the like apart.
+fn exit_code(err: &Error) -> ExitCode {
+    match err.kind() {
+        ErrorKind::CargoEditLib(kind) => ExitCode::for_error_kind(kind),
+        _ => None,
+    }
+    .unwrap_or_else(|| ExitCode::for_error(err))
+}
+
+fn main() {
+    let args: Command = Command::from_args();
+    let Command::Freeze(mut args) = args;
+
+    let result = match args.completions {
+        Some(shell) => print_completions(shell).map(|()| ExitCode::Success),
+        None if args.man => print_man_page().map(|()| ExitCode::Success),
+        None => args.apply_config().and_then(|()| process(&args)),
+    };
+    match result {
+        Ok(code) => process::exit(code.code()),
+        Err(err) => {
+            eprintln!("Command failed due to unhandled error: {}\n", err);
+
+            for e in err.iter().skip(1) {
+                eprintln!("Caused by: {}", e);
+            }
+
+            if let Some(backtrace) = err.backtrace() {
+                eprintln!("Backtrace: {:?}", backtrace);
+            }
+
+            process::exit(exit_code(&err).code());
+        }
+    }
+}
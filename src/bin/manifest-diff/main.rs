@@ -0,0 +1,192 @@
+//! `cargo manifest-diff`
+#![warn(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+#[macro_use]
+extern crate error_chain;
+
+use cargo_edit::{
+    init_tracing, manifest_diff, set_cargo_flags, set_verbosity, write_completions, write_man_page,
+    DynamicCompletions, ExitCode, Verbosity,
+};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+use structopt::{
+    clap::{AppSettings, Shell},
+    StructOpt,
+};
+
+mod errors {
+    error_chain! {
+        errors {
+            /// The manifests differ and `--exit-code` was given.
+            ManifestsDiffer(count: usize) {
+                description("Manifests differ")
+                display("Found {} change(s)", count)
+            }
+        }
+        links {
+            CargoEditLib(::cargo_edit::Error, ::cargo_edit::ErrorKind);
+        }
+        foreign_links {
+            Io(::std::io::Error);
+        }
+    }
+}
+use crate::errors::*;
+
+#[derive(Debug, StructOpt)]
+#[structopt(bin_name = "cargo")]
+enum Command {
+    /// Show the changes between two Cargo.toml manifest files.
+    #[structopt(name = "manifest-diff")]
+    #[structopt(after_help = "\
+Manifests are compared by meaning rather than by text: formatting, comments and the order of keys \
+are ignored. Dependency changes are listed first, followed by changes to any other value, like \
+`package.edition` or a feature.")]
+    ManifestDiff(Args),
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(setting = AppSettings::ColoredHelp)]
+struct Args {
+    /// The original manifest.
+    #[structopt(
+        value_name = "old",
+        required_unless_one = &["completions", "man"],
+        parse(from_os_str)
+    )]
+    old: Option<PathBuf>,
+
+    /// The changed manifest.
+    #[structopt(
+        value_name = "new",
+        required_unless_one = &["completions", "man"],
+        parse(from_os_str)
+    )]
+    new: Option<PathBuf>,
+
+    /// Exit with status 3 if the manifests differ.
+    #[structopt(long = "exit-code")]
+    exit_code: bool,
+
+    /// Do not print any output in case of success.
+    #[structopt(long = "quiet", short = "q")]
+    quiet: bool,
+
+    /// Print the crates queried from the index and cache hits, and with `-vv` HTTP requests too.
+    #[structopt(
+        long = "verbose",
+        short = "v",
+        parse(from_occurrences),
+        conflicts_with = "quiet"
+    )]
+    verbose: u8,
+
+    /// Run without accessing the network
+    #[structopt(long = "offline")]
+    offline: bool,
+
+    /// Fail rather than change the lock file
+    #[structopt(long = "locked")]
+    locked: bool,
+
+    /// Both `--locked` and `--offline`
+    #[structopt(long = "frozen")]
+    frozen: bool,
+
+    /// Print the completion script of the command for the given shell, and exit.
+    #[structopt(long = "completions", value_name = "shell", possible_values = &Shell::variants())]
+    completions: Option<Shell>,
+
+    /// Print the man page of the command, in roff, and exit.
+    #[structopt(long = "man", conflicts_with = "completions")]
+    man: bool,
+}
+
+fn read(path: &Option<PathBuf>) -> Result<String> {
+    // Both manifests are required unless completions or the man page are asked for instead.
+    let path = path.as_ref().chain_err(|| "Missing manifest to compare")?;
+    fs::read_to_string(path).chain_err(|| format!("Failed to read {}", path.display()))
+}
+
+fn handle_diff(args: &Args) -> Result<()> {
+    set_verbosity(Verbosity::new(args.quiet, args.verbose));
+    set_cargo_flags(args.offline, args.locked, args.frozen);
+    init_tracing()?;
+
+    let changes = manifest_diff(&read(&args.old)?, &read(&args.new)?)?;
+    if !args.quiet {
+        print!("{}", changes);
+    }
+
+    let count = changes.iter().count();
+    if args.exit_code && count > 0 {
+        return Err(ErrorKind::ManifestsDiffer(count).into());
+    }
+    Ok(())
+}
+
+/// Print the completion script of `cargo manifest-diff` for `shell`.
+fn print_completions(shell: Shell) -> Result<()> {
+    let dynamic = DynamicCompletions::default();
+    write_completions(
+        Command::clap(),
+        "manifest-diff",
+        shell,
+        dynamic,
+        &mut io::stdout(),
+    )?;
+    Ok(())
+}
+
+/// Print the man page of `cargo manifest-diff`.
+fn print_man_page() -> Result<()> {
+    write_man_page(Command::clap(), "manifest-diff", &mut io::stdout())?;
+    Ok(())
+}
+
+/// The exit code for `err`, telling network failures, unparsable manifests and the like apart.
+fn exit_code(err: &Error) -> ExitCode {
+    match err.kind() {
+        ErrorKind::CargoEditLib(kind) => ExitCode::for_error_kind(kind),
+        ErrorKind::ManifestsDiffer(..) => Some(ExitCode::ChangesPending),
+        _ => None,
+    }
+    .unwrap_or_else(|| ExitCode::for_error(err))
+}
+
+fn main() {
+    let args: Command = Command::from_args();
+    let Command::ManifestDiff(args) = args;
+
+    let result = match args.completions {
+        Some(shell) => print_completions(shell),
+        None if args.man => print_man_page(),
+        None => handle_diff(&args),
+    };
+    if let Err(err) = result {
+        eprintln!("Command failed due to unhandled error: {}\n", err);
+
+        for e in err.iter().skip(1) {
+            eprintln!("Caused by: {}", e);
+        }
+
+        if let Some(backtrace) = err.backtrace() {
+            eprintln!("Backtrace: {:?}", backtrace);
+        }
+
+        process::exit(exit_code(&err).code());
+    }
+}
@@ -14,13 +14,24 @@
 #[macro_use]
 extern crate error_chain;
 
-use cargo_edit::{manifest_from_pkgid, Manifest};
+use cargo_edit::{
+    cancel_on_ctrl_c, confirm, ensure_package, find, init_tracing, json_error_response,
+    manifest_from_pkgid, notable_reverse_dependencies, read_json_request, set_cargo_flags,
+    set_color_preference, set_fix_duplicates, set_generated_manifest_policy, set_verbosity,
+    stdout_color_choice, warn_metadata_violations, write_completions, write_man_page,
+    ColorPreference, Config, DynamicCompletions, ExitCode, GeneratedManifestPolicy, Manifest,
+    ManifestLock, ManifestTransaction, OutputFormat, PackageIdSpec, Platform, Report, StyleConfig,
+    Verbosity, Workspace,
+};
 use std::borrow::Cow;
-use std::io::Write;
+use std::io::{self, Write};
 use std::path::PathBuf;
 use std::process;
-use structopt::{clap::AppSettings, StructOpt};
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+use structopt::{
+    clap::{AppSettings, Shell},
+    StructOpt,
+};
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
 
 mod errors {
     error_chain! {
@@ -30,6 +41,13 @@ mod errors {
         foreign_links {
             Io(::std::io::Error);
         }
+        errors {
+            /// No member of the workspace has the dependency
+            NotInWorkspace(name: String, table: String) {
+                description("dependency not found in the workspace")
+                display("The dependency `{}` could not be found in `{}` of any workspace member.", name, table)
+            }
+        }
     }
 }
 use crate::errors::*;
@@ -45,8 +63,11 @@ enum Command {
 #[derive(Debug, StructOpt)]
 #[structopt(setting = AppSettings::ColoredHelp)]
 struct Args {
-    /// Crates to be removed.
-    #[structopt(name = "crates", required = true)]
+    /// Crates to be removed, by name or package ID spec.
+    #[structopt(
+        name = "crates",
+        required_unless_one = &["completions", "man", "json-request"]
+    )]
     crates: Vec<String>,
 
     /// Remove crate as development dependency.
@@ -57,6 +78,15 @@ struct Args {
     #[structopt(long = "build", short = "B", conflicts_with = "dev")]
     build: bool,
 
+    /// Remove crate as dependency for the given target platform.
+    #[structopt(
+        long = "target",
+        conflicts_with = "dev",
+        conflicts_with = "build",
+        parse(try_from_str = Platform::parse_argument)
+    )]
+    target: Option<Platform>,
+
     /// Path to the manifest to remove a dependency from.
     #[structopt(long = "manifest-path", value_name = "path", conflicts_with = "pkgid")]
     manifest_path: Option<PathBuf>,
@@ -66,16 +96,122 @@ struct Args {
         long = "package",
         short = "p",
         value_name = "pkgid",
-        conflicts_with = "path"
+        conflicts_with = "manifest-path",
+        conflicts_with = "workspace"
     )]
     pkgid: Option<String>,
 
+    /// Remove the crates from every member of the workspace having them, and from
+    /// `[workspace.dependencies]` once no member inherits them.
+    #[structopt(long = "workspace", conflicts_with = "pkgid")]
+    workspace: bool,
+
     /// Do not print any output in case of success.
     #[structopt(long = "quiet", short = "q")]
     quiet: bool,
+
+    /// How to print the changes made: as `plain` progress lines, or as a `json` or `markdown`
+    /// report.
+    #[structopt(long = "output", value_name = "format", possible_values = OutputFormat::NAMES)]
+    output: Option<OutputFormat>,
+
+    /// Warn if the package is published and other crates on crates.io depend on it, and ask
+    /// before removing dependencies then.
+    #[structopt(long = "check-dependents")]
+    check_dependents: bool,
+
+    /// Don't ask before removing dependencies other crates may rely on.
+    #[structopt(long = "yes", short = "y")]
+    yes: bool,
+
+    /// Print the crates queried from the index and cache hits, and with `-vv` HTTP requests too.
+    #[structopt(
+        long = "verbose",
+        short = "v",
+        parse(from_occurrences),
+        conflicts_with = "quiet"
+    )]
+    verbose: u8,
+
+    /// Run without accessing the network
+    #[structopt(long = "offline")]
+    offline: bool,
+
+    /// Fail rather than change the lock file
+    #[structopt(long = "locked")]
+    locked: bool,
+
+    /// Both `--locked` and `--offline`
+    #[structopt(long = "frozen")]
+    frozen: bool,
+
+    /// Merge dependencies listed more than once in a table, e.g. after a bad merge, instead of
+    /// failing to read the manifest.
+    #[structopt(long = "fix-duplicates")]
+    fix_duplicates: bool,
+
+    /// Edit `Cargo.toml.orig` instead of a manifest cargo generated when packaging the crate, as
+    /// found in an extracted `.crate`.
+    #[structopt(long = "orig")]
+    orig: bool,
+
+    /// Print the completion script of the command for the given shell, and exit.
+    #[structopt(long = "completions", value_name = "shell", possible_values = &Shell::variants())]
+    completions: Option<Shell>,
+
+    /// Print the man page of the command, in roff, and exit.
+    #[structopt(long = "man", conflicts_with = "completions")]
+    man: bool,
+
+    /// Read the other options as one JSON object from stdin, and print the report or error as JSON.
+    #[structopt(
+        long = "json-request",
+        conflicts_with = "completions",
+        conflicts_with = "man"
+    )]
+    json_request: bool,
 }
 
 impl Args {
+    /// Fill in the settings not given on the command line from the configuration.
+    fn apply_config(&mut self) -> Result<()> {
+        if self.fix_duplicates {
+            set_fix_duplicates();
+        }
+        let config = Config::load(&find(&self.manifest_path)?, "rm")?;
+        set_color_preference(config.color()?);
+        self.quiet = config.flag("quiet", self.quiet)?;
+        self.yes = config.flag("yes", self.yes)?;
+        if self.output.is_none() {
+            self.output = config.get("output")?;
+        }
+        if self.json_request {
+            // Nothing is printed but the one JSON document, and nothing is asked.
+            self.output = Some(OutputFormat::Json);
+            self.quiet = true;
+            self.yes = true;
+            set_color_preference(ColorPreference::Never);
+        }
+        set_verbosity(Verbosity::new(self.quiet, self.verbose));
+        set_cargo_flags(self.offline, self.locked, self.frozen);
+        set_generated_manifest_policy(if self.orig {
+            GeneratedManifestPolicy::Original
+        } else {
+            GeneratedManifestPolicy::Ask
+        });
+        Ok(())
+    }
+
+    /// The format to report changes in.
+    fn output(&self) -> OutputFormat {
+        self.output.unwrap_or_default()
+    }
+
+    /// Whether progress lines are left out, because of `--quiet` or a report being printed.
+    fn is_quiet(&self) -> bool {
+        self.quiet || self.output() != OutputFormat::Plain
+    }
+
     /// Get depenency section
     pub fn get_section(&self) -> &'static str {
         if self.dev {
@@ -86,68 +222,334 @@ impl Args {
             "dependencies"
         }
     }
+
+    /// Get the path of the dependency table in `manifest`, which for a target is the existing
+    /// `[target]` table for it, however it is spelled
+    pub fn get_table_path(&self, manifest: &Manifest) -> Vec<String> {
+        match self.target {
+            Some(ref target) => vec![
+                "target".to_owned(),
+                manifest.target_key(target),
+                self.get_section().to_owned(),
+            ],
+            None => vec![self.get_section().to_owned()],
+        }
+    }
 }
 
-fn print_msg(name: &str, section: &str) -> Result<()> {
-    let colorchoice = if atty::is(atty::Stream::Stdout) {
-        ColorChoice::Auto
-    } else {
-        ColorChoice::Never
-    };
+/// Print that `name` is being removed from the table at `table_path`, of `member` when removing
+/// from several members.
+fn print_msg(name: &str, table_path: &[String], member: Option<&str>) -> Result<()> {
+    let colorchoice = stdout_color_choice();
     let mut output = StandardStream::stdout(colorchoice);
     output.set_color(ColorSpec::new().set_fg(Some(Color::Green)).set_bold(true))?;
     write!(output, "{:>12}", "Removing")?;
     output.reset()?;
-    writeln!(output, " {} from {}", name, section)?;
+    match table_path {
+        [first, target, section] if first == "target" => {
+            write!(output, " {} from {} for target `{}`", name, section, target)?
+        }
+        _ => write!(output, " {} from {}", name, table_path.join("."))?,
+    }
+    match member {
+        Some(member) => writeln!(output, " of `{}`", member)?,
+        None => writeln!(output)?,
+    }
     Ok(())
 }
 
+/// How many dependents to name when warning about them.
+const NOTABLE_DEPENDENTS: usize = 5;
+
+/// Warn that crates depending on the package may break, as removed dependencies can be part of
+/// its public API, returning whether there are any.
+fn warn_dependents(manifest: &Manifest, args: &Args) -> Result<bool> {
+    let package = &manifest.data["package"];
+    let name = match package["name"].as_str() {
+        Some(name) => name,
+        None => return Ok(false),
+    };
+    if args.get_section() != "dependencies" || package["publish"].as_bool() == Some(false) {
+        return Ok(false);
+    }
+
+    let dependents = notable_reverse_dependencies(name, NOTABLE_DEPENDENTS)?;
+    if dependents.total == 0 {
+        return Ok(false);
+    }
+    let names: Vec<&str> = dependents.crates.iter().map(|c| c.name.as_str()).collect();
+    eprintln!(
+        "WARN: `{}` is used by {} crate(s) on crates.io, including {}. They break if a removed \
+         dependency is part of its public API.",
+        name,
+        dependents.total,
+        names.join(", ")
+    );
+    Ok(true)
+}
+
 fn handle_rm(args: &Args) -> Result<()> {
+    init_tracing()?;
+    cancel_on_ctrl_c()?;
+
+    if args.workspace {
+        return rm_from_workspace(args);
+    }
     let manifest_path = if let Some(ref pkgid) = args.pkgid {
         let pkg = manifest_from_pkgid(pkgid)?;
         Cow::Owned(Some(pkg.manifest_path))
     } else {
         Cow::Borrowed(&args.manifest_path)
     };
+    // Held until the manifest is written, so concurrent edits can't overwrite each other.
+    let _lock = ManifestLock::acquire(&find(&manifest_path)?)?;
+    ensure_package(&find(&manifest_path)?)?;
     let mut manifest = Manifest::open(&manifest_path)?;
-    let deps = &args.crates;
+    // Specs copied from cargo name the crate to remove, whatever version they give.
+    let deps = args
+        .crates
+        .iter()
+        .map(|dep| PackageIdSpec::parse(dep).map(|spec| spec.name))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
 
+    if args.check_dependents && warn_dependents(&manifest, args)? {
+        let names: Vec<String> = deps.iter().map(|dep| format!("`{}`", dep)).collect();
+        confirm(&format!("Remove {} anyway?", names.join(", ")), args.yes)?;
+    }
+
+    let table_path = args.get_table_path(&manifest);
     deps.iter()
         .map(|dep| {
-            if !args.quiet {
-                print_msg(&dep, args.get_section())?;
+            // Renamed dependencies can be named by their key or their package.
+            let key = manifest
+                .dependency_key(&table_path, dep)
+                .unwrap_or_else(|_| dep.clone());
+            if !args.is_quiet() {
+                let name = manifest
+                    .get_dependency(&table_path, &key)
+                    .map_or_else(|_| key.clone(), |dep| dep.display_name());
+                print_msg(&name, &table_path, None)?;
             }
-            manifest
-                .remove_from_table(args.get_section(), dep)
-                .map_err(Into::into)
+            manifest.remove_from_table(&table_path, &key)?;
+            manifest.remove_feature_references(&key);
+            Ok(())
         })
         .collect::<Result<Vec<_>>>()
         .map_err(|err| {
-            eprintln!("Could not edit `Cargo.toml`.\n\nERROR: {}", err);
+            if !args.json_request {
+                eprintln!("Could not edit `Cargo.toml`.\n\nERROR: {}", err);
+            }
             err
         })?;
 
-    let mut file = Manifest::find_file(&manifest_path)?;
-    manifest.write_to_file(&mut file)?;
+    let manifest_path = find(&manifest_path)?;
+    let original = std::fs::read_to_string(&manifest_path)?;
+    let style = StyleConfig::for_manifest(&manifest_path)?;
+    // Written through a transaction, so a failure or Ctrl-C leaves the manifest as it was.
+    let mut transaction = ManifestTransaction::new();
+    transaction.stage_with_style(&manifest_path, &manifest, &style)?;
+    transaction.commit()?;
+    warn_metadata_violations(&manifest, &manifest_path);
+
+    if args.output() != OutputFormat::Plain {
+        let mut report = Report::new("rm");
+        report.add_diff(
+            &manifest_path,
+            &original,
+            &manifest.to_string_with_style(&style)?,
+        )?;
+        print!("{}", report.render(args.output()));
+    }
 
     Ok(())
 }
 
-fn main() {
-    let args: Command = Command::from_args();
-    let Command::Rm(args) = args;
+/// Remove the crates from every member of the workspace having them in the table given, and from
+/// `[workspace.dependencies]` once no member inherits them any more.
+fn rm_from_workspace(args: &Args) -> Result<()> {
+    let manifest_path = find(&args.manifest_path)?;
+    // Held until the manifests are written, so concurrent edits can't overwrite each other.
+    let _lock = ManifestLock::acquire(&manifest_path)?;
+    let mut workspace = Workspace::load_partial(&manifest_path)?;
+    let deps = args
+        .crates
+        .iter()
+        .map(|dep| PackageIdSpec::parse(dep).map(|spec| spec.name))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    // The entries to remove, each with the member listing it.
+    let entries = workspace.dependency_entries()?;
+    let mut removals = Vec::new();
+    for dep in &deps {
+        let before = removals.len();
+        for (member, manifest) in workspace.member_documents() {
+            let table_path = args.get_table_path(manifest);
+            // Renamed dependencies can be named by their key or their package.
+            let key = match manifest.dependency_key(&table_path, dep) {
+                Ok(key) => key,
+                Err(err) => match err.kind() {
+                    cargo_edit::ErrorKind::NonExistentDependency(..)
+                    | cargo_edit::ErrorKind::NonExistentTable(..) => continue,
+                    _ => return Err(err.into()),
+                },
+            };
+            let entry = entries.iter().find(|entry| {
+                entry.manifest_path.as_ref() == Some(&member.manifest_path)
+                    && !entry.table.workspace
+                    && entry.table.path() == table_path
+                    && entry.key == key
+            });
+            if let Some(entry) = entry {
+                removals.push((entry.clone(), member.name.clone()));
+            }
+        }
+        let declared_by_workspace = entries
+            .iter()
+            .any(|entry| entry.table.workspace && (entry.key == *dep || entry.name() == dep));
+        if removals.len() == before && !declared_by_workspace {
+            let table = match args.target {
+                Some(ref target) => format!("target.{}.{}", target, args.get_section()),
+                None => args.get_section().to_owned(),
+            };
+            return Err(ErrorKind::NotInWorkspace(dep.clone(), table).into());
+        }
+    }
+
+    if args.check_dependents {
+        let mut any = false;
+        for (member, manifest) in workspace.member_documents() {
+            if removals.iter().any(|(_, name)| *name == member.name) {
+                any |= warn_dependents(manifest, args)?;
+            }
+        }
+        if any {
+            let names: Vec<String> = deps.iter().map(|dep| format!("`{}`", dep)).collect();
+            confirm(&format!("Remove {} anyway?", names.join(", ")), args.yes)?;
+        }
+    }
 
-    if let Err(err) = handle_rm(&args) {
-        eprintln!("Command failed due to unhandled error: {}\n", err);
+    for (entry, member) in &removals {
+        if !args.is_quiet() {
+            let name = entry
+                .to_dependency()
+                .map_or_else(|| entry.key.clone(), |dep| dep.display_name());
+            print_msg(&name, &entry.table.path(), Some(member))?;
+        }
+        workspace.remove_dependency(entry)?;
+    }
 
-        for e in err.iter().skip(1) {
-            eprintln!("Caused by: {}", e);
+    // Entries of `[workspace.dependencies]` named, and no longer inherited by any member.
+    let remaining = workspace.dependency_entries()?;
+    let unused: Vec<_> = remaining
+        .iter()
+        .filter(|entry| entry.table.workspace)
+        .filter(|entry| {
+            deps.iter()
+                .any(|dep| entry.key == *dep || entry.name() == dep)
+        })
+        .filter(|entry| {
+            !remaining.iter().any(|inheriting| {
+                !inheriting.table.workspace
+                    && inheriting.is_inherited()
+                    && inheriting.key == entry.key
+            })
+        })
+        .collect();
+    for entry in unused {
+        if !args.is_quiet() {
+            print_msg(&entry.key, &entry.table.path(), None)?;
         }
+        workspace.remove_dependency(entry)?;
+    }
+
+    let mut transaction = ManifestTransaction::new();
+    workspace.stage_changes(&mut transaction)?;
+    let paths: Vec<PathBuf> = transaction.paths().map(ToOwned::to_owned).collect();
+    let originals = paths
+        .iter()
+        .map(std::fs::read_to_string)
+        .collect::<io::Result<Vec<_>>>()?;
+    // Write all manifests at once, so a failure can't leave the workspace half-edited.
+    transaction.commit()?;
 
-        if let Some(backtrace) = err.backtrace() {
-            eprintln!("Backtrace: {:?}", backtrace);
+    let mut report = Report::new("rm");
+    for (path, original) in paths.iter().zip(&originals) {
+        if let Some(manifest) = workspace.manifest(path) {
+            warn_metadata_violations(manifest, path);
         }
+        report.add_diff(path, original, &std::fs::read_to_string(path)?)?;
+    }
+    if args.output() != OutputFormat::Plain {
+        print!("{}", report.render(args.output()));
+    }
 
-        process::exit(1);
+    let broken = workspace.broken_manifests().to_vec();
+    if !broken.is_empty() {
+        return Err(cargo_edit::Error::from(cargo_edit::ErrorKind::BrokenManifests(broken)).into());
     }
+    Ok(())
+}
+
+/// Print the completion script of `cargo rm` for `shell`.
+fn print_completions(shell: Shell) -> Result<()> {
+    let dynamic = DynamicCompletions {
+        members: true,
+        dependencies: true,
+    };
+    write_completions(Command::clap(), "rm", shell, dynamic, &mut io::stdout())?;
+    Ok(())
+}
+
+/// Print the man page of `cargo rm`.
+fn print_man_page() -> Result<()> {
+    write_man_page(Command::clap(), "rm", &mut io::stdout())?;
+    Ok(())
+}
+
+/// The exit code for `err`, telling network failures, unparsable manifests and the like apart.
+fn exit_code(err: &Error) -> ExitCode {
+    match err.kind() {
+        ErrorKind::CargoEditLib(kind) => ExitCode::for_error_kind(kind),
+        _ => None,
+    }
+    .unwrap_or_else(|| ExitCode::for_error(err))
+}
+
+fn main() {
+    let args: Command = Command::from_args();
+    let Command::Rm(mut args) = args;
+
+    let result = match args.completions {
+        Some(shell) => print_completions(shell),
+        None if args.man => print_man_page(),
+        None if args.json_request => handle_json_request(),
+        None => args.apply_config().and_then(|()| handle_rm(&args)),
+    };
+    match result {
+        Ok(()) => {}
+        Err(err) if args.json_request => {
+            print!("{}", json_error_response("rm", &err, exit_code(&err)));
+            process::exit(exit_code(&err).code());
+        }
+        Err(err) => {
+            eprintln!("Command failed due to unhandled error: {}\n", err);
+
+            for e in err.iter().skip(1) {
+                eprintln!("Caused by: {}", e);
+            }
+
+            if let Some(backtrace) = err.backtrace() {
+                eprintln!("Backtrace: {:?}", backtrace);
+            }
+
+            process::exit(exit_code(&err).code());
+        }
+    }
+}
+
+/// Remove the dependencies the JSON request read from stdin names, see `--json-request`.
+fn handle_json_request() -> Result<()> {
+    let Command::Rm(mut args) = read_json_request("rm", &["crates"])?;
+    args.apply_config()?;
+    handle_rm(&args)
 }
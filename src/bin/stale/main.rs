@@ -0,0 +1,596 @@
+//! `cargo stale`
+#![warn(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+#[macro_use]
+extern crate error_chain;
+#[macro_use]
+extern crate serde_derive;
+
+use crate::errors::*;
+use cargo_edit::{
+    apply_tls, cancel_on_ctrl_c, disable_proxy, ensure_online, find, init_tracing, is_offline,
+    manifest_from_pkgid, proxy_for_url, set_cargo_flags, set_color_preference, set_verbosity,
+    stdout_color_choice, write_completions, write_man_page, BrokenManifest, Config, CratesIoClient,
+    DynamicCompletions, ExitCode, Manifest, Progress, Verbosity, Workspace,
+};
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{env, fmt, fs};
+use structopt::{
+    clap::{AppSettings, Shell},
+    StructOpt,
+};
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
+use url::Url;
+
+mod errors {
+    error_chain! {
+        links {
+            CargoEditLib(::cargo_edit::Error, ::cargo_edit::ErrorKind);
+        }
+        foreign_links {
+            CargoMetadata(::failure::Compat<::cargo_metadata::Error>);
+            Git(::git2::Error);
+            Io(::std::io::Error);
+        }
+    }
+}
+
+const GITHUB_API: &str = "https://api.github.com/repos";
+const ADVISORY_DB: &str = "https://github.com/RustSec/advisory-db.git";
+const USER_AGENT: &str = "cargo-edit (https://github.com/killercup/cargo-edit)";
+
+#[derive(Debug, StructOpt)]
+#[structopt(bin_name = "cargo")]
+enum Command {
+    /// List dependencies which are no longer maintained.
+    #[structopt(name = "stale")]
+    #[structopt(after_help = "\
+Every direct crates.io dependency of the selected packages is checked for
+- a newest release older than `--max-age` days,
+- a GitHub repository which has been archived, and
+- a RustSec advisory flagging the crate as unmaintained.
+
+Flagged dependencies are listed with the most severe findings first. The advisory database is \
+cloned to `$CARGO_HOME/advisory-db`, the same location `cargo audit` uses. Set `GITHUB_TOKEN` to \
+avoid GitHub's API rate limit when checking many repositories.")]
+    Stale(Args),
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(setting = AppSettings::ColoredHelp)]
+struct Args {
+    /// Path to the manifest to check dependencies of.
+    #[structopt(long = "manifest-path", value_name = "path", conflicts_with = "pkgid")]
+    manifest_path: Option<PathBuf>,
+
+    /// Package id of the crate to check dependencies of.
+    #[structopt(
+        long = "package",
+        short = "p",
+        value_name = "pkgid",
+        conflicts_with = "workspace"
+    )]
+    pkgid: Option<String>,
+
+    /// Check the dependencies of all packages in the workspace.
+    #[structopt(long = "workspace", conflicts_with = "pkgid")]
+    workspace: bool,
+
+    /// Flag dependencies whose newest release is older than this many days.
+    #[structopt(long = "max-age", value_name = "days", default_value = "365")]
+    max_age: u64,
+
+    /// Crates to exclude and not check.
+    #[structopt(long)]
+    exclude: Vec<String>,
+
+    /// Don't check whether repositories are archived.
+    #[structopt(long = "skip-repository")]
+    skip_repository: bool,
+
+    /// Don't check the RustSec advisory database.
+    #[structopt(long = "skip-advisories")]
+    skip_advisories: bool,
+
+    /// Ignore proxy settings and connect directly.
+    #[structopt(long = "no-proxy")]
+    no_proxy: bool,
+
+    /// Do not print any output in case of success.
+    #[structopt(long = "quiet", short = "q")]
+    quiet: bool,
+
+    /// Print the crates queried from the index and cache hits, and with `-vv` HTTP requests too.
+    #[structopt(
+        long = "verbose",
+        short = "v",
+        parse(from_occurrences),
+        conflicts_with = "quiet"
+    )]
+    verbose: u8,
+
+    /// Run without accessing the network
+    #[structopt(long = "offline")]
+    offline: bool,
+
+    /// Fail rather than change the lock file
+    #[structopt(long = "locked")]
+    locked: bool,
+
+    /// Both `--locked` and `--offline`
+    #[structopt(long = "frozen")]
+    frozen: bool,
+
+    /// Print the completion script of the command for the given shell, and exit.
+    #[structopt(long = "completions", value_name = "shell", possible_values = &Shell::variants())]
+    completions: Option<Shell>,
+
+    /// Print the man page of the command, in roff, and exit.
+    #[structopt(long = "man", conflicts_with = "completions")]
+    man: bool,
+}
+
+impl Args {
+    /// Fill in the settings not given on the command line from the configuration.
+    fn apply_config(&mut self) -> Result<()> {
+        let config = Config::load(&find(&self.manifest_path)?, "stale")?;
+        // Crates excluded in the configuration stay excluded.
+        let exclude: Vec<String> = config.get("exclude")?.unwrap_or_default();
+        self.exclude.extend(exclude);
+        set_color_preference(config.color()?);
+        self.quiet = config.flag("quiet", self.quiet)?;
+        set_verbosity(Verbosity::new(self.quiet, self.verbose));
+        set_cargo_flags(self.offline, self.locked, self.frozen);
+        Ok(())
+    }
+}
+
+/// Why a dependency is considered stale, ordered by increasing risk.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Finding {
+    /// The newest release is this many days old.
+    Outdated(u64),
+    /// The repository has been archived by its owners.
+    Archived(String),
+    /// A RustSec advisory flags the crate as unmaintained.
+    Unmaintained(String),
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::Outdated(days) => write!(f, "no release in {} days", days),
+            Finding::Archived(repository) => write!(f, "repository archived ({})", repository),
+            Finding::Unmaintained(id) => write!(f, "unmaintained ({})", id),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RepositoryResponse {
+    archived: bool,
+}
+
+/// The newest release of a crate.
+#[derive(Debug)]
+struct Release {
+    version: String,
+    /// Release date as `YYYY-MM-DD`.
+    date: String,
+    age: u64,
+    repository: Option<String>,
+}
+
+fn http_get<T: serde::de::DeserializeOwned>(url: &str) -> Result<T> {
+    ensure_online(url)?;
+    let mut req = ureq::get(url);
+    req.timeout(Duration::from_secs(10));
+    req.set("User-Agent", USER_AGENT);
+    if url.starts_with(GITHUB_API) {
+        if let Ok(token) = env::var("GITHUB_TOKEN") {
+            req.set("Authorization", &format!("token {}", token));
+        }
+    }
+    let parsed = Url::parse(url).chain_err(|| format!("Invalid URL `{}`", url))?;
+    if let Some(proxy) = proxy_for_url(&parsed)? {
+        // ureq wants `scheme://host:port`, without a path.
+        let spec = format!(
+            "{}://{}:{}",
+            proxy.scheme(),
+            proxy.host_str().unwrap_or_default(),
+            proxy.port_or_known_default().unwrap_or(1080)
+        );
+        req.set_proxy(
+            ureq::Proxy::new(&spec).map_err(|e| format!("Invalid proxy `{}`: {}", proxy, e))?,
+        );
+    }
+    apply_tls(&mut req)?;
+    let res = req.call();
+    if res.error() {
+        return Err(format!(
+            "HTTP request `{}` failed: {}",
+            url,
+            res.synthetic_error()
+                .as_ref()
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| res.status().to_string())
+        )
+        .into());
+    }
+    res.into_json_deserialize()
+        .chain_err(|| format!("Invalid response from `{}`", url))
+}
+
+/// Days since the Unix epoch of a `YYYY-MM-DD...` timestamp.
+fn days_since_epoch(timestamp: &str) -> Option<i64> {
+    let mut parts = timestamp.get(..10)?.split('-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+fn today() -> i64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    (now.as_secs() / 86_400) as i64
+}
+
+fn newest_release(client: &CratesIoClient, name: &str) -> Result<Release> {
+    if env::var("CARGO_IS_TEST").is_ok() {
+        // We are in a simulated reality. Nothing is real here.
+        let (date, repository) = match name {
+            "test_outdated" | "test_unmaintained" => ("2015-05-15", None),
+            "test_archived" => ("2020-01-01", Some("https://github.com/test/archived")),
+            _ => ("2100-01-01", None),
+        };
+        return Ok(Release {
+            version: "0.1.0".to_owned(),
+            date: date.to_owned(),
+            age: (today() - days_since_epoch(date).unwrap_or_default()).max(0) as u64,
+            repository: repository.map(ToOwned::to_owned),
+        });
+    }
+
+    let info = client
+        .crate_info(name)?
+        .chain_err(|| cargo_edit::ErrorKind::NoCrate(name.to_owned(), Vec::new()))?;
+    let newest = info
+        .newest_release()
+        .chain_err(|| cargo_edit::ErrorKind::NoMatchingVersion(name.to_owned(), true, false))?;
+    let released = days_since_epoch(&newest.created_at)
+        .chain_err(|| format!("Invalid release date `{}`", newest.created_at))?;
+    Ok(Release {
+        version: newest.num.clone(),
+        date: newest.created_at[..10].to_owned(),
+        age: (today() - released).max(0) as u64,
+        repository: info.repository.clone(),
+    })
+}
+
+/// Whether `repository` is an archived GitHub repository. Other hosts are not checked.
+fn is_archived(repository: &str) -> Result<bool> {
+    if env::var("CARGO_IS_TEST").is_ok() {
+        return Ok(repository.ends_with("/archived"));
+    }
+
+    let path = match repository
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .split("github.com/")
+        .nth(1)
+    {
+        Some(path) => path,
+        None => return Ok(false),
+    };
+    let mut segments = path.split('/');
+    let (owner, repo) = match (segments.next(), segments.next()) {
+        (Some(owner), Some(repo)) => (owner, repo),
+        _ => return Ok(false),
+    };
+    let response: RepositoryResponse = http_get(&format!("{}/{}/{}", GITHUB_API, owner, repo))?;
+    Ok(response.archived)
+}
+
+/// The parts of a RustSec advisory relevant to us.
+#[derive(Debug, Deserialize)]
+struct AdvisoryFile {
+    advisory: Advisory,
+}
+
+#[derive(Debug, Deserialize)]
+struct Advisory {
+    id: String,
+    informational: Option<String>,
+    withdrawn: Option<toml::value::Datetime>,
+}
+
+/// Clone or update the RustSec advisory database, returning its location; offline, a clone is
+/// used as it is.
+fn update_advisory_db() -> Result<PathBuf> {
+    let cargo_home = match env::var_os("CARGO_HOME") {
+        Some(home) => PathBuf::from(home),
+        None => dirs_next::home_dir()
+            .map(|home| home.join(".cargo"))
+            .chain_err(|| cargo_edit::ErrorKind::ReadHomeDirFailure)?,
+    };
+    let path = cargo_home.join("advisory-db");
+    if is_offline() && path.exists() {
+        return Ok(path);
+    }
+    ensure_online(ADVISORY_DB)?;
+    let mut proxy_options = git2::ProxyOptions::new();
+    let proxy = proxy_for_url(&Url::parse(ADVISORY_DB).expect("valid URL"))?;
+    if let Some(ref proxy) = proxy {
+        proxy_options.url(proxy.as_str());
+    }
+    let mut fetch_options = git2::FetchOptions::new();
+    fetch_options.proxy_options(proxy_options);
+
+    if !path.exists() {
+        git2::build::RepoBuilder::new()
+            .fetch_options(fetch_options)
+            .clone(ADVISORY_DB, &path)
+            .chain_err(|| "Failed to clone the RustSec advisory database")?;
+        return Ok(path);
+    }
+
+    let repo = git2::Repository::open(&path)?;
+    repo.find_remote("origin")?
+        .fetch(&["main"], Some(&mut fetch_options), None)
+        .chain_err(|| "Failed to update the RustSec advisory database")?;
+    let head = repo.find_reference("FETCH_HEAD")?.peel_to_commit()?;
+    repo.reset(head.as_object(), git2::ResetType::Hard, None)?;
+    Ok(path)
+}
+
+/// IDs of the active advisories flagging `name` as unmaintained.
+fn unmaintained_advisories(db: &Path, name: &str) -> Result<Vec<String>> {
+    if env::var("CARGO_IS_TEST").is_ok() {
+        return Ok(match name {
+            "test_unmaintained" => vec!["RUSTSEC-0000-0000".to_owned()],
+            _ => Vec::new(),
+        });
+    }
+
+    let dir = db.join("crates").join(name);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut ids = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "md") {
+            continue;
+        }
+        // Advisories are markdown files with their metadata in a leading ```toml block.
+        let content = fs::read_to_string(&path)?;
+        let front_matter = content
+            .split("```toml")
+            .nth(1)
+            .and_then(|rest| rest.split("```").next())
+            .unwrap_or_default();
+        let file: AdvisoryFile = toml::from_str(front_matter)
+            .chain_err(|| format!("Invalid advisory `{}`", path.display()))?;
+        let advisory = file.advisory;
+        if advisory.informational.as_deref() == Some("unmaintained") && advisory.withdrawn.is_none()
+        {
+            ids.push(advisory.id);
+        }
+    }
+    ids.sort();
+    Ok(ids)
+}
+
+/// Read the manifests to check, along with the member manifests skipped as they can't be parsed.
+fn get_manifests(args: &Args) -> Result<(Vec<Manifest>, Vec<BrokenManifest>)> {
+    let path = if let Some(ref pkgid) = args.pkgid {
+        manifest_from_pkgid(pkgid)?.manifest_path
+    } else if !args.workspace {
+        find(&args.manifest_path)?
+    } else {
+        let workspace = Workspace::load_partial(&find(&args.manifest_path)?)?;
+        let manifests = workspace
+            .member_documents()
+            .map(|(_, manifest)| manifest.clone())
+            .collect();
+        return Ok((manifests, workspace.broken_manifests().to_vec()));
+    };
+    Ok((vec![Manifest::open(&Some(path))?], Vec::new()))
+}
+
+/// The names of all direct crates.io dependencies of the selected manifests, along with the member
+/// manifests skipped as they can't be parsed.
+fn get_dependencies(args: &Args) -> Result<(BTreeSet<String>, Vec<BrokenManifest>)> {
+    let mut names = BTreeSet::new();
+    let (manifests, broken) = get_manifests(args)?;
+    for manifest in manifests {
+        for entry in manifest.dependency_entries() {
+            if entry.table.workspace {
+                continue;
+            }
+            let is_crates_io_dep =
+                entry.path().is_none() && !entry.is_git() && entry.registry().is_none();
+            let name = entry.name();
+            if is_crates_io_dep && !args.exclude.iter().any(|d| d == name || *d == entry.key) {
+                names.insert(name.to_owned());
+            }
+        }
+    }
+    Ok((names, broken))
+}
+
+fn print_finding(name: &str, release: &Release, findings: &[Finding]) -> Result<()> {
+    let colorchoice = stdout_color_choice();
+    let mut output = StandardStream::stdout(colorchoice);
+    let color = match findings.iter().max() {
+        Some(Finding::Outdated(_)) => Color::Yellow,
+        _ => Color::Red,
+    };
+    output.set_color(ColorSpec::new().set_fg(Some(color)).set_bold(true))?;
+    write!(output, "{:>12}", "Stale")?;
+    output.reset()?;
+    let findings = findings
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    writeln!(
+        output,
+        " {} v{} (released {}): {}",
+        name, release.version, release.date, findings
+    )?;
+    Ok(())
+}
+
+fn process(args: &Args) -> Result<()> {
+    init_tracing()?;
+    cancel_on_ctrl_c()?;
+
+    let (names, broken) = get_dependencies(args)?;
+    let advisory_db = if args.skip_advisories {
+        None
+    } else if env::var("CARGO_IS_TEST").is_ok() {
+        Some(PathBuf::new())
+    } else {
+        Some(update_advisory_db()?)
+    };
+
+    let client = CratesIoClient::new()?;
+    let mut report = Vec::new();
+    let mut warnings = Vec::new();
+    let progress = Progress::new("Checking", names.len());
+    for name in &names {
+        let release = newest_release(&client, name)
+            .chain_err(|| format!("Failed to get the newest release of `{}`", name))?;
+        let mut findings = Vec::new();
+        if release.age > args.max_age {
+            findings.push(Finding::Outdated(release.age));
+        }
+        if let Some(ref repository) = release.repository {
+            if !args.skip_repository {
+                match is_archived(repository) {
+                    Ok(true) => findings.push(Finding::Archived(repository.clone())),
+                    Ok(false) => {}
+                    Err(err) => warnings.push(format!("Could not check `{}`: {}", repository, err)),
+                }
+            }
+        }
+        if let Some(ref db) = advisory_db {
+            for id in unmaintained_advisories(db, name)? {
+                findings.push(Finding::Unmaintained(id));
+            }
+        }
+
+        if !findings.is_empty() {
+            findings.sort_by(|a, b| b.cmp(a));
+            report.push((name, release, findings));
+        }
+        progress.tick(name);
+    }
+    // The warnings are held back until the progress line is gone.
+    drop(progress);
+    if !args.quiet {
+        for warning in &warnings {
+            eprintln!("WARN: {}", warning);
+        }
+    }
+
+    // Riskiest first: by the worst finding, then the number of findings, then the age.
+    report.sort_by(|(_, a_release, a), (_, b_release, b)| {
+        b[0].cmp(&a[0])
+            .then(b.len().cmp(&a.len()))
+            .then(b_release.age.cmp(&a_release.age))
+    });
+    for (name, release, findings) in &report {
+        print_finding(name, release, findings)?;
+    }
+
+    if !args.quiet {
+        println!(
+            "Found {} stale dependencies out of {}",
+            report.len(),
+            names.len()
+        );
+    }
+    if !broken.is_empty() {
+        return Err(cargo_edit::Error::from(cargo_edit::ErrorKind::BrokenManifests(broken)).into());
+    }
+    Ok(())
+}
+
+/// Print the completion script of `cargo stale` for `shell`.
+fn print_completions(shell: Shell) -> Result<()> {
+    let dynamic = DynamicCompletions {
+        members: true,
+        dependencies: true,
+    };
+    write_completions(Command::clap(), "stale", shell, dynamic, &mut io::stdout())?;
+    Ok(())
+}
+
+/// Print the man page of `cargo stale`.
+fn print_man_page() -> Result<()> {
+    write_man_page(Command::clap(), "stale", &mut io::stdout())?;
+    Ok(())
+}
+
+/// The exit code for `err`, telling network failures, unparsable manifests and the like apart.
+fn exit_code(err: &Error) -> ExitCode {
+    match err.kind() {
+        ErrorKind::CargoEditLib(kind) => ExitCode::for_error_kind(kind),
+        ErrorKind::Git(..) => Some(ExitCode::Network),
+        _ => None,
+    }
+    .unwrap_or_else(|| ExitCode::for_error(err))
+}
+
+fn main() {
+    let args: Command = Command::from_args();
+    let Command::Stale(mut args) = args;
+    if args.no_proxy {
+        disable_proxy();
+    }
+
+    let result = match args.completions {
+        Some(shell) => print_completions(shell),
+        None if args.man => print_man_page(),
+        None => args.apply_config().and_then(|()| process(&args)),
+    };
+    if let Err(err) = result {
+        eprintln!("Command failed due to unhandled error: {}\n", err);
+
+        for e in err.iter().skip(1) {
+            eprintln!("Caused by: {}", e);
+        }
+
+        if let Some(backtrace) = err.backtrace() {
+            eprintln!("Backtrace: {:?}", backtrace);
+        }
+
+        process::exit(exit_code(&err).code());
+    }
+}
@@ -0,0 +1,422 @@
+//! `cargo thaw`
+#![warn(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+#[macro_use]
+extern crate error_chain;
+
+use crate::errors::*;
+use cargo_edit::{
+    cancel_on_ctrl_c, find, init_tracing, manifest_from_pkgid, set_cargo_flags,
+    set_color_preference, set_fix_duplicates, set_generated_manifest_policy, set_verbosity,
+    stdout_color_choice, warn_metadata_violations, write_completions, write_man_page,
+    write_manifest_diff, BrokenManifest, Config, DynamicCompletions, ExitCode,
+    GeneratedManifestPolicy, Manifest, ManifestLock, ManifestTransaction, StyleConfig, Verbosity,
+    Workspace,
+};
+use semver::Version;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use structopt::{
+    clap::{AppSettings, Shell},
+    StructOpt,
+};
+use termcolor::{BufferWriter, Color, ColorSpec, StandardStream, WriteColor};
+
+mod errors {
+    error_chain! {
+        links {
+            CargoEditLib(::cargo_edit::Error, ::cargo_edit::ErrorKind);
+        }
+        foreign_links {
+            CargoMetadata(::failure::Compat<::cargo_metadata::Error>);
+            Io(::std::io::Error);
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(bin_name = "cargo")]
+enum Command {
+    /// Relax exact `=` pins back to caret requirements.
+    #[structopt(name = "thaw")]
+    #[structopt(after_help = "\
+Every direct dependency requirement of the selected packages which pins a single version, e.g. \
+`rand = \"=0.3.23\"`, is replaced with a caret requirement on that version. `--precision` \
+controls how much of the version is kept: `patch` gives `0.3.23`, `minor` gives `0.3` and `major` \
+gives `0`. Pins on a pre-release keep their whole version, as a shorter requirement would not \
+match the pre-release anymore. Other requirements are left untouched.
+
+If `<dependency>`(s) are provided, only the specified dependencies will be relaxed.
+
+Pins recorded with `cargo freeze --reason` are kept when `--keep-reasoned` is given; the recorded \
+reason of every relaxed dependency is removed.")]
+    Thaw(Args),
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(setting = AppSettings::ColoredHelp)]
+struct Args {
+    /// Crates to be relaxed.
+    dependency: Vec<String>,
+
+    /// Path to the manifest to relax dependencies in.
+    #[structopt(long = "manifest-path", value_name = "path", conflicts_with = "pkgid")]
+    manifest_path: Option<PathBuf>,
+
+    /// Package id of the crate to relax dependencies in.
+    #[structopt(
+        long = "package",
+        short = "p",
+        value_name = "pkgid",
+        conflicts_with = "workspace"
+    )]
+    pkgid: Option<String>,
+
+    /// Relax the dependencies of all packages in the workspace.
+    #[structopt(long = "workspace", conflicts_with = "pkgid")]
+    workspace: bool,
+
+    /// How much of the pinned version to keep in the relaxed requirement.
+    #[structopt(
+        long,
+        value_name = "precision",
+        possible_values = &["major", "minor", "patch"],
+        default_value = "patch"
+    )]
+    precision: Precision,
+
+    /// Keep pins which have a reason recorded in `[package.metadata.cargo-edit.pins]`.
+    #[structopt(long = "keep-reasoned")]
+    keep_reasoned: bool,
+
+    /// Print changes to be made without making them.
+    #[structopt(long = "dry-run")]
+    dry_run: bool,
+
+    /// Exit with status 2 if no requirement is relaxed, or 3 if a dry run finds some to relax.
+    #[structopt(long = "exit-code")]
+    exit_code: bool,
+
+    /// Crates to exclude and not relax.
+    #[structopt(long)]
+    exclude: Vec<String>,
+
+    /// Do not print any output in case of success.
+    #[structopt(long = "quiet", short = "q")]
+    quiet: bool,
+
+    /// Print the crates queried from the index and cache hits, and with `-vv` HTTP requests too.
+    #[structopt(
+        long = "verbose",
+        short = "v",
+        parse(from_occurrences),
+        conflicts_with = "quiet"
+    )]
+    verbose: u8,
+
+    /// Run without accessing the network
+    #[structopt(long = "offline")]
+    offline: bool,
+
+    /// Fail rather than change the lock file
+    #[structopt(long = "locked")]
+    locked: bool,
+
+    /// Both `--locked` and `--offline`
+    #[structopt(long = "frozen")]
+    frozen: bool,
+
+    /// Merge dependencies listed more than once in a table, e.g. after a bad merge, instead of
+    /// failing to read the manifest.
+    #[structopt(long = "fix-duplicates")]
+    fix_duplicates: bool,
+
+    /// Edit `Cargo.toml.orig` instead of a manifest cargo generated when packaging the crate, as
+    /// found in an extracted `.crate`.
+    #[structopt(long = "orig")]
+    orig: bool,
+
+    /// Print the completion script of the command for the given shell, and exit.
+    #[structopt(long = "completions", value_name = "shell", possible_values = &Shell::variants())]
+    completions: Option<Shell>,
+
+    /// Print the man page of the command, in roff, and exit.
+    #[structopt(long = "man", conflicts_with = "completions")]
+    man: bool,
+}
+
+impl Args {
+    /// Fill in the settings not given on the command line from the configuration.
+    fn apply_config(&mut self) -> Result<()> {
+        if self.fix_duplicates {
+            set_fix_duplicates();
+        }
+        let config = Config::load(&find(&self.manifest_path)?, "thaw")?;
+        self.quiet = config.flag("quiet", self.quiet)?;
+        // Crates excluded in the configuration stay excluded, and pinned ones unless named.
+        let exclude: Vec<String> = config.get("exclude")?.unwrap_or_default();
+        self.exclude.extend(exclude);
+        let pinned: Vec<String> = config.get("pinned")?.unwrap_or_default();
+        let named = &self.dependency;
+        self.exclude
+            .extend(pinned.into_iter().filter(|name| !named.contains(name)));
+        set_color_preference(config.color()?);
+        set_verbosity(Verbosity::new(self.quiet, self.verbose));
+        set_cargo_flags(self.offline, self.locked, self.frozen);
+        set_generated_manifest_policy(if self.orig {
+            GeneratedManifestPolicy::Original
+        } else {
+            GeneratedManifestPolicy::Ask
+        });
+        Ok(())
+    }
+}
+
+/// The number of version components kept when relaxing a pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Precision {
+    Major,
+    Minor,
+    Patch,
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "major" => Ok(Precision::Major),
+            "minor" => Ok(Precision::Minor),
+            "patch" => Ok(Precision::Patch),
+            _ => Err(format!("Unknown precision `{}`", s)),
+        }
+    }
+}
+
+impl Precision {
+    /// Turn an exact `=X.Y.Z` requirement into a caret requirement, or `None` if `req` is not an
+    /// exact pin.
+    ///
+    /// A pre-release is kept whole whatever the precision, as only requirements naming it match it.
+    fn relax(self, req: &str) -> Option<String> {
+        let version = req.trim().strip_prefix('=')?.trim();
+        let mut version = Version::parse(version).ok()?;
+        version.build.clear();
+        Some(match self {
+            _ if version.is_prerelease() => version.to_string(),
+            Precision::Major => version.major.to_string(),
+            Precision::Minor => format!("{}.{}", version.major, version.minor),
+            Precision::Patch => version.to_string(),
+        })
+    }
+}
+
+/// The manifests to edit, and the member manifests skipped as they can't be parsed.
+type Manifests = (Vec<(PathBuf, Manifest)>, Vec<BrokenManifest>);
+
+/// Read the manifests to edit.
+fn get_manifests(args: &Args) -> Result<Manifests> {
+    let path = if let Some(ref pkgid) = args.pkgid {
+        manifest_from_pkgid(pkgid)?.manifest_path
+    } else if !args.workspace {
+        find(&args.manifest_path)?
+    } else {
+        let workspace = Workspace::load_partial(&find(&args.manifest_path)?)?;
+        let manifests = workspace
+            .member_documents()
+            .map(|(member, manifest)| (member.manifest_path.clone(), manifest.clone()))
+            .collect();
+        return Ok((manifests, workspace.broken_manifests().to_vec()));
+    };
+    let manifest = Manifest::open(&Some(path.clone()))?;
+    Ok((vec![(path, manifest)], Vec::new()))
+}
+
+fn dry_run_message() -> Result<()> {
+    let bufwtr = BufferWriter::stdout(stdout_color_choice());
+    let mut buffer = bufwtr.buffer();
+    buffer
+        .set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true))
+        .chain_err(|| "Failed to set output colour")?;
+    write!(&mut buffer, "Starting dry run. ").chain_err(|| "Failed to write dry run message")?;
+    buffer
+        .set_color(&ColorSpec::new())
+        .chain_err(|| "Failed to clear output colour")?;
+    writeln!(&mut buffer, "Changes will not be saved.")
+        .chain_err(|| "Failed to write dry run message")?;
+    bufwtr
+        .print(&buffer)
+        .chain_err(|| "Failed to print dry run message")
+}
+
+/// Print the changes to the manifest at `path` as a diff.
+fn print_diff(path: &Path, old: &str, new: &str) -> Result<()> {
+    let colorchoice = stdout_color_choice();
+    let mut output = StandardStream::stdout(colorchoice);
+    write_manifest_diff(&mut output, path, old, new)?;
+    Ok(())
+}
+
+/// A pinned dependency entry whose requirement will be relaxed.
+struct Thaw {
+    table_path: Vec<String>,
+    key: String,
+    new_req: String,
+}
+
+/// Work out the relaxed requirement for every pinned dependency of a manifest.
+fn get_thaws(manifest: &Manifest, args: &Args) -> Vec<Thaw> {
+    let mut thaws = Vec::new();
+    for entry in manifest.dependency_entries() {
+        if entry.table.workspace {
+            continue;
+        }
+        let (key, name) = (entry.key.as_str(), entry.name());
+        let old_req = match entry.version_req() {
+            Some(req) => req,
+            None => continue,
+        };
+        let selected =
+            args.dependency.is_empty() || args.dependency.iter().any(|d| d == name || d == key);
+        let excluded = args.exclude.iter().any(|d| d == name || d == key);
+        if !selected || excluded {
+            continue;
+        }
+        if args.keep_reasoned && manifest.pin_reason(key).is_some() {
+            continue;
+        }
+
+        if let Some(new_req) = args.precision.relax(old_req) {
+            thaws.push(Thaw {
+                table_path: entry.table.path(),
+                key: key.to_owned(),
+                new_req,
+            });
+        }
+    }
+    thaws
+}
+
+fn process(args: &Args) -> Result<ExitCode> {
+    init_tracing()?;
+    cancel_on_ctrl_c()?;
+
+    // Held until the manifests are written, so concurrent edits can't overwrite each other.
+    let _lock = if args.dry_run {
+        None
+    } else {
+        Some(ManifestLock::acquire(&find(&args.manifest_path)?)?)
+    };
+    let (manifests, broken) = get_manifests(args)?;
+
+    if args.dry_run {
+        dry_run_message()?;
+    }
+
+    let mut count = 0;
+    let mut transaction = ManifestTransaction::new();
+    for (manifest_path, mut manifest) in manifests {
+        let thaws = get_thaws(&manifest, args);
+
+        let original = std::fs::read_to_string(&manifest_path)?;
+        for thaw in &thaws {
+            manifest.set_table_entry_version(&thaw.table_path, &thaw.key, &thaw.new_req)?;
+            manifest.remove_pin_reason(&thaw.key);
+        }
+        count += thaws.len();
+        if thaws.is_empty() {
+            continue;
+        }
+
+        let style = StyleConfig::for_manifest(&manifest_path)?;
+        if !args.quiet {
+            print_diff(
+                &manifest_path,
+                &original,
+                &manifest.to_string_with_style(&style)?,
+            )?;
+        }
+        if !args.dry_run {
+            transaction.stage_with_style(&manifest_path, &manifest, &style)?;
+            warn_metadata_violations(&manifest, &manifest_path);
+        }
+    }
+    // Write all manifests at once, so a failure can't leave the workspace half-edited.
+    transaction.commit()?;
+
+    if !args.quiet {
+        println!("Relaxed {} dependency requirement(s)", count);
+    }
+    if !broken.is_empty() {
+        return Err(cargo_edit::Error::from(cargo_edit::ErrorKind::BrokenManifests(broken)).into());
+    }
+    Ok(if args.exit_code {
+        ExitCode::for_changes(count > 0, args.dry_run)
+    } else {
+        ExitCode::Success
+    })
+}
+
+/// Print the completion script of `cargo thaw` for `shell`.
+fn print_completions(shell: Shell) -> Result<()> {
+    let dynamic = DynamicCompletions {
+        members: true,
+        dependencies: true,
+    };
+    write_completions(Command::clap(), "thaw", shell, dynamic, &mut io::stdout())?;
+    Ok(())
+}
+
+/// Print the man page of `cargo thaw`.
+fn print_man_page() -> Result<()> {
+    write_man_page(Command::clap(), "thaw", &mut io::stdout())?;
+    Ok(())
+}
+
+/// The exit code for `err`, telling network failures, unparsable manifests and the like apart.
+fn exit_code(err: &Error) -> ExitCode {
+    match err.kind() {
+        ErrorKind::CargoEditLib(kind) => ExitCode::for_error_kind(kind),
+        _ => None,
+    }
+    .unwrap_or_else(|| ExitCode::for_error(err))
+}
+
+fn main() {
+    let args: Command = Command::from_args();
+    let Command::Thaw(mut args) = args;
+
+    let result = match args.completions {
+        Some(shell) => print_completions(shell).map(|()| ExitCode::Success),
+        None if args.man => print_man_page().map(|()| ExitCode::Success),
+        None => args.apply_config().and_then(|()| process(&args)),
+    };
+    match result {
+        Ok(code) => process::exit(code.code()),
+        Err(err) => {
+            eprintln!("Command failed due to unhandled error: {}\n", err);
+
+            for e in err.iter().skip(1) {
+                eprintln!("Caused by: {}", e);
+            }
+
+            if let Some(backtrace) = err.backtrace() {
+                eprintln!("Backtrace: {:?}", backtrace);
+            }
+
+            process::exit(exit_code(&err).code());
+        }
+    }
+}
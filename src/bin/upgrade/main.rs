@@ -16,16 +16,32 @@ extern crate error_chain;
 
 use crate::errors::*;
 use cargo_edit::{
-    find, get_latest_dependency, manifest_from_pkgid, registry_url, update_registry_index,
-    CrateName, Dependency, LocalManifest,
+    cache_ttl, cancel_on_ctrl_c, cargo_options, confirm, disable_progress, disable_proxy,
+    explain_dependency, find, find_workspace_root, get_git_revision, get_latest_dependencies,
+    get_latest_dependencies_with_candidates, get_msrv_compatibility,
+    get_rust_version_from_manifest, get_yanked_versions, init_tracing, json_error_response,
+    manifest_diff, manifest_from_pkgid, original_manifest, read_json_request, registry_name,
+    registry_url, same_file, set_cargo_flags, set_color_preference, set_fix_duplicates,
+    set_generated_manifest_policy, set_offline_cache_ttl, set_verbosity, stderr_color_choice,
+    stdout_color_choice, terminal_width, update_registry_index_if_stale, verbosity,
+    warn_metadata_violations, write_completions, write_man_page, write_manifest_diff,
+    ColorPreference, Config, Dependency, DependencyStatus, DynamicCompletions, ExitCode,
+    GeneratedManifestPolicy, Latest, LocalManifest, Manifest, ManifestChange, ManifestLock,
+    ManifestTransaction, MsrvCompatible, OutputFormat, PackageIdSpec, Report, RustVersion,
+    Selection, SkipReason, Table, UpgradeCandidates, Verbosity, VersionPolicy,
 };
 use failure::Fail;
 use std::collections::{HashMap, HashSet};
-use std::io::Write;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use std::process;
-use structopt::{clap::AppSettings, StructOpt};
-use termcolor::{BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
+use std::str::FromStr;
+use std::time::Duration;
+use structopt::{
+    clap::{AppSettings, Shell},
+    StructOpt,
+};
+use termcolor::{BufferWriter, Color, ColorSpec, StandardStream, WriteColor};
 use url::Url;
 
 mod errors {
@@ -36,6 +52,13 @@ mod errors {
         foreign_links {
             CargoMetadata(::failure::Compat<::cargo_metadata::Error>);
         }
+        errors {
+            /// `--rust-version auto` found no `rust-version` to keep supporting.
+            NoRustVersion {
+                description("No `rust-version` declared")
+                display("None of the manifests upgraded declares a `rust-version` for `--rust-version auto` to keep supporting.")
+            }
+        }
     }
 }
 
@@ -55,7 +78,18 @@ Dev, build, and all target dependencies will also be upgraded. Only dependencies
 are supported. Git/path dependencies will be ignored.
 
 All packages in the workspace will be upgraded if the `--workspace` flag is supplied. The \
-`--workspace` flag may be supplied in the presence of a virtual manifest.
+`--workspace` flag may be supplied in the presence of a virtual manifest. The \
+`[workspace.dependencies]` of the root manifest are upgraded too, and members inheriting them with \
+`workspace = true` keep doing so.
+
+With `--rust-version`, dependencies are only upgraded to the newest versions whose `rust-version` \
+is supported by the given Rust version. `--rust-version auto` takes the lowest `rust-version` of \
+the packages upgraded, including one inherited from `[workspace.package]`. Versions which don't \
+declare a `rust-version` are assumed to support it, unless `--msrv-strict` is given.
+
+Dependencies overridden in a `[patch]` table of the workspace root are left alone unless named, \
+as the patch decides what is built. With `--upgrade-patches`, the `rev` of their git patches is \
+moved to the newest commit of the repository's default branch instead.
 
 If the '--to-lockfile' flag is supplied, all dependencies will be upgraded to the currently locked \
 version as recorded in the Cargo.lock file. This flag requires that the Cargo.lock file is \
@@ -67,7 +101,7 @@ an error. If the '--to-lockfile' flag is supplied then the network won't be acce
 #[derive(Debug, StructOpt)]
 #[structopt(setting = AppSettings::ColoredHelp)]
 struct Args {
-    /// Crates to be upgraded.
+    /// Crates to be upgraded, by name or package ID spec (e.g. `docopt@1.0`).
     dependency: Vec<String>,
 
     /// Path to the manifest to upgrade
@@ -79,7 +113,7 @@ struct Args {
         long = "package",
         short = "p",
         value_name = "pkgid",
-        conflicts_with = "path",
+        conflicts_with = "manifest-path",
         conflicts_with = "all",
         conflicts_with = "workspace"
     )]
@@ -106,14 +140,89 @@ struct Args {
     #[structopt(long = "dry-run")]
     dry_run: bool,
 
+    /// Exit with status 2 if nothing is upgraded, or 3 if a dry run finds upgrades.
+    #[structopt(long = "exit-code")]
+    exit_code: bool,
+
+    /// How to print the changes made: as a `plain` diff of each manifest, or as a `json` or
+    /// `markdown` report.
+    #[structopt(long = "output", value_name = "format", possible_values = OutputFormat::NAMES)]
+    output: Option<OutputFormat>,
+
+    /// The columns of the summary printed at the end, in order [default: name,kind,old,new,latest,note]
+    #[structopt(
+        long = "columns",
+        value_name = "columns",
+        use_delimiter = true,
+        possible_values = SummaryColumn::NAMES
+    )]
+    columns: Vec<SummaryColumn>,
+
+    /// Print why each dependency is upgraded to the version it is, and why newer ones are passed
+    /// over, e.g. for being yanked or pre-releases.
+    #[structopt(
+        long = "explain",
+        conflicts_with = "output",
+        conflicts_with = "to-lockfile",
+        conflicts_with = "json-request"
+    )]
+    explain: bool,
+
     /// Only update a dependency if the new version is semver incompatible.
-    #[structopt(long = "skip-compatible", conflicts_with = "to_lockfile")]
+    #[structopt(long = "skip-compatible", conflicts_with = "to-lockfile")]
     skip_compatible: bool,
 
+    /// Only upgrade to versions supporting this Rust version, or with `auto` the lowest
+    /// `rust-version` of the packages upgraded.
+    #[structopt(
+        long = "rust-version",
+        value_name = "version|auto",
+        conflicts_with = "to-lockfile"
+    )]
+    rust_version: Option<String>,
+
+    /// With `--rust-version`, pass over versions which don't declare the Rust version they need.
+    #[structopt(long = "msrv-strict", conflicts_with = "to-lockfile")]
+    msrv_strict: bool,
+
+    /// Move the `rev` of the git `[patch]` entries of patched crates to the newest commit of
+    /// their repository's default branch.
+    #[structopt(long = "upgrade-patches", conflicts_with = "to-lockfile")]
+    upgrade_patches: bool,
+
+    /// Don't ask before downgrades, or before applying many incompatible upgrades at once.
+    #[structopt(long = "yes", short = "y")]
+    yes: bool,
+
     /// Run without accessing the network
     #[structopt(long = "offline")]
     pub offline: bool,
 
+    /// Fail rather than change the lock file
+    #[structopt(long = "locked")]
+    pub locked: bool,
+
+    /// Both `--locked` and `--offline`
+    #[structopt(long = "frozen")]
+    pub frozen: bool,
+
+    /// Update the registry index even if it was updated recently (see `CARGO_EDIT_CACHE_TTL`).
+    #[structopt(
+        long = "refresh",
+        conflicts_with = "offline",
+        conflicts_with = "frozen"
+    )]
+    pub refresh: bool,
+
+    /// Offline, only use cached index files fetched less than this many seconds ago, rather than
+    /// however old they are.
+    #[structopt(long = "offline-cache-ttl", value_name = "seconds")]
+    pub offline_cache_ttl: Option<u64>,
+
+    /// Ignore proxy settings and connect directly.
+    #[structopt(long = "no-proxy")]
+    pub no_proxy: bool,
+
     /// Upgrade all packages to the version in the lockfile.
     #[structopt(long = "to-lockfile", conflicts_with = "dependency")]
     pub to_lockfile: bool,
@@ -121,23 +230,148 @@ struct Args {
     /// Crates to exclude and not upgrade.
     #[structopt(long)]
     exclude: Vec<String>,
+
+    /// How long a registry index update is good for, as configured.
+    #[structopt(skip)]
+    cache_ttl: Option<Duration>,
+
+    /// Crates whose pre-release versions are included, as configured in `prerelease-crates`.
+    #[structopt(skip)]
+    prerelease_crates: Vec<String>,
+
+    /// Crates excluded for being `pinned` in the configuration.
+    #[structopt(skip)]
+    pinned: Vec<String>,
+
+    /// Do not print any output in case of success.
+    #[structopt(long = "quiet", short = "q")]
+    quiet: bool,
+
+    /// Print the crates queried from the index and cache hits, and with `-vv` HTTP requests too.
+    #[structopt(
+        long = "verbose",
+        short = "v",
+        parse(from_occurrences),
+        conflicts_with = "quiet"
+    )]
+    verbose: u8,
+
+    /// Merge dependencies listed more than once in a table, e.g. after a bad merge, instead of
+    /// failing to read the manifest.
+    #[structopt(long = "fix-duplicates")]
+    fix_duplicates: bool,
+
+    /// Edit `Cargo.toml.orig` instead of a manifest cargo generated when packaging the crate, as
+    /// found in an extracted `.crate`.
+    #[structopt(long = "orig")]
+    orig: bool,
+
+    /// Print the completion script of the command for the given shell, and exit.
+    #[structopt(long = "completions", value_name = "shell", possible_values = &Shell::variants())]
+    completions: Option<Shell>,
+
+    /// Print the man page of the command, in roff, and exit.
+    #[structopt(long = "man", conflicts_with = "completions")]
+    man: bool,
+
+    /// Read the other options as one JSON object from stdin, and print the report or error as JSON.
+    #[structopt(
+        long = "json-request",
+        conflicts_with = "completions",
+        conflicts_with = "man"
+    )]
+    json_request: bool,
+}
+
+impl Args {
+    /// Fill in the settings not given on the command line from the configuration.
+    fn apply_config(&mut self) -> Result<()> {
+        if self.fix_duplicates {
+            set_fix_duplicates();
+        }
+        let config = Config::load(&find(&self.manifest_path)?, "upgrade")?;
+        set_color_preference(config.color()?);
+        self.allow_prerelease = config.flag("allow-prerelease", self.allow_prerelease)?;
+        self.prerelease_crates = config.get("prerelease-crates")?.unwrap_or_default();
+        if self.rust_version.is_none() {
+            self.rust_version = config.get("rust-version")?;
+        }
+        self.msrv_strict = config.flag("msrv-strict", self.msrv_strict)?;
+        self.offline = config.flag("offline", self.offline)? || self.frozen;
+        self.yes = config.flag("yes", self.yes)?;
+        if self.output.is_none() {
+            self.output = config.get("output")?;
+        }
+        if self.columns.is_empty() {
+            let columns: Option<Vec<String>> = config.get("columns")?;
+            self.columns = match columns {
+                Some(columns) => columns
+                    .iter()
+                    .map(|column| column.parse())
+                    .collect::<Result<_>>()?,
+                None => SummaryColumn::ALL.to_vec(),
+            };
+        }
+        // Crates excluded in the configuration stay excluded, and pinned ones unless named.
+        let exclude: Vec<String> = config.get("exclude")?.unwrap_or_default();
+        self.exclude.extend(exclude);
+        let named: Vec<String> = self
+            .dependency
+            .iter()
+            .filter_map(|name| PackageIdSpec::parse(name).ok())
+            .map(|spec| spec.name)
+            .collect();
+        let pinned: Vec<String> = config.get("pinned")?.unwrap_or_default();
+        self.pinned = pinned
+            .into_iter()
+            .filter(|name| !named.contains(name))
+            .collect();
+        self.exclude.extend(self.pinned.iter().cloned());
+        self.cache_ttl = Some(config.cache_ttl()?);
+        self.quiet = config.flag("quiet", self.quiet)?;
+        if self.json_request {
+            // Nothing is printed but the one JSON document, and nothing is asked.
+            self.output = Some(OutputFormat::Json);
+            self.quiet = true;
+            self.yes = true;
+            set_color_preference(ColorPreference::Never);
+        }
+        set_verbosity(Verbosity::new(self.quiet, self.verbose));
+        set_cargo_flags(self.offline, self.locked, self.frozen);
+        if self.offline_cache_ttl.is_none() {
+            self.offline_cache_ttl = config.get("offline-cache-ttl")?;
+        }
+        if let Some(ttl) = self.offline_cache_ttl {
+            set_offline_cache_ttl(Duration::from_secs(ttl));
+        }
+        set_generated_manifest_policy(if self.orig {
+            GeneratedManifestPolicy::Original
+        } else {
+            GeneratedManifestPolicy::Ask
+        });
+        Ok(())
+    }
 }
 
-/// A collection of manifests.
-struct Manifests(Vec<(LocalManifest, cargo_metadata::Package)>);
+/// A collection of manifests, and the root manifest of a virtual workspace whose
+/// `[workspace.dependencies]` are upgraded along with its members.
+struct Manifests(
+    Vec<(LocalManifest, cargo_metadata::Package)>,
+    Option<LocalManifest>,
+);
 
 /// Helper function to check whether a `cargo_metadata::Dependency` is a version dependency.
 fn is_version_dep(dependency: &cargo_metadata::Dependency) -> bool {
     match dependency.source {
         // This is the criterion cargo uses (in `SourceId::from_url`) to decide whether a
         // dependency has the 'registry' kind.
-        Some(ref s) => s.splitn(2, '+').next() == Some("registry"),
+        Some(ref s) => s.split('+').next() == Some("registry"),
         _ => false,
     }
 }
 
 fn deprecated_message(message: &str) -> Result<()> {
-    let bufwtr = BufferWriter::stderr(ColorChoice::Always);
+    let bufwtr = BufferWriter::stderr(stderr_color_choice());
     let mut buffer = bufwtr.buffer();
     buffer
         .set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true))
@@ -152,7 +386,7 @@ fn deprecated_message(message: &str) -> Result<()> {
 }
 
 fn dry_run_message() -> Result<()> {
-    let bufwtr = BufferWriter::stdout(ColorChoice::Always);
+    let bufwtr = BufferWriter::stdout(stdout_color_choice());
     let mut buffer = bufwtr.buffer();
     buffer
         .set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true))
@@ -173,40 +407,50 @@ impl Manifests {
     fn get_all(manifest_path: &Option<PathBuf>) -> Result<Self> {
         let mut cmd = cargo_metadata::MetadataCommand::new();
         cmd.no_deps();
+        cmd.other_options(cargo_options(Vec::new()));
         if let Some(path) = manifest_path {
             cmd.manifest_path(path);
         }
         let result = cmd.exec().map_err(|e| {
             Error::from(e.compat()).chain_err(|| "Failed to get workspace metadata")
         })?;
-        result
+        // Members are independent files, so they are read and parsed concurrently.
+        let paths: Vec<PathBuf> = result
             .packages
-            .into_iter()
-            .map(|package| {
-                Ok((
-                    LocalManifest::try_new(Path::new(&package.manifest_path))?,
-                    package,
-                ))
-            })
-            .collect::<Result<Vec<_>>>()
-            .map(Manifests)
+            .iter()
+            .map(|package| package.manifest_path.clone())
+            .collect();
+        let manifests = LocalManifest::open_all(&paths)?;
+        // A virtual root has no package, but its `[workspace.dependencies]` are inherited by them.
+        let root_path = result.workspace_root.join("Cargo.toml");
+        let virtual_root = if paths.contains(&root_path) {
+            None
+        } else {
+            Some(LocalManifest::try_new(&root_path)?)
+                .filter(|root| root.data["workspace"]["dependencies"].is_table_like())
+        };
+        Ok(Manifests(
+            manifests.into_iter().zip(result.packages).collect(),
+            virtual_root,
+        ))
     }
 
     fn get_pkgid(pkgid: &str) -> Result<Self> {
         let package = manifest_from_pkgid(pkgid)?;
         let manifest = LocalManifest::try_new(Path::new(&package.manifest_path))?;
-        Ok(Manifests(vec![(manifest, package)]))
+        Ok(Manifests(vec![(manifest, package)], None))
     }
 
     /// Get the manifest specified by the manifest path. Try to make an educated guess if no path is
     /// provided.
     fn get_local_one(manifest_path: &Option<PathBuf>) -> Result<Self> {
-        let resolved_manifest_path: String = find(&manifest_path)?.to_string_lossy().into();
+        let resolved_manifest_path = find(manifest_path)?;
 
-        let manifest = LocalManifest::find(&manifest_path)?;
+        let manifest = LocalManifest::find(manifest_path)?;
 
         let mut cmd = cargo_metadata::MetadataCommand::new();
         cmd.no_deps();
+        cmd.other_options(cargo_options(Vec::new()));
         if let Some(path) = manifest_path {
             cmd.manifest_path(path);
         }
@@ -216,7 +460,12 @@ impl Manifests {
         let packages = result.packages;
         let package = packages
             .iter()
-            .find(|p| p.manifest_path.to_string_lossy() == resolved_manifest_path)
+            // With `--orig`, the manifest found is the original of the package's.
+            .find(|p| {
+                let path = Path::new(&p.manifest_path);
+                same_file(path, &resolved_manifest_path)
+                    || original_manifest(path).as_ref() == Some(&resolved_manifest_path)
+            })
             // If we have successfully got metadata, but our manifest path does not correspond to a
             // package, we must have been called against a virtual manifest.
             .chain_err(|| {
@@ -224,7 +473,72 @@ impl Manifests {
                  actual package in this workspace. Try adding `--workspace`."
             })?;
 
-        Ok(Manifests(vec![(manifest, package.to_owned())]))
+        Ok(Manifests(vec![(manifest, package.to_owned())], None))
+    }
+
+    /// The lowest `rust-version` declared by the manifests, for `--rust-version auto`.
+    fn rust_version(&self) -> Result<RustVersion> {
+        let mut lowest = None;
+        for manifest in self.0.iter().map(|(manifest, _)| manifest).chain(&self.1) {
+            if let Some(rust_version) = get_rust_version_from_manifest(&manifest.path)? {
+                lowest = Some(
+                    lowest.map_or(rust_version, |lowest: RustVersion| lowest.min(rust_version)),
+                );
+            }
+        }
+        lowest.ok_or_else(|| ErrorKind::NoRustVersion.into())
+    }
+
+    /// Explain why the dependencies in `exclude` are left alone: for being `pinned` in the
+    /// configuration, `patched` in the workspace root, or excluded with `--exclude`.
+    fn explain_excluded(
+        &self,
+        exclude: &[String],
+        pinned: &[String],
+        patched: &[String],
+        explanations: &mut Vec<(String, String)>,
+    ) {
+        let mut names: Vec<&String> = self
+            .0
+            .iter()
+            .flat_map(|(_, package)| &package.dependencies)
+            .filter(|dependency| is_version_dep(dependency))
+            .flat_map(|dependency| Some(&dependency.name).into_iter().chain(&dependency.rename))
+            .filter(|name| exclude.contains(name))
+            .collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            let reason = if pinned.contains(name) {
+                "left alone, pinned in the configuration"
+            } else if patched.contains(name) {
+                "left alone, patched in the workspace root"
+            } else {
+                "left alone, excluded"
+            };
+            explanations.push((name.clone(), reason.to_owned()));
+        }
+    }
+
+    /// The registry dependencies of the manifests which are overridden by one of `patches` of the
+    /// workspace root at `root`, with the source patched, by name.
+    fn patched(&self, patches: &[Patch], root: &Path) -> Vec<(String, String)> {
+        let mut patched: Vec<(String, String)> = self
+            .0
+            .iter()
+            .flat_map(|(_, package)| &package.dependencies)
+            .filter(|dependency| is_version_dep(dependency))
+            .filter_map(|dependency| {
+                let patch = patches.iter().find(|patch| {
+                    patch.package == dependency.name
+                        && patch.applies_to(dependency.registry.as_deref(), root)
+                })?;
+                Some((dependency.name.clone(), patch.source.clone()))
+            })
+            .collect();
+        patched.sort();
+        patched.dedup();
+        patched
     }
 
     /// Get the the combined set of dependencies to upgrade. If the user has specified
@@ -238,106 +552,200 @@ impl Manifests {
         let selected_dependencies = only_update
             .into_iter()
             .map(|name| {
-                if let Some(dependency) = CrateName::new(&name).parse_as_version()? {
-                    Ok((
-                        dependency.name.clone(),
-                        dependency.version().map(String::from),
-                    ))
-                } else {
-                    Ok((name, None))
-                }
+                let spec = PackageIdSpec::parse(&name)?;
+                Ok((spec.name, spec.version))
             })
             .collect::<Result<HashMap<_, _>>>()?;
 
-        Ok(DesiredUpgrades(
-            self.0
-                .iter()
-                .flat_map(|&(_, ref package)| package.dependencies.clone())
-                .filter(is_version_dep)
-                .filter(|dependency| !exclude.contains(&dependency.name))
-                // Exclude renamed dependecies aswell
-                .filter(|dependency| {
-                    dependency
-                        .rename
-                        .as_ref()
-                        .map_or(true, |rename| !exclude.contains(rename))
-                })
-                .filter_map(|dependency| {
-                    let is_prerelease = dependency.req.to_string().contains('-');
-                    if selected_dependencies.is_empty() {
-                        // User hasn't asked for any specific dependencies to be upgraded,
-                        // so upgrade all the dependencies.
-                        let mut dep = Dependency::new(&dependency.name);
-                        if let Some(rename) = dependency.rename {
-                            dep = dep.set_rename(&rename);
-                        }
-                        Some((
+        let occurrences: Vec<(Dependency, UpgradeMetadata)> = self
+            .0
+            .iter()
+            .flat_map(|(_, package)| package.dependencies.clone())
+            .filter(is_version_dep)
+            .filter(|dependency| !exclude.contains(&dependency.name))
+            // Exclude renamed dependecies aswell
+            .filter(|dependency| {
+                dependency
+                    .rename
+                    .as_ref()
+                    .is_none_or(|rename| !exclude.contains(rename))
+            })
+            .filter_map(|dependency| {
+                let is_prerelease = dependency.req.to_string().contains('-');
+                let current = current_version(&dependency.req.to_string());
+                let req = semver::VersionReq::parse(&dependency.req.to_string()).ok();
+                if selected_dependencies.is_empty() {
+                    // User hasn't asked for any specific dependencies to be upgraded,
+                    // so upgrade all the dependencies.
+                    let mut dep = Dependency::new(&dependency.name);
+                    if let Some(rename) = dependency.rename {
+                        dep = dep.set_rename(&rename);
+                    }
+                    Some((
+                        dep,
+                        UpgradeMetadata {
+                            registry: dependency.registry,
+                            version: None,
+                            is_prerelease,
+                            current,
+                            req,
+                        },
+                    ))
+                } else {
+                    // User has asked for specific dependencies. Check if this dependency
+                    // was specified, populating the registry from the lockfile metadata.
+                    // Renamed dependencies can be selected by their key or their package.
+                    let selected = selected_dependencies.get(&dependency.name).or_else(|| {
+                        dependency
+                            .rename
+                            .as_ref()
+                            .and_then(|rename| selected_dependencies.get(rename))
+                    });
+                    let mut dep = Dependency::new(&dependency.name);
+                    if let Some(rename) = &dependency.rename {
+                        dep = dep.set_rename(rename);
+                    }
+                    match selected {
+                        Some(version) => Some((
                             dep,
                             UpgradeMetadata {
                                 registry: dependency.registry,
-                                version: None,
+                                version: version.clone(),
                                 is_prerelease,
+                                current,
+                                req,
                             },
-                        ))
-                    } else {
-                        // User has asked for specific dependencies. Check if this dependency
-                        // was specified, populating the registry from the lockfile metadata.
-                        match selected_dependencies.get(&dependency.name) {
-                            Some(version) => Some((
-                                Dependency::new(&dependency.name),
-                                UpgradeMetadata {
-                                    registry: dependency.registry,
-                                    version: version.clone(),
-                                    is_prerelease,
-                                },
-                            )),
-                            None => None,
-                        }
+                        )),
+                        None => None,
                     }
-                })
-                .collect(),
+                }
+            })
+            .collect();
+        // A crate required by several manifests is upgraded as the last of them says.
+        Ok(DesiredUpgrades(
+            occurrences.iter().cloned().collect(),
+            occurrences,
         ))
     }
 
-    /// Upgrade the manifests on disk following the previously-determined upgrade schema.
+    /// Upgrade the manifests on disk following the previously-determined upgrade schema. A JSON
+    /// report tells where each dependency stands, from the `candidates` found while choosing the
+    /// upgrades, and why those in `scope` were left alone.
+    #[allow(clippy::too_many_arguments)]
     fn upgrade(
         self,
         upgraded_deps: &ActualUpgrades,
+        scope: &UpgradeScope,
+        candidates: &Candidates,
         dry_run: bool,
         skip_compatible: bool,
-    ) -> Result<()> {
-        if dry_run {
+        output: OutputFormat,
+        columns: &[SummaryColumn],
+    ) -> Result<bool> {
+        let mut report = Report::new("upgrade");
+        report.set_dry_run(dry_run);
+        if dry_run && output == OutputFormat::Plain && !verbosity().is_quiet() {
             dry_run_message()?;
         }
 
-        for (mut manifest, package) in self.0 {
-            println!("{}:", package.name);
+        let mut transaction = ManifestTransaction::new();
+        let mut changed = false;
+        let mut summary = Vec::new();
+        let manifests: Vec<LocalManifest> = self
+            .0
+            .into_iter()
+            .map(|(manifest, _)| manifest)
+            .chain(self.1)
+            .collect();
+        // The patches are upgraded in the workspace root, even if it isn't upgraded otherwise.
+        let patch_revs = &upgraded_deps.2;
+        let root = if patch_revs.revs.is_empty()
+            || manifests
+                .iter()
+                .any(|manifest| same_file(&manifest.path, &patch_revs.root))
+        {
+            None
+        } else {
+            Some(LocalManifest::try_new(&patch_revs.root)?)
+        };
+        for mut manifest in manifests.into_iter().chain(root) {
+            let original = start_upgrade(&mut manifest)?;
+            if same_file(&manifest.path, &patch_revs.root) {
+                patch_revs.apply(&mut manifest)?;
+            }
 
             for (dep, version) in &upgraded_deps.0 {
                 let mut new_dep = Dependency::new(&dep.name).set_version(version);
                 if let Some(rename) = dep.rename() {
-                    new_dep = new_dep.set_rename(&rename);
+                    new_dep = new_dep.set_rename(rename);
                 }
-                manifest.upgrade(&new_dep, dry_run, skip_compatible)?;
+                // The changes are made in memory to show them, even in a dry run.
+                manifest.upgrade_in_memory(&new_dep, false, skip_compatible)?;
             }
+            changed |= show_changes(&manifest, &original, output, &mut report)?;
+            if output == OutputFormat::Json {
+                let statuses = dependency_statuses(
+                    &manifest,
+                    &original,
+                    scope,
+                    skip_compatible,
+                    &upgraded_deps.3,
+                    candidates,
+                )?;
+                report.add_dependencies(&manifest.path, statuses);
+            }
+            summary.extend(summary_rows(&manifest, &original, |key, old, new| {
+                let (dep, version) = match upgraded_deps
+                    .0
+                    .iter()
+                    .find(|(dep, _)| dep.rename().unwrap_or(&dep.name) == key)
+                {
+                    Some(upgrade) => upgrade,
+                    None => return (String::new(), ""),
+                };
+                let requested = upgraded_deps.1.contains(&dep.name);
+                let latest = if requested {
+                    String::new()
+                } else {
+                    version.clone()
+                };
+                (latest, summary_note(old, new, requested))
+            })?);
+            manifest.stage(&mut transaction)?;
+            warn_metadata_violations(&manifest, &manifest.path);
         }
 
-        Ok(())
+        // Write all manifests at once, so a failure can't leave the workspace half-upgraded.
+        if !dry_run {
+            transaction.commit()?;
+        }
+        if output != OutputFormat::Plain {
+            print!("{}", report.render(output));
+        } else if !verbosity().is_quiet() {
+            print_summary(summary, columns);
+        }
+        Ok(changed)
     }
 
     /// Update dependencies in Cargo.toml file(s) to match the corresponding
     /// version in Cargo.lock.
-    fn sync_to_lockfile(self, dry_run: bool, skip_compatible: bool) -> Result<()> {
+    fn sync_to_lockfile(
+        self,
+        dry_run: bool,
+        skip_compatible: bool,
+        output: OutputFormat,
+        columns: &[SummaryColumn],
+    ) -> Result<bool> {
         // Get locked dependencies. For workspaces with multiple Cargo.toml
         // files, there is only a single lockfile, so it suffices to get
         // metadata for any one of Cargo.toml files.
-        let (manifest, _package) = self.0.get(0).ok_or(ErrorKind::CargoEditLib(
+        let (manifest, _package) = self.0.first().ok_or(ErrorKind::CargoEditLib(
             ::cargo_edit::ErrorKind::InvalidCargoConfig,
         ))?;
         let mut cmd = cargo_metadata::MetadataCommand::new();
         cmd.manifest_path(manifest.path.clone());
         cmd.features(cargo_metadata::CargoOpt::AllFeatures);
-        cmd.other_options(vec!["--locked".to_string()]);
+        cmd.other_options(cargo_options(vec!["--locked".to_string()]));
 
         let result = cmd
             .exec()
@@ -349,18 +757,32 @@ impl Manifests {
             .filter(|p| p.source.is_some()) // Source is none for local packages
             .collect::<Vec<_>>();
 
-        if dry_run {
+        let mut report = Report::new("upgrade");
+        report.set_dry_run(dry_run);
+        if dry_run && output == OutputFormat::Plain && !verbosity().is_quiet() {
             dry_run_message()?;
         }
 
-        for (mut manifest, package) in self.0 {
-            println!("{}:", package.name);
+        let mut transaction = ManifestTransaction::new();
+        let mut changed = false;
+        let mut summary = Vec::new();
+        // The virtual root lists the dependencies of every member it is inherited by.
+        let all_dependencies: Vec<cargo_metadata::Dependency> = self
+            .0
+            .iter()
+            .flat_map(|(_, package)| package.dependencies.clone())
+            .collect();
+        let manifests = self
+            .0
+            .into_iter()
+            .map(|(manifest, package)| (manifest, package.dependencies))
+            .chain(self.1.map(|root| (root, all_dependencies)));
+        for (mut manifest, dependencies) in manifests {
+            let original = start_upgrade(&mut manifest)?;
 
             // Upgrade the manifests one at a time, as multiple manifests may
             // request the same dependency at differing versions.
-            for (name, version) in package
-                .dependencies
-                .clone()
+            for (name, version) in dependencies
                 .into_iter()
                 .filter(is_version_dep)
                 .filter_map(|d| {
@@ -374,87 +796,835 @@ impl Manifests {
                     None
                 })
             {
-                manifest.upgrade(
+                manifest.upgrade_in_memory(
                     &Dependency::new(&name).set_version(&version),
-                    dry_run,
+                    false,
                     skip_compatible,
                 )?;
             }
+            changed |= show_changes(&manifest, &original, output, &mut report)?;
+            summary.extend(summary_rows(&manifest, &original, |_, _, _| {
+                (String::new(), "locked")
+            })?);
+            manifest.stage(&mut transaction)?;
+            warn_metadata_violations(&manifest, &manifest.path);
+        }
+
+        if !dry_run {
+            transaction.commit()?;
+        }
+        if output != OutputFormat::Plain {
+            print!("{}", report.render(output));
+        } else if !verbosity().is_quiet() {
+            print_summary(summary, columns);
+        }
+        Ok(changed)
+    }
+}
+
+/// Prepare `manifest` to be upgraded, returning its current contents to show the changes
+/// against.
+fn start_upgrade(manifest: &mut LocalManifest) -> Result<String> {
+    // The changes are shown as a whole once they are made.
+    manifest.set_quiet(true);
+    std::fs::read_to_string(&manifest.path)
+        .chain_err(|| format!("Failed to read {}", manifest.path.display()))
+}
+
+/// Print the changes made to `manifest` as a diff, or add them to `report` if one is printed
+/// instead, returning whether there are any.
+fn show_changes(
+    manifest: &LocalManifest,
+    original: &str,
+    output: OutputFormat,
+    report: &mut Report,
+) -> Result<bool> {
+    let new = manifest.to_formatted_string()?;
+    let changed = original != new;
+    if output == OutputFormat::Plain {
+        if verbosity().is_quiet() {
+            return Ok(changed);
+        }
+        let colorchoice = stdout_color_choice();
+        write_manifest_diff(
+            &mut StandardStream::stdout(colorchoice),
+            &manifest.path,
+            original,
+            &new,
+        )?;
+    } else {
+        report.add_diff(&manifest.path, original, &new)?;
+    }
+    Ok(changed)
+}
+
+/// A crate overridden in a `[patch.<source>]` table of the workspace root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Patch {
+    /// The source patched: `crates-io`, or the name or URL of a registry
+    source: String,
+    /// The key of the entry in its table
+    key: String,
+    /// The crate patched, unless renamed with `package` the key
+    package: String,
+    /// The repository of a patch pinned to a git `rev`
+    git_rev: Option<String>,
+}
+
+impl Patch {
+    /// The entries of the `[patch]` tables of `root`.
+    fn find(root: &LocalManifest) -> Vec<Patch> {
+        let mut patches = Vec::new();
+        let sources = match root.data["patch"].as_table_like() {
+            Some(sources) => sources,
+            None => return patches,
+        };
+        for (source, table) in sources.iter() {
+            let table = match table.as_table_like() {
+                Some(table) => table,
+                None => continue,
+            };
+            for (key, entry) in table.iter() {
+                let git_rev = match (entry["git"].as_str(), entry["rev"].is_str()) {
+                    (Some(repo), true) => Some(repo.to_owned()),
+                    _ => None,
+                };
+                patches.push(Patch {
+                    source: source.to_owned(),
+                    key: key.to_owned(),
+                    package: entry["package"].as_str().unwrap_or(key).to_owned(),
+                    git_rev,
+                });
+            }
+        }
+        patches
+    }
+
+    /// Whether the patch overrides a dependency from `registry`, given by cargo metadata as its
+    /// index URL, or crates.io if `None`.
+    fn applies_to(&self, registry: Option<&str>, root: &Path) -> bool {
+        match registry {
+            None => self.source == "crates-io",
+            Some(registry) => {
+                let trimmed = |url: &str| url.trim_end_matches('/').to_owned();
+                trimmed(&self.source) == trimmed(registry)
+                    || registry_url(root, Some(&self.source))
+                        .is_ok_and(|url| trimmed(url.as_str()) == trimmed(registry))
+            }
+        }
+    }
+}
+
+/// New `rev`s for the git `[patch]` entries of the workspace root.
+struct PatchRevs {
+    root: PathBuf,
+    revs: Vec<(Patch, String)>,
+}
+
+impl PatchRevs {
+    /// Look up the newest commit of the repository of each of `patches` pinned to a git `rev`.
+    fn find(root: PathBuf, patches: &[Patch]) -> Result<Self> {
+        let mut revs = Vec::new();
+        for patch in patches {
+            if let Some(repo) = &patch.git_rev {
+                let rev = get_git_revision(repo, None)
+                    .chain_err(|| format!("Failed to get the newest commit of {}", repo))?;
+                revs.push((patch.clone(), rev));
+            }
+        }
+        Ok(PatchRevs { root, revs })
+    }
+
+    /// Set the new `rev`s in `root`, the workspace root being upgraded.
+    fn apply(&self, root: &mut LocalManifest) -> Result<()> {
+        for (patch, rev) in &self.revs {
+            let current = root.data["patch"][&patch.source][&patch.key]["rev"].as_str();
+            if current != Some(rev) {
+                root.set_patch_rev(&patch.source, &patch.key, rev)?;
+            }
         }
         Ok(())
     }
 }
 
+/// What `cargo upgrade` was asked to upgrade, to tell why dependencies were left alone.
+struct UpgradeScope {
+    /// The crates named to be upgraded, if any were
+    selected: Vec<String>,
+    /// The crates excluded, including the pinned ones
+    exclude: Vec<String>,
+    /// The crates pinned in the configuration
+    pinned: Vec<String>,
+    /// The crates left alone for being patched in the workspace root
+    patched: Vec<String>,
+}
+
+impl UpgradeScope {
+    /// Why the dependency under `key` on `package` is left alone regardless of its versions.
+    fn skip_reason(&self, key: &str, package: &str) -> Option<SkipReason> {
+        let named = |names: &[String]| names.iter().any(|name| name == key || name == package);
+        if named(&self.pinned) {
+            Some(SkipReason::Pinned)
+        } else if named(&self.patched) {
+            Some(SkipReason::Patched)
+        } else if named(&self.exclude) {
+            Some(SkipReason::Excluded)
+        } else if !self.selected.is_empty() && !named(&self.selected) {
+            Some(SkipReason::NotSelected)
+        } else {
+            None
+        }
+    }
+}
+
+/// The versions found while choosing the upgrades, for the reports, by crate, registry name and
+/// requirement.
+type Candidates = HashMap<(String, Option<String>, Option<semver::VersionReq>), UpgradeCandidates>;
+
+/// Where each registry dependency of `manifest` stands after upgrading it from `original`.
+///
+/// The newest versions are those in `candidates`, found while choosing the upgrades. They are
+/// unknown for dependencies which were not looked up, e.g. as they are excluded.
+fn dependency_statuses(
+    manifest: &LocalManifest,
+    original: &str,
+    scope: &UpgradeScope,
+    skip_compatible: bool,
+    held_back: &HashSet<String>,
+    candidates: &Candidates,
+) -> Result<Vec<DependencyStatus>> {
+    let before: Manifest = original.parse()?;
+    let after: Vec<_> = manifest.dependency_entries().collect();
+    let mut statuses = Vec::new();
+    for entry in before.dependency_entries() {
+        if entry.path().is_some() || entry.is_git() || entry.is_inherited() {
+            continue;
+        }
+        let package = entry.name().to_owned();
+        let current = entry.version_req().map(ToOwned::to_owned);
+        let new = after
+            .iter()
+            .find(|new| new.table == entry.table && new.key == entry.key)
+            .and_then(|new| new.version_req())
+            .map(ToOwned::to_owned);
+        let req = current
+            .as_deref()
+            .and_then(|req| semver::VersionReq::parse(req).ok());
+        let registry = entry.registry().map(ToOwned::to_owned);
+        let found = candidates
+            .get(&(package.clone(), registry, req.clone()))
+            .cloned()
+            .unwrap_or_default();
+
+        let skipped =
+            scope
+                .skip_reason(&entry.key, &package)
+                .or_else(|| match (&req, &found.latest) {
+                    _ if new != current => None,
+                    _ if held_back.contains(&package) => Some(SkipReason::RustVersion),
+                    (Some(req), Some(latest))
+                        if skip_compatible
+                            && semver::Version::parse(latest).is_ok_and(|v| req.matches(&v)) =>
+                    {
+                        Some(SkipReason::SkipCompatible)
+                    }
+                    _ if !found.yanked.is_empty() => Some(SkipReason::Yanked),
+                    _ => None,
+                });
+        statuses.push(DependencyStatus {
+            table: entry.table.path().join("."),
+            name: entry.key.clone(),
+            package,
+            current,
+            new,
+            latest_compatible: found.latest_compatible.clone(),
+            latest: found.latest.clone(),
+            skipped,
+        });
+    }
+    Ok(statuses)
+}
+
+/// A column of the summary printed once the manifests are upgraded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SummaryColumn {
+    Name,
+    Kind,
+    Old,
+    New,
+    Latest,
+    Note,
+}
+
+impl SummaryColumn {
+    const NAMES: &'static [&'static str] = &["name", "kind", "old", "new", "latest", "note"];
+
+    const ALL: [SummaryColumn; 6] = [
+        SummaryColumn::Name,
+        SummaryColumn::Kind,
+        SummaryColumn::Old,
+        SummaryColumn::New,
+        SummaryColumn::Latest,
+        SummaryColumn::Note,
+    ];
+}
+
+impl FromStr for SummaryColumn {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        SummaryColumn::NAMES
+            .iter()
+            .position(|&name| name == s)
+            .map(|column| SummaryColumn::ALL[column])
+            .ok_or_else(|| {
+                format!(
+                    "Unknown column `{}`, expected one of {}",
+                    s,
+                    SummaryColumn::NAMES.join(", ")
+                )
+                .into()
+            })
+    }
+}
+
+/// A requirement changed by the upgrade, as a row of the summary.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct SummaryRow {
+    name: String,
+    kind: String,
+    old: String,
+    new: String,
+    latest: String,
+    note: &'static str,
+}
+
+impl SummaryRow {
+    fn cell(&self, column: SummaryColumn) -> &str {
+        match column {
+            SummaryColumn::Name => &self.name,
+            SummaryColumn::Kind => &self.kind,
+            SummaryColumn::Old => &self.old,
+            SummaryColumn::New => &self.new,
+            SummaryColumn::Latest => &self.latest,
+            SummaryColumn::Note => self.note,
+        }
+    }
+}
+
+/// The summary rows of the requirements changed in `manifest`, `describe` giving the latest
+/// version and a note for a dependency name and its old and new requirements.
+fn summary_rows(
+    manifest: &LocalManifest,
+    original: &str,
+    describe: impl Fn(&str, &str, &str) -> (String, &'static str),
+) -> Result<Vec<SummaryRow>> {
+    let changes = manifest_diff(original, &manifest.to_formatted_string()?)?;
+    // Renamed dependencies are shown with the package they are renamed from.
+    let display_name = |key: &str| {
+        manifest
+            .dependency_entries()
+            .find(|entry| entry.key == key)
+            .and_then(|entry| entry.to_dependency())
+            .map_or_else(|| key.to_owned(), |dep| dep.display_name())
+    };
+    Ok(changes
+        .iter()
+        .filter_map(|change| match change {
+            ManifestChange::DepVersionChanged {
+                table,
+                name,
+                old,
+                new,
+            } => {
+                let old = old.clone().unwrap_or_else(|| "*".to_owned());
+                let new = new.clone().unwrap_or_else(|| "*".to_owned());
+                let (latest, note) = describe(name, &old, &new);
+                Some(SummaryRow {
+                    name: display_name(name),
+                    kind: summary_kind(table),
+                    old,
+                    new,
+                    latest,
+                    note,
+                })
+            }
+            // `[workspace.dependencies]` are compared as metadata, value by value.
+            ManifestChange::MetadataChanged { key, old, new } => {
+                let name = key.strip_prefix("workspace.dependencies.")?;
+                let name = name.strip_suffix(".version").unwrap_or(name);
+                if name.contains('.') {
+                    return None;
+                }
+                let unquote = |value: &Option<String>| {
+                    value
+                        .as_deref()
+                        .map_or("*", |value| value.trim_matches('"'))
+                        .to_owned()
+                };
+                let (old, new) = (unquote(old), unquote(new));
+                let (latest, note) = describe(name, &old, &new);
+                Some(SummaryRow {
+                    name: display_name(name),
+                    kind: summary_kind("workspace.dependencies"),
+                    old,
+                    new,
+                    latest,
+                    note,
+                })
+            }
+            _ => None,
+        })
+        .collect())
+}
+
+/// The kind of a dependency table like `dev-dependencies` or `target.'cfg(unix)'.dependencies`,
+/// like `dev` or `normal, cfg(unix)`.
+fn summary_kind(table: &str) -> String {
+    let (target, kind) = match table.rsplit_once('.') {
+        Some((target, kind)) => (Some(target), kind),
+        None => (None, table),
+    };
+    let kind = match kind {
+        "dev-dependencies" => "dev",
+        "build-dependencies" => "build",
+        _ => "normal",
+    };
+    match target {
+        Some("workspace") => "workspace".to_owned(),
+        Some(target) => {
+            let target = target
+                .trim_start_matches("target.")
+                .trim_matches(['\'', '"']);
+            format!("{}, {}", kind, target)
+        }
+        None => kind.to_owned(),
+    }
+}
+
+/// What the summary notes about a requirement changed from `old` to `new`, `requested` on the
+/// command line rather than the latest version.
+fn summary_note(old: &str, new: &str, requested: bool) -> &'static str {
+    match (current_version(old), current_version(new)) {
+        (Some(old), Some(new)) if new < old => "downgrade",
+        (Some(old), Some(new)) if !is_compatible(&old, &new) => "incompatible",
+        _ if requested => "requested",
+        _ => "",
+    }
+}
+
+/// Print the requirements changed as a table of `columns`, fitted to the terminal.
+fn print_summary(mut rows: Vec<SummaryRow>, columns: &[SummaryColumn]) {
+    // The same requirement is usually changed in several members of a workspace.
+    rows.sort();
+    rows.dedup();
+    if rows.is_empty() || columns.is_empty() {
+        return;
+    }
+    let headings: Vec<&str> = columns
+        .iter()
+        .map(|&column| SummaryColumn::NAMES[column as usize])
+        .collect();
+    let mut table = Table::new(&headings);
+    for row in &rows {
+        table.add_row(
+            columns
+                .iter()
+                .map(|&column| row.cell(column).to_owned())
+                .collect(),
+        );
+    }
+    print!("\n{}", table.render(terminal_width()));
+}
+
 // Some metadata about the dependency
 // we're trying to upgrade.
+#[derive(Clone)]
 struct UpgradeMetadata {
     registry: Option<String>,
     // `Some` if the user has specified an explicit
     // version to upgrade to.
     version: Option<String>,
     is_prerelease: bool,
+    // The version the current requirement is based on, e.g. `1.2.0` for `^1.2`.
+    current: Option<semver::Version>,
+    // The current requirement.
+    req: Option<semver::VersionReq>,
+}
+
+/// The lowest version a requirement like `^1.2.3`, `=1.2.3` or `>=1.2.0, <1.3.0` allows, if it
+/// has a lower bound.
+fn current_version(req: &str) -> Option<semver::Version> {
+    let lower_bound = req.split(',').next()?.trim();
+    let exclusive = lower_bound.starts_with('>') && !lower_bound.starts_with(">=");
+    if lower_bound.starts_with('<') || exclusive {
+        return None;
+    }
+    let version = lower_bound.trim_start_matches(['>', '^', '=', '~']).trim();
+    let parts = version.split('-').next()?.split('.').count();
+    let padded = match parts {
+        1 => format!("{}.0.0", version),
+        2 => format!("{}.0", version),
+        _ => version.to_owned(),
+    };
+    semver::Version::parse(&padded).ok()
 }
 
+/// A dependency to look up.
+struct Query {
+    dep: Dependency,
+    /// The version its requirement is based on
+    current: Option<semver::Version>,
+    req: Option<semver::VersionReq>,
+    /// Whether it is upgraded, rather than only looked up for the reports
+    upgraded: bool,
+}
+
+/// Dependencies to look up, batched by registry and whether pre-releases are allowed.
+type Queries = HashMap<(Option<String>, bool), Vec<Query>>;
+
 /// The set of dependencies to be upgraded, alongside the registries returned from cargo metadata, and
-/// the desired versions, if specified by the user.
-struct DesiredUpgrades(HashMap<Dependency, UpgradeMetadata>);
+/// the desired versions, if specified by the user. Every requirement on them is kept too, as the
+/// reports tell where each one stands.
+struct DesiredUpgrades(
+    HashMap<Dependency, UpgradeMetadata>,
+    Vec<(Dependency, UpgradeMetadata)>,
+);
 
 /// The complete specification of the upgrades that will be performed. Map of the dependency names
-/// to the new versions.
-struct ActualUpgrades(HashMap<Dependency, String>);
+/// to the new versions, the names of those whose version was requested rather than the latest, the
+/// new `rev`s of git patches, and the names of those held back by `--rust-version`.
+struct ActualUpgrades(
+    HashMap<Dependency, String>,
+    HashSet<String>,
+    PatchRevs,
+    HashSet<String>,
+);
+
+/// The Rust version upgrades keep supporting, with `--rust-version`.
+struct RustVersionCap {
+    version: RustVersion,
+    /// Whether versions not declaring the Rust version they need are passed over
+    strict: bool,
+}
 
 impl DesiredUpgrades {
+    /// Warn about dependencies whose current version has been yanked.
+    ///
+    /// Only the yank status of these versions is re-checked, without refreshing the whole index
+    /// cache. Failures are ignored, as they are not worth aborting the upgrade for.
+    fn warn_yanked(&self, manifest_path: &Path) {
+        for (
+            dep,
+            UpgradeMetadata {
+                registry, current, ..
+            },
+        ) in &self.0
+        {
+            let current = match current {
+                Some(current) => current,
+                None => continue,
+            };
+            let registry_url = registry.as_ref().and_then(|x| Url::parse(x).ok());
+            let yanked = get_yanked_versions(
+                &dep.name,
+                std::slice::from_ref(current),
+                manifest_path,
+                &registry_url,
+            );
+            if yanked.is_ok_and(|yanked| !yanked.is_empty()) {
+                eprintln!(
+                    "WARN: The current version of `{}` (v{}) has been yanked",
+                    dep.name, current
+                );
+            }
+        }
+    }
+
     /// Transform the dependencies into their upgraded forms. If a version is specified, all
-    /// dependencies will get that version.
-    fn get_upgraded(self, allow_prerelease: bool, manifest_path: &Path) -> Result<ActualUpgrades> {
-        self.0
-            .into_iter()
-            .map(
-                |(
-                    dep,
-                    UpgradeMetadata {
-                        registry,
-                        version,
-                        is_prerelease,
-                    },
-                )| {
-                    if let Some(v) = version {
-                        Ok((dep, v))
-                    } else {
-                        let registry_url = match registry {
-                            Some(x) => Some(Url::parse(&x).map_err(|_| {
-                                ErrorKind::CargoEditLib(::cargo_edit::ErrorKind::InvalidCargoConfig)
-                            })?),
-                            None => None,
-                        };
-                        let allow_prerelease = allow_prerelease || is_prerelease;
-                        get_latest_dependency(
-                            &dep.name,
-                            allow_prerelease,
-                            manifest_path,
-                            &registry_url,
-                        )
-                        .map(|new_dep| {
-                            (
-                                dep,
-                                new_dep
-                                    .version()
-                                    .expect("Invalid dependency type")
-                                    .to_string(),
-                            )
-                        })
-                        .chain_err(|| "Failed to get new version")
+    /// dependencies will get that version. Pre-releases are included for all dependencies if
+    /// `allow_prerelease`, or else for those in `prerelease_crates` and those already on one.
+    /// With a `rust_version`, the latest versions supporting it are chosen instead, and those with
+    /// none newer than the current one are left alone. With `explain`, why each version is chosen
+    /// is added to `explanations`, and with `candidates` the versions found for every requirement
+    /// on the dependencies are added to them.
+    #[allow(clippy::too_many_arguments)]
+    fn get_upgraded(
+        self,
+        patch_revs: PatchRevs,
+        allow_prerelease: bool,
+        prerelease_crates: &[String],
+        manifest_path: &Path,
+        rust_version: Option<&RustVersionCap>,
+        mut explanations: Option<&mut Vec<(String, String)>>,
+        mut candidates: Option<&mut Candidates>,
+    ) -> Result<ActualUpgrades> {
+        let mut upgrades = HashMap::new();
+        let mut requested = HashSet::new();
+        let mut held_back = HashSet::new();
+        let mut queries = Queries::new();
+        let batch = |registry, is_prerelease, name: &String| {
+            (
+                registry,
+                allow_prerelease || is_prerelease || prerelease_crates.contains(name),
+            )
+        };
+        for (
+            dep,
+            UpgradeMetadata {
+                registry,
+                version,
+                is_prerelease,
+                current,
+                req,
+            },
+        ) in self.0
+        {
+            match version {
+                Some(v) => {
+                    if let Some(explanations) = explanations.as_deref_mut() {
+                        explanations.push((dep.name.clone(), format!("{}, as requested", v)));
                     }
+                    requested.insert(dep.name.clone());
+                    upgrades.insert(dep, v);
+                }
+                None => queries
+                    .entry(batch(registry, is_prerelease, &dep.name))
+                    .or_default()
+                    .push(Query {
+                        dep,
+                        current,
+                        req,
+                        upgraded: true,
+                    }),
+            }
+        }
+        // The reports tell where every requirement stands, not only the one upgraded from.
+        if candidates.is_some() {
+            for (
+                dep,
+                UpgradeMetadata {
+                    registry,
+                    version,
+                    is_prerelease,
+                    req,
+                    ..
                 },
-            )
-            .collect::<Result<_>>()
-            .map(ActualUpgrades)
+            ) in self.1
+            {
+                if version.is_some() {
+                    continue;
+                }
+                let queued = queries
+                    .entry(batch(registry, is_prerelease, &dep.name))
+                    .or_default();
+                if !queued
+                    .iter()
+                    .any(|query| query.dep.name == dep.name && query.req == req)
+                {
+                    queued.push(Query {
+                        dep,
+                        current: None,
+                        req,
+                        upgraded: false,
+                    });
+                }
+            }
+        }
+
+        for ((registry, allow_prerelease), deps) in queries {
+            let registry_url = match registry {
+                Some(x) => Some(Url::parse(&x).map_err(|_| {
+                    ErrorKind::CargoEditLib(::cargo_edit::ErrorKind::InvalidCargoConfig)
+                })?),
+                None => None,
+            };
+            if let Some(candidates) = candidates.as_deref_mut() {
+                let queried: Vec<_> = deps
+                    .iter()
+                    .map(|query| (query.dep.name.as_str(), query.req.as_ref()))
+                    .collect();
+                let found = get_latest_dependencies_with_candidates(
+                    &queried,
+                    allow_prerelease,
+                    manifest_path,
+                    &registry_url,
+                );
+                let registry = match &registry_url {
+                    Some(url) => registry_name(manifest_path, url)?,
+                    None => None,
+                };
+                for (query, found) in deps.iter().zip(found) {
+                    let (new_dep, found) = found.chain_err(|| "Failed to get new version")?;
+                    let key = (query.dep.name.clone(), registry.clone(), query.req.clone());
+                    candidates.insert(key, found);
+                    if query.upgraded && rust_version.is_none() {
+                        let version = new_dep
+                            .version()
+                            .expect("Invalid dependency type")
+                            .to_string();
+                        upgrades.insert(query.dep.clone(), version);
+                    }
+                }
+            }
+            let deps: Vec<Query> = deps.into_iter().filter(|query| query.upgraded).collect();
+            let names: Vec<&str> = deps.iter().map(|query| query.dep.name.as_str()).collect();
+            if let Some(explanations) = explanations.as_deref_mut() {
+                let latest = Latest { allow_prerelease };
+                let policy: Box<dyn VersionPolicy> = match rust_version {
+                    Some(cap) => Box::new(
+                        MsrvCompatible::new(latest, cap.version.to_version()).strict(cap.strict),
+                    ),
+                    None => Box::new(latest),
+                };
+                for name in &names {
+                    let selection =
+                        explain_dependency(name, &*policy, manifest_path, &registry_url)
+                            .chain_err(|| "Failed to explain the version chosen")?;
+                    explanations.push((name.to_string(), explain_selection(&selection)));
+                }
+            }
+            let cap = match rust_version {
+                Some(cap) => cap,
+                // Already chosen along with the versions for the reports.
+                None if candidates.is_some() => continue,
+                None => {
+                    let latest = get_latest_dependencies(
+                        &names,
+                        allow_prerelease,
+                        manifest_path,
+                        &registry_url,
+                    );
+                    for (query, new_dep) in deps.iter().zip(latest) {
+                        let new_dep = new_dep.chain_err(|| "Failed to get new version")?;
+                        let version = new_dep
+                            .version()
+                            .expect("Invalid dependency type")
+                            .to_string();
+                        upgrades.insert(query.dep.clone(), version);
+                    }
+                    continue;
+                }
+            };
+            let compatibility = get_msrv_compatibility(
+                &names,
+                &cap.version,
+                allow_prerelease,
+                cap.strict,
+                manifest_path,
+                &registry_url,
+            );
+            for (Query { dep, current, .. }, compatibility) in deps.into_iter().zip(compatibility) {
+                let compatibility = compatibility.chain_err(|| "Failed to get new version")?;
+                if compatibility.is_held_back() {
+                    held_back.insert(dep.name.clone());
+                }
+                // Never downgrade to support the Rust version, but leave the dependency alone.
+                match compatibility.compatible {
+                    Some(compatible)
+                        if current
+                            .as_ref()
+                            .is_none_or(|current| compatible.version >= *current) =>
+                    {
+                        upgrades.insert(dep, compatible.version.to_string());
+                    }
+                    _ if verbosity().is_quiet() => {}
+                    _ => eprintln!(
+                        "WARN: Not upgrading `{}`, as no newer version supports Rust {}",
+                        dep.name, cap.version
+                    ),
+                }
+            }
+        }
+        Ok(ActualUpgrades(upgrades, requested, patch_revs, held_back))
+    }
+}
+
+/// Why `selection` is the version chosen, and why each newer one is passed over, one per line.
+fn explain_selection(selection: &Selection) -> String {
+    let mut explanation = match selection.selected {
+        Some(ref selected) if selection.rejected.is_empty() => {
+            format!("{}, the newest version", selected)
+        }
+        Some(ref selected) => format!("{}, the newest version allowed", selected),
+        None => "no version can be chosen".to_owned(),
+    };
+    let width = selection
+        .rejected
+        .iter()
+        .map(|(version, _)| version.to_string().len())
+        .max()
+        .unwrap_or_default();
+    for (version, rejection) in &selection.rejected {
+        explanation.push_str(&format!(
+            "\n    {:width$}  {}",
+            version.to_string(),
+            rejection,
+            width = width
+        ));
+    }
+    explanation
+}
+
+/// Print the explanations of `--explain`, by crate name.
+fn print_explanations(mut explanations: Vec<(String, String)>) {
+    explanations.sort();
+    for (name, explanation) in explanations {
+        println!("{}: {}", name, explanation);
+    }
+}
+
+/// How many incompatible upgrades `cargo upgrade` applies before asking to confirm them.
+const MANY_INCOMPATIBLE_UPGRADES: usize = 50;
+
+/// Ask before downgrading dependencies to versions given on the command line, and before applying
+/// many incompatible upgrades at once. `current` holds the versions the requirements are based on.
+fn confirm_upgrades(
+    current: &HashMap<Dependency, semver::Version>,
+    upgrades: &ActualUpgrades,
+    yes: bool,
+) -> Result<()> {
+    let mut downgrades = Vec::new();
+    let mut incompatible = 0;
+    for (dep, version) in &upgrades.0 {
+        let (current, new) = match (current.get(dep), current_version(version)) {
+            (Some(current), Some(new)) => (current, new),
+            _ => continue,
+        };
+        if new < *current {
+            downgrades.push(format!("`{}` from {} to {}", dep.name, current, new));
+        } else if !is_compatible(current, &new) {
+            incompatible += 1;
+        }
+    }
+    if !downgrades.is_empty() {
+        downgrades.sort();
+        confirm(&format!("Downgrade {}?", downgrades.join(", ")), yes)?;
+    }
+    if incompatible >= MANY_INCOMPATIBLE_UPGRADES {
+        confirm(
+            &format!("Apply {} incompatible upgrades?", incompatible),
+            yes,
+        )?;
+    }
+    Ok(())
+}
+
+/// Whether a `^current` requirement allows `new`, ignoring pre-releases.
+fn is_compatible(current: &semver::Version, new: &semver::Version) -> bool {
+    match (current.major, current.minor) {
+        (0, 0) => new.major == 0 && new.minor == 0 && new.patch == current.patch,
+        (0, minor) => new.major == 0 && new.minor == minor,
+        (major, _) => new.major == major,
     }
 }
 
 /// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
 /// messages.
-fn process(args: Args) -> Result<()> {
+fn process(args: Args) -> Result<ExitCode> {
+    init_tracing()?;
+    cancel_on_ctrl_c()?;
+
     let Args {
         dependency,
         manifest_path,
@@ -466,20 +1636,38 @@ fn process(args: Args) -> Result<()> {
         to_lockfile,
         workspace,
         exclude,
+        msrv_strict,
         ..
     } = args;
+    let output = args.output.unwrap_or_default();
+    // Progress lines are left out of reports too.
+    let quiet = args.quiet || output != OutputFormat::Plain;
+    if output != OutputFormat::Plain {
+        disable_progress();
+    }
 
-    if all {
+    if all && !args.quiet {
         deprecated_message("The flag `--all` has been deprecated in favor of `--workspace`")?;
     }
 
     let all = workspace || all;
+    let index_max_age = if args.refresh {
+        Duration::from_secs(0)
+    } else {
+        args.cache_ttl.unwrap_or_else(cache_ttl)
+    };
 
     if !args.offline && !to_lockfile && std::env::var("CARGO_IS_TEST").is_err() {
         let url = registry_url(&find(&manifest_path)?, None)?;
-        update_registry_index(&url, false)?;
+        update_registry_index_if_stale(&url, quiet, index_max_age)?;
     }
 
+    // Held until the manifests are written, so concurrent edits can't overwrite each other.
+    let _lock = if dry_run {
+        None
+    } else {
+        Some(ManifestLock::acquire(&find(&manifest_path)?)?)
+    };
     let manifests = if all {
         Manifests::get_all(&manifest_path)
     } else if let Some(ref pkgid) = pkgid {
@@ -488,51 +1676,213 @@ fn process(args: Args) -> Result<()> {
         Manifests::get_local_one(&manifest_path)
     }?;
 
-    if to_lockfile {
-        manifests.sync_to_lockfile(dry_run, skip_compatible)
+    let changed = if to_lockfile {
+        manifests.sync_to_lockfile(dry_run, skip_compatible, output, &args.columns)?
     } else {
+        let selected: Vec<String> = dependency
+            .iter()
+            .filter_map(|name| PackageIdSpec::parse(name).ok())
+            .map(|spec| spec.name)
+            .collect();
+        // Patched crates are left alone unless named, as their patch decides what is built.
+        let root = find_workspace_root(&find(&manifest_path)?)?;
+        let patches = Patch::find(&LocalManifest::try_new(&root)?);
+        let mut exclude = exclude;
+        let mut patched = Vec::new();
+        let mut upgraded_patches = Vec::new();
+        for (name, source) in manifests.patched(&patches, &root) {
+            if exclude.contains(&name) || !(selected.is_empty() || selected.contains(&name)) {
+                continue;
+            }
+            if !selected.contains(&name) {
+                if !args.quiet {
+                    eprintln!(
+                        "WARN: Not upgrading `{}`, which is patched in `[patch.{}]`",
+                        name, source
+                    );
+                }
+                exclude.push(name.clone());
+                patched.push(name.clone());
+            } else if !args.quiet {
+                eprintln!(
+                    "WARN: `{}` is patched in `[patch.{}]`, which cargo only uses while it \
+                     matches the upgraded requirement",
+                    name, source
+                );
+            }
+            if args.upgrade_patches {
+                upgraded_patches.extend(
+                    patches
+                        .iter()
+                        .filter(|patch| patch.package == name && patch.source == source),
+                );
+            }
+        }
+        let upgraded_patches: Vec<Patch> = upgraded_patches.into_iter().cloned().collect();
+        let patch_revs = PatchRevs::find(root, &upgraded_patches)?;
+        let scope = UpgradeScope {
+            selected,
+            exclude: exclude.clone(),
+            pinned: args.pinned.clone(),
+            patched: patched.clone(),
+        };
+        let mut explanations = Vec::new();
+        if args.explain {
+            manifests.explain_excluded(&exclude, &args.pinned, &patched, &mut explanations);
+        }
+        let rust_version = match args.rust_version.as_deref() {
+            Some("auto") => Some(manifests.rust_version()?),
+            Some(version) => Some(version.parse()?),
+            None => None,
+        }
+        .map(|version| RustVersionCap {
+            version,
+            strict: msrv_strict,
+        });
         let existing_dependencies = manifests.get_dependencies(dependency, exclude)?;
+        let mut candidates = Candidates::new();
 
         // Update indices for any alternative registries, unless
         // we're offline.
         if !args.offline && std::env::var("CARGO_IS_TEST").is_err() {
             for registry_url in existing_dependencies
-                .0
-                .values()
-                .filter_map(|UpgradeMetadata { registry, .. }| registry.as_ref())
+                .1
+                .iter()
+                .filter_map(|(_, UpgradeMetadata { registry, .. })| registry.as_ref())
                 .collect::<HashSet<_>>()
             {
-                update_registry_index(
+                update_registry_index_if_stale(
                     &Url::parse(registry_url).map_err(|_| {
                         ErrorKind::CargoEditLib(::cargo_edit::ErrorKind::InvalidCargoConfig)
                     })?,
-                    false,
+                    quiet,
+                    index_max_age,
                 )?;
             }
         }
 
-        let upgraded_dependencies =
-            existing_dependencies.get_upgraded(allow_prerelease, &find(&manifest_path)?)?;
+        if !args.offline && !args.quiet {
+            existing_dependencies.warn_yanked(&find(&manifest_path)?);
+        }
+
+        let current: HashMap<Dependency, semver::Version> = existing_dependencies
+            .0
+            .iter()
+            .filter_map(|(dep, metadata)| Some((dep.clone(), metadata.current.clone()?)))
+            .collect();
+        let upgraded_dependencies = existing_dependencies.get_upgraded(
+            patch_revs,
+            allow_prerelease,
+            &args.prerelease_crates,
+            &find(&manifest_path)?,
+            rust_version.as_ref(),
+            if args.explain {
+                Some(&mut explanations)
+            } else {
+                None
+            },
+            if output == OutputFormat::Json {
+                Some(&mut candidates)
+            } else {
+                None
+            },
+        )?;
+        print_explanations(explanations);
+        if !dry_run {
+            confirm_upgrades(&current, &upgraded_dependencies, args.yes)?;
+        }
+
+        manifests.upgrade(
+            &upgraded_dependencies,
+            &scope,
+            &candidates,
+            dry_run,
+            skip_compatible,
+            output,
+            &args.columns,
+        )?
+    };
 
-        manifests.upgrade(&upgraded_dependencies, dry_run, skip_compatible)
+    Ok(if args.exit_code {
+        ExitCode::for_changes(changed, dry_run)
+    } else {
+        ExitCode::Success
+    })
+}
+
+/// Print the completion script of `cargo upgrade` for `shell`.
+fn print_completions(shell: Shell) -> Result<()> {
+    let dynamic = DynamicCompletions {
+        members: true,
+        dependencies: true,
+    };
+    write_completions(
+        Command::clap(),
+        "upgrade",
+        shell,
+        dynamic,
+        &mut io::stdout(),
+    )?;
+    Ok(())
+}
+
+/// Print the man page of `cargo upgrade`.
+fn print_man_page() -> Result<()> {
+    write_man_page(Command::clap(), "upgrade", &mut io::stdout())?;
+    Ok(())
+}
+
+/// Upgrade as the JSON request read from stdin says, see `--json-request`.
+fn handle_json_request() -> Result<ExitCode> {
+    let Command::Upgrade(mut args) = read_json_request("upgrade", &["dependencies"])?;
+    if args.no_proxy {
+        disable_proxy();
     }
+    args.apply_config()?;
+    process(args)
+}
+
+/// The exit code for `err`, telling network failures, unparsable manifests and the like apart.
+fn exit_code(err: &Error) -> ExitCode {
+    match err.kind() {
+        ErrorKind::CargoEditLib(kind) => ExitCode::for_error_kind(kind),
+        _ => None,
+    }
+    .unwrap_or_else(|| ExitCode::for_error(err))
 }
 
 fn main() {
     let args: Command = Command::from_args();
-    let Command::Upgrade(args) = args;
-
-    if let Err(err) = process(args) {
-        eprintln!("Command failed due to unhandled error: {}\n", err);
+    let Command::Upgrade(mut args) = args;
+    if args.no_proxy {
+        disable_proxy();
+    }
+    let json_request = args.json_request;
 
-        for e in err.iter().skip(1) {
-            eprintln!("Caused by: {}", e);
+    let result = match args.completions {
+        Some(shell) => print_completions(shell).map(|()| ExitCode::Success),
+        None if args.man => print_man_page().map(|()| ExitCode::Success),
+        None if args.json_request => handle_json_request(),
+        None => args.apply_config().and_then(|()| process(args)),
+    };
+    match result {
+        Ok(code) => process::exit(code.code()),
+        Err(err) if json_request => {
+            print!("{}", json_error_response("upgrade", &err, exit_code(&err)));
+            process::exit(exit_code(&err).code());
         }
+        Err(err) => {
+            eprintln!("Command failed due to unhandled error: {}\n", err);
 
-        if let Some(backtrace) = err.backtrace() {
-            eprintln!("Backtrace: {:?}", backtrace);
-        }
+            for e in err.iter().skip(1) {
+                eprintln!("Caused by: {}", e);
+            }
+
+            if let Some(backtrace) = err.backtrace() {
+                eprintln!("Backtrace: {:?}", backtrace);
+            }
 
-        process::exit(1);
+            process::exit(exit_code(&err).code());
+        }
     }
 }
@@ -0,0 +1,276 @@
+//! `cargo verify-manifest`
+#![warn(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+#[macro_use]
+extern crate error_chain;
+
+use cargo_edit::{
+    find, init_tracing, manifest_from_pkgid, set_cargo_flags, set_color_preference, set_verbosity,
+    stdout_color_choice, validate_metadata, write_completions, write_man_page, BrokenManifest,
+    Config, DynamicCompletions, ExitCode, Manifest, Verbosity, Workspace,
+};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use structopt::{
+    clap::{AppSettings, Shell},
+    StructOpt,
+};
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
+
+mod errors {
+    error_chain! {
+        errors {
+            /// The metadata of at least one manifest did not match its schema.
+            SchemaViolations(count: usize) {
+                description("Metadata does not match its schema")
+                display("Found {} metadata schema violation(s)", count)
+            }
+            /// At least one `[target]` table key is not a valid platform.
+            InvalidTargets(count: usize) {
+                description("Invalid target tables")
+                display("Found {} invalid target table(s)", count)
+            }
+        }
+        links {
+            CargoEditLib(::cargo_edit::Error, ::cargo_edit::ErrorKind);
+        }
+        foreign_links {
+            CargoMetadata(::failure::Compat<::cargo_metadata::Error>);
+            Io(::std::io::Error);
+        }
+    }
+}
+use crate::errors::*;
+
+#[derive(Debug, StructOpt)]
+#[structopt(bin_name = "cargo")]
+enum Command {
+    /// Validate custom metadata tables in a Cargo.toml manifest file against their schemas.
+    #[structopt(name = "verify-manifest")]
+    #[structopt(after_help = "\
+`[target]` tables are checked to be keyed by a target name or a valid `cfg(...)` expression.
+
+Schemas are declared in `[package.metadata.cargo-edit.schema]` or \
+`[workspace.metadata.cargo-edit.schema]`, keyed by the name of the metadata table they describe. \
+A schema is either an inline table or the path to a JSON file, relative to the manifest.
+
+`cargo add`, `cargo rm` and `cargo upgrade` run the same checks after editing a manifest and \
+print a warning for every violation.")]
+    VerifyManifest(Args),
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(setting = AppSettings::ColoredHelp)]
+struct Args {
+    /// Path to the manifest to verify.
+    #[structopt(long = "manifest-path", value_name = "path", conflicts_with = "pkgid")]
+    manifest_path: Option<PathBuf>,
+
+    /// Package id of the crate to verify.
+    #[structopt(
+        long = "package",
+        short = "p",
+        value_name = "pkgid",
+        conflicts_with = "workspace"
+    )]
+    pkgid: Option<String>,
+
+    /// Verify the workspace root and all workspace members.
+    #[structopt(long = "workspace", conflicts_with = "pkgid")]
+    workspace: bool,
+
+    /// Do not print any output in case of success.
+    #[structopt(long = "quiet", short = "q")]
+    quiet: bool,
+
+    /// Print the crates queried from the index and cache hits, and with `-vv` HTTP requests too.
+    #[structopt(
+        long = "verbose",
+        short = "v",
+        parse(from_occurrences),
+        conflicts_with = "quiet"
+    )]
+    verbose: u8,
+
+    /// Run without accessing the network
+    #[structopt(long = "offline")]
+    offline: bool,
+
+    /// Fail rather than change the lock file
+    #[structopt(long = "locked")]
+    locked: bool,
+
+    /// Both `--locked` and `--offline`
+    #[structopt(long = "frozen")]
+    frozen: bool,
+
+    /// Print the completion script of the command for the given shell, and exit.
+    #[structopt(long = "completions", value_name = "shell", possible_values = &Shell::variants())]
+    completions: Option<Shell>,
+
+    /// Print the man page of the command, in roff, and exit.
+    #[structopt(long = "man", conflicts_with = "completions")]
+    man: bool,
+}
+
+impl Args {
+    /// Fill in the settings not given on the command line from the configuration.
+    fn apply_config(&mut self) -> Result<()> {
+        let config = Config::load(&find(&self.manifest_path)?, "verify-manifest")?;
+        self.quiet = config.flag("quiet", self.quiet)?;
+        set_color_preference(config.color()?);
+        set_verbosity(Verbosity::new(self.quiet, self.verbose));
+        set_cargo_flags(self.offline, self.locked, self.frozen);
+        Ok(())
+    }
+}
+
+/// The manifests to check, and the member manifests skipped as they can't be parsed.
+type Manifests = (Vec<(PathBuf, Manifest)>, Vec<BrokenManifest>);
+
+/// Read the manifests selected by the arguments.
+fn get_manifests(args: &Args) -> Result<Manifests> {
+    let path = if let Some(ref pkgid) = args.pkgid {
+        manifest_from_pkgid(pkgid)?.manifest_path
+    } else if !args.workspace {
+        find(&args.manifest_path)?
+    } else {
+        let workspace = Workspace::load_partial(&find(&args.manifest_path)?)?;
+
+        // The workspace root is checked first, as it is not a package if the manifest is virtual.
+        let root = workspace.root_manifest();
+        let mut manifests: Vec<_> = workspace
+            .manifest(root)
+            .map(|manifest| (root.to_owned(), manifest.clone()))
+            .into_iter()
+            .collect();
+        manifests.extend(
+            workspace
+                .member_documents()
+                .filter(|(member, _)| member.manifest_path != root)
+                .map(|(member, manifest)| (member.manifest_path.clone(), manifest.clone())),
+        );
+        return Ok((manifests, workspace.broken_manifests().to_vec()));
+    };
+    let manifest = Manifest::open(&Some(path.clone()))?;
+    Ok((vec![(path, manifest)], Vec::new()))
+}
+
+fn print_ok(manifest_path: &Path) -> Result<()> {
+    let colorchoice = stdout_color_choice();
+    let mut output = StandardStream::stdout(colorchoice);
+    output.set_color(ColorSpec::new().set_fg(Some(Color::Green)).set_bold(true))?;
+    write!(output, "{:>12}", "Verified")?;
+    output.reset()?;
+    writeln!(output, " {}", manifest_path.display())?;
+    Ok(())
+}
+
+fn handle_verify(args: &Args) -> Result<()> {
+    init_tracing()?;
+
+    let mut count = 0;
+    let mut invalid_targets = 0;
+    let (manifests, broken) = get_manifests(args)?;
+    for (manifest_path, manifest) in manifests {
+        let violations = validate_metadata(&manifest, &manifest_path)?;
+        let invalid = manifest.invalid_target_keys();
+        if violations.is_empty() && invalid.is_empty() {
+            if !args.quiet {
+                print_ok(&manifest_path)?;
+            }
+            continue;
+        }
+
+        eprintln!("{}:", manifest_path.display());
+        for violation in &violations {
+            eprintln!("    {}", violation);
+        }
+        for err in &invalid {
+            eprintln!("    {}", err);
+        }
+        count += violations.len();
+        invalid_targets += invalid.len();
+    }
+
+    // A broken manifest can't be verified at all, which matters most.
+    if !broken.is_empty() {
+        return Err(cargo_edit::Error::from(cargo_edit::ErrorKind::BrokenManifests(broken)).into());
+    }
+    if count > 0 {
+        return Err(ErrorKind::SchemaViolations(count).into());
+    }
+    if invalid_targets > 0 {
+        return Err(ErrorKind::InvalidTargets(invalid_targets).into());
+    }
+    Ok(())
+}
+
+/// Print the completion script of `cargo verify-manifest` for `shell`.
+fn print_completions(shell: Shell) -> Result<()> {
+    let dynamic = DynamicCompletions {
+        members: true,
+        dependencies: false,
+    };
+    write_completions(
+        Command::clap(),
+        "verify-manifest",
+        shell,
+        dynamic,
+        &mut io::stdout(),
+    )?;
+    Ok(())
+}
+
+/// Print the man page of `cargo verify-manifest`.
+fn print_man_page() -> Result<()> {
+    write_man_page(Command::clap(), "verify-manifest", &mut io::stdout())?;
+    Ok(())
+}
+
+/// The exit code for `err`, telling network failures, unparsable manifests and the like apart.
+fn exit_code(err: &Error) -> ExitCode {
+    match err.kind() {
+        ErrorKind::CargoEditLib(kind) => ExitCode::for_error_kind(kind),
+        ErrorKind::SchemaViolations(..) | ErrorKind::InvalidTargets(..) => {
+            Some(ExitCode::PolicyViolation)
+        }
+        _ => None,
+    }
+    .unwrap_or_else(|| ExitCode::for_error(err))
+}
+
+fn main() {
+    let args: Command = Command::from_args();
+    let Command::VerifyManifest(mut args) = args;
+
+    let result = match args.completions {
+        Some(shell) => print_completions(shell),
+        None if args.man => print_man_page(),
+        None => args.apply_config().and_then(|()| handle_verify(&args)),
+    };
+    if let Err(err) = result {
+        eprintln!("Command failed due to unhandled error: {}\n", err);
+
+        for e in err.iter().skip(1) {
+            eprintln!("Caused by: {}", e);
+        }
+
+        if let Some(backtrace) = err.backtrace() {
+            eprintln!("Backtrace: {:?}", backtrace);
+        }
+
+        process::exit(exit_code(&err).code());
+    }
+}
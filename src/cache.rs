@@ -0,0 +1,615 @@
+//! A persistent cache of registry responses, shared between invocations.
+//!
+//! Entries live in `$CARGO_HOME/cargo-edit/cache/<registry>`: the body of each key in `entries`,
+//! and a small JSON file recording when it was fetched and the HTTP validators needed to
+//! revalidate it in `meta`. Keeping them apart means no key can be mistaken for the metadata of
+//! another, e.g. `config.json` for that of `config`.
+//!
+//! Several invocations may share a cache, e.g. parallel CI jobs on one runner, so every access
+//! takes an advisory lock on the `.lock` file of the directory: shared for reading an entry,
+//! exclusive for changing one. The OS releases the lock of a process which exits, so one still
+//! held after the lock timeout belongs to a live process, e.g. a hung one; rather than sharing the
+//! cache with it, the access fails with an error naming it. Exclusive holders write their PID into
+//! the lock file for that message.
+//!
+//! Offline, entries are used however old they are, unless bounded with [`set_offline_cache_ttl`].
+//! Entries are never evicted on their own; [`purge_caches`] removes the old ones.
+use crate::cancel::check_cancelled;
+use crate::errors::*;
+use crate::manifest_lock::holder;
+use crate::registry::{cache_path_from_url, cache_root};
+use crate::transaction::write_atomically;
+use std::fs::{self, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+/// How long cached entries are used without asking the registry again, unless overridden by
+/// `CARGO_EDIT_CACHE_TTL`.
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// The environment variable holding the cache TTL in seconds.
+const CACHE_TTL_ENV: &str = "CARGO_EDIT_CACHE_TTL";
+
+/// How long to wait for another invocation to release a cache before giving up.
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The file in a cache directory holding the lock.
+const LOCK_FILE: &str = ".lock";
+
+/// The directory in a cache directory holding the bodies of the entries.
+const ENTRIES_DIR: &str = "entries";
+
+/// The directory in a cache directory holding the metadata of the entries.
+const META_DIR: &str = "meta";
+
+/// How often to try again to take a lock held by someone else.
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The time-to-live of cached entries, read from `CARGO_EDIT_CACHE_TTL` (in seconds).
+pub fn cache_ttl() -> Duration {
+    std::env::var(CACHE_TTL_ENV)
+        .ok()
+        .and_then(|ttl| ttl.trim().parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_CACHE_TTL)
+}
+
+/// How old, in seconds, cached entries may be to be used offline.
+static OFFLINE_CACHE_TTL: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// Only use cached entries younger than `ttl` while offline for the rest of the process, e.g. for
+/// `--offline-cache-ttl`, rather than however old they are.
+pub fn set_offline_cache_ttl(ttl: Duration) {
+    OFFLINE_CACHE_TTL.store(ttl.as_secs(), Ordering::SeqCst);
+}
+
+/// How old cached entries may be to be used offline, unbounded unless set with
+/// [`set_offline_cache_ttl`].
+pub fn offline_cache_ttl() -> Duration {
+    Duration::from_secs(OFFLINE_CACHE_TTL.load(Ordering::SeqCst))
+}
+
+/// Remove the entries fetched more than `older_than` ago from the caches of every registry,
+/// returning how many were removed.
+pub fn purge_caches(older_than: Duration) -> Result<usize> {
+    let registries = match fs::read_dir(cache_root()?) {
+        Ok(registries) => registries,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let mut purged = 0;
+    for registry in registries {
+        let registry = registry?;
+        if registry.file_type()?.is_dir() {
+            purged += IndexCache::at(registry.path()).purge(older_than)?;
+        }
+    }
+    Ok(purged)
+}
+
+/// The bookkeeping stored next to each cached body.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct EntryMeta {
+    /// Seconds since the unix epoch.
+    fetched_at: u64,
+    #[serde(default, flatten)]
+    validators: Validators,
+}
+
+/// The HTTP validators a response was served with, to make conditional requests for it later.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    /// The `ETag` header, sent back as `If-None-Match`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    /// The `Last-Modified` header, sent back as `If-Modified-Since`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    /// Whether there is nothing to revalidate with.
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// A cached response together with the time it was fetched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    /// The cached content.
+    pub body: Vec<u8>,
+    /// When the content was fetched from the registry, or last confirmed to be unchanged.
+    pub fetched_at: SystemTime,
+    /// The validators the content was served with.
+    pub validators: Validators,
+}
+
+impl CacheEntry {
+    /// Time elapsed since the entry was fetched.
+    pub fn age(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(self.fetched_at)
+            .unwrap_or_default()
+    }
+
+    /// Whether the entry is younger than `ttl`.
+    pub fn is_fresh(&self, ttl: Duration) -> bool {
+        self.age() < ttl
+    }
+}
+
+/// The on-disk cache of one registry.
+#[derive(Debug, Clone)]
+pub struct IndexCache {
+    dir: PathBuf,
+    lock_timeout: Duration,
+}
+
+/// A lock on a cache directory, released when dropped.
+#[derive(Debug)]
+struct CacheLock {
+    /// `None` where the directory can't be locked, e.g. when it is read-only.
+    file: Option<fs::File>,
+    exclusive: bool,
+}
+
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        // The PID of an exclusive holder only names it while it holds the lock.
+        if let (Some(file), true) = (&self.file, self.exclusive) {
+            let _ = file.set_len(0);
+        }
+    }
+}
+
+impl IndexCache {
+    /// The cache of `registry`, in the cargo home directory.
+    pub fn for_registry(registry: &Url) -> Result<Self> {
+        Ok(Self::at(cache_path_from_url(registry)?))
+    }
+
+    /// A cache stored in `dir`.
+    pub fn at(dir: impl Into<PathBuf>) -> Self {
+        IndexCache {
+            dir: dir.into(),
+            lock_timeout: DEFAULT_LOCK_TIMEOUT,
+        }
+    }
+
+    /// Set how long to wait for another invocation to release the cache before failing with
+    /// [`ErrorKind::CacheLocked`].
+    pub fn set_lock_timeout(&mut self, lock_timeout: Duration) -> &mut Self {
+        self.lock_timeout = lock_timeout;
+        self
+    }
+
+    /// The directory holding the cached entries.
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Look up the entry stored under `key`, if any.
+    pub fn get(&self, key: &str) -> Result<Option<CacheEntry>> {
+        let _lock = self.lock(false)?;
+        let meta = match fs::read(self.meta_path(key)) {
+            Ok(meta) => meta,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        // A corrupted entry is as good as a missing one; it gets overwritten on the next insert.
+        let meta: EntryMeta = match serde_json::from_slice(&meta) {
+            Ok(meta) => meta,
+            Err(_) => {
+                event!(DEBUG, key, "corrupted cache metadata");
+                return Ok(None);
+            }
+        };
+        let body = match fs::read(self.body_path(key)) {
+            Ok(body) => body,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(CacheEntry {
+            body,
+            fetched_at: UNIX_EPOCH + Duration::from_secs(meta.fetched_at),
+            validators: meta.validators,
+        }))
+    }
+
+    /// Look up the entry stored under `key` if it is younger than `ttl`.
+    pub fn get_fresh(&self, key: &str, ttl: Duration) -> Result<Option<CacheEntry>> {
+        Ok(self.get(key)?.filter(|entry| entry.is_fresh(ttl)))
+    }
+
+    /// Store `body` under `key`, fetched now.
+    pub fn insert(&self, key: &str, body: &[u8]) -> Result<()> {
+        self.insert_with_validators(key, body, &Validators::default())
+    }
+
+    /// Store `body` under `key`, fetched now and served with `validators`.
+    pub fn insert_with_validators(
+        &self,
+        key: &str,
+        body: &[u8],
+        validators: &Validators,
+    ) -> Result<()> {
+        event!(TRACE, key, bytes = body.len(), "caching");
+        for dir in &[ENTRIES_DIR, META_DIR] {
+            let dir = self.dir.join(dir);
+            fs::create_dir_all(&dir)
+                .chain_err(|| format!("Failed to create cache directory {}", dir.display()))?;
+        }
+        let _lock = self.lock(true)?;
+        // The body is written first, so a reader never sees fresh metadata for a stale body.
+        write_atomically(&self.body_path(key), body)?;
+        self.write_meta(key, validators)
+    }
+
+    /// Mark the entry stored under `key` as fetched now, e.g. after the registry answered a
+    /// conditional request with `304 Not Modified`.
+    pub fn touch(&self, key: &str) -> Result<()> {
+        let _lock = self.lock(true)?;
+        let meta = fs::read(self.meta_path(key))?;
+        let meta: EntryMeta = serde_json::from_slice(&meta).unwrap_or_default();
+        self.write_meta(key, &meta.validators)
+    }
+
+    fn write_meta(&self, key: &str, validators: &Validators) -> Result<()> {
+        let fetched_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let meta = serde_json::to_vec(&EntryMeta {
+            fetched_at,
+            validators: validators.clone(),
+        })
+        .expect("cache metadata is always serializable");
+        write_atomically(&self.meta_path(key), &meta)
+    }
+
+    /// Remove the entry stored under `key`.
+    pub fn remove(&self, key: &str) -> Result<()> {
+        let _lock = self.lock(true)?;
+        for path in &[self.meta_path(key), self.body_path(key)] {
+            remove_file_if_exists(path)?;
+        }
+        Ok(())
+    }
+
+    /// Remove all entries.
+    pub fn clear(&self) -> Result<()> {
+        let _lock = self.lock(true)?;
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        // The lock file stays, as others may be waiting on it.
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_name() == LOCK_FILE {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                fs::remove_dir_all(entry.path())?;
+            } else {
+                fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the entries fetched more than `older_than` ago, and the corrupted ones, returning how
+    /// many were removed.
+    pub fn purge(&self, older_than: Duration) -> Result<usize> {
+        let _lock = self.lock(true)?;
+        let mut purged = 0;
+        for entry in read_dir_if_exists(&self.dir.join(ENTRIES_DIR))? {
+            let body_path = entry?.path();
+            let meta_path = match body_path.file_name().and_then(|name| name.to_str()) {
+                Some(name) => self.dir.join(META_DIR).join(format!("{}.json", name)),
+                None => continue,
+            };
+            let meta: Option<EntryMeta> = fs::read(&meta_path)
+                .ok()
+                .and_then(|meta| serde_json::from_slice(&meta).ok());
+            let fetched_at = meta.map(|meta| UNIX_EPOCH + Duration::from_secs(meta.fetched_at));
+            let age = fetched_at.map(|fetched_at| {
+                SystemTime::now()
+                    .duration_since(fetched_at)
+                    .unwrap_or_default()
+            });
+            if age.is_some_and(|age| age < older_than) {
+                continue;
+            }
+            event!(TRACE, path = %body_path.display(), "purging");
+            for path in &[&meta_path, &body_path] {
+                remove_file_if_exists(path)?;
+            }
+            purged += 1;
+        }
+        // Any other file isn't an entry, e.g. one left by a version keeping bodies and metadata
+        // side by side, and goes as well.
+        for entry in read_dir_if_exists(&self.dir)? {
+            let entry = entry?;
+            if entry.file_name() != LOCK_FILE && entry.file_type()?.is_file() {
+                remove_file_if_exists(&entry.path())?;
+            }
+        }
+        Ok(purged)
+    }
+
+    /// Lock the cache directory, `exclusive`ly to change entries, and otherwise shared with other
+    /// readers, waiting up to the lock timeout for another invocation holding it.
+    ///
+    /// Caches which can't be locked, because the directory doesn't exist or is read-only or the
+    /// file system has no locks, are used without one.
+    fn lock(&self, exclusive: bool) -> Result<CacheLock> {
+        let path = self.dir.join(LOCK_FILE);
+        let unlocked = CacheLock {
+            file: None,
+            exclusive,
+        };
+        let mut file = match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+        {
+            Ok(file) => file,
+            Err(_) => return Ok(unlocked),
+        };
+
+        let deadline = Instant::now() + self.lock_timeout;
+        let mut waiting = false;
+        loop {
+            let locked = if exclusive {
+                file.try_lock()
+            } else {
+                file.try_lock_shared()
+            };
+            match locked {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) => {}
+                Err(TryLockError::Error(_)) => return Ok(unlocked),
+            }
+            let holder = holder(&path);
+            if !waiting {
+                event!(DEBUG, path = %path.display(), ?holder, "waiting for the cache lock");
+                waiting = true;
+            }
+            check_cancelled()?;
+            if Instant::now() >= deadline {
+                return Err(ErrorKind::CacheLocked(self.dir.clone(), holder).into());
+            }
+            thread::sleep(LOCK_POLL_INTERVAL);
+        }
+
+        if exclusive {
+            // Best effort: the PID only makes the error of a waiting process more helpful.
+            let _ = file
+                .set_len(0)
+                .and_then(|()| write!(file, "{}", std::process::id()))
+                .and_then(|()| file.flush());
+        }
+        Ok(CacheLock {
+            file: Some(file),
+            exclusive,
+        })
+    }
+
+    fn body_path(&self, key: &str) -> PathBuf {
+        self.dir.join(ENTRIES_DIR).join(file_name(key))
+    }
+
+    fn meta_path(&self, key: &str) -> PathBuf {
+        self.dir
+            .join(META_DIR)
+            .join(format!("{}.json", file_name(key)))
+    }
+}
+
+/// The entries of `dir`, none if it doesn't exist.
+fn read_dir_if_exists(dir: &Path) -> Result<impl Iterator<Item = std::io::Result<fs::DirEntry>>> {
+    match fs::read_dir(dir) {
+        Ok(entries) => Ok(Some(entries).into_iter().flatten()),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None.into_iter().flatten()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn remove_file_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Keys are crate names or similar; anything which is not safe in a file name is replaced.
+fn file_name(key: &str) -> String {
+    key.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c.to_ascii_lowercase(),
+            _ => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_get() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = IndexCache::at(dir.path().join("cache"));
+        assert_eq!(cache.get("serde").unwrap(), None);
+
+        cache.insert("serde", b"{}").unwrap();
+        let entry = cache.get("serde").unwrap().unwrap();
+        assert_eq!(entry.body, b"{}");
+        assert!(entry.is_fresh(Duration::from_secs(60)));
+        assert!(!entry.is_fresh(Duration::from_secs(0)));
+        assert!(cache
+            .get_fresh("serde", Duration::from_secs(60))
+            .unwrap()
+            .is_some());
+        assert!(cache
+            .get_fresh("serde", Duration::from_secs(0))
+            .unwrap()
+            .is_none());
+
+        cache.remove("serde").unwrap();
+        assert_eq!(cache.get("serde").unwrap(), None);
+    }
+
+    #[test]
+    fn validators_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = IndexCache::at(dir.path());
+        let validators = Validators {
+            etag: Some("\"abc\"".to_owned()),
+            last_modified: None,
+        };
+        cache
+            .insert_with_validators("serde", b"{}", &validators)
+            .unwrap();
+        assert_eq!(cache.get("serde").unwrap().unwrap().validators, validators);
+
+        cache.touch("serde").unwrap();
+        let entry = cache.get("serde").unwrap().unwrap();
+        assert_eq!(entry.body, b"{}");
+        assert_eq!(entry.validators, validators);
+    }
+
+    #[test]
+    fn clear_removes_everything() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = IndexCache::at(dir.path().join("cache"));
+        cache.insert("a", b"1").unwrap();
+        cache.insert("b", b"2").unwrap();
+        cache.clear().unwrap();
+        assert_eq!(cache.get("a").unwrap(), None);
+        assert_eq!(cache.get("b").unwrap(), None);
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn purge_removes_old_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = IndexCache::at(dir.path());
+        cache.insert("serde", b"{}").unwrap();
+        cache.insert("log", b"{}").unwrap();
+        let old = serde_json::to_vec(&EntryMeta::default()).unwrap();
+        fs::write(cache.meta_path("log"), old).unwrap();
+        cache.insert("rand", b"{}").unwrap();
+        fs::write(cache.meta_path("rand"), "not json").unwrap();
+        fs::write(dir.path().join("serde.json"), "left over").unwrap();
+
+        assert_eq!(cache.purge(Duration::from_secs(60)).unwrap(), 2);
+        assert!(cache.get("serde").unwrap().is_some());
+        assert!(!cache.body_path("log").exists());
+        assert!(!cache.body_path("rand").exists());
+        assert!(!dir.path().join("serde.json").exists());
+        assert!(dir.path().join(LOCK_FILE).exists());
+        assert_eq!(cache.purge(Duration::from_secs(0)).unwrap(), 1);
+        assert_eq!(cache.get("serde").unwrap(), None);
+    }
+
+    #[test]
+    fn concurrent_inserts_keep_entries_consistent() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = IndexCache::at(dir.path());
+        thread::scope(|scope| {
+            for i in 0..8 {
+                let cache = &cache;
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        let body = i.to_string();
+                        let validators = Validators {
+                            etag: Some(body.clone()),
+                            last_modified: None,
+                        };
+                        cache
+                            .insert_with_validators("serde", body.as_bytes(), &validators)
+                            .unwrap();
+                        let entry = cache.get("serde").unwrap().unwrap();
+                        assert_eq!(entry.validators.etag.unwrap().as_bytes(), &entry.body[..]);
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn fails_on_held_locks() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = IndexCache::at(dir.path());
+        cache.set_lock_timeout(Duration::from_millis(50));
+        let mut hung = fs::File::create(dir.path().join(LOCK_FILE)).unwrap();
+        hung.lock().unwrap();
+        write!(hung, "4242").unwrap();
+
+        let err = cache.insert("serde", b"{}").unwrap_err();
+        match err.kind() {
+            ErrorKind::CacheLocked(path, holder) => {
+                assert_eq!(path, dir.path());
+                assert_eq!(*holder, Some(4242));
+            }
+            kind => panic!("unexpected error {:?}", kind),
+        }
+        assert!(cache.get("serde").is_err());
+
+        drop(hung);
+        cache.insert("serde", b"{}").unwrap();
+        assert_eq!(cache.get("serde").unwrap().unwrap().body, b"{}");
+    }
+
+    #[test]
+    fn exclusive_holders_name_themselves() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = IndexCache::at(dir.path());
+        let lock_path = dir.path().join(LOCK_FILE);
+
+        let lock = cache.lock(true).unwrap();
+        assert_eq!(holder(&lock_path), Some(std::process::id()));
+        drop(lock);
+        assert_eq!(holder(&lock_path), None);
+    }
+
+    #[test]
+    fn corrupted_metadata_is_a_miss() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = IndexCache::at(dir.path());
+        cache.insert("serde", b"{}").unwrap();
+        fs::write(cache.meta_path("serde"), "not json").unwrap();
+        assert_eq!(cache.get("serde").unwrap(), None);
+    }
+
+    #[test]
+    fn keys_are_not_mistaken_for_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = IndexCache::at(dir.path());
+        cache.insert("config", b"crate").unwrap();
+        cache.insert("config.json", b"{\"dl\":\"x\"}").unwrap();
+        assert_eq!(cache.get("config").unwrap().unwrap().body, b"crate");
+        assert_eq!(
+            cache.get("config.json").unwrap().unwrap().body,
+            b"{\"dl\":\"x\"}"
+        );
+
+        assert_eq!(cache.purge(Duration::from_secs(60)).unwrap(), 0);
+        assert!(cache.get("config").unwrap().is_some());
+        assert!(cache.get("config.json").unwrap().is_some());
+        cache.remove("config").unwrap();
+        assert_eq!(cache.get("config").unwrap(), None);
+        assert!(cache.get("config.json").unwrap().is_some());
+    }
+}
@@ -0,0 +1,67 @@
+//! Stopping long-running work, e.g. on Ctrl-C, without leaving anything half-done.
+//!
+//! Cancelling only raises a flag. Registry queries and network requests check it between steps
+//! and give up with [`ErrorKind::Interrupted`], and [`ManifestTransaction::commit`] checks it
+//! before and while replacing manifests, restoring those already replaced.
+//!
+//! [`ManifestTransaction::commit`]: crate::ManifestTransaction::commit
+#[cfg(feature = "index")]
+use crate::errors::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "fetch")]
+use {
+    std::thread,
+    std::time::{Duration, Instant},
+};
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// The longest a sleeping thread goes without noticing a cancellation.
+#[cfg(feature = "fetch")]
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Ask all running operations to stop as soon as they safely can.
+pub fn cancel() {
+    CANCELLED.store(true, Ordering::SeqCst);
+}
+
+/// Whether [`cancel`] was called.
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// Fail with [`ErrorKind::Interrupted`] if [`cancel`] was called.
+#[cfg(feature = "index")]
+pub(crate) fn check_cancelled() -> Result<()> {
+    if is_cancelled() {
+        Err(ErrorKind::Interrupted.into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Sleep for `duration`, or until [`cancel`] is called.
+#[cfg(feature = "fetch")]
+pub(crate) fn sleep(duration: Duration) -> Result<()> {
+    let until = Instant::now() + duration;
+    loop {
+        check_cancelled()?;
+        let now = Instant::now();
+        if now >= until {
+            return Ok(());
+        }
+        thread::sleep((until - now).min(POLL_INTERVAL));
+    }
+}
+
+/// Cancel on Ctrl-C instead of exiting right away; a second Ctrl-C exits with status 130 without
+/// waiting any longer.
+#[cfg(feature = "ctrlc")]
+pub fn cancel_on_ctrl_c() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if CANCELLED.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+    })
+    .chain_err(|| "Failed to install the Ctrl-C handler")
+}
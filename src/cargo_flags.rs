@@ -0,0 +1,76 @@
+//! Cargo's `--offline`, `--locked` and `--frozen`, as switches for the rest of the process.
+//!
+//! Offline, every request to a registry or another server fails with [`ErrorKind::Offline`]
+//! instead of being sent, git indices are used as they are, and index entries cached earlier are
+//! used however old they are, unless bounded with [`set_offline_cache_ttl`](crate::set_offline_cache_ttl). Locked, the lock file is never written, and `cargo` is run with
+//! `--locked` so that it fails rather than change it. Frozen is both.
+use crate::errors::*;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+static LOCKED: AtomicBool = AtomicBool::new(false);
+
+/// Never access the network for the rest of the process, e.g. for `--offline`.
+pub fn set_offline() {
+    OFFLINE.store(true, Ordering::SeqCst);
+}
+
+/// Never change the lock file for the rest of the process, e.g. for `--locked`.
+pub fn set_locked() {
+    LOCKED.store(true, Ordering::SeqCst);
+}
+
+/// Apply the `--offline`, `--locked` and `--frozen` flags of a command; `frozen` implies both
+/// others.
+pub fn set_cargo_flags(offline: bool, locked: bool, frozen: bool) {
+    if offline || frozen {
+        set_offline();
+    }
+    if locked || frozen {
+        set_locked();
+    }
+}
+
+/// Whether the network may not be accessed.
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::SeqCst)
+}
+
+/// Whether the lock file may not be changed.
+pub fn is_locked() -> bool {
+    LOCKED.load(Ordering::SeqCst)
+}
+
+/// Fail with [`ErrorKind::Offline`] if the network may not be accessed to reach `url`.
+pub fn ensure_online(url: &str) -> Result<()> {
+    if is_offline() {
+        return Err(ErrorKind::Offline(url.to_owned()).into());
+    }
+    Ok(())
+}
+
+/// Fail with [`ErrorKind::LockfileLocked`] if the lock file at `path` may not be changed.
+pub(crate) fn ensure_unlocked(path: &Path) -> Result<()> {
+    if is_locked() {
+        return Err(ErrorKind::LockfileLocked(path.to_owned()).into());
+    }
+    Ok(())
+}
+
+/// The flags to run `cargo` with, so that it follows the same rules, merged with the flags
+/// `options` already has.
+pub fn cargo_options(mut options: Vec<String>) -> Vec<String> {
+    let mut add = |flag: &str| {
+        if !options.iter().any(|option| option == flag) {
+            options.push(flag.to_owned());
+        }
+    };
+    if is_offline() {
+        add("--offline");
+    }
+    if is_locked() {
+        add("--locked");
+    }
+    options
+}
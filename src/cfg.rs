@@ -0,0 +1,615 @@
+//! The platforms of `[target]` tables: target triples and `cfg(...)` expressions.
+use crate::errors::*;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::{CharIndices, FromStr};
+
+/// A single configuration option, like `unix` or `target_os = "linux"`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Cfg {
+    /// A name on its own, like `unix`
+    Name(String),
+    /// A key with a value, like `target_os = "linux"`
+    KeyPair(String, String),
+}
+
+/// A `cfg` expression, the part between the parentheses of `cfg(...)`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CfgExpr {
+    /// `not(...)`
+    Not(Box<CfgExpr>),
+    /// `all(...)`, true if all of them are, including when there are none
+    All(Vec<CfgExpr>),
+    /// `any(...)`, true if any of them is, so false when there are none
+    Any(Vec<CfgExpr>),
+    /// A configuration option
+    Value(Cfg),
+}
+
+/// The key of a `[target]` table: either a target triple (or the name of a custom target
+/// specification), or a `cfg(...)` expression.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::Platform;
+///
+///   let platform: Platform = r#"cfg(all( any(unix) ,target_arch="x86_64"))"#.parse().unwrap();
+///   assert_eq!(platform.clone().normalize().to_string(), r#"cfg(all(unix, target_arch = "x86_64"))"#);
+///   assert!(platform.matches_triple("x86_64-unknown-linux-gnu"));
+///   assert!(!platform.matches_triple("x86_64-pc-windows-msvc"));
+///
+///   assert!("cfg(unix".parse::<Platform>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Platform {
+    /// A target triple, like `x86_64-unknown-linux-gnu`
+    Name(String),
+    /// A `cfg(...)` expression
+    Cfg(CfgExpr),
+}
+
+impl Cfg {
+    fn name(name: &str) -> Self {
+        Cfg::Name(name.to_owned())
+    }
+
+    fn key_pair(key: &str, value: &str) -> Self {
+        Cfg::KeyPair(key.to_owned(), value.to_owned())
+    }
+}
+
+impl CfgExpr {
+    /// Whether the expression holds for a target with the configuration options `cfgs`.
+    pub fn matches(&self, cfgs: &[Cfg]) -> bool {
+        match self {
+            CfgExpr::Not(expr) => !expr.matches(cfgs),
+            CfgExpr::All(exprs) => exprs.iter().all(|e| e.matches(cfgs)),
+            CfgExpr::Any(exprs) => exprs.iter().any(|e| e.matches(cfgs)),
+            CfgExpr::Value(cfg) => cfgs.contains(cfg),
+        }
+    }
+
+    /// An equivalent expression in its simplest spelling.
+    ///
+    /// Nested `all` and `any` are flattened into their parents, those with a single operand are
+    /// replaced by it, repeated operands are dropped and double negations removed.
+    pub fn normalize(self) -> Self {
+        match self {
+            CfgExpr::Not(expr) => match expr.normalize() {
+                CfgExpr::Not(inner) => *inner,
+                expr => CfgExpr::Not(Box::new(expr)),
+            },
+            CfgExpr::All(exprs) => Self::normalize_list(exprs, CfgExpr::All, |e| match e {
+                CfgExpr::All(exprs) => Ok(exprs),
+                e => Err(e),
+            }),
+            CfgExpr::Any(exprs) => Self::normalize_list(exprs, CfgExpr::Any, |e| match e {
+                CfgExpr::Any(exprs) => Ok(exprs),
+                e => Err(e),
+            }),
+            value @ CfgExpr::Value(_) => value,
+        }
+    }
+
+    fn normalize_list(
+        exprs: Vec<CfgExpr>,
+        wrap: fn(Vec<CfgExpr>) -> CfgExpr,
+        unwrap: fn(CfgExpr) -> std::result::Result<Vec<CfgExpr>, CfgExpr>,
+    ) -> Self {
+        let mut operands: Vec<CfgExpr> = Vec::new();
+        for expr in exprs {
+            let nested = match unwrap(expr.normalize()) {
+                Ok(nested) => nested,
+                Err(expr) => vec![expr],
+            };
+            for expr in nested {
+                if !operands.contains(&expr) {
+                    operands.push(expr);
+                }
+            }
+        }
+        if operands.len() == 1 {
+            operands.remove(0)
+        } else {
+            wrap(operands)
+        }
+    }
+}
+
+impl Platform {
+    /// Parse a platform given on the command line, where `cfg` strings may be quoted with `'`
+    /// to spare escaping `"` from the shell, like `cfg(target_os='linux')`.
+    ///
+    /// The platform is written with `"` like any other, as cargo only accepts those.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    ///   use cargo_edit::Platform;
+    ///
+    ///   let platform = Platform::parse_argument("cfg(target_os='linux')").unwrap();
+    ///   assert_eq!(platform.to_string(), r#"cfg(target_os = "linux")"#);
+    ///   assert!("cfg(target_os='linux')".parse::<Platform>().is_err());
+    /// ```
+    pub fn parse_argument(s: &str) -> Result<Self> {
+        parse_platform(s, true)
+    }
+
+    /// The same platform, with any `cfg` expression normalized as by [`CfgExpr::normalize`].
+    pub fn normalize(self) -> Self {
+        match self {
+            Platform::Cfg(expr) => Platform::Cfg(expr.normalize()),
+            name => name,
+        }
+    }
+
+    /// Whether the platform applies when building for `triple`, whose configuration options are
+    /// `cfgs`.
+    pub fn matches(&self, triple: &str, cfgs: &[Cfg]) -> bool {
+        match self {
+            Platform::Name(name) => name == triple,
+            Platform::Cfg(expr) => expr.matches(cfgs),
+        }
+    }
+
+    /// Whether the platform applies when building for `triple`, with the configuration options
+    /// guessed by [`target_cfgs`].
+    pub fn matches_triple(&self, triple: &str) -> bool {
+        self.matches(triple, &target_cfgs(triple))
+    }
+}
+
+/// The configuration options `rustc` sets for the target `triple`, as far as they follow from the
+/// triple itself.
+///
+/// This covers `target_arch`, `target_os`, `target_family` (and `unix`/`windows`), `target_env`,
+/// `target_vendor`, `target_pointer_width` and `target_endian` for the usual triples; options
+/// depending on the compiler or build, like `target_feature` or `debug_assertions`, are not
+/// included. Ask `rustc --print cfg --target <triple>` for the full set.
+pub fn target_cfgs(triple: &str) -> Vec<Cfg> {
+    let parts: Vec<&str> = triple.split('-').collect();
+    let (arch, vendor, os, env) = match parts.as_slice() {
+        [arch, vendor, os, env, ..] => (*arch, *vendor, *os, *env),
+        // `aarch64-linux-android` leaves out the vendor.
+        [arch, os, env] if *os == "linux" => (*arch, "unknown", *os, *env),
+        [arch, vendor, os] => (*arch, *vendor, *os, ""),
+        _ => return Vec::new(),
+    };
+
+    let arch = normalize_arch(arch);
+    let (os, env) = match (os, env) {
+        ("darwin", _) => ("macos", ""),
+        ("linux", env) if env.starts_with("android") => ("android", ""),
+        (os, env) => (os, env),
+    };
+    let env = ["gnu", "musl", "msvc", "uclibc", "sgx"]
+        .iter()
+        .find(|e| env.starts_with(*e))
+        .copied()
+        .unwrap_or("");
+
+    let mut cfgs = Vec::new();
+    let family = match os {
+        "windows" => Some("windows"),
+        "linux" | "android" | "macos" | "ios" | "tvos" | "watchos" | "freebsd" | "netbsd"
+        | "openbsd" | "dragonfly" | "solaris" | "illumos" | "haiku" | "fuchsia" | "redox"
+        | "emscripten" => Some("unix"),
+        _ => None,
+    };
+    if let Some(family) = family {
+        cfgs.push(Cfg::name(family));
+        cfgs.push(Cfg::key_pair("target_family", family));
+    }
+    if arch.starts_with("wasm") {
+        cfgs.push(Cfg::key_pair("target_family", "wasm"));
+    }
+    cfgs.push(Cfg::key_pair("target_arch", arch));
+    cfgs.push(Cfg::key_pair("target_os", os));
+    cfgs.push(Cfg::key_pair("target_env", env));
+    cfgs.push(Cfg::key_pair("target_vendor", vendor));
+    cfgs.push(Cfg::key_pair("target_pointer_width", pointer_width(arch)));
+    cfgs.push(Cfg::key_pair("target_endian", endian(triple)));
+    cfgs
+}
+
+/// The `target_arch` of the architecture part of a triple, like `arm` for `armv7`.
+fn normalize_arch(arch: &str) -> &str {
+    match arch {
+        "i386" | "i586" | "i686" => "x86",
+        "arm64" => "aarch64",
+        "powerpc64le" => "powerpc64",
+        "mipsel" => "mips",
+        "mips64el" => "mips64",
+        "sparcv9" => "sparc64",
+        _ if arch.starts_with("armeb") || arch.starts_with("thumb") || arch.starts_with("arm") => {
+            "arm"
+        }
+        _ if arch.starts_with("riscv32") => "riscv32",
+        _ if arch.starts_with("riscv64") => "riscv64",
+        _ => arch,
+    }
+}
+
+fn pointer_width(arch: &str) -> &'static str {
+    match arch {
+        "x86_64" | "aarch64" | "wasm64" | "riscv64" | "powerpc64" | "mips64" | "s390x"
+        | "sparc64" | "loongarch64" => "64",
+        "avr" | "msp430" => "16",
+        _ => "32",
+    }
+}
+
+fn endian(triple: &str) -> &'static str {
+    let arch = triple.split('-').next().unwrap_or_default();
+    let big = match arch {
+        "powerpc" | "powerpc64" | "mips" | "mips64" | "s390x" | "sparc" | "sparc64" | "sparcv9" => {
+            true
+        }
+        _ => arch.starts_with("armeb") || arch.ends_with("_be"),
+    };
+    if big {
+        "big"
+    } else {
+        "little"
+    }
+}
+
+impl FromStr for CfgExpr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_cfg_expr(s, false)
+    }
+}
+
+fn parse_cfg_expr(s: &str, single_quotes: bool) -> Result<CfgExpr> {
+    let mut parser = Parser::new(s, single_quotes);
+    let expr = parser.expr()?;
+    match parser.next()? {
+        None => Ok(expr),
+        Some(token) => Err(parser.error(format!("unexpected {}", token))),
+    }
+}
+
+impl FromStr for Platform {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_platform(s, false)
+    }
+}
+
+fn parse_platform(s: &str, single_quotes: bool) -> Result<Platform> {
+    let s = s.trim();
+    if let Some(inner) = s.strip_prefix("cfg(") {
+        let inner = inner
+            .strip_suffix(')')
+            .ok_or_else(|| invalid_cfg(s, "missing `)`".to_owned()))?;
+        let expr = parse_cfg_expr(inner, single_quotes)
+            .map_err(|err: Error| invalid_cfg(s, cfg_reason(err)))?;
+        return Ok(Platform::Cfg(expr));
+    }
+    if s.is_empty() {
+        return Err(invalid_cfg(s, "the target may not be empty".to_owned()));
+    }
+    if let Some(c) = s
+        .chars()
+        .find(|c| !(c.is_alphanumeric() || ['_', '-', '.'].contains(c)))
+    {
+        return Err(invalid_cfg(
+            s,
+            format!(
+                "unexpected character `{}` in target name; expected a target triple or `cfg(...)`",
+                c
+            ),
+        ));
+    }
+    Ok(Platform::Name(s.to_owned()))
+}
+
+fn invalid_cfg(expr: &str, reason: String) -> Error {
+    ErrorKind::InvalidCfg(expr.to_owned(), reason).into()
+}
+
+/// The reason a nested `cfg` expression failed to parse, to report it against the whole target.
+fn cfg_reason(err: Error) -> String {
+    match err.kind() {
+        ErrorKind::InvalidCfg(_, reason) => reason.clone(),
+        _ => err.to_string(),
+    }
+}
+
+impl fmt::Display for Cfg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cfg::Name(name) => write!(f, "{}", name),
+            Cfg::KeyPair(key, value) => write!(f, "{} = \"{}\"", key, value),
+        }
+    }
+}
+
+impl fmt::Display for CfgExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn list(f: &mut fmt::Formatter<'_>, name: &str, exprs: &[CfgExpr]) -> fmt::Result {
+            write!(f, "{}(", name)?;
+            for (i, expr) in exprs.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", expr)?;
+            }
+            write!(f, ")")
+        }
+
+        match self {
+            CfgExpr::Not(expr) => write!(f, "not({})", expr),
+            CfgExpr::All(exprs) => list(f, "all", exprs),
+            CfgExpr::Any(exprs) => list(f, "any", exprs),
+            CfgExpr::Value(cfg) => write!(f, "{}", cfg),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Platform::Name(name) => write!(f, "{}", name),
+            Platform::Cfg(expr) => write!(f, "cfg({})", expr),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+    Ident(&'a str),
+    String(&'a str),
+    LeftParen,
+    RightParen,
+    Comma,
+    Equals,
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "`{}`", ident),
+            Token::String(s) => write!(f, "\"{}\"", s),
+            Token::LeftParen => write!(f, "`(`"),
+            Token::RightParen => write!(f, "`)`"),
+            Token::Comma => write!(f, "`,`"),
+            Token::Equals => write!(f, "`=`"),
+        }
+    }
+}
+
+/// A recursive descent parser for `cfg` expressions, following the grammar `rustc` accepts.
+struct Parser<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    peeked: Option<Token<'a>>,
+    /// Whether strings may be quoted with `'` as well as `"`
+    single_quotes: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str, single_quotes: bool) -> Self {
+        Parser {
+            input,
+            chars: input.char_indices().peekable(),
+            peeked: None,
+            single_quotes,
+        }
+    }
+
+    fn error(&self, reason: String) -> Error {
+        invalid_cfg(self.input, reason)
+    }
+
+    fn expr(&mut self) -> Result<CfgExpr> {
+        let ident = match self.next()? {
+            Some(Token::Ident(ident)) => ident,
+            Some(token) => {
+                return Err(self.error(format!("expected an identifier, found {}", token)))
+            }
+            None => return Err(self.error("expected an identifier".to_owned())),
+        };
+        match (ident, self.peek()?) {
+            ("all", Some(Token::LeftParen)) => Ok(CfgExpr::All(self.list()?)),
+            ("any", Some(Token::LeftParen)) => Ok(CfgExpr::Any(self.list()?)),
+            ("not", Some(Token::LeftParen)) => {
+                let mut exprs = self.list()?;
+                if exprs.len() != 1 {
+                    return Err(self.error("`not` takes exactly one expression".to_owned()));
+                }
+                Ok(CfgExpr::Not(Box::new(exprs.remove(0))))
+            }
+            (_, Some(Token::Equals)) => {
+                self.next()?;
+                match self.next()? {
+                    Some(Token::String(value)) => Ok(CfgExpr::Value(Cfg::key_pair(ident, value))),
+                    _ => Err(self.error(format!("expected a string after `{} =`", ident))),
+                }
+            }
+            (_, Some(Token::LeftParen)) => Err(self.error(format!(
+                "unknown operator `{}`; expected `all`, `any` or `not`",
+                ident
+            ))),
+            _ => Ok(CfgExpr::Value(Cfg::name(ident))),
+        }
+    }
+
+    /// A parenthesized, comma separated list of expressions, allowing a trailing comma.
+    fn list(&mut self) -> Result<Vec<CfgExpr>> {
+        self.next()?;
+        let mut exprs = Vec::new();
+        loop {
+            if self.peek()? == Some(&Token::RightParen) {
+                self.next()?;
+                return Ok(exprs);
+            }
+            exprs.push(self.expr()?);
+            match self.next()? {
+                Some(Token::Comma) => {}
+                Some(Token::RightParen) => return Ok(exprs),
+                Some(token) => {
+                    return Err(self.error(format!("expected `,` or `)`, found {}", token)))
+                }
+                None => return Err(self.error("missing `)`".to_owned())),
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<&Token<'a>>> {
+        if self.peeked.is_none() {
+            self.peeked = self.token()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    fn next(&mut self) -> Result<Option<Token<'a>>> {
+        match self.peeked.take() {
+            Some(token) => Ok(Some(token)),
+            None => self.token(),
+        }
+    }
+
+    fn token(&mut self) -> Result<Option<Token<'a>>> {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let (start, c) = match self.chars.next() {
+            Some(next) => next,
+            None => return Ok(None),
+        };
+        let token = match c {
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            ',' => Token::Comma,
+            '=' => Token::Equals,
+            '"' => {
+                let end = self
+                    .chars
+                    .find(|(_, c)| *c == '"')
+                    .ok_or_else(|| self.error("unterminated string".to_owned()))?
+                    .0;
+                Token::String(&self.input[start + 1..end])
+            }
+            '\'' if self.single_quotes => {
+                let end = self
+                    .chars
+                    .find(|(_, c)| *c == '\'')
+                    .ok_or_else(|| self.error("unterminated string".to_owned()))?
+                    .0;
+                let value = &self.input[start + 1..end];
+                if value.contains('"') {
+                    return Err(self.error("a string may not contain `\"`".to_owned()));
+                }
+                Token::String(value)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) = self
+                    .chars
+                    .next_if(|(_, c)| c.is_alphanumeric() || *c == '_')
+                {
+                    end = i + c.len_utf8();
+                }
+                Token::Ident(&self.input[start..end])
+            }
+            c => return Err(self.error(format!("unexpected character `{}`", c))),
+        };
+        Ok(Some(token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn platform(s: &str) -> Platform {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_and_prints() {
+        assert_eq!(
+            platform("x86_64-unknown-linux-gnu"),
+            Platform::Name("x86_64-unknown-linux-gnu".to_owned())
+        );
+        assert_eq!(platform("windows.json").to_string(), "windows.json");
+        assert_eq!(
+            platform(r#"cfg(any(windows,all(target_os="linux" , not(target_env = "musl")),))"#)
+                .to_string(),
+            r#"cfg(any(windows, all(target_os = "linux", not(target_env = "musl"))))"#
+        );
+        assert_eq!(platform("cfg(all())").to_string(), "cfg(all())");
+
+        for invalid in &[
+            "",
+            "cfg(unix",
+            "cfg()",
+            "cfg(unix windows)",
+            "cfg(not(unix, windows))",
+            "cfg(foo(unix))",
+            r#"cfg(target_os = "linux)"#,
+            "cfg(target_os = linux)",
+            "x86 64",
+        ] {
+            assert!(invalid.parse::<Platform>().is_err(), "{}", invalid);
+        }
+        let err = "cfg(unix windows)".parse::<Platform>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid target `cfg(unix windows)`: unexpected `windows`"
+        );
+    }
+
+    #[test]
+    fn parses_arguments_with_single_quotes() {
+        let argument = |s| Platform::parse_argument(s).map(|p| p.to_string());
+        assert_eq!(
+            argument("cfg(all(unix, target_env='gnu'))").unwrap(),
+            r#"cfg(all(unix, target_env = "gnu"))"#
+        );
+        assert_eq!(
+            argument(r#"cfg(target_os = "linux")"#).unwrap(),
+            r#"cfg(target_os = "linux")"#
+        );
+        assert!(argument(r#"cfg(target_os = 'li"nux')"#).is_err());
+        assert!(argument("cfg(target_os = 'linux)").is_err());
+    }
+
+    #[test]
+    fn normalizes() {
+        let normalized = |s| platform(s).normalize().to_string();
+        assert_eq!(normalized("cfg(all(unix))"), "cfg(unix)");
+        assert_eq!(normalized("cfg(not(not(unix)))"), "cfg(unix)");
+        assert_eq!(
+            normalized("cfg(any(unix, any(windows, unix), all(any(target_os = \"wasi\"))))"),
+            r#"cfg(any(unix, windows, target_os = "wasi"))"#
+        );
+        assert_eq!(normalized("cfg(all(any(), all()))"), "cfg(any())");
+    }
+
+    #[test]
+    fn evaluates_against_triples() {
+        let linux = "x86_64-unknown-linux-gnu";
+        assert!(platform("cfg(unix)").matches_triple(linux));
+        assert!(platform(r#"cfg(target_pointer_width = "64")"#).matches_triple(linux));
+        assert!(platform(linux).matches_triple(linux));
+        assert!(!platform("cfg(windows)").matches_triple(linux));
+        assert!(platform("cfg(all())").matches_triple(linux));
+        assert!(!platform("cfg(any())").matches_triple(linux));
+
+        assert!(platform(r#"cfg(all(windows, target_env = "msvc"))"#)
+            .matches_triple("x86_64-pc-windows-msvc"));
+        assert!(platform(r#"cfg(target_os = "macos")"#).matches_triple("aarch64-apple-darwin"));
+        assert!(platform(r#"cfg(target_os = "android")"#).matches_triple("aarch64-linux-android"));
+        assert!(
+            platform(r#"cfg(all(target_arch = "arm", target_env = "gnu"))"#)
+                .matches_triple("armv7-unknown-linux-gnueabihf")
+        );
+        assert!(platform(r#"cfg(all(target_family = "wasm", not(unix)))"#)
+            .matches_triple("wasm32-unknown-unknown"));
+        assert!(platform(r#"cfg(target_endian = "big")"#).matches_triple("s390x-unknown-linux-gnu"));
+    }
+}
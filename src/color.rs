@@ -0,0 +1,103 @@
+//! When the binaries color their output, as configured by `color`.
+
+use crate::errors::*;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use termcolor::ColorChoice;
+
+/// When to color output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorPreference {
+    /// Color output written to a terminal
+    #[default]
+    Auto,
+    /// Always color output, e.g. for a pager
+    Always,
+    /// Never color output
+    Never,
+}
+
+impl ColorPreference {
+    /// The names of the preferences, as accepted by `FromStr` and the configuration.
+    pub const NAMES: &'static [&'static str] = &["auto", "always", "never"];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ColorPreference::Auto => "auto",
+            ColorPreference::Always => "always",
+            ColorPreference::Never => "never",
+        }
+    }
+}
+
+impl FromStr for ColorPreference {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(ColorPreference::Auto),
+            "always" => Ok(ColorPreference::Always),
+            "never" => Ok(ColorPreference::Never),
+            _ => Err(format!("Unknown color preference `{}`", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for ColorPreference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+static PREFERENCE: AtomicU8 = AtomicU8::new(0);
+
+/// Set when output is colored, for the rest of the process.
+pub fn set_color_preference(preference: ColorPreference) {
+    let value = match preference {
+        ColorPreference::Auto => 0,
+        ColorPreference::Always => 1,
+        ColorPreference::Never => 2,
+    };
+    PREFERENCE.store(value, Ordering::Relaxed);
+}
+
+/// When output is colored, `Auto` unless set otherwise.
+pub fn color_preference() -> ColorPreference {
+    match PREFERENCE.load(Ordering::Relaxed) {
+        1 => ColorPreference::Always,
+        2 => ColorPreference::Never,
+        _ => ColorPreference::Auto,
+    }
+}
+
+/// How to color output written to stdout.
+#[cfg(feature = "atty")]
+pub fn stdout_color_choice() -> ColorChoice {
+    color_choice_or(tty_choice(atty::Stream::Stdout))
+}
+
+/// How to color output written to stderr.
+#[cfg(feature = "atty")]
+pub fn stderr_color_choice() -> ColorChoice {
+    color_choice_or(tty_choice(atty::Stream::Stderr))
+}
+
+#[cfg(feature = "atty")]
+fn tty_choice(stream: atty::Stream) -> ColorChoice {
+    if atty::is(stream) {
+        ColorChoice::Auto
+    } else {
+        ColorChoice::Never
+    }
+}
+
+/// How to color output, with `auto` when it is up to the caller.
+pub(crate) fn color_choice_or(auto: ColorChoice) -> ColorChoice {
+    match color_preference() {
+        ColorPreference::Always => ColorChoice::Always,
+        ColorPreference::Never => ColorChoice::Never,
+        ColorPreference::Auto => auto,
+    }
+}
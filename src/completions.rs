@@ -0,0 +1,399 @@
+//! Shell completion scripts for the subcommands.
+//!
+//! The scripts are generated by clap from the arguments of a subcommand, so they never fall out of
+//! step with its flags. Workspace members and dependency names depend on the manifest at hand, so
+//! for those the bash, zsh and fish scripts ask `cargo edit complete` while completing.
+use crate::errors::*;
+use crate::manifest::{find, Manifest};
+use crate::workspace::Workspace;
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::clap::{App, Shell};
+
+/// Arguments completed with names from the manifest at hand.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DynamicCompletions {
+    /// Complete `-p`/`--package` with the names of the workspace members
+    pub members: bool,
+    /// Complete positional arguments and `--exclude` with the dependencies of the manifest
+    pub dependencies: bool,
+}
+
+/// The names `cargo edit complete` lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionNames {
+    /// The packages of the workspace
+    Members,
+    /// The dependencies of the manifest, in any table
+    Dependencies,
+}
+
+impl CompletionNames {
+    /// The names of the lists, as accepted by `cargo edit complete`.
+    pub const NAMES: &'static [&'static str] = &["members", "dependencies"];
+
+    /// The names found from the manifest at `manifest_path`, or the one found from the working
+    /// directory, sorted and without duplicates.
+    pub fn list(self, manifest_path: &Option<PathBuf>) -> Result<Vec<String>> {
+        let manifest_path = find(manifest_path)?;
+        let names: BTreeSet<String> = match self {
+            CompletionNames::Members => Workspace::load(&manifest_path)?
+                .members()
+                .iter()
+                .map(|member| member.name.clone())
+                .collect(),
+            CompletionNames::Dependencies => Manifest::open(&Some(manifest_path))?
+                .dependency_entries()
+                .map(|entry| entry.key)
+                .collect(),
+        };
+        Ok(names.into_iter().collect())
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            CompletionNames::Members => "members",
+            CompletionNames::Dependencies => "dependencies",
+        }
+    }
+}
+
+impl FromStr for CompletionNames {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "members" => Ok(CompletionNames::Members),
+            "dependencies" => Ok(CompletionNames::Dependencies),
+            _ => Err(format!("Unknown completion names `{}`", s).into()),
+        }
+    }
+}
+
+/// Write the completion script of `cargo <subcommand>` for `shell`.
+///
+/// `app` is the `cargo` command with `subcommand` in it, as cargo invokes the binary. The bash
+/// and zsh scripts hook into cargo's own completion instead of replacing it, and complete the
+/// `dynamic` arguments with names from the manifest at hand.
+pub fn write_completions(
+    mut app: App<'_, '_>,
+    subcommand: &str,
+    shell: Shell,
+    dynamic: DynamicCompletions,
+    out: &mut impl Write,
+) -> Result<()> {
+    let mut script = Vec::new();
+    app.gen_completions_to("cargo", shell, &mut script);
+    let script = String::from_utf8(script).chain_err(|| "Invalid completion script")?;
+    let script = match shell {
+        Shell::Bash => bash(&script, subcommand, dynamic),
+        Shell::Zsh => zsh(&script, subcommand, dynamic),
+        Shell::Fish => fish(&script, subcommand, dynamic),
+        Shell::PowerShell | Shell::Elvish => script,
+    };
+    out.write_all(script.as_bytes())
+        .chain_err(|| "Failed to write completion script")
+}
+
+fn list_command(names: CompletionNames) -> String {
+    format!("cargo edit complete {} 2>/dev/null", names.as_str())
+}
+
+/// Whether `option`, with its value, is completed with `names`.
+fn completes_with(option: &str, dynamic: DynamicCompletions) -> Option<CompletionNames> {
+    match option {
+        "-p" | "--package" if dynamic.members => Some(CompletionNames::Members),
+        "--exclude" if dynamic.dependencies => Some(CompletionNames::Dependencies),
+        _ => None,
+    }
+}
+
+fn bash(script: &str, subcommand: &str, dynamic: DynamicCompletions) -> String {
+    let function = format!("_cargo_edit_{}", subcommand.replace('-', "_"));
+    let block = format!("cargo__{})", subcommand.replace('-', "__"));
+    let mut out = String::new();
+    let mut in_block = false;
+    let mut option_names = None;
+    for line in script.lines() {
+        let trimmed = line.trim();
+        if trimmed == "_cargo() {" {
+            out.push_str(&format!("{}() {{\n", function));
+            continue;
+        }
+        if trimmed.starts_with("complete -F _cargo ") {
+            continue;
+        }
+        if trimmed.ends_with(')') && !trimmed.contains(' ') && trimmed.starts_with("cargo") {
+            in_block = trimmed == block;
+        }
+        if in_block {
+            if let Some(names) = option_names.take() {
+                if trimmed.starts_with("COMPREPLY=($(compgen -f") {
+                    let indent = &line[..line.len() - line.trim_start().len()];
+                    out.push_str(&format!(
+                        "{}COMPREPLY=($(compgen -W \"$({})\" -- \"${{cur}}\"))\n",
+                        indent,
+                        list_command(names)
+                    ));
+                    continue;
+                }
+            }
+            if let Some(option) = trimmed.strip_suffix(')') {
+                option_names = completes_with(option, dynamic);
+            }
+            // Positional arguments are completed last, once options are ruled out.
+            if dynamic.dependencies
+                && trimmed == "COMPREPLY=( $(compgen -W \"${opts}\" -- \"${cur}\") )"
+            {
+                let indent = &line[..line.len() - line.trim_start().len()];
+                out.push_str(&format!(
+                    "{}COMPREPLY=( $(compgen -W \"$({})\" -- \"${{cur}}\") )\n",
+                    indent,
+                    list_command(CompletionNames::Dependencies)
+                ));
+                continue;
+            }
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    // Other cargo commands are left to the completion cargo already had.
+    let fallback = format!("{}_fallback", function);
+    out.push_str(&format!(
+        r#"
+if ! complete -p cargo >/dev/null 2>&1 && declare -F _completion_loader >/dev/null; then
+    _completion_loader cargo
+fi
+if [[ "$(complete -p cargo 2>/dev/null)" != *" {function}_complete "* ]]; then
+    {fallback}="$(complete -p cargo 2>/dev/null | sed -n 's/.*-F \([^ ]*\) .*/\1/p')"
+fi
+
+{function}_complete() {{
+    if [[ "${{COMP_WORDS[1]}}" == {subcommand} ]]; then
+        {function} "$@"
+    elif [[ -n "${{{fallback}}}" ]]; then
+        "${{{fallback}}}" "$@"
+    fi
+}}
+
+complete -F {function}_complete -o bashdefault -o default cargo
+"#,
+        function = function,
+        fallback = fallback,
+        subcommand = subcommand,
+    ));
+    out
+}
+
+fn zsh(script: &str, subcommand: &str, dynamic: DynamicCompletions) -> String {
+    let function = format!("_cargo_edit_{}", subcommand.replace('-', "_"));
+    let plugin = format!("_cargo-{}", subcommand);
+    let mut out = String::new();
+    let mut in_block = false;
+    for line in script.lines() {
+        if line == "#compdef cargo" {
+            out.push_str(&format!("#compdef cargo-{}\n", subcommand));
+            continue;
+        }
+        if line == "_cargo \"$@\"" {
+            continue;
+        }
+        let trimmed = line.trim();
+        if trimmed.starts_with('(') && trimmed.ends_with(')') {
+            in_block = trimmed == format!("({})", subcommand);
+        }
+        let line = line.replace("_cargo", &function);
+        if !in_block {
+            out.push_str(&line);
+            out.push('\n');
+            continue;
+        }
+        // Options with a value are written `'-p+[help]'` and `'--package=[help]'`, after the
+        // options they conflict with, if any, like `'(--manifest-path)-p+[help]'`.
+        let option = line
+            .strip_prefix('\'')
+            .map(|spec| match spec.strip_prefix('(') {
+                Some(spec) => spec.split_once(')').map_or(spec, |(_, spec)| spec),
+                None => spec,
+            })
+            .and_then(|spec| spec.split(['+', '=']).next())
+            .filter(|option| option.starts_with('-'));
+        let names = match option {
+            Some(option) if line.contains("]' \\") => completes_with(option, dynamic),
+            // Positional arguments are written `':name -- help:_files'`.
+            None if dynamic.dependencies && line.ends_with(":_files' \\") => {
+                Some(CompletionNames::Dependencies)
+            }
+            _ => None,
+        };
+        let line = match names {
+            Some(names) if option.is_some() => line.replacen(
+                "]' \\",
+                &format!("]: :_cargo_edit_{}' \\", names.as_str()),
+                1,
+            ),
+            Some(names) => line.replacen(
+                ":_files' \\",
+                &format!(":_cargo_edit_{}' \\", names.as_str()),
+                1,
+            ),
+            None => line,
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    for names in &[CompletionNames::Members, CompletionNames::Dependencies] {
+        out.push_str(&format!(
+            r#"(( $+functions[_cargo_edit_{names}] )) ||
+_cargo_edit_{names}() {{
+    local -a names
+    names=(${{(f)"$({list})"}})
+    _describe -t {names} '{names}' names
+}}
+"#,
+            names = names.as_str(),
+            list = list_command(*names),
+        ));
+    }
+    // cargo's completion calls `_cargo-<subcommand>` with the subcommand as the first word.
+    out.push_str(&format!(
+        r#"
+{plugin}() {{
+    words=(cargo "${{words[@]}}")
+    (( CURRENT += 1 ))
+    {function} "$@"
+}}
+
+{plugin} "$@"
+"#,
+        plugin = plugin,
+        function = function,
+    ));
+    out
+}
+
+fn fish(script: &str, subcommand: &str, dynamic: DynamicCompletions) -> String {
+    let condition = format!("-n \"__fish_seen_subcommand_from {}\"", subcommand);
+    let mut out = String::new();
+    for line in script.lines() {
+        // Only the subcommand itself is added to cargo's completions.
+        if line.contains("__fish_use_subcommand")
+            && !line.contains(&format!("-a \"{}\"", subcommand))
+        {
+            continue;
+        }
+        if line.contains("__fish_seen_subcommand_from") && !line.contains(&condition) {
+            continue;
+        }
+        let names = [
+            ("-s p ", "-p"),
+            ("-l package ", "--package"),
+            ("-l exclude ", "--exclude"),
+        ]
+        .iter()
+        .filter(|(spec, _)| line.contains(spec))
+        .find_map(|(_, option)| completes_with(option, dynamic));
+        match names {
+            Some(names) => out.push_str(&format!("{} -x -a \"({})\"\n", line, list_command(names))),
+            None => {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+    if dynamic.dependencies {
+        out.push_str(&format!(
+            "complete -c cargo {} -f -a \"({})\"\n",
+            condition,
+            list_command(CompletionNames::Dependencies)
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::clap::{Arg, SubCommand};
+
+    fn app() -> App<'static, 'static> {
+        App::new("cargo-edit").bin_name("cargo").subcommand(
+            SubCommand::with_name("rm")
+                .arg(Arg::with_name("crates").required(true).multiple(true))
+                .arg(
+                    Arg::with_name("pkgid")
+                        .long("package")
+                        .short("p")
+                        .takes_value(true)
+                        .conflicts_with("manifest-path"),
+                )
+                .arg(Arg::with_name("exclude").long("exclude").takes_value(true))
+                .arg(
+                    Arg::with_name("manifest-path")
+                        .long("manifest-path")
+                        .takes_value(true),
+                ),
+        )
+    }
+
+    fn script(shell: Shell) -> String {
+        let dynamic = DynamicCompletions {
+            members: true,
+            dependencies: true,
+        };
+        let mut out = Vec::new();
+        write_completions(app(), "rm", shell, dynamic, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn bash_hooks_into_cargo() {
+        let script = script(Shell::Bash);
+        assert!(script.contains("_cargo_edit_rm() {"));
+        assert!(!script.contains("_cargo()"));
+        assert!(!script.contains("complete -F _cargo "));
+        assert!(script.contains("complete -F _cargo_edit_rm_complete -o bashdefault"));
+        assert_eq!(
+            script
+                .matches("$(cargo edit complete members 2>/dev/null)")
+                .count(),
+            2,
+            "{}",
+            script
+        );
+        assert!(script.contains(
+            "--exclude)\n                    COMPREPLY=($(compgen -W \"$(cargo edit complete dependencies"
+        ));
+        // Files are still completed for other options.
+        assert!(script.contains("--manifest-path)\n                    COMPREPLY=($(compgen -f"));
+    }
+
+    #[test]
+    fn zsh_defines_a_cargo_plugin() {
+        let script = script(Shell::Zsh);
+        assert!(script.starts_with("#compdef cargo-rm\n"));
+        assert!(script.contains("'(--manifest-path)-p+[]: :_cargo_edit_members' \\"));
+        assert!(script.contains("'(--manifest-path)--package=[]: :_cargo_edit_members' \\"));
+        assert!(script.contains("'--exclude=[]: :_cargo_edit_dependencies' \\"));
+        assert!(script.contains("'--manifest-path=[]' \\"));
+        assert!(script.contains(":crates:_cargo_edit_dependencies' \\"));
+        assert!(script.contains("_cargo_edit_rm_commands"));
+        assert!(!script.contains("_cargo_commands"));
+        assert!(script.ends_with("_cargo-rm \"$@\"\n"));
+    }
+
+    #[test]
+    fn fish_adds_to_cargo() {
+        let script = script(Shell::Fish);
+        assert!(!script.contains("-n \"__fish_use_subcommand\" -s h"));
+        assert!(script.contains("-f -a \"rm\""));
+        assert!(
+            script.contains("-s p -l package -x -a \"(cargo edit complete members 2>/dev/null)\"")
+        );
+        assert!(script.ends_with(
+            "complete -c cargo -n \"__fish_seen_subcommand_from rm\" -f -a \"(cargo edit complete dependencies 2>/dev/null)\"\n"
+        ));
+    }
+}
@@ -0,0 +1,354 @@
+//! Settings shared by all subcommands, merged from several places.
+//!
+//! From lowest to highest precedence, settings come from:
+//!
+//! 1. the built-in defaults,
+//! 2. `[workspace.metadata.cargo-edit]` in the workspace root manifest, the project's policy
+//!    shared by everyone working on it,
+//! 3. `$CARGO_HOME/cargo-edit.toml`, the personal defaults of the user,
+//! 4. `CARGO_EDIT_*` environment variables, like `CARGO_EDIT_ALLOW_PRERELEASE=true` for
+//!    `allow-prerelease`,
+//! 5. command-line flags.
+//!
+//! Each file can also hold a table per subcommand, whose settings only apply to it and take
+//! precedence over the general ones of the same file:
+//!
+//! ```toml
+//! # $CARGO_HOME/cargo-edit.toml
+//! offline = true
+//!
+//! [upgrade]
+//! allow-prerelease = true
+//! exclude = ["tokio"]
+//! ```
+//!
+//! The environment variable for a subcommand's setting is prefixed by its name, like
+//! `CARGO_EDIT_UPGRADE_ALLOW_PRERELEASE`.
+use crate::color::ColorPreference;
+use crate::errors::*;
+use crate::manifest::CARGO_EDIT_KEY;
+use crate::registry::cargo_home;
+use crate::workspace::find_workspace_root;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "index")]
+use {crate::cache::DEFAULT_CACHE_TTL, std::time::Duration};
+
+/// The built-in defaults, the lowest layer of every configuration.
+const DEFAULTS: &str = r#"
+allow-prerelease = false
+color = "auto"
+offline = false
+quiet = false
+sort = false
+yes = false
+cache-ttl = 300
+"#;
+
+/// The name of the configuration file in `$CARGO_HOME`.
+const CONFIG_FILE: &str = "cargo-edit.toml";
+
+/// The prefix of configuration environment variables.
+const ENV_PREFIX: &str = "CARGO_EDIT_";
+
+/// Where a setting comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// The built-in defaults
+    Default,
+    /// `[workspace.metadata.cargo-edit]` in the workspace root manifest
+    Workspace(PathBuf),
+    /// The configuration file in `$CARGO_HOME`
+    User(PathBuf),
+    /// An environment variable
+    Environment(String),
+    /// A command-line flag
+    CommandLine,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "the defaults"),
+            ConfigSource::User(path) => write!(f, "`{}`", path.display()),
+            ConfigSource::Workspace(path) => write!(
+                f,
+                "`workspace.metadata.{}` in `{}`",
+                CARGO_EDIT_KEY,
+                path.display()
+            ),
+            ConfigSource::Environment(var) => write!(f, "`{}`", var),
+            ConfigSource::CommandLine => write!(f, "the command line"),
+        }
+    }
+}
+
+/// The settings for one subcommand, from every layer.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{Config, ConfigSource};
+///
+///   let mut config = Config::defaults("upgrade");
+///   config.add_layer(
+///       ConfigSource::CommandLine,
+///       "offline = true\n[upgrade]\nexclude = ['tokio']".parse().unwrap(),
+///   );
+///   assert_eq!(config.get::<bool>("offline").unwrap(), Some(true));
+///   assert_eq!(config.get::<Vec<String>>("exclude").unwrap(), Some(vec!["tokio".to_owned()]));
+///   assert_eq!(config.get::<bool>("allow-prerelease").unwrap(), Some(false));
+///   assert_eq!(config.get::<String>("registry").unwrap(), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Config {
+    command: String,
+    /// From lowest to highest precedence, except for the environment.
+    layers: Vec<(ConfigSource, toml::Value)>,
+    env: BTreeMap<String, String>,
+    cli: toml::value::Table,
+}
+
+impl Config {
+    /// The settings for `command`, like `upgrade`, when editing the manifest at `manifest_path`.
+    ///
+    /// A missing configuration file is skipped, and an invalid one is an error. Manifests which
+    /// can't be read are skipped too, and left for the command to report.
+    pub fn load(manifest_path: &Path, command: &str) -> Result<Self> {
+        let mut config = Config::defaults(command);
+        let root = find_workspace_root(manifest_path).ok();
+        let workspace = root.and_then(|root| Some((read_file(&root).ok()??, root)));
+        if let Some((workspace, root)) = workspace {
+            let table = workspace
+                .get("workspace")
+                .and_then(|w| w.get("metadata"))
+                .and_then(|m| m.get(CARGO_EDIT_KEY));
+            if let Some(table) = table {
+                config.add_layer(ConfigSource::Workspace(root), table.clone());
+            }
+        }
+        let user_path = cargo_home()?.join(CONFIG_FILE);
+        if let Some(user) = read_file(&user_path)? {
+            config.add_layer(ConfigSource::User(user_path), user);
+        }
+        config.env = std::env::vars()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .collect();
+        Ok(config)
+    }
+
+    /// The built-in defaults for `command`, without reading any file or environment variable.
+    pub fn defaults(command: &str) -> Self {
+        Config {
+            command: command.to_owned(),
+            layers: vec![(
+                ConfigSource::Default,
+                DEFAULTS.parse().expect("the defaults are valid TOML"),
+            )],
+            env: BTreeMap::new(),
+            cli: toml::value::Table::new(),
+        }
+    }
+
+    /// Add settings taking precedence over those added before; environment variables and flags
+    /// still take precedence over them.
+    pub fn add_layer(&mut self, source: ConfigSource, settings: toml::Value) {
+        self.layers.push((source, settings));
+    }
+
+    /// Set `key` as given by a command-line flag, overriding every other layer.
+    pub fn set(&mut self, key: &str, value: impl Into<toml::Value>) {
+        self.cli.insert(key.to_owned(), value.into());
+    }
+
+    /// The value of `key`, from the layer with the highest precedence which has it.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        match self.lookup(key) {
+            Some((source, value)) => value
+                .try_into()
+                .map(Some)
+                .chain_err(|| ErrorKind::InvalidConfig(key.to_owned(), source.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    /// Where the value of `key` comes from, if it is set.
+    pub fn source_of(&self, key: &str) -> Option<ConfigSource> {
+        self.lookup(key).map(|(source, _)| source)
+    }
+
+    /// A flag-like setting: true if set by the flag, or else as configured.
+    pub fn flag(&self, key: &str, flag: bool) -> Result<bool> {
+        Ok(flag || self.get(key)?.unwrap_or(false))
+    }
+
+    /// When to color output, from `color`.
+    pub fn color(&self) -> Result<ColorPreference> {
+        Ok(self.get("color")?.unwrap_or_default())
+    }
+
+    /// How long a registry index update is good for, from `cache-ttl` in seconds.
+    #[cfg(feature = "index")]
+    pub fn cache_ttl(&self) -> Result<Duration> {
+        Ok(self
+            .get("cache-ttl")?
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_CACHE_TTL))
+    }
+
+    fn lookup(&self, key: &str) -> Option<(ConfigSource, toml::Value)> {
+        if let Some(value) = self.cli.get(key) {
+            return Some((ConfigSource::CommandLine, value.clone()));
+        }
+        for var in &[env_name(Some(&self.command), key), env_name(None, key)] {
+            if let Some(value) = self.env.get(var) {
+                return Some((ConfigSource::Environment(var.clone()), parse_env(value)));
+            }
+        }
+        self.layers.iter().rev().find_map(|(source, settings)| {
+            let value = settings
+                .get(&self.command)
+                .and_then(|command| get_dotted(command, key))
+                .or_else(|| get_dotted(settings, key))?;
+            Some((source.clone(), value.clone()))
+        })
+    }
+}
+
+/// The environment variable for `key`, for a single command if `command` is given.
+fn env_name(command: Option<&str>, key: &str) -> String {
+    let name = match command {
+        Some(command) => format!("{}{}_{}", ENV_PREFIX, command, key),
+        None => format!("{}{}", ENV_PREFIX, key),
+    };
+    name.replace(['-', '.'], "_").to_ascii_uppercase()
+}
+
+/// An environment variable's value, as TOML if it is a valid value, like `true` or `["a", "b"]`,
+/// or else as a string.
+fn parse_env(value: &str) -> toml::Value {
+    format!("value = {}", value)
+        .parse::<toml::Value>()
+        .ok()
+        .and_then(|mut table| table.as_table_mut()?.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_owned()))
+}
+
+fn get_dotted<'a>(value: &'a toml::Value, key: &str) -> Option<&'a toml::Value> {
+    key.split('.')
+        .try_fold(value, |value, part| value.get(part))
+}
+
+fn read_file(path: &Path) -> Result<Option<toml::Value>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    content
+        .parse()
+        .map(Some)
+        .chain_err(|| format!("Invalid configuration in `{}`", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::version_req::ReqPrecision;
+
+    fn layer(settings: &str) -> toml::Value {
+        settings.parse().unwrap()
+    }
+
+    #[test]
+    fn precedence() {
+        let mut config = Config::defaults("add");
+        config.add_layer(
+            ConfigSource::Workspace("Cargo.toml".into()),
+            layer(
+                "registry = 'workspace'\nsort = true\nprecision = 'minor'\n[upgrade]\nsort = false",
+            ),
+        );
+        config.add_layer(
+            ConfigSource::User("cargo-edit.toml".into()),
+            layer("registry = 'user'\nprecision = 'major'\n[add]\nquiet = true"),
+        );
+        config
+            .env
+            .insert("CARGO_EDIT_ADD_CACHE_TTL".to_owned(), "60".to_owned());
+        config
+            .env
+            .insert("CARGO_EDIT_REGISTRY".to_owned(), "env".to_owned());
+
+        assert_eq!(config.get::<bool>("sort").unwrap(), Some(true));
+        assert_eq!(config.get::<bool>("quiet").unwrap(), Some(true));
+        assert_eq!(
+            config.source_of("sort"),
+            Some(ConfigSource::Workspace("Cargo.toml".into()))
+        );
+        // Personal defaults take precedence over the project's.
+        assert_eq!(
+            config.get::<ReqPrecision>("precision").unwrap(),
+            Some(ReqPrecision::Major)
+        );
+        #[cfg(feature = "index")]
+        assert_eq!(config.cache_ttl().unwrap(), Duration::from_secs(60));
+        assert_eq!(
+            config.get::<String>("registry").unwrap().as_deref(),
+            Some("env")
+        );
+        assert_eq!(
+            config.source_of("registry"),
+            Some(ConfigSource::Environment("CARGO_EDIT_REGISTRY".to_owned()))
+        );
+
+        config.set("registry", "flag");
+        assert_eq!(
+            config.get::<String>("registry").unwrap().as_deref(),
+            Some("flag")
+        );
+        assert_eq!(config.source_of("offline"), Some(ConfigSource::Default));
+        assert!(config.flag("offline", true).unwrap());
+    }
+
+    #[test]
+    fn color_preference() {
+        let mut config = Config::defaults("freeze");
+        assert_eq!(config.color().unwrap(), ColorPreference::Auto);
+        config.add_layer(
+            ConfigSource::User("cargo-edit.toml".into()),
+            layer("color = 'never'\n[freeze]\ncolor = 'always'"),
+        );
+        assert_eq!(config.color().unwrap(), ColorPreference::Always);
+    }
+
+    #[test]
+    fn invalid_values() {
+        let mut config = Config::defaults("add");
+        config.add_layer(
+            ConfigSource::User("cargo-edit.toml".into()),
+            layer("offline = 'yes'"),
+        );
+        let err = config.get::<bool>("offline").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid setting `offline` in `cargo-edit.toml`"
+        );
+    }
+
+    #[test]
+    fn environment_variables() {
+        assert_eq!(
+            env_name(Some("add"), "allow-prerelease"),
+            "CARGO_EDIT_ADD_ALLOW_PRERELEASE"
+        );
+        assert_eq!(parse_env("true"), toml::Value::Boolean(true));
+        assert_eq!(parse_env("['a']").as_array().unwrap().len(), 1);
+        assert_eq!(
+            parse_env("mirror"),
+            toml::Value::String("mirror".to_owned())
+        );
+    }
+}
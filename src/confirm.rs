@@ -0,0 +1,170 @@
+//! Asking before changes with a large blast radius, like removing a dependency other crates rely
+//! on, and letting the user pick among choices, like the features of a crate being added.
+
+use crate::errors::*;
+use std::io::{self, BufRead, Write};
+
+/// Go ahead with a change only if the user confirms `question`, like
+/// ``Remove `serde` from `my-crate` anyway?``, or fail with [`ErrorKind::Declined`].
+///
+/// The question is asked on stderr when both stdin and stderr are terminals; only `y` or `yes`
+/// confirms. Without a terminal there is nobody to ask, so the change goes ahead, as it does with
+/// `assume_yes`, e.g. from `--yes`.
+pub fn confirm(question: &str, assume_yes: bool) -> Result<()> {
+    if assume_yes || !can_ask() {
+        return Ok(());
+    }
+    let stdin = io::stdin();
+    if ask(question, &mut stdin.lock(), &mut io::stderr())? {
+        Ok(())
+    } else {
+        Err(ErrorKind::Declined.into())
+    }
+}
+
+/// Whether the user takes up the offer in `question`, like ``Edit `Cargo.toml.orig` instead?``.
+///
+/// Unlike [`confirm`], an offer nobody can be asked about, without a terminal, is declined.
+pub(crate) fn offer(question: &str) -> Result<bool> {
+    if !can_ask() {
+        return Ok(false);
+    }
+    let stdin = io::stdin();
+    ask(question, &mut stdin.lock(), &mut io::stderr())
+}
+
+/// Whether there is somebody to ask: both stdin and stderr are terminals.
+pub fn can_ask() -> bool {
+    atty::is(atty::Stream::Stdin) && atty::is(atty::Stream::Stderr)
+}
+
+/// Let the user check and uncheck any of `options`, listed under `title` and checked as in
+/// `checked`, and return which are checked once they accept.
+///
+/// Options are toggled by number or by name, several at once separated by spaces or commas, and an
+/// empty line accepts. Without a terminal there is nobody to ask, so `checked` is returned as is.
+pub fn select(title: &str, options: &[String], checked: &[bool]) -> Result<Vec<bool>> {
+    if !can_ask() {
+        return Ok(checked.to_vec());
+    }
+    let stdin = io::stdin();
+    pick(
+        title,
+        options,
+        checked,
+        &mut stdin.lock(),
+        &mut io::stderr(),
+    )
+}
+
+/// Ask `question` on `output` and read the answer from `input`.
+fn ask(question: &str, input: &mut impl BufRead, output: &mut impl Write) -> Result<bool> {
+    write!(output, "{} [y/N] ", question)?;
+    output.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// List `options` on `output` and read which to toggle from `input`, until an empty line.
+fn pick(
+    title: &str,
+    options: &[String],
+    checked: &[bool],
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<Vec<bool>> {
+    let mut checked = checked.to_vec();
+    loop {
+        writeln!(output, "{}", title)?;
+        let width = options.len().to_string().len();
+        for (number, (option, checked)) in options.iter().zip(&checked).enumerate() {
+            let mark = if *checked { 'x' } else { ' ' };
+            writeln!(
+                output,
+                "  {:>width$} [{}] {}",
+                number + 1,
+                mark,
+                option,
+                width = width
+            )?;
+        }
+        write!(
+            output,
+            "Toggle by number or name, or press Enter to accept: "
+        )?;
+        output.flush()?;
+
+        let mut answer = String::new();
+        input.read_line(&mut answer)?;
+        let words: Vec<&str> = answer
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| !word.is_empty())
+            .collect();
+        if words.is_empty() {
+            return Ok(checked);
+        }
+        for word in words {
+            let index = match word.parse::<usize>() {
+                Ok(number) if (1..=options.len()).contains(&number) => Some(number - 1),
+                _ => options.iter().position(|option| option == word),
+            };
+            match index {
+                Some(index) => checked[index] = !checked[index],
+                None => writeln!(output, "No option `{}`", word)?,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(input: &str) -> (bool, String) {
+        let mut output = Vec::new();
+        let confirmed = ask("Go on?", &mut input.as_bytes(), &mut output).unwrap();
+        (confirmed, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn only_yes_confirms() {
+        assert_eq!(answer("y\n"), (true, "Go on? [y/N] ".to_owned()));
+        assert!(answer("Yes\n").0);
+        assert!(!answer("\n").0);
+        assert!(!answer("no\n").0);
+        // Stdin closed without an answer.
+        assert!(!answer("").0);
+    }
+
+    #[test]
+    fn toggles_options_until_accepted() {
+        let options = ["std".to_owned(), "derive".to_owned(), "rc".to_owned()];
+        let mut output = Vec::new();
+        let checked = pick(
+            "Features:",
+            &options,
+            &[true, false, false],
+            &mut "2 1, rc\nrc nope\n\n".as_bytes(),
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(checked, [false, true, false]);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with(
+            "Features:\n  1 [x] std\n  2 [ ] derive\n  3 [ ] rc\n\
+             Toggle by number or name, or press Enter to accept: Features:\n  1 [ ] std\n"
+        ));
+        assert!(output.contains("No option `nope`\n"));
+
+        // Stdin closed without an answer accepts the options as they are.
+        let checked = pick(
+            "",
+            &options,
+            &[true, false, true],
+            &mut "".as_bytes(),
+            &mut Vec::new(),
+        );
+        assert_eq!(checked.unwrap(), [true, false, true]);
+    }
+}
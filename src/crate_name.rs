@@ -1,10 +1,115 @@
-//! Crate name parsing.
+//! Crate name parsing, validation and normalization.
 use crate::errors::*;
-use crate::Dependency;
-use crate::{get_crate_name_from_github, get_crate_name_from_gitlab, get_crate_name_from_path};
+use crate::registry::CRATES_IO_REGISTRY;
+#[cfg(feature = "fetch")]
+use crate::{get_crate_name_from_github, get_crate_name_from_gitlab};
+use crate::{get_crate_name_from_path, Dependency, PackageIdSpec};
+
+/// The longest crate name crates.io accepts.
+pub const MAX_CRATE_NAME_LEN: usize = 64;
+
+/// Names of the crates shipped with Rust, which crates.io reserves, normalized.
+const RUST_CRATES: [&str; 5] = ["alloc", "core", "proc_macro", "std", "test"];
+
+/// Names Windows reserves for devices, which crates.io doesn't allow as they can't be files.
+const WINDOWS_DEVICES: [&str; 22] = [
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// The rules a crate name has to follow, which depend on the registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NamingRules {
+    /// Cargo's rules for any registry: ASCII letters, digits, `-` and `_`, not starting with a
+    /// digit.
+    Cargo,
+    /// crates.io's rules: cargo's, starting with a letter, at most [`MAX_CRATE_NAME_LEN`]
+    /// characters, and not the name of a crate shipped with Rust or of a Windows device.
+    CratesIo,
+}
+
+impl NamingRules {
+    /// The rules of the registry called `registry`, crates.io if `None`.
+    ///
+    /// Other registries are only known to follow cargo's rules.
+    pub fn for_registry(registry: Option<&str>) -> Self {
+        match registry {
+            None | Some(CRATES_IO_REGISTRY) => NamingRules::CratesIo,
+            Some(_) => NamingRules::Cargo,
+        }
+    }
+}
+
+/// Check that `name` is a valid crate name under `rules`.
+///
+/// This fails with [`ErrorKind::EmptyCrateName`] for an empty name, and with
+/// [`ErrorKind::InvalidCrateName`] saying which rule is broken otherwise.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{validate_crate_name, NamingRules};
+///
+///   assert!(validate_crate_name("serde_json", NamingRules::CratesIo).is_ok());
+///   assert!(validate_crate_name("std", NamingRules::CratesIo).is_err());
+///   assert!(validate_crate_name("_private", NamingRules::CratesIo).is_err());
+///   assert!(validate_crate_name("_private", NamingRules::Cargo).is_ok());
+///   assert!(validate_crate_name("serde json", NamingRules::Cargo).is_err());
+/// ```
+pub fn validate_crate_name(name: &str, rules: NamingRules) -> Result<()> {
+    let invalid = |reason: String| -> Result<()> {
+        Err(ErrorKind::InvalidCrateName(name.to_owned(), reason).into())
+    };
+    let first = match name.chars().next() {
+        Some(first) => first,
+        None => return Err(ErrorKind::EmptyCrateName.into()),
+    };
+    if let Some(c) = name
+        .chars()
+        .find(|&c| !c.is_ascii_alphanumeric() && c != '-' && c != '_')
+    {
+        return invalid(format!(
+            "`{}` is not allowed, only ASCII letters, digits, `-` and `_` are",
+            c.escape_debug()
+        ));
+    }
+    if first.is_ascii_digit() {
+        return invalid("it must not start with a digit".to_owned());
+    }
+    if rules == NamingRules::Cargo {
+        return Ok(());
+    }
+
+    if !first.is_ascii_alphabetic() {
+        return invalid("crates.io names must start with a letter".to_owned());
+    }
+    if name.len() > MAX_CRATE_NAME_LEN {
+        return invalid(format!(
+            "crates.io names are at most {} characters long",
+            MAX_CRATE_NAME_LEN
+        ));
+    }
+    let normalized = normalize_crate_name(name);
+    if RUST_CRATES.contains(&normalized.as_str()) {
+        return invalid("it is reserved for a crate shipped with Rust".to_owned());
+    }
+    if WINDOWS_DEVICES.contains(&normalized.as_str()) {
+        return invalid("it is reserved by Windows".to_owned());
+    }
+    Ok(())
+}
+
+/// The canonical form of a crate name: lower case, with `-` replaced by `_`.
+///
+/// Registries consider names with the same canonical form to be the same crate, so only one of
+/// them can be published.
+pub fn normalize_crate_name(name: &str) -> String {
+    name.to_ascii_lowercase().replace('-', "_")
+}
 
 /// A crate specifier. This can be a plain name (e.g. `docopt`), a name and a versionreq (e.g.
-/// `docopt@^0.8`), a URL, or a path.
+/// `docopt@^0.8`), a package ID spec (e.g. `git+https://github.com/x/y#docopt@0.8`), a URL, or a
+/// path.
 #[derive(Debug)]
 pub struct CrateName<'a>(&'a str);
 
@@ -24,6 +129,23 @@ impl<'a> CrateName<'a> {
         self.0.contains('@')
     }
 
+    /// Is this a package ID spec with a source, as cargo prints them?
+    ///
+    /// Plain repository URLs without a `#name` fragment are not, as the crate name is looked up in
+    /// the repository for those.
+    pub fn is_package_id_spec(&self) -> bool {
+        self.0.contains("://")
+            && (self.0.contains('#')
+                || ["registry+", "sparse+", "git+", "path+"]
+                    .iter()
+                    .any(|kind| self.0.starts_with(kind)))
+    }
+
+    /// Parse this crate specifier as a package ID spec.
+    pub fn parse_as_package_id_spec(&self) -> Result<PackageIdSpec> {
+        PackageIdSpec::parse(self.0)
+    }
+
     /// Is this a URI?
     pub fn is_url_or_path(&self) -> bool {
         self.is_github_url() || self.is_gitlab_url() || self.is_path()
@@ -45,26 +167,31 @@ impl<'a> CrateName<'a> {
     /// If this crate specifier includes a version (e.g. `docopt@0.8`), extract the name and
     /// version.
     pub fn parse_as_version(&self) -> Result<Option<Dependency>> {
-        if self.has_version() {
-            let xs: Vec<_> = self.0.splitn(2, '@').collect();
-            let (name, version) = (xs[0], xs[1]);
-            semver::VersionReq::parse(version).chain_err(|| "Invalid crate version requirement")?;
+        if self.has_version() && !self.is_package_id_spec() {
+            let spec = self.parse_as_package_id_spec()?;
+            let version = spec.version.as_deref().unwrap_or_default();
 
-            Ok(Some(Dependency::new(name).set_version(version)))
+            Ok(Some(Dependency::new(&spec.name).set_version(version)))
         } else {
             Ok(None)
         }
     }
 
     /// Will parse this crate name on the assumption that it is a URI.
+    ///
+    /// Repositories on GitHub and GitLab are only looked up with the `fetch` feature.
     pub fn parse_crate_name_from_uri(&self) -> Result<Dependency> {
-        if self.is_github_url() {
-            if let Ok(ref crate_name) = get_crate_name_from_github(self.0) {
-                return Ok(Dependency::new(crate_name).set_git(self.0, None));
-            }
-        } else if self.is_gitlab_url() {
-            if let Ok(ref crate_name) = get_crate_name_from_gitlab(self.0) {
-                return Ok(Dependency::new(crate_name).set_git(self.0, None));
+        if self.is_github_url() || self.is_gitlab_url() {
+            #[cfg(feature = "fetch")]
+            {
+                let crate_name = if self.is_github_url() {
+                    get_crate_name_from_github(self.0)
+                } else {
+                    get_crate_name_from_gitlab(self.0)
+                };
+                if let Ok(ref crate_name) = crate_name {
+                    return Ok(Dependency::new(crate_name).set_git(self.0, None));
+                }
             }
         } else if self.is_path() {
             if let Ok(ref crate_name) = get_crate_name_from_path(self.0) {
@@ -75,3 +202,64 @@ impl<'a> CrateName<'a> {
         bail!("Unable to obtain crate informations from `{}`.\n", self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(name: &str, rules: NamingRules) -> String {
+        validate_crate_name(name, rules).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn crates_io_rules() {
+        for name in &["serde", "serde_json", "proc-macro2", "Inflector", "a"] {
+            assert!(validate_crate_name(name, NamingRules::CratesIo).is_ok());
+        }
+        assert_eq!(
+            reason("proc-macro", NamingRules::CratesIo),
+            "Invalid crate name `proc-macro`: it is reserved for a crate shipped with Rust"
+        );
+        assert_eq!(
+            reason("Nul", NamingRules::CratesIo),
+            "Invalid crate name `Nul`: it is reserved by Windows"
+        );
+        assert_eq!(
+            reason(&"a".repeat(65), NamingRules::CratesIo),
+            format!(
+                "Invalid crate name `{}`: crates.io names are at most 64 characters long",
+                "a".repeat(65)
+            )
+        );
+        assert!(validate_crate_name(&"a".repeat(65), NamingRules::Cargo).is_ok());
+    }
+
+    #[test]
+    fn cargo_rules() {
+        assert_eq!(
+            reason("serde json", NamingRules::Cargo),
+            "Invalid crate name `serde json`: ` ` is not allowed, only ASCII letters, digits, `-` \
+             and `_` are"
+        );
+        assert_eq!(
+            reason("2d", NamingRules::Cargo),
+            "Invalid crate name `2d`: it must not start with a digit"
+        );
+        assert_eq!(reason("", NamingRules::Cargo), "Found empty crate name");
+        assert!(validate_crate_name("std", NamingRules::Cargo).is_ok());
+    }
+
+    #[test]
+    fn registry_rules() {
+        assert_eq!(NamingRules::for_registry(None), NamingRules::CratesIo);
+        assert_eq!(
+            NamingRules::for_registry(Some("crates-io")),
+            NamingRules::CratesIo
+        );
+        assert_eq!(
+            NamingRules::for_registry(Some("mirror")),
+            NamingRules::Cargo
+        );
+        assert_eq!(normalize_crate_name("Serde-JSON"), "serde_json");
+    }
+}
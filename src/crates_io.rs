@@ -0,0 +1,682 @@
+//! A client for the crates.io web API, for what the index does not know: descriptions, owners,
+//! publish dates, download counts and repositories.
+//!
+//! Responses are cached like index files (see [`IndexCache`]), and requests are spaced out to
+//! follow the crates.io crawler policy of at most one request per second. When crates.io answers
+//! `429 Too Many Requests` anyway, the request is retried as configured with a [`RetryPolicy`].
+use crate::cache::{cache_ttl, offline_cache_ttl, IndexCache};
+use crate::cargo_flags::is_offline;
+use crate::errors::*;
+use crate::proxy::apply_proxy;
+use crate::repository::{Changelog, Repository};
+use crate::retry::{call_with_retry, RetryPolicy};
+use crate::tls::apply_tls;
+use std::env;
+use std::io::Read;
+use std::time::Duration;
+use url::Url;
+
+/// The base URL of the crates.io web API.
+pub const CRATES_IO_API: &str = "https://crates.io/api/v1/";
+
+/// Identifies cargo-edit to crates.io, as its crawler policy asks for.
+const USER_AGENT: &str = "cargo-edit (https://github.com/killercup/cargo-edit)";
+
+/// What crates.io knows about a crate.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CrateInfo {
+    /// The name of the crate
+    pub name: String,
+    /// The description of the newest release
+    pub description: Option<String>,
+    /// The repository URL of the newest release
+    pub repository: Option<String>,
+    /// The homepage URL of the newest release
+    pub homepage: Option<String>,
+    /// The documentation URL of the newest release
+    pub documentation: Option<String>,
+    /// Downloads of all releases
+    pub downloads: u64,
+    /// Downloads of all releases in the last 90 days
+    pub recent_downloads: Option<u64>,
+    /// When the crate was first published, as an RFC 3339 timestamp
+    pub created_at: String,
+    /// When the crate was last published, as an RFC 3339 timestamp
+    pub updated_at: String,
+    /// All releases, newest first
+    #[serde(skip)]
+    pub versions: Vec<CrateRelease>,
+}
+
+impl CrateInfo {
+    /// The most recently published release which has not been yanked.
+    pub fn newest_release(&self) -> Option<&CrateRelease> {
+        self.versions
+            .iter()
+            .filter(|release| !release.yanked)
+            .max_by(|a, b| a.created_at.cmp(&b.created_at))
+    }
+}
+
+/// A single release of a crate.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CrateRelease {
+    /// The version number
+    pub num: String,
+    /// When the release was published, as an RFC 3339 timestamp
+    pub created_at: String,
+    /// Whether the release has been yanked
+    pub yanked: bool,
+    /// Downloads of this release
+    pub downloads: u64,
+    /// The license expression of this release
+    pub license: Option<String>,
+}
+
+/// A user or team allowed to publish a crate.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Owner {
+    /// The login, e.g. `dtolnay` or `github:rust-lang:libs`
+    pub login: String,
+    /// The display name, if set
+    pub name: Option<String>,
+    /// `user` or `team`
+    pub kind: String,
+}
+
+/// A crate depending on another one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReverseDependency {
+    /// The name of the dependent crate
+    pub name: String,
+    /// The newest version of the dependent crate with the dependency
+    pub version: String,
+    /// The version requirement on the dependency
+    pub req: String,
+    /// `normal`, `dev` or `build`
+    pub kind: String,
+    /// Whether the dependency is optional
+    pub optional: bool,
+    /// Downloads of the dependent crate
+    pub downloads: u64,
+}
+
+/// The crates depending on a crate, the most downloaded first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReverseDependencies {
+    /// The number of crates depending on the crate
+    pub total: u64,
+    /// The most downloaded of them
+    pub crates: Vec<ReverseDependency>,
+}
+
+/// Where the source and the release notes of a crate are.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CrateLinks {
+    /// The repository, if the crate gives one that could be understood
+    pub repository: Option<Repository>,
+    /// The best guess at where the release notes of the version are
+    pub changelog: Option<Changelog>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CrateResponse {
+    #[serde(rename = "crate")]
+    krate: CrateInfo,
+    versions: Vec<CrateRelease>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OwnersResponse {
+    users: Vec<Owner>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReverseDependenciesResponse {
+    dependencies: Vec<DependencyData>,
+    versions: Vec<DependentVersion>,
+    meta: Meta,
+}
+
+#[derive(Debug, Deserialize)]
+struct DependencyData {
+    version_id: u64,
+    req: String,
+    kind: String,
+    optional: bool,
+    #[serde(default)]
+    downloads: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct DependentVersion {
+    id: u64,
+    #[serde(rename = "crate")]
+    krate: String,
+    num: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Meta {
+    total: u64,
+}
+
+/// A client for the crates.io web API.
+#[derive(Debug)]
+pub struct CratesIoClient {
+    base: Url,
+    cache: IndexCache,
+    max_age: Duration,
+    min_interval: Duration,
+    retry: RetryPolicy,
+}
+
+impl CratesIoClient {
+    /// A client for crates.io, caching responses in the cargo home directory.
+    pub fn new() -> Result<Self> {
+        let base = Url::parse(CRATES_IO_API).expect("valid URL");
+        let cache = IndexCache::for_registry(&base)?;
+        Ok(Self::with_cache(&base, cache))
+    }
+
+    /// A client for the API at `base`, e.g. a crates.io mirror, caching responses in `cache`.
+    pub fn with_cache(base: &Url, cache: IndexCache) -> Self {
+        let mut base = base.clone();
+        if !base.path().ends_with('/') {
+            let path = format!("{}/", base.path());
+            base.set_path(&path);
+        }
+        CratesIoClient {
+            base,
+            cache,
+            max_age: cache_ttl(),
+            min_interval: Duration::from_secs(1),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Use cached responses younger than `max_age` instead of asking the API again.
+    pub fn set_max_age(&mut self, max_age: Duration) -> &mut Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Wait at least `min_interval` between two requests.
+    pub fn set_min_interval(&mut self, min_interval: Duration) -> &mut Self {
+        self.min_interval = min_interval;
+        self
+    }
+
+    /// Retry failed requests following `retry`.
+    pub fn set_retry_policy(&mut self, retry: RetryPolicy) -> &mut Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Everything crates.io knows about `crate_name`, or `None` if there is no such crate.
+    pub fn crate_info(&self, crate_name: &str) -> Result<Option<CrateInfo>> {
+        let path = format!("crates/{}", crate_name);
+        let body = match self.fetch(&crate_name.to_ascii_lowercase(), &path)? {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+        let response: CrateResponse = serde_json::from_slice(&body)
+            .chain_err(|| format!("Invalid response from `{}`", path))?;
+        let mut info = response.krate;
+        info.versions = response.versions;
+        Ok(Some(info))
+    }
+
+    /// The users and teams allowed to publish `crate_name`, or `None` if there is no such crate.
+    pub fn owners(&self, crate_name: &str) -> Result<Option<Vec<Owner>>> {
+        let path = format!("crates/{}/owners", crate_name);
+        let key = format!("{}.owners", crate_name.to_ascii_lowercase());
+        let body = match self.fetch(&key, &path)? {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+        let response: OwnersResponse = serde_json::from_slice(&body)
+            .chain_err(|| format!("Invalid response from `{}`", path))?;
+        Ok(Some(response.users))
+    }
+
+    /// The `limit` most downloaded crates depending on `crate_name`, or `None` if there is no
+    /// such crate.
+    pub fn reverse_dependencies(
+        &self,
+        crate_name: &str,
+        limit: usize,
+    ) -> Result<Option<ReverseDependencies>> {
+        let path = format!(
+            "crates/{}/reverse_dependencies?per_page={}",
+            crate_name, limit
+        );
+        let key = format!(
+            "{}.reverse-dependencies.{}",
+            crate_name.to_ascii_lowercase(),
+            limit
+        );
+        let body = match self.fetch(&key, &path)? {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+        let response: ReverseDependenciesResponse = serde_json::from_slice(&body)
+            .chain_err(|| format!("Invalid response from `{}`", path))?;
+
+        let versions = response.versions;
+        let mut crates: Vec<ReverseDependency> = response
+            .dependencies
+            .into_iter()
+            .filter_map(|dep| {
+                let version = versions.iter().find(|v| v.id == dep.version_id)?;
+                Some(ReverseDependency {
+                    name: version.krate.clone(),
+                    version: version.num.clone(),
+                    req: dep.req,
+                    kind: dep.kind,
+                    optional: dep.optional,
+                    downloads: dep.downloads,
+                })
+            })
+            .collect();
+        crates.sort_by(|a, b| b.downloads.cmp(&a.downloads).then(a.name.cmp(&b.name)));
+        crates.truncate(limit);
+        Ok(Some(ReverseDependencies {
+            total: response.meta.total,
+            crates,
+        }))
+    }
+
+    /// The repository `crate_name` gives, or `None` if there is no such crate, or it gives none
+    /// that could be understood.
+    pub fn repository(&self, crate_name: &str) -> Result<Option<Repository>> {
+        Ok(self
+            .crate_info(crate_name)?
+            .and_then(|info| info.repository)
+            .and_then(|url| Repository::parse(&url)))
+    }
+
+    /// The repository of `crate_name` and where the release notes of its `version` are.
+    ///
+    /// On known forges, the changelog is the first of the usual changelog files which exists in
+    /// the crate's directory or the root of the repository, or else the release tagged like
+    /// `v1.2.3` or `foo-1.2.3`, or else the list of releases. What was found is cached like the
+    /// API responses.
+    pub fn crate_links(&self, crate_name: &str, version: &semver::Version) -> Result<CrateLinks> {
+        let repository = match self.repository(crate_name)? {
+            Some(repository) => repository,
+            None => return Ok(CrateLinks::default()),
+        };
+        let key = format!("{}.changelog.{}", crate_name.to_ascii_lowercase(), version);
+        let changelog = match self.cache.get_fresh(&key, self.max_age)? {
+            Some(entry) => decode_changelog(&entry.body),
+            None => {
+                let changelog = self.find_changelog(crate_name, version, &repository)?;
+                self.cache
+                    .insert(&key, encode_changelog(&changelog).as_bytes())?;
+                changelog
+            }
+        };
+        Ok(CrateLinks {
+            repository: Some(repository),
+            changelog,
+        })
+    }
+
+    fn find_changelog(
+        &self,
+        crate_name: &str,
+        version: &semver::Version,
+        repository: &Repository,
+    ) -> Result<Option<Changelog>> {
+        let files = repository.changelog_paths().into_iter().filter_map(|path| {
+            Some((
+                repository.raw_file_url(&path)?,
+                Changelog::File(repository.file_url(&path)?),
+            ))
+        });
+        let releases = repository
+            .release_tags(crate_name, version)
+            .into_iter()
+            .filter_map(|tag| {
+                let url = repository.release_url(&tag)?;
+                Some((url.clone(), Changelog::Release(url)))
+            });
+        let candidates: Vec<_> = files.chain(releases).collect();
+        Ok(self
+            .first_existing(candidates)?
+            .or_else(|| repository.releases_url().map(Changelog::Releases)))
+    }
+
+    /// The first candidate whose URL exists.
+    fn first_existing(&self, candidates: Vec<(Url, Changelog)>) -> Result<Option<Changelog>> {
+        for (url, changelog) in candidates {
+            let mut req = ureq::head(url.as_str());
+            req.timeout(Duration::from_secs(30));
+            req.set("User-Agent", USER_AGENT);
+            apply_proxy(&mut req, &url)?;
+            apply_tls(&mut req)?;
+            apply_tls(&mut req)?;
+            let res = call_with_retry(&mut req, &url, &self.retry, Duration::from_secs(0))?;
+            match res.status() {
+                404 | 410 => event!(TRACE, %url, "not found"),
+                _ if res.ok() => return Ok(Some(changelog)),
+                status => {
+                    return Err(ErrorKind::Network(
+                        url.to_string(),
+                        res.synthetic_error()
+                            .as_ref()
+                            .map(|x| x.to_string())
+                            .unwrap_or_else(|| status.to_string()),
+                    )
+                    .into())
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Fetch the document at `path`, cached as `key`, or `None` if the API does not have it.
+    fn fetch(&self, key: &str, path: &str) -> Result<Option<Vec<u8>>> {
+        // Offline, whatever was cached is better than nothing, unless it is too stale.
+        let max_age = if is_offline() {
+            offline_cache_ttl()
+        } else {
+            self.max_age
+        };
+        if let Some(entry) = self.cache.get_fresh(key, max_age)? {
+            event!(DEBUG, path, "cache hit");
+            return Ok(Some(entry.body));
+        }
+
+        let url = self
+            .base
+            .join(path)
+            .chain_err(|| format!("Invalid crates.io API path `{}`", path))?;
+        let mut req = ureq::get(url.as_str());
+        req.timeout(Duration::from_secs(30));
+        req.set("User-Agent", USER_AGENT);
+        apply_proxy(&mut req, &url)?;
+
+        let res = call_with_retry(&mut req, &url, &self.retry, self.min_interval)?;
+        match res.status() {
+            404 => {
+                self.cache.remove(key)?;
+                Ok(None)
+            }
+            _ if res.ok() => {
+                let mut body = Vec::new();
+                res.into_reader()
+                    .read_to_end(&mut body)
+                    .chain_err(|| format!("Failed to read `{}`", url))?;
+                self.cache.insert(key, &body)?;
+                Ok(Some(body))
+            }
+            status => Err(ErrorKind::Network(
+                url.to_string(),
+                res.synthetic_error()
+                    .as_ref()
+                    .map(|x| x.to_string())
+                    .unwrap_or_else(|| status.to_string()),
+            )
+            .into()),
+        }
+    }
+}
+
+/// The repository of `crate_name` on crates.io and where the release notes of its `version` are.
+///
+/// See [`CratesIoClient::crate_links`]. A crate which is not on crates.io has no links.
+pub fn crate_links(crate_name: &str, version: &semver::Version) -> Result<CrateLinks> {
+    if env::var("CARGO_IS_TEST").is_ok() {
+        // We are in a simulated reality. Nothing is real here.
+        return Ok(match crate_name {
+            "test_changelog" => {
+                let repository = Repository::parse("https://github.com/cargo-edit/test_changelog");
+                CrateLinks {
+                    changelog: repository
+                        .as_ref()
+                        .and_then(|repository| repository.file_url("CHANGELOG.md"))
+                        .map(Changelog::File),
+                    repository,
+                }
+            }
+            _ => CrateLinks::default(),
+        });
+    }
+
+    let client = CratesIoClient::new()?;
+    client.crate_links(crate_name, version)
+}
+
+/// A changelog as cached: its kind and URL, or nothing if none was found.
+fn encode_changelog(changelog: &Option<Changelog>) -> String {
+    match changelog {
+        Some(Changelog::File(url)) => format!("file {}", url),
+        Some(Changelog::Release(url)) => format!("release {}", url),
+        Some(Changelog::Releases(url)) => format!("releases {}", url),
+        None => String::new(),
+    }
+}
+
+fn decode_changelog(cached: &[u8]) -> Option<Changelog> {
+    let cached = std::str::from_utf8(cached).ok()?;
+    let (kind, url) = cached.split_once(' ')?;
+    let url = Url::parse(url).ok()?;
+    match kind {
+        "file" => Some(Changelog::File(url)),
+        "release" => Some(Changelog::Release(url)),
+        "releases" => Some(Changelog::Releases(url)),
+        _ => None,
+    }
+}
+
+/// The `limit` most downloaded crates on crates.io depending on `crate_name`.
+///
+/// A crate which is not on crates.io has no reverse dependencies.
+pub fn notable_reverse_dependencies(crate_name: &str, limit: usize) -> Result<ReverseDependencies> {
+    if env::var("CARGO_IS_TEST").is_ok() {
+        // We are in a simulated reality. Nothing is real here.
+        let dependent = |name: &str, downloads| ReverseDependency {
+            name: name.to_owned(),
+            version: "1.0.0".to_owned(),
+            req: "^0.1".to_owned(),
+            kind: "normal".to_owned(),
+            optional: false,
+            downloads,
+        };
+        return Ok(match crate_name {
+            "test_popular" => ReverseDependencies {
+                total: 3,
+                crates: vec![dependent("dependent_a", 200), dependent("dependent_b", 100)]
+                    .into_iter()
+                    .take(limit)
+                    .collect(),
+            },
+            _ => ReverseDependencies::default(),
+        });
+    }
+
+    let client = CratesIoClient::new()?;
+    Ok(client
+        .reverse_dependencies(crate_name, limit)?
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serve `responses` to consecutive requests on a local port, returning the base URL and
+    /// the requests received.
+    fn serve(responses: Vec<String>) -> (Url, std::thread::JoinHandle<Vec<String>>) {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/api/v1", listener.local_addr().unwrap())).unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    request.push_str(&line);
+                }
+                stream.write_all(response.as_bytes()).unwrap();
+                requests.push(request);
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    fn ok(body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    }
+
+    const CRATE: &str = r#"{
+        "crate": {
+            "name": "foo", "description": "Does foo", "repository": "https://github.com/foo/foo",
+            "homepage": null, "documentation": null, "downloads": 1200, "recent_downloads": 30,
+            "created_at": "2019-01-01T00:00:00Z", "updated_at": "2020-06-01T00:00:00Z"
+        },
+        "versions": [
+            {"num": "1.1.0", "created_at": "2020-06-01T00:00:00Z", "yanked": true, "downloads": 10, "license": "MIT"},
+            {"num": "1.0.0", "created_at": "2019-01-01T00:00:00Z", "yanked": false, "downloads": 1190, "license": "MIT"}
+        ]
+    }"#;
+
+    #[test]
+    fn crate_info_is_cached() {
+        let not_found = "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let (url, server) = serve(vec![ok(CRATE), not_found.to_owned()]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut client = CratesIoClient::with_cache(&url, IndexCache::at(dir.path()));
+        client
+            .set_max_age(Duration::from_secs(60))
+            .set_min_interval(Duration::from_millis(0));
+
+        let info = client.crate_info("foo").unwrap().unwrap();
+        assert_eq!(info.description.as_deref(), Some("Does foo"));
+        assert_eq!(info.downloads, 1200);
+        assert_eq!(info.versions.len(), 2);
+        assert_eq!(info.newest_release().unwrap().num, "1.0.0");
+        // Served from the cache.
+        assert_eq!(client.crate_info("foo").unwrap().unwrap(), info);
+        assert_eq!(client.crate_info("bar").unwrap(), None);
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].starts_with("GET /api/v1/crates/foo "));
+        assert!(requests[0].contains("User-Agent: cargo-edit"));
+        assert!(requests[1].starts_with("GET /api/v1/crates/bar "));
+    }
+
+    #[test]
+    fn retries_when_rate_limited() {
+        let too_many =
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let owners = r#"{"users": [{"login": "alice", "name": "Alice", "kind": "user"}]}"#;
+        let (url, server) = serve(vec![too_many.to_owned(), ok(owners)]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut client = CratesIoClient::with_cache(&url, IndexCache::at(dir.path()));
+        client.set_min_interval(Duration::from_millis(0));
+
+        let owners = client.owners("foo").unwrap().unwrap();
+        assert_eq!(owners[0].login, "alice");
+        assert_eq!(owners[0].kind, "user");
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].starts_with("GET /api/v1/crates/foo/owners "));
+    }
+
+    #[test]
+    fn changelogs_are_probed_and_cached() {
+        let not_found = "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let (url, server) = serve(vec![not_found.to_owned(), ok("")]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut client = CratesIoClient::with_cache(&url, IndexCache::at(dir.path()));
+        client
+            .set_max_age(Duration::from_secs(60))
+            .set_min_interval(Duration::from_millis(0));
+
+        let candidate = |path: &str| {
+            let url = url.join(path).unwrap();
+            (url.clone(), Changelog::File(url))
+        };
+        let found = client
+            .first_existing(vec![candidate("CHANGES.md"), candidate("CHANGELOG.md")])
+            .unwrap();
+        assert_eq!(found, Some(candidate("CHANGELOG.md").1));
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("HEAD /api/CHANGES.md "));
+        assert!(requests[1].starts_with("HEAD /api/CHANGELOG.md "));
+
+        // Everything is answered from the cache.
+        client.cache.insert("foo", CRATE.as_bytes()).unwrap();
+        let release = "https://github.com/foo/foo/releases/tag/v1.0.0";
+        client
+            .cache
+            .insert(
+                "foo.changelog.1.0.0",
+                format!("release {}", release).as_bytes(),
+            )
+            .unwrap();
+        let links = client
+            .crate_links("foo", &semver::Version::new(1, 0, 0))
+            .unwrap();
+        assert_eq!(
+            links.repository.unwrap().url().as_str(),
+            "https://github.com/foo/foo"
+        );
+        assert_eq!(
+            links.changelog,
+            Some(Changelog::Release(Url::parse(release).unwrap()))
+        );
+        assert_eq!(decode_changelog(encode_changelog(&None).as_bytes()), None);
+    }
+
+    #[test]
+    fn reverse_dependencies_by_downloads() {
+        let body = r#"{
+            "dependencies": [
+                {"version_id": 1, "req": "^1.0", "kind": "normal", "optional": false, "downloads": 10},
+                {"version_id": 2, "req": "^1.0", "kind": "dev", "optional": true, "downloads": 500}
+            ],
+            "versions": [
+                {"id": 1, "crate": "small", "num": "0.1.0"},
+                {"id": 2, "crate": "big", "num": "2.0.0"}
+            ],
+            "meta": {"total": 42}
+        }"#;
+        let (url, server) = serve(vec![ok(body)]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut client = CratesIoClient::with_cache(&url, IndexCache::at(dir.path()));
+        client.set_min_interval(Duration::from_millis(0));
+
+        let reverse = client.reverse_dependencies("foo", 2).unwrap().unwrap();
+        assert_eq!(reverse.total, 42);
+        let names: Vec<_> = reverse.crates.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["big", "small"]);
+        assert_eq!(reverse.crates[0].kind, "dev");
+        assert!(reverse.crates[0].optional);
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /api/v1/crates/foo/reverse_dependencies?per_page=2 "));
+    }
+}
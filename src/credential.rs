@@ -0,0 +1,514 @@
+//! Registry authentication through cargo's credential providers.
+//!
+//! ref: https://doc.rust-lang.org/cargo/reference/registry-authentication.html
+use crate::errors::*;
+use crate::registry::{
+    cargo_home, config_paths, CRATES_IO_INDEX, CRATES_IO_REGISTRY, CRATES_IO_SPARSE_INDEX,
+};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use url::Url;
+
+/// Get a token to read from `registry`, the way cargo would.
+///
+/// The providers configured with `registries.<name>.credential-provider` (or
+/// `registry.credential-provider` for crates.io) are asked first, then those in
+/// `registry.global-credential-providers`, latest first. Without any configuration this reads the
+/// token from the environment, the cargo config or `credentials.toml` (`cargo:token`).
+///
+/// Returns `None` if no provider has a token for the registry.
+pub fn registry_token(manifest_path: &Path, registry: &Url) -> Result<Option<String>> {
+    let mut configs = Vec::new();
+    for path in config_paths(manifest_path)? {
+        configs.push(read_toml(&path)?);
+    }
+    let credentials_path = cargo_home()?.join("credentials.toml");
+    let credentials_path = if credentials_path.is_file() {
+        Some(credentials_path)
+    } else {
+        Some(cargo_home()?.join("credentials")).filter(|path| path.is_file())
+    };
+    let credentials = match credentials_path {
+        Some(path) => Some(read_toml(&path)?),
+        None => None,
+    };
+    let env: HashMap<String, String> = std::env::vars().collect();
+
+    CredentialConfig::new(registry, &configs, credentials.as_ref(), &env)?.token()
+}
+
+fn read_toml(path: &Path) -> Result<toml::Value> {
+    let content = std::fs::read(path)?;
+    toml::from_slice(&content).chain_err(|| ErrorKind::InvalidCargoConfig)
+}
+
+/// A way to obtain a token, as configured in a `credential-provider`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Provider {
+    /// `cargo:token`, the token stored in the config, `credentials.toml` or the environment.
+    Token,
+    /// `cargo:token-from-stdout <command>`, the first line printed by a command.
+    TokenFromStdout(Vec<String>),
+    /// A built-in provider we can't emulate, e.g. `cargo:paseto` or a platform keychain.
+    Unsupported(String),
+    /// An external program speaking the credential-provider protocol.
+    Process(Vec<String>),
+}
+
+impl Provider {
+    /// Parse a provider string, resolving the first word through `credential-alias`.
+    fn parse(args: Vec<String>, aliases: &HashMap<String, Vec<String>>) -> Result<Self> {
+        let (first, rest) = match args.split_first() {
+            Some((first, rest)) => (first.as_str(), rest),
+            None => return Err(ErrorKind::InvalidCargoConfig.into()),
+        };
+        Ok(match first {
+            "cargo:token" => Provider::Token,
+            "cargo:token-from-stdout" if !rest.is_empty() => {
+                Provider::TokenFromStdout(rest.to_vec())
+            }
+            "cargo:token-from-stdout" => return Err(ErrorKind::InvalidCargoConfig.into()),
+            builtin if builtin.starts_with("cargo:") => Provider::Unsupported(builtin.to_owned()),
+            alias => match aliases.get(alias) {
+                Some(command) => Provider::Process(command.iter().chain(rest).cloned().collect()),
+                None => Provider::Process(args),
+            },
+        })
+    }
+}
+
+/// A config value which is either a space separated string or an array of strings.
+fn string_list(value: &toml::Value) -> Option<Vec<String>> {
+    match value {
+        toml::Value::String(s) => Some(s.split_whitespace().map(ToOwned::to_owned).collect()),
+        toml::Value::Array(a) => a
+            .iter()
+            .map(|v| v.as_str().map(ToOwned::to_owned))
+            .collect(),
+        _ => None,
+    }
+}
+
+fn lookup<'a>(value: &'a toml::Value, path: &[&str]) -> Option<&'a toml::Value> {
+    path.iter().try_fold(value, |value, key| value.get(key))
+}
+
+/// Everything needed to get the token of one registry.
+#[derive(Debug)]
+struct CredentialConfig {
+    index_url: Url,
+    /// The registry's name in the cargo config, if it has one.
+    name: Option<String>,
+    /// Providers to try, in order.
+    providers: Vec<Provider>,
+    /// The token `cargo:token` provides.
+    token: Option<String>,
+}
+
+impl CredentialConfig {
+    /// Resolve the configuration for `registry` from cargo configs (the most specific first), the
+    /// contents of `credentials.toml` and the environment.
+    fn new(
+        registry: &Url,
+        configs: &[toml::Value],
+        credentials: Option<&toml::Value>,
+        env: &HashMap<String, String>,
+    ) -> Result<Self> {
+        let first = |path: &[&str]| configs.iter().find_map(|config| lookup(config, path));
+
+        let name = if is_crates_io(registry) {
+            Some(CRATES_IO_REGISTRY.to_owned())
+        } else {
+            configs.iter().find_map(|config| {
+                config
+                    .get("registries")?
+                    .as_table()?
+                    .iter()
+                    .find(|(_, r)| {
+                        r.get("index")
+                            .and_then(toml::Value::as_str)
+                            .is_some_and(|index| same_url(index, registry.as_str()))
+                    })
+                    .map(|(name, _)| name.clone())
+            })
+        };
+        // crates.io is configured under `[registry]`, other registries under `[registries.<name>]`.
+        let table_path: Option<Vec<&str>> = match name.as_deref() {
+            Some(CRATES_IO_REGISTRY) => Some(vec!["registry"]),
+            Some(name) => Some(vec!["registries", name]),
+            None => None,
+        };
+
+        let mut aliases = HashMap::new();
+        for config in configs.iter().rev() {
+            if let Some(table) = config.get("credential-alias").and_then(|a| a.as_table()) {
+                for (alias, command) in table {
+                    let command =
+                        string_list(command).chain_err(|| ErrorKind::InvalidCargoConfig)?;
+                    aliases.insert(alias.clone(), command);
+                }
+            }
+        }
+
+        let mut providers = Vec::new();
+        let specific = table_path.as_ref().and_then(|table_path| {
+            let mut path = table_path.clone();
+            path.push("credential-provider");
+            first(&path)
+        });
+        if let Some(provider) = specific {
+            let provider = string_list(provider).chain_err(|| ErrorKind::InvalidCargoConfig)?;
+            providers.push(Provider::parse(provider, &aliases)?);
+        } else {
+            // Arrays are merged across config files, and later providers take precedence.
+            for config in configs.iter() {
+                let global = match lookup(config, &["registry", "global-credential-providers"]) {
+                    Some(toml::Value::Array(global)) => global,
+                    Some(_) => return Err(ErrorKind::InvalidCargoConfig.into()),
+                    None => continue,
+                };
+                for provider in global.iter().rev() {
+                    let provider =
+                        string_list(provider).chain_err(|| ErrorKind::InvalidCargoConfig)?;
+                    providers.push(Provider::parse(provider, &aliases)?);
+                }
+            }
+            if providers.is_empty() {
+                providers.push(Provider::Token);
+            }
+        }
+
+        let token = match (name.as_deref(), table_path) {
+            (Some(name), Some(table_path)) => {
+                let env_key = if name == CRATES_IO_REGISTRY {
+                    "CARGO_REGISTRY_TOKEN".to_owned()
+                } else {
+                    format!(
+                        "CARGO_REGISTRIES_{}_TOKEN",
+                        name.to_ascii_uppercase().replace('-', "_")
+                    )
+                };
+                let mut path = table_path;
+                path.push("token");
+                env.get(&env_key)
+                    .cloned()
+                    .or_else(|| first(&path).and_then(|t| t.as_str()).map(ToOwned::to_owned))
+                    .or_else(|| {
+                        credentials
+                            .and_then(|c| lookup(c, &path))
+                            .and_then(|t| t.as_str())
+                            .map(ToOwned::to_owned)
+                    })
+            }
+            _ => None,
+        };
+
+        Ok(CredentialConfig {
+            index_url: registry.clone(),
+            name,
+            providers,
+            token,
+        })
+    }
+
+    /// Ask the providers in turn for a token.
+    fn token(&self) -> Result<Option<String>> {
+        for provider in &self.providers {
+            let token = match provider {
+                Provider::Token => self.token.clone(),
+                Provider::TokenFromStdout(command) => Some(self.token_from_stdout(command)?),
+                Provider::Unsupported(name) => {
+                    return Err(ErrorKind::UnsupportedCredentialProvider(name.clone()).into())
+                }
+                Provider::Process(command) => self.token_from_process(command)?,
+            };
+            if token.is_some() {
+                return Ok(token);
+            }
+        }
+        Ok(None)
+    }
+
+    fn command(&self, command: &[String]) -> Command {
+        let mut cmd = Command::new(&command[0]);
+        cmd.args(&command[1..])
+            .env("CARGO_REGISTRY_INDEX_URL", self.index_url.as_str());
+        if let Some(ref name) = self.name {
+            cmd.env("CARGO_REGISTRY_NAME_OPT", name);
+        }
+        cmd
+    }
+
+    fn token_from_stdout(&self, command: &[String]) -> Result<String> {
+        let output = self
+            .command(command)
+            .stderr(Stdio::inherit())
+            .output()
+            .chain_err(|| format!("Failed to run credential provider `{}`", command[0]))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Credential provider `{}` failed with {}",
+                command[0], output.status
+            )
+            .into());
+        }
+        String::from_utf8(output.stdout)
+            .ok()
+            .and_then(|stdout| stdout.lines().next().map(|line| line.trim().to_owned()))
+            .filter(|token| !token.is_empty())
+            .chain_err(|| format!("Credential provider `{}` printed no token", command[0]))
+    }
+
+    /// Run a credential provider plugin.
+    ///
+    /// ref: https://doc.rust-lang.org/cargo/reference/credential-provider-protocol.html
+    fn token_from_process(&self, command: &[String]) -> Result<Option<String>> {
+        let failed = || format!("Credential provider `{}` failed", command[0]);
+        let mut child = self
+            .command(command)
+            .arg("--cargo-plugin")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .chain_err(|| format!("Failed to run credential provider `{}`", command[0]))?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let mut stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+
+        let mut hello = String::new();
+        stdout.read_line(&mut hello).chain_err(failed)?;
+        let hello: ProviderHello = serde_json::from_str(&hello).chain_err(failed)?;
+        if !hello.v.contains(&PROTOCOL_VERSION) {
+            return Err(format!(
+                "Credential provider `{}` does not support protocol version {}",
+                command[0], PROTOCOL_VERSION
+            )
+            .into());
+        }
+
+        let request = serde_json::json!({
+            "v": PROTOCOL_VERSION,
+            "registry": {
+                "index-url": self.index_url.as_str(),
+                "name": self.name,
+            },
+            "kind": "get",
+            "operation": "read",
+            "args": command[1..],
+        });
+        writeln!(stdin, "{}", request).chain_err(failed)?;
+        let mut response = String::new();
+        stdout.read_line(&mut response).chain_err(failed)?;
+        drop(stdin);
+        child.wait().chain_err(failed)?;
+
+        match serde_json::from_str(&response).chain_err(failed)? {
+            ProviderResponse::Ok(ProviderToken { token }) => Ok(Some(token)),
+            ProviderResponse::Err(ProviderError { kind, message }) => match kind.as_str() {
+                "not-found" | "url-not-supported" => Ok(None),
+                _ => Err(message
+                    .map(|message| format!("{}: {}", failed(), message))
+                    .unwrap_or_else(failed)
+                    .into()),
+            },
+        }
+    }
+}
+
+/// The only version of the credential-provider protocol.
+const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Deserialize)]
+struct ProviderHello {
+    v: Vec<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+enum ProviderResponse {
+    Ok(ProviderToken),
+    Err(ProviderError),
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderToken {
+    token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderError {
+    kind: String,
+    message: Option<String>,
+}
+
+fn is_crates_io(registry: &Url) -> bool {
+    same_url(registry.as_str(), CRATES_IO_INDEX)
+        || same_url(registry.as_str(), CRATES_IO_SPARSE_INDEX)
+}
+
+fn same_url(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/') == b.trim_end_matches('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(
+        registry: &str,
+        configs: &[&str],
+        credentials: Option<&str>,
+        env: &[(&str, &str)],
+    ) -> CredentialConfig {
+        let configs: Vec<toml::Value> =
+            configs.iter().map(|c| toml::from_str(c).unwrap()).collect();
+        let credentials: Option<toml::Value> = credentials.map(|c| toml::from_str(c).unwrap());
+        let env = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        CredentialConfig::new(
+            &Url::parse(registry).unwrap(),
+            &configs,
+            credentials.as_ref(),
+            &env,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn token_from_credentials_and_env() {
+        let registry = "sparse+https://example.com/index/";
+        let configs = [r#"registries.private.index = "sparse+https://example.com/index""#];
+        let credentials = Some("[registries.private]\ntoken = \"from-file\"");
+
+        let cfg = config(registry, &configs, credentials, &[]);
+        assert_eq!(cfg.name.as_deref(), Some("private"));
+        assert_eq!(cfg.providers, vec![Provider::Token]);
+        assert_eq!(cfg.token().unwrap().as_deref(), Some("from-file"));
+
+        let env = [("CARGO_REGISTRIES_PRIVATE_TOKEN", "from-env")];
+        let cfg = config(registry, &configs, credentials, &env);
+        assert_eq!(cfg.token().unwrap().as_deref(), Some("from-env"));
+
+        let cfg = config(
+            CRATES_IO_SPARSE_INDEX,
+            &[],
+            Some("[registry]\ntoken = \"io\""),
+            &[],
+        );
+        assert_eq!(cfg.token().unwrap().as_deref(), Some("io"));
+
+        let cfg = config(
+            "sparse+https://unknown.example/",
+            &configs,
+            credentials,
+            &[],
+        );
+        assert_eq!(cfg.token().unwrap(), None);
+    }
+
+    #[test]
+    fn providers_and_aliases() {
+        let configs = [
+            r#"
+            [registry]
+            global-credential-providers = ["cargo:token", "my-alias --flag"]
+
+            [credential-alias]
+            my-alias = ["/usr/bin/provider", "--account", "me"]
+            "#,
+            r#"
+            [registries.special]
+            index = "https://example.com/git-index"
+            credential-provider = "cargo:token-from-stdout get-token special"
+            "#,
+        ];
+
+        let cfg = config("sparse+https://example.com/", &configs, None, &[]);
+        assert_eq!(
+            cfg.providers,
+            vec![
+                Provider::Process(vec![
+                    "/usr/bin/provider".to_owned(),
+                    "--account".to_owned(),
+                    "me".to_owned(),
+                    "--flag".to_owned()
+                ]),
+                Provider::Token,
+            ]
+        );
+
+        let cfg = config("https://example.com/git-index", &configs, None, &[]);
+        assert_eq!(
+            cfg.providers,
+            vec![Provider::TokenFromStdout(vec![
+                "get-token".to_owned(),
+                "special".to_owned()
+            ])]
+        );
+
+        let cfg = config(
+            CRATES_IO_INDEX,
+            &[r#"registry.credential-provider = "cargo:paseto""#],
+            None,
+            &[],
+        );
+        assert_eq!(
+            cfg.providers,
+            vec![Provider::Unsupported("cargo:paseto".to_owned())]
+        );
+        assert!(cfg.token().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn plugin_protocol() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let plugin = dir.path().join("provider");
+        let request = dir.path().join("request");
+        std::fs::write(
+            &plugin,
+            format!(
+                r#"#!/bin/sh
+echo '{{"v":[1]}}'
+read -r line
+echo "$line" > '{}'
+case "$line" in
+  *private*) echo '{{"Ok":{{"kind":"get","token":"v3.public.signed","cache":"session","operation_independent":false}}}}' ;;
+  *) echo '{{"Err":{{"kind":"not-found"}}}}' ;;
+esac
+"#,
+                request.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let provider = format!(
+            r#"registry.global-credential-providers = ["{}"]"#,
+            plugin.display()
+        );
+        let configs = [
+            provider.as_str(),
+            r#"registries.private.index = "sparse+https://example.com/""#,
+        ];
+        let cfg = config("sparse+https://example.com/", &configs, None, &[]);
+        assert_eq!(cfg.token().unwrap().as_deref(), Some("v3.public.signed"));
+        let request: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&request).unwrap()).unwrap();
+        assert_eq!(request["kind"], "get");
+        assert_eq!(request["operation"], "read");
+        assert_eq!(
+            request["registry"]["index-url"],
+            "sparse+https://example.com/"
+        );
+        assert_eq!(request["registry"]["name"], "private");
+
+        let cfg = config("sparse+https://other.example/", &configs, None, &[]);
+        assert_eq!(cfg.token().unwrap(), None);
+    }
+}
@@ -1,3 +1,8 @@
+use crate::dependency_path::portable_path;
+use crate::errors::*;
+#[cfg(feature = "index")]
+use crate::index::CrateVersionInfo;
+use crate::pkgid::GitReference;
 use std::iter::FromIterator;
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
@@ -10,10 +15,38 @@ enum DependencySource {
     Git {
         repo: String,
         branch: Option<String>,
+        tag: Option<String>,
+        rev: Option<String>,
     },
+    /// `workspace = true`, inheriting the source from `[workspace.dependencies]`
+    Workspace,
 }
 
 /// A dependency handled by Cargo
+///
+/// Dependencies are built up with the `set_*` methods, and can be checked with
+/// [`validate`](Dependency::validate) before being written to a manifest.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::Dependency;
+///
+///   let dep = Dependency::new("serde")
+///       .set_version("1.0")
+///       .set_registry("mirror")
+///       .set_features(Some(vec!["derive".to_owned()]))
+///       .set_public(true);
+///   assert!(dep.validate().is_ok());
+///
+///   let (key, item) = dep.to_toml();
+///   assert_eq!(Dependency::from_toml(&key, &item), Some(dep));
+///
+///   let dep = Dependency::new("serde")
+///       .set_git("https://github.com/serde-rs/serde", Some("master".to_owned()))
+///       .set_version("^^1");
+///   assert!(dep.validate().is_err());
+/// ```
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub struct Dependency {
     /// The name of the dependency (as it is set in its `Cargo.toml` and known to crates.io)
@@ -26,6 +59,13 @@ pub struct Dependency {
     /// If the dependency is renamed, this is the new name for the dependency
     /// as a string.  None if it is not renamed.
     rename: Option<String>,
+    public: bool,
+    /// The artifacts depended on, like `bin` or `cdylib`, for an artifact dependency.
+    artifact: Option<Vec<String>>,
+    /// Whether the library is depended on as well as the artifacts.
+    lib: bool,
+    /// The target the artifacts are built for, if not that of the dependent.
+    artifact_target: Option<String>,
 }
 
 impl Default for Dependency {
@@ -33,6 +73,10 @@ impl Default for Dependency {
         Dependency {
             name: "".into(),
             rename: None,
+            public: false,
+            artifact: None,
+            lib: false,
+            artifact_target: None,
             optional: false,
             features: None,
             default_features: true,
@@ -77,10 +121,41 @@ impl Dependency {
         self.source = DependencySource::Git {
             repo: repo.into(),
             branch,
+            tag: None,
+            rev: None,
         };
         self
     }
 
+    /// Set dependency to a given tag of a repository
+    pub fn set_git_tag(mut self, repo: &str, tag: &str) -> Dependency {
+        self.source = DependencySource::Git {
+            repo: repo.into(),
+            branch: None,
+            tag: Some(tag.into()),
+            rev: None,
+        };
+        self
+    }
+
+    /// Set dependency to a given commit of a repository
+    pub fn set_git_rev(mut self, repo: &str, rev: &str) -> Dependency {
+        self.source = DependencySource::Git {
+            repo: repo.into(),
+            branch: None,
+            tag: None,
+            rev: Some(rev.into()),
+        };
+        self
+    }
+
+    /// Inherit the dependency from `[workspace.dependencies]`, dropping any version, path,
+    /// registry or git repository
+    pub fn set_workspace(mut self) -> Dependency {
+        self.source = DependencySource::Workspace;
+        self
+    }
+
     /// Set dependency to a given path
     pub fn set_path(mut self, path: &str) -> Dependency {
         let old_version = match self.source {
@@ -89,7 +164,7 @@ impl Dependency {
         };
         self.source = DependencySource::Version {
             version: old_version,
-            path: Some(path.replace('\\', "/")),
+            path: Some(portable_path(path)),
             registry: None,
         };
         self
@@ -104,8 +179,7 @@ impl Dependency {
     pub fn set_features(mut self, features: Option<Vec<String>>) -> Dependency {
         self.features = features.map(|f| {
             f.iter()
-                .map(|x| x.split(' ').map(String::from))
-                .flatten()
+                .flat_map(|x| x.split(' ').map(String::from))
                 .filter(|s| !s.is_empty())
                 .collect::<Vec<String>>()
         });
@@ -124,11 +198,46 @@ impl Dependency {
         self
     }
 
+    /// Set whether the dependency is public, i.e. part of the crate's API
+    pub fn set_public(mut self, public: bool) -> Dependency {
+        self.public = public;
+        self
+    }
+
+    /// Depend on artifacts of the dependency, like `bin` or `cdylib`, rather than (or with `lib`,
+    /// as well as) its library; this needs the unstable `bindeps` feature of cargo.
+    pub fn set_artifact(mut self, artifact: Option<Vec<String>>) -> Dependency {
+        self.artifact = artifact;
+        self
+    }
+
+    /// Set whether the library of an artifact dependency is depended on as well
+    pub fn set_lib(mut self, lib: bool) -> Dependency {
+        self.lib = lib;
+        self
+    }
+
+    /// Set the target the artifacts of the dependency are built for, like `wasm32-wasi` or
+    /// `target` for that of the build
+    pub fn set_artifact_target(mut self, target: Option<&str>) -> Dependency {
+        self.artifact_target = target.map(String::from);
+        self
+    }
+
     /// Get the dependency name as defined in the manifest,
     /// that is, either the alias (rename field if Some),
     /// or the official package name (name field).
     pub fn name_in_manifest(&self) -> &str {
-        &self.rename().unwrap_or(&self.name)
+        self.rename().unwrap_or(&self.name)
+    }
+
+    /// The name to show for the dependency in messages: its key, and the package it is renamed
+    /// from if it is, like `alias (package dep)`.
+    pub fn display_name(&self) -> String {
+        match self.rename() {
+            Some(rename) => format!("{} (package {})", rename, self.name),
+            None => self.name.clone(),
+        }
     }
 
     /// Set the value of registry for the dependency
@@ -163,6 +272,255 @@ impl Dependency {
         self.rename.as_deref()
     }
 
+    /// Get whether the dependency is optional
+    pub fn optional(&self) -> bool {
+        self.optional
+    }
+
+    /// Get whether the default features of the dependency are enabled
+    pub fn default_features(&self) -> bool {
+        self.default_features
+    }
+
+    /// Get whether the dependency is public
+    pub fn public(&self) -> bool {
+        self.public
+    }
+
+    /// Get the artifacts depended on, for an artifact dependency
+    pub fn artifact(&self) -> Option<&[String]> {
+        self.artifact.as_deref()
+    }
+
+    /// Get whether the library of an artifact dependency is depended on as well
+    pub fn lib(&self) -> bool {
+        self.lib
+    }
+
+    /// Get the target the artifacts of the dependency are built for (if any)
+    pub fn artifact_target(&self) -> Option<&str> {
+        self.artifact_target.as_deref()
+    }
+
+    /// Get the local path of the dependency (if any)
+    pub fn path(&self) -> Option<&str> {
+        match self.source {
+            DependencySource::Version { ref path, .. } => path.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Get the repository of the dependency, if it is a git dependency
+    pub fn git_repo(&self) -> Option<&str> {
+        match self.source {
+            DependencySource::Git { ref repo, .. } => Some(repo),
+            _ => None,
+        }
+    }
+
+    /// Get the branch, tag or commit of a git dependency, unless it uses the default branch
+    pub fn git_reference(&self) -> Option<GitReference> {
+        match self.source {
+            DependencySource::Git {
+                ref branch,
+                ref tag,
+                ref rev,
+                ..
+            } => branch
+                .clone()
+                .map(GitReference::Branch)
+                .or_else(|| tag.clone().map(GitReference::Tag))
+                .or_else(|| rev.clone().map(GitReference::Rev)),
+            _ => None,
+        }
+    }
+
+    /// Get the registry of the dependency, if it is not the default one
+    pub fn registry(&self) -> Option<&str> {
+        match self.source {
+            DependencySource::Version { ref registry, .. } => registry.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Whether the dependency is inherited from `[workspace.dependencies]`
+    pub fn is_workspace(&self) -> bool {
+        self.source == DependencySource::Workspace
+    }
+
+    /// Check that the dependency can be written to a manifest as it is.
+    ///
+    /// This fails with [`ErrorKind::EmptyCrateName`] for a dependency without a name, with
+    /// [`ErrorKind::ParseVersion`] for an invalid version requirement, and with
+    /// [`ErrorKind::InvalidDependency`] for a dependency without a source, a git dependency with
+    /// more than one of a branch, tag and commit, an empty feature, an inherited dependency
+    /// which is renamed, or `lib` or an artifact target without artifacts.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: &str| -> Result<()> {
+            Err(ErrorKind::InvalidDependency(self.name.clone(), reason.to_owned()).into())
+        };
+        if self.name.is_empty() {
+            return Err(ErrorKind::EmptyCrateName.into());
+        }
+        if let Some(version) = self.version() {
+            if semver::VersionReq::parse(version).is_err() {
+                return Err(ErrorKind::ParseVersion(version.into(), self.name.clone()).into());
+            }
+        }
+        match self.source {
+            DependencySource::Version {
+                version: None,
+                path: None,
+                ..
+            } => return invalid("it has no version, path or git repository"),
+            DependencySource::Git {
+                ref branch,
+                ref tag,
+                ref rev,
+                ..
+            } if [branch, tag, rev].iter().filter(|r| r.is_some()).count() > 1 => {
+                return invalid("only one of a branch, tag or rev can be given")
+            }
+            DependencySource::Workspace if self.rename.is_some() => {
+                return invalid("an inherited dependency can't be renamed")
+            }
+            _ => {}
+        }
+        if let Some(ref features) = self.features {
+            if features.iter().any(|f| f.trim().is_empty()) {
+                return invalid("features can't be empty");
+            }
+        }
+        if self.artifact.is_none() && (self.lib || self.artifact_target.is_some()) {
+            return invalid("`lib` and `target` need an `artifact`");
+        }
+        Ok(())
+    }
+
+    /// Convert the dependency to a tag of a git repository, e.g. to try out an unreleased fix.
+    ///
+    /// Any version, path or registry is dropped; the name, rename, features and flags are kept.
+    pub fn to_git(self, repo: &str, tag: &str) -> Dependency {
+        self.set_git_tag(repo, tag)
+    }
+
+    /// Convert the dependency to a version requirement on a registry, dropping any git repository
+    /// or path. `registry` is `None` for the default registry.
+    ///
+    /// The name, rename, features and flags are kept.
+    pub fn to_registry(mut self, version: &str, registry: Option<&str>) -> Dependency {
+        self.source = DependencySource::Version {
+            version: None,
+            path: None,
+            registry: registry.map(String::from),
+        };
+        self.set_version(version)
+    }
+
+    /// Convert a git or path dependency to the registry release of `version`, if it is published.
+    ///
+    /// `published` are the releases of the crate, as returned by
+    /// [`get_crate_versions`](crate::get_crate_versions). This fails with
+    /// [`ErrorKind::NoMatchingVersion`] if `version` was never published or has been yanked.
+    #[cfg(feature = "index")]
+    pub fn to_published(
+        self,
+        version: &semver::Version,
+        published: &[CrateVersionInfo],
+        registry: Option<&str>,
+    ) -> Result<Dependency> {
+        let releases: Vec<_> = published
+            .iter()
+            .filter(|release| release.version == *version)
+            .collect();
+        if !releases.iter().any(|release| !release.yanked) {
+            return Err(ErrorKind::NoMatchingVersion(
+                self.name.clone(),
+                !releases.is_empty(),
+                false,
+            )
+            .into());
+        }
+        Ok(self.to_registry(&version.to_string(), registry))
+    }
+
+    /// Read a dependency back from its entry in a dependency table.
+    ///
+    /// `key` is the name the entry is listed under, which differs from the package name for
+    /// renamed dependencies. Returns `None` if `item` is neither a version string nor a table.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    ///   use cargo_edit::Dependency;
+    ///
+    ///   let item = toml_edit::value("1.0");
+    ///   let dep = Dependency::from_toml("serde", &item).unwrap();
+    ///   assert_eq!(dep, Dependency::new("serde").set_version("1.0"));
+    /// ```
+    pub fn from_toml(key: &str, item: &toml_edit::Item) -> Option<Dependency> {
+        if let Some(version) = item.as_str() {
+            return Some(Dependency::new(key).set_version(version));
+        }
+
+        let table = item.as_table_like()?;
+        let get_str = |field: &str| table.get(field).and_then(|v| v.as_str()).map(String::from);
+        let get_bool = |field: &str| table.get(field).and_then(toml_edit::Item::as_bool);
+
+        let source = match get_str("git") {
+            Some(repo) => DependencySource::Git {
+                repo,
+                branch: get_str("branch"),
+                tag: get_str("tag"),
+                rev: get_str("rev"),
+            },
+            None if get_bool("workspace") == Some(true) => DependencySource::Workspace,
+            None => DependencySource::Version {
+                version: get_str("version"),
+                path: get_str("path"),
+                registry: get_str("registry"),
+            },
+        };
+        let features = table.get("features").and_then(|f| f.as_array()).map(|f| {
+            f.iter()
+                .filter_map(|f| f.as_str().map(String::from))
+                .collect()
+        });
+        // A single artifact may be written without an array.
+        let artifact = table.get("artifact").and_then(|artifact| {
+            artifact
+                .as_str()
+                .map(|kind| vec![kind.to_owned()])
+                .or_else(|| {
+                    artifact.as_array().map(|kinds| {
+                        kinds
+                            .iter()
+                            .filter_map(|kind| kind.as_str().map(String::from))
+                            .collect()
+                    })
+                })
+        });
+        let (name, rename) = match get_str("package") {
+            Some(package) => (package, Some(key.to_owned())),
+            None => (key.to_owned(), None),
+        };
+
+        Some(Dependency {
+            name,
+            rename,
+            public: get_bool("public").unwrap_or(false),
+            artifact,
+            lib: get_bool("lib").unwrap_or(false),
+            artifact_target: get_str("target"),
+            optional: get_bool("optional").unwrap_or(false),
+            features,
+            default_features: get_bool("default-features")
+                .or_else(|| get_bool("default_features"))
+                .unwrap_or(true),
+            source,
+        })
+    }
+
     /// Convert dependency to TOML
     ///
     /// Returns a tuple with the dependency's name and either the version as a `String`
@@ -171,11 +529,12 @@ impl Dependency {
     /// an `InlineTable` is returned in any case.)
     pub fn to_toml(&self) -> (String, toml_edit::Item) {
         let data: toml_edit::Item = match (
-            self.optional,
+            self.optional || self.artifact.is_some(),
             self.features.as_ref(),
             self.default_features,
             self.source.clone(),
             self.rename.as_ref(),
+            self.public,
         ) {
             // Extra short when version flag only
             (
@@ -188,9 +547,10 @@ impl Dependency {
                     registry: None,
                 },
                 None,
+                false,
             ) => toml_edit::value(v),
             // Other cases are represented as an inline table
-            (optional, features, default_features, source, rename) => {
+            (optional, features, default_features, source, rename, public) => {
                 let mut data = toml_edit::InlineTable::default();
 
                 match source {
@@ -209,9 +569,19 @@ impl Dependency {
                             data.get_or_insert("registry", r);
                         }
                     }
-                    DependencySource::Git { repo, branch } => {
+                    DependencySource::Git {
+                        repo,
+                        branch,
+                        tag,
+                        rev,
+                    } => {
                         data.get_or_insert("git", repo);
                         branch.map(|branch| data.get_or_insert("branch", branch));
+                        tag.map(|tag| data.get_or_insert("tag", tag));
+                        rev.map(|rev| data.get_or_insert("rev", rev));
+                    }
+                    DependencySource::Workspace => {
+                        data.get_or_insert("workspace", true);
                     }
                 }
                 if self.optional {
@@ -227,6 +597,24 @@ impl Dependency {
                 if rename.is_some() {
                     data.get_or_insert("package", self.name.clone());
                 }
+                if public {
+                    data.get_or_insert("public", public);
+                }
+                if let Some(artifact) = &self.artifact {
+                    match artifact.as_slice() {
+                        [kind] => data.get_or_insert("artifact", kind.clone()),
+                        kinds => data.get_or_insert(
+                            "artifact",
+                            toml_edit::Value::from_iter(kinds.iter().cloned()),
+                        ),
+                    };
+                }
+                if self.lib {
+                    data.get_or_insert("lib", true);
+                }
+                if let Some(target) = &self.artifact_target {
+                    data.get_or_insert("target", target.clone());
+                }
 
                 data.fmt();
                 toml_edit::value(toml_edit::Value::InlineTable(data))
@@ -241,6 +629,162 @@ impl Dependency {
 mod tests {
     use crate::dependency::Dependency;
 
+    #[test]
+    fn from_toml_round_trip() {
+        let deps = [
+            Dependency::new("dep").set_version("1.0"),
+            Dependency::new("dep")
+                .set_version("1.0")
+                .set_registry("alternative")
+                .set_optional(true)
+                .set_default_features(false)
+                .set_features(Some(vec!["std".to_owned()])),
+            Dependency::new("dep").set_path("../dep"),
+            Dependency::new("dep").set_git("https://github.com/a/b", Some("main".to_owned())),
+            Dependency::new("dep").set_git_tag("https://github.com/a/b", "v1.0.0"),
+            Dependency::new("dep")
+                .set_version("0.2")
+                .set_rename("alias"),
+            Dependency::new("dep").set_git_rev("https://github.com/a/b", "0a1b2c3"),
+            Dependency::new("dep")
+                .set_workspace()
+                .set_optional(true)
+                .set_features(Some(vec!["std".to_owned()])),
+            Dependency::new("dep").set_version("1.0").set_public(true),
+            Dependency::new("dep")
+                .set_path("../dep")
+                .set_artifact(Some(vec!["bin".to_owned(), "cdylib".to_owned()]))
+                .set_lib(true)
+                .set_artifact_target(Some("wasm32-wasi")),
+        ];
+        for dep in deps.iter() {
+            let (key, item) = dep.to_toml();
+            assert_eq!(Dependency::from_toml(&key, &item).as_ref(), Some(dep));
+        }
+        assert_eq!(Dependency::from_toml("dep", &toml_edit::value(1)), None);
+    }
+
+    #[test]
+    fn validation() {
+        use crate::errors::ErrorKind;
+        use crate::pkgid::GitReference;
+
+        let kind = |dep: Dependency| dep.validate().unwrap_err().kind().to_string();
+        assert!(Dependency::new("dep").set_version("1.0").validate().is_ok());
+        assert!(Dependency::new("dep").set_workspace().validate().is_ok());
+        assert_eq!(
+            kind(Dependency::new("dep").set_version("1.0").set_lib(true)),
+            "Invalid dependency `dep`: `lib` and `target` need an `artifact`"
+        );
+        assert!(matches!(
+            Dependency::new("")
+                .set_version("1.0")
+                .validate()
+                .unwrap_err()
+                .kind(),
+            ErrorKind::EmptyCrateName
+        ));
+        assert!(matches!(
+            Dependency::new("dep")
+                .set_version("one")
+                .validate()
+                .unwrap_err()
+                .kind(),
+            ErrorKind::ParseVersion(_, _)
+        ));
+        assert_eq!(
+            kind(Dependency::new("dep")),
+            "Invalid dependency `dep`: it has no version, path or git repository"
+        );
+        assert_eq!(
+            kind(Dependency::new("dep").set_workspace().set_rename("alias")),
+            "Invalid dependency `dep`: an inherited dependency can't be renamed"
+        );
+        assert_eq!(
+            kind(
+                Dependency::new("dep")
+                    .set_version("1.0")
+                    .set_features(Some(vec!["\t".to_owned()]))
+            ),
+            "Invalid dependency `dep`: features can't be empty"
+        );
+
+        let item: toml_edit::Item = toml_edit::value(
+            "{ git = 'https://github.com/a/b', tag = 'v1', rev = '0a1b2c3' }"
+                .parse::<toml_edit::Value>()
+                .unwrap(),
+        );
+        let dep = Dependency::from_toml("dep", &item).unwrap();
+        assert_eq!(
+            dep.git_reference(),
+            Some(GitReference::Tag("v1".to_owned()))
+        );
+        assert_eq!(
+            kind(dep),
+            "Invalid dependency `dep`: only one of a branch, tag or rev can be given"
+        );
+    }
+
+    #[test]
+    fn convert_between_sources() {
+        let dep = Dependency::new("dep")
+            .set_version("1.0")
+            .set_rename("alias")
+            .set_optional(true)
+            .set_features(Some(vec!["std".to_owned()]));
+
+        let git = dep.clone().to_git("https://github.com/a/dep", "v1.1.0");
+        assert_eq!(git.git_repo(), Some("https://github.com/a/dep"));
+        assert_eq!(git.version(), None);
+        let (key, item) = git.to_toml();
+        assert_eq!(key, "alias");
+        let table = item.as_inline_table().unwrap();
+        assert_eq!(table.get("tag").unwrap().as_str(), Some("v1.1.0"));
+        assert_eq!(table.get("package").unwrap().as_str(), Some("dep"));
+        assert_eq!(table.get("optional").unwrap().as_bool(), Some(true));
+
+        let back = git.to_registry("1.0", None);
+        assert_eq!(back, dep);
+
+        let path = Dependency::new("dep").set_path("../dep").set_version("1.0");
+        let registry = path.to_registry("1.0", Some("alternative"));
+        assert_eq!(registry.path(), None);
+        assert_eq!(registry.registry(), Some("alternative"));
+    }
+
+    #[cfg(feature = "index")]
+    #[test]
+    fn convert_to_published_release() {
+        use crate::errors::ErrorKind;
+        use crate::index::CrateVersionInfo;
+
+        let v = |s| semver::Version::parse(s).unwrap();
+        let mut yanked = CrateVersionInfo::new("dep", v("1.1.0"));
+        yanked.yanked = true;
+        let published = vec![CrateVersionInfo::new("dep", v("1.0.0")), yanked];
+        let dep = Dependency::new("dep").set_git("https://github.com/a/dep", None);
+
+        let converted = dep
+            .clone()
+            .to_published(&v("1.0.0"), &published, None)
+            .unwrap();
+        assert_eq!(converted, Dependency::new("dep").set_version("1.0.0"));
+
+        let err = dep
+            .clone()
+            .to_published(&v("1.1.0"), &published, None)
+            .unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::NoMatchingVersion(_, true, false)
+        ));
+        let err = dep.to_published(&v("2.0.0"), &published, None).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::NoMatchingVersion(_, false, false)
+        ));
+    }
+
     #[test]
     fn to_toml_simple_dep() {
         let toml = Dependency::new("dep").to_toml();
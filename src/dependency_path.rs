@@ -0,0 +1,216 @@
+//! The `path` of path dependencies as written into manifests: with forward slashes, which cargo
+//! understands on every platform, and relative to the manifest where possible, so that manifests
+//! work in any checkout.
+//!
+//! Windows paths are written without their verbatim prefix (`\\?\C:\…` or `\\?\UNC\…`), which
+//! `canonicalize` adds. A path on another drive or network share than the manifest can't be made
+//! relative, and is written as an absolute path instead.
+use crate::verbosity::verbosity;
+use std::path::Path;
+
+/// `path` with forward slashes and without a Windows verbatim prefix.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::portable_path;
+///
+///   assert_eq!(portable_path(r"..\sibling\crate"), "../sibling/crate");
+///   assert_eq!(portable_path(r"\\?\C:\work\crate"), "C:/work/crate");
+///   assert_eq!(portable_path(r"\\?\UNC\server\share\crate"), "//server/share/crate");
+/// ```
+pub fn portable_path(path: &str) -> String {
+    let path = if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", unc)
+    } else if let Some(local) = path
+        .strip_prefix(r"\\?\")
+        .or_else(|| path.strip_prefix(r"\\.\"))
+    {
+        local.to_owned()
+    } else {
+        path.to_owned()
+    };
+    path.replace('\\', "/")
+}
+
+/// `path` relative to the directory `base`, with forward slashes, or `None` if it can't be, like
+/// for a path on another drive.
+///
+/// Both paths are taken as they are, without resolving symlinks; `.` and `..` are resolved
+/// lexically.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::relative_path;
+///
+///   let work = std::env::current_dir().unwrap();
+///   assert_eq!(
+///       relative_path(&work.join("app"), &work.join("libs/foo")),
+///       Some("../libs/foo".to_owned())
+///   );
+/// ```
+pub fn relative_path(base: &Path, path: &Path) -> Option<String> {
+    relative_to(
+        &base.to_string_lossy(),
+        &path.to_string_lossy(),
+        cfg!(windows),
+    )
+}
+
+/// The `path` of a dependency on the crate at `path` for the manifest at `manifest_path`:
+/// relative to the manifest if possible, absolute with a warning otherwise.
+pub(crate) fn dependency_path(manifest_path: &Path, path: &Path) -> String {
+    let manifest_dir = manifest_path.parent().unwrap_or_else(|| Path::new("."));
+    let absolute = |path: &Path| {
+        crate::logical_path::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_owned())
+    };
+    if let Some(relative) = relative_path(&absolute(manifest_dir), &absolute(path)) {
+        return relative;
+    }
+    if !verbosity().is_quiet() {
+        eprintln!(
+            "WARN: {} can't be reached with a relative path from {}, e.g. as it is on another \
+             drive, so its absolute path is written",
+            path.display(),
+            manifest_dir.display()
+        );
+    }
+    portable_path(&path.to_string_lossy())
+}
+
+fn relative_to(base: &str, path: &str, windows: bool) -> Option<String> {
+    let (base_root, base) = split_root(base, windows)?;
+    let (root, path) = split_root(path, windows)?;
+    let same = |a: &str, b: &str| {
+        if windows {
+            a.eq_ignore_ascii_case(b)
+        } else {
+            a == b
+        }
+    };
+    if !same(&base_root, &root) {
+        return None;
+    }
+    let common = base
+        .iter()
+        .zip(&path)
+        .take_while(|(a, b)| same(a, b))
+        .count();
+    let parts: Vec<&str> = std::iter::repeat_n("..", base.len() - common)
+        .chain(path[common..].iter().copied())
+        .collect();
+    if parts.is_empty() {
+        Some(".".to_owned())
+    } else {
+        Some(parts.join("/"))
+    }
+}
+
+/// The root of the absolute `path`, like `/`, `C:` or `//server/share`, and its components with
+/// `.` and `..` resolved, or `None` for a relative path.
+fn split_root(path: &str, windows: bool) -> Option<(String, Vec<&str>)> {
+    let (root, rest) = if windows {
+        let verbatim = path.strip_prefix(r"\\?\");
+        let path = verbatim.unwrap_or(path);
+        let is_separator = |c: char| c == '/' || c == '\\';
+        if let Some(unc) = verbatim
+            .and_then(|path| path.strip_prefix(r"UNC\"))
+            .or_else(|| path.strip_prefix(r"\\"))
+            .or_else(|| path.strip_prefix("//"))
+        {
+            let mut parts = unc.splitn(3, is_separator);
+            let server = parts.next().filter(|server| !server.is_empty())?;
+            let share = parts.next().filter(|share| !share.is_empty())?;
+            (
+                format!("//{}/{}", server, share),
+                parts.next().unwrap_or_default(),
+            )
+        } else {
+            let mut chars = path.chars();
+            match (chars.next(), chars.next(), chars.next()) {
+                (Some(drive), Some(':'), Some(separator))
+                    if drive.is_ascii_alphabetic() && is_separator(separator) =>
+                {
+                    (format!("{}:", drive), &path[3..])
+                }
+                _ => return None,
+            }
+        }
+    } else {
+        ("/".to_owned(), path.strip_prefix('/')?)
+    };
+    let mut components = Vec::new();
+    for component in rest.split(|c| c == '/' || (windows && c == '\\')) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    Some((root, components))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_windows_paths() {
+        let relative = |base, path| relative_to(base, path, true);
+        assert_eq!(
+            relative(r"C:\work\app", r"C:\work\libs\foo"),
+            Some("../libs/foo".to_owned())
+        );
+        // Drive letters and names differ in case only.
+        assert_eq!(
+            relative(r"c:\Work\app", r"\\?\C:\work\app\crates\foo"),
+            Some("crates/foo".to_owned())
+        );
+        assert_eq!(
+            relative(r"\\server\share\app", r"\\?\UNC\server\share\libs\.\foo"),
+            Some("../libs/foo".to_owned())
+        );
+        assert_eq!(
+            relative(r"C:\work\app", r"C:/work/app/"),
+            Some(".".to_owned())
+        );
+        assert_eq!(relative(r"C:\work\app", r"D:\libs\foo"), None);
+        assert_eq!(relative(r"C:\work\app", r"\\server\share\foo"), None);
+        assert_eq!(relative(r"C:\work\app", r"libs\foo"), None);
+    }
+
+    #[test]
+    fn relative_unix_paths() {
+        let relative = |base, path| relative_to(base, path, false);
+        assert_eq!(
+            relative("/work/app", "/work/app/../libs/foo"),
+            Some("../libs/foo".to_owned())
+        );
+        // Names differing in case are different directories.
+        assert_eq!(
+            relative("/work/App", "/work/app/foo"),
+            Some("../app/foo".to_owned())
+        );
+        assert_eq!(relative("/work/app", "libs/foo"), None);
+    }
+
+    #[test]
+    fn falls_back_to_absolute_paths() {
+        let manifest = Path::new("/work/app/Cargo.toml");
+        assert_eq!(
+            dependency_path(manifest, Path::new("/work/libs/foo")),
+            "../libs/foo"
+        );
+        if cfg!(windows) {
+            assert_eq!(
+                dependency_path(Path::new(r"C:\app\Cargo.toml"), Path::new(r"D:\libs\foo")),
+                "D:/libs/foo"
+            );
+        }
+    }
+}
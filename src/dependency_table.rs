@@ -0,0 +1,283 @@
+//! Every dependency entry of a manifest, whichever table it is in.
+use crate::cfg::Platform;
+use crate::dependency::Dependency;
+use crate::errors::*;
+use std::path::PathBuf;
+
+/// The section a dependency is declared in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyKind {
+    /// `[dependencies]`
+    #[default]
+    Normal,
+    /// `[build-dependencies]`
+    Build,
+    /// `[dev-dependencies]`
+    Dev,
+}
+
+/// A dependency table: `[dependencies]`, `[dev-dependencies]` or `[build-dependencies]`, any of
+/// them under `[target.<platform>]`, or `[workspace.dependencies]`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DependencyTable {
+    /// Which kind of dependencies the table lists; `Normal` for `[workspace.dependencies]`
+    pub kind: DependencyKind,
+    /// The key of the `[target]` table it is in, like `cfg(unix)`, if any
+    pub target: Option<String>,
+    /// Whether this is `[workspace.dependencies]`, which members inherit from
+    pub workspace: bool,
+}
+
+impl DependencyTable {
+    /// The table of `kind` dependencies, for all targets.
+    pub fn new(kind: DependencyKind) -> Self {
+        DependencyTable {
+            kind,
+            target: None,
+            workspace: false,
+        }
+    }
+
+    /// The table of `kind` dependencies under `[target.<target>]`.
+    pub fn for_target(kind: DependencyKind, target: &str) -> Self {
+        DependencyTable {
+            target: Some(target.to_owned()),
+            ..Self::new(kind)
+        }
+    }
+
+    /// `[workspace.dependencies]`.
+    pub fn workspace() -> Self {
+        DependencyTable {
+            workspace: true,
+            ..Self::new(DependencyKind::Normal)
+        }
+    }
+
+    /// The name of the table, e.g. `dev-dependencies`, without any enclosing tables.
+    pub fn kind_name(&self) -> &'static str {
+        match self.kind {
+            DependencyKind::Normal => "dependencies",
+            DependencyKind::Dev => "dev-dependencies",
+            DependencyKind::Build => "build-dependencies",
+        }
+    }
+
+    /// The path of the table, as taken by [`Manifest::get_table`](crate::Manifest::get_table).
+    pub fn path(&self) -> Vec<String> {
+        let mut path = Vec::new();
+        if self.workspace {
+            path.push("workspace".to_owned());
+        }
+        if let Some(ref target) = self.target {
+            path.push("target".to_owned());
+            path.push(target.clone());
+        }
+        path.push(self.kind_name().to_owned());
+        path
+    }
+
+    /// The platform the table applies to, if it is target-specific.
+    pub fn platform(&self) -> Option<Result<Platform>> {
+        self.target.as_ref().map(|target| target.parse())
+    }
+}
+
+/// A dependency listed in a manifest, and where it is listed.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{DependencyKind, Manifest};
+///
+///   let manifest: Manifest = r#"
+///   [package]
+///   name = "app"
+///   version = "0.1.0"
+///
+///   [dev-dependencies]
+///   regex = "1.4"
+///
+///   [target.'cfg(unix)'.dependencies]
+///   sys = { package = "libc", version = "0.2" }
+///   "#.parse().unwrap();
+///
+///   let entries: Vec<_> = manifest.dependency_entries().collect();
+///   assert_eq!(entries[0].key, "sys");
+///   assert_eq!(entries[0].name(), "libc");
+///   assert_eq!(entries[0].table.target.as_deref(), Some("cfg(unix)"));
+///   assert_eq!(entries[1].table.kind, DependencyKind::Dev);
+///   assert_eq!(entries[1].version_req(), Some("1.4"));
+///   assert_eq!(entries[1].package.as_deref(), Some("app"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct DependencyEntry {
+    /// The table it is listed in
+    pub table: DependencyTable,
+    /// The name of the package whose manifest lists it, unless the manifest is virtual
+    pub package: Option<String>,
+    /// The manifest listing it, when the entries of several manifests are gathered
+    pub manifest_path: Option<PathBuf>,
+    /// The key it is listed under, which is the name of the dependency unless it is renamed
+    pub key: String,
+    /// The entry itself, a version requirement or a table
+    pub item: toml_edit::Item,
+}
+
+impl DependencyEntry {
+    fn field(&self, field: &str) -> Option<&toml_edit::Item> {
+        self.item.as_table_like().and_then(|t| t.get(field))
+    }
+
+    /// The name of the crate depended on, taking renames into account.
+    pub fn name(&self) -> &str {
+        self.field("package")
+            .and_then(toml_edit::Item::as_str)
+            .unwrap_or(&self.key)
+    }
+
+    /// The version requirement, if there is one.
+    pub fn version_req(&self) -> Option<&str> {
+        self.item
+            .as_str()
+            .or_else(|| self.field("version").and_then(toml_edit::Item::as_str))
+    }
+
+    /// The `path` of the dependency, relative to the manifest listing it.
+    pub fn path(&self) -> Option<&str> {
+        self.field("path").and_then(toml_edit::Item::as_str)
+    }
+
+    /// Whether the dependency comes from a git repository.
+    pub fn is_git(&self) -> bool {
+        self.field("git").is_some()
+    }
+
+    /// The name of the registry the dependency comes from, unless that's crates.io.
+    pub fn registry(&self) -> Option<&str> {
+        self.field("registry").and_then(toml_edit::Item::as_str)
+    }
+
+    /// Whether the dependency is inherited from `[workspace.dependencies]`.
+    pub fn is_inherited(&self) -> bool {
+        self.field("workspace")
+            .and_then(toml_edit::Item::as_bool)
+            .unwrap_or(false)
+    }
+
+    /// The entry as a [`Dependency`], if it is well-formed.
+    pub fn to_dependency(&self) -> Option<Dependency> {
+        Dependency::from_toml(&self.key, &self.item)
+    }
+}
+
+/// Gather the entries of every dependency table in `data`, the root of a manifest.
+pub(crate) fn entries(data: &toml_edit::Document) -> Vec<DependencyEntry> {
+    let package = data["package"]["name"].as_str().map(ToOwned::to_owned);
+    let mut tables = vec![(
+        DependencyTable::workspace(),
+        &data["workspace"]["dependencies"],
+    )];
+    let kinds = [
+        DependencyKind::Normal,
+        DependencyKind::Dev,
+        DependencyKind::Build,
+    ];
+    for kind in kinds.iter().copied() {
+        let table = DependencyTable::new(kind);
+        let item = &data[table.kind_name()];
+        tables.push((table, item));
+
+        let targets = data
+            .as_table()
+            .get("target")
+            .and_then(toml_edit::Item::as_table_like)
+            .into_iter()
+            .flat_map(toml_edit::TableLike::iter);
+        for (target, tables_for_target) in targets {
+            let table = DependencyTable::for_target(kind, target);
+            let item = &tables_for_target[table.kind_name()];
+            tables.push((table, item));
+        }
+    }
+
+    let mut entries = Vec::new();
+    for (table, item) in tables {
+        let items = match item.as_table_like() {
+            Some(items) => items,
+            None => continue,
+        };
+        // Entries removed in this edit are still iterated over, as empty items.
+        for (key, item) in items.iter().filter(|(_, item)| !item.is_none()) {
+            entries.push(DependencyEntry {
+                table: table.clone(),
+                package: package.clone(),
+                manifest_path: None,
+                key: key.to_owned(),
+                item: item.clone(),
+            });
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DependencyKind, Manifest};
+
+    #[test]
+    fn entries_of_every_table() {
+        let manifest: Manifest = r#"
+[workspace.dependencies]
+serde = "1.0"
+
+[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+serde = { workspace = true }
+local = { path = "../local" }
+
+[build-dependencies]
+cc = "1.0"
+
+[target.'cfg(windows)'.dependencies]
+winapi = { version = "0.3", registry = "mirror" }
+
+[target.'cfg(windows)'.dev-dependencies]
+tempfile = "3"
+"#
+        .parse()
+        .unwrap();
+
+        let entries: Vec<_> = manifest.dependency_entries().collect();
+        let paths: Vec<(String, &str)> = entries
+            .iter()
+            .map(|e| (e.table.path().join("."), e.key.as_str()))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                ("workspace.dependencies".to_owned(), "serde"),
+                ("dependencies".to_owned(), "serde"),
+                ("dependencies".to_owned(), "local"),
+                ("target.cfg(windows).dependencies".to_owned(), "winapi"),
+                (
+                    "target.cfg(windows).dev-dependencies".to_owned(),
+                    "tempfile"
+                ),
+                ("build-dependencies".to_owned(), "cc"),
+            ]
+        );
+
+        assert!(entries[0].table.workspace);
+        assert!(entries[1].is_inherited());
+        assert_eq!(entries[2].path(), Some("../local"));
+        assert_eq!(entries[3].registry(), Some("mirror"));
+        assert_eq!(entries[4].table.kind, DependencyKind::Dev);
+        assert!(entries[4].table.platform().unwrap().is_ok());
+        assert_eq!(entries[5].to_dependency().unwrap().version(), Some("1.0"));
+    }
+}
@@ -0,0 +1,388 @@
+//! Semantic differences between two versions of a manifest.
+use crate::dependency::Dependency;
+use crate::duplicates::fix_duplicates;
+use crate::errors::*;
+use crate::manifest::Manifest;
+use crate::toml_key::quote_key;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// A single change between two versions of a manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestChange {
+    /// A dependency was added to `table`, e.g. `dependencies` or `target.'cfg(unix)'.dependencies`.
+    DepAdded {
+        /// The dependency table
+        table: String,
+        /// The name the dependency is listed under
+        name: String,
+        /// The version requirement, if any
+        version: Option<String>,
+    },
+    /// A dependency was removed from `table`.
+    DepRemoved {
+        /// The dependency table
+        table: String,
+        /// The name the dependency was listed under
+        name: String,
+        /// The version requirement it had, if any
+        version: Option<String>,
+    },
+    /// The version requirement of a dependency changed.
+    DepVersionChanged {
+        /// The dependency table
+        table: String,
+        /// The name the dependency is listed under
+        name: String,
+        /// The previous requirement
+        old: Option<String>,
+        /// The new requirement
+        new: Option<String>,
+    },
+    /// A feature of a dependency was enabled or disabled.
+    FeatureToggled {
+        /// The dependency table
+        table: String,
+        /// The name the dependency is listed under
+        name: String,
+        /// The feature
+        feature: String,
+        /// Whether the feature is now enabled
+        enabled: bool,
+    },
+    /// Anything else about a dependency changed, like its source or `optional`.
+    DepChanged {
+        /// The dependency table
+        table: String,
+        /// The name the dependency is listed under
+        name: String,
+        /// The previous entry, without version and features
+        old: String,
+        /// The new entry, without version and features
+        new: String,
+    },
+    /// A value outside of the dependency tables changed, like `package.edition` or a feature.
+    MetadataChanged {
+        /// The dotted path of the value, e.g. `package.edition`
+        key: String,
+        /// The previous value, if it existed
+        old: Option<String>,
+        /// The new value, if it still exists
+        new: Option<String>,
+    },
+}
+
+impl fmt::Display for ManifestChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let or_any = |version: &Option<String>| version.clone().unwrap_or_else(|| "*".to_owned());
+        match self {
+            ManifestChange::DepAdded {
+                table,
+                name,
+                version,
+            } => write!(f, "{}: added {} {}", table, name, or_any(version)),
+            ManifestChange::DepRemoved {
+                table,
+                name,
+                version,
+            } => write!(f, "{}: removed {} {}", table, name, or_any(version)),
+            ManifestChange::DepVersionChanged {
+                table,
+                name,
+                old,
+                new,
+            } => write!(f, "{}: {} {} -> {}", table, name, or_any(old), or_any(new)),
+            ManifestChange::FeatureToggled {
+                table,
+                name,
+                feature,
+                enabled,
+            } => write!(
+                f,
+                "{}: {} feature `{}` {}",
+                table,
+                name,
+                feature,
+                if *enabled { "enabled" } else { "disabled" }
+            ),
+            ManifestChange::DepChanged {
+                table,
+                name,
+                old,
+                new,
+            } => write!(f, "{}: {} {} -> {}", table, name, old, new),
+            ManifestChange::MetadataChanged { key, old, new } => match (old, new) {
+                (None, Some(new)) => write!(f, "{}: added {}", key, new),
+                (Some(old), None) => write!(f, "{}: removed {}", key, old),
+                (old, new) => write!(
+                    f,
+                    "{}: {} -> {}",
+                    key,
+                    old.as_deref().unwrap_or_default(),
+                    new.as_deref().unwrap_or_default()
+                ),
+            },
+        }
+    }
+}
+
+/// All changes between two versions of a manifest, dependency changes first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestChanges(pub Vec<ManifestChange>);
+
+impl ManifestChanges {
+    /// Whether the manifests are equivalent.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterate over the changes.
+    pub fn iter(&self) -> std::slice::Iter<'_, ManifestChange> {
+        self.0.iter()
+    }
+}
+
+impl fmt::Display for ManifestChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.0 {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare two manifests by meaning rather than by text: formatting, comments and the order of
+/// keys are ignored, and a dependency written as `"1.0"` equals `{ version = "1.0" }`.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{manifest_diff, ManifestChange};
+///
+///   let old = "[dependencies]\nserde = \"1.0\"\n";
+///   let new = "[dependencies]\nserde = { version = \"1.1\", features = [\"derive\"] }\n";
+///   let changes = manifest_diff(old, new).unwrap();
+///   assert_eq!(changes.iter().count(), 2);
+///   assert!(matches!(changes.0[0], ManifestChange::DepVersionChanged { .. }));
+/// ```
+pub fn manifest_diff(old: &str, new: &str) -> Result<ManifestChanges> {
+    let old_manifest: Manifest = old.parse()?;
+    let new_manifest: Manifest = new.parse()?;
+    let mut changes = Vec::new();
+
+    let old_deps = dependencies(&old_manifest);
+    let new_deps = dependencies(&new_manifest);
+    let keys: BTreeSet<_> = old_deps.keys().chain(new_deps.keys()).collect();
+    for key in keys {
+        let (table, name) = (key.0.clone(), key.1.clone());
+        match (old_deps.get(key), new_deps.get(key)) {
+            (None, Some(dep)) => changes.push(ManifestChange::DepAdded {
+                table,
+                name,
+                version: dep.version().map(String::from),
+            }),
+            (Some(dep), None) => changes.push(ManifestChange::DepRemoved {
+                table,
+                name,
+                version: dep.version().map(String::from),
+            }),
+            (Some(old), Some(new)) => diff_dependency(table, name, old, new, &mut changes),
+            (None, None) => unreachable!("key comes from one of the maps"),
+        }
+    }
+
+    let old_values = metadata(old)?;
+    let new_values = metadata(new)?;
+    let keys: BTreeSet<_> = old_values.keys().chain(new_values.keys()).collect();
+    for key in keys {
+        let (old, new) = (old_values.get(key), new_values.get(key));
+        if old != new {
+            changes.push(ManifestChange::MetadataChanged {
+                key: key.clone(),
+                old: old.cloned(),
+                new: new.cloned(),
+            });
+        }
+    }
+
+    Ok(ManifestChanges(changes))
+}
+
+fn diff_dependency(
+    table: String,
+    name: String,
+    old: &Dependency,
+    new: &Dependency,
+    changes: &mut Vec<ManifestChange>,
+) {
+    if old.version() != new.version() {
+        changes.push(ManifestChange::DepVersionChanged {
+            table: table.clone(),
+            name: name.clone(),
+            old: old.version().map(String::from),
+            new: new.version().map(String::from),
+        });
+    }
+
+    let features =
+        |dep: &Dependency| -> BTreeSet<String> { dep.features.iter().flatten().cloned().collect() };
+    let (old_features, new_features) = (features(old), features(new));
+    for feature in old_features.symmetric_difference(&new_features) {
+        changes.push(ManifestChange::FeatureToggled {
+            table: table.clone(),
+            name: name.clone(),
+            feature: feature.clone(),
+            enabled: new_features.contains(feature),
+        });
+    }
+
+    let (old_rest, new_rest) = (other_fields(old), other_fields(new));
+    if old_rest != new_rest {
+        changes.push(ManifestChange::DepChanged {
+            table,
+            name,
+            old: old_rest,
+            new: new_rest,
+        });
+    }
+}
+
+/// The entry of a dependency without its version and features, e.g. `{ path = "../foo" }`.
+fn other_fields(dep: &Dependency) -> String {
+    let (_, item) = dep.to_toml();
+    let fields: Vec<String> = match item.as_inline_table() {
+        Some(table) => table
+            .iter()
+            .filter(|(key, _)| *key != "version" && *key != "features")
+            .map(|(key, value)| format!("{} = {}", quote_key(key), value.to_string().trim()))
+            .collect(),
+        None => Vec::new(),
+    };
+    format!("{{ {} }}", fields.join(", "))
+}
+
+/// Every dependency, keyed by its table and the name it is listed under.
+fn dependencies(manifest: &Manifest) -> BTreeMap<(String, String), Dependency> {
+    let mut deps = BTreeMap::new();
+    // `[workspace.dependencies]` is compared like the rest of the metadata.
+    for entry in manifest.dependency_entries().filter(|e| !e.table.workspace) {
+        if let Some(dep) = entry.to_dependency() {
+            deps.insert((entry.table.path().join("."), entry.key), dep);
+        }
+    }
+    deps
+}
+
+const DEPENDENCY_TABLES: [&str; 5] = [
+    "dependencies",
+    "dev-dependencies",
+    "build-dependencies",
+    "dev_dependencies",
+    "build_dependencies",
+];
+
+/// Every value outside of the dependency tables, keyed by its dotted path.
+fn metadata(manifest: &str) -> Result<BTreeMap<String, String>> {
+    let value: toml::Value =
+        toml::from_str(&fix_duplicates(manifest)).chain_err(|| ErrorKind::ParseCargoToml)?;
+    let mut values = BTreeMap::new();
+    flatten(&value, "", &mut values);
+    Ok(values)
+}
+
+fn flatten(value: &toml::Value, prefix: &str, values: &mut BTreeMap<String, String>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                let is_dependency_table = DEPENDENCY_TABLES.contains(&key.as_str())
+                    && (prefix.is_empty() || prefix.starts_with("target."));
+                if is_dependency_table {
+                    continue;
+                }
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(value, &path, values);
+            }
+        }
+        value => {
+            values.insert(prefix.to_owned(), value.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dependency_changes() {
+        let old = r#"
+[dependencies]
+serde = { version = "1.0", features = ["derive"] }
+rand = "0.7"
+local = { path = "../local" }
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+"#;
+        let new = r#"
+[dependencies]
+serde = { version = "1.0", features = ["rc"] }
+regex = "1"
+local = { path = "../local", optional = true }
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2.80"
+"#;
+        let changes = manifest_diff(old, new).unwrap();
+        let lines: Vec<_> = changes.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "dependencies: local { path = \"../local\" } -> { path = \"../local\", optional = true }",
+                "dependencies: removed rand 0.7",
+                "dependencies: added regex 1",
+                "dependencies: serde feature `derive` disabled",
+                "dependencies: serde feature `rc` enabled",
+                "target.cfg(unix).dependencies: libc 0.2 -> 0.2.80",
+            ]
+        );
+    }
+
+    #[test]
+    fn metadata_changes() {
+        let old = "[package]\nname = \"foo\"\nedition = \"2015\"\nkeywords = [\"a\"]\n\n[features]\nstd = []\n";
+        let new = "[package]\nedition = \"2018\" # comment\nname = \"foo\"\n\n[features]\nstd = []\nalloc = []\n";
+        let changes = manifest_diff(old, new).unwrap();
+        assert_eq!(
+            changes.0,
+            vec![
+                ManifestChange::MetadataChanged {
+                    key: "features.alloc".to_owned(),
+                    old: None,
+                    new: Some("[]".to_owned()),
+                },
+                ManifestChange::MetadataChanged {
+                    key: "package.edition".to_owned(),
+                    old: Some("\"2015\"".to_owned()),
+                    new: Some("\"2018\"".to_owned()),
+                },
+                ManifestChange::MetadataChanged {
+                    key: "package.keywords".to_owned(),
+                    old: Some("[\"a\"]".to_owned()),
+                    new: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn formatting_is_ignored() {
+        let old = "[dependencies]\nserde = \"1.0\"\n";
+        let new = "[dependencies]\n# serialization\nserde = { version = \"1.0\" }\n";
+        assert!(manifest_diff(old, new).unwrap().is_empty());
+    }
+}
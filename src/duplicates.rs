@@ -0,0 +1,510 @@
+//! Dependencies listed more than once in the same table, e.g. after a bad merge.
+//!
+//! TOML forbids a key twice in a table, so a manifest with `foo = "1"` and a `[dependencies.foo]`
+//! table, or two `foo` entries, can't be parsed, let alone edited. Such entries are found in the
+//! text of the manifest instead, to tell where they are and, with [`set_fix_duplicates`], to merge
+//! them into one before it is parsed.
+use crate::errors::*;
+use crate::toml_key::{parse_dotted_key, quote_key};
+use crate::verbosity::verbosity;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use toml_edit::{Document, InlineTable, Item, Value};
+
+static FIX_DUPLICATES: AtomicBool = AtomicBool::new(false);
+
+/// Merge dependencies listed more than once for the rest of the process, e.g. for a
+/// `--fix-duplicates` flag, instead of failing to parse their manifest.
+pub fn set_fix_duplicates() {
+    FIX_DUPLICATES.store(true, Ordering::SeqCst);
+}
+
+/// A dependency listed more than once in the same table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateDependency {
+    /// The path of the table, like `["target", "cfg(unix)", "dependencies"]`
+    pub table: Vec<String>,
+    /// The key of the dependency
+    pub key: String,
+    /// The 1-based lines each entry starts on
+    pub lines: Vec<usize>,
+    /// The values a later entry replaced when merged, like `version = "1.2" over "1"`
+    pub overridden: Vec<String>,
+}
+
+impl fmt::Display for DuplicateDependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let table: Vec<String> = self.table.iter().map(|s| quote_key(s)).collect();
+        let lines: Vec<String> = self.lines.iter().map(ToString::to_string).collect();
+        write!(
+            f,
+            "`{}` in `{}` (lines {})",
+            self.key,
+            table.join("."),
+            lines.join(", ")
+        )?;
+        if !self.overridden.is_empty() {
+            write!(f, ", keeping {}", self.overridden.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Find the dependencies listed more than once in the same table of the manifest `content`.
+pub fn find_duplicate_dependencies(content: &str) -> Vec<DuplicateDependency> {
+    duplicate_groups(&scan(content))
+        .into_iter()
+        .map(|group| DuplicateDependency {
+            table: group[0].table.clone(),
+            key: group[0].key.clone(),
+            lines: group.iter().map(|entry| entry.line).collect(),
+            overridden: Vec::new(),
+        })
+        .collect()
+}
+
+/// Merge the dependencies listed more than once in the same table of the manifest `content`, in
+/// place of the first of their entries.
+///
+/// Fields only one entry has are kept, `features` are combined, and a field the entries disagree
+/// on takes the value of the last one, as told by [`DuplicateDependency::overridden`].
+pub fn merge_duplicate_dependencies(content: &str) -> Result<(String, Vec<DuplicateDependency>)> {
+    let entries = scan(content);
+    let mut edits: Vec<(usize, usize, String)> = Vec::new();
+    let mut merged = Vec::new();
+    for group in duplicate_groups(&entries) {
+        let mut fields: Vec<(String, Value)> = Vec::new();
+        let mut overridden = Vec::new();
+        for entry in &group {
+            for (key, value) in entry_fields(content, entry)? {
+                merge_field(&mut fields, &mut overridden, key, value);
+            }
+        }
+        let first = group[0];
+        edits.push((first.start, first.end, render(content, first, fields)?));
+        edits.extend(group[1..].iter().map(|e| (e.start, e.end, String::new())));
+        merged.push(DuplicateDependency {
+            table: first.table.clone(),
+            key: first.key.clone(),
+            lines: group.iter().map(|entry| entry.line).collect(),
+            overridden,
+        });
+    }
+    edits.sort_by_key(|&(start, _, _)| std::cmp::Reverse(start));
+    let mut content = content.to_owned();
+    for (start, end, text) in edits {
+        content.replace_range(start..end, &text);
+    }
+    Ok((content, merged))
+}
+
+/// Parse `input` as a manifest, telling which dependencies are listed more than once if that is
+/// why it can't be, or merging them first if [`set_fix_duplicates`] was called.
+pub(crate) fn parse_document(input: &str) -> Result<Document> {
+    let err = match input.parse::<Document>() {
+        Ok(document) => return Ok(document),
+        Err(err) => Error::manifest_parse(&err),
+    };
+    let duplicates = find_duplicate_dependencies(input);
+    if duplicates.is_empty() {
+        return Err(err);
+    }
+    if !FIX_DUPLICATES.load(Ordering::SeqCst) {
+        return Err(Error::with_chain(
+            err,
+            ErrorKind::DuplicateDependencies(duplicates),
+        ));
+    }
+    let (fixed, merged) = merge_duplicate_dependencies(input)?;
+    let document = fixed
+        .parse::<Document>()
+        .map_err(|e| Error::manifest_parse(&e))?;
+    if !verbosity().is_quiet() {
+        // The same manifest may be read several times, e.g. as a member of its workspace.
+        let mut warned = warned()
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for duplicate in merged {
+            let warning = format!("WARN: Merged the entries of {}", duplicate);
+            if warned.insert(warning.clone()) {
+                eprintln!("{}", warning);
+            }
+        }
+    }
+    Ok(document)
+}
+
+/// The warnings printed so far.
+fn warned() -> &'static Mutex<HashSet<String>> {
+    static WARNED: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    WARNED.get_or_init(Default::default)
+}
+
+/// `content` with its duplicate dependencies merged if [`set_fix_duplicates`] was called, for
+/// readers other than [`parse_document`] which would fail on them too.
+pub(crate) fn fix_duplicates(content: &str) -> Cow<'_, str> {
+    if !FIX_DUPLICATES.load(Ordering::SeqCst) || find_duplicate_dependencies(content).is_empty() {
+        return Cow::Borrowed(content);
+    }
+    match merge_duplicate_dependencies(content) {
+        Ok((fixed, _)) => Cow::Owned(fixed),
+        Err(_) => Cow::Borrowed(content),
+    }
+}
+
+/// An entry of a dependency table, either a `key = value` line or a `[table.key]` table.
+#[derive(Debug)]
+struct Entry {
+    table: Vec<String>,
+    key: String,
+    line: usize,
+    /// Byte offset of the start of its first line
+    start: usize,
+    /// Byte offset past its last line, or past the last line before the next table header
+    end: usize,
+    /// Byte offset of its value, or of the line after the header of a table
+    value_start: usize,
+    is_table: bool,
+}
+
+/// The entries of all dependency tables in `content`.
+fn scan(content: &str) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut table: Vec<String> = Vec::new();
+    let mut open_table: Option<usize> = None;
+    let mut offset = 0;
+    let mut lines = content.split_inclusive('\n').enumerate();
+    while let Some((index, line)) = lines.next() {
+        let start = offset;
+        offset += line.len();
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('[') {
+            if let Some(entry) = open_table.take() {
+                entries[entry].end = start;
+            }
+            let header = trimmed.trim_start_matches('[');
+            table = match parse_dotted_key(header) {
+                Some((path, rest)) if rest.starts_with(']') => path,
+                _ => Vec::new(),
+            };
+            if let Some((key, parent)) = table.split_last() {
+                if !trimmed.starts_with("[[") && is_dependency_table(parent) {
+                    open_table = Some(entries.len());
+                    entries.push(Entry {
+                        table: parent.to_vec(),
+                        key: key.clone(),
+                        line: index + 1,
+                        start,
+                        end: content.len(),
+                        value_start: offset,
+                        is_table: true,
+                    });
+                }
+            }
+            continue;
+        }
+        if !is_dependency_table(&table) {
+            continue;
+        }
+        let (key, rest) = match parse_dotted_key(trimmed) {
+            Some((mut path, rest)) if path.len() == 1 && rest.starts_with('=') => {
+                (path.remove(0), rest)
+            }
+            _ => continue,
+        };
+        let value_start = start + (line.len() - rest.len()) + 1;
+        // A value may span several lines, like an array with one element per line.
+        let mut state = ValueState::default();
+        state.feed(&rest[1..]);
+        while state.is_open() {
+            match lines.next() {
+                Some((_, line)) => {
+                    offset += line.len();
+                    state.feed(line);
+                }
+                None => break,
+            }
+        }
+        entries.push(Entry {
+            table: table.clone(),
+            key,
+            line: index + 1,
+            start,
+            end: offset,
+            value_start,
+            is_table: false,
+        });
+    }
+    entries
+}
+
+/// The entries of the same key in the same table, in the order they first appear.
+fn duplicate_groups(entries: &[Entry]) -> Vec<Vec<&Entry>> {
+    let mut groups: Vec<Vec<&Entry>> = Vec::new();
+    for entry in entries {
+        match groups
+            .iter_mut()
+            .find(|group| group[0].table == entry.table && group[0].key == entry.key)
+        {
+            Some(group) => group.push(entry),
+            None => groups.push(vec![entry]),
+        }
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+/// Whether `path` is a table of dependencies, like `dev-dependencies` or
+/// `target.'cfg(unix)'.dependencies`.
+fn is_dependency_table(path: &[String]) -> bool {
+    let is_kind = |kind: &str| {
+        matches!(
+            kind,
+            "dependencies"
+                | "dev-dependencies"
+                | "dev_dependencies"
+                | "build-dependencies"
+                | "build_dependencies"
+        )
+    };
+    match path {
+        [kind] => is_kind(kind),
+        [target, _, kind] => target == "target" && is_kind(kind),
+        [workspace, kind] => workspace == "workspace" && kind == "dependencies",
+        _ => false,
+    }
+}
+
+/// How far a value has been read, to tell whether it goes on on the next line.
+#[derive(Debug, Default)]
+struct ValueState {
+    depth: usize,
+    multiline_string: Option<&'static str>,
+}
+
+impl ValueState {
+    fn is_open(&self) -> bool {
+        self.depth > 0 || self.multiline_string.is_some()
+    }
+
+    fn feed(&mut self, line: &str) {
+        let mut rest = line;
+        while !rest.is_empty() {
+            if let Some(delimiter) = self.multiline_string {
+                match rest.find(delimiter) {
+                    Some(end) => {
+                        rest = &rest[end + 3..];
+                        self.multiline_string = None;
+                    }
+                    None => return,
+                }
+                continue;
+            }
+            if let Some(delimiter) = ["\"\"\"", "'''"].iter().find(|d| rest.starts_with(**d)) {
+                self.multiline_string = Some(delimiter);
+                rest = &rest[3..];
+                continue;
+            }
+            let c = rest.chars().next().unwrap_or_default();
+            rest = &rest[c.len_utf8()..];
+            match c {
+                '"' | '\'' => {
+                    let mut escaped = false;
+                    let end = rest.find(|next| {
+                        let found = next == c && !escaped;
+                        escaped = c == '"' && next == '\\' && !escaped;
+                        found
+                    });
+                    rest = end.map(|end| &rest[end + 1..]).unwrap_or_default();
+                }
+                '[' | '{' => self.depth += 1,
+                ']' | '}' => self.depth = self.depth.saturating_sub(1),
+                '#' => return,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The fields of `entry`, a version requirement on its own counting as `version`.
+fn entry_fields(content: &str, entry: &Entry) -> Result<Vec<(String, Value)>> {
+    let text = &content[entry.value_start..entry.end];
+    let invalid = || {
+        ErrorKind::InvalidDependency(
+            entry.key.clone(),
+            format!("its entry on line {} can't be merged", entry.line),
+        )
+    };
+    let source = if entry.is_table {
+        format!("[entry]\n{}", text)
+    } else {
+        format!("entry ={}", text)
+    };
+    let document: Document = source.parse().map_err(|_| invalid())?;
+    match &document["entry"] {
+        Item::Value(Value::String(_)) => Ok(vec![(
+            "version".to_owned(),
+            document["entry"].as_value().cloned().unwrap(),
+        )]),
+        Item::Value(Value::InlineTable(table)) => Ok(table
+            .iter()
+            .map(|(key, value)| (key.to_owned(), value.clone()))
+            .collect()),
+        Item::Table(table) => table
+            .iter()
+            .filter(|(_, item)| !item.is_none())
+            .map(|(key, item)| {
+                Ok((
+                    key.to_owned(),
+                    item.as_value().cloned().ok_or_else(invalid)?,
+                ))
+            })
+            .collect(),
+        _ => Err(invalid().into()),
+    }
+}
+
+/// Merge `value` for `key` into `fields`, recording a value it replaces in `overridden`.
+fn merge_field(
+    fields: &mut Vec<(String, Value)>,
+    overridden: &mut Vec<String>,
+    key: String,
+    value: Value,
+) {
+    let existing = match fields.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, existing)) => existing,
+        None => {
+            fields.push((key, value));
+            return;
+        }
+    };
+    if let (Some(old), Some(new)) = (existing.as_array_mut(), value.as_array()) {
+        if key == "features" {
+            for feature in new.iter() {
+                if !old.iter().any(|f| f.as_str() == feature.as_str()) {
+                    let _ = old.push(toml_edit::decorated(feature.clone(), "", ""));
+                }
+            }
+            return;
+        }
+    }
+    let (old, new) = (bare(existing), bare(&value));
+    if old != new {
+        overridden.push(format!("{} = {} over {}", key, new, old));
+        *existing = value;
+    }
+}
+
+/// A value as written, without the whitespace and comments around it.
+fn bare(value: &Value) -> String {
+    toml_edit::decorated(value.clone(), "", "").to_string()
+}
+
+/// The text of the entry merged from `fields`, written the way `first` was.
+fn render(content: &str, first: &Entry, fields: Vec<(String, Value)>) -> Result<String> {
+    let head = &content[first.start..first.value_start];
+    let body = &content[first.value_start..first.end];
+    if first.is_table {
+        let mut document = Document::new();
+        let mut table = toml_edit::Table::new();
+        for (key, value) in fields {
+            *table.entry(&quote_key(&key)) = Item::Value(toml_edit::decorated(value, " ", ""));
+        }
+        document["entry"] = Item::Table(table);
+        let rendered = document.to_string();
+        let rows = rendered
+            .split_once("[entry]\n")
+            .map(|(_, rows)| rows)
+            .unwrap_or_default();
+        // Blank lines before the next table are kept.
+        let blank_lines = body
+            .split_inclusive('\n')
+            .rev()
+            .take_while(|line| line.trim().is_empty())
+            .count();
+        return Ok(format!("{}{}{}", head, rows, "\n".repeat(blank_lines)));
+    }
+    let value = match fields.as_slice() {
+        [(key, value)] if key == "version" && value.is_str() => bare(value),
+        _ => {
+            let mut table = InlineTable::default();
+            for (key, value) in fields {
+                table.get_or_insert(&quote_key(&key), value);
+            }
+            table.fmt();
+            bare(&Value::InlineTable(table))
+        }
+    };
+    let newline = if body.ends_with('\n') { "\n" } else { "" };
+    Ok(format!("{} {}{}", head, value, newline))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"[package]
+name = "demo"
+
+[dependencies]
+foo = "1"
+serde = { version = "1", features = ["derive"] }
+serde = { version = "1.0.100", features = ["rc"] }
+
+[dependencies.foo]
+version = "1.2"
+features = ["x"]
+
+[target.'cfg(unix)'.dependencies]
+foo = "1"
+"#;
+
+    #[test]
+    fn finds_duplicates() {
+        let duplicates = find_duplicate_dependencies(MANIFEST);
+        assert_eq!(
+            duplicates
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "`foo` in `dependencies` (lines 5, 9)",
+                "`serde` in `dependencies` (lines 6, 7)",
+            ]
+        );
+        assert!(find_duplicate_dependencies("[dependencies]\nfoo = \"1\"\n").is_empty());
+    }
+
+    #[test]
+    fn merges_duplicates() {
+        let (merged, duplicates) = merge_duplicate_dependencies(MANIFEST).unwrap();
+        assert_eq!(
+            merged,
+            r#"[package]
+name = "demo"
+
+[dependencies]
+foo = { version = "1.2", features = ["x"] }
+serde = { version = "1.0.100", features = ["derive", "rc"] }
+
+[target.'cfg(unix)'.dependencies]
+foo = "1"
+"#
+        );
+        assert_eq!(duplicates[0].overridden, [r#"version = "1.2" over "1""#]);
+        merged.parse::<Document>().unwrap();
+    }
+
+    #[test]
+    fn merges_into_the_first_table() {
+        let manifest = "[dependencies.foo]\nversion = \"1\"\n\n[dependencies]\n\"f\\u006Fo\" = { optional = true }\n";
+        let (merged, _) = merge_duplicate_dependencies(manifest).unwrap();
+        assert_eq!(
+            merged,
+            "[dependencies.foo]\nversion = \"1\"\noptional = true\n\n[dependencies]\n"
+        );
+    }
+}
@@ -1,9 +1,9 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 error_chain! {
     foreign_links {
         Io(::std::io::Error) #[doc = "An error from the std::io module"];
-        Git(::git2::Error)#[doc = "An error from the git2 crate"];
+        Git(::git2::Error)#[doc = "An error from the git2 crate"] #[cfg(feature = "fetch")];
         CargoMetadata(::failure::Compat<::cargo_metadata::Error>)#[doc = "An error from the cargo_metadata crate"];
     }
 
@@ -20,17 +20,69 @@ error_chain! {
         EmptyCrateName{
             description("Found empty crate name")
         }
-        /// No crate by that name exists
-        NoCrate(name: String) {
+        /// A crate name breaks the naming rules of its registry
+        InvalidCrateName(name: String, reason: String) {
+            description("Invalid crate name")
+            display("Invalid crate name `{}`: {}", name, reason)
+        }
+        /// No crate by that name exists; `suggestions` lists published crates with similar names
+        NoCrate(name: String, suggestions: Vec<String>) {
             description("The crate could not be found in registry index.")
-            display("The crate `{}` could not be found in registry index.", name)
+            display("The crate `{}` could not be found in registry index.{}", name,
+                    did_you_mean(suggestions))
+        }
+        /// The crate exists, but none of its versions can be used
+        ///
+        /// `yanked_only` is set when every otherwise suitable version was yanked, and
+        /// `prerelease_only` when only pre-releases are available.
+        NoMatchingVersion(name: String, yanked_only: bool, prerelease_only: bool) {
+            description("No available versions exist")
+            display("{}", no_matching_version(name, *yanked_only, *prerelease_only))
+        }
+        /// Suitable versions exist, but none supports the Rust toolchain in use
+        IncompatibleRustVersion(name: String, rustc: String, required: crate::RustVersion) {
+            description("No version supports the Rust toolchain")
+            display("No suitable version of `{}` supports Rust {}; the oldest requires Rust {}.", name, rustc, required)
+        }
+        /// No version of a crate declares the Rust version it needs, and that must be known
+        UnknownRustVersion(name: String, rustc: String) {
+            description("No version declares its Rust version")
+            display("No suitable version of `{}` declares the Rust version it needs, so none is known to support Rust {}.", name, rustc)
+        }
+        /// A request to a registry or another server failed
+        Network(url: String, reason: String) {
+            description("Network request failed")
+            display("Request to `{}` failed: {}{}", url, reason, certificate_hint(reason))
+        }
+        /// A request was not sent as the network may not be accessed, see `--offline`
+        Offline(url: String) {
+            description("Network access is disabled")
+            display("Can't reach `{}` as the network may not be accessed (`--offline` or `--frozen`)", url)
+        }
+        /// Dependencies are listed more than once in the same table, so the manifest can't be
+        /// parsed
+        DuplicateDependencies(duplicates: Vec<crate::DuplicateDependency>) {
+            description("Dependencies listed more than once")
+            display("Dependencies listed more than once: {}. Merge them with `--fix-duplicates`.",
+                    duplicates.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))
+        }
+        /// A manifest is not valid TOML; `line` and `column` are 1-based
+        ManifestParse(message: String, line: Option<usize>, column: Option<usize>) {
+            description("Unable to parse Cargo.toml")
+            display("Unable to parse Cargo.toml")
+        }
+        /// The manifest at `path` is not valid; `line` and `column` are 1-based, and the cause
+        /// shows a snippet of the manifest
+        ManifestParseAt(path: PathBuf, line: Option<usize>, column: Option<usize>) {
+            description("Unable to parse a manifest")
+            display("Unable to parse {}{}", path.display(), location(*line, *column))
         }
-        /// No versions available
-        NoVersionsAvailable {
-            description("No available versions exist. Either all were yanked \
-                         or only prerelease versions exist. Trying with the \
-                         --allow-prerelease flag might solve the issue."
-            )
+        /// Manifests of workspace members could not be parsed, and were skipped
+        BrokenManifests(broken: Vec<crate::BrokenManifest>) {
+            description("Manifests could not be parsed")
+            display("Skipped {} which could not be parsed:\n{}",
+                    if broken.len() == 1 { "a manifest".to_owned() } else { format!("{} manifests", broken.len()) },
+                    broken.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))
         }
         /// Unable to parse external Cargo.toml
         ParseCargoToml {
@@ -49,6 +101,21 @@ error_chain! {
             description("Found virtual manifest, but this command requires running against an \
                          actual package in this workspace.")
         }
+        /// A command editing a package was run against a virtual manifest; `members` lists the
+        /// packages of the workspace to pick from instead
+        VirtualManifest(members: Vec<String>) {
+            description("Found virtual manifest, but this command requires running against an \
+                         actual package in this workspace.")
+            display("Found virtual manifest, but this command requires running against an actual \
+                     package in this workspace. Pick one with `--package`: {}.", members.join(", "))
+        }
+        /// A package lies under a workspace root which neither lists nor excludes it
+        NotInWorkspace(package: PathBuf, root: PathBuf) {
+            description("Package believes it's in a workspace when it's not")
+            display("The package at `{}` believes it's in the workspace at `{}` when it's not; \
+                     add it to `workspace.members` or `workspace.exclude` there.",
+                    package.display(), root.display())
+        }
         /// The TOML table could not be found.
         NonExistentTable(table: String) {
             description("non existent table")
@@ -59,6 +126,12 @@ error_chain! {
             description("non existent dependency")
             display("The dependency `{}` could not be found in `{}`.", name, table)
         }
+        /// Several renamed dependencies are on the package named
+        AmbiguousDependency(name: String, table: String, keys: Vec<String>) {
+            description("ambiguous dependency")
+            display("Several dependencies in `{}` are on the package `{}`: {}. Pick one by its key.",
+                    table, name, keys.join(", "))
+        }
         /// Config of cargo is invalid
         InvalidCargoConfig {
             description("Invalid cargo config")
@@ -68,6 +141,16 @@ error_chain! {
             description("Unable to find the source specified by 'replace-with'")
             display("The source '{}' could not be found", name)
         }
+        /// The source replacing a registry can't be read
+        UnsupportedSource(name: String) {
+            description("Unsupported source replacement")
+            display("The source '{}' is a git repository, which can't be used as a registry", name)
+        }
+        /// A `rust-version` cargo would reject
+        InvalidRustVersion(input: String, reason: String) {
+            description("Invalid rust-version")
+            display("invalid rust-version `{}`: {}", input, reason)
+        }
         /// Unable to find the specified registry
         NoSuchRegistryFound(name: String) {
             display("The registry '{}' could not be found", name)
@@ -77,6 +160,16 @@ error_chain! {
             description("Failed to parse a version for a dependency")
             display("The version `{}` for the dependency `{}` couldn't be parsed", version, dep)
         }
+        /// A version requirement cargo would reject, or can't be computed with
+        InvalidVersionReq(req: String, reason: String) {
+            description("Invalid version requirement")
+            display("Invalid version requirement `{}`: {}", req, reason)
+        }
+        /// A version can't be bumped as asked, or given that build metadata
+        InvalidVersionBump(version: String, reason: String) {
+            description("Invalid version bump")
+            display("Cannot bump `{}`: {}", version, reason)
+        }
         /// Missing registry checkout in the cargo registry
         MissingRegistraryCheckout(path: PathBuf) {
             description("Missing registry checkout in the cargo registry")
@@ -87,5 +180,294 @@ error_chain! {
             // this is because git2 function takes &str instead of something like AsRef<Path>
             description("Path to cargos registry contains non unicode characters")
         }
+        /// The configured credential provider is not supported
+        UnsupportedCredentialProvider(name: String) {
+            description("Unsupported credential provider")
+            display("The credential provider `{}` is not supported, use a credential provider \
+                     plugin instead", name)
+        }
+        /// The registry requires authentication
+        RegistryAuth(url: String) {
+            description("The registry requires authentication")
+            display("`{}` requires authentication, but no token was found for the registry", url)
+        }
+        /// A metadata schema declaration could not be understood
+        InvalidMetadataSchema(table: String) {
+            description("Invalid metadata schema")
+            display("The metadata schema declared in `{}` is invalid", table)
+        }
+        /// A requested feature does not exist in the crate
+        UnknownFeature(name: String, feature: String, suggestions: Vec<String>) {
+            description("Unknown feature")
+            display("`{}` has no feature `{}`.{}", name, feature, did_you_mean(suggestions))
+        }
+        /// A package ID specification could not be parsed
+        InvalidPackageIdSpec(spec: String, reason: String) {
+            description("Invalid package ID specification")
+            display("Invalid package ID specification `{}`: {}", spec, reason)
+        }
+        /// A `[target]` table key is neither a target name nor a valid `cfg(...)` expression
+        InvalidCfg(target: String, reason: String) {
+            description("Invalid target")
+            display("Invalid target `{}`: {}", target, reason)
+        }
+        /// A setting has the wrong type; `source` describes where it was set
+        InvalidConfig(key: String, source: String) {
+            description("Invalid setting")
+            display("Invalid setting `{}` in {}", key, source)
+        }
+        /// A dependency can't be written to a manifest as it is
+        InvalidDependency(name: String, reason: String) {
+            description("Invalid dependency")
+            display("Invalid dependency `{}`: {}", name, reason)
+        }
+        /// The lock file needed changing, but may not be changed, see `--locked`
+        LockfileLocked(path: PathBuf) {
+            description("The lock file may not be changed")
+            display("The lock file `{}` needs to be updated but `--locked` or `--frozen` was given", path.display())
+        }
+        /// Another process kept the workspace at `root` locked for too long
+        ManifestLocked(root: PathBuf, holder: Option<u32>) {
+            description("The workspace is locked by another process")
+            display("{} is being edited by another process{}; try again once it is done",
+                    root.display(), crate::manifest_lock::held_by(*holder))
+        }
+        /// Another process kept the registry cache in `dir` locked for too long
+        CacheLocked(dir: PathBuf, holder: Option<u32>) {
+            description("The registry cache is locked by another process")
+            display("The registry cache in {} is being used by another process{}; try again once it is done",
+                    dir.display(), crate::manifest_lock::held_by(*holder))
+        }
+        /// The operation was cancelled, e.g. by Ctrl-C, before it changed any manifest
+        Interrupted {
+            description("Interrupted")
+            display("Interrupted; no manifest was changed")
+        }
+        /// A change was declined when asked to confirm it
+        Declined {
+            description("Declined")
+            display("Declined; no manifest was changed")
+        }
+    }
+}
+
+fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [one] => format!(" Did you mean `{}`?", one),
+        many => format!(" Did you mean one of `{}`?", many.join("`, `")),
+    }
+}
+
+/// Point requests failing on an unknown certificate, like those intercepted by a corporate proxy,
+/// to `http.cainfo`.
+fn certificate_hint(reason: &str) -> &'static str {
+    let reason = reason.to_ascii_lowercase();
+    if reason.contains("certificate") || reason.contains("unknownissuer") {
+        ". If a proxy intercepts TLS on this network, set `http.cainfo` in the cargo config (or \
+         `CARGO_HTTP_CAINFO`) to the file of its root certificates."
+    } else {
+        ""
+    }
+}
+
+/// `:line:column` after a path, as far as it is known.
+pub(crate) fn location(line: Option<usize>, column: Option<usize>) -> String {
+    match (line, column) {
+        (Some(line), Some(column)) => format!(":{}:{}", line, column),
+        (Some(line), None) => format!(":{}", line),
+        _ => String::new(),
+    }
+}
+
+fn no_matching_version(name: &str, yanked_only: bool, prerelease_only: bool) -> String {
+    if yanked_only {
+        format!("All suitable versions of `{}` have been yanked.", name)
+    } else if prerelease_only {
+        format!(
+            "Only prerelease versions of `{}` exist. Trying with the --allow-prerelease flag \
+             might solve the issue.",
+            name
+        )
+    } else {
+        format!("No available versions of `{}` exist.", name)
+    }
+}
+
+impl Error {
+    /// Name the manifest at `path` in a parse error, as [`ErrorKind::ManifestParseAt`]; other
+    /// errors are returned as they are.
+    pub(crate) fn in_manifest(self, path: &Path) -> Self {
+        let location = self.find_cause(|err| match err.kind() {
+            ErrorKind::ManifestParse(_, line, column) => Some((*line, *column)),
+            _ => None,
+        });
+        let kind = match location {
+            Some((line, column)) => ErrorKind::ManifestParseAt(path.to_owned(), line, column),
+            None => return self,
+        };
+        // The path replaces the bare "Cargo.toml" of a parse error, which the TOML error follows.
+        let toml_error = match self.kind() {
+            ErrorKind::ManifestParse(..) => std::error::Error::source(&self)
+                .and_then(|err| err.downcast_ref::<toml_edit::TomlError>())
+                .cloned(),
+            _ => None,
+        };
+        match toml_error {
+            Some(toml_error) => Error::with_chain(toml_error, kind),
+            None => Error::with_chain(self, kind),
+        }
+    }
+
+    /// A copy of this error, to report one failure for several things, e.g. for every crate
+    /// queried from a registry which could not be opened.
+    ///
+    /// The kind is kept along with the chain of causes, so that callers can still tell what went
+    /// wrong; causes from other crates are reduced to their message, except for I/O and git
+    /// errors. Every kind is copied explicitly, so a new one can't be added without saying how.
+    #[cfg(feature = "fetch")]
+    pub(crate) fn duplicate(&self) -> Self {
+        let kind = duplicate_kind(self.kind());
+        match std::error::Error::source(self) {
+            Some(cause) => Error::with_chain(duplicate_cause(cause), kind),
+            None => kind.into(),
+        }
+    }
+
+    /// The first error in the chain of this one for which `f` returns something.
+    pub(crate) fn find_cause<T>(&self, f: impl Fn(&Error) -> Option<T>) -> Option<T> {
+        let mut cause: Option<&(dyn std::error::Error + 'static)> = Some(self);
+        while let Some(err) = cause {
+            if let Some(found) = err.downcast_ref::<Error>().and_then(&f) {
+                return Some(found);
+            }
+            cause = err.source();
+        }
+        None
     }
+
+    /// Build a [`ErrorKind::ManifestParse`] from a TOML parse error, extracting its location.
+    pub(crate) fn manifest_parse(err: &toml_edit::TomlError) -> Self {
+        let message = err.to_string();
+        // toml_edit reports "TOML parse error at line 1, column 10" followed by a snippet.
+        let location = message
+            .lines()
+            .next()
+            .and_then(|line| line.strip_prefix("TOML parse error at line "))
+            .and_then(|rest| {
+                let (line, column) = rest.split_once(", column ")?;
+                Some((line.trim().parse().ok()?, column.trim().parse().ok()?))
+            });
+        Error::with_chain(
+            err.clone(),
+            ErrorKind::ManifestParse(
+                message.trim().to_owned(),
+                location.map(|(line, _)| line),
+                location.map(|(_, column)| column),
+            ),
+        )
+    }
+}
+
+#[cfg(feature = "fetch")]
+fn duplicate_cause(cause: &(dyn std::error::Error + 'static)) -> Error {
+    if let Some(err) = cause.downcast_ref::<Error>() {
+        return err.duplicate();
+    }
+    let kind = if let Some(err) = cause.downcast_ref::<std::io::Error>() {
+        ErrorKind::Io(std::io::Error::new(err.kind(), err.to_string()))
+    } else if let Some(err) = cause.downcast_ref::<git2::Error>() {
+        ErrorKind::Git(git2::Error::new(err.code(), err.class(), err.message()))
+    } else {
+        ErrorKind::Msg(cause.to_string())
+    };
+    match cause.source() {
+        Some(next) => Error::with_chain(duplicate_cause(next), kind),
+        None => kind.into(),
+    }
+}
+
+#[cfg(feature = "fetch")]
+fn duplicate_kind(kind: &ErrorKind) -> ErrorKind {
+    use ErrorKind::*;
+    match kind {
+        Msg(message) => Msg(message.clone()),
+        Io(err) => Io(std::io::Error::new(err.kind(), err.to_string())),
+        Git(err) => Git(git2::Error::new(err.code(), err.class(), err.message())),
+        ReadHomeDirFailure => ReadHomeDirFailure,
+        InvalidSummaryJson => InvalidSummaryJson,
+        EmptyCrateName => EmptyCrateName,
+        InvalidCrateName(name, reason) => InvalidCrateName(name.clone(), reason.clone()),
+        NoCrate(name, suggestions) => NoCrate(name.clone(), suggestions.clone()),
+        NoMatchingVersion(name, yanked_only, prerelease_only) => {
+            NoMatchingVersion(name.clone(), *yanked_only, *prerelease_only)
+        }
+        IncompatibleRustVersion(name, rustc, required) => {
+            IncompatibleRustVersion(name.clone(), rustc.clone(), *required)
+        }
+        UnknownRustVersion(name, rustc) => UnknownRustVersion(name.clone(), rustc.clone()),
+        Network(url, reason) => Network(url.clone(), reason.clone()),
+        Offline(url) => Offline(url.clone()),
+        DuplicateDependencies(duplicates) => DuplicateDependencies(duplicates.clone()),
+        ManifestParse(message, line, column) => ManifestParse(message.clone(), *line, *column),
+        ManifestParseAt(path, line, column) => ManifestParseAt(path.clone(), *line, *column),
+        BrokenManifests(broken) => BrokenManifests(broken.clone()),
+        ParseCargoToml => ParseCargoToml,
+        MissingManifest => MissingManifest,
+        InvalidManifest => InvalidManifest,
+        UnexpectedRootManifest => UnexpectedRootManifest,
+        VirtualManifest(members) => VirtualManifest(members.clone()),
+        NotInWorkspace(package, root) => NotInWorkspace(package.clone(), root.clone()),
+        NonExistentTable(table) => NonExistentTable(table.clone()),
+        NonExistentDependency(name, table) => NonExistentDependency(name.clone(), table.clone()),
+        AmbiguousDependency(name, table, keys) => {
+            AmbiguousDependency(name.clone(), table.clone(), keys.clone())
+        }
+        InvalidCargoConfig => InvalidCargoConfig,
+        NoSuchSourceFound(name) => NoSuchSourceFound(name.clone()),
+        UnsupportedSource(name) => UnsupportedSource(name.clone()),
+        InvalidRustVersion(input, reason) => InvalidRustVersion(input.clone(), reason.clone()),
+        NoSuchRegistryFound(name) => NoSuchRegistryFound(name.clone()),
+        ParseVersion(version, dep) => ParseVersion(version.clone(), dep.clone()),
+        InvalidVersionReq(req, reason) => InvalidVersionReq(req.clone(), reason.clone()),
+        InvalidVersionBump(version, reason) => InvalidVersionBump(version.clone(), reason.clone()),
+        MissingRegistraryCheckout(path) => MissingRegistraryCheckout(path.clone()),
+        NonUnicodeGitPath => NonUnicodeGitPath,
+        UnsupportedCredentialProvider(name) => UnsupportedCredentialProvider(name.clone()),
+        RegistryAuth(url) => RegistryAuth(url.clone()),
+        InvalidMetadataSchema(table) => InvalidMetadataSchema(table.clone()),
+        UnknownFeature(name, feature, suggestions) => {
+            UnknownFeature(name.clone(), feature.clone(), suggestions.clone())
+        }
+        InvalidPackageIdSpec(spec, reason) => InvalidPackageIdSpec(spec.clone(), reason.clone()),
+        InvalidCfg(target, reason) => InvalidCfg(target.clone(), reason.clone()),
+        InvalidConfig(key, source) => InvalidConfig(key.clone(), source.clone()),
+        InvalidDependency(name, reason) => InvalidDependency(name.clone(), reason.clone()),
+        LockfileLocked(path) => LockfileLocked(path.clone()),
+        ManifestLocked(root, holder) => ManifestLocked(root.clone(), *holder),
+        CacheLocked(dir, holder) => CacheLocked(dir.clone(), *holder),
+        CargoMetadata(err) => CargoMetadata(duplicate_cargo_metadata(err.get_ref())),
+        Interrupted => Interrupted,
+        Declined => Declined,
+        __Nonexhaustive {} => unreachable!("never constructed"),
+    }
+}
+
+#[cfg(feature = "fetch")]
+fn duplicate_cargo_metadata(err: &cargo_metadata::Error) -> failure::Compat<cargo_metadata::Error> {
+    use cargo_metadata::Error::*;
+    use failure::Fail;
+    let err = match err {
+        CargoMetadata { stderr } => CargoMetadata {
+            stderr: stderr.clone(),
+        },
+        Io(err) => Io(std::io::Error::new(err.kind(), err.to_string())),
+        Utf8(err) => Utf8(*err),
+        ErrUtf8(err) => ErrUtf8(
+            String::from_utf8(err.as_bytes().to_vec()).expect_err("the bytes were not UTF-8"),
+        ),
+        Json(err) => Json(serde::de::Error::custom(err)),
+        NoJson => NoJson,
+    };
+    err.compat()
 }
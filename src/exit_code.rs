@@ -0,0 +1,126 @@
+//! The exit codes of the binaries, so that scripts can tell outcomes apart without parsing the
+//! output.
+
+use crate::errors::*;
+use std::error::Error as StdError;
+
+/// How a command ended, as its exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// The command did what it was asked to; with `--exit-code`, it changed manifests
+    Success = 0,
+    /// A failure without a code of its own
+    Failure = 1,
+    /// With `--exit-code`, the command succeeded without anything to change
+    NoChanges = 2,
+    /// With `--exit-code`, a dry run or comparison found changes, like upgrades being available
+    ChangesPending = 3,
+    /// A manifest breaks a rule it is checked against, like its metadata schema
+    PolicyViolation = 4,
+    /// A registry or another server could not be reached, or may not be with `--offline`
+    Network = 5,
+    /// A manifest could not be parsed
+    ManifestParse = 6,
+    /// The command was interrupted, e.g. by Ctrl-C
+    Interrupted = 130,
+}
+
+impl ExitCode {
+    /// The code to exit the process with.
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// The outcome of a command run with `--exit-code`: whether it changed manifests or, in a
+    /// `dry_run`, would have.
+    pub fn for_changes(changed: bool, dry_run: bool) -> Self {
+        match (changed, dry_run) {
+            (false, _) => ExitCode::NoChanges,
+            (true, false) => ExitCode::Success,
+            (true, true) => ExitCode::ChangesPending,
+        }
+    }
+
+    /// The exit code of a failure of the library, if it has one of its own.
+    pub fn for_error_kind(kind: &ErrorKind) -> Option<Self> {
+        match kind {
+            ErrorKind::Network(..) | ErrorKind::Offline(..) => Some(ExitCode::Network),
+            #[cfg(feature = "fetch")]
+            ErrorKind::Git(..) => Some(ExitCode::Network),
+            ErrorKind::ManifestParse(..)
+            | ErrorKind::ManifestParseAt(..)
+            | ErrorKind::BrokenManifests(..)
+            | ErrorKind::DuplicateDependencies(..)
+            | ErrorKind::ParseCargoToml
+            | ErrorKind::InvalidManifest => Some(ExitCode::ManifestParse),
+            ErrorKind::Interrupted => Some(ExitCode::Interrupted),
+            _ => None,
+        }
+    }
+
+    /// The exit code of `err`: that of the first error of the library in its chain of causes
+    /// which has one of its own, or `Failure`.
+    pub fn for_error(err: &(dyn StdError + 'static)) -> Self {
+        let mut cause = Some(err);
+        while let Some(err) = cause {
+            let code = err
+                .downcast_ref::<Error>()
+                .and_then(|err| Self::for_error_kind(err.kind()));
+            #[cfg(feature = "fetch")]
+            let code =
+                code.or_else(|| err.downcast_ref::<git2::Error>().map(|_| ExitCode::Network));
+            if let Some(code) = code {
+                return code;
+            }
+            cause = err.source();
+        }
+        ExitCode::Failure
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_code_of_a_cause() {
+        let parse: Error =
+            ErrorKind::ManifestParse("expected `=`".to_owned(), Some(3), None).into();
+        let err = Error::with_chain(parse, "Failed to open the manifest");
+        assert_eq!(ExitCode::for_error(&err), ExitCode::ManifestParse);
+
+        let err: Error = "Failed to get new version".into();
+        assert_eq!(ExitCode::for_error(&err), ExitCode::Failure);
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn duplicates_keep_their_code() {
+        let offline: Error = ErrorKind::Offline("https://example.com".to_owned()).into();
+        let err = Error::with_chain(offline, "Failed to open the index");
+        let copy = err.duplicate();
+        assert_eq!(copy.to_string(), err.to_string());
+        assert_eq!(ExitCode::for_error(&copy), ExitCode::Network);
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn duplicates_keep_foreign_kinds() {
+        use failure::Fail;
+
+        let metadata = cargo_metadata::Error::CargoMetadata {
+            stderr: "error: failed to parse manifest".to_owned(),
+        };
+        let err: Error = ErrorKind::CargoMetadata(metadata.compat()).into();
+        let copy = err.duplicate();
+        assert_eq!(copy.to_string(), err.to_string());
+        assert!(matches!(copy.kind(), ErrorKind::CargoMetadata(_)));
+    }
+
+    #[test]
+    fn tells_changes_apart() {
+        assert_eq!(ExitCode::for_changes(true, false).code(), 0);
+        assert_eq!(ExitCode::for_changes(false, true).code(), 2);
+        assert_eq!(ExitCode::for_changes(true, true).code(), 3);
+    }
+}
@@ -0,0 +1,418 @@
+//! What enabling a set of features of a crate actually turns on.
+//!
+//! This follows cargo's rules: features enable other features, `dep:foo` activates the optional
+//! dependency `foo` without a feature of that name, `foo/bar` activates `foo` and its feature `bar`,
+//! and the weak `foo?/bar` only enables `bar` if `foo` is activated by something else. Optional
+//! dependencies never referenced with `dep:` also act as features of the same name.
+#[cfg(feature = "index")]
+use crate::errors::*;
+#[cfg(feature = "index")]
+use crate::index::{closest_names, CrateVersionInfo};
+#[cfg(feature = "index")]
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// A value in a list of `[features]`, in one of the syntaxes cargo accepts.
+///
+/// Values are written back exactly as they were parsed, so the `dep:` prefix and the weak `?`
+/// marker survive edits.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::FeatureValue;
+///
+///   let value = FeatureValue::from("serde?/derive");
+///   assert_eq!(value.dependency(), Some("serde"));
+///   assert_eq!(value.to_string(), "serde?/derive");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FeatureValue {
+    /// Another feature, or an optional dependency used as a feature of the same name: `std`
+    Feature(String),
+    /// An optional dependency, without a feature of the same name: `dep:serde`
+    Dep(String),
+    /// A feature of a dependency: `serde/derive`, which also activates `serde` if it is optional,
+    /// or the `weak` `serde?/derive`, which only applies if something else activates it
+    DepFeature {
+        /// The name of the dependency, as the key of its entry
+        dep: String,
+        /// The feature of the dependency
+        feature: String,
+        /// Whether the dependency is left alone unless something else activates it
+        weak: bool,
+    },
+}
+
+impl FeatureValue {
+    /// The dependency this value refers to explicitly, with `dep:` or a `/`.
+    ///
+    /// A plain `Feature` may refer to an optional dependency as well, which only the manifest can
+    /// tell.
+    pub fn dependency(&self) -> Option<&str> {
+        match self {
+            FeatureValue::Feature(_) => None,
+            FeatureValue::Dep(dep) | FeatureValue::DepFeature { dep, .. } => Some(dep),
+        }
+    }
+}
+
+impl From<&str> for FeatureValue {
+    fn from(value: &str) -> Self {
+        if let Some(dep) = value.strip_prefix("dep:") {
+            return FeatureValue::Dep(dep.to_owned());
+        }
+        match value.split_once('/') {
+            Some((dep, feature)) => {
+                let (dep, weak) = match dep.strip_suffix('?') {
+                    Some(dep) => (dep, true),
+                    None => (dep, false),
+                };
+                FeatureValue::DepFeature {
+                    dep: dep.to_owned(),
+                    feature: feature.to_owned(),
+                    weak,
+                }
+            }
+            None => FeatureValue::Feature(value.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for FeatureValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeatureValue::Feature(feature) => write!(f, "{}", feature),
+            FeatureValue::Dep(dep) => write!(f, "dep:{}", dep),
+            FeatureValue::DepFeature { dep, feature, weak } => {
+                write!(f, "{}{}/{}", dep, if *weak { "?" } else { "" }, feature)
+            }
+        }
+    }
+}
+
+/// Everything a selection of features enables in a crate.
+#[cfg(feature = "index")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnabledFeatures {
+    /// The features of the crate itself, including those implied by optional dependencies
+    pub features: BTreeSet<String>,
+    /// The optional dependencies which get activated
+    pub dependencies: BTreeSet<String>,
+    /// The features enabled on dependencies, optional or not, by the name of the dependency
+    pub dependency_features: BTreeMap<String, BTreeSet<String>>,
+}
+
+#[cfg(feature = "index")]
+impl EnabledFeatures {
+    /// Whether nothing is enabled.
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+            && self.dependencies.is_empty()
+            && self.dependency_features.is_empty()
+    }
+}
+
+/// Compute what enabling `requested` features of `version` enables, together with its default
+/// features if `default_features` is set.
+///
+/// Requested features may also name a feature of a dependency, like `serde/derive`. Fails with
+/// [`ErrorKind::UnknownFeature`] if a requested feature does not exist.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{resolve_features, CrateVersionInfo};
+///
+///   let mut version = CrateVersionInfo::new("foo", semver::Version::new(1, 0, 0));
+///   version.features.insert("default".to_owned(), vec!["std".to_owned()]);
+///   version.features.insert("std".to_owned(), vec![]);
+///   version.features.insert("full".to_owned(), vec!["std".to_owned(), "extra".to_owned()]);
+///   version.features.insert("extra".to_owned(), vec![]);
+///
+///   let enabled = resolve_features(&version, &["full".to_owned()], false).unwrap();
+///   assert_eq!(enabled.features.len(), 3);
+///   assert!(resolve_features(&version, &["fulll".to_owned()], true).is_err());
+/// ```
+#[cfg(feature = "index")]
+pub fn resolve_features(
+    version: &CrateVersionInfo,
+    requested: &[String],
+    default_features: bool,
+) -> Result<EnabledFeatures> {
+    let table = version.all_features();
+    let optional: BTreeSet<&str> = version
+        .deps
+        .iter()
+        .filter(|dep| dep.optional)
+        .map(|dep| dep.name.as_str())
+        .collect();
+    let implicit_features = implicit_features(version);
+    let implicit = |name: &str| implicit_features.contains(name);
+
+    for feature in requested {
+        let exists = match FeatureValue::from(feature.as_str()) {
+            FeatureValue::DepFeature { dep, .. } => version.deps.iter().any(|d| d.name == dep),
+            _ => table.contains_key(feature.as_str()) || implicit(feature),
+        };
+        if !exists {
+            let known = table.keys().map(|name| (*name).to_owned()).chain(
+                optional
+                    .iter()
+                    .filter(|name| implicit(name))
+                    .map(|name| (*name).to_owned()),
+            );
+            return Err(ErrorKind::UnknownFeature(
+                version.name.clone(),
+                feature.clone(),
+                closest_names(feature, known),
+            )
+            .into());
+        }
+    }
+
+    let mut enabled = EnabledFeatures::default();
+    let mut weak = Vec::new();
+    let mut queue: Vec<&str> = requested.iter().map(String::as_str).collect();
+    if default_features && table.contains_key("default") {
+        queue.push("default");
+    }
+    while let Some(value) = queue.pop() {
+        match FeatureValue::from(value) {
+            FeatureValue::Feature(_) => {}
+            FeatureValue::Dep(dep) => {
+                enabled.dependencies.insert(dep);
+                continue;
+            }
+            FeatureValue::DepFeature {
+                dep,
+                feature,
+                weak: true,
+            } => {
+                weak.push((dep, feature));
+                continue;
+            }
+            FeatureValue::DepFeature { dep, feature, .. } => {
+                if let Some(&optional) = optional.get(dep.as_str()) {
+                    enabled.dependencies.insert(dep.clone());
+                    if implicit(optional) {
+                        queue.push(optional);
+                    }
+                }
+                enabled
+                    .dependency_features
+                    .entry(dep)
+                    .or_default()
+                    .insert(feature);
+                continue;
+            }
+        }
+        if !enabled.features.insert(value.to_owned()) {
+            continue;
+        }
+        match table.get(value) {
+            Some(values) => queue.extend(values.iter().map(String::as_str)),
+            None if implicit(value) => {
+                enabled.dependencies.insert(value.to_owned());
+            }
+            None => {}
+        }
+    }
+
+    // Weak features only apply to dependencies which something else activated.
+    for (dep, feature) in weak {
+        if !optional.contains(dep.as_str()) || enabled.dependencies.contains(&dep) {
+            enabled
+                .dependency_features
+                .entry(dep)
+                .or_default()
+                .insert(feature);
+        }
+    }
+
+    Ok(enabled)
+}
+
+/// The features of `version` one can enable: those of its `[features]` table but `default`, and
+/// the optional dependencies acting as features, sorted by name.
+#[cfg(feature = "index")]
+pub fn feature_names(version: &CrateVersionInfo) -> Vec<String> {
+    let mut names: BTreeSet<&str> = version.all_features().into_keys().collect();
+    names.remove("default");
+    names.extend(implicit_features(version));
+    names.into_iter().map(ToOwned::to_owned).collect()
+}
+
+/// The features of `version` enabled by its default features directly, sorted by name.
+#[cfg(feature = "index")]
+pub fn default_feature_names(version: &CrateVersionInfo) -> Vec<String> {
+    let table = version.all_features();
+    let implicit = implicit_features(version);
+    let defaults: BTreeSet<&str> = table
+        .get("default")
+        .map_or(&[][..], |values| values)
+        .iter()
+        .filter(|value| table.contains_key(value.as_str()) || implicit.contains(value.as_str()))
+        .map(String::as_str)
+        .collect();
+    defaults.into_iter().map(ToOwned::to_owned).collect()
+}
+
+/// The optional dependencies of `version` acting as features of the same name, as no feature
+/// refers to them with `dep:` and no feature has their name.
+#[cfg(feature = "index")]
+fn implicit_features(version: &CrateVersionInfo) -> BTreeSet<&str> {
+    let table = version.all_features();
+    let explicit: BTreeSet<String> = table
+        .values()
+        .flat_map(|values| values.iter())
+        .filter_map(|value| match FeatureValue::from(value.as_str()) {
+            FeatureValue::Dep(dep) => Some(dep),
+            _ => None,
+        })
+        .collect();
+    version
+        .deps
+        .iter()
+        .filter(|dep| dep.optional)
+        .map(|dep| dep.name.as_str())
+        .filter(|name| !explicit.contains(*name) && !table.contains_key(name))
+        .collect()
+}
+
+#[cfg(all(test, feature = "index"))]
+mod tests {
+    use super::*;
+    use crate::index::IndexDependency;
+
+    fn version(features: &[(&str, &[&str])], optional: &[&str]) -> CrateVersionInfo {
+        let mut version = CrateVersionInfo::new("foo", semver::Version::new(1, 0, 0));
+        for (name, values) in features {
+            version.features.insert(
+                (*name).to_owned(),
+                values.iter().map(|v| (*v).to_owned()).collect(),
+            );
+        }
+        for name in optional.iter().chain(&["serde"]) {
+            version.deps.push(IndexDependency {
+                name: (*name).to_owned(),
+                req: "1".to_owned(),
+                features: Vec::new(),
+                optional: *name != "serde",
+                default_features: true,
+                target: None,
+                kind: Default::default(),
+                registry: None,
+                package: None,
+            });
+        }
+        version
+    }
+
+    fn names(set: &BTreeSet<String>) -> Vec<&str> {
+        set.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn defaults_and_implicit_features() {
+        let version = version(
+            &[
+                ("default", &["std"]),
+                ("std", &["serde/std"]),
+                ("full", &["rand"]),
+            ],
+            &["rand"],
+        );
+
+        let enabled = resolve_features(&version, &[], true).unwrap();
+        assert_eq!(names(&enabled.features), ["default", "std"]);
+        assert!(enabled.dependencies.is_empty());
+        assert_eq!(names(&enabled.dependency_features["serde"]), ["std"]);
+
+        let enabled = resolve_features(&version, &["full".to_owned()], false).unwrap();
+        assert_eq!(names(&enabled.features), ["full", "rand"]);
+        assert_eq!(names(&enabled.dependencies), ["rand"]);
+
+        assert!(resolve_features(&version, &[], false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn selectable_features() {
+        let version = version(
+            &[
+                ("default", &["std", "dep:json"]),
+                ("std", &["serde/std"]),
+                ("full", &["rand"]),
+            ],
+            &["rand", "json"],
+        );
+        assert_eq!(feature_names(&version), ["full", "rand", "std"]);
+        assert_eq!(default_feature_names(&version), ["std"]);
+    }
+
+    #[test]
+    fn dep_and_weak_features() {
+        let version = version(
+            &[
+                ("rand", &["dep:rand", "log?/std"]),
+                ("logging", &["dep:log"]),
+                ("json", &["serde_json/std"]),
+            ],
+            &["rand", "log", "serde_json"],
+        );
+
+        let enabled = resolve_features(&version, &["rand".to_owned()], false).unwrap();
+        assert_eq!(names(&enabled.features), ["rand"]);
+        assert_eq!(names(&enabled.dependencies), ["rand"]);
+        assert!(enabled.dependency_features.is_empty());
+
+        let requested = ["rand".to_owned(), "logging".to_owned()];
+        let enabled = resolve_features(&version, &requested, false).unwrap();
+        assert_eq!(names(&enabled.dependencies), ["log", "rand"]);
+        assert_eq!(names(&enabled.dependency_features["log"]), ["std"]);
+
+        // `serde_json` is never referenced with `dep:`, so it is also a feature.
+        let enabled = resolve_features(&version, &["json".to_owned()], false).unwrap();
+        assert_eq!(names(&enabled.features), ["json", "serde_json"]);
+        assert_eq!(names(&enabled.dependencies), ["serde_json"]);
+
+        // `log` is only referenced with `dep:`, so it is not.
+        match resolve_features(&version, &["log".to_owned()], false)
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::UnknownFeature(name, feature, _) => {
+                assert_eq!((name.as_str(), feature.as_str()), ("foo", "log"))
+            }
+            kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn unknown_features_get_suggestions() {
+        let version = version(&[("derive", &[])], &[]);
+        let err = resolve_features(&version, &["derives".to_owned()], true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`foo` has no feature `derives`. Did you mean `derive`?"
+        );
+        assert!(resolve_features(&version, &["serde/derive".to_owned()], true).is_ok());
+        assert!(resolve_features(&version, &["nope/derive".to_owned()], true).is_err());
+    }
+
+    #[test]
+    fn feature_values_round_trip() {
+        for value in &["std", "dep:serde", "serde/derive", "serde?/derive"] {
+            assert_eq!(FeatureValue::from(*value).to_string(), *value);
+        }
+        assert_eq!(
+            FeatureValue::from("serde?/derive"),
+            FeatureValue::DepFeature {
+                dep: "serde".to_owned(),
+                feature: "derive".to_owned(),
+                weak: true,
+            }
+        );
+        assert_eq!(FeatureValue::from("std").dependency(), None);
+    }
+}
@@ -1,21 +1,35 @@
+use crate::cancel::check_cancelled;
 use crate::errors::*;
-use crate::registry::{registry_path, registry_path_from_url};
-use crate::{Dependency, Manifest};
-use regex::Regex;
-use std::env;
-use std::io::Write;
-use std::path::{Path, PathBuf};
-use std::time::Duration;
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
-use url::Url;
-
-#[derive(Deserialize)]
-struct CrateVersion {
-    name: String,
-    #[serde(rename = "vers")]
-    version: semver::Version,
-    yanked: bool,
-}
+use crate::index::{CrateVersionInfo, IndexSource};
+use crate::policy::{explain_selection, Compatible, Latest, Rejection, Selection, VersionPolicy};
+use crate::verbosity::verbosity;
+use crate::Dependency;
+#[cfg(feature = "fetch")]
+use {
+    crate::cache::IndexCache,
+    crate::cargo_flags::{ensure_online, is_offline},
+    crate::color::stdout_color_choice,
+    crate::index::{get_checkout_name, is_local, is_sparse, open_index},
+    crate::manifest::get_name_from_manifest,
+    crate::pkgid::GitReference,
+    crate::progress::Progress,
+    crate::protocol::protocol_for,
+    crate::proxy::{apply_proxy, git_proxy_args, proxy_disabled},
+    crate::registry::{registry_path_from_url, registry_url},
+    crate::retry::{call_with_retry, RetryPolicy},
+    crate::tls::{apply_tls, git_tls_args},
+    crate::Manifest,
+    regex::Regex,
+    std::env,
+    std::io::Write,
+    std::path::Path,
+    std::sync::atomic::{AtomicUsize, Ordering},
+    std::sync::Mutex,
+    std::thread,
+    std::time::Duration,
+    termcolor::{Color, ColorSpec, StandardStream, WriteColor},
+    url::Url,
+};
 
 /// Query latest version from a registry index
 ///
@@ -27,6 +41,7 @@ struct CrateVersion {
 /// - there is no Internet connection and offline is false.
 /// - summaries in registry index with an incorrect format.
 /// - a crate with the given name does not exist on the registry.
+#[cfg(feature = "fetch")]
 pub fn get_latest_dependency(
     crate_name: &str,
     flag_allow_prerelease: bool,
@@ -49,95 +64,850 @@ pub fn get_latest_dependency(
         return Ok(Dependency::new(crate_name).set_version(&new_version));
     }
 
-    if crate_name.is_empty() {
-        return Err(ErrorKind::EmptyCrateName.into());
+    let index = open_index(manifest_path, &resolve_registry(manifest_path, registry)?)?;
+    get_latest_dependency_from(&*index, crate_name, flag_allow_prerelease)
+}
+
+/// Query latest version from any index source
+///
+/// This is [`get_latest_dependency`] for a given [`IndexSource`], e.g. an [`InMemoryIndex`] in
+/// tests.
+///
+/// [`InMemoryIndex`]: crate::InMemoryIndex
+pub fn get_latest_dependency_from(
+    index: &dyn IndexSource,
+    crate_name: &str,
+    flag_allow_prerelease: bool,
+) -> Result<Dependency> {
+    let dep = select_from_index(
+        index,
+        crate_name,
+        &Latest {
+            allow_prerelease: flag_allow_prerelease,
+        },
+    )?;
+
+    if dep.name != crate_name && !verbosity().is_quiet() {
+        println!("WARN: Added `{}` instead of `{}`", dep.name, crate_name);
     }
 
-    let registry_path = match registry {
-        Some(url) => registry_path_from_url(url)?,
-        None => registry_path(manifest_path, None)?,
+    Ok(dep)
+}
+
+/// Query latest version from the first of several registries which has the crate
+///
+/// The registries are tried in order, e.g. an internal registry mirroring some crates before
+/// crates.io; `None` stands for the default registry, as in [`get_latest_dependency`]. A registry
+/// is skipped if it doesn't have the crate, or none of its versions can be used, but other
+/// failures, like an unreachable registry, are reported rather than hidden by the next registry.
+/// If no registry has the crate, the error is the one of the first registry.
+///
+/// The position in `registries` of the registry the version comes from is returned along with it.
+#[cfg(feature = "fetch")]
+pub fn get_latest_dependency_with_fallback(
+    crate_name: &str,
+    flag_allow_prerelease: bool,
+    manifest_path: &Path,
+    registries: &[Option<Url>],
+) -> Result<(usize, Dependency)> {
+    let registries = if registries.is_empty() {
+        &[None]
+    } else {
+        registries
     };
+    let mut first_err = None;
+    for (position, registry) in registries.iter().enumerate() {
+        let err =
+            match get_latest_dependency(crate_name, flag_allow_prerelease, manifest_path, registry)
+            {
+                Ok(dep) => return Ok((position, dep)),
+                Err(err) => err,
+            };
+        match err.kind() {
+            ErrorKind::NoCrate(..) | ErrorKind::NoMatchingVersion(..) => {
+                event!(
+                    DEBUG,
+                    crate_name,
+                    ?registry,
+                    "not available, trying the next registry"
+                );
+                first_err.get_or_insert(err);
+            }
+            _ => return Err(err),
+        }
+    }
+    Err(first_err.expect("at least one registry is tried"))
+}
 
-    let crate_versions = fuzzy_query_registry_index(crate_name, &registry_path)?;
+/// Query latest version from every one of several registries which has the crate
+///
+/// Unlike [`get_latest_dependency_with_fallback`], all `registries` are queried, e.g. to tell
+/// whether a crate name is taken on several of them, and the position in `registries` of each one
+/// having a usable version is returned along with it. Failures of the first registry, other than
+/// not having the crate, are reported; the other registries are only looked into, so one which
+/// fails, e.g. because it can't be reached, is logged and left out. If no registry has the crate,
+/// the error is the one of the first registry.
+#[cfg(feature = "fetch")]
+pub fn get_latest_dependency_candidates(
+    crate_name: &str,
+    flag_allow_prerelease: bool,
+    manifest_path: &Path,
+    registries: &[Option<Url>],
+) -> Result<Vec<(usize, Dependency)>> {
+    let registries = if registries.is_empty() {
+        &[None]
+    } else {
+        registries
+    };
+    let mut candidates = Vec::new();
+    let mut first_err = None;
+    for (position, registry) in registries.iter().enumerate() {
+        let err =
+            match get_latest_dependency(crate_name, flag_allow_prerelease, manifest_path, registry)
+            {
+                Ok(dep) => {
+                    candidates.push((position, dep));
+                    continue;
+                }
+                Err(err) => err,
+            };
+        match err.kind() {
+            ErrorKind::NoCrate(..) | ErrorKind::NoMatchingVersion(..) => {}
+            ErrorKind::Interrupted => return Err(err),
+            _ if position == 0 => return Err(err),
+            _ => {
+                event!(WARN, crate_name, ?registry, error = %err, "registry left out");
+                continue;
+            }
+        }
+        if position == 0 {
+            first_err = Some(err);
+        }
+    }
+    match first_err {
+        Some(err) if candidates.is_empty() => Err(err),
+        _ => Ok(candidates),
+    }
+}
 
-    let dep = read_latest_version(&crate_versions, flag_allow_prerelease)?;
+/// Query the latest versions of several crates from the same registry index
+///
+/// Duplicate names are only looked up once, and up to `MAX_CONCURRENT_QUERIES` crates are queried
+/// at the same time. The results are returned in the order of `crate_names`. Each lookup can fail
+/// for the same reasons as [`get_latest_dependency`].
+#[cfg(feature = "fetch")]
+pub fn get_latest_dependencies(
+    crate_names: &[&str],
+    flag_allow_prerelease: bool,
+    manifest_path: &Path,
+    registry: &Option<Url>,
+) -> Vec<Result<Dependency>> {
+    let _span = span!(INFO, "get_latest_dependencies", crates = crate_names.len());
+    let mut unique_names: Vec<&str> = Vec::new();
+    for name in crate_names {
+        if !unique_names.contains(name) {
+            unique_names.push(name);
+        }
+    }
 
-    if dep.name != crate_name {
-        println!("WARN: Added `{}` instead of `{}`", dep.name, crate_name);
+    let progress = Progress::new("Querying", unique_names.len());
+    let mut results: Vec<Option<Result<Dependency>>> = if env::var("CARGO_IS_TEST").is_ok()
+        || unique_names.iter().any(|n| n.is_empty())
+    {
+        unique_names
+            .iter()
+            .map(|name| {
+                let result =
+                    get_latest_dependency(name, flag_allow_prerelease, manifest_path, registry);
+                progress.tick(name);
+                Some(result)
+            })
+            .collect()
+    } else {
+        resolve_registry(manifest_path, registry)
+            .map(|registry| {
+                query_index_concurrently(
+                    &unique_names,
+                    &progress,
+                    &|| open_index(manifest_path, &registry),
+                    &|index, i| {
+                        get_latest_dependency_from(index, unique_names[i], flag_allow_prerelease)
+                    },
+                )
+            })
+            .unwrap_or_else(|err| {
+                unique_names
+                    .iter()
+                    .map(|_| Some(Err(err.duplicate())))
+                    .collect()
+            })
+    };
+
+    crate_names
+        .iter()
+        .map(|name| {
+            let index = unique_names
+                .iter()
+                .position(|n| n == name)
+                .expect("all names are listed");
+            let result = results[index].take().expect("every query has a result");
+            // Keep a copy for any later duplicates of this name.
+            results[index] = Some(match &result {
+                Ok(dep) => Ok(dep.clone()),
+                Err(err) => Err(err.duplicate()),
+            });
+            result
+        })
+        .collect()
+}
+
+/// Query the latest versions of several crates from the same registry index, along with the
+/// versions a dependency on each could be upgraded to from its current requirement
+///
+/// This is [`get_latest_dependencies`] and [`get_upgrade_candidates`] in a single pass over the
+/// index, e.g. to report where dependencies stand after upgrading them. The results are returned in
+/// the order of `crates`.
+#[cfg(feature = "fetch")]
+pub fn get_latest_dependencies_with_candidates(
+    crates: &[(&str, Option<&semver::VersionReq>)],
+    flag_allow_prerelease: bool,
+    manifest_path: &Path,
+    registry: &Option<Url>,
+) -> Vec<Result<(Dependency, UpgradeCandidates)>> {
+    let _span = span!(
+        INFO,
+        "get_latest_dependencies_with_candidates",
+        crates = crates.len()
+    );
+    let names: Vec<&str> = crates.iter().map(|(name, _)| *name).collect();
+    let progress = Progress::new("Querying", names.len());
+    if env::var("CARGO_IS_TEST").is_ok() || names.iter().any(|n| n.is_empty()) {
+        return crates
+            .iter()
+            .map(|&(name, version_req)| {
+                let result =
+                    get_latest_dependency(name, flag_allow_prerelease, manifest_path, registry)
+                        .and_then(|dep| {
+                            let candidates = get_upgrade_candidates(
+                                name,
+                                version_req,
+                                flag_allow_prerelease,
+                                manifest_path,
+                                registry,
+                            )?;
+                            Ok((dep, candidates))
+                        });
+                progress.tick(name);
+                result
+            })
+            .collect();
+    }
+
+    let registry = match resolve_registry(manifest_path, registry) {
+        Ok(registry) => registry,
+        Err(err) => return crates.iter().map(|_| Err(err.duplicate())).collect(),
+    };
+    query_index_concurrently(
+        &names,
+        &progress,
+        &|| open_index(manifest_path, &registry),
+        &|index, i| {
+            let (name, version_req) = crates[i];
+            let dep = get_latest_dependency_from(index, name, flag_allow_prerelease)?;
+            let candidates =
+                get_upgrade_candidates_from(index, name, version_req, flag_allow_prerelease)?;
+            Ok((dep, candidates))
+        },
+    )
+    .into_iter()
+    .map(|result| result.expect("every query has a result"))
+    .collect()
+}
+
+/// The number of registry index queries `get_latest_dependencies` runs at the same time.
+#[cfg(feature = "fetch")]
+const MAX_CONCURRENT_QUERIES: usize = 8;
+
+/// Run `query` for the `i`th of `crate_names` on an index from `open`, for every one of them.
+#[cfg(feature = "fetch")]
+fn query_index_concurrently<T: Send>(
+    crate_names: &[&str],
+    progress: &Progress,
+    open: &(dyn Fn() -> Result<Box<dyn IndexSource>> + Sync),
+    query: &(dyn Fn(&dyn IndexSource, usize) -> Result<T> + Sync),
+) -> Vec<Option<Result<T>>> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(
+        crate_names
+            .iter()
+            .map(|_| None)
+            .collect::<Vec<Option<Result<T>>>>(),
+    );
+
+    thread::scope(|scope| {
+        for _ in 0..MAX_CONCURRENT_QUERIES.min(crate_names.len()) {
+            scope.spawn(|| {
+                // `git2::Repository` can't be shared between threads, so each worker opens its own.
+                let index = open();
+                loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    let crate_name = match crate_names.get(i) {
+                        Some(crate_name) => *crate_name,
+                        None => break,
+                    };
+                    let result = match index {
+                        Ok(ref index) => query(&**index, i),
+                        Err(ref err) => Err(err.duplicate()),
+                    };
+                    results.lock().unwrap_or_else(|e| e.into_inner())[i] = Some(result);
+                    progress.tick(crate_name);
+                }
+            });
+        }
+    });
+
+    results.into_inner().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Query the newest version of a crate which satisfies `version_req` from a registry index
+///
+/// Yanked versions are never selected. Pre-releases are only selected if `version_req` asks for
+/// them, following cargo's matching rules. This fails under the same conditions as
+/// [`get_latest_dependency`].
+#[cfg(feature = "fetch")]
+pub fn get_compatible_dependency(
+    crate_name: &str,
+    version_req: &semver::VersionReq,
+    manifest_path: &Path,
+    registry: &Option<Url>,
+) -> Result<Dependency> {
+    if env::var("CARGO_IS_TEST").is_ok() {
+        // We are in a simulated reality. Nothing is real here.
+        let new_version = format!("{}--COMPATIBLE_VERSION_TEST", crate_name);
+        return Ok(Dependency::new(crate_name).set_version(&new_version));
+    }
+
+    let index = open_index(manifest_path, &resolve_registry(manifest_path, registry)?)?;
+    get_compatible_dependency_from(&*index, crate_name, version_req)
+}
+
+/// Query the newest version satisfying `version_req` from any index source
+///
+/// This is [`get_compatible_dependency`] for a given [`IndexSource`].
+pub fn get_compatible_dependency_from(
+    index: &dyn IndexSource,
+    crate_name: &str,
+    version_req: &semver::VersionReq,
+) -> Result<Dependency> {
+    let dep = select_from_index(
+        index,
+        crate_name,
+        &Compatible {
+            req: version_req.clone(),
+        },
+    )?;
+
+    if dep.name != crate_name && !verbosity().is_quiet() {
+        println!("WARN: Using `{}` instead of `{}`", dep.name, crate_name);
     }
 
     Ok(dep)
 }
 
-// Checks whether a version object is a stable release
-fn version_is_stable(version: &CrateVersion) -> bool {
-    !version.version.is_prerelease()
+/// The versions of a crate a dependency on it could be upgraded to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpgradeCandidates {
+    /// The newest version, leaving out yanked versions and pre-releases unless allowed
+    pub latest: Option<String>,
+    /// The newest version the current requirement allows, leaving out yanked versions
+    pub latest_compatible: Option<String>,
+    /// The yanked versions newer than `latest`, newest first
+    pub yanked: Vec<semver::Version>,
+}
+
+/// Query the versions a dependency on a crate could be upgraded to from a registry index
+///
+/// `version_req` is the current requirement, if it has one. This fails under the same conditions
+/// as [`get_crate_versions`].
+#[cfg(feature = "fetch")]
+pub fn get_upgrade_candidates(
+    crate_name: &str,
+    version_req: Option<&semver::VersionReq>,
+    flag_allow_prerelease: bool,
+    manifest_path: &Path,
+    registry: &Option<Url>,
+) -> Result<UpgradeCandidates> {
+    if env::var("CARGO_IS_TEST").is_ok() {
+        // We are in a simulated reality. Nothing is real here.
+        let latest =
+            get_latest_dependency(crate_name, flag_allow_prerelease, manifest_path, registry)?;
+        return Ok(UpgradeCandidates {
+            latest: latest.version().map(ToOwned::to_owned),
+            latest_compatible: version_req
+                .map(|_| format!("{}--COMPATIBLE_VERSION_TEST", crate_name)),
+            yanked: Vec::new(),
+        });
+    }
+
+    let index = open_index(manifest_path, &resolve_registry(manifest_path, registry)?)?;
+    get_upgrade_candidates_from(&*index, crate_name, version_req, flag_allow_prerelease)
+}
+
+/// Query the versions a dependency on a crate could be upgraded to from any index source
+///
+/// This is [`get_upgrade_candidates`] for a given [`IndexSource`].
+pub fn get_upgrade_candidates_from(
+    index: &dyn IndexSource,
+    crate_name: &str,
+    version_req: Option<&semver::VersionReq>,
+    flag_allow_prerelease: bool,
+) -> Result<UpgradeCandidates> {
+    let versions = fuzzy_query(index, crate_name)?;
+    let latest = explain_selection(
+        &Latest {
+            allow_prerelease: flag_allow_prerelease,
+        },
+        &versions,
+    );
+    let latest_compatible = version_req.and_then(|req| {
+        Compatible { req: req.clone() }
+            .select(&versions)
+            .ok()
+            .map(|compatible| compatible.version.to_string())
+    });
+    Ok(UpgradeCandidates {
+        latest: latest.selected.map(|version| version.to_string()),
+        latest_compatible,
+        yanked: latest
+            .rejected
+            .into_iter()
+            .filter(|(_, rejection)| *rejection == Rejection::Yanked)
+            .map(|(version, _)| version)
+            .collect(),
+    })
+}
+
+/// Query the version of a crate chosen by `policy` from a registry index
+///
+/// This is the general form of [`get_latest_dependency`] and [`get_compatible_dependency`], for
+/// custom selection rules. It fails under the same conditions, or with whatever error the policy
+/// reports.
+#[cfg(feature = "fetch")]
+pub fn get_dependency_with_policy(
+    crate_name: &str,
+    policy: &dyn VersionPolicy,
+    manifest_path: &Path,
+    registry: &Option<Url>,
+) -> Result<Dependency> {
+    let index = open_index(manifest_path, &resolve_registry(manifest_path, registry)?)?;
+    get_dependency_with_policy_from(&*index, crate_name, policy)
+}
+
+/// Query the version of a crate chosen by `policy` from any index source
+pub fn get_dependency_with_policy_from(
+    index: &dyn IndexSource,
+    crate_name: &str,
+    policy: &dyn VersionPolicy,
+) -> Result<Dependency> {
+    let dep = select_from_index(index, crate_name, policy)?;
+
+    if dep.name != crate_name && !verbosity().is_quiet() {
+        println!("WARN: Using `{}` instead of `{}`", dep.name, crate_name);
+    }
+
+    Ok(dep)
+}
+
+/// Explain which version of a crate `policy` selects from a registry index, and why it passes
+/// over the newer ones
+///
+/// This fails under the same conditions as [`get_crate_versions`]; a policy selecting nothing is
+/// explained rather than reported as an error.
+#[cfg(feature = "fetch")]
+pub fn explain_dependency(
+    crate_name: &str,
+    policy: &dyn VersionPolicy,
+    manifest_path: &Path,
+    registry: &Option<Url>,
+) -> Result<Selection> {
+    let index = open_index(manifest_path, &resolve_registry(manifest_path, registry)?)?;
+    explain_dependency_from(&*index, crate_name, policy)
+}
+
+/// Explain which version of a crate `policy` selects from any index source
+///
+/// This is [`explain_dependency`] for a given [`IndexSource`].
+pub fn explain_dependency_from(
+    index: &dyn IndexSource,
+    crate_name: &str,
+    policy: &dyn VersionPolicy,
+) -> Result<Selection> {
+    Ok(explain_selection(policy, &fuzzy_query(index, crate_name)?))
+}
+
+/// Read every published version of a crate from a registry index
+///
+/// Like [`get_latest_dependency`], this matches names fuzzily, so `foo_bar` finds `foo-bar`; check
+/// [`CrateVersionInfo::name`] for the actual name. Yanked versions are included.
+#[cfg(feature = "fetch")]
+pub fn get_crate_versions(
+    crate_name: &str,
+    manifest_path: &Path,
+    registry: &Option<Url>,
+) -> Result<Vec<CrateVersionInfo>> {
+    let index = open_index(manifest_path, &resolve_registry(manifest_path, registry)?)?;
+    fuzzy_query(&*index, crate_name)
+}
+
+/// Re-check which of `versions` of a crate are yanked
+///
+/// Unlike the other queries, this bypasses the cache for this one crate: on sparse registries its
+/// index file is revalidated even if the cached copy is still fresh, so yanks published since are
+/// seen without refreshing everything else. Versions the index does not list are not reported.
+#[cfg(feature = "fetch")]
+pub fn get_yanked_versions(
+    crate_name: &str,
+    versions: &[semver::Version],
+    manifest_path: &Path,
+    registry: &Option<Url>,
+) -> Result<Vec<semver::Version>> {
+    if env::var("CARGO_IS_TEST").is_ok() {
+        // We are in a simulated reality. Nothing is real here.
+        return Ok(match crate_name {
+            "test_yanked" => versions.to_vec(),
+            _ => Vec::new(),
+        });
+    }
+
+    let index = open_index(manifest_path, &resolve_registry(manifest_path, registry)?)?;
+    get_yanked_versions_from(&*index, crate_name, versions)
+}
+
+/// Re-check which of `versions` of a crate are yanked in any index source
+pub fn get_yanked_versions_from(
+    index: &dyn IndexSource,
+    crate_name: &str,
+    versions: &[semver::Version],
+) -> Result<Vec<semver::Version>> {
+    let published = index
+        .refresh_crate_versions(crate_name)?
+        .chain_err(|| ErrorKind::NoCrate(crate_name.to_owned(), Vec::new()))?;
+    Ok(versions
+        .iter()
+        .filter(|version| {
+            published
+                .iter()
+                .any(|info| info.yanked && info.version == **version)
+        })
+        .cloned()
+        .collect())
+}
+
+/// Read the checksum a registry index records for a published version of a crate
+///
+/// This is the SHA-256 of the `.crate` file as a hex string, the one cargo checks the download
+/// against, e.g. for provenance tooling to cross-check what cargo later fetches. `None` if the
+/// index lists the version without a checksum, as vendored sources may. Names are matched fuzzily,
+/// like in [`get_latest_dependency`], and yanked versions are included.
+#[cfg(feature = "fetch")]
+pub fn get_version_checksum(
+    crate_name: &str,
+    version: &semver::Version,
+    manifest_path: &Path,
+    registry: &Option<Url>,
+) -> Result<Option<String>> {
+    if env::var("CARGO_IS_TEST").is_ok() {
+        // We are in a simulated reality. Nothing is real here.
+        return Ok(Some(format!("{}--CHECKSUM_TEST", crate_name)));
+    }
+
+    let index = open_index(manifest_path, &resolve_registry(manifest_path, registry)?)?;
+    get_version_checksum_from(&*index, crate_name, version)
+}
+
+/// Read the checksum of a published version of a crate from any index source
+///
+/// This is [`get_version_checksum`] for a given [`IndexSource`]. It fails with
+/// [`ErrorKind::NoMatchingVersion`] if the index doesn't list `version`.
+pub fn get_version_checksum_from(
+    index: &dyn IndexSource,
+    crate_name: &str,
+    version: &semver::Version,
+) -> Result<Option<String>> {
+    let versions = fuzzy_query_where(index, crate_name, &|candidate| {
+        candidate.version == *version
+    })?;
+    let info = versions
+        .into_iter()
+        .next()
+        .ok_or_else(|| ErrorKind::NoMatchingVersion(crate_name.to_owned(), false, false))?;
+    Ok(info.checksum().map(ToOwned::to_owned))
+}
+
+/// The given registry, or the default registry for the manifest.
+#[cfg(feature = "fetch")]
+pub(crate) fn resolve_registry(manifest_path: &Path, registry: &Option<Url>) -> Result<Url> {
+    match registry {
+        Some(url) => Ok(url.clone()),
+        None => registry_url(manifest_path, None),
+    }
+}
+
+/// Fuzzy query crate from registry index, trying the spellings [`IndexSource::fuzzy_names`] generates
+pub(crate) fn fuzzy_query(
+    index: &dyn IndexSource,
+    crate_name: &str,
+) -> Result<Vec<CrateVersionInfo>> {
+    fuzzy_query_with(index, crate_name, |name| index.crate_versions(name))
+}
+
+/// Like [`fuzzy_query`], but only keeping the versions `keep` accepts, as
+/// [`IndexSource::crate_versions_where`] does.
+pub(crate) fn fuzzy_query_where(
+    index: &dyn IndexSource,
+    crate_name: &str,
+    keep: &dyn Fn(&CrateVersionInfo) -> bool,
+) -> Result<Vec<CrateVersionInfo>> {
+    fuzzy_query_with(index, crate_name, |name| {
+        index.crate_versions_where(name, keep)
+    })
+}
+
+fn fuzzy_query_with(
+    index: &dyn IndexSource,
+    crate_name: &str,
+    query: impl Fn(&str) -> Result<Option<Vec<CrateVersionInfo>>>,
+) -> Result<Vec<CrateVersionInfo>> {
+    if crate_name.is_empty() {
+        return Err(ErrorKind::EmptyCrateName.into());
+    }
+
+    let _span = span!(DEBUG, "query", crate_name);
+    for the_name in index.fuzzy_names().candidates(crate_name) {
+        check_cancelled()?;
+        if let Some(versions) = query(&the_name)? {
+            event!(DEBUG, name = %the_name, versions = versions.len(), "found");
+            return Ok(versions);
+        }
+        event!(TRACE, name = %the_name, "not in the index");
+    }
+    let suggestions = index.similar_names(crate_name).unwrap_or_default();
+    Err(ErrorKind::NoCrate(crate_name.to_owned(), suggestions).into())
 }
 
 /// Read latest version from Versions structure
+#[cfg(test)]
 fn read_latest_version(
-    versions: &[CrateVersion],
+    versions: &[CrateVersionInfo],
     flag_allow_prerelease: bool,
 ) -> Result<Dependency> {
-    let latest = versions
-        .iter()
-        .filter(|&v| flag_allow_prerelease || version_is_stable(v))
-        .filter(|&v| !v.yanked)
-        .max_by_key(|&v| v.version.clone())
-        .ok_or(ErrorKind::NoVersionsAvailable)?;
+    select_dependency(
+        versions,
+        Latest {
+            allow_prerelease: flag_allow_prerelease,
+        },
+    )
+}
 
-    let name = &latest.name;
-    let version = latest.version.to_string();
-    Ok(Dependency::new(name).set_version(&version))
+/// Read the newest version matching `version_req` from Versions structure
+#[cfg(test)]
+fn read_compatible_version(
+    versions: &[CrateVersionInfo],
+    version_req: &semver::VersionReq,
+) -> Result<Dependency> {
+    select_dependency(
+        versions,
+        Compatible {
+            req: version_req.clone(),
+        },
+    )
 }
 
-/// update registry index for given project
-pub fn update_registry_index(registry: &Url, quiet: bool) -> Result<()> {
-    let registry_path = registry_path_from_url(registry)?;
+fn select_dependency(
+    versions: &[CrateVersionInfo],
+    policy: impl VersionPolicy,
+) -> Result<Dependency> {
+    let selected = policy.select(versions)?;
+    Ok(Dependency::new(&selected.name).set_version(&selected.version.to_string()))
+}
 
-    let colorchoice = if atty::is(atty::Stream::Stdout) {
-        ColorChoice::Auto
-    } else {
-        ColorChoice::Never
-    };
-    let mut output = StandardStream::stdout(colorchoice);
+/// Select a version of `crate_name` with `policy`, only parsing the versions it could select in
+/// full.
+pub(crate) fn select_from_index(
+    index: &dyn IndexSource,
+    crate_name: &str,
+    policy: &dyn VersionPolicy,
+) -> Result<Dependency> {
+    let candidates = fuzzy_query_where(index, crate_name, &|candidate| {
+        !candidate.yanked && policy.accepts(candidate)
+    })?;
+    match select_dependency(&candidates, policy) {
+        Ok(dep) => Ok(dep),
+        // Explaining why nothing could be selected takes every version.
+        Err(_) => select_dependency(&fuzzy_query(index, crate_name)?, policy),
+    }
+}
 
-    if !registry_path.as_path().exists() {
-        output.set_color(ColorSpec::new().set_fg(Some(Color::Green)).set_bold(true))?;
-        write!(output, "{:>12}", "Initializing")?;
-        output.reset()?;
-        writeln!(output, " '{}' index", registry)?;
+/// The cache key recording when the registry index was last fetched.
+#[cfg(feature = "fetch")]
+const INDEX_UPDATED_KEY: &str = ".index-updated";
 
-        let mut opts = git2::RepositoryInitOptions::new();
-        opts.bare(true);
-        git2::Repository::init_opts(&registry_path, &opts)?;
+/// Update the registry index unless it was fetched less than `max_age` ago.
+///
+/// Consecutive invocations share the time of the last update through the [`IndexCache`], so a
+/// `max_age` of zero always updates.
+#[cfg(feature = "fetch")]
+pub fn update_registry_index_if_stale(
+    registry: &Url,
+    quiet: bool,
+    max_age: Duration,
+) -> Result<()> {
+    let cache = IndexCache::for_registry(registry)?;
+    if registry_path_from_url(registry)?.exists()
+        && cache.get_fresh(INDEX_UPDATED_KEY, max_age)?.is_some()
+    {
+        event!(DEBUG, %registry, ?max_age, "index is fresh, not updating it");
         return Ok(());
     }
+    update_registry_index(registry, quiet)
+}
+
+/// update registry index for given project
+///
+/// Sparse registries have no index to update: their entries are fetched, and revalidated, when
+/// they are queried. Neither have local sources, which are used as they are. Registries served by
+/// a [registered protocol](crate::register_protocol) are updated by it. Offline (see
+/// [`set_offline`](crate::set_offline)), no index is updated.
+#[cfg(feature = "fetch")]
+pub fn update_registry_index(registry: &Url, quiet: bool) -> Result<()> {
+    if is_offline() {
+        event!(DEBUG, %registry, "offline, not updating the index");
+        return Ok(());
+    }
+    let quiet = quiet || verbosity().is_quiet();
+    if let Some(protocol) = protocol_for(registry) {
+        return protocol.update(registry, quiet);
+    }
+    if is_sparse(registry) || is_local(registry) {
+        return Ok(());
+    }
+    let _span = span!(INFO, "update_index", %registry);
+    check_cancelled()?;
+    let registry_path = registry_path_from_url(registry)?;
 
-    let repo = git2::Repository::open(&registry_path)?;
     if !quiet {
+        let colorchoice = stdout_color_choice();
+        let mut output = StandardStream::stdout(colorchoice);
+        let status = if registry_path.exists() {
+            "Updating"
+        } else {
+            "Initializing"
+        };
         output.set_color(ColorSpec::new().set_fg(Some(Color::Green)).set_bold(true))?;
-        write!(output, "{:>12}", "Updating")?;
+        write!(output, "{:>12}", status)?;
         output.reset()?;
         writeln!(output, " '{}' index", registry)?;
     }
 
-    let refspec = format!(
-        "refs/heads/{0}:refs/remotes/origin/{0}",
-        get_checkout_name(registry_path)?
-    );
-    fetch_with_cli(&repo, registry.as_str(), &refspec)?;
+    fetch_git_index(&registry_path, registry.as_str())?;
 
-    Ok(())
+    IndexCache::for_registry(registry)?.insert(INDEX_UPDATED_KEY, registry.as_str().as_bytes())
+}
+
+/// Fetch the git index at `url`, or another repository, into the bare repository at
+/// `registry_path`.
+///
+/// A missing index is cloned shallowly, with only the newest commit of the default branch, which
+/// is all we need to look up versions. A shallow index stays shallow; a full one, e.g. created by
+/// cargo, is fetched incrementally as it is.
+///
+/// Like cargo, the index is fetched with the `git` command line if `net.git-fetch-with-cli` is
+/// set, and with libgit2 otherwise, which can't fetch shallowly.
+#[cfg(feature = "fetch")]
+pub(crate) fn fetch_git_index(registry_path: &Path, url: &str) -> Result<()> {
+    ensure_online(url)?;
+    let repo = open_bare_repository(registry_path)?;
+    // Follow the remote's default branch unless the index already tracks a named one; nothing
+    // was fetched yet if an earlier attempt got interrupted.
+    let refspec = match get_checkout_name(registry_path) {
+        Ok(branch) if branch != "HEAD" => format!("refs/heads/{0}:refs/remotes/origin/{0}", branch),
+        _ => "HEAD:refs/remotes/origin/HEAD".to_owned(),
+    };
+    let shallow = repo.is_shallow() || refspec.starts_with("HEAD:");
+    fetch_git_refspec(&repo, url, &refspec, shallow)
+}
+
+/// The commit `reference` of the git repository at `repo` points to, or the newest one of its
+/// default branch, as the full hash cargo pins a `rev` to.
+///
+/// Only that commit is fetched, shallowly if the command line is used, like a git index. A `rev`
+/// is returned as it is.
+#[cfg(feature = "fetch")]
+pub fn get_git_revision(repo: &str, reference: Option<&GitReference>) -> Result<String> {
+    ensure_online(repo)?;
+    let url = Url::parse(repo).chain_err(|| format!("Invalid URL `{}`", repo))?;
+    let (refspec, reference) = match reference {
+        Some(GitReference::Rev(rev)) => return Ok(rev.clone()),
+        Some(GitReference::Branch(branch)) => (
+            format!("refs/heads/{0}:refs/remotes/origin/{0}", branch),
+            format!("refs/remotes/origin/{}", branch),
+        ),
+        Some(GitReference::Tag(tag)) => (
+            format!("refs/tags/{0}:refs/tags/{0}", tag),
+            format!("refs/tags/{}", tag),
+        ),
+        None => (
+            "HEAD:refs/remotes/origin/HEAD".to_owned(),
+            "refs/remotes/origin/HEAD".to_owned(),
+        ),
+    };
+    let repository = open_bare_repository(&crate::registry::git_path_from_url(&url)?)?;
+    fetch_git_refspec(&repository, repo, &refspec, true)?;
+    let commit = repository.find_reference(&reference)?.peel_to_commit()?;
+    Ok(commit.id().to_string())
+}
+
+/// Open the bare repository at `path`, creating it if it doesn't exist yet.
+#[cfg(feature = "fetch")]
+fn open_bare_repository(path: &Path) -> Result<git2::Repository> {
+    if path.exists() {
+        Ok(git2::Repository::open(path)?)
+    } else {
+        let mut opts = git2::RepositoryInitOptions::new();
+        opts.bare(true);
+        Ok(git2::Repository::init_opts(path, &opts)?)
+    }
+}
+
+/// Fetch `refspec` of the repository at `url` into `repo`, with the `git` command line if
+/// `net.git-fetch-with-cli` is set, and with libgit2 otherwise.
+#[cfg(feature = "fetch")]
+fn fetch_git_refspec(
+    repo: &git2::Repository,
+    url: &str,
+    refspec: &str,
+    shallow: bool,
+) -> Result<()> {
+    if crate::git::git_fetch_with_cli()? {
+        event!(DEBUG, url, %refspec, shallow, "fetching git repository");
+        fetch_with_cli(repo, url, refspec, shallow)
+    } else {
+        event!(DEBUG, url, %refspec, "fetching git repository with libgit2");
+        crate::git::fetch_with_libgit2(repo, url, refspec)
+    }
 }
 
 // https://github.com/rust-lang/cargo/blob/57986eac7157261c33f0123bade7ccd20f15200f/src/cargo/sources/git/utils.rs#L758
-fn fetch_with_cli(repo: &git2::Repository, url: &str, refspec: &str) -> Result<()> {
-    let cmd = subprocess::Exec::shell("git")
+#[cfg(feature = "fetch")]
+fn fetch_with_cli(repo: &git2::Repository, url: &str, refspec: &str, shallow: bool) -> Result<()> {
+    let mut cmd = subprocess::Exec::cmd("git")
+        .args(&git_proxy_args()?)
+        .args(&git_tls_args()?)
         .arg("fetch")
         .arg("--tags") // fetch all tags
         .arg("--force") // handle force pushes
         .arg("--update-head-ok") // see discussion in rust-lang/cargo#2078
+        .args(if shallow { &["--depth=1"][..] } else { &[] })
         .arg(url)
         .arg(refspec)
         // If cargo is run by git (for example, the `exec` command in `git
@@ -151,18 +921,241 @@ fn fetch_with_cli(repo: &git2::Repository, url: &str, refspec: &str) -> Result<(
         .env_remove("GIT_INDEX_FILE")
         .env_remove("GIT_OBJECT_DIRECTORY")
         .env_remove("GIT_ALTERNATE_OBJECT_DIRECTORIES")
-        .cwd(repo.path());
+        .cwd(repo.path())
+        .stderr(subprocess::Redirection::Merge);
+    if proxy_disabled() {
+        for name in &[
+            "http_proxy",
+            "HTTP_PROXY",
+            "https_proxy",
+            "HTTPS_PROXY",
+            "all_proxy",
+            "ALL_PROXY",
+        ] {
+            cmd = cmd.env_remove(name);
+        }
+    }
 
-    let _ = cmd.capture().map_err(|e| match e {
+    let output = cmd.capture().map_err(|e| match e {
         subprocess::PopenError::IoError(io) => ErrorKind::Io(io),
         _ => unreachable!("expected only io error"),
     })?;
+    // git was interrupted by the same Ctrl-C.
+    check_cancelled()?;
+    if !output.success() {
+        return Err(
+            ErrorKind::Network(url.to_owned(), output.stdout_str().trim().to_owned()).into(),
+        );
+    }
     Ok(())
 }
 
+#[test]
+fn query_in_memory_index() {
+    let mut index = crate::InMemoryIndex::new();
+    for version in &["0.1.0", "0.2.0", "0.2.1", "0.3.0-alpha.1"] {
+        index.insert(CrateVersionInfo::new(
+            "foo-bar",
+            semver::Version::parse(version).unwrap(),
+        ));
+    }
+
+    let latest = get_latest_dependency_from(&index, "foo_bar", false).unwrap();
+    assert_eq!(latest.name, "foo-bar");
+    assert_eq!(latest.version(), Some("0.2.1"));
+    let prerelease = get_latest_dependency_from(&index, "foo-bar", true).unwrap();
+    assert_eq!(prerelease.version(), Some("0.3.0-alpha.1"));
+    let req = semver::VersionReq::parse("0.1").unwrap();
+    let compatible = get_compatible_dependency_from(&index, "foo-bar", &req).unwrap();
+    assert_eq!(compatible.version(), Some("0.1.0"));
+    assert!(get_latest_dependency_from(&index, "baz", false).is_err());
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn falls_back_to_later_registries() {
+    struct Mirror(&'static str, &'static [&'static str]);
+
+    impl crate::RegistryProtocol for Mirror {
+        fn handles(&self, registry: &Url) -> bool {
+            registry.host_str() == Some(self.0) && registry.scheme() == "mirror+https"
+        }
+
+        fn open(&self, _: &Path, _: &Url) -> Result<Box<dyn IndexSource>> {
+            let mut index = crate::InMemoryIndex::new();
+            for name in self.1 {
+                index.insert(CrateVersionInfo::new(name, semver::Version::new(1, 0, 0)));
+            }
+            Ok(Box::new(index))
+        }
+    }
+
+    crate::register_protocol(Mirror("internal.example", &["internal-only", "both"]));
+    crate::register_protocol(Mirror("public.example", &["both", "public-only"]));
+    let registries = [
+        Some(Url::parse("mirror+https://internal.example/").unwrap()),
+        Some(Url::parse("mirror+https://public.example/").unwrap()),
+    ];
+    let manifest_path = Path::new("Cargo.toml");
+    let lookup =
+        |name| get_latest_dependency_with_fallback(name, false, manifest_path, &registries);
+
+    assert_eq!(lookup("internal-only").unwrap().0, 0);
+    assert_eq!(lookup("both").unwrap().0, 0);
+    assert_eq!(lookup("public-only").unwrap().0, 1);
+    assert_eq!(
+        lookup("nowhere").unwrap_err().to_string(),
+        "The crate `nowhere` could not be found in registry index."
+    );
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn finds_the_registries_having_a_crate() {
+    struct Registry(&'static str, &'static [&'static str]);
+
+    impl crate::RegistryProtocol for Registry {
+        fn handles(&self, registry: &Url) -> bool {
+            registry.host_str() == Some(self.0) && registry.scheme() == "candidates+https"
+        }
+
+        fn open(&self, _: &Path, _: &Url) -> Result<Box<dyn IndexSource>> {
+            if self.1.is_empty() {
+                return Err(ErrorKind::Network(self.0.to_owned(), "unreachable".to_owned()).into());
+            }
+            let mut index = crate::InMemoryIndex::new();
+            for name in self.1 {
+                index.insert(CrateVersionInfo::new(name, semver::Version::new(1, 0, 0)));
+            }
+            Ok(Box::new(index))
+        }
+    }
+
+    crate::register_protocol(Registry("default.example", &["default-only", "both"]));
+    crate::register_protocol(Registry("other.example", &["both", "other-only"]));
+    crate::register_protocol(Registry("down.example", &[]));
+    let registries = [
+        Some(Url::parse("candidates+https://default.example/").unwrap()),
+        Some(Url::parse("candidates+https://other.example/").unwrap()),
+        Some(Url::parse("candidates+https://down.example/").unwrap()),
+    ];
+    let manifest_path = Path::new("Cargo.toml");
+    let lookup = |name| {
+        get_latest_dependency_candidates(name, false, manifest_path, &registries).map(
+            |candidates| {
+                candidates
+                    .into_iter()
+                    .map(|(position, _)| position)
+                    .collect::<Vec<_>>()
+            },
+        )
+    };
+
+    assert_eq!(lookup("both").unwrap(), vec![0, 1]);
+    assert_eq!(lookup("default-only").unwrap(), vec![0]);
+    assert_eq!(lookup("other-only").unwrap(), vec![1]);
+    assert!(lookup("nowhere").is_err());
+    // Only the registry looked up first may not fail.
+    assert!(
+        get_latest_dependency_candidates("both", false, manifest_path, &registries[2..]).is_err()
+    );
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn shallow_git_index() {
+    let upstream = tempfile::tempdir().unwrap();
+    let repo = git2::Repository::init(upstream.path()).unwrap();
+    let signature = git2::Signature::now("test", "test@example.com").unwrap();
+    let mut parent = None;
+    for version in &["0.1.0", "0.2.0"] {
+        let file = upstream.path().join("3/f/foo");
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        let mut content = std::fs::read_to_string(&file).unwrap_or_default();
+        content.push_str(&format!("{{\"name\":\"foo\",\"vers\":\"{}\"}}\n", version));
+        std::fs::write(&file, content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("3/f/foo")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parents: Vec<_> = parent.iter().collect();
+        let commit = repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                version,
+                &tree,
+                &parents,
+            )
+            .unwrap();
+        parent = Some(repo.find_commit(commit).unwrap());
+    }
+    let url = Url::from_directory_path(upstream.path()).unwrap();
+
+    let cache = tempfile::tempdir().unwrap();
+    let registry_path = cache.path().join("index");
+    fetch_git_index(&registry_path, url.as_str()).unwrap();
+    assert!(git2::Repository::open(&registry_path).unwrap().is_shallow());
+    let index = crate::GitIndex::open(&registry_path).unwrap();
+    let versions = index.crate_versions("foo").unwrap().unwrap();
+    assert_eq!(versions.len(), 2);
+
+    // Fetching again is incremental and keeps the index shallow.
+    fetch_git_index(&registry_path, url.as_str()).unwrap();
+    assert!(git2::Repository::open(&registry_path).unwrap().is_shallow());
+}
+
+#[test]
+fn yanked_versions_from_index() {
+    let mut index = crate::InMemoryIndex::new();
+    for (version, yanked) in &[("0.1.0", true), ("0.1.1", false)] {
+        let mut info = CrateVersionInfo::new("foo", semver::Version::parse(version).unwrap());
+        info.yanked = *yanked;
+        index.insert(info);
+    }
+
+    let versions = [
+        semver::Version::parse("0.1.0").unwrap(),
+        semver::Version::parse("0.1.1").unwrap(),
+        semver::Version::parse("0.2.0").unwrap(),
+    ];
+    assert_eq!(
+        get_yanked_versions_from(&index, "foo", &versions).unwrap(),
+        &versions[..1]
+    );
+    assert!(get_yanked_versions_from(&index, "bar", &versions).is_err());
+}
+
+#[test]
+fn upgrade_candidates_from_index() {
+    let mut index = crate::InMemoryIndex::new();
+    for (version, yanked) in &[
+        ("0.1.0", false),
+        ("0.1.1", false),
+        ("0.2.0", false),
+        ("0.3.0", true),
+        ("0.4.0-alpha.1", false),
+    ] {
+        let mut info = CrateVersionInfo::new("foo", semver::Version::parse(version).unwrap());
+        info.yanked = *yanked;
+        index.insert(info);
+    }
+
+    let req = semver::VersionReq::parse("0.1").unwrap();
+    let candidates = get_upgrade_candidates_from(&index, "foo", Some(&req), false).unwrap();
+    assert_eq!(candidates.latest.as_deref(), Some("0.2.0"));
+    assert_eq!(candidates.latest_compatible.as_deref(), Some("0.1.1"));
+    assert_eq!(candidates.yanked, [semver::Version::new(0, 3, 0)]);
+
+    let candidates = get_upgrade_candidates_from(&index, "foo", None, true).unwrap();
+    assert_eq!(candidates.latest.as_deref(), Some("0.4.0-alpha.1"));
+    assert_eq!(candidates.latest_compatible, None);
+    assert!(candidates.yanked.is_empty());
+}
+
 #[test]
 fn get_latest_stable_version_from_json() {
-    let versions: Vec<CrateVersion> = serde_json::from_str(
+    let versions: Vec<CrateVersionInfo> = serde_json::from_str(
         r#"[
         {
           "name": "foo",
@@ -189,7 +1182,7 @@ fn get_latest_stable_version_from_json() {
 
 #[test]
 fn get_latest_unstable_or_stable_version_from_json() {
-    let versions: Vec<CrateVersion> = serde_json::from_str(
+    let versions: Vec<CrateVersionInfo> = serde_json::from_str(
         r#"[
         {
           "name": "foo",
@@ -216,7 +1209,7 @@ fn get_latest_unstable_or_stable_version_from_json() {
 
 #[test]
 fn get_latest_version_from_json_test() {
-    let versions: Vec<CrateVersion> = serde_json::from_str(
+    let versions: Vec<CrateVersionInfo> = serde_json::from_str(
         r#"[
         {
           "name": "treexml",
@@ -243,7 +1236,7 @@ fn get_latest_version_from_json_test() {
 
 #[test]
 fn get_no_latest_version_from_json_when_all_are_yanked() {
-    let versions: Vec<CrateVersion> = serde_json::from_str(
+    let versions: Vec<CrateVersionInfo> = serde_json::from_str(
         r#"[
         {
           "name": "treexml",
@@ -259,70 +1252,124 @@ fn get_no_latest_version_from_json_when_all_are_yanked() {
     )
     .expect("crate version is correctly parsed");
 
-    assert!(read_latest_version(&versions, false).is_err());
-}
-
-/// Gets the checkedout branch name of .cargo/registry/index/github.com-*/.git/refs or
-/// .cargo/registry/index/github.com-*/refs for bare git repository
-fn get_checkout_name(registry_path: impl AsRef<Path>) -> Result<String> {
-    let checkout_dir = registry_path
-        .as_ref()
-        .join(".git")
-        .join("refs/remotes/origin/");
-    let bare_checkout_dir = registry_path.as_ref().join("refs/remotes/origin/");
-
-    Ok(checkout_dir
-        .read_dir() // .git repo
-        .or_else(|_| bare_checkout_dir.read_dir())? // there's no .git, it's bare one
-        .next() //Is there always only one branch? (expecting either master og HEAD)
-        .ok_or(ErrorKind::MissingRegistraryCheckout(checkout_dir))??
-        .file_name()
-        .into_string()
-        .map_err(|_| ErrorKind::NonUnicodeGitPath)?)
-}
-
-/// Fuzzy query crate from registry index
-fn fuzzy_query_registry_index(
-    crate_name: impl Into<String>,
-    registry_path: impl AsRef<Path>,
-) -> Result<Vec<CrateVersion>> {
-    let crate_name = crate_name.into();
-    let remotes = PathBuf::from("refs/remotes/origin/");
-    let repo = git2::Repository::open(&registry_path)?;
-    let tree = repo
-        .find_reference(
-            remotes
-                .join(get_checkout_name(&registry_path)?)
-                .to_str()
-                .ok_or(ErrorKind::NonUnicodeGitPath)?,
-        )?
-        .peel_to_tree()?;
-
-    let mut names = gen_fuzzy_crate_names(crate_name.clone())?;
-    if let Some(index) = names.iter().position(|x| *x == crate_name) {
-        // ref: https://github.com/killercup/cargo-edit/pull/317#discussion_r307365704
-        names.swap(index, 0);
-    }
-
-    for the_name in names {
-        let file = match tree.get_path(&PathBuf::from(summary_raw_path(&the_name))) {
-            Ok(x) => x.to_object(&repo)?.peel_to_blob()?,
-            Err(_) => continue,
-        };
-        let content = String::from_utf8(file.content().to_vec())
-            .map_err(|_| ErrorKind::InvalidSummaryJson)?;
-
-        return content
-            .lines()
-            .map(|line: &str| {
-                serde_json::from_str::<CrateVersion>(line)
-                    .map_err(|_| ErrorKind::InvalidSummaryJson.into())
-            })
-            .collect::<Result<Vec<CrateVersion>>>();
+    assert!(matches!(
+        read_latest_version(&versions, false).unwrap_err().kind(),
+        ErrorKind::NoMatchingVersion(name, true, false) if name == "treexml"
+    ));
+}
+
+#[test]
+fn typed_query_errors() {
+    let mut index = crate::InMemoryIndex::new();
+    index.insert(CrateVersionInfo::new(
+        "serde",
+        semver::Version::parse("1.0.0").unwrap(),
+    ));
+    index.insert(CrateVersionInfo::new(
+        "tokio",
+        semver::Version::parse("2.0.0-alpha.1").unwrap(),
+    ));
+
+    match get_latest_dependency_from(&index, "serdd", false)
+        .unwrap_err()
+        .kind()
+    {
+        ErrorKind::NoCrate(name, suggestions) => {
+            assert_eq!(name, "serdd");
+            assert_eq!(suggestions, &["serde".to_owned()]);
+        }
+        kind => panic!("unexpected error {:?}", kind),
     }
-    Err(ErrorKind::NoCrate(crate_name).into())
+    assert!(matches!(
+        get_latest_dependency_from(&index, "tokio", false)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::NoMatchingVersion(_, false, true)
+    ));
+    let req = semver::VersionReq::parse("2").unwrap();
+    assert!(matches!(
+        get_compatible_dependency_from(&index, "serde", &req)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::NoMatchingVersion(_, false, false)
+    ));
+}
+
+#[test]
+fn select_from_index_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("index/3/f/foo");
+    std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+    std::fs::write(
+        &file,
+        r#"{"name":"foo","vers":"0.1.0","deps":[],"cksum":"f00d"}
+{"name":"foo","vers":"0.2.0","deps":[],"yanked":true,"cksum":"beef"}
+{"name":"foo","vers":"0.3.0-rc.1","deps":[]}
+"#,
+    )
+    .unwrap();
+    let index = crate::LocalRegistry::open(dir.path()).unwrap();
+
+    let dep = get_latest_dependency_from(&index, "foo", false).unwrap();
+    assert_eq!(dep.version(), Some("0.1.0"));
+    let dep = get_latest_dependency_from(&index, "foo", true).unwrap();
+    assert_eq!(dep.version(), Some("0.3.0-rc.1"));
+    // Nothing is selected, and the error looks at the versions which were filtered out.
+    let req = semver::VersionReq::parse("0.2").unwrap();
+    assert!(matches!(
+        get_compatible_dependency_from(&index, "foo", &req)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::NoMatchingVersion(_, true, false)
+    ));
+
+    let version = |v| semver::Version::parse(v).unwrap();
+    let checksum = |v| get_version_checksum_from(&index, "foo", &version(v));
+    assert_eq!(checksum("0.1.0").unwrap().as_deref(), Some("f00d"));
+    assert_eq!(checksum("0.2.0").unwrap().as_deref(), Some("beef"));
+    assert_eq!(checksum("0.3.0-rc.1").unwrap(), None);
+    assert!(matches!(
+        checksum("0.4.0").unwrap_err().kind(),
+        ErrorKind::NoMatchingVersion(..)
+    ));
+}
+
+#[test]
+fn get_compatible_version_from_json() {
+    let versions: Vec<CrateVersionInfo> = serde_json::from_str(
+        r#"[
+        {
+          "name": "foo",
+          "vers": "0.5.2",
+          "yanked": true
+        },
+        {
+          "name": "foo",
+          "vers": "0.5.1",
+          "yanked": false
+        },
+        {
+          "name": "foo",
+          "vers": "0.6.0",
+          "yanked": false
+        }
+      ]"#,
+    )
+    .expect("crate version is correctly parsed");
+
+    let req = semver::VersionReq::parse("0.5").unwrap();
+    assert_eq!(
+        read_compatible_version(&versions, &req)
+            .unwrap()
+            .version()
+            .unwrap(),
+        "0.5.1"
+    );
+    let req = semver::VersionReq::parse("0.7").unwrap();
+    assert!(read_compatible_version(&versions, &req).is_err());
 }
 
+#[cfg(feature = "fetch")]
 fn get_crate_name_from_repository<T>(repo: &str, matcher: &Regex, url_template: T) -> Result<String>
 where
     T: Fn(&str, &str) -> String,
@@ -331,9 +1378,12 @@ where
         .captures(repo)
         .ok_or_else(|| "Unable to parse git repo URL".into())
         .and_then(|cap| match (cap.get(1), cap.get(2)) {
-            (Some(user), Some(repo)) => {
-                let url = url_template(user.as_str(), repo.as_str());
+            (Some(user), Some(name)) => {
+                let url = url_template(user.as_str(), name.as_str());
+                // Raw files of private repositories can't be downloaded anonymously, but
+                // fetching with git authenticates.
                 let data: Result<Manifest> = get_cargo_toml_from_git_url(&url)
+                    .or_else(|_| get_cargo_toml_from_git_repo(repo))
                     .and_then(|m| m.parse().chain_err(|| ErrorKind::ParseCargoToml));
                 data.and_then(|ref manifest| get_name_from_manifest(manifest))
             }
@@ -348,6 +1398,7 @@ where
 /// - there is no Internet connection,
 /// - Cargo.toml is not present in the root of the master branch,
 /// - the response from github is an error or in an incorrect format.
+#[cfg(feature = "fetch")]
 pub fn get_crate_name_from_github(repo: &str) -> Result<String> {
     let re =
         Regex::new(r"^https://github.com/([-_0-9a-zA-Z]+)/([-_0-9a-zA-Z]+)(/|.git)?$").unwrap();
@@ -367,6 +1418,7 @@ pub fn get_crate_name_from_github(repo: &str) -> Result<String> {
 /// - there is no Internet connection,
 /// - Cargo.toml is not present in the root of the master branch,
 /// - the response from gitlab is an error or in an incorrect format.
+#[cfg(feature = "fetch")]
 pub fn get_crate_name_from_gitlab(repo: &str) -> Result<String> {
     let re =
         Regex::new(r"^https://gitlab.com/([-_0-9a-zA-Z]+)/([-_0-9a-zA-Z]+)(/|.git)?$").unwrap();
@@ -379,44 +1431,26 @@ pub fn get_crate_name_from_gitlab(repo: &str) -> Result<String> {
     })
 }
 
-/// Query crate name by accessing Cargo.toml in a local path
-///
-/// The name will be returned as a string. This will fail, when
-/// Cargo.toml is not present in the root of the path.
-pub fn get_crate_name_from_path(path: &str) -> Result<String> {
-    let cargo_file = Path::new(path).join("Cargo.toml");
-    Manifest::open(&Some(cargo_file))
-        .chain_err(|| "Unable to open local Cargo.toml")
-        .and_then(|ref manifest| get_name_from_manifest(manifest))
-}
-
-fn get_name_from_manifest(manifest: &Manifest) -> Result<String> {
-    manifest
-        .data
-        .as_table()
-        .get("package")
-        .and_then(|m| m["name"].as_str().map(std::string::ToString::to_string))
-        .ok_or_else(|| ErrorKind::ParseCargoToml.into())
-}
-
+#[cfg(feature = "fetch")]
 fn get_cargo_toml_from_git_url(url: &str) -> Result<String> {
+    let parsed = Url::parse(url).chain_err(|| format!("Invalid URL `{}`", url))?;
     let mut req = ureq::get(url);
     req.timeout(get_default_timeout());
-    if let Some(proxy) = env_proxy::for_url_str(url)
-        .to_url()
-        .and_then(|url| ureq::Proxy::new(url).ok())
-    {
-        req.set_proxy(proxy);
-    }
-    let res = req.call();
+    apply_proxy(&mut req, &parsed)?;
+    apply_tls(&mut req)?;
+    let res = call_with_retry(
+        &mut req,
+        &parsed,
+        &RetryPolicy::default(),
+        Duration::from_secs(0),
+    )?;
     if res.error() {
-        return Err(format!(
-            "HTTP request `{}` failed: {}",
-            url,
+        return Err(ErrorKind::Network(
+            url.to_owned(),
             res.synthetic_error()
                 .as_ref()
                 .map(|x| x.to_string())
-                .unwrap_or_else(|| res.status().to_string())
+                .unwrap_or_else(|| res.status().to_string()),
         )
         .into());
     }
@@ -425,88 +1459,98 @@ fn get_cargo_toml_from_git_url(url: &str) -> Result<String> {
         .chain_err(|| "Git response not a valid `String`")
 }
 
-const fn get_default_timeout() -> Duration {
-    Duration::from_secs(10)
+/// Fetch the `Cargo.toml` at the root of the default branch of the git repository at `repo`,
+/// authenticating like cargo.
+#[cfg(feature = "fetch")]
+fn get_cargo_toml_from_git_repo(repo: &str) -> Result<String> {
+    let url = Url::parse(repo).chain_err(|| format!("Invalid URL `{}`", repo))?;
+    let path = crate::registry::git_path_from_url(&url)?;
+    fetch_git_index(&path, repo)?;
+    let repository = git2::Repository::open(&path)?;
+    crate::git::read_file(
+        &repository,
+        "refs/remotes/origin/HEAD",
+        Path::new("Cargo.toml"),
+    )?
+    .ok_or_else(|| format!("{} has no Cargo.toml at its root", repo).into())
 }
 
-/// Generate all similar crate names
-///
-/// Examples:
-///
-/// | input | output |
-/// | ----- | ------ |
-/// | cargo | cargo  |
-/// | cargo-edit | cargo-edit, cargo_edit |
-/// | parking_lot_core | parking_lot_core, parking_lot-core, parking-lot_core, parking-lot-core |
-fn gen_fuzzy_crate_names(crate_name: String) -> Result<Vec<String>> {
-    const PATTERN: [u8; 2] = [b'-', b'_'];
-
-    let wildcard_indexs = crate_name
-        .bytes()
-        .enumerate()
-        .filter(|(_, item)| PATTERN.contains(item))
-        .map(|(index, _)| index)
-        .take(10)
-        .collect::<Vec<usize>>();
-    if wildcard_indexs.is_empty() {
-        return Ok(vec![crate_name]);
-    }
-
-    let mut result = vec![];
-    let mut bytes = crate_name.into_bytes();
-    for mask in 0..2u128.pow(wildcard_indexs.len() as u32) {
-        for (mask_index, wildcard_index) in wildcard_indexs.iter().enumerate() {
-            let mask_value = (mask >> mask_index) & 1 == 1;
-            if mask_value {
-                bytes[*wildcard_index] = b'-';
-            } else {
-                bytes[*wildcard_index] = b'_';
-            }
-        }
-        result.push(String::from_utf8(bytes.clone()).unwrap());
-    }
-    Ok(result)
+#[cfg(feature = "fetch")]
+const fn get_default_timeout() -> Duration {
+    Duration::from_secs(10)
 }
 
+#[cfg(feature = "fetch")]
 #[test]
-fn test_gen_fuzzy_crate_names() {
-    fn test_helper(input: &str, expect: &[&str]) {
-        let mut actual = gen_fuzzy_crate_names(input.to_string()).unwrap();
-        actual.sort();
-
-        let mut expect = expect.iter().map(|x| x.to_string()).collect::<Vec<_>>();
-        expect.sort();
-
-        assert_eq!(actual, expect);
+fn query_index_concurrently_keeps_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = git2::Repository::init_bare(dir.path()).unwrap();
+    let entries = [
+        ("3/f/foo", r#"{"name":"foo","vers":"0.1.0","yanked":false}"#),
+        ("3/b/bar", r#"{"name":"bar","vers":"1.2.0","yanked":false}"#),
+        (
+            "ba/z_/baz_qux",
+            r#"{"name":"baz-qux","vers":"2.0.0","yanked":false}"#,
+        ),
+    ];
+    let mut index = repo.index().unwrap();
+    for (path, content) in entries.iter() {
+        let blob = repo.blob(content.as_bytes()).unwrap();
+        let entry = git2::IndexEntry {
+            ctime: git2::IndexTime::new(0, 0),
+            mtime: git2::IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o100_644,
+            uid: 0,
+            gid: 0,
+            file_size: content.len() as u32,
+            id: blob,
+            flags: path.len() as u16,
+            flags_extended: 0,
+            path: path.as_bytes().to_vec(),
+        };
+        index.add(&entry).unwrap();
     }
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("test", "test@example.com").unwrap();
+    repo.commit(
+        Some("refs/remotes/origin/master"),
+        &signature,
+        &signature,
+        "index",
+        &tree,
+        &[],
+    )
+    .unwrap();
 
-    test_helper("", &[""]);
-    test_helper("-", &["_", "-"]);
-    test_helper("DCjanus", &["DCjanus"]);
-    test_helper("DC-janus", &["DC-janus", "DC_janus"]);
-    test_helper(
-        "DC-_janus",
-        &["DC__janus", "DC_-janus", "DC-_janus", "DC--janus"],
+    let names = ["bar", "missing", "foo", "baz_qux"];
+    let progress = Progress::new("Querying", names.len());
+    let results = query_index_concurrently(
+        &names,
+        &progress,
+        &|| {
+            let index: Box<dyn IndexSource> = Box::new(crate::GitIndex::open(dir.path())?);
+            Ok(index)
+        },
+        &|index, i| get_latest_dependency_from(index, names[i], false),
+    );
+    let versions: Vec<_> = results
+        .into_iter()
+        .map(|result| {
+            result
+                .unwrap()
+                .ok()
+                .map(|dep| dep.version().unwrap().to_owned())
+        })
+        .collect();
+    assert_eq!(
+        versions,
+        vec![
+            Some("1.2.0".to_owned()),
+            None,
+            Some("0.1.0".to_owned()),
+            Some("2.0.0".to_owned())
+        ]
     );
-}
-
-fn summary_raw_path(crate_name: &str) -> String {
-    let crate_name = crate_name.to_ascii_lowercase();
-    match crate_name.len() {
-        0 => unreachable!("we check that crate_name is not empty here"),
-        1 => format!("1/{}", crate_name),
-        2 => format!("2/{}", crate_name),
-        3 => format!("3/{}/{}", &crate_name[..1], crate_name),
-        _ => format!("{}/{}/{}", &crate_name[..2], &crate_name[2..4], crate_name),
-    }
-}
-
-#[test]
-fn test_summary_raw_path() {
-    assert_eq!(summary_raw_path("a"), "1/a");
-    assert_eq!(summary_raw_path("ab"), "2/ab");
-    assert_eq!(summary_raw_path("abc"), "3/a/abc");
-    assert_eq!(summary_raw_path("abcd"), "ab/cd/abcd");
-    assert_eq!(summary_raw_path("abcdefg"), "ab/cd/abcdefg");
-    assert_eq!(summary_raw_path("Inflector"), "in/fl/inflector");
 }
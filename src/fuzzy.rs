@@ -0,0 +1,216 @@
+//! Finding a crate whose name was typed with the wrong separators, like `serde-json` for
+//! `serde_json`.
+//!
+//! Crate names differing only in `-` and `_` can't both be published, but indexes are keyed by the
+//! name as published, so a lookup tries each spelling in turn until one is found.
+use crate::errors::*;
+use crate::index::{CrateVersionInfo, IndexSource};
+use std::cmp::Reverse;
+
+/// Which spellings of a crate name are looked up first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameOrder {
+    /// The name as typed, then the other spellings
+    AsTyped,
+    /// The spellings using this separator most often
+    Prefer(char),
+}
+
+/// Which spellings of a crate name are looked up, and in which order.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{FuzzyNames, NameOrder};
+///
+///   let names = FuzzyNames {
+///       order: NameOrder::Prefer('_'),
+///       ..FuzzyNames::default()
+///   };
+///   assert_eq!(names.candidates("a-b"), ["a_b", "a-b"]);
+///   assert_eq!(FuzzyNames::exact().candidates("a-b"), ["a-b"]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyNames {
+    /// Characters which may stand in for each other
+    pub separators: Vec<char>,
+    /// How many separators, from the start of the name, are varied; the rest are kept as typed
+    pub max_wildcards: usize,
+    /// The order spellings are tried in
+    pub order: NameOrder,
+}
+
+impl Default for FuzzyNames {
+    /// Varies up to 10 `-` and `_`, trying the name as typed first.
+    fn default() -> Self {
+        FuzzyNames {
+            separators: vec!['_', '-'],
+            max_wildcards: 10,
+            order: NameOrder::AsTyped,
+        }
+    }
+}
+
+impl FuzzyNames {
+    /// Only look up the name as typed, for registries where no other spelling can exist.
+    pub fn exact() -> Self {
+        FuzzyNames {
+            max_wildcards: 0,
+            ..FuzzyNames::default()
+        }
+    }
+
+    /// Generate all spellings of `crate_name` to look up, in order
+    ///
+    /// Examples:
+    ///
+    /// | input | output |
+    /// | ----- | ------ |
+    /// | cargo | cargo  |
+    /// | cargo-edit | cargo-edit, cargo_edit |
+    /// | parking_lot_core | parking_lot_core, parking_lot-core, parking-lot_core, parking-lot-core |
+    pub fn candidates(&self, crate_name: &str) -> Vec<String> {
+        let mut chars: Vec<char> = crate_name.chars().collect();
+        let wildcards: Vec<usize> = chars
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| self.separators.contains(c))
+            .map(|(index, _)| index)
+            .take(self.max_wildcards)
+            .collect();
+
+        let radix = self.separators.len();
+        let mut names = Vec::new();
+        for combination in 0..radix.saturating_pow(wildcards.len() as u32) {
+            let mut digits = combination;
+            for wildcard in &wildcards {
+                chars[*wildcard] = self.separators[digits % radix];
+                digits /= radix;
+            }
+            names.push(chars.iter().collect::<String>());
+        }
+
+        match self.order {
+            NameOrder::AsTyped => {
+                if let Some(index) = names.iter().position(|name| name == crate_name) {
+                    // ref: https://github.com/killercup/cargo-edit/pull/317#discussion_r307365704
+                    names.swap(index, 0);
+                }
+            }
+            NameOrder::Prefer(separator) => {
+                names.sort_by_key(|name| Reverse(name.matches(separator).count()))
+            }
+        }
+        names
+    }
+}
+
+/// An index source which looks up other spellings of crate names following custom
+/// [`FuzzyNames`] instead of the defaults.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{get_latest_dependency_from, CrateVersionInfo, FuzzyNames, InMemoryIndex,
+///       WithFuzzyNames};
+///
+///   let mut index = InMemoryIndex::new();
+///   index.insert(CrateVersionInfo::new("foo-bar", semver::Version::new(1, 0, 0)));
+///
+///   let strict = WithFuzzyNames::new(index, FuzzyNames::exact());
+///   assert!(get_latest_dependency_from(&strict, "foo_bar", false).is_err());
+///   assert!(get_latest_dependency_from(&strict, "foo-bar", false).is_ok());
+/// ```
+#[derive(Debug, Clone)]
+pub struct WithFuzzyNames<I> {
+    inner: I,
+    names: FuzzyNames,
+}
+
+impl<I: IndexSource> WithFuzzyNames<I> {
+    /// Look up crates in `inner`, trying the spellings `names` generates.
+    pub fn new(inner: I, names: FuzzyNames) -> Self {
+        WithFuzzyNames { inner, names }
+    }
+
+    /// The wrapped index source.
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+impl<I: IndexSource> IndexSource for WithFuzzyNames<I> {
+    fn crate_versions(&self, crate_name: &str) -> Result<Option<Vec<CrateVersionInfo>>> {
+        self.inner.crate_versions(crate_name)
+    }
+
+    fn refresh_crate_versions(&self, crate_name: &str) -> Result<Option<Vec<CrateVersionInfo>>> {
+        self.inner.refresh_crate_versions(crate_name)
+    }
+
+    fn crate_versions_where(
+        &self,
+        crate_name: &str,
+        keep: &dyn Fn(&CrateVersionInfo) -> bool,
+    ) -> Result<Option<Vec<CrateVersionInfo>>> {
+        self.inner.crate_versions_where(crate_name, keep)
+    }
+
+    fn similar_names(&self, crate_name: &str) -> Result<Vec<String>> {
+        self.inner.similar_names(crate_name)
+    }
+
+    fn fuzzy_names(&self) -> FuzzyNames {
+        self.names.clone()
+    }
+
+    fn raw_index_file(&self, crate_name: &str) -> Result<Option<Vec<u8>>> {
+        self.inner.raw_index_file(crate_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gen_fuzzy_crate_names() {
+        fn test_helper(input: &str, expect: &[&str]) {
+            let mut actual = FuzzyNames::default().candidates(input);
+            actual.sort();
+
+            let mut expect = expect.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+            expect.sort();
+
+            assert_eq!(actual, expect);
+        }
+
+        test_helper("", &[""]);
+        test_helper("-", &["_", "-"]);
+        test_helper("DCjanus", &["DCjanus"]);
+        test_helper("DC-janus", &["DC-janus", "DC_janus"]);
+        test_helper(
+            "DC-_janus",
+            &["DC__janus", "DC_-janus", "DC-_janus", "DC--janus"],
+        );
+    }
+
+    #[test]
+    fn configured_candidates() {
+        let typed = FuzzyNames::default().candidates("a-b_c");
+        assert_eq!(typed[0], "a-b_c");
+        assert_eq!(typed.len(), 4);
+
+        let hyphens = FuzzyNames {
+            order: NameOrder::Prefer('-'),
+            ..FuzzyNames::default()
+        };
+        assert_eq!(hyphens.candidates("a_b_c")[0], "a-b-c");
+
+        let first_only = FuzzyNames {
+            max_wildcards: 1,
+            ..FuzzyNames::default()
+        };
+        assert_eq!(first_only.candidates("a_b_c"), ["a_b_c", "a-b_c"]);
+    }
+}
@@ -0,0 +1,169 @@
+//! Manifests cargo generated when packaging a crate, as found in an extracted `.crate` file.
+//!
+//! `cargo package` rewrites the manifest of a crate, normalized and without its workspace, and
+//! keeps the original next to it as `Cargo.toml.orig`. Edits to the generated manifest don't carry
+//! over to the source repository of the crate, so commands which edit manifests say so and offer
+//! to edit the original instead, e.g. when patching a vendored crate.
+use crate::errors::*;
+use crate::verbosity::verbosity;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+/// The first line cargo writes into the manifests it generates.
+const GENERATED_HEADER: &str = "# THIS FILE IS AUTOMATICALLY GENERATED BY CARGO";
+
+/// What to do when the manifest found for a command was generated by cargo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratedManifestPolicy {
+    /// Use it like any other manifest, without a word
+    Edit = 0,
+    /// Warn, and offer to edit `Cargo.toml.orig` instead when there is a terminal to ask on
+    Ask = 1,
+    /// Warn, and edit `Cargo.toml.orig` instead, e.g. for `--orig`
+    Original = 2,
+}
+
+static POLICY: AtomicU8 = AtomicU8::new(GeneratedManifestPolicy::Edit as u8);
+
+/// Set what [`find`](crate::find) does with generated manifests for the rest of the process.
+pub fn set_generated_manifest_policy(policy: GeneratedManifestPolicy) {
+    POLICY.store(policy as u8, Ordering::SeqCst);
+}
+
+fn policy() -> GeneratedManifestPolicy {
+    match POLICY.load(Ordering::SeqCst) {
+        1 => GeneratedManifestPolicy::Ask,
+        2 => GeneratedManifestPolicy::Original,
+        _ => GeneratedManifestPolicy::Edit,
+    }
+}
+
+/// Whether the manifest at `path` was generated by cargo when packaging its crate.
+pub fn is_generated_manifest(path: &Path) -> bool {
+    std::fs::read_to_string(path)
+        .map(|content| content.starts_with(GENERATED_HEADER))
+        .unwrap_or(false)
+}
+
+/// The original manifest kept next to the generated manifest at `path`, if there is one.
+pub fn original_manifest(path: &Path) -> Option<PathBuf> {
+    if !is_generated_manifest(path) {
+        return None;
+    }
+    let mut original = path.as_os_str().to_owned();
+    original.push(".orig");
+    let original = PathBuf::from(original);
+    if original.is_file() {
+        Some(original)
+    } else {
+        None
+    }
+}
+
+/// The manifest a command should edit in place of the one found at `path`, following the
+/// [policy](set_generated_manifest_policy) for generated manifests.
+///
+/// The question is only asked once for each manifest.
+pub(crate) fn manifest_to_edit(path: PathBuf) -> Result<PathBuf> {
+    let policy = policy();
+    if policy == GeneratedManifestPolicy::Edit {
+        return Ok(path);
+    }
+    let mut decisions = decisions()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(decided) = decisions.get(&path) {
+        return Ok(decided.clone());
+    }
+    let decided = decide(&path, policy)?;
+    decisions.insert(path, decided.clone());
+    Ok(decided)
+}
+
+/// The manifests decided on so far, by the manifest found.
+fn decisions() -> &'static Mutex<HashMap<PathBuf, PathBuf>> {
+    static DECISIONS: OnceLock<Mutex<HashMap<PathBuf, PathBuf>>> = OnceLock::new();
+    DECISIONS.get_or_init(Default::default)
+}
+
+fn decide(path: &Path, policy: GeneratedManifestPolicy) -> Result<PathBuf> {
+    if !is_generated_manifest(path) {
+        return Ok(path.to_owned());
+    }
+    let warn = |message: String| {
+        if !verbosity().is_quiet() {
+            eprintln!("WARN: {}", message);
+        }
+    };
+    let generated = format!(
+        "{} was generated by cargo when its crate was packaged, so edits to it won't match the \
+         source repository of the crate",
+        path.display()
+    );
+    let original = match original_manifest(path) {
+        Some(original) => original,
+        None => {
+            warn(generated);
+            return Ok(path.to_owned());
+        }
+    };
+    if policy == GeneratedManifestPolicy::Original {
+        warn(format!(
+            "{}; editing {} instead",
+            generated,
+            original.display()
+        ));
+        return Ok(original);
+    }
+    warn(generated);
+    let question = format!("Edit {} instead?", original.display());
+    if accepts(&question)? {
+        Ok(original)
+    } else {
+        Ok(path.to_owned())
+    }
+}
+
+/// Whether the user takes up the offer in `question`.
+#[cfg(feature = "atty")]
+fn accepts(question: &str) -> Result<bool> {
+    crate::confirm::offer(question)
+}
+
+/// Whether the user takes up the offer in `question`; there is no terminal to ask on.
+#[cfg(not(feature = "atty"))]
+fn accepts(_question: &str) -> Result<bool> {
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_original_of_generated_manifests() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("Cargo.toml");
+        std::fs::write(&manifest, "[package]\nname = \"foo\"\n").unwrap();
+        assert!(!is_generated_manifest(&manifest));
+        assert_eq!(original_manifest(&manifest), None);
+
+        std::fs::write(
+            &manifest,
+            format!("{}\n#\n[package]\nname = \"foo\"\n", GENERATED_HEADER),
+        )
+        .unwrap();
+        assert!(is_generated_manifest(&manifest));
+        assert_eq!(original_manifest(&manifest), None);
+
+        let original = dir.path().join("Cargo.toml.orig");
+        std::fs::write(&original, "[package]\nname = \"foo\"\n").unwrap();
+        assert_eq!(original_manifest(&manifest), Some(original.clone()));
+        assert_eq!(
+            decide(&manifest, GeneratedManifestPolicy::Original).unwrap(),
+            original
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Fetching git repositories the way cargo does, for git indices and repositories of git
+//! dependencies.
+//!
+//! Cargo fetches with libgit2 unless `net.git-fetch-with-cli` is set, in which case it runs the
+//! `git` command line, which authenticates with git's own configuration. With libgit2, cargo
+//! authenticates with the SSH agent for SSH URLs and git's credential helpers for HTTPS URLs, and
+//! so does this module.
+//!
+//! Without `net.git-fetch-with-cli` in the cargo config or `CARGO_NET_GIT_FETCH_WITH_CLI`, the
+//! command line is still preferred when `git` is installed, as it keeps indices shallow, which
+//! libgit2 can't.
+use crate::cancel::{check_cancelled, is_cancelled};
+use crate::errors::*;
+use crate::proxy::proxy_for_url;
+use crate::registry::config_paths;
+use crate::tls::apply_git_tls;
+use git2::{Cred, CredentialType};
+use std::path::Path;
+use url::Url;
+
+/// Whether git repositories are fetched with the `git` command line rather than libgit2.
+pub(crate) fn git_fetch_with_cli() -> Result<bool> {
+    Ok(match configured_fetch_with_cli()? {
+        Some(with_cli) => with_cli,
+        None => git_is_installed(),
+    })
+}
+
+/// `net.git-fetch-with-cli` from `CARGO_NET_GIT_FETCH_WITH_CLI` or the cargo config files of the
+/// current directory.
+fn configured_fetch_with_cli() -> Result<Option<bool>> {
+    if let Ok(value) = std::env::var("CARGO_NET_GIT_FETCH_WITH_CLI") {
+        return match value.trim() {
+            "true" => Ok(Some(true)),
+            "false" => Ok(Some(false)),
+            _ => Err(ErrorKind::InvalidCargoConfig.into()),
+        };
+    }
+    let cwd = std::env::current_dir()?;
+    // `config_paths` looks in the ancestors of a manifest's directory.
+    for path in config_paths(&cwd.join("Cargo.toml"))? {
+        let content = std::fs::read(&path)?;
+        let config: toml::Value =
+            toml::from_slice(&content).chain_err(|| ErrorKind::InvalidCargoConfig)?;
+        if let Some(with_cli) = config
+            .get("net")
+            .and_then(|net| net.get("git-fetch-with-cli"))
+        {
+            return with_cli
+                .as_bool()
+                .map(Some)
+                .ok_or_else(|| ErrorKind::InvalidCargoConfig.into());
+        }
+    }
+    Ok(None)
+}
+
+/// Whether a `git` executable is on the `PATH`.
+fn git_is_installed() -> bool {
+    let path = match std::env::var_os("PATH") {
+        Some(path) => path,
+        None => return false,
+    };
+    std::env::split_paths(&path)
+        .any(|dir| dir.join("git").is_file() || dir.join("git.exe").is_file())
+}
+
+/// Fetch `refspec` of the repository at `url` into `repo` with libgit2, authenticating like
+/// cargo.
+pub(crate) fn fetch_with_libgit2(repo: &git2::Repository, url: &str, refspec: &str) -> Result<()> {
+    let git_config = git2::Config::open_default()?;
+    let mut tried = Tried::default();
+    let mut callbacks = git2::RemoteCallbacks::new();
+    callbacks.credentials(|url, username, allowed| {
+        credentials(&mut tried, url, username, allowed, |url, username| {
+            Cred::credential_helper(&git_config, url, username)
+        })
+    });
+    // Returning `false` stops the transfer, e.g. on Ctrl-C.
+    callbacks.transfer_progress(|_| !is_cancelled());
+
+    let mut proxy = git2::ProxyOptions::new();
+    // scp-like SSH URLs such as `git@github.com:org/index` aren't URLs to take a proxy for.
+    if let Ok(parsed) = Url::parse(url) {
+        if let Some(proxy_url) = proxy_for_url(&parsed)? {
+            proxy.url(proxy_url.as_str());
+        }
+    }
+    let mut options = git2::FetchOptions::new();
+    options
+        .remote_callbacks(callbacks)
+        .proxy_options(proxy)
+        .download_tags(git2::AutotagOption::All);
+
+    apply_git_tls()?;
+    let mut remote = repo.remote_anonymous(url)?;
+    let fetched = remote.fetch(&[format!("+{}", refspec)], Some(&mut options), None);
+    check_cancelled()?;
+    fetched.map_err(|err| ErrorKind::Network(url.to_owned(), err.message().to_owned()).into())
+}
+
+/// The credentials libgit2 has been given so far, so that none is offered twice.
+#[derive(Debug, Default)]
+struct Tried {
+    username: bool,
+    ssh_agent: bool,
+    credential_helper: bool,
+    default: bool,
+}
+
+/// The next credentials to try for `url`, as cargo picks them: the user name of the URL (or
+/// `git`) and the SSH agent for SSH, git's credential helpers for user names and passwords, and
+/// the default credentials of the system, e.g. for NTLM or Kerberos.
+fn credentials(
+    tried: &mut Tried,
+    url: &str,
+    username: Option<&str>,
+    allowed: CredentialType,
+    credential_helper: impl FnOnce(&str, Option<&str>) -> std::result::Result<Cred, git2::Error>,
+) -> std::result::Result<Cred, git2::Error> {
+    let ssh_username = username.unwrap_or("git");
+    if allowed.contains(CredentialType::USERNAME) && !tried.username {
+        tried.username = true;
+        return Cred::username(ssh_username);
+    }
+    if allowed.contains(CredentialType::SSH_KEY) && !tried.ssh_agent {
+        tried.ssh_agent = true;
+        return Cred::ssh_key_from_agent(ssh_username);
+    }
+    if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) && !tried.credential_helper {
+        tried.credential_helper = true;
+        return credential_helper(url, username);
+    }
+    if allowed.contains(CredentialType::DEFAULT) && !tried.default {
+        tried.default = true;
+        return Cred::default();
+    }
+    Err(git2::Error::from_str(&format!(
+        "failed to authenticate to {}; `cargo build` needs the same SSH agent or git credential \
+         helper, or `net.git-fetch-with-cli` to use git's own configuration",
+        url
+    )))
+}
+
+/// The contents of `path` in the tree fetched as `reference` into `repo`, if it has that file.
+pub(crate) fn read_file(
+    repo: &git2::Repository,
+    reference: &str,
+    path: &Path,
+) -> Result<Option<String>> {
+    let tree = repo.find_reference(reference)?.peel_to_tree()?;
+    let entry = match tree.get_path(path) {
+        Ok(entry) => entry,
+        Err(err) if err.code() == git2::ErrorCode::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let blob = entry.to_object(repo)?.peel_to_blob()?;
+    Ok(Some(String::from_utf8_lossy(blob.content()).into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tries_each_kind_of_credentials_once() {
+        let mut tried = Tried::default();
+        let mut helped = 0;
+        let mut next = |allowed| {
+            credentials(
+                &mut tried,
+                "https://example.com/index",
+                None,
+                allowed,
+                |_, _| {
+                    helped += 1;
+                    Cred::username("helped")
+                },
+            )
+        };
+        let plaintext = CredentialType::USER_PASS_PLAINTEXT | CredentialType::DEFAULT;
+        assert!(next(plaintext).is_ok());
+        assert!(next(plaintext).is_ok());
+        // Neither the credential helper nor the default credentials worked.
+        assert!(next(plaintext).is_err());
+        assert_eq!(helped, 1);
+    }
+
+    #[test]
+    fn fetches_without_the_command_line() {
+        let upstream = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(upstream.path()).unwrap();
+        std::fs::write(
+            upstream.path().join("Cargo.toml"),
+            "[package]\nname = \"foo\"\n",
+        )
+        .unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("Cargo.toml")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let fetched = git2::Repository::init_bare(dir.path()).unwrap();
+        let url = Url::from_directory_path(upstream.path()).unwrap();
+        fetch_with_libgit2(&fetched, url.as_str(), "HEAD:refs/remotes/origin/HEAD").unwrap();
+        assert_eq!(
+            read_file(
+                &fetched,
+                "refs/remotes/origin/HEAD",
+                Path::new("Cargo.toml")
+            )
+            .unwrap(),
+            Some("[package]\nname = \"foo\"\n".to_owned())
+        );
+        assert_eq!(
+            read_file(&fetched, "refs/remotes/origin/HEAD", Path::new("missing")).unwrap(),
+            None
+        );
+    }
+}
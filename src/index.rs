@@ -0,0 +1,1288 @@
+//! Registry indices and their entries.
+//!
+//! See <https://doc.rust-lang.org/cargo/reference/registries.html#index-format> for the format.
+use crate::dependency_table::DependencyKind;
+use crate::errors::*;
+use crate::fuzzy::FuzzyNames;
+use crate::rust_version::RustVersion;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "fetch")]
+use {
+    crate::cache::{cache_ttl, offline_cache_ttl, IndexCache, Validators},
+    crate::cargo_flags::is_offline,
+    crate::credential::registry_token,
+    crate::protocol::protocol_for,
+    crate::proxy::apply_proxy,
+    crate::registry::{registry_path, registry_path_from_url},
+    crate::retry::{call_with_retry, RetryPolicy},
+    crate::snapshot::{installed_snapshot, is_unreachable, WithSnapshot},
+    crate::tls::apply_tls,
+    std::fmt,
+    std::io::Read,
+    std::time::Duration,
+    url::Url,
+};
+
+/// A place to look up the published versions of crates, like a registry index.
+///
+/// The query functions, e.g. [`get_latest_dependency_from`](crate::get_latest_dependency_from),
+/// accept any source, so version selection can be exercised against an [`InMemoryIndex`].
+pub trait IndexSource {
+    /// All published versions of the crate named exactly `crate_name` (ignoring ASCII case), or
+    /// `None` if the index has no such crate.
+    fn crate_versions(&self, crate_name: &str) -> Result<Option<Vec<CrateVersionInfo>>>;
+
+    /// Like [`IndexSource::crate_versions`], but bypassing any cached copy of this one crate's
+    /// entry, e.g. to see a version which was yanked since.
+    ///
+    /// Sources without a per-crate cache just look the crate up again.
+    fn refresh_crate_versions(&self, crate_name: &str) -> Result<Option<Vec<CrateVersionInfo>>> {
+        self.crate_versions(crate_name)
+    }
+
+    /// The versions of the crate named exactly `crate_name` which `keep` accepts, or `None` if the
+    /// index has no such crate.
+    ///
+    /// Sources reading index files check each version as soon as its `name`, `version`,
+    /// `yanked`, `rust_version` and `pubtime` are read, and only parse the rest, like its
+    /// dependencies, if `keep` accepts it; `keep` must not look at anything else. For crates with
+    /// thousands of versions, this is much faster than [`IndexSource::crate_versions`].
+    fn crate_versions_where(
+        &self,
+        crate_name: &str,
+        keep: &dyn Fn(&CrateVersionInfo) -> bool,
+    ) -> Result<Option<Vec<CrateVersionInfo>>> {
+        Ok(self
+            .crate_versions(crate_name)?
+            .map(|versions| versions.into_iter().filter(|v| keep(v)).collect()))
+    }
+
+    /// Names of published crates similar to `crate_name`, to suggest when it is not found.
+    ///
+    /// Sources which can't list their crates suggest nothing.
+    fn similar_names(&self, crate_name: &str) -> Result<Vec<String>> {
+        let _ = crate_name;
+        Ok(Vec::new())
+    }
+
+    /// The spellings tried when looking up a crate name, e.g. `foo_bar` for `foo-bar`.
+    ///
+    /// Wrap a source in [`WithFuzzyNames`](crate::WithFuzzyNames) to change them.
+    fn fuzzy_names(&self) -> FuzzyNames {
+        FuzzyNames::default()
+    }
+
+    /// The index file of the crate named exactly `crate_name` (ignoring ASCII case) as the
+    /// registry serves it, one JSON object per version, e.g. to [snapshot](crate::IndexSnapshot)
+    /// it.
+    ///
+    /// `None` if the index has no such crate, or the source has no index files, like a directory
+    /// of unpacked crates.
+    fn raw_index_file(&self, crate_name: &str) -> Result<Option<Vec<u8>>> {
+        let _ = crate_name;
+        Ok(None)
+    }
+}
+
+/// The git checkout of a registry index which cargo maintains in `$CARGO_HOME/registry/index`.
+#[cfg(feature = "fetch")]
+pub struct GitIndex {
+    path: PathBuf,
+    repo: git2::Repository,
+    tree: git2::Oid,
+}
+
+#[cfg(feature = "fetch")]
+impl fmt::Debug for GitIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GitIndex")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+#[cfg(feature = "fetch")]
+impl GitIndex {
+    /// Open the index checked out at `registry_path`.
+    pub fn open(registry_path: &Path) -> Result<Self> {
+        let remotes = PathBuf::from("refs/remotes/origin/");
+        let repo = git2::Repository::open(registry_path)?;
+        let tree = repo
+            .find_reference(
+                remotes
+                    .join(get_checkout_name(registry_path)?)
+                    .to_str()
+                    .ok_or(ErrorKind::NonUnicodeGitPath)?,
+            )?
+            .peel_to_tree()?
+            .id();
+        Ok(GitIndex {
+            path: registry_path.to_owned(),
+            repo,
+            tree,
+        })
+    }
+
+    /// Open the index of `registry`, or of the default registry for the given manifest.
+    pub fn for_registry(manifest_path: &Path, registry: &Option<Url>) -> Result<Self> {
+        let registry_path = match registry {
+            Some(url) => registry_path_from_url(url)?,
+            None => registry_path(manifest_path, None)?,
+        };
+        Self::open(&registry_path)
+    }
+
+    /// The index file of `crate_name`, if the index has it.
+    fn index_file(&self, crate_name: &str) -> Result<Option<git2::Blob<'_>>> {
+        let tree = self.repo.find_tree(self.tree)?;
+        match tree.get_path(&PathBuf::from(summary_raw_path(crate_name))) {
+            Ok(x) => Ok(Some(x.to_object(&self.repo)?.peel_to_blob()?)),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+#[cfg(feature = "fetch")]
+impl IndexSource for GitIndex {
+    fn crate_versions(&self, crate_name: &str) -> Result<Option<Vec<CrateVersionInfo>>> {
+        self.index_file(crate_name)?
+            .map(|file| parse_summaries(file.content()))
+            .transpose()
+    }
+
+    fn raw_index_file(&self, crate_name: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .index_file(crate_name)?
+            .map(|file| file.content().to_owned()))
+    }
+
+    fn crate_versions_where(
+        &self,
+        crate_name: &str,
+        keep: &dyn Fn(&CrateVersionInfo) -> bool,
+    ) -> Result<Option<Vec<CrateVersionInfo>>> {
+        self.index_file(crate_name)?
+            .map(|file| parse_summaries_where(file.content(), keep))
+            .transpose()
+    }
+
+    fn similar_names(&self, crate_name: &str) -> Result<Vec<String>> {
+        // Only the crates sharing the directory of `crate_name` are cheap to list.
+        let path = summary_raw_path(crate_name);
+        let dir = match path.rfind('/') {
+            Some(i) => &path[..i],
+            None => return Ok(Vec::new()),
+        };
+        let tree = self.repo.find_tree(self.tree)?;
+        let dir = match tree.get_path(Path::new(dir)) {
+            Ok(dir) => dir.to_object(&self.repo)?.peel_to_tree()?,
+            Err(_) => return Ok(Vec::new()),
+        };
+        Ok(closest_names(
+            crate_name,
+            dir.iter()
+                .filter_map(|entry| entry.name().map(ToOwned::to_owned)),
+        ))
+    }
+}
+
+/// A registry served over HTTP with the sparse protocol, e.g. `sparse+https://index.crates.io/`.
+///
+/// Index files are kept in the registry's [`IndexCache`]. Entries younger than the cache TTL are
+/// used as they are; older ones are revalidated with a conditional request, so an unchanged crate
+/// costs a `304 Not Modified` instead of its full index file.
+///
+/// Registries which require authentication get the token set with [`SparseIndex::set_token`],
+/// see [`registry_token`](crate::registry_token).
+#[cfg(feature = "fetch")]
+#[derive(Clone)]
+pub struct SparseIndex {
+    url: Url,
+    cache: IndexCache,
+    max_age: Duration,
+    token: Option<String>,
+    retry: RetryPolicy,
+}
+
+#[cfg(feature = "fetch")]
+impl fmt::Debug for SparseIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print the token.
+        f.debug_struct("SparseIndex")
+            .field("url", &self.url)
+            .field("cache", &self.cache)
+            .field("max_age", &self.max_age)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("retry", &self.retry)
+            .finish()
+    }
+}
+
+#[cfg(feature = "fetch")]
+impl SparseIndex {
+    /// Open the sparse registry at `registry`, with or without the `sparse+` prefix, caching its
+    /// index files in the cargo home directory.
+    pub fn new(registry: &Url) -> Result<Self> {
+        Ok(Self::with_cache(
+            registry,
+            IndexCache::for_registry(registry)?,
+        ))
+    }
+
+    /// Open the sparse registry at `registry`, caching its index files in `cache`.
+    pub fn with_cache(registry: &Url, cache: IndexCache) -> Self {
+        let url = registry.as_str();
+        let url = url.strip_prefix(SPARSE_PREFIX).unwrap_or(url);
+        let mut url = Url::parse(url).unwrap_or_else(|_| registry.clone());
+        if !url.path().ends_with('/') {
+            let path = format!("{}/", url.path());
+            url.set_path(&path);
+        }
+        SparseIndex {
+            url,
+            cache,
+            max_age: cache_ttl(),
+            token: None,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Authenticate requests with `token`.
+    pub fn set_token(&mut self, token: Option<String>) -> &mut Self {
+        self.token = token;
+        self
+    }
+
+    /// Whether the registry's `config.json` asks for authentication, or the registry refuses to
+    /// serve it without.
+    pub fn auth_required(&self) -> Result<bool> {
+        let config = match self.fetch("config.json", "config.json", self.max_age) {
+            Ok(Some(config)) => config,
+            Ok(None) => return Ok(false),
+            Err(Error(ErrorKind::RegistryAuth(_), _)) => return Ok(true),
+            Err(e) => return Err(e),
+        };
+        let config: serde_json::Value =
+            serde_json::from_slice(&config).chain_err(|| ErrorKind::InvalidCargoConfig)?;
+        Ok(config["auth-required"].as_bool().unwrap_or(false))
+    }
+
+    /// Use cached index files younger than `max_age` without revalidating them.
+    pub fn set_max_age(&mut self, max_age: Duration) -> &mut Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Retry failed requests following `retry`.
+    pub fn set_retry_policy(&mut self, retry: RetryPolicy) -> &mut Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Fetch the file at `path`, cached as `key`, or `None` if the registry does not have it.
+    ///
+    /// A cached copy younger than `max_age` is used as is; older ones are revalidated.
+    fn fetch(&self, key: &str, path: &str, max_age: Duration) -> Result<Option<Vec<u8>>> {
+        let cached = self.cache.get(key)?;
+        if let Some(ref entry) = cached {
+            // Offline, whatever was cached is better than nothing, unless it is too stale.
+            if entry.is_fresh(max_age) || (is_offline() && entry.is_fresh(offline_cache_ttl())) {
+                event!(DEBUG, path, "cache hit");
+                return Ok(Some(entry.body.clone()));
+            }
+        }
+        event!(DEBUG, path, cached = cached.is_some(), "cache miss");
+
+        let url = self
+            .url
+            .join(path)
+            .chain_err(|| ErrorKind::InvalidCargoConfig)?;
+        let mut req = ureq::get(url.as_str());
+        req.timeout(Duration::from_secs(30));
+        apply_proxy(&mut req, &url)?;
+        apply_tls(&mut req)?;
+        if let Some(ref token) = self.token {
+            req.set("Authorization", token);
+        }
+        if let Some(ref entry) = cached {
+            if let Some(ref etag) = entry.validators.etag {
+                req.set("If-None-Match", etag);
+            }
+            if let Some(ref last_modified) = entry.validators.last_modified {
+                req.set("If-Modified-Since", last_modified);
+            }
+        }
+
+        let res = call_with_retry(&mut req, &url, &self.retry, Duration::from_secs(0))?;
+        match res.status() {
+            304 if cached.is_some() => {
+                event!(DEBUG, path, "not modified");
+                self.cache.touch(key)?;
+                Ok(cached.map(|entry| entry.body))
+            }
+            401 => Err(ErrorKind::RegistryAuth(url.to_string()).into()),
+            // crates.io answers 403 for crates it does not know, like a missing S3 object.
+            403 | 404 | 410 | 451 => {
+                self.cache.remove(key)?;
+                Ok(None)
+            }
+            _ if res.ok() => {
+                let validators = Validators {
+                    etag: res.header("ETag").map(ToOwned::to_owned),
+                    last_modified: res.header("Last-Modified").map(ToOwned::to_owned),
+                };
+                let mut body = Vec::new();
+                res.into_reader()
+                    .read_to_end(&mut body)
+                    .chain_err(|| format!("Failed to read `{}`", url))?;
+                self.cache.insert_with_validators(key, &body, &validators)?;
+                Ok(Some(body))
+            }
+            status => Err(ErrorKind::Network(
+                url.to_string(),
+                res.synthetic_error()
+                    .as_ref()
+                    .map(|x| x.to_string())
+                    .unwrap_or_else(|| status.to_string()),
+            )
+            .into()),
+        }
+    }
+}
+
+#[cfg(feature = "fetch")]
+impl SparseIndex {
+    /// The index file of `crate_name`, if the registry has it, revalidated if it was cached more
+    /// than `max_age` ago.
+    fn index_file(&self, crate_name: &str, max_age: Duration) -> Result<Option<Vec<u8>>> {
+        let key = crate_name.to_ascii_lowercase();
+        self.fetch(&key, &summary_raw_path(crate_name), max_age)
+    }
+
+    fn crate_versions_younger_than(
+        &self,
+        crate_name: &str,
+        max_age: Duration,
+    ) -> Result<Option<Vec<CrateVersionInfo>>> {
+        self.index_file(crate_name, max_age)?
+            .map(|content| parse_summaries(&content))
+            .transpose()
+    }
+}
+
+#[cfg(feature = "fetch")]
+impl IndexSource for SparseIndex {
+    fn crate_versions(&self, crate_name: &str) -> Result<Option<Vec<CrateVersionInfo>>> {
+        self.crate_versions_younger_than(crate_name, self.max_age)
+    }
+
+    fn crate_versions_where(
+        &self,
+        crate_name: &str,
+        keep: &dyn Fn(&CrateVersionInfo) -> bool,
+    ) -> Result<Option<Vec<CrateVersionInfo>>> {
+        self.index_file(crate_name, self.max_age)?
+            .map(|content| parse_summaries_where(&content, keep))
+            .transpose()
+    }
+
+    /// Revalidates this crate's index file with a conditional request, leaving the rest of the
+    /// cache alone.
+    fn refresh_crate_versions(&self, crate_name: &str) -> Result<Option<Vec<CrateVersionInfo>>> {
+        self.crate_versions_younger_than(crate_name, Duration::from_secs(0))
+    }
+
+    fn raw_index_file(&self, crate_name: &str) -> Result<Option<Vec<u8>>> {
+        self.index_file(crate_name, self.max_age)
+    }
+}
+
+/// A `local-registry` source, as created by `cargo local-registry`: an index in the layout of a
+/// git registry, next to the `.crate` files it describes.
+#[derive(Debug, Clone)]
+pub struct LocalRegistry {
+    path: PathBuf,
+}
+
+impl LocalRegistry {
+    /// Open the local registry in the directory `path`.
+    pub fn open(path: &Path) -> Result<Self> {
+        if !path.join("index").is_dir() {
+            return Err(format!("`{}` is not a local registry", path.display()).into());
+        }
+        Ok(LocalRegistry {
+            path: path.to_owned(),
+        })
+    }
+
+    /// The index file of `crate_name`, if the registry has it.
+    fn index_file(&self, crate_name: &str) -> Result<Option<Vec<u8>>> {
+        let file = self.path.join("index").join(summary_raw_path(crate_name));
+        match std::fs::read(&file) {
+            Ok(content) => Ok(Some(content)),
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl IndexSource for LocalRegistry {
+    fn crate_versions(&self, crate_name: &str) -> Result<Option<Vec<CrateVersionInfo>>> {
+        self.index_file(crate_name)?
+            .map(|content| parse_summaries(&content))
+            .transpose()
+    }
+
+    fn crate_versions_where(
+        &self,
+        crate_name: &str,
+        keep: &dyn Fn(&CrateVersionInfo) -> bool,
+    ) -> Result<Option<Vec<CrateVersionInfo>>> {
+        self.index_file(crate_name)?
+            .map(|content| parse_summaries_where(&content, keep))
+            .transpose()
+    }
+
+    fn similar_names(&self, crate_name: &str) -> Result<Vec<String>> {
+        let path = self.path.join("index").join(summary_raw_path(crate_name));
+        let names = match path.parent().map(std::fs::read_dir) {
+            Some(Ok(entries)) => entries
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                .collect::<Vec<_>>(),
+            _ => return Ok(Vec::new()),
+        };
+        Ok(closest_names(crate_name, names.into_iter()))
+    }
+
+    fn raw_index_file(&self, crate_name: &str) -> Result<Option<Vec<u8>>> {
+        self.index_file(crate_name)
+    }
+}
+
+/// A `directory` source of vendored crates, as created by `cargo vendor`: one directory per crate
+/// version, each with its manifest.
+///
+/// Versions are read from the vendored manifests, so they are never yanked and have no checksum
+/// unless `.cargo-checksum.json` records one.
+#[derive(Debug, Clone)]
+pub struct DirectorySource {
+    path: PathBuf,
+}
+
+impl DirectorySource {
+    /// Open the directory source at `path`.
+    pub fn open(path: &Path) -> Result<Self> {
+        if !path.is_dir() {
+            return Err(format!("`{}` is not a directory", path.display()).into());
+        }
+        Ok(DirectorySource {
+            path: path.to_owned(),
+        })
+    }
+
+    /// Every vendored crate version.
+    fn vendored(&self) -> Result<Vec<CrateVersionInfo>> {
+        let mut versions = Vec::new();
+        for entry in std::fs::read_dir(&self.path)? {
+            let dir = entry?.path();
+            let manifest = match std::fs::read_to_string(dir.join("Cargo.toml")) {
+                Ok(manifest) => manifest,
+                Err(_) => continue,
+            };
+            let manifest: toml::Value = toml::from_str(&manifest)
+                .chain_err(|| format!("Invalid manifest in `{}`", dir.display()))?;
+            if let Some(mut version) = version_from_manifest(&manifest) {
+                version.checksum = std::fs::read(dir.join(".cargo-checksum.json"))
+                    .ok()
+                    .and_then(|checksums| {
+                        serde_json::from_slice::<serde_json::Value>(&checksums).ok()
+                    })
+                    .and_then(|checksums| checksums["package"].as_str().map(ToOwned::to_owned))
+                    .unwrap_or_default();
+                versions.push(version);
+            }
+        }
+        Ok(versions)
+    }
+}
+
+impl IndexSource for DirectorySource {
+    fn crate_versions(&self, crate_name: &str) -> Result<Option<Vec<CrateVersionInfo>>> {
+        let mut versions: Vec<_> = self
+            .vendored()?
+            .into_iter()
+            .filter(|version| version.name.eq_ignore_ascii_case(crate_name))
+            .collect();
+        if versions.is_empty() {
+            return Ok(None);
+        }
+        versions.sort_by(|a, b| a.version.cmp(&b.version));
+        Ok(Some(versions))
+    }
+
+    fn similar_names(&self, crate_name: &str) -> Result<Vec<String>> {
+        let names: std::collections::BTreeSet<String> = self
+            .vendored()?
+            .into_iter()
+            .map(|version| version.name)
+            .collect();
+        Ok(closest_names(crate_name, names.into_iter()))
+    }
+}
+
+/// The index entry a vendored manifest corresponds to.
+fn version_from_manifest(manifest: &toml::Value) -> Option<CrateVersionInfo> {
+    let package = manifest.get("package")?;
+    let name = package.get("name")?.as_str()?;
+    let version = semver::Version::parse(package.get("version")?.as_str()?).ok()?;
+    let mut info = CrateVersionInfo::new(name, version);
+    info.links = package
+        .get("links")
+        .and_then(|links| links.as_str())
+        .map(ToOwned::to_owned);
+    info.rust_version = package
+        .get("rust-version")
+        .and_then(|v| v.as_str())
+        .and_then(|v| v.parse().ok());
+    if let Some(features) = manifest.get("features").and_then(|f| f.as_table()) {
+        for (feature, enables) in features {
+            let enables = enables
+                .as_array()
+                .map(|values| {
+                    values
+                        .iter()
+                        .filter_map(|v| v.as_str().map(ToOwned::to_owned))
+                        .collect()
+                })
+                .unwrap_or_default();
+            info.features.insert(feature.clone(), enables);
+        }
+    }
+    let kinds = [
+        ("dependencies", DependencyKind::Normal),
+        ("build-dependencies", DependencyKind::Build),
+        ("dev-dependencies", DependencyKind::Dev),
+    ];
+    let targets = manifest
+        .get("target")
+        .and_then(|t| t.as_table())
+        .into_iter()
+        .flatten()
+        .map(|(target, tables)| (Some(target.as_str()), tables));
+    for (target, tables) in std::iter::once((None, manifest)).chain(targets) {
+        for (table, kind) in kinds.iter() {
+            for (name, dep) in tables
+                .get(*table)
+                .and_then(|t| t.as_table())
+                .into_iter()
+                .flatten()
+            {
+                let mut dep = IndexDependency::from_manifest(name, dep, *kind);
+                dep.target = target.map(ToOwned::to_owned);
+                info.deps.push(dep);
+            }
+        }
+    }
+    Some(info)
+}
+
+/// The prefix marking the index URL of a sparse registry.
+#[cfg(feature = "fetch")]
+const SPARSE_PREFIX: &str = "sparse+";
+
+/// Whether `registry` is served with the sparse protocol rather than as a git repository.
+#[cfg(feature = "fetch")]
+pub(crate) fn is_sparse(registry: &Url) -> bool {
+    registry.as_str().starts_with(SPARSE_PREFIX)
+}
+
+/// Whether `registry` is a `local-registry` or `directory` source on disk.
+#[cfg(feature = "fetch")]
+pub(crate) fn is_local(registry: &Url) -> bool {
+    registry.scheme() == "file"
+}
+
+/// Open the index of `registry`, which may be a git or a sparse registry, or one served by a
+/// [registered protocol](crate::register_protocol).
+///
+/// A git index which was never fetched is cloned shallowly first. Sparse registries which require
+/// authentication get a token from the credential providers configured for `manifest_path`.
+///
+/// Registries with an [installed snapshot](crate::IndexSnapshot::install) fall back on it while
+/// they can't be reached.
+#[cfg(feature = "fetch")]
+pub(crate) fn open_index(manifest_path: &Path, registry: &Url) -> Result<Box<dyn IndexSource>> {
+    let _span = span!(DEBUG, "open_index", %registry);
+    let opened = open_registry_index(manifest_path, registry);
+    let snapshot = match installed_snapshot(registry)? {
+        Some(snapshot) => snapshot,
+        None => return opened,
+    };
+    match opened {
+        Ok(index) => Ok(Box::new(WithSnapshot::new(index, snapshot))),
+        Err(ref err) if is_unreachable(err) => {
+            event!(WARN, %registry, error = %err, "registry unreachable, using its snapshot");
+            Ok(Box::new(snapshot))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(feature = "fetch")]
+fn open_registry_index(manifest_path: &Path, registry: &Url) -> Result<Box<dyn IndexSource>> {
+    if let Some(protocol) = protocol_for(registry) {
+        protocol.open(manifest_path, registry)
+    } else if is_sparse(registry) {
+        let mut index = SparseIndex::new(registry)?;
+        if index.auth_required()? {
+            let token = registry_token(manifest_path, registry)?
+                .chain_err(|| ErrorKind::RegistryAuth(registry.to_string()))?;
+            index.set_token(Some(token));
+        }
+        Ok(Box::new(index))
+    } else if is_local(registry) {
+        let path = registry
+            .to_file_path()
+            .map_err(|()| ErrorKind::InvalidCargoConfig)?;
+        if path.join("index").is_dir() {
+            Ok(Box::new(LocalRegistry::open(&path)?))
+        } else {
+            Ok(Box::new(DirectorySource::open(&path)?))
+        }
+    } else {
+        let registry_path = registry_path_from_url(registry)?;
+        if get_checkout_name(&registry_path).is_err() {
+            event!(INFO, path = %registry_path.display(), "no git index yet, cloning it");
+            crate::fetch::fetch_git_index(&registry_path, registry.as_str())?;
+        }
+        Ok(Box::new(GitIndex::open(&registry_path)?))
+    }
+}
+
+/// Parse an index file, one JSON object per line.
+pub(crate) fn parse_summaries(content: &[u8]) -> Result<Vec<CrateVersionInfo>> {
+    summary_lines(content)?
+        .map(parse_summary::<CrateVersionInfo>)
+        .collect()
+}
+
+/// Parse the versions in an index file which `keep` accepts.
+///
+/// Each line is first read without its dependencies and features, which make up most of it, and
+/// only parsed in full if `keep` accepts the version.
+fn parse_summaries_where(
+    content: &[u8],
+    keep: &dyn Fn(&CrateVersionInfo) -> bool,
+) -> Result<Vec<CrateVersionInfo>> {
+    let mut versions = Vec::new();
+    for line in summary_lines(content)? {
+        let summary: SummaryHeader = parse_summary(line)?;
+        if keep(&summary.into()) {
+            versions.push(parse_summary(line)?);
+        }
+    }
+    Ok(versions)
+}
+
+/// The non-empty lines of an index file.
+fn summary_lines(content: &[u8]) -> Result<impl Iterator<Item = &str>> {
+    let content = std::str::from_utf8(content).map_err(|_| ErrorKind::InvalidSummaryJson)?;
+    Ok(content.lines().filter(|line| !line.trim().is_empty()))
+}
+
+fn parse_summary<'a, T: Deserialize<'a>>(line: &'a str) -> Result<T> {
+    serde_json::from_str(line).map_err(|_| ErrorKind::InvalidSummaryJson.into())
+}
+
+/// The fields of an index entry which [`IndexSource::crate_versions_where`] filters on; the
+/// others are skipped without being parsed.
+#[derive(Deserialize)]
+struct SummaryHeader {
+    name: String,
+    vers: semver::Version,
+    #[serde(default)]
+    yanked: bool,
+    #[serde(default, deserialize_with = "lenient_rust_version")]
+    rust_version: Option<RustVersion>,
+    #[serde(default)]
+    pubtime: Option<String>,
+}
+
+impl From<SummaryHeader> for CrateVersionInfo {
+    fn from(summary: SummaryHeader) -> Self {
+        CrateVersionInfo {
+            yanked: summary.yanked,
+            rust_version: summary.rust_version,
+            pubtime: summary.pubtime,
+            ..CrateVersionInfo::new(&summary.name, summary.vers)
+        }
+    }
+}
+
+/// An index held in memory, e.g. to test version selection without a registry.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{get_latest_dependency_from, CrateVersionInfo, InMemoryIndex};
+///
+///   let mut index = InMemoryIndex::new();
+///   index.insert(CrateVersionInfo::new("foo", semver::Version::parse("1.0.0").unwrap()));
+///   index.insert(CrateVersionInfo::new("foo", semver::Version::parse("1.1.0-rc.1").unwrap()));
+///   let dep = get_latest_dependency_from(&index, "foo", false).unwrap();
+///   assert_eq!(dep.version(), Some("1.0.0"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryIndex {
+    crates: BTreeMap<String, Vec<CrateVersionInfo>>,
+}
+
+impl InMemoryIndex {
+    /// Create an empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Publish a version.
+    pub fn insert(&mut self, version: CrateVersionInfo) -> &mut Self {
+        self.crates
+            .entry(version.name.to_ascii_lowercase())
+            .or_default()
+            .push(version);
+        self
+    }
+}
+
+impl IndexSource for InMemoryIndex {
+    fn crate_versions(&self, crate_name: &str) -> Result<Option<Vec<CrateVersionInfo>>> {
+        Ok(self.crates.get(&crate_name.to_ascii_lowercase()).cloned())
+    }
+
+    fn similar_names(&self, crate_name: &str) -> Result<Vec<String>> {
+        Ok(closest_names(
+            crate_name,
+            self.crates
+                .values()
+                .filter_map(|versions| versions.first().map(|v| v.name.clone())),
+        ))
+    }
+}
+
+/// The names at most two edits away from `crate_name`, closest first.
+pub(crate) fn closest_names(crate_name: &str, names: impl Iterator<Item = String>) -> Vec<String> {
+    const MAX_DISTANCE: usize = 2;
+    const MAX_SUGGESTIONS: usize = 3;
+
+    let crate_name = crate_name.to_ascii_lowercase().replace('_', "-");
+    let mut close: Vec<(usize, String)> = names
+        .filter_map(|name| {
+            let distance = edit_distance(&crate_name, &name.to_ascii_lowercase().replace('_', "-"));
+            Some((distance, name)).filter(|(distance, _)| *distance <= MAX_DISTANCE)
+        })
+        .collect();
+    close.sort();
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, name)| name)
+        .collect()
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Gets the checkedout branch name of .cargo/registry/index/github.com-*/.git/refs or
+/// .cargo/registry/index/github.com-*/refs for bare git repository
+#[cfg(feature = "fetch")]
+pub(crate) fn get_checkout_name(registry_path: impl AsRef<Path>) -> Result<String> {
+    let checkout_dir = registry_path
+        .as_ref()
+        .join(".git")
+        .join("refs/remotes/origin/");
+    let bare_checkout_dir = registry_path.as_ref().join("refs/remotes/origin/");
+
+    Ok(checkout_dir
+        .read_dir() // .git repo
+        .or_else(|_| bare_checkout_dir.read_dir())? // there's no .git, it's bare one
+        .next() //Is there always only one branch? (expecting either master og HEAD)
+        .ok_or(ErrorKind::MissingRegistraryCheckout(checkout_dir))??
+        .file_name()
+        .into_string()
+        .map_err(|_| ErrorKind::NonUnicodeGitPath)?)
+}
+
+pub(crate) fn summary_raw_path(crate_name: &str) -> String {
+    let crate_name = crate_name.to_ascii_lowercase();
+    match crate_name.len() {
+        0 => unreachable!("we check that crate_name is not empty here"),
+        1 => format!("1/{}", crate_name),
+        2 => format!("2/{}", crate_name),
+        3 => format!("3/{}/{}", &crate_name[..1], crate_name),
+        _ => format!("{}/{}/{}", &crate_name[..2], &crate_name[2..4], crate_name),
+    }
+}
+
+/// A single published version of a crate, as recorded in the registry index.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CrateVersionInfo {
+    /// The name of the crate
+    pub name: String,
+    /// The version of this release
+    #[serde(rename = "vers")]
+    pub version: semver::Version,
+    /// The dependencies of this release
+    #[serde(default)]
+    pub deps: Vec<IndexDependency>,
+    /// SHA-256 checksum of the `.crate` file, as a hex string
+    #[serde(rename = "cksum", default)]
+    pub checksum: String,
+    /// The features of this release, mapped to the features and dependencies they enable
+    #[serde(default)]
+    pub features: BTreeMap<String, Vec<String>>,
+    /// Features using the `dep:` or `?` syntax, which newer registries list separately
+    #[serde(default)]
+    pub features2: BTreeMap<String, Vec<String>>,
+    /// Whether this release has been yanked
+    #[serde(default)]
+    pub yanked: bool,
+    /// The native library this release links to, if any
+    pub links: Option<String>,
+    /// The minimum supported Rust version, if declared
+    #[serde(default, deserialize_with = "lenient_rust_version")]
+    pub rust_version: Option<RustVersion>,
+    /// When this release was published, as an RFC 3339 timestamp, if the registry records it
+    #[serde(default)]
+    pub pubtime: Option<String>,
+}
+
+/// Registries don't validate `rust_version` the way cargo does; a malformed one is ignored rather
+/// than making the whole release unreadable.
+fn lenient_rust_version<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<RustVersion>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let version: Option<String> = Option::deserialize(deserializer)?;
+    Ok(version.and_then(|version| version.parse().ok()))
+}
+
+impl CrateVersionInfo {
+    /// A version without dependencies or features, e.g. to populate an [`InMemoryIndex`].
+    pub fn new(name: &str, version: semver::Version) -> Self {
+        CrateVersionInfo {
+            name: name.to_owned(),
+            version,
+            deps: Vec::new(),
+            checksum: String::new(),
+            features: BTreeMap::new(),
+            features2: BTreeMap::new(),
+            yanked: false,
+            links: None,
+            rust_version: None,
+            pubtime: None,
+        }
+    }
+
+    /// Whether this is a pre-release, e.g. `1.0.0-beta.1`
+    pub fn is_prerelease(&self) -> bool {
+        self.version.is_prerelease()
+    }
+
+    /// The checksum of the `.crate` file, if the index records one
+    pub fn checksum(&self) -> Option<&str> {
+        Some(self.checksum.as_str()).filter(|checksum| !checksum.is_empty())
+    }
+
+    /// All features of this release, including those listed in `features2`
+    pub fn all_features(&self) -> BTreeMap<&str, &[String]> {
+        self.features
+            .iter()
+            .chain(self.features2.iter())
+            .map(|(name, enables)| (name.as_str(), enables.as_slice()))
+            .collect()
+    }
+}
+
+/// A dependency of a published crate version.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct IndexDependency {
+    /// The name the dependency is known by, which differs from `package` if it was renamed
+    pub name: String,
+    /// The version requirement of the dependency
+    pub req: String,
+    /// Features enabled on the dependency
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Whether the dependency is optional
+    #[serde(default)]
+    pub optional: bool,
+    /// Whether the default features of the dependency are enabled
+    #[serde(default = "default_true")]
+    pub default_features: bool,
+    /// The platform the dependency is restricted to, e.g. `cfg(unix)`
+    pub target: Option<String>,
+    /// Whether this is a normal, build or dev dependency
+    #[serde(default)]
+    pub kind: DependencyKind,
+    /// The index URL of the registry the dependency comes from, if not the same registry
+    pub registry: Option<String>,
+    /// The actual name of the package, if the dependency was renamed
+    pub package: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl IndexDependency {
+    /// The name of the package depended on, resolving renames
+    pub fn package_name(&self) -> &str {
+        self.package.as_deref().unwrap_or(&self.name)
+    }
+
+    /// The dependency `name` as declared in a manifest, e.g. `"1.0"` or `{ version = "1.0" }`.
+    fn from_manifest(name: &str, dep: &toml::Value, kind: DependencyKind) -> Self {
+        let field = |key: &str| dep.get(key).and_then(|v| v.as_str()).map(ToOwned::to_owned);
+        IndexDependency {
+            name: name.to_owned(),
+            req: dep
+                .as_str()
+                .map(ToOwned::to_owned)
+                .or_else(|| field("version"))
+                .unwrap_or_else(|| "*".to_owned()),
+            features: dep
+                .get("features")
+                .and_then(|f| f.as_array())
+                .map(|f| {
+                    f.iter()
+                        .filter_map(|v| v.as_str().map(ToOwned::to_owned))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            optional: dep
+                .get("optional")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            default_features: dep
+                .get("default-features")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true),
+            target: None,
+            kind,
+            registry: field("registry-index"),
+            package: field("package"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_raw_path() {
+        assert_eq!(summary_raw_path("a"), "1/a");
+        assert_eq!(summary_raw_path("ab"), "2/ab");
+        assert_eq!(summary_raw_path("abc"), "3/a/abc");
+        assert_eq!(summary_raw_path("abcd"), "ab/cd/abcd");
+        assert_eq!(summary_raw_path("abcdefg"), "ab/cd/abcdefg");
+        assert_eq!(summary_raw_path("Inflector"), "in/fl/inflector");
+    }
+
+    #[test]
+    fn in_memory_index_ignores_case() {
+        let mut index = InMemoryIndex::new();
+        index.insert(CrateVersionInfo::new(
+            "Inflector",
+            semver::Version::parse("0.11.4").unwrap(),
+        ));
+        assert_eq!(index.crate_versions("inflector").unwrap().unwrap().len(), 1);
+        assert!(index.crate_versions("deflector").unwrap().is_none());
+    }
+
+    #[test]
+    fn parse_full_entry() {
+        let info: CrateVersionInfo = serde_json::from_str(
+            r#"{"name":"foo","vers":"1.2.0","deps":[{"name":"bar","req":"^0.3","features":["std"],"optional":true,"default_features":false,"target":"cfg(unix)","kind":"dev","package":"bar-sys"}],"cksum":"abc123","features":{"default":["std"],"std":[]},"features2":{"baz":["dep:bar"]},"yanked":false,"links":"foo","rust_version":"1.56"}"#,
+        )
+        .unwrap();
+        assert_eq!(info.version, semver::Version::parse("1.2.0").unwrap());
+        assert_eq!(info.checksum, "abc123");
+        assert_eq!(info.links.as_deref(), Some("foo"));
+        assert_eq!(
+            info.rust_version.map(|v| v.to_string()).as_deref(),
+            Some("1.56")
+        );
+        assert_eq!(
+            info.all_features().keys().collect::<Vec<_>>(),
+            vec![&"baz", &"default", &"std"]
+        );
+
+        let dep = &info.deps[0];
+        assert_eq!(dep.package_name(), "bar-sys");
+        assert_eq!(dep.kind, DependencyKind::Dev);
+        assert!(dep.optional);
+        assert!(!dep.default_features);
+    }
+
+    #[test]
+    fn parse_only_kept_entries() {
+        let content = br#"{"name":"foo","vers":"0.1.0","deps":[{"name":"bar","req":"^0.3"}],"yanked":true}
+{"name":"foo","vers":"0.2.0-rc.1","deps":[{"name":"bar","req":"^0.3"}],"yanked":false}
+
+{"name":"foo","vers":"0.2.0","deps":[{"name":"bar","req":"^0.4"}],"yanked":false,"rust_version":"1.56"}
+"#;
+        let kept = parse_summaries_where(content, &|v| !v.yanked && !v.is_prerelease()).unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0], parse_summaries(content).unwrap()[2]);
+        assert_eq!(kept[0].deps[0].req, "^0.4");
+
+        let msrv = |v: &CrateVersionInfo| v.rust_version.is_none();
+        assert_eq!(parse_summaries_where(content, &msrv).unwrap().len(), 2);
+        assert!(parse_summaries_where(b"{\"name\":\"foo\"}", &|_| true).is_err());
+    }
+
+    #[test]
+    fn parse_minimal_entry() {
+        let info: CrateVersionInfo =
+            serde_json::from_str(r#"{"name":"foo","vers":"0.1.0-alpha","yanked":true}"#).unwrap();
+        assert!(info.yanked);
+        assert!(info.is_prerelease());
+        assert!(info.deps.is_empty());
+        assert_eq!(info.rust_version, None);
+    }
+
+    /// Serve `responses` to consecutive requests on a local port, returning the base URL and
+    /// the requests received.
+    #[cfg(feature = "fetch")]
+    fn serve(responses: Vec<String>) -> (Url, std::thread::JoinHandle<Vec<String>>) {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!(
+            "sparse+http://{}/index/",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    request.push_str(&line);
+                }
+                stream.write_all(response.as_bytes()).unwrap();
+                requests.push(request);
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn sparse_index_revalidates_with_etag() {
+        let body = "{\"name\":\"foo\",\"vers\":\"1.0.0\"}\n";
+        let ok = format!(
+            "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let not_modified =
+            "HTTP/1.1 304 Not Modified\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let not_found = "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let (url, server) = serve(vec![ok, not_modified.to_owned(), not_found.to_owned()]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut index = SparseIndex::with_cache(&url, IndexCache::at(dir.path()));
+        index.set_max_age(Duration::from_secs(0));
+
+        let versions = index.crate_versions("foo").unwrap().unwrap();
+        assert_eq!(
+            versions[0].version,
+            semver::Version::parse("1.0.0").unwrap()
+        );
+        let versions = index.crate_versions("foo").unwrap().unwrap();
+        assert_eq!(
+            versions[0].version,
+            semver::Version::parse("1.0.0").unwrap()
+        );
+        assert!(index.crate_versions("bar").unwrap().is_none());
+
+        // Fresh entries are used without asking the server at all.
+        index.set_max_age(Duration::from_secs(60));
+        assert!(index.crate_versions("foo").unwrap().is_some());
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /index/3/f/foo "));
+        assert!(!requests[0].contains("If-None-Match"));
+        assert!(requests[1].contains("If-None-Match: \"v1\""));
+        assert!(requests[2].starts_with("GET /index/3/b/bar "));
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn sparse_index_refreshes_one_crate() {
+        let ok = |body: &str| {
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        };
+        let (url, server) = serve(vec![
+            ok("{\"name\":\"foo\",\"vers\":\"1.0.0\"}"),
+            ok("{\"name\":\"foo\",\"vers\":\"1.0.0\",\"yanked\":true}"),
+        ]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut index = SparseIndex::with_cache(&url, IndexCache::at(dir.path()));
+        index.set_max_age(Duration::from_secs(60));
+
+        assert!(!index.crate_versions("foo").unwrap().unwrap()[0].yanked);
+        // Still fresh, so only a refresh asks the registry again.
+        assert!(!index.crate_versions("foo").unwrap().unwrap()[0].yanked);
+        assert!(index.refresh_crate_versions("foo").unwrap().unwrap()[0].yanked);
+        assert!(index.crate_versions("foo").unwrap().unwrap()[0].yanked);
+
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn sparse_index_retries_server_errors() {
+        let unavailable =
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let body = "{\"name\":\"foo\",\"vers\":\"1.0.0\"}";
+        let ok = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let (url, server) = serve(vec![unavailable.to_owned(), unavailable.to_owned(), ok]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut index = SparseIndex::with_cache(&url, IndexCache::at(dir.path()));
+        index.set_retry_policy(RetryPolicy {
+            retries: 1,
+            initial_backoff: Duration::from_millis(0),
+            max_backoff: Duration::from_millis(0),
+        });
+        assert!(matches!(
+            index.crate_versions("foo").unwrap_err().kind(),
+            ErrorKind::Network(_, reason) if reason == "503"
+        ));
+        index.set_retry_policy(RetryPolicy::none());
+        assert!(index.crate_versions("foo").unwrap().is_some());
+
+        assert_eq!(server.join().unwrap().len(), 3);
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn local_registry() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("index/3/f/foo");
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(
+            &file,
+            "{\"name\":\"foo\",\"vers\":\"0.1.0\"}\n{\"name\":\"foo\",\"vers\":\"0.2.0\"}\n",
+        )
+        .unwrap();
+
+        let url = Url::from_directory_path(dir.path()).unwrap();
+        let index = open_index(Path::new("Cargo.toml"), &url).unwrap();
+        let versions = index.crate_versions("Foo").unwrap().unwrap();
+        assert_eq!(versions.len(), 2);
+        assert!(index.crate_versions("bar").unwrap().is_none());
+        assert_eq!(index.similar_names("fob").unwrap(), ["foo"]);
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn directory_source() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, version) in &[("foo", "0.1.0"), ("foo", "0.2.0"), ("bar", "1.0.0")] {
+            let crate_dir = dir.path().join(format!("{}-{}", name, version));
+            std::fs::create_dir(&crate_dir).unwrap();
+            std::fs::write(
+                crate_dir.join("Cargo.toml"),
+                format!(
+                    "[package]\nname = \"{}\"\nversion = \"{}\"\nrust-version = \"1.56\"\n\n\
+                     [features]\nstd = [\"serde/std\"]\n\n\
+                     [dependencies]\nserde = {{ version = \"1.0\", optional = true }}\n\n\
+                     [target.'cfg(unix)'.dependencies]\nlibc = \"0.2\"\n",
+                    name, version
+                ),
+            )
+            .unwrap();
+            std::fs::write(
+                crate_dir.join(".cargo-checksum.json"),
+                "{\"files\":{},\"package\":\"abc\"}",
+            )
+            .unwrap();
+        }
+
+        let url = Url::from_directory_path(dir.path()).unwrap();
+        let index = open_index(Path::new("Cargo.toml"), &url).unwrap();
+        let versions = index.crate_versions("foo").unwrap().unwrap();
+        let numbers: Vec<_> = versions.iter().map(|v| v.version.to_string()).collect();
+        assert_eq!(numbers, ["0.1.0", "0.2.0"]);
+        let latest = &versions[1];
+        assert_eq!(latest.checksum, "abc");
+        assert_eq!(latest.rust_version.unwrap().to_string(), "1.56");
+        assert_eq!(latest.features["std"], ["serde/std"]);
+        assert!(latest.deps[0].optional);
+        assert_eq!(latest.deps[1].target.as_deref(), Some("cfg(unix)"));
+        assert!(index.crate_versions("baz").unwrap().is_none());
+        assert_eq!(index.similar_names("baz").unwrap(), ["bar"]);
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn sparse_index_sends_token() {
+        let unauthorized =
+            "HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let body = "{\"name\":\"foo\",\"vers\":\"1.0.0\"}";
+        let ok = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let (url, server) = serve(vec![unauthorized.to_owned(), ok]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut index = SparseIndex::with_cache(&url, IndexCache::at(dir.path()));
+        assert!(index.auth_required().unwrap());
+        index.set_token(Some("secret".to_owned()));
+        assert!(index.crate_versions("foo").unwrap().is_some());
+        assert!(!format!("{:?}", index).contains("secret"));
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /index/config.json "));
+        assert!(!requests[0].contains("Authorization"));
+        assert!(requests[1].contains("Authorization: secret"));
+    }
+}
@@ -0,0 +1,157 @@
+//! How dependency entries relate to the `[workspace.dependencies]` entries they inherit from.
+//!
+//! A member inheriting a dependency with `foo = { workspace = true }` can only add `features` and
+//! make it `optional`; everything else comes from `[workspace.dependencies]`, and has to be changed
+//! there.
+use crate::dependency::Dependency;
+use crate::dependency_table::DependencyEntry;
+use crate::errors::*;
+
+/// A part of a dependency entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DependencyField {
+    /// The version requirement
+    Version,
+    /// Where the dependency comes from: `path`, `registry`, `git` and its `branch`, `tag` or
+    /// `rev`, and the name of the crate in `package`
+    Source,
+    /// `default-features`
+    DefaultFeatures,
+    /// `features`, which members add to those of the workspace entry
+    Features,
+    /// `optional`
+    Optional,
+}
+
+impl DependencyField {
+    /// Whether a member inheriting a dependency sets the field in its own entry, rather than in
+    /// `[workspace.dependencies]`.
+    pub fn is_member_local(self) -> bool {
+        match self {
+            DependencyField::Features | DependencyField::Optional => true,
+            DependencyField::Version
+            | DependencyField::Source
+            | DependencyField::DefaultFeatures => false,
+        }
+    }
+}
+
+/// A dependency entry, along with the `[workspace.dependencies]` entry it inherits from if it
+/// says `workspace = true`.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{DependencyField, Manifest, ResolvedDependency};
+///
+///   let manifest: Manifest = r#"
+///   [workspace.dependencies]
+///   serde = { version = "1.0", features = ["derive"] }
+///
+///   [package]
+///   name = "app"
+///   version = "0.1.0"
+///
+///   [dependencies]
+///   serde = { workspace = true, features = ["rc"], optional = true }
+///   "#.parse().unwrap();
+///
+///   let entries: Vec<_> = manifest.dependency_entries().collect();
+///   let serde = ResolvedDependency::resolve(entries[1].clone(), &entries).unwrap();
+///   assert!(serde.is_inherited());
+///   assert_eq!(serde.version_req(), Some("1.0"));
+///   assert!(serde.entry_for(DependencyField::Version).table.workspace);
+///   assert!(!serde.entry_for(DependencyField::Features).table.workspace);
+///
+///   let effective = serde.effective().unwrap();
+///   assert_eq!(effective.features, Some(vec!["derive".to_owned(), "rc".to_owned()]));
+///   assert!(effective.optional());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ResolvedDependency {
+    /// The entry as listed
+    pub entry: DependencyEntry,
+    /// The `[workspace.dependencies]` entry it inherits from, if it is inherited
+    pub inherited_from: Option<DependencyEntry>,
+}
+
+impl ResolvedDependency {
+    /// Look up the entry `entry` inherits from among `workspace_entries`, which may hold any
+    /// entries of the root manifest; only those of `[workspace.dependencies]` are considered.
+    ///
+    /// This fails with [`ErrorKind::NonExistentDependency`] if `entry` is inherited but
+    /// `[workspace.dependencies]` has no entry with its key.
+    pub fn resolve(entry: DependencyEntry, workspace_entries: &[DependencyEntry]) -> Result<Self> {
+        if !entry.is_inherited() || entry.table.workspace {
+            return Ok(ResolvedDependency {
+                entry,
+                inherited_from: None,
+            });
+        }
+        let inherited_from = workspace_entries
+            .iter()
+            .find(|e| e.table.workspace && e.key == entry.key)
+            .cloned()
+            .ok_or_else(|| {
+                ErrorKind::NonExistentDependency(
+                    entry.key.clone(),
+                    "workspace.dependencies".to_owned(),
+                )
+            })?;
+        Ok(ResolvedDependency {
+            entry,
+            inherited_from: Some(inherited_from),
+        })
+    }
+
+    /// Whether the entry inherits from `[workspace.dependencies]`.
+    pub fn is_inherited(&self) -> bool {
+        self.inherited_from.is_some()
+    }
+
+    /// The entry holding `field`, which is the one to change to edit it.
+    pub fn entry_for(&self, field: DependencyField) -> &DependencyEntry {
+        match self.inherited_from {
+            Some(ref inherited_from) if !field.is_member_local() => inherited_from,
+            _ => &self.entry,
+        }
+    }
+
+    /// The name of the crate depended on.
+    pub fn name(&self) -> &str {
+        self.entry_for(DependencyField::Source).name()
+    }
+
+    /// The version requirement in effect, if there is one.
+    pub fn version_req(&self) -> Option<&str> {
+        self.entry_for(DependencyField::Version).version_req()
+    }
+
+    /// The dependency as cargo sees it: the workspace entry with the member's `features` added,
+    /// and optional if the member says so. `None` if either entry is malformed.
+    pub fn effective(&self) -> Option<Dependency> {
+        let member = self.entry.to_dependency()?;
+        let inherited_from = match self.inherited_from {
+            Some(ref inherited_from) => inherited_from,
+            None => return Some(member),
+        };
+
+        let inherited = inherited_from.to_dependency()?;
+        let mut features = inherited.features.clone().unwrap_or_default();
+        for feature in member.features.iter().flatten() {
+            if !features.contains(feature) {
+                features.push(feature.clone());
+            }
+        }
+        let features = if features.is_empty() {
+            None
+        } else {
+            Some(features)
+        };
+        Some(
+            inherited
+                .set_features(features)
+                .set_optional(member.optional()),
+        )
+    }
+}
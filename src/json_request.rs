@@ -0,0 +1,170 @@
+//! Single requests as JSON, for editors and other tools which would rather not build command
+//! lines.
+//!
+//! With `--json-request`, `cargo add`, `cargo rm` and `cargo upgrade` read one JSON object from
+//! stdin, like `{"crates": ["serde"], "features": "derive", "dev": true}`, and write one JSON
+//! document to stdout: the JSON report of what they changed, or the error they failed with.
+//!
+//! The keys of the request are the long flags of the command, with `-` or `_` between words, and
+//! the names of its positional arguments, like `crates`. Flags are set with `true`, options take
+//! a string or a number, and options taking several values or positional arguments an array.
+use crate::errors::*;
+use crate::exit_code::ExitCode;
+use serde_json::{json, Map, Value};
+use std::error::Error as StdError;
+use std::io::Read;
+use structopt::clap::AppSettings;
+use structopt::StructOpt;
+
+/// Read the request for `cargo <subcommand>` from stdin, and parse it like its command line.
+///
+/// `C` is the `cargo` command with `subcommand` in it, as cargo invokes the binary, and
+/// `positionals` the names of the positional arguments of the subcommand.
+pub fn read_json_request<C: StructOpt>(subcommand: &str, positionals: &[&str]) -> Result<C> {
+    let mut request = String::new();
+    std::io::stdin()
+        .read_to_string(&mut request)
+        .chain_err(|| "Failed to read the request from stdin")?;
+    parse_json_request(&request, subcommand, positionals)
+}
+
+fn parse_json_request<C: StructOpt>(
+    request: &str,
+    subcommand: &str,
+    positionals: &[&str],
+) -> Result<C> {
+    // `--json-request` is given again, so that the flags it conflicts with are refused.
+    let mut argv = vec![
+        "cargo".to_owned(),
+        subcommand.to_owned(),
+        "--json-request".to_owned(),
+    ];
+    argv.extend(request_args(request, positionals)?);
+    let matches = C::clap()
+        .global_setting(AppSettings::ColorNever)
+        .get_matches_from_safe(argv)
+        .map_err(|err| {
+            // Only the first line says what is wrong; the rest is usage for people.
+            let message = err.message.lines().next().unwrap_or_default();
+            format!(
+                "Invalid request: {}",
+                message.trim_start_matches("error: ").trim()
+            )
+        })?;
+    Ok(C::from_clap(&matches))
+}
+
+/// The command line arguments equivalent to `request`, after the name of the subcommand.
+fn request_args(request: &str, positionals: &[&str]) -> Result<Vec<String>> {
+    let request: Map<String, Value> =
+        serde_json::from_str(request).chain_err(|| "The request is not a JSON object")?;
+    let mut args = Vec::new();
+    let mut positional_args = Vec::new();
+    for (key, value) in request {
+        let key = key.replace('_', "-");
+        if key == "json-request" {
+            return Err("Invalid request: `json-request` can't be nested".into());
+        }
+        let values = match value {
+            Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                Value::Null | Value::Bool(false) => continue,
+                Value::Bool(true) => None,
+                Value::String(value) => Some(value),
+                Value::Number(value) => Some(value.to_string()),
+                _ => {
+                    return Err(format!(
+                        "Invalid request: `{}` must be a string, number or flag",
+                        key
+                    )
+                    .into())
+                }
+            };
+            match value {
+                Some(value) if positionals.contains(&key.as_str()) => positional_args.push(value),
+                Some(value) => args.push(format!("--{}={}", key, value)),
+                None => args.push(format!("--{}", key)),
+            }
+        }
+    }
+    if !positional_args.is_empty() {
+        args.push("--".to_owned());
+        args.extend(positional_args);
+    }
+    Ok(args)
+}
+
+/// The document written for a request which failed with `err`: its message, the errors which
+/// caused it and the exit code.
+pub fn json_error_response(
+    subcommand: &str,
+    err: &(dyn StdError + 'static),
+    code: ExitCode,
+) -> String {
+    let mut causes = Vec::new();
+    let mut cause = err.source();
+    while let Some(err) = cause {
+        causes.push(err.to_string());
+        cause = err.source();
+    }
+    let response = json!({
+        "command": subcommand,
+        "error": {
+            "message": err.to_string(),
+            "causes": causes,
+            "exit_code": code.code(),
+        },
+    });
+    format!("{}\n", response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_requests_to_arguments() {
+        let request = r#"{"crates": ["serde", "-weird"], "features": ["derive", "rc"], "dev": true,
+            "optional": false, "manifest_path": "a/Cargo.toml", "vers": 1}"#;
+        assert_eq!(
+            request_args(request, &["crates"]).unwrap(),
+            [
+                "--dev",
+                "--features=derive",
+                "--features=rc",
+                "--manifest-path=a/Cargo.toml",
+                "--vers=1",
+                "--",
+                "serde",
+                "-weird",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        assert!(request_args("[]", &[]).is_err());
+        assert!(request_args(r#"{"dev": {"yes": true}}"#, &[]).is_err());
+        assert!(request_args(r#"{"json_request": true}"#, &[]).is_err());
+    }
+
+    #[test]
+    fn reports_errors_with_their_causes() {
+        let err = Error::with_chain(
+            Error::from(ErrorKind::ParseCargoToml),
+            "Failed to open the manifest",
+        );
+        let response: Value =
+            serde_json::from_str(&json_error_response("add", &err, ExitCode::ManifestParse))
+                .unwrap();
+        assert_eq!(response["error"]["message"], "Failed to open the manifest");
+        assert_eq!(
+            response["error"]["causes"][0],
+            "Unable to parse external Cargo.toml"
+        );
+        assert_eq!(response["error"]["exit_code"], 6);
+    }
+}
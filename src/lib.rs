@@ -1,5 +1,57 @@
 //! Show and Edit Cargo's Manifest Files
-#![recursion_limit = "128"]
+//!
+//! Besides the `cargo add`, `cargo rm` and `cargo upgrade` binaries, this crate can be used as a
+//! library to edit `Cargo.toml` files while preserving their formatting:
+//!
+//! ```
+//! use cargo_edit::{Dependency, Manifest};
+//!
+//! let mut manifest: Manifest = r#"
+//! [package]
+//! name = "demo"
+//! version = "0.1.0"
+//!
+//! [dependencies]
+//! regex = "1.3"
+//! log = "0.4" # untouched entries keep their formatting
+//! "#
+//! .parse()?;
+//!
+//! let dependencies = vec!["dependencies".to_owned()];
+//! manifest.insert_into_table(&dependencies, &Dependency::new("serde").set_version("1.0"))?;
+//! manifest.update_table_entry(&dependencies, &Dependency::new("regex").set_version("1.4"), false)?;
+//!
+//! let target = vec!["target".to_owned(), "cfg(unix)".to_owned(), "dependencies".to_owned()];
+//! manifest.insert_into_table(&target, &Dependency::new("libc").set_version("0.2"))?;
+//! manifest.remove_from_table(&target, "libc")?;
+//!
+//! assert_eq!(
+//!     manifest.get_dependency(&dependencies, "serde")?,
+//!     Dependency::new("serde").set_version("1.0")
+//! );
+//! let toml = manifest.data.to_string();
+//! assert!(toml.contains(r#"regex = "1.4""#));
+//! assert!(toml.contains(r#"log = "0.4" # untouched entries keep their formatting"#));
+//! # Ok::<(), cargo_edit::Error>(())
+//! ```
+//!
+//! Tables are addressed by their path, e.g. `["dev-dependencies"]` or
+//! `["target", "cfg(unix)", "dependencies"]`. Use [`LocalManifest`] to edit a manifest on disk.
+//!
+//! # Errors
+//!
+//! Failures are reported as an [`Error`] whose [`kind`](Error::kind) can be matched on, e.g.
+//! [`ErrorKind::NoCrate`] carries suggestions for misspelled crate names,
+//! [`ErrorKind::NoMatchingVersion`] tells whether only yanked or pre-release versions exist,
+//! [`ErrorKind::ManifestParse`] gives the location of a syntax error and [`ErrorKind::Network`] and
+//! [`ErrorKind::RegistryAuth`] separate connection problems from missing credentials.
+//!
+//! # Stability
+//!
+//! The items re-exported from the crate root follow semantic versioning: breaking changes to them
+//! are only made in a release which bumps the (minor, while below 1.0) version and are listed in the
+//! release notes. Modules not re-exported here are implementation details of the binaries.
+#![recursion_limit = "512"]
 #![cfg_attr(test, allow(dead_code))]
 #![warn(
     missing_docs,
@@ -18,21 +70,225 @@ extern crate error_chain;
 #[macro_use]
 extern crate serde_derive;
 
+#[macro_use]
+mod trace;
+
+#[cfg(feature = "async")]
+mod async_fetch;
+#[cfg(feature = "index")]
+mod cache;
+mod cancel;
+mod cargo_flags;
+mod cfg;
+mod color;
+#[cfg(feature = "structopt")]
+mod completions;
+mod config;
+#[cfg(feature = "atty")]
+mod confirm;
 mod crate_name;
+#[cfg(feature = "crates-io-api")]
+mod crates_io;
+#[cfg(feature = "index")]
+mod credential;
 mod dependency;
+mod dependency_path;
+mod dependency_table;
+mod diff;
+mod duplicates;
 mod errors;
+mod exit_code;
+mod features;
+#[cfg(feature = "index")]
 mod fetch;
+#[cfg(feature = "index")]
+mod fuzzy;
+mod generated;
+#[cfg(feature = "fetch")]
+mod git;
+#[cfg(feature = "index")]
+mod index;
+mod inheritance;
+#[cfg(feature = "structopt")]
+mod json_request;
+mod lockfile;
+mod logical_path;
+#[cfg(feature = "structopt")]
+mod man_page;
 mod manifest;
+mod manifest_lock;
 mod metadata;
+#[cfg(feature = "index")]
+mod msrv;
+mod parallel;
+mod pkgid;
+#[cfg(feature = "index")]
+mod policy;
+mod prerelease;
+#[cfg(feature = "fetch")]
+mod progress;
+#[cfg(feature = "fetch")]
+mod protocol;
+#[cfg(feature = "fetch")]
+mod proxy;
 mod registry;
+mod report;
+mod repository;
+#[cfg(feature = "fetch")]
+mod retry;
+mod rust_version;
+mod schema;
+#[cfg(feature = "fetch")]
+mod service;
+#[cfg(feature = "index")]
+mod snapshot;
+mod style;
+mod table;
+mod text_diff;
+#[cfg(feature = "fetch")]
+mod tls;
+mod toml_key;
+mod transaction;
+mod verbosity;
+mod version_req;
+mod workspace;
 
-pub use crate::crate_name::CrateName;
+#[cfg(feature = "async")]
+pub use crate::async_fetch::{get_compatible_dependency_async, get_latest_dependency_async, Query};
+#[cfg(feature = "index")]
+pub use crate::cache::{
+    cache_ttl, offline_cache_ttl, purge_caches, set_offline_cache_ttl, CacheEntry, IndexCache,
+    Validators, DEFAULT_CACHE_TTL,
+};
+#[cfg(feature = "ctrlc")]
+pub use crate::cancel::cancel_on_ctrl_c;
+pub use crate::cancel::{cancel, is_cancelled};
+pub use crate::cargo_flags::{
+    cargo_options, ensure_online, is_locked, is_offline, set_cargo_flags, set_locked, set_offline,
+};
+pub use crate::cfg::{target_cfgs, Cfg, CfgExpr, Platform};
+pub use crate::color::{color_preference, set_color_preference, ColorPreference};
+#[cfg(feature = "atty")]
+pub use crate::color::{stderr_color_choice, stdout_color_choice};
+#[cfg(feature = "structopt")]
+pub use crate::completions::{write_completions, CompletionNames, DynamicCompletions};
+pub use crate::config::{Config, ConfigSource};
+#[cfg(feature = "atty")]
+pub use crate::confirm::{can_ask, confirm, select};
+pub use crate::crate_name::{
+    normalize_crate_name, validate_crate_name, CrateName, NamingRules, MAX_CRATE_NAME_LEN,
+};
+#[cfg(feature = "crates-io-api")]
+pub use crate::crates_io::{
+    crate_links, notable_reverse_dependencies, CrateInfo, CrateLinks, CrateRelease, CratesIoClient,
+    Owner, ReverseDependencies, ReverseDependency, CRATES_IO_API,
+};
+#[cfg(feature = "index")]
+pub use crate::credential::registry_token;
 pub use crate::dependency::Dependency;
+pub use crate::dependency_path::{portable_path, relative_path};
+pub use crate::dependency_table::{DependencyEntry, DependencyKind, DependencyTable};
+pub use crate::diff::{manifest_diff, ManifestChange, ManifestChanges};
+pub use crate::duplicates::{
+    find_duplicate_dependencies, merge_duplicate_dependencies, set_fix_duplicates,
+    DuplicateDependency,
+};
 pub use crate::errors::*;
+pub use crate::exit_code::ExitCode;
+pub use crate::features::FeatureValue;
+#[cfg(feature = "index")]
+pub use crate::features::{
+    default_feature_names, feature_names, resolve_features, EnabledFeatures,
+};
+#[cfg(feature = "fetch")]
+pub use crate::fetch::{
+    explain_dependency, get_compatible_dependency, get_crate_name_from_github,
+    get_crate_name_from_gitlab, get_crate_versions, get_dependency_with_policy, get_git_revision,
+    get_latest_dependencies, get_latest_dependencies_with_candidates, get_latest_dependency,
+    get_latest_dependency_candidates, get_latest_dependency_with_fallback, get_upgrade_candidates,
+    get_version_checksum, get_yanked_versions, update_registry_index,
+    update_registry_index_if_stale,
+};
+#[cfg(feature = "index")]
 pub use crate::fetch::{
-    get_crate_name_from_github, get_crate_name_from_gitlab, get_crate_name_from_path,
-    get_latest_dependency, update_registry_index,
+    explain_dependency_from, get_compatible_dependency_from, get_dependency_with_policy_from,
+    get_latest_dependency_from, get_upgrade_candidates_from, get_version_checksum_from,
+    get_yanked_versions_from, UpgradeCandidates,
 };
-pub use crate::manifest::{find, LocalManifest, Manifest};
+#[cfg(feature = "index")]
+pub use crate::fuzzy::{FuzzyNames, NameOrder, WithFuzzyNames};
+pub use crate::generated::{
+    is_generated_manifest, original_manifest, set_generated_manifest_policy,
+    GeneratedManifestPolicy,
+};
+#[cfg(feature = "index")]
+pub use crate::index::{
+    CrateVersionInfo, DirectorySource, InMemoryIndex, IndexDependency, IndexSource, LocalRegistry,
+};
+#[cfg(feature = "fetch")]
+pub use crate::index::{GitIndex, SparseIndex};
+pub use crate::inheritance::{DependencyField, ResolvedDependency};
+#[cfg(feature = "structopt")]
+pub use crate::json_request::{json_error_response, read_json_request};
+pub use crate::lockfile::{LockedPackage, Lockfile};
+pub use crate::logical_path::same_file;
+#[cfg(feature = "structopt")]
+pub use crate::man_page::write_man_page;
+pub use crate::manifest::{
+    find, get_crate_name_from_path, get_crate_version_from_path, get_rust_version_from_manifest,
+    LocalManifest, Manifest,
+};
+pub use crate::manifest_lock::ManifestLock;
 pub use crate::metadata::manifest_from_pkgid;
-pub use crate::registry::registry_url;
+#[cfg(feature = "fetch")]
+pub use crate::msrv::get_msrv_compatibility;
+#[cfg(feature = "index")]
+pub use crate::msrv::{get_msrv_compatibility_from, MsrvCompatibility};
+pub use crate::pkgid::{GitReference, PackageIdSpec, SpecSource};
+#[cfg(feature = "index")]
+pub use crate::policy::{
+    explain_selection, Compatible, Latest, MsrvCompatible, PublishedBefore, Rejection, Selection,
+    VersionPolicy,
+};
+pub use crate::prerelease::{
+    bump_prerelease, compare_prereleases, same_upcoming_release, with_build_metadata, Channel,
+};
+#[cfg(feature = "fetch")]
+pub use crate::progress::{disable_progress, Progress};
+#[cfg(feature = "fetch")]
+pub use crate::protocol::{register_protocol, RegistryProtocol};
+#[cfg(feature = "fetch")]
+pub use crate::proxy::{disable_proxy, proxy_for_url};
+pub use crate::registry::{registry_name, registry_names, registry_url};
+pub use crate::report::{
+    DependencyStatus, ManifestReport, OutputFormat, Report, SkipReason, REPORT_SCHEMA_VERSION,
+};
+pub use crate::repository::{Changelog, Forge, Repository};
+#[cfg(feature = "fetch")]
+pub use crate::retry::RetryPolicy;
+pub use crate::rust_version::RustVersion;
+pub use crate::schema::{
+    validate_metadata, warn_metadata_violations, Schema, SchemaType, SchemaViolation,
+};
+#[cfg(feature = "fetch")]
+pub use crate::service::Service;
+#[cfg(feature = "index")]
+pub use crate::snapshot::IndexSnapshot;
+pub use crate::style::{QuoteStyle, StyleConfig, TableStyle};
+#[cfg(feature = "term_size")]
+pub use crate::table::terminal_width;
+pub use crate::table::Table;
+pub use crate::text_diff::write_manifest_diff;
+#[cfg(feature = "fetch")]
+pub use crate::tls::apply_tls;
+pub use crate::toml_key::{parse_dotted_key, quote_key};
+#[cfg(feature = "tracing")]
+pub use crate::trace::init_tracing;
+pub use crate::transaction::ManifestTransaction;
+pub use crate::verbosity::{set_verbosity, verbosity, Verbosity};
+pub use crate::version_req::{
+    covering_req, narrow_req, req_with_precision, reqs_overlap, widen_req, ReqPrecision,
+};
+pub use crate::workspace::{
+    ensure_package, find_workspace_root, BrokenManifest, Workspace, WorkspaceMember,
+};
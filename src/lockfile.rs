@@ -0,0 +1,337 @@
+//! Reading `Cargo.lock`, and making small targeted edits to it.
+//!
+//! Every format version cargo writes is read, from the unversioned v1 to v4. Edits are made to the
+//! text of the lock file, so everything not edited stays byte for byte as cargo wrote it.
+use crate::cargo_flags::ensure_unlocked;
+use crate::errors::*;
+use crate::transaction::write_atomically;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// A package recorded in `Cargo.lock`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct LockedPackage {
+    /// The name of the package
+    pub name: String,
+    /// The locked version
+    pub version: semver::Version,
+    /// Where the package comes from, like `registry+https://github.com/rust-lang/crates.io-index`,
+    /// or `None` for workspace members and path dependencies
+    pub source: Option<String>,
+    /// The checksum of the downloaded package, for registry packages since format v2
+    pub checksum: Option<String>,
+    /// The packages this one depends on, as written in the lock file: `name`, `name version` or
+    /// `name version (source)`, whichever is needed to tell packages apart
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+}
+
+impl LockedPackage {
+    /// Whether the package comes from a registry, as opposed to git or a path.
+    pub fn is_registry(&self) -> bool {
+        self.source
+            .as_ref()
+            .is_some_and(|s| s.starts_with("registry+") || s.starts_with("sparse+"))
+    }
+
+    /// Whether the dependency entry `entry` refers to this package.
+    fn is_referred_to_by(&self, entry: &str) -> bool {
+        let mut parts = entry.splitn(3, ' ');
+        let name = parts.next().unwrap_or_default();
+        let version = parts.next();
+        let source = parts
+            .next()
+            .map(|s| s.trim_start_matches('(').trim_end_matches(')'));
+        name == self.name
+            && version.is_none_or(|v| v == self.version.to_string())
+            && source.is_none_or(|s| self.source.as_deref() == Some(s))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawLockfile {
+    version: Option<u32>,
+    #[serde(default)]
+    package: Vec<LockedPackage>,
+}
+
+/// The contents of a `Cargo.lock`.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::Lockfile;
+///
+///   let mut lockfile: Lockfile = r#"
+///   version = 3
+///
+///   [[package]]
+///   name = "app"
+///   version = "0.1.0"
+///   dependencies = [
+///    "log",
+///   ]
+///
+///   [[package]]
+///   name = "log"
+///   version = "0.4.14"
+///   source = "registry+https://github.com/rust-lang/crates.io-index"
+///   "#.parse().unwrap();
+///
+///   let app = lockfile.package("app").unwrap();
+///   assert_eq!(lockfile.dependencies(app)[0].name, "log");
+///
+///   let old = semver::Version::new(0, 1, 0);
+///   lockfile.set_package_version("app", &old, &semver::Version::new(0, 2, 0)).unwrap();
+///   assert!(lockfile.to_string().contains("version = \"0.2.0\""));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Lockfile {
+    /// The format version, or `None` for the unversioned v1 and v2 formats
+    pub version: Option<u32>,
+    packages: Vec<LockedPackage>,
+    content: String,
+}
+
+impl Lockfile {
+    /// The lock file of the workspace rooted at `workspace_root`.
+    pub fn path_for_workspace(workspace_root: &Path) -> PathBuf {
+        workspace_root.join("Cargo.lock")
+    }
+
+    /// Read the lock file at `path`.
+    pub fn open(path: &Path) -> Result<Self> {
+        let _span = span!(DEBUG, "parse_lockfile", path = %path.display());
+        let content = std::fs::read_to_string(path)
+            .chain_err(|| format!("Failed to read lock file `{}`", path.display()))?;
+        content
+            .parse()
+            .chain_err(|| format!("Invalid lock file `{}`", path.display()))
+    }
+
+    /// Write the lock file to `path`, unless the lock file may not be changed (see
+    /// [`set_locked`](crate::set_locked)).
+    pub fn write(&self, path: &Path) -> Result<()> {
+        ensure_unlocked(path)?;
+        write_atomically(path, self.content.as_bytes())
+            .chain_err(|| format!("Failed to write lock file `{}`", path.display()))
+    }
+
+    /// All locked packages.
+    pub fn packages(&self) -> &[LockedPackage] {
+        &self.packages
+    }
+
+    /// The locked package named `name`, if there is exactly one.
+    pub fn package(&self, name: &str) -> Option<&LockedPackage> {
+        let mut packages = self.packages.iter().filter(|p| p.name == name);
+        let package = packages.next()?;
+        if packages.next().is_some() {
+            return None;
+        }
+        Some(package)
+    }
+
+    /// The newest locked registry version of `name` which satisfies `req`.
+    pub fn locked_version(&self, name: &str, req: &semver::VersionReq) -> Option<&semver::Version> {
+        self.packages
+            .iter()
+            .filter(|p| p.name == name && p.is_registry())
+            .map(|p| &p.version)
+            .filter(|v| req.matches(v))
+            .max()
+    }
+
+    /// The packages `package` depends on.
+    pub fn dependencies(&self, package: &LockedPackage) -> Vec<&LockedPackage> {
+        package
+            .dependencies
+            .iter()
+            .filter_map(|entry| self.packages.iter().find(|p| p.is_referred_to_by(entry)))
+            .collect()
+    }
+
+    /// The packages depending on `package`.
+    pub fn dependents(&self, package: &LockedPackage) -> Vec<&LockedPackage> {
+        self.packages
+            .iter()
+            .filter(|p| {
+                p.dependencies
+                    .iter()
+                    .any(|entry| package.is_referred_to_by(entry))
+            })
+            .collect()
+    }
+
+    /// Change the locked version of a package, e.g. of a workspace member after bumping its
+    /// version, along with every entry referring to it.
+    ///
+    /// Fails if no package `name` is locked at version `old`.
+    pub fn set_package_version(
+        &mut self,
+        name: &str,
+        old: &semver::Version,
+        new: &semver::Version,
+    ) -> Result<()> {
+        let index = self
+            .packages
+            .iter()
+            .position(|p| p.name == name && p.version == *old)
+            .chain_err(|| format!("`{} {}` is not in the lock file", name, old))?;
+
+        let (old_ref, new_ref) = (format!("{} {}", name, old), format!("{} {}", name, new));
+        let mut in_package = false;
+        let mut content = String::with_capacity(self.content.len());
+        for line in self.content.split_inclusive('\n') {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') && !trimmed.starts_with("[\"") {
+                in_package = false;
+            }
+            if trimmed == format!("name = \"{}\"", name) {
+                in_package = true;
+            }
+            let edited = if in_package && trimmed == format!("version = \"{}\"", old) {
+                in_package = false;
+                line.replacen(&old.to_string(), &new.to_string(), 1)
+            } else if is_dependency_entry(trimmed, &old_ref) {
+                line.replacen(&old_ref, &new_ref, 1)
+            } else {
+                line.to_owned()
+            };
+            content.push_str(&edited);
+        }
+        self.content = content;
+
+        for package in &mut self.packages {
+            for entry in &mut package.dependencies {
+                if entry == &old_ref || entry.starts_with(&format!("{} (", old_ref)) {
+                    *entry = entry.replacen(&old_ref, &new_ref, 1);
+                }
+            }
+        }
+        self.packages[index].version = new.clone();
+        Ok(())
+    }
+}
+
+/// Whether the lock file line `line` is a dependency entry for `name version`.
+fn is_dependency_entry(line: &str, reference: &str) -> bool {
+    let entry = line.trim_end_matches(',');
+    entry == format!("\"{}\"", reference) || entry.starts_with(&format!("\"{} (", reference))
+}
+
+impl FromStr for Lockfile {
+    type Err = Error;
+
+    fn from_str(content: &str) -> Result<Self> {
+        let raw: RawLockfile = toml::from_str(content).chain_err(|| "Invalid lock file")?;
+        Ok(Lockfile {
+            version: raw.version,
+            packages: raw.package,
+            content: content.to_owned(),
+        })
+    }
+}
+
+impl std::fmt::Display for Lockfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1: &str = include_str!("../tests/fixtures/upgrade/Cargo.lock");
+
+    const V4: &str = r#"# This file is automatically @generated by Cargo.
+# It is not intended for manual editing.
+version = 4
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "lib",
+ "rand 0.7.3",
+ "rand 0.8.5",
+]
+
+[[package]]
+name = "lib"
+version = "0.1.0"
+dependencies = [
+ "rand 0.8.5",
+]
+
+[[package]]
+name = "rand"
+version = "0.7.3"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "6a6b1679d49b24bbfe0c803429aa1874472f50d9b363131f0e89fc356b544d03"
+
+[[package]]
+name = "rand"
+version = "0.8.5"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "34af8d1a0e25924bc5b7c43c7c4f68b5a49f7f6b1e2b6f2d5b8c3e3c8a4d4f2e"
+"#;
+
+    fn version(v: &str) -> semver::Version {
+        semver::Version::parse(v).unwrap()
+    }
+
+    #[test]
+    fn reads_all_formats() {
+        let v1: Lockfile = V1.parse().unwrap();
+        assert_eq!(v1.version, None);
+        let rand = v1.package("rand").unwrap();
+        let deps: Vec<String> = v1
+            .dependencies(rand)
+            .iter()
+            .map(|p| format!("{} {}", p.name, p.version))
+            .collect();
+        assert_eq!(deps, ["advapi32-sys 0.1.2", "libc 0.1.12", "winapi 0.2.8"]);
+
+        let v4: Lockfile = V4.parse().unwrap();
+        assert_eq!(v4.version, Some(4));
+        assert!(v4.package("rand").is_none());
+        let req = semver::VersionReq::parse("0.7").unwrap();
+        assert_eq!(v4.locked_version("rand", &req), Some(&version("0.7.3")));
+        let lib = v4.package("lib").unwrap();
+        let dependents: Vec<&str> = v4.dependents(lib).iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(dependents, ["app"]);
+    }
+
+    #[test]
+    fn edits_only_the_package() {
+        let mut lockfile: Lockfile = V4.parse().unwrap();
+        lockfile
+            .set_package_version("rand", &version("0.8.5"), &version("0.8.6"))
+            .unwrap();
+        let expected = V4
+            .replace("version = \"0.8.5\"", "version = \"0.8.6\"")
+            .replace("\"rand 0.8.5\"", "\"rand 0.8.6\"");
+        assert_eq!(lockfile.to_string(), expected);
+        assert_eq!(
+            lockfile
+                .dependencies(lockfile.package("app").unwrap())
+                .len(),
+            3
+        );
+        assert!(lockfile
+            .set_package_version("rand", &version("0.8.5"), &version("0.8.6"))
+            .is_err());
+
+        let mut lockfile: Lockfile = V1.parse().unwrap();
+        lockfile
+            .set_package_version("libc", &version("0.2.65"), &version("0.2.66"))
+            .unwrap();
+        let expected = V1
+            .replacen("version = \"0.2.65\"", "version = \"0.2.66\"", 1)
+            .replace("\"libc 0.2.65 (", "\"libc 0.2.66 (");
+        assert_eq!(lockfile.to_string(), expected);
+    }
+}
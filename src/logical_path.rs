@@ -0,0 +1,27 @@
+//! Paths as the user sees them, through symlinks, rather than as the file system resolves them.
+//!
+//! Workspaces often gather members from other repositories with symlinks. Paths are kept as they
+//! are written, so that relative paths are computed against that layout, and files are only
+//! compared by where they really are, so that a member reached through two paths counts once.
+use std::path::{Path, PathBuf};
+
+/// The current directory as the shell shows it: `PWD` if it is the same directory as the one the
+/// operating system reports, which has symlinks resolved.
+pub(crate) fn current_dir() -> std::io::Result<PathBuf> {
+    let physical = std::env::current_dir()?;
+    let logical = std::env::var_os("PWD")
+        .map(PathBuf::from)
+        .filter(|pwd| pwd.is_absolute() && pwd != &physical && same_file(pwd, &physical));
+    Ok(logical.unwrap_or(physical))
+}
+
+/// Whether `a` and `b` are the same file, maybe reached through different symlinks.
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    a == b || identity(a) == identity(b)
+}
+
+/// What identifies the file at `path`: its path with symlinks resolved if it exists, `path`
+/// otherwise.
+pub(crate) fn identity(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
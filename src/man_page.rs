@@ -0,0 +1,279 @@
+//! Man pages of the `cargo` subcommands, rendered from their help
+
+use crate::errors::*;
+use std::io::Write;
+use structopt::clap::{App, AppSettings, ErrorKind as ClapErrorKind};
+
+/// An argument listed in a section of the help, with its description.
+struct Entry {
+    spec: String,
+    paragraphs: Vec<String>,
+}
+
+/// Write the man page of `cargo <subcommand>`, in roff, to `out`.
+///
+/// `app` is the `cargo` command with `subcommand` in it, as cargo invokes the binary. The page is
+/// rendered from the long help of the subcommand, so it lists the same arguments: its sections are
+/// NAME, SYNOPSIS, DESCRIPTION (from the text after the arguments), one per group of arguments
+/// and SEE ALSO.
+pub fn write_man_page(app: App<'_, '_>, subcommand: &str, out: &mut impl Write) -> Result<()> {
+    let help = long_help(app, subcommand)?;
+    let page = render(&help, subcommand);
+    out.write_all(page.as_bytes())
+        .chain_err(|| "Failed to write man page")
+}
+
+fn long_help(app: App<'_, '_>, subcommand: &str) -> Result<String> {
+    let app = app
+        .global_setting(AppSettings::ColorNever)
+        // Lines are wrapped by the man page viewer instead.
+        .set_term_width(0);
+    match app.get_matches_from_safe(["cargo", subcommand, "--help"]) {
+        Err(err) if err.kind == ClapErrorKind::HelpDisplayed => Ok(err.message),
+        _ => Err(format!("No help for `cargo {}`", subcommand).into()),
+    }
+}
+
+fn render(help: &str, subcommand: &str) -> String {
+    let command = format!("cargo-{}", subcommand);
+    let mut lines = help.lines();
+    let version = lines
+        .next()
+        .and_then(|title| title.strip_prefix(&command))
+        .map(str::trim)
+        .unwrap_or_default();
+    let about = lines.next().unwrap_or_default();
+
+    let mut page = format!(
+        ".TH \"{}\" \"1\" \"\" \"cargo-edit {}\" \"Cargo Manual\"\n",
+        command.to_uppercase(),
+        version
+    );
+    page.push_str(&format!(
+        ".SH NAME\n{} \\- {}\n",
+        escape(&command),
+        escape(about)
+    ));
+
+    let mut sections = Vec::new();
+    let mut description = Vec::new();
+    let mut current: Option<(String, Vec<String>)> = None;
+    for line in lines {
+        if let Some(heading) = line.strip_suffix(':').filter(|h| is_heading(h)) {
+            sections.extend(current.take());
+            current = Some((heading.to_owned(), Vec::new()));
+        } else if line.is_empty() || line.starts_with(' ') {
+            match current {
+                Some((_, ref mut body)) => body.push(line.to_owned()),
+                None => description.push(line.to_owned()),
+            }
+        } else {
+            // Unindented text after the arguments describes the command.
+            sections.extend(current.take());
+            description.push(line.to_owned());
+        }
+    }
+    sections.extend(current);
+
+    for (heading, body) in &sections {
+        if heading == "USAGE" {
+            page.push_str(".SH SYNOPSIS\n");
+            for usage in body
+                .iter()
+                .map(|line| line.trim())
+                .filter(|l| !l.is_empty())
+            {
+                page.push_str(&format!("{}\n.br\n", synopsis(usage, subcommand)));
+            }
+        }
+    }
+    let description = paragraphs(&description);
+    if !description.is_empty() {
+        page.push_str(".SH DESCRIPTION\n");
+        page.push_str(
+            &description
+                .iter()
+                .map(|p| escape(p))
+                .collect::<Vec<_>>()
+                .join("\n.PP\n"),
+        );
+        page.push('\n');
+    }
+    for (heading, body) in sections.iter().filter(|(heading, _)| heading != "USAGE") {
+        page.push_str(&format!(".SH {}\n", heading));
+        for entry in entries(body) {
+            page.push_str(&format!(".TP\n{}\n", spec(&entry.spec)));
+            let paragraphs: Vec<String> = entry.paragraphs.iter().map(|p| escape(p)).collect();
+            page.push_str(&paragraphs.join("\n.sp\n"));
+            page.push('\n');
+        }
+    }
+    page.push_str(".SH \"SEE ALSO\"\ncargo(1)\n");
+    page
+}
+
+/// Whether `line` is a heading like `USAGE` or `FLAGS`.
+fn is_heading(line: &str) -> bool {
+    !line.is_empty() && line.chars().all(|c| c.is_ascii_uppercase() || c == ' ')
+}
+
+/// The paragraphs of `lines`, each joined into one line.
+fn paragraphs(lines: &[String]) -> Vec<String> {
+    lines
+        .split(|line| line.trim().is_empty())
+        .map(|lines| {
+            lines
+                .iter()
+                .map(|line| line.trim())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect()
+}
+
+/// The arguments of a section, like `    -p, --package <pkgid>    Package id of the crate`.
+///
+/// Arguments are indented by four spaces, or eight for flags without a short name. With long
+/// help, descriptions start on the line after the argument, indented by twelve.
+fn entries(body: &[String]) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut lines = Vec::new();
+    for line in body {
+        let indent = line.len() - line.trim_start().len();
+        let trimmed = line.trim();
+        if !trimmed.is_empty() && indent < 12 {
+            if let Some(entry) = entries.last_mut() {
+                entry.paragraphs.extend(paragraphs(&lines));
+            }
+            lines.clear();
+            let (spec, description) = match trimmed.find("  ") {
+                Some(split) => (&trimmed[..split], trimmed[split..].trim()),
+                None => (trimmed, ""),
+            };
+            entries.push(Entry {
+                spec: spec.to_owned(),
+                paragraphs: Vec::new(),
+            });
+            lines.push(description.to_owned());
+        } else {
+            lines.push(trimmed.to_owned());
+        }
+    }
+    if let Some(entry) = entries.last_mut() {
+        entry.paragraphs.extend(paragraphs(&lines));
+    }
+    entries
+}
+
+/// The synopsis of the command, with the command in bold.
+fn synopsis(usage: &str, subcommand: &str) -> String {
+    let command = format!("cargo {}", subcommand);
+    match usage.strip_prefix(&command) {
+        Some(rest) => format!("\\fB{}\\fR{}", escape(&command), escape(rest)),
+        None => escape(usage),
+    }
+}
+
+/// An argument in bold, with its value in italics, like `\fB\-p\fR, \fB\-\-package\fR \fI<pkgid>\fR`.
+fn spec(spec: &str) -> String {
+    spec.split(", ")
+        .map(|name| {
+            name.split(' ')
+                .map(|word| {
+                    let font = if word.starts_with('-') { "B" } else { "I" };
+                    format!("\\f{}{}\\fR", font, escape(word))
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `text` with the characters roff gives a meaning escaped.
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    if text.starts_with('.') || text.starts_with('\'') {
+        format!("\\&{}", text)
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::clap::{Arg, SubCommand};
+
+    fn app() -> App<'static, 'static> {
+        App::new("cargo-edit").bin_name("cargo").subcommand(
+            SubCommand::with_name("rm")
+                .version("0.7.0")
+                .about("Remove a dependency from a Cargo.toml manifest file")
+                .after_help("Dependencies are removed from every table.\n\nThe manifest is found from the working directory.")
+                .arg(
+                    Arg::with_name("crates")
+                        .required(true)
+                        .multiple(true)
+                        .help("Crates to be removed"),
+                )
+                .arg(
+                    Arg::with_name("pkgid")
+                        .long("package")
+                        .short("p")
+                        .value_name("pkgid")
+                        .help("Package id of the crate to remove this dependency from"),
+                )
+                .arg(
+                    Arg::with_name("quiet")
+                        .long("quiet")
+                        .help("Do not print any output in case of success")
+                        .long_help("Do not print any output in case of success.\n\nErrors are still printed."),
+                ),
+        )
+    }
+
+    fn page() -> String {
+        let mut out = Vec::new();
+        write_man_page(app(), "rm", &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn renders_the_sections() {
+        let page = page();
+        assert!(
+            page.starts_with(
+                ".TH \"CARGO-RM\" \"1\" \"\" \"cargo-edit 0.7.0\" \"Cargo Manual\"\n\
+             .SH NAME\n\
+             cargo\\-rm \\- Remove a dependency from a Cargo.toml manifest file\n\
+             .SH SYNOPSIS\n\
+             \\fBcargo rm\\fR [FLAGS] [OPTIONS] <crates>...\n"
+            ),
+            "{}",
+            page
+        );
+        assert!(page.contains(
+            ".SH DESCRIPTION\n\
+             Dependencies are removed from every table.\n\
+             .PP\n\
+             The manifest is found from the working directory.\n"
+        ));
+        assert!(page.ends_with(".SH \"SEE ALSO\"\ncargo(1)\n"));
+    }
+
+    #[test]
+    fn lists_the_arguments() {
+        let page = page();
+        assert!(page.contains(
+            ".TP\n\\fB\\-p\\fR, \\fB\\-\\-package\\fR \\fI<pkgid>\\fR\n\
+             Package id of the crate to remove this dependency from\n"
+        ));
+        assert!(page.contains(
+            ".TP\n\\fB\\-\\-quiet\\fR\n\
+             Do not print any output in case of success.\n.sp\nErrors are still printed.\n"
+        ));
+        assert!(page.contains(".SH ARGS\n.TP\n\\fI<crates>...\\fR\nCrates to be removed\n"));
+    }
+}
@@ -2,16 +2,31 @@ use std::fs::{self, File, OpenOptions};
 use std::io::{Read, Write};
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
-use std::{env, str};
+use std::str;
 
 use semver::{Version, VersionReq};
 use termcolor::{BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
 
+use crate::cfg::Platform;
+use crate::color::color_choice_or;
 use crate::dependency::Dependency;
+use crate::dependency_table::{self, DependencyEntry, DependencyKind};
+use crate::duplicates::parse_document;
 use crate::errors::*;
+use crate::features::FeatureValue;
+use crate::generated::manifest_to_edit;
+use crate::rust_version::RustVersion;
+use crate::style::StyleConfig;
+use crate::toml_key::{self, parse_dotted_key, quote_key};
+use crate::transaction::ManifestTransaction;
+use crate::verbosity::verbosity;
+use crate::workspace;
 
 const MANIFEST_FILENAME: &str = "Cargo.toml";
 
+/// The key below `metadata` under which cargo-edit reads its own settings.
+pub(crate) const CARGO_EDIT_KEY: &str = "cargo-edit";
+
 /// A Cargo manifest
 #[derive(Debug, Clone)]
 pub struct Manifest {
@@ -24,18 +39,24 @@ pub struct Manifest {
 /// If a manifest is specified, return that one. If a path is specified, perform a manifest search
 /// starting from there. If nothing is specified, start searching from the current directory
 /// (`cwd`).
+///
+/// A manifest generated by cargo for a packaged crate may be swapped for its `Cargo.toml.orig`,
+/// as [`set_generated_manifest_policy`](crate::set_generated_manifest_policy) says.
 pub fn find(specified: &Option<PathBuf>) -> Result<PathBuf> {
-    match *specified {
+    let found = match *specified {
         Some(ref path)
-            if fs::metadata(&path)
+            if fs::metadata(path)
                 .chain_err(|| "Failed to get cargo file metadata")?
                 .is_file() =>
         {
-            Ok(path.to_owned())
+            path.to_owned()
         }
-        Some(ref path) => search(path),
-        None => search(&env::current_dir().chain_err(|| "Failed to get current directory")?),
-    }
+        Some(ref path) => search(path)?,
+        None => search(
+            &crate::logical_path::current_dir().chain_err(|| "Failed to get current directory")?,
+        )?,
+    };
+    manifest_to_edit(found)
 }
 
 /// Search for Cargo.toml in this directory and recursively up the tree until one is found.
@@ -47,7 +68,7 @@ fn search(dir: &Path) -> Result<PathBuf> {
     } else {
         dir.parent()
             .ok_or_else(|| ErrorKind::MissingManifest.into())
-            .and_then(|dir| search(dir))
+            .and_then(search)
     }
 }
 
@@ -57,8 +78,77 @@ fn merge_inline_table(old_dep: &mut toml_edit::Item, new: &toml_edit::Item) {
         .expect("expected an inline table")
         .iter()
     {
-        old_dep[k] = toml_edit::value(v.clone());
+        if !holds_value(&old_dep[k], v) {
+            set_value_keeping_comments(&mut old_dep[k], v.clone());
+        }
+    }
+}
+
+/// Whether `old` already holds `new`, maybe written differently, like `"bin"` for `["bin"]`, so
+/// that it can be left as written.
+fn holds_value(old: &toml_edit::Item, new: &toml_edit::Value) -> bool {
+    fn values(value: &toml_edit::Value) -> Option<Vec<String>> {
+        match value {
+            toml_edit::Value::String(s) => Some(vec![s.value().clone()]),
+            toml_edit::Value::Boolean(b) => Some(vec![b.value().to_string()]),
+            toml_edit::Value::Array(array) => array
+                .iter()
+                .map(|value| value.as_str().map(String::from))
+                .collect(),
+            _ => None,
+        }
+    }
+    match (old.as_value().and_then(values), values(new)) {
+        (Some(old), Some(new)) => old == new,
+        _ => false,
+    }
+}
+
+/// Replace the value in `slot` with `new`, keeping the whitespace and comments around the old
+/// value, like the comment at the end of its line.
+fn set_value_keeping_comments(slot: &mut toml_edit::Item, new: toml_edit::Value) {
+    let new = match slot.as_value() {
+        Some(old) => toml_edit::decorated(new, old.decor().prefix(), old.decor().suffix()),
+        None => toml_edit::decorated(new, " ", ""),
+    };
+    *slot = toml_edit::Item::Value(new);
+}
+
+/// `new`, replacing the plain version `old`, with the comment at the end of the old line moved to
+/// the end of its first line.
+fn table_keeping_comments(old: &toml_edit::Item, mut new: toml_edit::Table) -> toml_edit::Table {
+    let suffix = old.as_value().map(|old| old.decor().suffix()).unwrap_or("");
+    let first_key = new.iter().map(|(key, _)| key.to_owned()).next();
+    if let Some(first_key) = first_key {
+        if let Some(first) = new[&first_key].as_value().cloned() {
+            new[&first_key] = toml_edit::Item::Value(toml_edit::decorated(first, " ", suffix));
+        }
+    }
+    new
+}
+
+/// Whether there are comments between the entry `key` of `table` and the one before it.
+fn has_comments_above(table: &toml_edit::Item, key: &str) -> bool {
+    let mut entry = match table.as_table() {
+        Some(table) => table.clone(),
+        None => return false,
+    };
+    let others: Vec<String> = entry
+        .iter()
+        .map(|(other, _)| other.to_owned())
+        .filter(|other| other != key)
+        .collect();
+    for other in others {
+        entry.remove(&other);
     }
+    // Without a header, the table is rendered as the lines of the entry, comments first.
+    let rendered = entry.to_string();
+    rendered
+        .lines()
+        .take_while(|line| {
+            parse_dotted_key(line.trim_start()).map(|(path, _)| path) != Some(vec![key.to_owned()])
+        })
+        .any(|line| line.trim_start().starts_with('#'))
 }
 
 fn str_or_1_len_table(item: &toml_edit::Item) -> bool {
@@ -66,21 +156,58 @@ fn str_or_1_len_table(item: &toml_edit::Item) -> bool {
 }
 /// Merge a new dependency into an old entry. See `Dependency::to_toml` for what the format of the
 /// new dependency will be.
+///
+/// Comments around the old entry are kept: replaced values keep the comment at the end of their
+/// line, and a `[dependencies.name]` table stays a table instead of becoming an inline one.
 fn merge_dependencies(old_dep: &mut toml_edit::Item, new: &Dependency) {
     assert!(!old_dep.is_none());
 
     let new_toml = new.to_toml().1;
+    // The keys of the old entry which are replaced rather than removed.
+    let replaced = |key: &str| match new_toml.as_inline_table() {
+        Some(new) => new.contains_key(key),
+        None => key == "version",
+    };
 
-    if str_or_1_len_table(old_dep) {
+    if old_dep.is_value() && str_or_1_len_table(old_dep) {
         // The old dependency is just a version/git/path. We are safe to overwrite.
-        *old_dep = new_toml;
+        let new = new_toml
+            .as_value()
+            .cloned()
+            .expect("a dependency is a value");
+        set_value_keeping_comments(old_dep, new);
     } else if old_dep.is_table_like() {
-        for key in &["version", "path", "git"] {
+        let removed: Vec<String> = match old_dep.as_table_like() {
+            // A table with only a version/git/path is overwritten, but stays a table.
+            Some(old) if old.len() == 1 => old.iter().map(|(key, _)| key.to_owned()).collect(),
+            // Inheriting from `[workspace.dependencies]` takes the whole source from there.
+            _ if new.is_workspace() => {
+                ["version", "path", "git", "branch", "tag", "rev", "registry"]
+                    .iter()
+                    .map(|&key| key.to_owned())
+                    .collect()
+            }
+            // A git reference makes no sense without its repository, and another reference of
+            // the same repository would make two.
+            _ => [
+                "version",
+                "path",
+                "git",
+                "branch",
+                "tag",
+                "rev",
+                "workspace",
+            ]
+            .iter()
+            .map(|&key| key.to_owned())
+            .collect(),
+        };
+        for key in removed.iter().filter(|key| !replaced(key)) {
             // remove this key/value pairs
-            old_dep[key] = toml_edit::Item::None;
+            *toml_key::entry(old_dep, key) = toml_edit::Item::None;
         }
         if let Some(name) = new_toml.as_str() {
-            old_dep["version"] = toml_edit::value(name);
+            set_value_keeping_comments(&mut old_dep["version"], toml_edit::Value::from(name));
         } else {
             merge_inline_table(old_dep, &new_toml);
         }
@@ -118,7 +245,7 @@ fn old_version_compatible(dependency: &Dependency, old_version: &str) -> Result<
         None => return Ok(false),
     };
 
-    let current_version = Version::parse(&current_version).chain_err(|| {
+    let current_version = Version::parse(current_version).chain_err(|| {
         ErrorKind::ParseVersion(dependency.name.to_string(), current_version.into())
     })?;
 
@@ -138,7 +265,7 @@ fn print_upgrade_if_necessary(
         if old_version == new_version {
             return Ok(());
         }
-        let bufwtr = BufferWriter::stdout(ColorChoice::Always);
+        let bufwtr = BufferWriter::stdout(color_choice_or(ColorChoice::Always));
         let mut buffer = bufwtr.buffer();
         buffer
             .set_color(ColorSpec::new().set_fg(Some(Color::Green)).set_bold(true))
@@ -177,12 +304,19 @@ impl Manifest {
 
     /// Open the `Cargo.toml` for a path (or the process' `cwd`)
     pub fn open(path: &Option<PathBuf>) -> Result<Manifest> {
-        let mut file = Manifest::find_file(path)?;
+        let _span = span!(DEBUG, "parse_manifest", path = ?path);
+        let path = find(path)?;
+        let mut file = Manifest::find_file(&Some(path.clone()))?;
         let mut data = String::new();
         file.read_to_string(&mut data)
             .chain_err(|| "Failed to read manifest contents")?;
 
-        data.parse().chain_err(|| "Unable to parse Cargo.toml")
+        data.parse().map_err(|err: Error| err.in_manifest(&path))
+    }
+
+    /// Open and parse the manifests at `paths` concurrently, returning them in the same order.
+    pub fn open_all(paths: &[PathBuf]) -> Result<Vec<Manifest>> {
+        crate::parallel::try_map(paths, |path| Manifest::open(&Some(path.clone())))
     }
 
     /// Get the specified table from the manifest.
@@ -192,8 +326,15 @@ impl Manifest {
             input: &'a mut toml_edit::Item,
             path: &[String],
         ) -> Result<&'a mut toml_edit::Item> {
-            if let Some(segment) = path.get(0) {
-                let value = input[&segment].or_insert(toml_edit::table());
+            if let Some(segment) = path.first() {
+                let created = input[&segment].is_none();
+                let value = toml_key::entry(input, segment).or_insert(toml_edit::table());
+                // Tables created only to hold others, like `[target]`, get no header of their own.
+                if created && path.len() > 1 {
+                    if let Some(table) = value.as_table_mut() {
+                        table.set_implicit(true);
+                    }
+                }
 
                 if value.is_table_like() {
                     descend(value, &path[1..])
@@ -208,11 +349,18 @@ impl Manifest {
         descend(&mut self.data.root, table_path)
     }
 
-    /// Get all sections in the manifest that exist and might contain dependencies.
-    /// The returned items are always `Table` or `InlineTable`.
+    /// Get all sections in the manifest that exist and might contain dependencies, including
+    /// `[workspace.dependencies]`. The returned items are always `Table` or `InlineTable`.
     pub fn get_sections(&self) -> Vec<(Vec<String>, toml_edit::Item)> {
         let mut sections = Vec::new();
 
+        if self.data["workspace"]["dependencies"].is_table_like() {
+            sections.push((
+                vec!["workspace".to_owned(), "dependencies".to_owned()],
+                self.data["workspace"]["dependencies"].clone(),
+            ));
+        }
+
         for dependency_type in &["dev-dependencies", "build-dependencies", "dependencies"] {
             // Dependencies can be in the three standard sections...
             if self.data[dependency_type].is_table_like() {
@@ -250,18 +398,79 @@ impl Manifest {
         sections
     }
 
-    /// Overwrite a file with TOML data.
-    pub fn write_to_file(&self, file: &mut File) -> Result<()> {
-        if self.data["package"].is_none() && self.data["project"].is_none() {
-            if !self.data["workspace"].is_none() {
-                return Err(ErrorKind::UnexpectedRootManifest.into());
-            } else {
-                return Err(ErrorKind::InvalidManifest.into());
+    /// Every dependency entry, in `[workspace.dependencies]`, then in `[dependencies]`,
+    /// `[dev-dependencies]` and `[build-dependencies]`, each followed by its target-specific
+    /// variants.
+    pub fn dependency_entries(&self) -> impl Iterator<Item = DependencyEntry> {
+        dependency_table::entries(&self.data).into_iter()
+    }
+
+    /// The keys of the `[target]` tables.
+    fn target_keys(&self) -> Vec<&str> {
+        self.data
+            .as_table()
+            .get("target")
+            .and_then(toml_edit::Item::as_table_like)
+            .into_iter()
+            .flat_map(toml_edit::TableLike::iter)
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    /// The key of the `[target]` table for `platform`.
+    ///
+    /// This is an existing key for the same platform if there is one, even if spelled
+    /// differently (like `cfg(all(unix))` for `cfg(unix)`), or else the normalized spelling of
+    /// `platform`.
+    pub fn target_key(&self, platform: &Platform) -> String {
+        let normalized = platform.clone().normalize();
+        self.target_keys()
+            .into_iter()
+            .find(|key| {
+                key.parse::<Platform>()
+                    .is_ok_and(|existing| existing.normalize() == normalized)
+            })
+            .map_or_else(|| normalized.to_string(), str::to_owned)
+    }
+
+    /// `table_path`, with the platform of a path into `[target]` replaced by its
+    /// [key](Manifest::target_key), so that e.g. `cfg( unix )` finds the existing
+    /// `[target.'cfg(unix)']` rather than creating another table.
+    ///
+    /// This fails with [`ErrorKind::InvalidCfg`] for a platform which is not valid and not
+    /// already a key of `[target]`.
+    fn resolve_target_path(&self, table_path: &[String]) -> Result<Vec<String>> {
+        let mut table_path = table_path.to_vec();
+        if let [first, platform, ..] = table_path.as_mut_slice() {
+            if first == "target" && !self.target_keys().contains(&platform.as_str()) {
+                *platform = self.target_key(&platform.parse()?);
             }
         }
+        Ok(table_path)
+    }
+
+    /// Why each `[target]` table key that is neither a target name nor a valid `cfg(...)`
+    /// expression is invalid.
+    pub fn invalid_target_keys(&self) -> Vec<Error> {
+        self.target_keys()
+            .into_iter()
+            .filter_map(|key| key.parse::<Platform>().err())
+            .collect()
+    }
+
+    /// Overwrite a file with TOML data.
+    ///
+    /// The file is rewritten in place; see [`Manifest::write_to_path_with_style`] for a write
+    /// which can't leave it half-written.
+    pub fn write_to_file(&self, file: &mut File) -> Result<()> {
+        self.write_to_file_with_style(file, &StyleConfig::default())
+    }
 
-        let s = self.data.to_string_in_original_order();
+    /// Overwrite a file with TOML data, formatted according to `style`.
+    pub fn write_to_file_with_style(&self, file: &mut File, style: &StyleConfig) -> Result<()> {
+        let s = self.to_string_with_style(style)?;
         let new_contents_bytes = s.as_bytes();
+        event!(DEBUG, bytes = new_contents_bytes.len(), "writing manifest");
 
         // We need to truncate the file, otherwise the new contents
         // will be mixed up with the old ones.
@@ -271,11 +480,69 @@ impl Manifest {
             .chain_err(|| "Failed to write updated Cargo.toml")
     }
 
+    /// Replace the manifest at `path` with TOML data, formatted according to `style`.
+    ///
+    /// The data is written to a temporary file which is renamed over `path`, so the manifest is
+    /// never left half-written, even if the process is killed; a [cancelled](crate::cancel) write
+    /// leaves it as it was.
+    pub fn write_to_path_with_style(&self, path: &Path, style: &StyleConfig) -> Result<()> {
+        let mut transaction = ManifestTransaction::new();
+        transaction.stage_with_style(path, self, style)?;
+        transaction.commit()
+    }
+
+    /// The TOML data as it would be written, formatted according to `style`.
+    pub fn to_string_with_style(&self, style: &StyleConfig) -> Result<String> {
+        if self.data["package"].is_none() && self.data["project"].is_none() {
+            if self.data["workspace"].is_none() {
+                return Err(ErrorKind::InvalidManifest.into());
+            }
+            // A virtual manifest can be edited, e.g. its `[workspace.dependencies]`, but can't
+            // have dependencies of its own.
+            let package_tables = [
+                "dependencies",
+                "dev-dependencies",
+                "build-dependencies",
+                "target",
+            ];
+            if package_tables.iter().any(|t| !self.data[t].is_none()) {
+                return Err(ErrorKind::UnexpectedRootManifest.into());
+            }
+        }
+
+        Ok(style.apply_to_document(&self.data.to_string_in_original_order()))
+    }
+
     /// Add entry to a Cargo.toml.
     pub fn insert_into_table(&mut self, table_path: &[String], dep: &Dependency) -> Result<()> {
+        self.insert_into_table_with_style(table_path, dep, &StyleConfig::default())
+    }
+
+    /// Add entry to a Cargo.toml, formatting new entries according to `style`.
+    ///
+    /// Existing entries keep their form, except plain versions which need more keys: those take
+    /// the configured table style, or that of most entries of the manifest.
+    pub fn insert_into_table_with_style(
+        &mut self,
+        table_path: &[String],
+        dep: &Dependency,
+        style: &StyleConfig,
+    ) -> Result<()> {
+        let style = style.resolve_for(self);
+        let table_path = &self.resolve_target_path(table_path)?;
         let table = self.get_table(table_path)?;
 
-        let existing_dep = Self::find_dep(table, &dep.name);
+        // An entry under the key of a renamed `dep` is the one to update, even if other entries
+        // are renamed from the same package.
+        let existing_dep = match dep.rename() {
+            Some(renamed)
+                if Dependency::from_toml(renamed, &table[renamed])
+                    .is_some_and(|existing| existing.name == dep.name) =>
+            {
+                Some((renamed.to_owned(), table[renamed].clone()))
+            }
+            _ => Self::find_dep(table, &dep.name).map(|(key, item)| (key, item.clone())),
+        };
         if let Some((mut dep_name, dep_item)) = existing_dep {
             // update an existing entry
 
@@ -287,30 +554,41 @@ impl Manifest {
             // to
             // alias = { version = "0.2", package = "a" }
             if let Some(renamed) = dep.rename() {
-                table[renamed] = dep_item.clone();
-                table[&dep_name] = toml_edit::Item::None;
-                dep_name = renamed.to_owned();
+                if renamed != dep_name {
+                    *toml_key::entry(table, renamed) = dep_item;
+                    *toml_key::entry(table, &dep_name) = toml_edit::Item::None;
+                    dep_name = renamed.to_owned();
+                }
             } else if dep.name != dep_name {
                 // if `dep` had been renamed in the manifest,
                 // and is not rename in the `add` command,
-                // we need to remove the old entry and insert a new one
-                // e.g. from
+                // we need to move the old entry to its package name, e.g. from
                 // alias = { version = "0.1", package = "a" }
                 // to
                 // a = "0.2"
-                table[&dep_name] = toml_edit::Item::None;
-                let (ref name, ref mut new_dependency) = dep.to_toml();
-                table[name] = new_dependency.clone();
+                let mut dep_item = dep_item;
+                dep_item["package"] = toml_edit::Item::None;
+                *toml_key::entry(table, &dep_name) = toml_edit::Item::None;
+                *toml_key::entry(table, &dep.name) = dep_item;
                 dep_name = dep.name.to_owned();
             }
-            merge_dependencies(&mut table[dep_name], dep);
+            // Turning a plain version into a table of its own would lose the comments above it,
+            // so such entries stay on their line.
+            let commented = has_comments_above(table, &dep_name);
+            let entry = toml_key::entry(table, &dep_name);
+            match style.dependency_to_toml(dep).1 {
+                toml_edit::Item::Table(new_table) if entry.is_str() && !commented => {
+                    *entry = toml_edit::Item::Table(table_keeping_comments(entry, new_table));
+                }
+                _ => merge_dependencies(entry, dep),
+            }
             if let Some(t) = table.as_inline_table_mut() {
                 t.fmt()
             }
         } else {
             // insert a new entry
-            let (ref name, ref mut new_dependency) = dep.to_toml();
-            table[name] = new_dependency.clone();
+            let (ref name, ref mut new_dependency) = style.dependency_to_toml(dep);
+            *toml_key::entry(table, name) = new_dependency.clone();
         }
         Ok(())
     }
@@ -332,14 +610,32 @@ impl Manifest {
         item_name: &str,
         dep: &Dependency,
         dry_run: bool,
+    ) -> Result<()> {
+        self.merge_table_named_entry(table_path, item_name, dep, dry_run, false)
+    }
+
+    fn merge_table_named_entry(
+        &mut self,
+        table_path: &[String],
+        item_name: &str,
+        dep: &Dependency,
+        dry_run: bool,
+        quiet: bool,
     ) -> Result<()> {
         let table = self.get_table(table_path)?;
         let new_dep = dep.to_toml().1;
 
         // If (and only if) there is an old entry, merge the new one in.
         if !table[item_name].is_none() {
-            if let Err(e) = print_upgrade_if_necessary(&dep.name, &table[item_name], &new_dep) {
-                eprintln!("Error while displaying upgrade message, {}", e);
+            if !quiet && !verbosity().is_quiet() {
+                let name = if item_name == dep.name {
+                    dep.name.clone()
+                } else {
+                    dep.clone().set_rename(item_name).display_name()
+                };
+                if let Err(e) = print_upgrade_if_necessary(&name, &table[item_name], &new_dep) {
+                    eprintln!("Error while displaying upgrade message, {}", e);
+                }
             }
             if !dry_run {
                 merge_dependencies(&mut table[item_name], dep);
@@ -352,7 +648,109 @@ impl Manifest {
         Ok(())
     }
 
-    /// Remove entry from a Cargo.toml.
+    /// Replace the version requirement of the entry `item_name` in the given table, leaving every
+    /// other key of the entry untouched.
+    pub fn set_table_entry_version(
+        &mut self,
+        table_path: &[String],
+        item_name: &str,
+        version: &str,
+    ) -> Result<()> {
+        let table = self.get_table(table_path)?;
+        let entry = &mut table[item_name];
+        if entry.is_str() {
+            set_value_keeping_comments(entry, toml_edit::Value::from(version));
+        } else if entry.is_table_like() {
+            set_value_keeping_comments(&mut entry["version"], toml_edit::Value::from(version));
+            if let Some(t) = entry.as_inline_table_mut() {
+                t.fmt()
+            }
+        } else {
+            return Err(
+                ErrorKind::NonExistentDependency(item_name.into(), table_path.join(".")).into(),
+            );
+        }
+        Ok(())
+    }
+
+    /// The reason recorded for pinning the dependency `name`, if any.
+    ///
+    /// Reasons are kept in `[package.metadata.cargo-edit.pins]`.
+    pub fn pin_reason(&self, name: &str) -> Option<&str> {
+        self.data["package"]["metadata"][CARGO_EDIT_KEY]["pins"][name].as_str()
+    }
+
+    /// Record why the dependency `name` is pinned.
+    pub fn set_pin_reason(&mut self, name: &str, reason: &str) -> Result<()> {
+        let pins_path: Vec<String> = vec![
+            "package".to_owned(),
+            "metadata".to_owned(),
+            CARGO_EDIT_KEY.to_owned(),
+            "pins".to_owned(),
+        ];
+        // Avoid emitting empty `[package.metadata]` headers for freshly created parent tables.
+        for depth in 2..pins_path.len() {
+            if let Some(table) = self.get_table(&pins_path[..depth])?.as_table_mut() {
+                if table.is_empty() {
+                    table.set_implicit(true);
+                }
+            }
+        }
+        *toml_key::entry(self.get_table(&pins_path)?, name) = toml_edit::value(reason);
+        Ok(())
+    }
+
+    /// Forget the reason recorded for pinning the dependency `name`.
+    pub fn remove_pin_reason(&mut self, name: &str) {
+        // Indexing mutably would create the tables, so only descend when they exist.
+        if self.pin_reason(name).is_none() {
+            return;
+        }
+        let cargo_edit = &mut self.data["package"]["metadata"][CARGO_EDIT_KEY];
+        if let Some(pins) = cargo_edit["pins"].as_table_mut() {
+            pins.remove(name);
+            if pins.is_empty() {
+                cargo_edit.as_table_mut().map(|t| t.remove("pins"));
+            }
+        }
+    }
+
+    /// Pin the git entry `key` of `[patch.<source>]` to the commit `rev`, keeping its comments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    ///   use cargo_edit::Manifest;
+    ///
+    ///   let mut manifest: Manifest = r#"
+    ///   [patch.crates-io]
+    ///   foo = { git = "https://github.com/foo/foo", rev = "1a2b3c" } # until 1.1 is out
+    ///   bar = { path = "../bar" }
+    ///   "#
+    ///   .parse()
+    ///   .unwrap();
+    ///   manifest.set_patch_rev("crates-io", "foo", "4d5e6f").unwrap();
+    ///   assert!(manifest.data.to_string().contains(
+    ///       r#"foo = { git = "https://github.com/foo/foo", rev = "4d5e6f" } # until 1.1 is out"#
+    ///   ));
+    ///   assert!(manifest.set_patch_rev("crates-io", "bar", "4d5e6f").is_err());
+    /// ```
+    pub fn set_patch_rev(&mut self, source: &str, key: &str, rev: &str) -> Result<()> {
+        // Indexing mutably would create the tables, so only descend when the entry exists.
+        if self.data["patch"][source][key]["git"].as_str().is_none() {
+            return Err(format!("`{}` is not a git entry of `[patch.{}]`", key, source).into());
+        }
+        let entry = &mut self.data["patch"][source][key];
+        set_value_keeping_comments(&mut entry["rev"], toml_edit::Value::from(rev));
+        if let Some(table) = entry.as_inline_table_mut() {
+            table.fmt();
+        }
+        Ok(())
+    }
+
+    /// Remove entry from a Cargo.toml, by its key or, if renamed, the name of its package.
+    ///
+    /// The table is removed as well if it ends up empty.
     ///
     /// # Examples
     ///
@@ -362,30 +760,167 @@ impl Manifest {
     ///
     ///   let mut manifest = Manifest { data: toml_edit::Document::new() };
     ///   let dep = Dependency::new("cargo-edit").set_version("0.1.0");
-    ///   let _ = manifest.insert_into_table(&vec!["dependencies".to_owned()], &dep);
-    ///   assert!(manifest.remove_from_table("dependencies", &dep.name).is_ok());
-    ///   assert!(manifest.remove_from_table("dependencies", &dep.name).is_err());
+    ///   let table = vec!["dependencies".to_owned()];
+    ///   let _ = manifest.insert_into_table(&table, &dep);
+    ///   assert!(manifest.remove_from_table(&table, &dep.name).is_ok());
+    ///   assert!(manifest.remove_from_table(&table, &dep.name).is_err());
     ///   assert!(manifest.data["dependencies"].is_none());
     /// ```
-    pub fn remove_from_table(&mut self, table: &str, name: &str) -> Result<()> {
-        if !self.data[table].is_table_like() {
-            return Err(ErrorKind::NonExistentTable(table.into()).into());
-        } else {
+    pub fn remove_from_table(&mut self, table_path: &[String], name: &str) -> Result<()> {
+        let table_path = &self.resolve_target_path(table_path)?;
+        let key = self.dependency_key(table_path, name)?;
+        let table = self.get_table(table_path)?;
+        // remove the dependency
+        *toml_key::entry(table, &key) = toml_edit::Item::None;
+
+        // remove table if empty, along with any parent tables, like `target.'cfg(unix)'`, that
+        // only existed to hold it
+        for depth in (1..=table_path.len()).rev() {
+            let (parent, key) = table_path[..depth].split_at(depth - 1);
+            let parent = self.get_table(parent)?;
+            if !parent[&key[0]]
+                .as_table_like()
+                .is_some_and(|t| t.is_empty())
             {
-                let dep = &mut self.data[table][name];
-                if dep.is_none() {
-                    return Err(ErrorKind::NonExistentDependency(name.into(), table.into()).into());
-                }
-                // remove the dependency
-                *dep = toml_edit::Item::None;
+                break;
+            }
+            parent[&key[0]] = toml_edit::Item::None;
+        }
+        Ok(())
+    }
+
+    /// Drop the values of `[features]` that refer to the dependency listed under `key`, once it
+    /// was removed.
+    ///
+    /// `dep:key`, `key/feature` and `key?/feature` go when no normal or build dependency is left
+    /// under `key`, and `dep:key` when the one left isn't optional. A bare `key` goes in both
+    /// cases, unless `[features]` defines a feature of that name. Features left without values
+    /// are kept, as other crates may enable them. Returns whether anything was dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    ///   use cargo_edit::Manifest;
+    ///
+    ///   let mut manifest: Manifest = "[features]\nderive = [\"dep:serde\", \"log?/std\"]\n"
+    ///       .parse()
+    ///       .unwrap();
+    ///   assert!(manifest.remove_feature_references("serde"));
+    ///   assert_eq!(manifest.data.to_string(), "[features]\nderive = [\"log?/std\"]\n");
+    /// ```
+    pub fn remove_feature_references(&mut self, key: &str) -> bool {
+        let remaining: Vec<DependencyEntry> = self
+            .dependency_entries()
+            .filter(|entry| {
+                entry.key == key
+                    && !entry.table.workspace
+                    && entry.table.kind != DependencyKind::Dev
+            })
+            .collect();
+        let optional = remaining.iter().any(|entry| {
+            entry
+                .item
+                .as_table_like()
+                .and_then(|t| t.get("optional"))
+                .and_then(toml_edit::Item::as_bool)
+                .unwrap_or(false)
+        });
+        let features = match self.data.root.as_table_mut().and_then(|root| {
+            root.get("features")?;
+            root.entry("features").as_table_mut()
+        }) {
+            Some(features) => features,
+            None => return false,
+        };
+        let defined = features.contains_key(key);
+        let stale = |value: &FeatureValue| match value {
+            FeatureValue::Feature(feature) => feature == key && !defined && !optional,
+            FeatureValue::Dep(dep) => dep == key && !optional,
+            FeatureValue::DepFeature { dep, .. } => dep == key && remaining.is_empty(),
+        };
+
+        let names: Vec<String> = features.iter().map(|(name, _)| name.to_owned()).collect();
+        let mut removed = false;
+        for name in names {
+            if let Some(values) = features.entry(&quote_key(&name)).as_array_mut() {
+                removed |= remove_array_values(values, |value| {
+                    value
+                        .as_str()
+                        .map(FeatureValue::from)
+                        .as_ref()
+                        .is_some_and(stale)
+                });
             }
+        }
+        removed
+    }
+
+    /// Get a dependency by name (matching on package name for renamed deps).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    ///   use cargo_edit::{Dependency, Manifest};
+    ///
+    ///   let manifest: Manifest = "[dependencies]\nalias = { version = \"0.1\", package = \"dep\" }"
+    ///       .parse()
+    ///       .unwrap();
+    ///   let dep = manifest.get_dependency(&["dependencies".to_owned()], "dep").unwrap();
+    ///   assert_eq!(dep, Dependency::new("dep").set_version("0.1").set_rename("alias"));
+    /// ```
+    pub fn get_dependency(&self, table_path: &[String], name: &str) -> Result<Dependency> {
+        let key = self.dependency_key(table_path, name)?;
+        let table = self.existing_table(table_path)?;
+        table
+            .get(&key)
+            .and_then(|item| Dependency::from_toml(&key, item))
+            .chain_err(|| ErrorKind::NonExistentDependency(name.into(), table_path.join(".")))
+    }
 
-            // remove table if empty
-            if self.data[table].as_table_like().unwrap().is_empty() {
-                self.data[table] = toml_edit::Item::None;
+    /// The key of the dependency `name` in the given table: `name` itself if there is an entry
+    /// under it, or else the key of the one entry renamed from the package `name`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    ///   use cargo_edit::Manifest;
+    ///
+    ///   let manifest: Manifest = "[dependencies]\nalias = { version = \"0.1\", package = \"dep\" }"
+    ///       .parse()
+    ///       .unwrap();
+    ///   let table = ["dependencies".to_owned()];
+    ///   assert_eq!(manifest.dependency_key(&table, "dep").unwrap(), "alias");
+    ///   assert_eq!(manifest.dependency_key(&table, "alias").unwrap(), "alias");
+    /// ```
+    pub fn dependency_key(&self, table_path: &[String], name: &str) -> Result<String> {
+        let table = self.existing_table(table_path)?;
+        if table.get(name).is_some_and(|item| !item.is_none()) {
+            return Ok(name.to_owned());
+        }
+        let keys: Vec<String> = table
+            .iter()
+            .filter_map(|(key, item)| Dependency::from_toml(key, item))
+            .filter(|dep| dep.name == name)
+            .map(|dep| dep.name_in_manifest().to_owned())
+            .collect();
+        match keys.len() {
+            0 => Err(ErrorKind::NonExistentDependency(name.into(), table_path.join(".")).into()),
+            1 => Ok(keys.into_iter().next().expect("one key")),
+            _ => {
+                Err(ErrorKind::AmbiguousDependency(name.into(), table_path.join("."), keys).into())
             }
         }
-        Ok(())
+    }
+
+    /// The table at `table_path`, without creating it like `get_table` would.
+    fn existing_table(&self, table_path: &[String]) -> Result<&dyn toml_edit::TableLike> {
+        self.resolve_target_path(table_path)?
+            .iter()
+            .try_fold(&self.data.root, |item, key| {
+                item.as_table_like().and_then(|t| t.get(key))
+            })
+            .and_then(toml_edit::Item::as_table_like)
+            .chain_err(|| ErrorKind::NonExistentTable(table_path.join(".")))
     }
 
     /// Add multiple dependencies to manifest
@@ -424,12 +959,35 @@ impl Manifest {
     }
 }
 
+/// Remove the values of `array` for which `remove` is true, giving the values after them the
+/// formatting of those removed, so that e.g. `[ "b"]` isn't left after removing `"a"`.
+fn remove_array_values(
+    array: &mut toml_edit::Array,
+    remove: impl Fn(&toml_edit::Value) -> bool,
+) -> bool {
+    let mut removed = false;
+    for index in (0..array.len()).rev() {
+        if !array.get(index).is_some_and(&remove) {
+            continue;
+        }
+        let old = array.remove(index);
+        removed = true;
+        if let Some(next) = array.get(index) {
+            let next =
+                toml_edit::decorated(next.clone(), old.decor().prefix(), next.decor().suffix());
+            // Values of the same array all have the same type.
+            let _ = array.replace_formatted(index, next);
+        }
+    }
+    removed
+}
+
 impl str::FromStr for Manifest {
     type Err = Error;
 
     /// Read manifest data from string
     fn from_str(input: &str) -> ::std::result::Result<Self, Self::Err> {
-        let d: toml_edit::Document = input.parse().chain_err(|| "Manifest not valid TOML")?;
+        let d = parse_document(input)?;
 
         Ok(Manifest { data: d })
     }
@@ -442,6 +1000,10 @@ pub struct LocalManifest {
     pub path: PathBuf,
     /// Manifest contents
     manifest: Manifest,
+    /// The formatting configured for the manifest
+    style: StyleConfig,
+    /// Whether upgrades are made without printing them
+    quiet: bool,
 }
 
 impl Deref for LocalManifest {
@@ -465,31 +1027,64 @@ impl LocalManifest {
         let path = path.to_path_buf();
         Ok(LocalManifest {
             manifest: Manifest::open(&Some(path.clone()))?,
+            style: StyleConfig::for_manifest(&path)?,
             path,
+            quiet: false,
         })
     }
 
-    /// Get the `File` corresponding to this manifest.
-    fn get_file(&self) -> Result<File> {
-        Manifest::find_file(&Some(self.path.clone()))
+    /// Upgrade dependencies without printing an `Upgrading` line for each, e.g. when the changes
+    /// are reported as a whole with a [`Report`](crate::Report).
+    pub fn set_quiet(&mut self, quiet: bool) -> &mut Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Construct the `LocalManifest`s of `paths` concurrently, returning them in the same order.
+    pub fn open_all(paths: &[PathBuf]) -> Result<Vec<Self>> {
+        crate::parallel::try_map(paths, |path| Self::try_new(path))
     }
 
     /// Instruct this manifest to upgrade a single dependency. If this manifest does not have that
     /// dependency, it does nothing.
+    ///
+    /// Entries are matched by the name of their package, so that renamed entries are upgraded
+    /// too, unless `dependency` is renamed itself: then only the entry under its key is.
     pub fn upgrade(
         &mut self,
         dependency: &Dependency,
         dry_run: bool,
         skip_compatible: bool,
+    ) -> Result<()> {
+        self.upgrade_in_memory(dependency, dry_run, skip_compatible)?;
+
+        self.write_to_path_with_style(&self.path, &self.style)
+            .chain_err(|| "Failed to write new manifest contents")
+    }
+
+    /// Like [`LocalManifest::upgrade`], but without writing the manifest, e.g. to
+    /// [stage](LocalManifest::stage) it with others.
+    pub fn upgrade_in_memory(
+        &mut self,
+        dependency: &Dependency,
+        dry_run: bool,
+        skip_compatible: bool,
     ) -> Result<()> {
         for (table_path, table) in self.get_sections() {
             let table_like = table.as_table_like().expect("Unexpected non-table");
             for (name, toml_item) in table_like.iter() {
+                // Inherited entries are upgraded in `[workspace.dependencies]` of the root.
+                let inherited = toml_item["workspace"].as_bool() == Some(true);
+                if inherited {
+                    continue;
+                }
                 let dep_name = toml_item
                     .as_table_like()
                     .and_then(|t| t.get("package").and_then(|p| p.as_str()))
                     .unwrap_or(name);
-                if dep_name == dependency.name {
+                // A renamed dependency only upgrades the entry under its key.
+                let key_matches = dependency.rename().is_none_or(|rename| rename == name);
+                if dep_name == dependency.name && key_matches {
                     if skip_compatible {
                         if let Some(old_version) = get_version(toml_item)?.as_str() {
                             if old_version_compatible(dependency, old_version)? {
@@ -497,27 +1092,126 @@ impl LocalManifest {
                             }
                         }
                     }
-                    self.manifest.update_table_named_entry(
+                    self.manifest.merge_table_named_entry(
                         &table_path,
-                        &name,
+                        name,
                         dependency,
                         dry_run,
+                        self.quiet,
                     )?;
                 }
             }
         }
+        Ok(())
+    }
 
-        let mut file = self.get_file()?;
-        self.write_to_file(&mut file)
-            .chain_err(|| "Failed to write new manifest contents")
+    /// Like [`Manifest::set_patch_rev`], without writing the manifest.
+    pub fn set_patch_rev(&mut self, source: &str, key: &str, rev: &str) -> Result<()> {
+        self.manifest.set_patch_rev(source, key, rev)
+    }
+
+    /// The manifest as [`LocalManifest::stage`] would write it, formatted with its configured style.
+    pub fn to_formatted_string(&self) -> Result<String> {
+        self.manifest.to_string_with_style(&self.style)
+    }
+
+    /// Stage this manifest, formatted with its configured style, to be written with `transaction`.
+    pub fn stage(&self, transaction: &mut ManifestTransaction) -> Result<()> {
+        transaction.stage_with_style(&self.path, &self.manifest, &self.style)
     }
 }
 
+/// Query crate name by accessing Cargo.toml in a local path
+///
+/// The name will be returned as a string. This will fail, when
+/// Cargo.toml is not present in the root of the path.
+pub fn get_crate_name_from_path(path: &str) -> Result<String> {
+    let cargo_file = Path::new(path).join("Cargo.toml");
+    Manifest::open(&Some(cargo_file))
+        .chain_err(|| "Unable to open local Cargo.toml")
+        .and_then(|ref manifest| get_name_from_manifest(manifest))
+}
+
+/// Query the version of the crate in a local path, e.g. to replace a path dependency by the
+/// published release of the same version.
+///
+/// A version inherited with `version = { workspace = true }` is read from `[workspace.package]`
+/// of the workspace root.
+///
+/// This fails when Cargo.toml is not present in the root of the path or has no valid version.
+pub fn get_crate_version_from_path(path: &str) -> Result<Version> {
+    let cargo_file = Path::new(path).join("Cargo.toml");
+    let manifest = Manifest::open(&Some(cargo_file.clone()))
+        .chain_err(|| "Unable to open local Cargo.toml")?;
+    let package = manifest.data["package"]
+        .as_table()
+        .ok_or(ErrorKind::ParseCargoToml)?;
+    let root = match workspace::find_workspace_root(&cargo_file) {
+        Ok(root) if package["version"].is_table_like() => {
+            Manifest::open(&Some(root)).chain_err(|| "Unable to open the workspace Cargo.toml")?
+        }
+        _ => manifest.clone(),
+    };
+    let version = workspace::package_version(package, &root).ok_or(ErrorKind::ParseCargoToml)?;
+    Version::parse(&version).chain_err(|| ErrorKind::ParseCargoToml)
+}
+
+/// Query the `rust-version` of the package with the manifest at `manifest_path`, e.g. to only
+/// upgrade its dependencies to releases still supporting it.
+///
+/// A `rust-version` inherited with `rust-version = { workspace = true }` is read from
+/// `[workspace.package]` of the workspace root, which is also where it is read from for a virtual
+/// manifest. This is `None` when no `rust-version` is declared.
+pub fn get_rust_version_from_manifest(manifest_path: &Path) -> Result<Option<RustVersion>> {
+    let manifest = Manifest::open(&Some(manifest_path.to_owned()))?;
+    let rust_version = match manifest.data["package"].as_table() {
+        Some(package) if package["rust-version"].is_table_like() => {
+            let root = workspace::find_workspace_root(manifest_path)?;
+            let root = Manifest::open(&Some(root))
+                .chain_err(|| "Unable to open the workspace Cargo.toml")?;
+            workspace::package_field(package, &root, "rust-version")
+        }
+        Some(package) => workspace::package_field(package, &manifest, "rust-version"),
+        None => manifest.data["workspace"]["package"]["rust-version"]
+            .as_str()
+            .map(String::from),
+    };
+    rust_version
+        .map(|rust_version| {
+            rust_version
+                .parse()
+                .chain_err(|| format!("Invalid `rust-version` in {}", manifest_path.display()))
+        })
+        .transpose()
+}
+
+pub(crate) fn get_name_from_manifest(manifest: &Manifest) -> Result<String> {
+    manifest
+        .data
+        .as_table()
+        .get("package")
+        .and_then(|m| m["name"].as_str().map(ToString::to_string))
+        .ok_or_else(|| ErrorKind::ParseCargoToml.into())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::dependency::Dependency;
-    use toml_edit;
+
+    #[test]
+    fn parse_error_location() {
+        let err = "[package]\nname = \"foo\"\nversion = 0.1.0\n"
+            .parse::<Manifest>()
+            .unwrap_err();
+        match err.kind() {
+            ErrorKind::ManifestParse(message, line, column) => {
+                assert_eq!((*line, *column), (Some(3), Some(14)));
+                assert!(message.starts_with("TOML parse error at line 3"));
+            }
+            kind => panic!("unexpected error {:?}", kind),
+        }
+    }
 
     #[test]
     fn add_remove_dependency() {
@@ -528,7 +1222,7 @@ mod tests {
         let dep = Dependency::new("cargo-edit").set_version("0.1.0");
         let _ = manifest.insert_into_table(&["dependencies".to_owned()], &dep);
         assert!(manifest
-            .remove_from_table("dependencies", &dep.name)
+            .remove_from_table(&["dependencies".to_owned()], &dep.name)
             .is_ok());
         assert_eq!(manifest.data.to_string(), clone.data.to_string());
     }
@@ -568,6 +1262,176 @@ mod tests {
         assert_eq!(manifest.data.to_string(), original.data.to_string());
     }
 
+    #[test]
+    fn set_dependency_version_keeps_other_keys() {
+        let mut manifest: Manifest = r#"
+[dependencies]
+plain = "0.1"
+table = { version = "0.1", features = ["foo"] }
+"#
+        .parse()
+        .unwrap();
+        let table = ["dependencies".to_owned()];
+        manifest
+            .set_table_entry_version(&table, "plain", "=0.1.5")
+            .unwrap();
+        manifest
+            .set_table_entry_version(&table, "table", "=0.1.5")
+            .unwrap();
+        assert!(manifest
+            .set_table_entry_version(&table, "missing", "=0.1.5")
+            .is_err());
+
+        assert_eq!(
+            manifest.data["dependencies"]["plain"].as_str(),
+            Some("=0.1.5")
+        );
+        let entry = manifest.data["dependencies"]["table"]
+            .as_inline_table()
+            .unwrap();
+        assert_eq!(entry.get("version").unwrap().as_str(), Some("=0.1.5"));
+        assert!(entry.get("features").is_some());
+    }
+
+    #[test]
+    fn switching_to_and_from_inherited_dependencies() {
+        let mut manifest: Manifest = r#"
+[dependencies]
+serde = { version = "0.9", registry = "mirror", features = ["derive"] }
+"#
+        .parse()
+        .unwrap();
+        let table = ["dependencies".to_owned()];
+        manifest
+            .insert_into_table(&table, &Dependency::new("serde").set_workspace())
+            .unwrap();
+        assert_eq!(
+            manifest.data["dependencies"]["serde"]
+                .as_value()
+                .unwrap()
+                .to_string(),
+            r#" { features = ["derive"], workspace = true }"#
+        );
+
+        manifest
+            .insert_into_table(&table, &Dependency::new("serde").set_version("1.0"))
+            .unwrap();
+        assert_eq!(
+            manifest.data["dependencies"]["serde"]
+                .as_value()
+                .unwrap()
+                .to_string(),
+            r#" { version = "1.0", features = ["derive"] }"#
+        );
+    }
+
+    #[test]
+    fn update_dependency_keeps_comments() {
+        let mut manifest: Manifest = r#"[dependencies]
+# Parsing
+docopt = "0.8" # keep in sync with the CLI
+serde = { version = "1.0", features = ["derive"] } # serialization
+
+# Only a version
+[dependencies.rand]
+version = "0.3" # random
+"#
+        .parse()
+        .unwrap();
+        let table = ["dependencies".to_owned()];
+        for dep in &[
+            Dependency::new("docopt").set_version("0.9"),
+            Dependency::new("serde").set_version("1.0.130"),
+            Dependency::new("rand").set_version("0.8"),
+        ] {
+            manifest.update_table_entry(&table, dep, false).unwrap();
+        }
+
+        assert_eq!(
+            manifest.data.to_string(),
+            r#"[dependencies]
+# Parsing
+docopt = "0.9" # keep in sync with the CLI
+serde = { version = "1.0.130", features = ["derive"] } # serialization
+
+# Only a version
+[dependencies.rand]
+version = "0.8" # random
+"#
+        );
+    }
+
+    #[test]
+    fn plain_versions_needing_more_keys_follow_the_table_style() {
+        let mut manifest: Manifest = r#"[dependencies]
+docopt = "0.8" # docopt
+# Random numbers
+rand = "0.7"
+
+[dependencies.serde]
+version = "1.0"
+features = ["derive"]
+"#
+        .parse()
+        .unwrap();
+        let table = ["dependencies".to_owned()];
+        let style = StyleConfig::default();
+        for dep in &[
+            Dependency::new("docopt")
+                .set_version("0.9")
+                .set_features(Some(vec!["a".to_owned()])),
+            Dependency::new("rand")
+                .set_version("0.8")
+                .set_features(Some(vec!["b".to_owned()])),
+        ] {
+            manifest
+                .insert_into_table_with_style(&table, dep, &style)
+                .unwrap();
+        }
+
+        assert_eq!(
+            manifest.data.to_string(),
+            r#"[dependencies]
+# Random numbers
+rand = { version = "0.8", features = ["b"] }
+
+[dependencies.docopt]
+version = "0.9" # docopt
+features = ["a"]
+
+[dependencies.serde]
+version = "1.0"
+features = ["derive"]
+"#
+        );
+    }
+
+    #[test]
+    fn pin_reasons_round_trip() {
+        let mut manifest: Manifest = r#"[package]
+name = "foo"
+version = "0.1.0"
+"#
+        .parse()
+        .unwrap();
+        assert_eq!(manifest.pin_reason("rand"), None);
+
+        manifest
+            .set_pin_reason("rand", "RUSTSEC-2020-0001")
+            .unwrap();
+        assert_eq!(manifest.pin_reason("rand"), Some("RUSTSEC-2020-0001"));
+        assert!(manifest
+            .data
+            .to_string()
+            .contains("[package.metadata.cargo-edit.pins]\nrand = \"RUSTSEC-2020-0001\""));
+        assert!(!manifest.data.to_string().contains("[package.metadata]"));
+
+        manifest.remove_pin_reason("rand");
+        manifest.remove_pin_reason("libc");
+        assert_eq!(manifest.pin_reason("rand"), None);
+        assert!(!manifest.data.to_string().contains("pins"));
+    }
+
     #[test]
     fn remove_dependency_no_section() {
         let mut manifest = Manifest {
@@ -575,7 +1439,7 @@ mod tests {
         };
         let dep = Dependency::new("cargo-edit").set_version("0.1.0");
         assert!(manifest
-            .remove_from_table("dependencies", &dep.name)
+            .remove_from_table(&["dependencies".to_owned()], &dep.name)
             .is_err());
     }
 
@@ -588,8 +1452,160 @@ mod tests {
         let other_dep = Dependency::new("other-dep").set_version("0.1.0");
         let _ = manifest.insert_into_table(&["dependencies".to_owned()], &other_dep);
         assert!(manifest
-            .remove_from_table("dependencies", &dep.name)
+            .remove_from_table(&["dependencies".to_owned()], &dep.name)
+            .is_err());
+    }
+
+    #[test]
+    fn remove_target_dependency() {
+        let mut manifest: Manifest = r#"[package]
+name = "foo"
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+"#
+        .parse()
+        .unwrap();
+        let table = [
+            "target".to_owned(),
+            "cfg(unix)".to_owned(),
+            "dependencies".to_owned(),
+        ];
+        assert_eq!(
+            manifest.get_dependency(&table, "libc").unwrap(),
+            Dependency::new("libc").set_version("0.2")
+        );
+        manifest.remove_from_table(&table, "libc").unwrap();
+        assert!(manifest.get_dependency(&table, "libc").is_err());
+        assert_eq!(manifest.data.to_string(), "[package]\nname = \"foo\"\n");
+    }
+
+    #[test]
+    fn target_tables_are_matched_by_platform() {
+        let mut manifest: Manifest = "[package]\nname = \"foo\"\n".parse().unwrap();
+        let table = |platform: &str| {
+            vec![
+                "target".to_owned(),
+                platform.to_owned(),
+                "dependencies".to_owned(),
+            ]
+        };
+        let dep = |name: &str| Dependency::new(name).set_version("1");
+        manifest
+            .insert_into_table(&table(r#"cfg(target_os="linux")"#), &dep("a"))
+            .unwrap();
+        manifest
+            .insert_into_table(&table(r#"cfg(all( target_os = "linux" ))"#), &dep("b"))
+            .unwrap();
+        assert!(manifest
+            .insert_into_table(&table("cfg(unix"), &dep("c"))
             .is_err());
+        assert_eq!(
+            manifest.data.to_string(),
+            r#"[package]
+name = "foo"
+
+[target.'cfg(target_os = "linux")'.dependencies]
+a = "1"
+b = "1"
+"#
+        );
+
+        manifest
+            .remove_from_table(&table(r#"cfg( target_os = "linux" )"#), "a")
+            .unwrap();
+        assert!(manifest
+            .get_dependency(&table(r#"cfg(target_os="linux")"#), "b")
+            .is_ok());
+    }
+
+    #[test]
+    fn artifact_dependencies_keep_their_keys() {
+        let mut manifest: Manifest = r#"[dependencies]
+tool = { version = "0.1", artifact = ["bin"], target = "wasm32-wasi", lib = true }
+alias = { version = "0.1", package = "runner", artifact = "bin" }
+"#
+        .parse()
+        .unwrap();
+        let table = ["dependencies".to_owned()];
+        let tool = manifest.get_dependency(&table, "tool").unwrap();
+        assert_eq!(tool.artifact(), Some(&["bin".to_owned()][..]));
+        manifest.insert_into_table(&table, &tool).unwrap();
+        manifest
+            .insert_into_table(&table, &tool.set_version("0.2"))
+            .unwrap();
+        manifest
+            .insert_into_table(&table, &Dependency::new("runner").set_version("0.2"))
+            .unwrap();
+        assert_eq!(
+            manifest.data.to_string(),
+            r#"[dependencies]
+tool = { version = "0.2", artifact = ["bin"], target = "wasm32-wasi", lib = true }
+runner = { version = "0.2", artifact = "bin" }
+"#
+        );
+    }
+
+    #[test]
+    fn renamed_dependencies_by_key_or_package() {
+        let mut manifest: Manifest = r#"[dependencies]
+rand07 = { version = "0.7", package = "rand" }
+rand08 = { version = "0.8", package = "rand" }
+log = "0.4"
+"#
+        .parse()
+        .unwrap();
+        let table = ["dependencies".to_owned()];
+        assert_eq!(manifest.dependency_key(&table, "rand08").unwrap(), "rand08");
+        assert_eq!(manifest.dependency_key(&table, "log").unwrap(), "log");
+        let err = manifest.remove_from_table(&table, "rand").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Several dependencies in `dependencies` are on the package `rand`: rand07, rand08. \
+             Pick one by its key."
+        );
+
+        manifest.remove_from_table(&table, "rand07").unwrap();
+        manifest.remove_from_table(&table, "rand").unwrap();
+        assert_eq!(manifest.data.to_string(), "[dependencies]\nlog = \"0.4\"\n");
+    }
+
+    #[test]
+    fn remove_feature_references_keeps_other_values() {
+        let mut manifest: Manifest = r#"[dependencies]
+log = { version = "0.4", optional = true }
+
+[dev-dependencies]
+serde = "1"
+
+[features]
+derive = ["dep:serde", "serde?/derive", "log?/std"]
+json = [
+    "serde/std",
+    "dep:log",
+]
+serde = []
+"#
+        .parse()
+        .unwrap();
+        assert!(manifest.remove_feature_references("serde"));
+        assert!(!manifest.remove_feature_references("log"));
+        assert_eq!(
+            manifest.data.to_string(),
+            r#"[dependencies]
+log = { version = "0.4", optional = true }
+
+[dev-dependencies]
+serde = "1"
+
+[features]
+derive = ["log?/std"]
+json = [
+    "dep:log",
+]
+serde = []
+"#
+        );
     }
 
     #[test]
@@ -0,0 +1,224 @@
+//! Keeping concurrent invocations, e.g. an editor extension racing a terminal command, from
+//! interleaving their edits of the same workspace.
+//!
+//! Each edit reads manifests, changes them and writes them back. Two processes doing so at once
+//! would each write their own version, silently dropping the other's changes, so edits take an
+//! advisory lock on the workspace first. The lock files live in
+//! `$CARGO_HOME/cargo-edit/locks`, one for each workspace root, and hold the PID of the process
+//! holding them, to tell who is being waited for.
+use crate::cancel::is_cancelled;
+use crate::errors::*;
+use crate::registry::cargo_home;
+use crate::verbosity::verbosity;
+use crate::workspace::find_workspace_root;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to wait for another process to release the lock before giving up.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often to check whether the lock was released.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An advisory lock on the manifests of a workspace, held until dropped.
+///
+/// Other cargo-edit processes taking the lock for the same workspace wait for it; processes
+/// ignoring it, like editors, are not held back.
+///
+/// # Examples
+///
+/// ```no_run
+///   use cargo_edit::{LocalManifest, ManifestLock};
+///   use std::path::Path;
+///
+///   let path = Path::new("Cargo.toml");
+///   let _lock = ManifestLock::acquire(path).unwrap();
+///   let mut manifest = LocalManifest::try_new(path).unwrap();
+///   // ... edit and write the manifest, then drop the lock
+/// ```
+#[derive(Debug)]
+pub struct ManifestLock {
+    /// The locked file, or `None` where locking isn't possible.
+    file: Option<File>,
+}
+
+impl ManifestLock {
+    /// Lock the workspace of the manifest at `manifest_path`, waiting up to 30 seconds for
+    /// another process holding it.
+    ///
+    /// Where no lock file can be created, e.g. as `$CARGO_HOME` is read-only, this warns and goes
+    /// on without a lock.
+    pub fn acquire(manifest_path: &Path) -> Result<Self> {
+        let root = find_workspace_root(manifest_path).unwrap_or_else(|_| manifest_path.to_owned());
+        let lock_path = match cargo_home() {
+            Ok(cargo_home) => lock_path(&cargo_home, &root),
+            Err(err) => return Ok(unlocked(&root, &err)),
+        };
+        acquire_within(&lock_path, &root, LOCK_TIMEOUT)
+    }
+
+    /// Whether the lock is held, rather than going on without one where locking isn't possible.
+    pub fn is_locked(&self) -> bool {
+        self.file.is_some()
+    }
+}
+
+/// Lock the file at `lock_path` on behalf of the workspace at `root`, waiting up to `timeout`.
+fn acquire_within(lock_path: &Path, root: &Path, timeout: Duration) -> Result<ManifestLock> {
+    let _span = span!(DEBUG, "lock_workspace", root = %root.display());
+    let opened = lock_path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| {
+            // Not truncated, as that would erase the PID of the process holding it.
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(lock_path)
+        });
+    let mut file = match opened {
+        Ok(file) => file,
+        Err(err) => return Ok(unlocked(root, &err)),
+    };
+
+    let deadline = Instant::now() + timeout;
+    let mut waiting = false;
+    loop {
+        match file.try_lock() {
+            Ok(()) => break,
+            Err(TryLockError::WouldBlock) => {}
+            Err(TryLockError::Error(err)) if err.kind() == std::io::ErrorKind::Unsupported => {
+                event!(DEBUG, "file locking is not supported");
+                return Ok(ManifestLock { file: None });
+            }
+            Err(TryLockError::Error(err)) => {
+                return Err(Error::with_chain(
+                    err,
+                    format!("Failed to lock {}", lock_path.display()),
+                ))
+            }
+        }
+        let holder = holder(lock_path);
+        if !waiting && !verbosity().is_quiet() {
+            eprintln!(
+                "    Blocking waiting for the lock on {}{}",
+                root.display(),
+                held_by(holder)
+            );
+        }
+        waiting = true;
+        if is_cancelled() {
+            return Err(ErrorKind::Interrupted.into());
+        }
+        if Instant::now() >= deadline {
+            return Err(ErrorKind::ManifestLocked(root.to_owned(), holder).into());
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    // Best effort: the PID only makes the message of a waiting process more helpful.
+    let _ = file
+        .set_len(0)
+        .and_then(|()| file.seek(SeekFrom::Start(0)))
+        .and_then(|_| write!(file, "{}", std::process::id()))
+        .and_then(|()| file.flush());
+    Ok(ManifestLock { file: Some(file) })
+}
+
+/// Where the lock of the workspace at `root` lives.
+fn lock_path(cargo_home: &Path, root: &Path) -> PathBuf {
+    #![allow(deprecated)]
+    use std::hash::{Hash, Hasher, SipHasher};
+
+    let root = crate::logical_path::identity(root);
+    // A hash which does not change between builds, so that all versions share the lock.
+    let mut hasher = SipHasher::new();
+    root.hash(&mut hasher);
+    let hash = hex::encode(hasher.finish().to_le_bytes());
+    let name = root
+        .parent()
+        .and_then(Path::file_name)
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    cargo_home
+        .join("cargo-edit")
+        .join("locks")
+        .join(format!("{}-{}.lock", name, hash))
+}
+
+/// The PID the process holding the lock at `lock_path` wrote into it.
+pub(crate) fn holder(lock_path: &Path) -> Option<u32> {
+    let mut pid = String::new();
+    File::open(lock_path).ok()?.read_to_string(&mut pid).ok()?;
+    pid.trim().parse().ok()
+}
+
+/// The end of a message about the lock, naming the process holding it if known.
+pub(crate) fn held_by(holder: Option<u32>) -> String {
+    holder
+        .map(|pid| format!(" held by PID {}", pid))
+        .unwrap_or_default()
+}
+
+fn unlocked(root: &Path, err: &dyn std::fmt::Display) -> ManifestLock {
+    if !verbosity().is_quiet() {
+        eprintln!(
+            "WARN: Editing {} without a lock, as none could be created: {}",
+            root.display(),
+            err
+        );
+    }
+    ManifestLock { file: None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_for_the_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock_path = dir.path().join("locks/ws.lock");
+        let root = dir.path().join("Cargo.toml");
+
+        let lock = acquire_within(&lock_path, &root, Duration::ZERO).unwrap();
+        assert_eq!(holder(&lock_path), Some(std::process::id()));
+        match acquire_within(&lock_path, &root, Duration::ZERO) {
+            Err(err) => match err.kind() {
+                ErrorKind::ManifestLocked(path, pid) => {
+                    assert_eq!(path, &root);
+                    assert_eq!(*pid, Some(std::process::id()));
+                }
+                kind => panic!("unexpected error {:?}", kind),
+            },
+            Ok(_) => panic!("locked twice"),
+        }
+
+        let waiting = {
+            let (lock_path, root) = (lock_path.clone(), root.clone());
+            thread::spawn(move || acquire_within(&lock_path, &root, Duration::from_secs(10)))
+        };
+        thread::sleep(POLL_INTERVAL * 2);
+        drop(lock);
+        assert!(waiting.join().unwrap().unwrap().is_locked());
+    }
+
+    #[test]
+    fn one_lock_per_workspace() {
+        let home = Path::new("/home/me/.cargo");
+        let lock = lock_path(home, Path::new("/work/app/Cargo.toml"));
+        assert!(lock.starts_with(home.join("cargo-edit/locks")));
+        assert!(lock
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("app-"));
+        assert_eq!(lock, lock_path(home, Path::new("/work/app/Cargo.toml")));
+        assert_ne!(lock, lock_path(home, Path::new("/work/lib/Cargo.toml")));
+    }
+}
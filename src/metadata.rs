@@ -1,18 +1,24 @@
+use crate::cargo_flags::cargo_options;
 use crate::errors::*;
+use crate::PackageIdSpec;
 use cargo_metadata::Package;
 use failure::Fail;
 
 /// Takes a pkgid and attempts to find the path to it's `Cargo.toml`, using `cargo`'s metadata
+///
+/// The pkgid may be a package name or any package ID spec, e.g. `foo@1.2`.
 pub fn manifest_from_pkgid(pkgid: &str) -> Result<Package> {
+    let spec = PackageIdSpec::parse(pkgid)?;
     let mut cmd = cargo_metadata::MetadataCommand::new();
     cmd.no_deps();
+    cmd.other_options(cargo_options(Vec::new()));
     let result = cmd
         .exec()
         .map_err(|e| Error::from(e.compat()).chain_err(|| "Invalid manifest"))?;
     let packages = result.packages;
     let package = packages
         .into_iter()
-        .find(|pkg| pkg.name == pkgid)
+        .find(|pkg| spec.matches(&pkg.name, &pkg.version))
         .chain_err(|| {
             "Found virtual manifest, but this command requires running against an \
              actual package in this workspace. Try adding `--workspace`."
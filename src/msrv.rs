@@ -0,0 +1,168 @@
+//! How far dependencies can be upgraded without raising the minimum supported Rust version.
+use crate::errors::*;
+use crate::fetch::fuzzy_query;
+use crate::index::{CrateVersionInfo, IndexSource};
+use crate::policy::{Latest, MsrvCompatible, VersionPolicy};
+use crate::rust_version::RustVersion;
+#[cfg(feature = "fetch")]
+use {crate::fetch::resolve_registry, crate::index::open_index, std::path::Path, url::Url};
+
+/// The newest versions of a crate with and without regard to a Rust version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsrvCompatibility {
+    /// The name of the crate, as published
+    pub name: String,
+    /// The newest version supporting the Rust version, if any does
+    pub compatible: Option<CrateVersionInfo>,
+    /// The newest version overall
+    pub latest: CrateVersionInfo,
+}
+
+impl MsrvCompatibility {
+    /// Whether the newest version requires a newer Rust than the one asked about.
+    pub fn is_held_back(&self) -> bool {
+        self.compatible
+            .as_ref()
+            .is_none_or(|compatible| compatible.version != self.latest.version)
+    }
+}
+
+/// Find the newest versions of several crates supporting Rust `rust_version`, as well as their
+/// newest versions overall, from a registry index
+///
+/// Versions without a declared `rust-version` count as compatible, unless `strict` is set. The
+/// results are returned in the order of `crate_names`; each lookup can fail for the same reasons as
+/// [`get_latest_dependency`](crate::get_latest_dependency).
+#[cfg(feature = "fetch")]
+pub fn get_msrv_compatibility(
+    crate_names: &[&str],
+    rust_version: &RustVersion,
+    flag_allow_prerelease: bool,
+    strict: bool,
+    manifest_path: &Path,
+    registry: &Option<Url>,
+) -> Vec<Result<MsrvCompatibility>> {
+    match resolve_registry(manifest_path, registry).and_then(|url| open_index(manifest_path, &url))
+    {
+        Ok(index) => get_msrv_compatibility_from(
+            &*index,
+            crate_names,
+            rust_version,
+            flag_allow_prerelease,
+            strict,
+        ),
+        Err(err) => crate_names.iter().map(|_| Err(err.duplicate())).collect(),
+    }
+}
+
+/// Find the newest versions of several crates supporting Rust `rust_version` in any index source
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{get_msrv_compatibility_from, CrateVersionInfo, InMemoryIndex};
+///
+///   let mut index = InMemoryIndex::new();
+///   for (version, rust_version) in &[("1.0.0", "1.56"), ("1.1.0", "1.70")] {
+///       let mut info = CrateVersionInfo::new("foo", semver::Version::parse(version).unwrap());
+///       info.rust_version = Some(rust_version.parse().unwrap());
+///       index.insert(info);
+///   }
+///
+///   let msrv = "1.65".parse().unwrap();
+///   let report = get_msrv_compatibility_from(&index, &["foo"], &msrv, false, false);
+///   let foo = report[0].as_ref().unwrap();
+///   assert_eq!(foo.compatible.as_ref().unwrap().version.to_string(), "1.0.0");
+///   assert_eq!(foo.latest.version.to_string(), "1.1.0");
+///   assert!(foo.is_held_back());
+/// ```
+pub fn get_msrv_compatibility_from(
+    index: &dyn IndexSource,
+    crate_names: &[&str],
+    rust_version: &RustVersion,
+    flag_allow_prerelease: bool,
+    strict: bool,
+) -> Vec<Result<MsrvCompatibility>> {
+    let latest = Latest {
+        allow_prerelease: flag_allow_prerelease,
+    };
+    let compatible = MsrvCompatible::new(latest, rust_version.to_version()).strict(strict);
+    crate_names
+        .iter()
+        .map(|name| {
+            let versions = fuzzy_query(index, name)?;
+            let newest = latest.select(&versions)?.clone();
+            let supported = match compatible.select(&versions) {
+                Ok(supported) => Some(supported.clone()),
+                Err(err) => match err.kind() {
+                    ErrorKind::IncompatibleRustVersion(..) | ErrorKind::UnknownRustVersion(..) => {
+                        None
+                    }
+                    _ => return Err(err),
+                },
+            };
+            Ok(MsrvCompatibility {
+                name: newest.name.clone(),
+                compatible: supported,
+                latest: newest,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InMemoryIndex;
+
+    fn release(name: &str, version: &str, rust_version: Option<&str>) -> CrateVersionInfo {
+        let mut info = CrateVersionInfo::new(name, semver::Version::parse(version).unwrap());
+        info.rust_version = rust_version.map(|v| v.parse().unwrap());
+        info
+    }
+
+    #[test]
+    fn reports_compatible_and_latest_versions() {
+        let mut index = InMemoryIndex::new();
+        index.insert(release("old", "0.1.0", None));
+        index.insert(release("new", "2.0.0", Some("1.80")));
+        index.insert(release("mixed", "1.0.0", Some("1.60")));
+        index.insert(release("mixed", "1.1.0", Some("1.60.1")));
+        index.insert(release("mixed", "1.2.0", Some("1.75")));
+
+        let msrv = "1.60".parse().unwrap();
+        let report = get_msrv_compatibility_from(
+            &index,
+            &["old", "new", "mixed", "nope"],
+            &msrv,
+            false,
+            false,
+        );
+
+        let old = report[0].as_ref().unwrap();
+        assert!(!old.is_held_back());
+
+        let new = report[1].as_ref().unwrap();
+        assert!(new.compatible.is_none());
+        assert!(new.is_held_back());
+
+        let mixed = report[2].as_ref().unwrap();
+        assert_eq!(
+            mixed.compatible.as_ref().unwrap().version.to_string(),
+            "1.0.0"
+        );
+        assert_eq!(mixed.latest.version.to_string(), "1.2.0");
+
+        assert!(report[3].is_err());
+
+        let strict = get_msrv_compatibility_from(&index, &["old", "mixed"], &msrv, false, true);
+        let old = strict[0].as_ref().unwrap();
+        assert!(old.compatible.is_none());
+        assert!(old.is_held_back());
+        let mixed = strict[1].as_ref().unwrap();
+        assert_eq!(
+            mixed.compatible.as_ref().unwrap().version.to_string(),
+            "1.0.0"
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! Running independent, blocking work like reading manifests on several threads.
+use crate::errors::*;
+use std::panic;
+use std::thread;
+
+/// Apply `f` to every item, spreading the items over as many threads as there are cores, and
+/// return the results in the order of `items`; the error of the first failing item wins.
+///
+/// Where threads can't be spawned, e.g. on wasm, the items are handled on the current thread.
+pub(crate) fn try_map<T, U, F>(items: &[T], f: F) -> Result<Vec<U>>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> Result<U> + Sync,
+{
+    let threads = thread::available_parallelism()
+        .map(usize::from)
+        .unwrap_or(1)
+        .min(items.len());
+    if threads <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let f = &f;
+    let chunks: Vec<Vec<U>> = thread::scope(|scope| {
+        let workers: Vec<_> = items
+            .chunks(items.len().div_ceil(threads))
+            .map(|chunk| {
+                thread::Builder::new()
+                    .spawn_scoped(scope, move || {
+                        chunk.iter().map(f).collect::<Result<Vec<_>>>()
+                    })
+                    .map_err(|_| chunk)
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| match worker {
+                Ok(handle) => handle
+                    .join()
+                    .unwrap_or_else(|payload| panic::resume_unwind(payload)),
+                Err(chunk) => chunk.iter().map(f).collect(),
+            })
+            .collect::<Result<_>>()
+    })?;
+    Ok(chunks.into_iter().flatten().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_order_and_first_error() {
+        let items: Vec<u32> = (0..100).collect();
+        assert_eq!(
+            try_map(&items, |i| Ok(i * 2)).unwrap(),
+            (0..200).step_by(2).collect::<Vec<_>>()
+        );
+
+        let err = try_map(&items, |&i| {
+            if i % 30 == 29 {
+                Err(format!("item {}", i).into())
+            } else {
+                Ok(i)
+            }
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "item 29");
+    }
+}
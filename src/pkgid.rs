@@ -0,0 +1,363 @@
+//! Package ID specifications, the way cargo names packages in its messages and in `cargo pkgid`.
+//!
+//! ref: https://doc.rust-lang.org/cargo/reference/pkgid-spec.html
+use crate::dependency_path::dependency_path;
+use crate::errors::*;
+use crate::registry::{path_to_url, registry_name, url_to_path, CRATES_IO_INDEX};
+use crate::Dependency;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use url::Url;
+
+/// A git reference selected with a query parameter, like `?branch=main`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitReference {
+    /// `?branch=<name>`
+    Branch(String),
+    /// `?tag=<name>`
+    Tag(String),
+    /// `?rev=<commit>`
+    Rev(String),
+}
+
+/// Where the package named by a [`PackageIdSpec`] comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpecSource {
+    /// A registry, by the URL of its index; sparse registries keep their `sparse+` prefix
+    Registry(Url),
+    /// A git repository
+    Git {
+        /// The repository, without the reference
+        url: Url,
+        /// The branch, tag or revision, if one was given
+        reference: Option<GitReference>,
+    },
+    /// A directory on disk
+    Path(PathBuf),
+}
+
+/// A package ID specification, like `serde@1.0`,
+/// `registry+https://github.com/rust-lang/crates.io-index#serde@1.0.130` or
+/// `https://github.com/dtolnay/syn#syn@1.0`.
+///
+/// URLs without a `registry+`, `sparse+`, `git+` or `path+` prefix are taken to be git
+/// repositories, except for the crates.io index and `file://` URLs.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{PackageIdSpec, SpecSource};
+///
+///   let spec: PackageIdSpec = "git+https://github.com/x/y?branch=dev#foo@1.2".parse().unwrap();
+///   assert_eq!(spec.name, "foo");
+///   assert_eq!(spec.version.as_deref(), Some("1.2"));
+///   assert!(matches!(spec.source, Some(SpecSource::Git { .. })));
+///
+///   let spec: PackageIdSpec = "https://github.com/x/bar".parse().unwrap();
+///   assert_eq!(spec.name, "bar");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageIdSpec {
+    /// The package name
+    pub name: String,
+    /// The version, which may be partial like `1.2`; any version requirement is accepted too
+    pub version: Option<String>,
+    /// Where the package comes from, if the spec says
+    pub source: Option<SpecSource>,
+}
+
+impl PackageIdSpec {
+    /// Parse a package ID specification.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = |reason: &str| -> Error {
+            ErrorKind::InvalidPackageIdSpec(spec.to_owned(), reason.to_owned()).into()
+        };
+
+        if !spec.contains("://") {
+            let (name, version) = match spec.split_once('@') {
+                Some((name, version)) => (name, Some(version)),
+                None => (spec, None),
+            };
+            return PackageIdSpec::new(spec, name, version, None);
+        }
+
+        let (kind, url) = match spec.split_once('+') {
+            Some((kind, url)) if !kind.contains(':') => (Some(kind), url),
+            _ => (None, spec),
+        };
+        let mut url = Url::parse(url).map_err(|err| invalid(&err.to_string()))?;
+        let fragment = url.fragment().map(ToOwned::to_owned);
+        url.set_fragment(None);
+        let last_segment = url
+            .path_segments()
+            .and_then(|mut segments| segments.rfind(|s| !s.is_empty()))
+            .map(|segment| segment.trim_end_matches(".git").to_owned())
+            .unwrap_or_default();
+
+        let (name, version) = match fragment.as_deref() {
+            // `name:version` is how older versions of cargo printed it.
+            Some(fragment) => match fragment.split_once(['@', ':']) {
+                Some((name, version)) => (name.to_owned(), Some(version)),
+                None if fragment.starts_with(|c: char| c.is_ascii_digit()) => {
+                    (last_segment, Some(fragment))
+                }
+                None => (fragment.to_owned(), None),
+            },
+            None => (last_segment, None),
+        };
+
+        let source = match kind {
+            Some("registry") => SpecSource::Registry(url),
+            Some("sparse") => SpecSource::Registry(
+                Url::parse(&format!("sparse+{}", url)).map_err(|err| invalid(&err.to_string()))?,
+            ),
+            Some("git") => git_source(url),
+            Some("path") => SpecSource::Path(
+                url_to_path(&url).ok_or_else(|| invalid("`path+` needs a `file://` URL"))?,
+            ),
+            Some(kind) => return Err(invalid(&format!("unknown source kind `{}`", kind))),
+            None if url.scheme() == "file" => {
+                SpecSource::Path(url_to_path(&url).ok_or_else(|| invalid("not a local path"))?)
+            }
+            None if url.as_str().trim_end_matches('/') == CRATES_IO_INDEX => {
+                SpecSource::Registry(url)
+            }
+            None => git_source(url),
+        };
+        PackageIdSpec::new(spec, &name, version, Some(source))
+    }
+
+    fn new(
+        spec: &str,
+        name: &str,
+        version: Option<&str>,
+        source: Option<SpecSource>,
+    ) -> Result<Self> {
+        if name.is_empty() {
+            return Err(ErrorKind::InvalidPackageIdSpec(
+                spec.to_owned(),
+                "missing package name".to_owned(),
+            )
+            .into());
+        }
+        if let Some(version) = version {
+            semver::VersionReq::parse(version).chain_err(|| "Invalid crate version requirement")?;
+        }
+        Ok(PackageIdSpec {
+            name: name.to_owned(),
+            version: version.map(ToOwned::to_owned),
+            source,
+        })
+    }
+
+    /// Whether `version` of a package named `name` is the one this spec refers to.
+    ///
+    /// A partial version like `1.2` matches every `1.2.x`.
+    pub fn matches(&self, name: &str, version: &semver::Version) -> bool {
+        name == self.name
+            && self.version.as_ref().is_none_or(|spec| {
+                let actual = version.to_string();
+                actual == *spec
+                    || actual
+                        .strip_prefix(spec.as_str())
+                        .is_some_and(|rest| rest.starts_with(['.', '-', '+']))
+            })
+    }
+
+    /// The dependency this spec refers to, as it would be added to the manifest at
+    /// `manifest_path`.
+    ///
+    /// The version of a registry package becomes the version requirement, while packages from git
+    /// or a path are added without one. Registries other than crates.io are looked up by their
+    /// index URL in the cargo config.
+    pub fn to_dependency(&self, manifest_path: &Path) -> Result<Dependency> {
+        let dependency = Dependency::new(&self.name);
+        let with_version = |dependency: Dependency| match &self.version {
+            Some(version) => dependency.set_version(version),
+            None => dependency,
+        };
+        Ok(match &self.source {
+            None => with_version(dependency),
+            Some(SpecSource::Registry(url)) => match registry_name(manifest_path, url)? {
+                Some(registry) => with_version(dependency.set_registry(&registry)),
+                None => with_version(dependency),
+            },
+            Some(SpecSource::Git { url, reference }) => match reference {
+                None => dependency.set_git(url.as_str(), None),
+                Some(GitReference::Branch(branch)) => {
+                    dependency.set_git(url.as_str(), Some(branch.clone()))
+                }
+                Some(GitReference::Tag(tag)) => dependency.set_git_tag(url.as_str(), tag),
+                Some(GitReference::Rev(rev)) => dependency.set_git_rev(url.as_str(), rev),
+            },
+            Some(SpecSource::Path(path)) => {
+                dependency.set_path(&dependency_path(manifest_path, path))
+            }
+        })
+    }
+}
+
+/// Split the reference off a git URL.
+fn git_source(mut url: Url) -> SpecSource {
+    let reference = url
+        .query_pairs()
+        .find_map(|(key, value)| match key.as_ref() {
+            "branch" => Some(GitReference::Branch(value.into_owned())),
+            "tag" => Some(GitReference::Tag(value.into_owned())),
+            "rev" => Some(GitReference::Rev(value.into_owned())),
+            _ => None,
+        });
+    url.set_query(None);
+    SpecSource::Git { url, reference }
+}
+
+impl FromStr for PackageIdSpec {
+    type Err = Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        PackageIdSpec::parse(spec)
+    }
+}
+
+impl fmt::Display for PackageIdSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            None => {}
+            Some(SpecSource::Registry(url)) if url.scheme().starts_with("sparse+") => {
+                write!(f, "{}#", url)?
+            }
+            Some(SpecSource::Registry(url)) => write!(f, "registry+{}#", url)?,
+            Some(SpecSource::Git { url, reference }) => {
+                write!(f, "git+{}", url)?;
+                match reference {
+                    None => {}
+                    Some(GitReference::Branch(branch)) => write!(f, "?branch={}", branch)?,
+                    Some(GitReference::Tag(tag)) => write!(f, "?tag={}", tag)?,
+                    Some(GitReference::Rev(rev)) => write!(f, "?rev={}", rev)?,
+                }
+                write!(f, "#")?
+            }
+            Some(SpecSource::Path(path)) => match path_to_url(path, false) {
+                Some(url) => write!(f, "path+{}#", url)?,
+                None => write!(f, "path+file://{}#", path.display())?,
+            },
+        }
+        write!(f, "{}", self.name)?;
+        if let Some(version) = &self.version {
+            write!(f, "@{}", version)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(spec: &str) -> PackageIdSpec {
+        spec.parse().unwrap()
+    }
+
+    #[test]
+    fn plain_specs() {
+        assert_eq!(parse("serde").name, "serde");
+        assert_eq!(parse("serde").version, None);
+        assert_eq!(parse("serde@1.0").version.as_deref(), Some("1.0"));
+        assert_eq!(parse("docopt@^0.8").version.as_deref(), Some("^0.8"));
+        assert!(PackageIdSpec::parse("@1.0").is_err());
+        assert!(PackageIdSpec::parse("serde@one").is_err());
+    }
+
+    #[test]
+    fn url_specs() {
+        let spec = parse("registry+https://github.com/rust-lang/crates.io-index#regex@1.4.3");
+        assert_eq!(
+            (spec.name.as_str(), spec.version.as_deref()),
+            ("regex", Some("1.4.3"))
+        );
+        assert_eq!(
+            spec.source,
+            Some(SpecSource::Registry(Url::parse(CRATES_IO_INDEX).unwrap()))
+        );
+        assert_eq!(
+            spec.to_string(),
+            "registry+https://github.com/rust-lang/crates.io-index#regex@1.4.3"
+        );
+
+        let spec = parse("sparse+https://index.crates.io/#regex");
+        match &spec.source {
+            Some(SpecSource::Registry(url)) => {
+                assert_eq!(url.as_str(), "sparse+https://index.crates.io/")
+            }
+            source => panic!("unexpected source {:?}", source),
+        }
+        assert_eq!(spec.to_string(), "sparse+https://index.crates.io/#regex");
+
+        // Older cargo versions printed the registry without prefix, and `name:version`.
+        let spec = parse("https://github.com/rust-lang/crates.io-index#regex:1.4.3");
+        assert!(matches!(spec.source, Some(SpecSource::Registry(_))));
+        assert_eq!(spec.version.as_deref(), Some("1.4.3"));
+
+        let spec = parse("https://github.com/x/y#name@1.2");
+        assert_eq!(spec.name, "name");
+        assert_eq!(spec.to_string(), "git+https://github.com/x/y#name@1.2");
+
+        let spec = parse("git+ssh://git@github.com/x/y.git?tag=v1#1.0.0");
+        assert_eq!(spec.name, "y");
+        assert_eq!(spec.version.as_deref(), Some("1.0.0"));
+        match spec.source {
+            Some(SpecSource::Git { url, reference }) => {
+                assert_eq!(url.as_str(), "ssh://git@github.com/x/y.git");
+                assert_eq!(reference, Some(GitReference::Tag("v1".to_owned())));
+            }
+            source => panic!("unexpected source {:?}", source),
+        }
+
+        #[cfg(unix)]
+        {
+            let spec = parse("path+file:///path/to/foo#0.1.0");
+            assert_eq!(spec.name, "foo");
+            assert_eq!(spec.source, Some(SpecSource::Path("/path/to/foo".into())));
+        }
+
+        assert!(PackageIdSpec::parse("svn+https://example.com/foo").is_err());
+    }
+
+    #[test]
+    fn matching_versions() {
+        let version = semver::Version::parse("1.2.3").unwrap();
+        assert!(parse("foo").matches("foo", &version));
+        assert!(parse("foo@1.2").matches("foo", &version));
+        assert!(parse("foo@1.2.3").matches("foo", &version));
+        assert!(!parse("foo@1.2.30").matches("foo", &version));
+        assert!(!parse("foo@1.1").matches("foo", &version));
+        assert!(!parse("bar").matches("foo", &version));
+    }
+
+    #[test]
+    fn dependencies_from_specs() {
+        let manifest = Path::new("Cargo.toml");
+        let dep = parse("serde@1.0").to_dependency(manifest).unwrap();
+        assert_eq!(dep.version(), Some("1.0"));
+
+        let dep = parse("git+https://github.com/x/y?branch=dev#foo@0.1")
+            .to_dependency(manifest)
+            .unwrap();
+        assert_eq!(dep.git_repo(), Some("https://github.com/x/y"));
+        assert_eq!(dep.version(), None);
+
+        let dep = parse("git+https://github.com/x/y?rev=abc#foo")
+            .to_dependency(manifest)
+            .unwrap();
+        assert_eq!(
+            dep.git_reference(),
+            Some(GitReference::Rev("abc".to_owned()))
+        );
+
+        let dep = parse("path+file:///work/libs/foo#0.1.0")
+            .to_dependency(Path::new("/work/app/Cargo.toml"))
+            .unwrap();
+        assert_eq!(dep.path(), Some("../libs/foo"));
+    }
+}
@@ -0,0 +1,446 @@
+//! Rules for choosing which published version of a crate to depend on.
+use crate::errors::*;
+use crate::index::CrateVersionInfo;
+use crate::rust_version::RustVersion;
+use std::fmt;
+
+/// Why a version of a crate was passed over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    /// The version was yanked
+    Yanked,
+    /// The version is a pre-release, and those are not allowed
+    Prerelease,
+    /// The version does not match the requirement
+    Mismatch(semver::VersionReq),
+    /// The version requires a newer Rust than the one supported
+    RustVersion(RustVersion),
+    /// The version does not declare the Rust version it needs, and that must be known
+    UnknownRustVersion,
+    /// The version was published at this time, after the cutoff
+    PublishedAfter(String),
+    /// A policy does not accept the version, without saying why
+    NotAccepted,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::Yanked => write!(f, "yanked"),
+            Rejection::Prerelease => write!(f, "pre-release"),
+            Rejection::Mismatch(req) => write!(f, "does not match `{}`", req),
+            Rejection::RustVersion(required) => write!(f, "requires Rust {}", required),
+            Rejection::UnknownRustVersion => write!(f, "does not declare its Rust version"),
+            Rejection::PublishedAfter(pubtime) => write!(f, "published too late, at {}", pubtime),
+            Rejection::NotAccepted => write!(f, "excluded by the policy"),
+        }
+    }
+}
+
+/// A rule for choosing among the published versions of a crate.
+///
+/// Implementors decide which candidates are acceptable; yanked versions are never selected, and
+/// among the acceptable ones the newest wins unless [`VersionPolicy::select`] is overridden.
+/// Policies compose: [`MsrvCompatible`] and [`PublishedBefore`] wrap another policy.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{CrateVersionInfo, Latest, PublishedBefore, VersionPolicy};
+///
+///   let mut old = CrateVersionInfo::new("foo", semver::Version::new(1, 0, 0));
+///   old.pubtime = Some("2020-01-01T00:00:00Z".to_owned());
+///   let mut new = CrateVersionInfo::new("foo", semver::Version::new(1, 1, 0));
+///   new.pubtime = Some("2021-01-01T00:00:00Z".to_owned());
+///
+///   let releases = [old, new];
+///   let policy = PublishedBefore::new(Latest::default(), "2020-06-01");
+///   let selected = policy.select(&releases).unwrap();
+///   assert_eq!(selected.version, semver::Version::new(1, 0, 0));
+/// ```
+pub trait VersionPolicy {
+    /// Whether `candidate` may be selected, leaving aside whether it is yanked.
+    fn accepts(&self, candidate: &CrateVersionInfo) -> bool;
+
+    /// Why `candidate` may not be selected, leaving aside whether it is yanked, or `None` if it
+    /// may be. Policies should say why here, for [`explain_selection`].
+    fn reject(&self, candidate: &CrateVersionInfo) -> Option<Rejection> {
+        if self.accepts(candidate) {
+            None
+        } else {
+            Some(Rejection::NotAccepted)
+        }
+    }
+
+    /// Select a version among `candidates`, all releases of the same crate.
+    fn select<'c>(&self, candidates: &'c [CrateVersionInfo]) -> Result<&'c CrateVersionInfo> {
+        candidates
+            .iter()
+            .filter(|candidate| !candidate.yanked && self.accepts(candidate))
+            .max_by_key(|candidate| &candidate.version)
+            .ok_or_else(|| self.explain(candidates))
+    }
+
+    /// Explain why nothing could be selected from `candidates`.
+    fn explain(&self, candidates: &[CrateVersionInfo]) -> Error {
+        no_matching_version(candidates, |candidate| self.accepts(candidate))
+    }
+}
+
+impl<P: VersionPolicy + ?Sized> VersionPolicy for &P {
+    fn accepts(&self, candidate: &CrateVersionInfo) -> bool {
+        (**self).accepts(candidate)
+    }
+
+    fn reject(&self, candidate: &CrateVersionInfo) -> Option<Rejection> {
+        (**self).reject(candidate)
+    }
+
+    fn select<'c>(&self, candidates: &'c [CrateVersionInfo]) -> Result<&'c CrateVersionInfo> {
+        (**self).select(candidates)
+    }
+
+    fn explain(&self, candidates: &[CrateVersionInfo]) -> Error {
+        (**self).explain(candidates)
+    }
+}
+
+/// The newest version, which is only a pre-release if those are allowed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Latest {
+    /// Whether pre-releases may be selected
+    pub allow_prerelease: bool,
+}
+
+impl VersionPolicy for Latest {
+    fn accepts(&self, candidate: &CrateVersionInfo) -> bool {
+        self.allow_prerelease || !candidate.is_prerelease()
+    }
+
+    fn reject(&self, candidate: &CrateVersionInfo) -> Option<Rejection> {
+        Some(Rejection::Prerelease).filter(|_| !self.accepts(candidate))
+    }
+}
+
+/// The newest version matching a requirement, following cargo's matching rules for pre-releases.
+#[derive(Debug, Clone)]
+pub struct Compatible {
+    /// The requirement to match
+    pub req: semver::VersionReq,
+}
+
+impl VersionPolicy for Compatible {
+    fn accepts(&self, candidate: &CrateVersionInfo) -> bool {
+        self.req.matches(&candidate.version)
+    }
+
+    fn reject(&self, candidate: &CrateVersionInfo) -> Option<Rejection> {
+        Some(Rejection::Mismatch(self.req.clone())).filter(|_| !self.accepts(candidate))
+    }
+}
+
+/// Versions accepted by another policy which also build with a given Rust toolchain.
+///
+/// Versions without a declared `rust-version` are assumed to be compatible, unless the policy is
+/// [`strict`](MsrvCompatible::strict).
+#[derive(Debug, Clone)]
+pub struct MsrvCompatible<P> {
+    inner: P,
+    rustc: semver::Version,
+    strict: bool,
+}
+
+impl<P: VersionPolicy> MsrvCompatible<P> {
+    /// Restrict `inner` to versions supporting `rustc`.
+    pub fn new(inner: P, rustc: semver::Version) -> Self {
+        MsrvCompatible {
+            inner,
+            rustc,
+            strict: false,
+        }
+    }
+
+    /// Whether to pass over versions which don't declare the Rust version they need.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+}
+
+impl<P: VersionPolicy> VersionPolicy for MsrvCompatible<P> {
+    fn accepts(&self, candidate: &CrateVersionInfo) -> bool {
+        self.inner.accepts(candidate)
+            && match candidate.rust_version {
+                Some(required) => required.is_compatible_with(&self.rustc),
+                None => !self.strict,
+            }
+    }
+
+    fn reject(&self, candidate: &CrateVersionInfo) -> Option<Rejection> {
+        self.inner
+            .reject(candidate)
+            .or_else(|| match candidate.rust_version {
+                Some(required) if !required.is_compatible_with(&self.rustc) => {
+                    Some(Rejection::RustVersion(required))
+                }
+                None if self.strict => Some(Rejection::UnknownRustVersion),
+                _ => None,
+            })
+    }
+
+    fn explain(&self, candidates: &[CrateVersionInfo]) -> Error {
+        // Only blame the toolchain if the inner policy alone would have found something.
+        let oldest_required = candidates
+            .iter()
+            .filter(|candidate| !candidate.yanked && self.inner.accepts(candidate))
+            .filter_map(|candidate| candidate.rust_version)
+            .min();
+        match (self.inner.select(candidates), oldest_required) {
+            (Ok(_), Some(required)) => ErrorKind::IncompatibleRustVersion(
+                candidates[0].name.clone(),
+                self.rustc.to_string(),
+                required,
+            )
+            .into(),
+            (Ok(_), None) if self.strict => {
+                ErrorKind::UnknownRustVersion(candidates[0].name.clone(), self.rustc.to_string())
+                    .into()
+            }
+            _ => self.inner.explain(candidates),
+        }
+    }
+}
+
+/// Versions accepted by another policy which were published before a date.
+///
+/// The date is compared with the `pubtime` of each version, an RFC 3339 timestamp, so a prefix
+/// like `2021-06-01` works too. Versions without a `pubtime` are accepted.
+#[derive(Debug, Clone)]
+pub struct PublishedBefore<P> {
+    inner: P,
+    cutoff: String,
+}
+
+impl<P: VersionPolicy> PublishedBefore<P> {
+    /// Restrict `inner` to versions published before `cutoff`.
+    pub fn new(inner: P, cutoff: &str) -> Self {
+        PublishedBefore {
+            inner,
+            cutoff: cutoff.to_owned(),
+        }
+    }
+}
+
+impl<P: VersionPolicy> VersionPolicy for PublishedBefore<P> {
+    fn accepts(&self, candidate: &CrateVersionInfo) -> bool {
+        self.inner.accepts(candidate)
+            && candidate
+                .pubtime
+                .as_ref()
+                .is_none_or(|pubtime| pubtime.as_str() < self.cutoff.as_str())
+    }
+
+    fn reject(&self, candidate: &CrateVersionInfo) -> Option<Rejection> {
+        self.inner.reject(candidate).or_else(|| {
+            candidate
+                .pubtime
+                .clone()
+                .filter(|pubtime| pubtime.as_str() >= self.cutoff.as_str())
+                .map(Rejection::PublishedAfter)
+        })
+    }
+}
+
+/// Which version of a crate a policy selects, and why it passes over the newer ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    /// The name of the crate, as published
+    pub name: String,
+    /// The version selected, if any can be
+    pub selected: Option<semver::Version>,
+    /// The versions newer than the one selected, or every version if none is, newest first, with
+    /// why each is passed over
+    pub rejected: Vec<(semver::Version, Rejection)>,
+}
+
+/// Explain which of `candidates`, all releases of the same crate, `policy` selects and why it
+/// passes over the newer ones, e.g. to answer why the latest version was not picked.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{explain_selection, CrateVersionInfo, Latest, Rejection};
+///
+///   let stable = CrateVersionInfo::new("foo", semver::Version::new(1, 0, 0));
+///   let mut yanked = CrateVersionInfo::new("foo", semver::Version::new(1, 1, 0));
+///   yanked.yanked = true;
+///   let beta = CrateVersionInfo::new("foo", semver::Version::parse("2.0.0-beta.1").unwrap());
+///
+///   let selection = explain_selection(&Latest::default(), &[stable, yanked, beta]);
+///   assert_eq!(selection.selected, Some(semver::Version::new(1, 0, 0)));
+///   assert_eq!(selection.rejected[0].1, Rejection::Prerelease);
+///   assert_eq!(selection.rejected[1].1, Rejection::Yanked);
+/// ```
+pub fn explain_selection(policy: &dyn VersionPolicy, candidates: &[CrateVersionInfo]) -> Selection {
+    let selected = policy
+        .select(candidates)
+        .ok()
+        .map(|selected| selected.version.clone());
+    let mut rejected: Vec<(semver::Version, Rejection)> = candidates
+        .iter()
+        .filter(|candidate| {
+            selected
+                .as_ref()
+                .is_none_or(|selected| candidate.version > *selected)
+        })
+        .map(|candidate| {
+            let rejection = if candidate.yanked {
+                Rejection::Yanked
+            } else {
+                policy.reject(candidate).unwrap_or(Rejection::NotAccepted)
+            };
+            (candidate.version.clone(), rejection)
+        })
+        .collect();
+    rejected.sort_by(|(a, _), (b, _)| b.cmp(a));
+    Selection {
+        name: candidates
+            .first()
+            .map(|candidate| candidate.name.clone())
+            .unwrap_or_default(),
+        selected,
+        rejected,
+    }
+}
+
+/// Explain why none of `versions` passing `suitable` could be selected.
+fn no_matching_version(
+    versions: &[CrateVersionInfo],
+    suitable: impl Fn(&CrateVersionInfo) -> bool,
+) -> Error {
+    let name = versions.first().map(|v| v.name.clone()).unwrap_or_default();
+    let yanked_only =
+        versions.iter().any(&suitable) && versions.iter().all(|v| !suitable(v) || v.yanked);
+    let prerelease_only = !yanked_only
+        && versions.iter().any(|v| !v.yanked)
+        && versions
+            .iter()
+            .all(|v| v.yanked || v.version.is_prerelease());
+    ErrorKind::NoMatchingVersion(name, yanked_only, prerelease_only).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(version: &str, rust_version: Option<&str>) -> CrateVersionInfo {
+        let mut info = CrateVersionInfo::new("foo", semver::Version::parse(version).unwrap());
+        info.rust_version = rust_version.map(|v| v.parse().unwrap());
+        info
+    }
+
+    #[test]
+    fn msrv_policy() {
+        let releases = [
+            release("1.0.0", None),
+            release("1.1.0", Some("1.56")),
+            release("1.2.0", Some("1.70")),
+        ];
+        let rustc = |v| semver::Version::parse(v).unwrap();
+
+        let policy = MsrvCompatible::new(Latest::default(), rustc("1.60.0"));
+        assert_eq!(
+            policy.select(&releases).unwrap().version.to_string(),
+            "1.1.0"
+        );
+
+        let policy = MsrvCompatible::new(Latest::default(), rustc("1.40.0"));
+        assert_eq!(
+            policy.select(&releases).unwrap().version.to_string(),
+            "1.0.0"
+        );
+
+        let policy = MsrvCompatible::new(Latest::default(), rustc("1.40.0"));
+        match policy.select(&releases[1..]).unwrap_err().kind() {
+            ErrorKind::IncompatibleRustVersion(name, rustc, required) => {
+                assert_eq!(name, "foo");
+                assert_eq!(rustc, "1.40.0");
+                assert_eq!(required.to_string(), "1.56");
+            }
+            kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn strict_msrv_policy() {
+        let releases = [
+            release("1.0.0", Some("1.40")),
+            release("1.1.0", None),
+            release("1.2.0", Some("1.70")),
+        ];
+        let policy = MsrvCompatible::new(Latest::default(), "1.60.0".parse().unwrap()).strict(true);
+        assert_eq!(
+            policy.select(&releases).unwrap().version.to_string(),
+            "1.0.0"
+        );
+        assert_eq!(
+            policy.reject(&releases[1]),
+            Some(Rejection::UnknownRustVersion)
+        );
+
+        match policy.select(&releases[1..2]).unwrap_err().kind() {
+            ErrorKind::UnknownRustVersion(name, rustc) => {
+                assert_eq!(name, "foo");
+                assert_eq!(rustc, "1.60.0");
+            }
+            kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn explains_passing_over_newer_versions() {
+        let mut releases = [
+            release("1.0.0", None),
+            release("1.1.0", Some("1.56")),
+            release("1.2.0", Some("1.70")),
+            release("1.3.0", None),
+        ];
+        releases[3].yanked = true;
+        let policy = MsrvCompatible::new(Latest::default(), "1.60.0".parse().unwrap());
+
+        let selection = explain_selection(&policy, &releases);
+        assert_eq!(selection.selected, Some("1.1.0".parse().unwrap()));
+        assert_eq!(
+            selection
+                .rejected
+                .iter()
+                .map(|(version, rejection)| format!("{} {}", version, rejection))
+                .collect::<Vec<_>>(),
+            ["1.3.0 yanked", "1.2.0 requires Rust 1.70"]
+        );
+    }
+
+    #[test]
+    fn custom_policy() {
+        struct EvenMinor;
+        impl VersionPolicy for EvenMinor {
+            fn accepts(&self, candidate: &CrateVersionInfo) -> bool {
+                candidate.version.minor.is_multiple_of(2)
+            }
+        }
+        let releases = [
+            release("1.0.0", None),
+            release("1.1.0", None),
+            release("1.2.0", None),
+            release("1.3.0", None),
+        ];
+        assert_eq!(
+            EvenMinor.select(&releases).unwrap().version.to_string(),
+            "1.2.0"
+        );
+        assert!(matches!(
+            EvenMinor.select(&releases[1..2]).unwrap_err().kind(),
+            ErrorKind::NoMatchingVersion(_, false, false)
+        ));
+    }
+}
@@ -0,0 +1,340 @@
+//! Telling pre-releases apart by channel, like `1.0.0-alpha.2` and `1.0.0-rc.1`, and bumping
+//! versions along them.
+use crate::errors::*;
+use semver::{Identifier, Version};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// The channel of a pre-release, from its first identifier.
+///
+/// `alpha`, `beta` and `rc` (also spelled `a`, `b`, `pre` or `cr`, and with a number attached
+/// like `beta2`) are ordered as expected; any other channel, like `dev` or `nightly`, can only be
+/// compared with itself.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// `alpha`, `a`
+    Alpha,
+    /// `beta`, `b`
+    Beta,
+    /// `rc`, `pre`, `cr`
+    Rc,
+    /// Any other channel, lowercased, or empty for a purely numeric pre-release like `1.0.0-1`
+    Custom(String),
+}
+
+impl Channel {
+    /// The channel of `version`, or `None` for a stable release.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    ///   use cargo_edit::Channel;
+    ///
+    ///   let version = |v| semver::Version::parse(v).unwrap();
+    ///   assert_eq!(Channel::of(&version("1.0.0-beta.2")), Some(Channel::Beta));
+    ///   assert_eq!(Channel::of(&version("1.0.0-RC1")), Some(Channel::Rc));
+    ///   assert_eq!(Channel::of(&version("1.0.0")), None);
+    ///   assert!(Channel::Alpha < Channel::Rc);
+    /// ```
+    pub fn of(version: &Version) -> Option<Self> {
+        let label = match version.pre.first()? {
+            Identifier::AlphaNumeric(label) => label.to_ascii_lowercase(),
+            Identifier::Numeric(_) => String::new(),
+        };
+        let name = label.trim_end_matches(|c: char| c.is_ascii_digit() || c == '-' || c == '_');
+        Some(match name {
+            "alpha" | "a" => Channel::Alpha,
+            "beta" | "b" => Channel::Beta,
+            "rc" | "pre" | "cr" => Channel::Rc,
+            _ => Channel::Custom(name.to_owned()),
+        })
+    }
+
+    /// The name the channel is written with, like `beta`.
+    pub fn name(&self) -> &str {
+        match self {
+            Channel::Alpha => "alpha",
+            Channel::Beta => "beta",
+            Channel::Rc => "rc",
+            Channel::Custom(name) => name,
+        }
+    }
+
+    /// How far along the release process the channel is, for the well-known ones.
+    fn rank(&self) -> Option<u8> {
+        match self {
+            Channel::Alpha => Some(0),
+            Channel::Beta => Some(1),
+            Channel::Rc => Some(2),
+            Channel::Custom(_) => None,
+        }
+    }
+}
+
+impl PartialOrd for Channel {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.rank(), other.rank()) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ if self == other => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Channel {
+    type Err = Error;
+
+    /// Parse a channel like `beta`, as given to bump a version along it.
+    fn from_str(s: &str) -> Result<Self> {
+        let valid = !s.is_empty()
+            && s.chars().all(|c| c.is_ascii_alphabetic() || c == '-')
+            && !s.starts_with('-');
+        if !valid {
+            return Err(format!("Invalid pre-release channel `{}`", s).into());
+        }
+        let version = Version::new(0, 0, 0);
+        let channel = Channel::of(&Version {
+            pre: vec![Identifier::AlphaNumeric(s.to_owned())],
+            ..version
+        });
+        Ok(channel.expect("a pre-release has a channel"))
+    }
+}
+
+/// Bump `version` to the next pre-release on `channel`.
+///
+/// A stable release moves on to the first pre-release of the next minor release. A pre-release
+/// on the same channel gets its counter incremented, and one on an earlier channel moves on to the
+/// first pre-release of `channel`, for the same release. Build metadata is dropped.
+///
+/// This fails for a channel before that of `version`, or one which can't be compared with it,
+/// like `dev` after `beta`.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{bump_prerelease, Channel};
+///
+///   let version = |v| semver::Version::parse(v).unwrap();
+///   let bump = |v, channel| bump_prerelease(&version(v), &channel).unwrap().to_string();
+///   assert_eq!(bump("1.2.3", Channel::Alpha), "1.3.0-alpha.1");
+///   assert_eq!(bump("1.3.0-alpha.1", Channel::Alpha), "1.3.0-alpha.2");
+///   assert_eq!(bump("1.3.0-alpha.2", Channel::Rc), "1.3.0-rc.1");
+///   assert!(bump_prerelease(&version("1.3.0-rc.1"), &Channel::Beta).is_err());
+/// ```
+pub fn bump_prerelease(version: &Version, channel: &Channel) -> Result<Version> {
+    let invalid = |reason: String| ErrorKind::InvalidVersionBump(version.to_string(), reason);
+    let (minor, counter) = match Channel::of(version) {
+        None => (version.minor + 1, 0),
+        Some(current) => match current.partial_cmp(channel) {
+            Some(Ordering::Equal) => (version.minor, prerelease_counter(version)),
+            Some(Ordering::Less) => (version.minor, 0),
+            Some(Ordering::Greater) => {
+                return Err(invalid(format!(
+                    "`{}` comes before its `{}` pre-release",
+                    channel, current
+                ))
+                .into())
+            }
+            None => {
+                return Err(invalid(format!(
+                    "`{}` can't be ordered with its `{}` pre-release",
+                    channel, current
+                ))
+                .into())
+            }
+        },
+    };
+    if channel.name().is_empty() {
+        return Err(invalid("a pre-release channel needs a name".to_owned()).into());
+    }
+    let patch = if minor == version.minor {
+        version.patch
+    } else {
+        0
+    };
+    Ok(Version {
+        major: version.major,
+        minor,
+        patch,
+        pre: vec![
+            Identifier::AlphaNumeric(channel.name().to_owned()),
+            Identifier::Numeric(counter + 1),
+        ],
+        build: Vec::new(),
+    })
+}
+
+/// The number of a pre-release on its channel, like 2 for `1.0.0-beta.2` or `1.0.0-beta2`, and 0
+/// if it has none, like `1.0.0-beta`.
+fn prerelease_counter(version: &Version) -> u64 {
+    match version.pre.as_slice() {
+        [_, Identifier::Numeric(counter), ..] => *counter,
+        [Identifier::AlphaNumeric(label), ..] => {
+            let digits = label.len() - label.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+            label[label.len() - digits..].parse().unwrap_or(0)
+        }
+        _ => 0,
+    }
+}
+
+/// `version` with its build metadata replaced by `metadata`, like `1.0.0+build.5` for `build.5`,
+/// or removed if `metadata` is empty.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::with_build_metadata;
+///
+///   let version = semver::Version::parse("1.3.0-beta.1+old").unwrap();
+///   assert_eq!(with_build_metadata(&version, "git.1a2b3c").unwrap().to_string(), "1.3.0-beta.1+git.1a2b3c");
+///   assert_eq!(with_build_metadata(&version, "").unwrap().to_string(), "1.3.0-beta.1");
+///   assert!(with_build_metadata(&version, "no spaces").is_err());
+/// ```
+pub fn with_build_metadata(version: &Version, metadata: &str) -> Result<Version> {
+    let mut version = version.clone();
+    version.build = Vec::new();
+    if metadata.is_empty() {
+        return Ok(version);
+    }
+    Version::parse(&format!("{}+{}", version, metadata)).map_err(|err| {
+        ErrorKind::InvalidVersionBump(
+            version.to_string(),
+            format!("invalid build metadata `{}`: {}", metadata, err),
+        )
+        .into()
+    })
+}
+
+/// Whether `candidate` is a pre-release or the final release of the same upcoming release as the
+/// pre-release `current`, like `1.0.0-rc.1` or `1.0.0` for `1.0.0-beta.3`.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::same_upcoming_release;
+///
+///   let version = |v| semver::Version::parse(v).unwrap();
+///   let current = version("2.0.0-beta.1");
+///   assert!(same_upcoming_release(&current, &version("2.0.0-rc.1")));
+///   assert!(same_upcoming_release(&current, &version("2.0.0")));
+///   assert!(!same_upcoming_release(&current, &version("2.1.0-alpha.1")));
+///   assert!(!same_upcoming_release(&version("2.0.0"), &version("2.0.0")));
+/// ```
+pub fn same_upcoming_release(current: &Version, candidate: &Version) -> bool {
+    current.is_prerelease()
+        && (current.major, current.minor, current.patch)
+            == (candidate.major, candidate.minor, candidate.patch)
+}
+
+/// Compare two pre-releases of the same upcoming release by channel, then by the rest of their
+/// identifiers.
+///
+/// Stable releases come after every pre-release. Returns `None` for versions of different
+/// releases, or whose channels can't be compared, like `dev` and `beta`.
+pub fn compare_prereleases(a: &Version, b: &Version) -> Option<Ordering> {
+    if (a.major, a.minor, a.patch) != (b.major, b.minor, b.patch) {
+        return None;
+    }
+    match (Channel::of(a), Channel::of(b)) {
+        (None, None) => Some(Ordering::Equal),
+        (None, Some(_)) => Some(Ordering::Greater),
+        (Some(_), None) => Some(Ordering::Less),
+        (Some(channel_a), Some(channel_b)) => match channel_a.partial_cmp(&channel_b)? {
+            Ordering::Equal => Some(a.cmp(b)),
+            ordering => Some(ordering),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    fn channel(v: &str) -> Option<Channel> {
+        Channel::of(&version(v))
+    }
+
+    #[test]
+    fn channels() {
+        assert_eq!(channel("1.0.0-alpha"), Some(Channel::Alpha));
+        assert_eq!(channel("1.0.0-a.1"), Some(Channel::Alpha));
+        assert_eq!(channel("1.0.0-beta2"), Some(Channel::Beta));
+        assert_eq!(channel("1.0.0-pre.0"), Some(Channel::Rc));
+        assert_eq!(
+            channel("1.0.0-dev.5"),
+            Some(Channel::Custom("dev".to_owned()))
+        );
+        assert_eq!(channel("1.0.0-1"), Some(Channel::Custom(String::new())));
+        assert_eq!(channel("1.0.0+build"), None);
+
+        assert!(Channel::Beta > Channel::Alpha);
+        let dev = Channel::Custom("dev".to_owned());
+        assert_eq!(dev.partial_cmp(&Channel::Beta), None);
+        assert_eq!(dev.partial_cmp(&dev.clone()), Some(Ordering::Equal));
+    }
+
+    #[test]
+    fn bumping_prereleases() {
+        let bump = |v: &str, channel: &str| {
+            bump_prerelease(&version(v), &channel.parse().unwrap()).map(|v| v.to_string())
+        };
+        assert_eq!(bump("1.2.3", "alpha").unwrap(), "1.3.0-alpha.1");
+        assert_eq!(bump("0.9.1", "rc").unwrap(), "0.10.0-rc.1");
+        assert_eq!(bump("1.2.3+build.7", "beta").unwrap(), "1.3.0-beta.1");
+        assert_eq!(bump("1.3.0-alpha.1", "alpha").unwrap(), "1.3.0-alpha.2");
+        assert_eq!(bump("1.3.0-alpha.9", "alpha").unwrap(), "1.3.0-alpha.10");
+        assert_eq!(bump("1.3.0-alpha", "alpha").unwrap(), "1.3.0-alpha.1");
+        assert_eq!(bump("1.3.0-beta2", "beta").unwrap(), "1.3.0-beta.3");
+        assert_eq!(bump("1.3.0-RC.1", "rc").unwrap(), "1.3.0-rc.2");
+        assert_eq!(bump("1.3.1-alpha.4", "beta").unwrap(), "1.3.1-beta.1");
+        assert_eq!(bump("1.3.0-beta.1", "rc").unwrap(), "1.3.0-rc.1");
+        assert_eq!(bump("1.3.0-dev.1", "dev").unwrap(), "1.3.0-dev.2");
+
+        assert_eq!(
+            bump("1.3.0-rc.1", "alpha").unwrap_err().to_string(),
+            "Cannot bump `1.3.0-rc.1`: `alpha` comes before its `rc` pre-release"
+        );
+        assert!(bump("1.3.0-dev.1", "beta").is_err());
+        assert!("beta.1".parse::<Channel>().is_err());
+        assert!("".parse::<Channel>().is_err());
+    }
+
+    #[test]
+    fn replacing_build_metadata() {
+        let with = |v: &str, metadata| with_build_metadata(&version(v), metadata);
+        assert_eq!(
+            with("1.0.0", "build.5").unwrap().to_string(),
+            "1.0.0+build.5"
+        );
+        assert_eq!(with("1.0.0+a", "b").unwrap().to_string(), "1.0.0+b");
+        assert_eq!(with("1.0.0+a", "").unwrap().to_string(), "1.0.0");
+        assert!(with("1.0.0", "a..b").is_err());
+    }
+
+    #[test]
+    fn comparing_prereleases() {
+        let cmp = |a, b| compare_prereleases(&version(a), &version(b));
+        assert_eq!(
+            cmp("1.0.0-beta.1", "1.0.0-alpha.9"),
+            Some(Ordering::Greater)
+        );
+        // Plain semver ordering gets this one wrong, as `RC` sorts before `alpha`.
+        assert_eq!(cmp("1.0.0-RC.1", "1.0.0-alpha.1"), Some(Ordering::Greater));
+        assert_eq!(cmp("1.0.0-rc.1", "1.0.0-rc.2"), Some(Ordering::Less));
+        assert_eq!(cmp("1.0.0", "1.0.0-rc.2"), Some(Ordering::Greater));
+        assert_eq!(cmp("1.0.0-dev.1", "1.0.0-rc.2"), None);
+        assert_eq!(cmp("1.0.0-rc.1", "1.1.0-rc.1"), None);
+    }
+}
@@ -0,0 +1,158 @@
+//! Progress of network-bound work, like querying the index for many crates, drawn on stderr.
+//!
+//! The progress line is only drawn when stderr is a terminal, the [`verbosity`] is not quiet and
+//! [`disable_progress`] wasn't called, e.g. because a report is printed instead of progress lines.
+//!
+//! [`verbosity`]: crate::verbosity()
+use crate::color::stderr_color_choice;
+use crate::verbosity::verbosity;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
+
+static PROGRESS_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// The shortest time between two redraws of the progress line.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Never draw progress for the rest of the process, e.g. for `--output json`.
+pub fn disable_progress() {
+    PROGRESS_DISABLED.store(true, Ordering::SeqCst);
+}
+
+/// The progress of `total` items of work, shared between the threads doing it.
+///
+/// Each finished item is counted with [`Progress::tick`], which redraws a line like
+/// `    Querying [3/12] serde, ETA 2s`. The line is cleared when the progress is dropped.
+#[derive(Debug)]
+pub struct Progress {
+    label: &'static str,
+    total: usize,
+    enabled: bool,
+    start: Instant,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    done: usize,
+    drawn: Option<Instant>,
+    width: usize,
+}
+
+impl Progress {
+    /// Track `total` items of work, described by `label` like `Querying`.
+    pub fn new(label: &'static str, total: usize) -> Self {
+        let enabled = !PROGRESS_DISABLED.load(Ordering::SeqCst)
+            && !verbosity().is_quiet()
+            && atty::is(atty::Stream::Stderr);
+        Progress {
+            label,
+            total,
+            enabled,
+            start: Instant::now(),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Count one more item as done, `item` being its name.
+    pub fn tick(&self, item: &str) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.done += 1;
+        if !self.enabled {
+            return;
+        }
+        let now = Instant::now();
+        let last = state.done == self.total;
+        if !last
+            && state
+                .drawn
+                .is_some_and(|drawn| now - drawn < REDRAW_INTERVAL)
+        {
+            return;
+        }
+        state.drawn = Some(now);
+        let eta = eta(now - self.start, state.done, self.total);
+        let details = progress_details(state.done, self.total, item, eta);
+        // Drawing is best effort: progress is not worth failing the work for.
+        let _ = self.draw(&details, &mut state.width);
+    }
+
+    fn draw(&self, details: &str, width: &mut usize) -> io::Result<()> {
+        let mut stderr = StandardStream::stderr(stderr_color_choice());
+        write!(stderr, "\r")?;
+        stderr.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true))?;
+        write!(stderr, "{:>12}", self.label)?;
+        stderr.reset()?;
+        let line_width = 13 + details.chars().count();
+        // Pad with spaces to overwrite what is left of a longer line.
+        write!(
+            stderr,
+            " {}{:pad$}",
+            details,
+            "",
+            pad = width.saturating_sub(line_width)
+        )?;
+        *width = line_width;
+        stderr.flush()
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if self.enabled && state.width > 0 {
+            let _ = write!(io::stderr(), "\r{:width$}\r", "", width = state.width);
+        }
+    }
+}
+
+/// The time left for the `total - done` items still to do, at the pace of the `done` ones.
+fn eta(elapsed: Duration, done: usize, total: usize) -> Option<Duration> {
+    if done == 0 || done >= total {
+        return None;
+    }
+    Some(elapsed / done as u32 * (total - done) as u32)
+}
+
+/// The progress after the label, like `[3/12] serde, ETA 2s`.
+fn progress_details(done: usize, total: usize, item: &str, eta: Option<Duration>) -> String {
+    let mut details = format!("[{}/{}] {}", done, total, item);
+    if let Some(eta) = eta {
+        let secs = eta.as_secs();
+        if secs >= 60 {
+            details.push_str(&format!(", ETA {}m {:02}s", secs / 60, secs % 60));
+        } else {
+            details.push_str(&format!(", ETA {}s", secs.max(1)));
+        }
+    }
+    details
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_the_time_left() {
+        let elapsed = Duration::from_secs(6);
+        assert_eq!(eta(elapsed, 3, 12), Some(Duration::from_secs(18)));
+        assert_eq!(eta(elapsed, 0, 12), None);
+        assert_eq!(eta(elapsed, 12, 12), None);
+    }
+
+    #[test]
+    fn describes_the_progress() {
+        assert_eq!(
+            progress_details(3, 12, "serde", Some(Duration::from_millis(1500))),
+            "[3/12] serde, ETA 1s"
+        );
+        assert_eq!(
+            progress_details(1, 200, "rand", Some(Duration::from_secs(65))),
+            "[1/200] rand, ETA 1m 05s"
+        );
+        assert_eq!(progress_details(12, 12, "tokio", None), "[12/12] tokio");
+    }
+}
@@ -0,0 +1,135 @@
+//! Registries served by something other than a cargo index.
+//!
+//! The built-in protocols handle git and sparse indices and local sources. Services which aren't
+//! cargo indices, like an internal HTTP JSON API, can be used by [registering](register_protocol)
+//! a [`RegistryProtocol`] for their URLs, e.g. those with an `acme+https` scheme configured as the
+//! `index` of a registry in `.cargo/config.toml`. Every registry query of the library, and so
+//! `cargo add` and `cargo upgrade` built on it, then goes through it.
+use crate::errors::*;
+use crate::index::IndexSource;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use url::Url;
+
+/// A way of reading the versions published to some registries.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{
+///       get_latest_dependency, register_protocol, CrateVersionInfo, InMemoryIndex, IndexSource,
+///       RegistryProtocol, Result,
+///   };
+///   use std::path::Path;
+///   use url::Url;
+///
+///   /// Asks the package service of ACME Corp, whose registries have `acme+https` URLs.
+///   struct Acme;
+///
+///   impl RegistryProtocol for Acme {
+///       fn handles(&self, registry: &Url) -> bool {
+///           registry.scheme() == "acme+https"
+///       }
+///
+///       fn open(&self, _manifest_path: &Path, _registry: &Url) -> Result<Box<dyn IndexSource>> {
+///           // A real protocol would list the versions from the service, e.g. on demand in its
+///           // own `IndexSource`.
+///           let mut index = InMemoryIndex::new();
+///           index.insert(CrateVersionInfo::new("widgets", semver::Version::new(2, 1, 0)));
+///           Ok(Box::new(index))
+///       }
+///   }
+///
+///   register_protocol(Acme);
+///   let registry = Url::parse("acme+https://packages.acme.example/").unwrap();
+///   let dep = get_latest_dependency("widgets", false, Path::new("Cargo.toml"), &Some(registry))
+///       .unwrap();
+///   assert_eq!(dep.version(), Some("2.1.0"));
+/// ```
+pub trait RegistryProtocol: Send + Sync {
+    /// Whether the protocol serves `registry`, e.g. judging by its scheme or host.
+    fn handles(&self, registry: &Url) -> bool;
+
+    /// Open `registry` to look up crates for the manifest at `manifest_path`, which tells where
+    /// to look for configuration like credentials.
+    fn open(&self, manifest_path: &Path, registry: &Url) -> Result<Box<dyn IndexSource>>;
+
+    /// Bring any local copy of `registry` up to date, printing progress unless `quiet`.
+    ///
+    /// This is what updating the index of a git registry is for the built-in protocols; protocols
+    /// without a local copy have nothing to do.
+    fn update(&self, registry: &Url, quiet: bool) -> Result<()> {
+        let _ = (registry, quiet);
+        Ok(())
+    }
+}
+
+static PROTOCOLS: RwLock<Vec<Arc<dyn RegistryProtocol>>> = RwLock::new(Vec::new());
+
+/// Use `protocol` for the registries it [handles](RegistryProtocol::handles), rather than the
+/// built-in ones.
+///
+/// Protocols registered later take precedence over those registered earlier.
+pub fn register_protocol(protocol: impl RegistryProtocol + 'static) {
+    PROTOCOLS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .push(Arc::new(protocol));
+}
+
+/// The registered protocol for `registry`, if any.
+pub(crate) fn protocol_for(registry: &Url) -> Option<Arc<dyn RegistryProtocol>> {
+    PROTOCOLS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .iter()
+        .rev()
+        .find(|protocol| protocol.handles(registry))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{update_registry_index, InMemoryIndex};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Counting {
+        scheme: &'static str,
+        updates: Arc<AtomicUsize>,
+    }
+
+    impl RegistryProtocol for Counting {
+        fn handles(&self, registry: &Url) -> bool {
+            registry.scheme() == self.scheme
+        }
+
+        fn open(&self, _: &Path, _: &Url) -> Result<Box<dyn IndexSource>> {
+            Ok(Box::new(InMemoryIndex::new()))
+        }
+
+        fn update(&self, _: &Url, _: bool) -> Result<()> {
+            self.updates.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn registered_protocols_update_their_registries() {
+        let (first, second) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        register_protocol(Counting {
+            scheme: "counting+https",
+            updates: first.clone(),
+        });
+        register_protocol(Counting {
+            scheme: "counting+https",
+            updates: second.clone(),
+        });
+
+        let registry = Url::parse("counting+https://example.com/").unwrap();
+        update_registry_index(&registry, true).unwrap();
+        assert_eq!(first.load(Ordering::SeqCst), 0);
+        assert_eq!(second.load(Ordering::SeqCst), 1);
+        assert!(protocol_for(&Url::parse("https://example.com/").unwrap()).is_none());
+    }
+}
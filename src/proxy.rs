@@ -0,0 +1,192 @@
+//! Proxy settings for network requests, resolved the way cargo resolves them.
+//!
+//! In order of precedence, the proxy is taken from `http.proxy` in the cargo config (or
+//! `CARGO_HTTP_PROXY`), git's `http.proxy`, and finally the `HTTPS_PROXY`/`HTTP_PROXY`/`ALL_PROXY`
+//! environment variables. Hosts listed in `NO_PROXY` are always reached directly.
+use crate::errors::*;
+use crate::registry::config_paths;
+use std::sync::atomic::{AtomicBool, Ordering};
+use url::Url;
+
+static PROXY_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Ignore all proxy settings for the rest of the process, e.g. for a `--no-proxy` flag.
+pub fn disable_proxy() {
+    PROXY_DISABLED.store(true, Ordering::SeqCst);
+}
+
+/// The proxy to reach `url` through, if any.
+pub fn proxy_for_url(url: &Url) -> Result<Option<Url>> {
+    if PROXY_DISABLED.load(Ordering::SeqCst) {
+        return Ok(None);
+    }
+    let host = url.host_str().unwrap_or_default();
+    if let Some(no_proxy) = env_var(&["NO_PROXY", "no_proxy"]) {
+        if no_proxy_matches(&no_proxy, host) {
+            return Ok(None);
+        }
+    }
+
+    let configured = match cargo_http_proxy()? {
+        Some(proxy) => Some(proxy),
+        None => git_http_proxy(),
+    };
+    let proxy = match configured {
+        Some(proxy) => proxy,
+        None => {
+            let names: &[&str] = if url.scheme() == "http" {
+                &["http_proxy", "HTTP_PROXY", "ALL_PROXY", "all_proxy"]
+            } else {
+                &["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]
+            };
+            match env_var(names) {
+                Some(proxy) => proxy,
+                None => return Ok(None),
+            }
+        }
+    };
+    parse_proxy(&proxy).map(Some)
+}
+
+/// Route `req` to `url` through the configured proxy.
+pub(crate) fn apply_proxy(req: &mut ureq::Request, url: &Url) -> Result<()> {
+    if let Some(proxy) = proxy_for_url(url)? {
+        let scheme = match proxy.scheme() {
+            "socks5h" => "socks5",
+            scheme => scheme,
+        };
+        let credentials = match proxy.password() {
+            Some(password) => format!("{}:{}@", proxy.username(), password),
+            None if !proxy.username().is_empty() => format!("{}@", proxy.username()),
+            None => String::new(),
+        };
+        // ureq wants `scheme://[user:password@]host:port`, without a path.
+        let spec = format!(
+            "{}://{}{}:{}",
+            scheme,
+            credentials,
+            proxy.host_str().unwrap_or_default(),
+            proxy.port_or_known_default().unwrap_or(1080)
+        );
+        let proxy =
+            ureq::Proxy::new(&spec).map_err(|e| format!("Invalid proxy `{}`: {}", proxy, e))?;
+        req.set_proxy(proxy);
+    }
+    Ok(())
+}
+
+/// The `-c` options making the `git` command line use the same proxy as cargo-edit.
+///
+/// Without cargo's `http.proxy` git is left to its own configuration and the environment.
+pub(crate) fn git_proxy_args() -> Result<Vec<String>> {
+    if PROXY_DISABLED.load(Ordering::SeqCst) {
+        return Ok(vec!["-c".to_owned(), "http.proxy=".to_owned()]);
+    }
+    Ok(match cargo_http_proxy()? {
+        Some(proxy) => vec!["-c".to_owned(), format!("http.proxy={}", proxy)],
+        None => Vec::new(),
+    })
+}
+
+/// Whether the proxy environment variables should be hidden from `git`.
+pub(crate) fn proxy_disabled() -> bool {
+    PROXY_DISABLED.load(Ordering::SeqCst)
+}
+
+/// `http.proxy` from the cargo config files of the current directory, or `CARGO_HTTP_PROXY`.
+fn cargo_http_proxy() -> Result<Option<String>> {
+    if let Some(proxy) = env_var(&["CARGO_HTTP_PROXY"]) {
+        return Ok(Some(proxy));
+    }
+    let cwd = std::env::current_dir()?;
+    // `config_paths` looks in the ancestors of a manifest's directory.
+    for path in config_paths(&cwd.join("Cargo.toml"))? {
+        let content = std::fs::read(&path)?;
+        let config: toml::Value =
+            toml::from_slice(&content).chain_err(|| ErrorKind::InvalidCargoConfig)?;
+        if let Some(proxy) = config
+            .get("http")
+            .and_then(|http| http.get("proxy"))
+            .and_then(|proxy| proxy.as_str())
+        {
+            return Ok(Some(proxy.to_owned()).filter(|proxy| !proxy.is_empty()));
+        }
+    }
+    Ok(None)
+}
+
+/// `http.proxy` from git's global and system config.
+fn git_http_proxy() -> Option<String> {
+    git2::Config::open_default()
+        .ok()?
+        .get_string("http.proxy")
+        .ok()
+        .filter(|proxy| !proxy.is_empty())
+}
+
+fn env_var(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.trim().is_empty())
+}
+
+/// Proxies are often given without a scheme, e.g. `proxy.example.com:3128`, which means HTTP.
+fn parse_proxy(proxy: &str) -> Result<Url> {
+    let proxy = proxy.trim();
+    let proxy = if proxy.contains("://") {
+        proxy.to_owned()
+    } else {
+        format!("http://{}", proxy)
+    };
+    Url::parse(&proxy).chain_err(|| format!("Invalid proxy `{}`", proxy))
+}
+
+/// Whether `host` is excluded by a `NO_PROXY` list like `localhost,.example.com,10.0.0.1`.
+fn no_proxy_matches(no_proxy: &str, host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    no_proxy
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .map(|entry| {
+            entry
+                .trim()
+                .trim_start_matches("*.")
+                .trim_start_matches('.')
+        })
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            let entry = entry.to_ascii_lowercase();
+            entry == "*"
+                || host == entry
+                || (host.ends_with(&entry) && host[..host.len() - entry.len()].ends_with('.'))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_proxy_lists() {
+        assert!(no_proxy_matches("*", "index.crates.io"));
+        assert!(no_proxy_matches("localhost, .crates.io", "index.crates.io"));
+        assert!(no_proxy_matches("crates.io", "index.crates.io"));
+        assert!(no_proxy_matches("*.crates.io", "crates.io"));
+        assert!(no_proxy_matches("10.0.0.1", "10.0.0.1"));
+        assert!(!no_proxy_matches("s.io", "crates.io"));
+        assert!(!no_proxy_matches("example.com", "index.crates.io"));
+        assert!(!no_proxy_matches("", "index.crates.io"));
+    }
+
+    #[test]
+    fn proxy_urls() {
+        assert_eq!(
+            parse_proxy("proxy.example.com:3128").unwrap().as_str(),
+            "http://proxy.example.com:3128/"
+        );
+        assert_eq!(
+            parse_proxy("socks5://127.0.0.1:1080").unwrap().as_str(),
+            "socks5://127.0.0.1:1080"
+        );
+    }
+}
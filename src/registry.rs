@@ -1,16 +1,18 @@
-use self::code_from_cargo::Kind;
 use crate::errors::*;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use url::Url;
 
-const CRATES_IO_INDEX: &str = "https://github.com/rust-lang/crates.io-index";
-const CRATES_IO_REGISTRY: &str = "crates-io";
+pub(crate) const CRATES_IO_INDEX: &str = "https://github.com/rust-lang/crates.io-index";
+pub(crate) const CRATES_IO_SPARSE_INDEX: &str = "sparse+https://index.crates.io/";
+pub(crate) const CRATES_IO_REGISTRY: &str = "crates-io";
 
+#[cfg(feature = "fetch")]
 pub fn registry_path(manifest_path: &Path, registry: Option<&str>) -> Result<PathBuf> {
     registry_path_from_url(&registry_url(manifest_path, registry)?)
 }
 
+#[cfg(feature = "fetch")]
 pub fn registry_path_from_url(registry: &Url) -> Result<PathBuf> {
     Ok(cargo_home()?
         .join("registry")
@@ -18,11 +20,61 @@ pub fn registry_path_from_url(registry: &Url) -> Result<PathBuf> {
         .join(short_name(registry)))
 }
 
+/// Where cargo-edit caches responses from `registry`.
+#[cfg(feature = "index")]
+pub(crate) fn cache_path_from_url(registry: &Url) -> Result<PathBuf> {
+    Ok(cache_root()?.join(short_name(registry)))
+}
+
+/// Where cargo-edit caches responses, one directory per registry.
+#[cfg(feature = "index")]
+pub(crate) fn cache_root() -> Result<PathBuf> {
+    Ok(cargo_home()?.join("cargo-edit").join("cache"))
+}
+
+/// Where snapshots of the index of `registry` are installed.
+#[cfg(feature = "index")]
+pub(crate) fn snapshot_path_from_url(registry: &Url) -> Result<PathBuf> {
+    Ok(cargo_home()?
+        .join("cargo-edit")
+        .join("snapshots")
+        .join(short_name(registry)))
+}
+
+/// Where the git repository at `repo` is fetched to look up the manifest of a git dependency.
+#[cfg(feature = "fetch")]
+pub(crate) fn git_path_from_url(repo: &Url) -> Result<PathBuf> {
+    Ok(cargo_home()?
+        .join("cargo-edit")
+        .join("git")
+        .join(short_name(repo)))
+}
+
 #[derive(Debug, Deserialize)]
 struct Source {
     #[serde(rename = "replace-with")]
     replace_with: Option<String>,
     registry: Option<String>,
+    #[serde(rename = "local-registry")]
+    local_registry: Option<PathBuf>,
+    directory: Option<PathBuf>,
+    git: Option<String>,
+    /// The directory relative paths in this source are resolved against.
+    #[serde(skip)]
+    base: PathBuf,
+}
+
+impl Source {
+    fn registry(index: String) -> Self {
+        Source {
+            replace_with: None,
+            registry: Some(index),
+            local_registry: None,
+            directory: None,
+            git: None,
+            base: PathBuf::new(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -38,7 +90,7 @@ struct CargoConfig {
     source: HashMap<String, Source>,
 }
 
-fn cargo_home() -> Result<PathBuf> {
+pub(crate) fn cargo_home() -> Result<PathBuf> {
     let default_cargo_home = dirs_next::home_dir()
         .map(|x| x.join(".cargo"))
         .chain_err(|| ErrorKind::ReadHomeDirFailure)?;
@@ -48,91 +100,175 @@ fn cargo_home() -> Result<PathBuf> {
     Ok(cargo_home)
 }
 
+/// The cargo config files which apply to a manifest, the most specific first.
+///
+/// ref: https://doc.rust-lang.org/cargo/reference/config.html#hierarchical-structure
+pub(crate) fn config_paths(manifest_path: &Path) -> Result<Vec<PathBuf>> {
+    fn config_in(dir: &Path) -> Option<PathBuf> {
+        let config_path = dir.join("config");
+        if config_path.is_file() {
+            return Some(config_path);
+        }
+        let config_path = dir.join("config.toml");
+        if config_path.is_file() {
+            return Some(config_path);
+        }
+        None
+    }
+
+    let mut paths: Vec<PathBuf> = manifest_path
+        .parent()
+        .expect("there must be a parent directory")
+        .ancestors()
+        .filter_map(|work_dir| config_in(&work_dir.join(".cargo")))
+        .collect();
+    if let Some(config_path) = config_in(&cargo_home()?) {
+        if !paths.contains(&config_path) {
+            paths.push(config_path);
+        }
+    }
+    Ok(paths)
+}
+
 /// Find the URL of a registry
+///
+/// Source replacement (`[source.<name>] replace-with = ...`) is followed to the source cargo
+/// would actually build from. `local-registry` and `directory` sources are returned as `file://`
+/// URLs of their directory.
+///
+/// ref: https://doc.rust-lang.org/cargo/reference/source-replacement.html
 pub fn registry_url(manifest_path: &Path, registry: Option<&str>) -> Result<Url> {
-    // TODO support local registry sources, directory sources, git sources: https://doc.rust-lang.org/cargo/reference/source-replacement.html?highlight=replace-with#source-replacement
-    fn read_config(registries: &mut HashMap<String, Source>, path: impl AsRef<Path>) -> Result<()> {
-        // TODO unit test for source replacement
+    fn read_config(registries: &mut HashMap<String, Source>, path: &Path) -> Result<()> {
         let content = std::fs::read(path)?;
         let config =
             toml::from_slice::<CargoConfig>(&content).map_err(|_| ErrorKind::InvalidCargoConfig)?;
+        // Paths are relative to the directory containing the `.cargo` directory.
+        let base = path
+            .parent()
+            .and_then(Path::parent)
+            .unwrap_or_else(|| Path::new(""));
         for (key, value) in config.registries {
-            registries.entry(key).or_insert(Source {
-                registry: value.index,
-                replace_with: None,
-            });
+            if let Some(index) = value.index {
+                merge(registries, key, Source::registry(index));
+            }
         }
-        for (key, value) in config.source {
-            registries.entry(key).or_insert(value);
+        for (key, mut value) in config.source {
+            value.base = base.to_owned();
+            merge(registries, key, value);
         }
         Ok(())
     }
+    // Configs are read from the most specific one, so settings already present win.
+    fn merge(registries: &mut HashMap<String, Source>, key: String, value: Source) {
+        let source = match registries.get_mut(&key) {
+            Some(source) => source,
+            None => {
+                registries.insert(key, value);
+                return;
+            }
+        };
+        source.replace_with = source.replace_with.take().or(value.replace_with);
+        source.registry = source.registry.take().or(value.registry);
+        source.git = source.git.take().or(value.git);
+        if source.local_registry.is_none() && source.directory.is_none() {
+            source.local_registry = value.local_registry;
+            source.directory = value.directory;
+            source.base = value.base;
+        }
+    }
     // registry might be replaced with another source
     // it's looks like a singly linked list
     // put relations in this map.
     let mut registries: HashMap<String, Source> = HashMap::new();
-    // ref: https://doc.rust-lang.org/cargo/reference/config.html#hierarchical-structure
-    for work_dir in manifest_path
-        .parent()
-        .expect("there must be a parent directory")
-        .ancestors()
-    {
-        let work_cargo_dir = work_dir.join(".cargo");
-        let config_path = work_cargo_dir.join("config");
-        if config_path.is_file() {
-            read_config(&mut registries, config_path)?;
-        } else {
-            let config_path = work_cargo_dir.join("config.toml");
-            if config_path.is_file() {
-                read_config(&mut registries, config_path)?;
-            }
-        }
-    }
-
-    let default_cargo_home = cargo_home()?;
-    let default_config_path = default_cargo_home.join("config");
-    if default_config_path.is_file() {
-        read_config(&mut registries, default_config_path)?;
-    } else {
-        let default_config_path = default_cargo_home.join("config.toml");
-        if default_config_path.is_file() {
-            read_config(&mut registries, default_config_path)?;
-        }
+    for config_path in config_paths(manifest_path)? {
+        read_config(&mut registries, &config_path)?;
     }
 
     // find head of the relevant linked list
     let mut source = match registry {
-        Some(CRATES_IO_INDEX) | None => {
-            registries
-                .remove(CRATES_IO_REGISTRY)
-                .unwrap_or_else(|| Source {
-                    replace_with: None,
-                    registry: Some(CRATES_IO_INDEX.to_string()),
-                })
-        }
+        Some(CRATES_IO_INDEX) | None => registries
+            .remove(CRATES_IO_REGISTRY)
+            .unwrap_or_else(|| Source::registry(CRATES_IO_INDEX.to_string())),
         Some(r) => registries
             .remove(r)
             .chain_err(|| ErrorKind::NoSuchRegistryFound(r.to_string()))?,
     };
 
     // search this linked list and find the tail
-    while let Some(replace_with) = &source.replace_with {
+    let mut name = registry.unwrap_or(CRATES_IO_REGISTRY).to_owned();
+    while let Some(replace_with) = source.replace_with.take() {
         source = registries
-            .remove(replace_with)
-            .chain_err(|| ErrorKind::NoSuchSourceFound(replace_with.to_string()))?;
+            .remove(&replace_with)
+            .chain_err(|| ErrorKind::NoSuchSourceFound(replace_with.clone()))?;
+        name = replace_with;
     }
 
-    let registry_url = source
-        .registry
-        .and_then(|x| Url::parse(&x).ok())
-        .chain_err(|| ErrorKind::InvalidCargoConfig)?;
+    if let Some(registry) = source.registry {
+        return Url::parse(&registry).chain_err(|| ErrorKind::InvalidCargoConfig);
+    }
+    if let Some(path) = source.local_registry.or(source.directory) {
+        let path = source.base.join(path);
+        return path_to_url(&path, true).chain_err(|| ErrorKind::InvalidCargoConfig);
+    }
+    if source.git.is_some() {
+        return Err(ErrorKind::UnsupportedSource(name).into());
+    }
+    Err(ErrorKind::InvalidCargoConfig.into())
+}
+
+/// The names of the registries configured in `registries.<name>.index`, sorted, not counting
+/// crates.io.
+pub fn registry_names(manifest_path: &Path) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for path in config_paths(manifest_path)? {
+        let content = std::fs::read(&path)?;
+        let config =
+            toml::from_slice::<CargoConfig>(&content).map_err(|_| ErrorKind::InvalidCargoConfig)?;
+        names.extend(
+            config
+                .registries
+                .into_iter()
+                .filter(|(name, value)| value.index.is_some() && name != CRATES_IO_REGISTRY)
+                .map(|(name, _)| name),
+        );
+    }
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
 
-    Ok(registry_url)
+/// Find the name `registry` is configured under in `registries.<name>.index`
+///
+/// Returns `None` for crates.io, which needs no name.
+pub fn registry_name(manifest_path: &Path, registry: &Url) -> Result<Option<String>> {
+    let same_url = |a: &str, b: &str| a.trim_end_matches('/') == b.trim_end_matches('/');
+    if same_url(registry.as_str(), CRATES_IO_INDEX)
+        || same_url(registry.as_str(), CRATES_IO_SPARSE_INDEX)
+    {
+        return Ok(None);
+    }
+    for path in config_paths(manifest_path)? {
+        let content = std::fs::read(&path)?;
+        let config =
+            toml::from_slice::<CargoConfig>(&content).map_err(|_| ErrorKind::InvalidCargoConfig)?;
+        let name = config.registries.into_iter().find_map(|(name, value)| {
+            value
+                .index
+                .filter(|index| same_url(index, registry.as_str()))
+                .map(|_| name)
+        });
+        if name.is_some() {
+            return Ok(name);
+        }
+    }
+    Err(ErrorKind::NoSuchRegistryFound(registry.to_string()).into())
 }
 
+#[cfg(feature = "index")]
 fn short_name(registry: &Url) -> String {
     // ref: https://github.com/rust-lang/cargo/blob/4c1fa54d10f58d69ac9ff55be68e1b1c25ecb816/src/cargo/sources/registry/mod.rs#L386-L390
     #![allow(deprecated)]
+    use self::code_from_cargo::Kind;
     use std::hash::{Hash, Hasher, SipHasher};
 
     let mut hasher = SipHasher::new();
@@ -145,6 +281,69 @@ fn short_name(registry: &Url) -> String {
     format!("{}-{}", ident, hash)
 }
 
+/// The local path of a `file://` URL.
+///
+/// `url` only converts paths on platforms with native paths; elsewhere, like on
+/// `wasm32-unknown-unknown`, the URL path is taken as a Unix path.
+pub(crate) fn url_to_path(url: &Url) -> Option<PathBuf> {
+    #[cfg(any(unix, windows, target_os = "redox", target_os = "wasi"))]
+    return url.to_file_path().ok();
+    #[cfg(not(any(unix, windows, target_os = "redox", target_os = "wasi")))]
+    {
+        if url.scheme() != "file" {
+            return None;
+        }
+        // Undo the percent-encoding of the URL path.
+        let path = url.path().as_bytes();
+        let mut bytes = Vec::with_capacity(path.len());
+        let mut i = 0;
+        while i < path.len() {
+            let escaped = path
+                .get(i + 1..i + 3)
+                .filter(|_| path[i] == b'%')
+                .and_then(|hex| hex::decode(hex).ok());
+            match escaped {
+                Some(decoded) => {
+                    bytes.extend(decoded);
+                    i += 3;
+                }
+                None => {
+                    bytes.push(path[i]);
+                    i += 1;
+                }
+            }
+        }
+        String::from_utf8(bytes).ok().map(PathBuf::from)
+    }
+}
+
+/// The `file://` URL of the absolute path `path`, ending with a slash if `directory` is set.
+///
+/// Like [`url_to_path`], this falls back to Unix paths on platforms without native ones.
+pub(crate) fn path_to_url(path: &Path, directory: bool) -> Option<Url> {
+    #[cfg(any(unix, windows, target_os = "redox", target_os = "wasi"))]
+    return if directory {
+        Url::from_directory_path(path)
+    } else {
+        Url::from_file_path(path)
+    }
+    .ok();
+    #[cfg(not(any(unix, windows, target_os = "redox", target_os = "wasi")))]
+    {
+        let path = path.to_str()?;
+        if !path.starts_with('/') {
+            return None;
+        }
+        let slash = if directory && !path.ends_with('/') {
+            "/"
+        } else {
+            ""
+        };
+        Url::parse(&format!("file://{}{}", path, slash)).ok()
+    }
+}
+
+#[cfg(feature = "index")]
 #[cfg_attr(target_pointer_width = "64", test)]
 fn test_short_name() {
     fn test_helper(url: &str, name: &str) {
@@ -157,6 +356,89 @@ fn test_short_name() {
     );
 }
 
+#[test]
+fn test_source_replacement() {
+    fn resolve(config: &str, registry: Option<&str>) -> (tempfile::TempDir, Result<Url>) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join(".cargo")).unwrap();
+        std::fs::write(dir.path().join(".cargo").join("config.toml"), config).unwrap();
+        let url = registry_url(&dir.path().join("Cargo.toml"), registry);
+        (dir, url)
+    }
+
+    let (_dir, url) = resolve(
+        r#"
+        [source.crates-io]
+        replace-with = "mirror"
+
+        [source.mirror]
+        registry = "sparse+https://mirror.example.com/index/"
+        "#,
+        None,
+    );
+    assert_eq!(
+        url.unwrap().as_str(),
+        "sparse+https://mirror.example.com/index/"
+    );
+
+    let (dir, url) = resolve(
+        r#"
+        [source.crates-io]
+        replace-with = "vendored-sources"
+
+        [source.vendored-sources]
+        directory = "vendor"
+        "#,
+        None,
+    );
+    assert_eq!(
+        url.unwrap(),
+        Url::from_directory_path(dir.path().join("vendor")).unwrap()
+    );
+
+    let (_dir, url) = resolve(
+        r#"
+        [registries.internal]
+        index = "https://git.example.com/index"
+
+        [source.internal]
+        replace-with = "offline"
+
+        [source.offline]
+        local-registry = "/srv/registry"
+        "#,
+        Some("internal"),
+    );
+    assert_eq!(url.unwrap().as_str(), "file:///srv/registry/");
+
+    let (_dir, url) = resolve(
+        r#"
+        [source.crates-io]
+        replace-with = "vendored"
+
+        [source.vendored]
+        git = "https://git.example.com/vendored"
+        "#,
+        None,
+    );
+    assert!(matches!(
+        url.unwrap_err().kind(),
+        ErrorKind::UnsupportedSource(name) if name == "vendored"
+    ));
+
+    let (_dir, url) = resolve(
+        r#"
+        [source.crates-io]
+        replace-with = "missing"
+        "#,
+        None,
+    );
+    assert!(matches!(
+        url.unwrap_err().kind(),
+        ErrorKind::NoSuchSourceFound(name) if name == "missing"
+    ));
+}
+
 mod code_from_cargo {
     #![allow(dead_code)]
 
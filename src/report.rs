@@ -0,0 +1,528 @@
+//! Reports of what a subcommand changed, rendered for people or for tools.
+//!
+//! `cargo add`, `cargo rm` and `cargo upgrade` print their report in the format given with
+//! `--output`. The JSON form is versioned by [`REPORT_SCHEMA_VERSION`]: fields are only added to it
+//! within a version, never renamed or removed.
+use crate::diff::{manifest_diff, ManifestChange, ManifestChanges};
+use crate::errors::*;
+use crate::manifest::{get_name_from_manifest, Manifest};
+use serde_json::{json, Value};
+use std::fmt::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The version of the JSON report schema.
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// How a report is printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    /// One line per change, for people
+    #[default]
+    Plain,
+    /// A single JSON document, for tools
+    Json,
+    /// A table per manifest, e.g. for pull request descriptions
+    Markdown,
+}
+
+impl OutputFormat {
+    /// The names of the formats, as accepted by `--output`.
+    pub const NAMES: &'static [&'static str] = &["plain", "json", "markdown"];
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "plain" => Ok(OutputFormat::Plain),
+            "json" => Ok(OutputFormat::Json),
+            "markdown" => Ok(OutputFormat::Markdown),
+            _ => Err(format!(
+                "Unknown output format `{}`, expected one of {}",
+                s,
+                OutputFormat::NAMES.join(", ")
+            )
+            .into()),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutputFormat::Plain => "plain",
+            OutputFormat::Json => "json",
+            OutputFormat::Markdown => "markdown",
+        })
+    }
+}
+
+/// Why `cargo upgrade` left a dependency alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkipReason {
+    /// The crate is `pinned` in the configuration
+    Pinned,
+    /// The crate was excluded, with `--exclude` or in the configuration
+    Excluded,
+    /// Other crates were named to be upgraded
+    NotSelected,
+    /// The crate is overridden in a `[patch]` table of the workspace root
+    Patched,
+    /// The upgrade is semver compatible, and `--skip-compatible` was given
+    SkipCompatible,
+    /// The newer versions don't support the Rust version given with `--rust-version`
+    RustVersion,
+    /// The newer versions are yanked
+    Yanked,
+}
+
+impl SkipReason {
+    /// The name of the reason in the JSON report, e.g. `not-selected`.
+    pub fn name(self) -> &'static str {
+        match self {
+            SkipReason::Pinned => "pinned",
+            SkipReason::Excluded => "excluded",
+            SkipReason::NotSelected => "not-selected",
+            SkipReason::Patched => "patched",
+            SkipReason::SkipCompatible => "skip-compatible",
+            SkipReason::RustVersion => "rust-version",
+            SkipReason::Yanked => "yanked",
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Where a dependency stands after an upgrade: its requirement before and after, and the versions
+/// it could have been upgraded to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyStatus {
+    /// The dependency table, e.g. `dependencies` or `target.'cfg(unix)'.dependencies`
+    pub table: String,
+    /// The key of the dependency, which is the name of the crate unless it is renamed
+    pub name: String,
+    /// The name of the crate
+    pub package: String,
+    /// The requirement before the upgrade
+    pub current: Option<String>,
+    /// The requirement after the upgrade
+    pub new: Option<String>,
+    /// The newest version `current` allows, if known
+    pub latest_compatible: Option<String>,
+    /// The newest version, if known
+    pub latest: Option<String>,
+    /// Why the dependency was left alone, if it was
+    pub skipped: Option<SkipReason>,
+}
+
+/// The changes made to one manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestReport {
+    /// The manifest changed
+    pub manifest_path: PathBuf,
+    /// The name of its package, unless it is a virtual manifest
+    pub package: Option<String>,
+    /// What changed
+    pub changes: ManifestChanges,
+    /// Where each of its dependencies stands, for commands which report it like `upgrade`
+    pub dependencies: Vec<DependencyStatus>,
+}
+
+/// What a subcommand changed, in every manifest it touched.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{OutputFormat, Report};
+///
+///   let old = "[dependencies]\nserde = \"1.0\"\n";
+///   let new = "[dependencies]\nserde = \"1.1\"\nlog = \"0.4\"\n";
+///   let mut report = Report::new("upgrade");
+///   report.add_diff("Cargo.toml", old, new).unwrap();
+///
+///   let json: serde_json::Value = serde_json::from_str(&report.render(OutputFormat::Json)).unwrap();
+///   assert_eq!(json["manifests"][0]["changes"][0]["kind"], "dependency-added");
+///   assert_eq!(json["manifests"][0]["changes"][1]["new"], "1.1");
+///   assert!(report.render(OutputFormat::Markdown).contains("| dependencies | serde | 1.0 → 1.1 |"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// The subcommand, e.g. `upgrade`
+    pub command: String,
+    /// Whether the changes were only previewed, not written
+    pub dry_run: bool,
+    /// The manifests changed, in the order they were handled
+    pub manifests: Vec<ManifestReport>,
+}
+
+impl Report {
+    /// An empty report for `command`.
+    pub fn new(command: &str) -> Self {
+        Report {
+            command: command.to_owned(),
+            dry_run: false,
+            manifests: Vec::new(),
+        }
+    }
+
+    /// Mark the changes as previewed rather than written.
+    pub fn set_dry_run(&mut self, dry_run: bool) -> &mut Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Record the changes from `old` to `new`, the contents of the manifest at `manifest_path`
+    /// before and after the command.
+    pub fn add_diff(
+        &mut self,
+        manifest_path: impl Into<PathBuf>,
+        old: &str,
+        new: &str,
+    ) -> Result<&mut Self> {
+        let changes = manifest_diff(old, new)?;
+        let package = get_name_from_manifest(&new.parse::<Manifest>()?).ok();
+        self.manifests.push(ManifestReport {
+            manifest_path: manifest_path.into(),
+            package,
+            changes,
+            dependencies: Vec::new(),
+        });
+        Ok(self)
+    }
+
+    /// Record where the dependencies of the manifest at `manifest_path` stand, once its changes
+    /// are added with [`Report::add_diff`].
+    pub fn add_dependencies(
+        &mut self,
+        manifest_path: &Path,
+        dependencies: Vec<DependencyStatus>,
+    ) -> &mut Self {
+        if let Some(manifest) = self
+            .manifests
+            .iter_mut()
+            .rev()
+            .find(|manifest| manifest.manifest_path == manifest_path)
+        {
+            manifest.dependencies.extend(dependencies);
+        }
+        self
+    }
+
+    /// Whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.manifests
+            .iter()
+            .all(|manifest| manifest.changes.is_empty())
+    }
+
+    /// The report as JSON, following [`REPORT_SCHEMA_VERSION`].
+    pub fn to_json(&self) -> Value {
+        let manifests: Vec<Value> = self
+            .manifests
+            .iter()
+            .map(|manifest| {
+                json!({
+                    "manifest_path": manifest.manifest_path,
+                    "package": manifest.package,
+                    "changes": manifest.changes.iter().map(change_to_json).collect::<Vec<_>>(),
+                    "dependencies": manifest.dependencies.iter().map(status_to_json).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        json!({
+            "schema": REPORT_SCHEMA_VERSION,
+            "command": self.command,
+            "dry_run": self.dry_run,
+            "manifests": manifests,
+        })
+    }
+
+    /// The report in `format`, ending with a newline.
+    pub fn render(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Plain => self.to_plain(),
+            OutputFormat::Json => format!("{}\n", self.to_json()),
+            OutputFormat::Markdown => self.to_markdown(),
+        }
+    }
+
+    fn to_plain(&self) -> String {
+        let mut out = String::new();
+        for manifest in &self.manifests {
+            if self.manifests.len() > 1 {
+                let _ = writeln!(out, "{}:", heading(manifest));
+            }
+            if manifest.changes.is_empty() {
+                out.push_str("no changes\n");
+            }
+            for change in manifest.changes.iter() {
+                let _ = writeln!(out, "{}", change);
+            }
+        }
+        if self.dry_run {
+            out.push_str("(dry run, nothing was written)\n");
+        }
+        out
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        for manifest in &self.manifests {
+            let _ = writeln!(out, "### {}\n", heading(manifest));
+            if manifest.changes.is_empty() {
+                out.push_str("No changes.\n\n");
+                continue;
+            }
+            out.push_str("| Table | Name | Change |\n|---|---|---|\n");
+            for change in manifest.changes.iter() {
+                let (table, name, change) = change_to_columns(change);
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {} |",
+                    escape(&table),
+                    escape(&name),
+                    escape(&change)
+                );
+            }
+            out.push('\n');
+        }
+        if self.dry_run {
+            out.push_str("_Dry run: nothing was written._\n");
+        }
+        out
+    }
+}
+
+fn heading(manifest: &ManifestReport) -> String {
+    match manifest.package {
+        Some(ref package) => format!("`{}` ({})", package, manifest.manifest_path.display()),
+        None => format!("`{}`", manifest.manifest_path.display()),
+    }
+}
+
+fn change_to_json(change: &ManifestChange) -> Value {
+    match change {
+        ManifestChange::DepAdded {
+            table,
+            name,
+            version,
+        } => json!({"kind": "dependency-added", "table": table, "name": name, "version": version}),
+        ManifestChange::DepRemoved {
+            table,
+            name,
+            version,
+        } => {
+            json!({"kind": "dependency-removed", "table": table, "name": name, "version": version})
+        }
+        ManifestChange::DepVersionChanged {
+            table,
+            name,
+            old,
+            new,
+        } => json!({
+            "kind": "version-changed", "table": table, "name": name, "old": old, "new": new,
+        }),
+        ManifestChange::FeatureToggled {
+            table,
+            name,
+            feature,
+            enabled,
+        } => json!({
+            "kind": "feature-toggled", "table": table, "name": name, "feature": feature,
+            "enabled": enabled,
+        }),
+        ManifestChange::DepChanged {
+            table,
+            name,
+            old,
+            new,
+        } => json!({
+            "kind": "dependency-changed", "table": table, "name": name, "old": old, "new": new,
+        }),
+        ManifestChange::MetadataChanged { key, old, new } => {
+            json!({"kind": "metadata-changed", "key": key, "old": old, "new": new})
+        }
+    }
+}
+
+fn status_to_json(status: &DependencyStatus) -> Value {
+    json!({
+        "table": status.table,
+        "name": status.name,
+        "package": status.package,
+        "current": status.current,
+        "new": status.new,
+        "latest_compatible": status.latest_compatible,
+        "latest": status.latest,
+        "skipped": status.skipped.map(SkipReason::name),
+    })
+}
+
+fn change_to_columns(change: &ManifestChange) -> (String, String, String) {
+    let or_any = |version: &Option<String>| version.clone().unwrap_or_else(|| "*".to_owned());
+    match change {
+        ManifestChange::DepAdded {
+            table,
+            name,
+            version,
+        } => (
+            table.clone(),
+            name.clone(),
+            format!("added {}", or_any(version)),
+        ),
+        ManifestChange::DepRemoved {
+            table,
+            name,
+            version,
+        } => (
+            table.clone(),
+            name.clone(),
+            format!("removed {}", or_any(version)),
+        ),
+        ManifestChange::DepVersionChanged {
+            table,
+            name,
+            old,
+            new,
+        } => (
+            table.clone(),
+            name.clone(),
+            format!("{} → {}", or_any(old), or_any(new)),
+        ),
+        ManifestChange::FeatureToggled {
+            table,
+            name,
+            feature,
+            enabled,
+        } => (
+            table.clone(),
+            name.clone(),
+            format!(
+                "feature `{}` {}",
+                feature,
+                if *enabled { "enabled" } else { "disabled" }
+            ),
+        ),
+        ManifestChange::DepChanged {
+            table,
+            name,
+            old,
+            new,
+        } => (table.clone(), name.clone(), format!("{} → {}", old, new)),
+        ManifestChange::MetadataChanged { key, old, new } => (
+            String::new(),
+            key.clone(),
+            format!(
+                "{} → {}",
+                old.as_deref().unwrap_or("(none)"),
+                new.as_deref().unwrap_or("(none)")
+            ),
+        ),
+    }
+}
+
+/// Keep `|` from ending a table cell.
+fn escape(cell: &str) -> String {
+    cell.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str =
+        "[package]\nname = \"app\"\n\n[dependencies]\nserde = \"1.0\"\nrand = \"0.7\"\n";
+    const NEW: &str =
+        "[package]\nname = \"app\"\n\n[dependencies]\nserde = \"1.1\"\n\n[dev-dependencies]\nlog = \"0.4\"\n";
+
+    const LIB: &str = "[workspace]\nmembers = [\"app\"]\n";
+
+    fn report() -> Report {
+        let mut report = Report::new("upgrade");
+        report
+            .add_diff("app/Cargo.toml", OLD, NEW)
+            .unwrap()
+            .add_diff("lib/Cargo.toml", LIB, LIB)
+            .unwrap()
+            .set_dry_run(true);
+        report
+    }
+
+    #[test]
+    fn renders_json() {
+        let json = report().to_json();
+        assert_eq!(json["schema"], REPORT_SCHEMA_VERSION);
+        assert_eq!(json["command"], "upgrade");
+        assert_eq!(json["dry_run"], true);
+        assert_eq!(
+            json["manifests"][0]["changes"],
+            json!([
+                {"kind": "dependency-removed", "table": "dependencies", "name": "rand",
+                 "version": "0.7"},
+                {"kind": "version-changed", "table": "dependencies", "name": "serde",
+                 "old": "1.0", "new": "1.1"},
+                {"kind": "dependency-added", "table": "dev-dependencies", "name": "log",
+                 "version": "0.4"},
+            ])
+        );
+        assert_eq!(json["manifests"][1]["package"], Value::Null);
+        assert_eq!(json["manifests"][1]["changes"], json!([]));
+    }
+
+    #[test]
+    fn renders_dependency_statuses() {
+        let mut report = report();
+        report.add_dependencies(
+            Path::new("app/Cargo.toml"),
+            vec![DependencyStatus {
+                table: "dependencies".to_owned(),
+                name: "serde".to_owned(),
+                package: "serde".to_owned(),
+                current: Some("1.0".to_owned()),
+                new: Some("1.0".to_owned()),
+                latest_compatible: Some("1.0.5".to_owned()),
+                latest: Some("2.0.0".to_owned()),
+                skipped: Some(SkipReason::Pinned),
+            }],
+        );
+
+        let json = report.to_json();
+        assert_eq!(
+            json["manifests"][0]["dependencies"],
+            json!([{
+                "table": "dependencies", "name": "serde", "package": "serde", "current": "1.0",
+                "new": "1.0", "latest_compatible": "1.0.5", "latest": "2.0.0", "skipped": "pinned",
+            }])
+        );
+        assert_eq!(json["manifests"][1]["dependencies"], json!([]));
+    }
+
+    #[test]
+    fn renders_plain_and_markdown() {
+        let plain = report().render(OutputFormat::Plain);
+        assert!(plain.starts_with("`app` (app/Cargo.toml):\n"));
+        assert!(plain.contains("dependencies: serde 1.0 -> 1.1\n"));
+        assert!(plain.contains("`lib/Cargo.toml`:\nno changes\n"));
+        assert!(plain.ends_with("(dry run, nothing was written)\n"));
+
+        let markdown = report().render(OutputFormat::Markdown);
+        assert!(markdown.starts_with("### `app` (app/Cargo.toml)\n\n| Table | Name | Change |\n"));
+        assert!(markdown.contains("| dev-dependencies | log | added 0.4 |\n"));
+        assert!(markdown.contains("### `lib/Cargo.toml`\n\nNo changes.\n"));
+    }
+
+    #[test]
+    fn parses_formats() {
+        for name in OutputFormat::NAMES {
+            assert_eq!(name.parse::<OutputFormat>().unwrap().to_string(), *name);
+        }
+        assert!("yaml".parse::<OutputFormat>().is_err());
+    }
+}
@@ -0,0 +1,324 @@
+//! Where the source of a crate lives, from the `repository` URL of its manifest.
+//!
+//! Crates give their repository in whatever form they like: with or without `.git`, as an SSH
+//! remote, or linking a subdirectory of a monorepo. [`Repository::parse`] brings them to one form,
+//! from which links to files and release notes on known forges are derived.
+use std::fmt;
+use url::Url;
+
+/// The changelog files looked for, in order of preference.
+const CHANGELOG_FILES: &[&str] = &[
+    "CHANGELOG.md",
+    "CHANGES.md",
+    "RELEASES.md",
+    "HISTORY.md",
+    "NEWS.md",
+];
+
+/// A code forge with a known URL layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Forge {
+    /// github.com
+    GitHub,
+    /// gitlab.com, or a self-hosted GitLab on a `gitlab.` host
+    GitLab,
+}
+
+/// A repository, as given by the `repository` field of a crate's manifest.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{Forge, Repository};
+///
+///   let repo = Repository::parse("https://github.com/tokio-rs/tokio/tree/master/tokio-util")
+///       .unwrap();
+///   assert_eq!(repo.forge(), Some(Forge::GitHub));
+///   assert_eq!((repo.owner.as_str(), repo.name.as_str()), ("tokio-rs", "tokio"));
+///   assert_eq!(repo.subdir.as_deref(), Some("tokio-util"));
+///   assert_eq!(repo.url().as_str(), "https://github.com/tokio-rs/tokio");
+///
+///   let repo = Repository::parse("git@github.com:serde-rs/serde.git").unwrap();
+///   assert_eq!(repo.to_string(), "https://github.com/serde-rs/serde");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Repository {
+    /// The host, lowercased and without `www.`
+    pub host: String,
+    /// The user or organization owning the repository; GitLab subgroups are separated by `/`
+    pub owner: String,
+    /// The name of the repository, without `.git`
+    pub name: String,
+    /// The directory of the crate within the repository, if the URL links one
+    pub subdir: Option<String>,
+}
+
+impl Repository {
+    /// Normalize a repository URL, which may be an `https`, `git`, `ssh` or `git+` URL, or an
+    /// scp-like SSH remote like `git@github.com:owner/repo.git`.
+    ///
+    /// `None` if it has no host, or no owner and name.
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = url.trim();
+        let url = url.strip_prefix("git+").unwrap_or(url);
+        let url = match scp_like(url) {
+            Some((host, path)) => Url::parse(&format!("ssh://{}/{}", host, path)).ok()?,
+            None => Url::parse(url).ok()?,
+        };
+        let host = url.host_str()?.to_ascii_lowercase();
+        let host = host.strip_prefix("www.").unwrap_or(&host).to_owned();
+        let segments: Vec<&str> = url
+            .path_segments()?
+            .filter(|segment| !segment.is_empty())
+            .collect();
+
+        let forge = forge_of(&host);
+        // GitLab nests projects in subgroups, and separates what follows the project with `-`.
+        let (path, rest) = match forge {
+            Some(Forge::GitLab) => {
+                let end = segments
+                    .iter()
+                    .position(|segment| *segment == "-")
+                    .unwrap_or(segments.len());
+                (&segments[..end], segments.get(end + 1..).unwrap_or(&[]))
+            }
+            _ => segments.split_at(segments.len().min(2)),
+        };
+        let (name, owner) = path.split_last()?;
+        if owner.is_empty() {
+            return None;
+        }
+        let name = name.strip_suffix(".git").unwrap_or(name);
+        // `tree/<branch>/<dir>` links a directory, `blob/<branch>/<file>` a file in it.
+        let subdir = match rest {
+            ["tree", _, dir @ ..] if !dir.is_empty() => Some(dir.join("/")),
+            ["blob", _, dir @ .., _] if !dir.is_empty() => Some(dir.join("/")),
+            _ => None,
+        };
+        if name.is_empty() {
+            return None;
+        }
+        Some(Repository {
+            host,
+            owner: owner.join("/"),
+            name: name.to_owned(),
+            subdir,
+        })
+    }
+
+    /// The forge hosting the repository, if its URL layout is known.
+    pub fn forge(&self) -> Option<Forge> {
+        forge_of(&self.host)
+    }
+
+    /// The web page of the repository, e.g. `https://github.com/serde-rs/serde`.
+    pub fn url(&self) -> Url {
+        Url::parse(&format!(
+            "https://{}/{}/{}",
+            self.host, self.owner, self.name
+        ))
+        .expect("host and path were parsed from a URL")
+    }
+
+    /// The web page of `path` on the default branch; `path` is relative to the root of the
+    /// repository, not to [`subdir`](Repository::subdir).
+    pub fn file_url(&self, path: &str) -> Option<Url> {
+        let prefix = match self.forge()? {
+            Forge::GitHub => "blob",
+            Forge::GitLab => "-/blob",
+        };
+        self.join(&format!("{}/HEAD/{}", prefix, path))
+    }
+
+    /// The raw content of `path` on the default branch, as [`file_url`](Repository::file_url).
+    pub fn raw_file_url(&self, path: &str) -> Option<Url> {
+        match self.forge()? {
+            Forge::GitHub => Url::parse(&format!(
+                "https://raw.githubusercontent.com/{}/{}/HEAD/{}",
+                self.owner, self.name, path
+            ))
+            .ok(),
+            Forge::GitLab => self.join(&format!("-/raw/HEAD/{}", path)),
+        }
+    }
+
+    /// The list of releases.
+    pub fn releases_url(&self) -> Option<Url> {
+        match self.forge()? {
+            Forge::GitHub => self.join("releases"),
+            Forge::GitLab => self.join("-/releases"),
+        }
+    }
+
+    /// The release made from `tag`.
+    pub fn release_url(&self, tag: &str) -> Option<Url> {
+        match self.forge()? {
+            Forge::GitHub => self.join(&format!("releases/tag/{}", tag)),
+            Forge::GitLab => self.join(&format!("-/releases/{}", tag)),
+        }
+    }
+
+    /// The paths a changelog may be at, those in [`subdir`](Repository::subdir) first.
+    pub fn changelog_paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        if let Some(ref subdir) = self.subdir {
+            paths.extend(
+                CHANGELOG_FILES
+                    .iter()
+                    .map(|file| format!("{}/{}", subdir, file)),
+            );
+        }
+        paths.extend(CHANGELOG_FILES.iter().map(|file| (*file).to_owned()));
+        paths
+    }
+
+    /// The tags a release of `version` of the crate `crate_name` may have, most common first.
+    pub fn release_tags(&self, crate_name: &str, version: &semver::Version) -> Vec<String> {
+        vec![
+            format!("v{}", version),
+            version.to_string(),
+            format!("{}-v{}", crate_name, version),
+            format!("{}-{}", crate_name, version),
+        ]
+    }
+
+    fn join(&self, path: &str) -> Option<Url> {
+        Url::parse(&format!("{}/{}", self.url(), path)).ok()
+    }
+}
+
+impl fmt::Display for Repository {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.url().fmt(f)
+    }
+}
+
+/// Where the release notes of a crate are.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Changelog {
+    /// A changelog file in the repository
+    File(Url),
+    /// The release of the version on the forge
+    Release(Url),
+    /// The list of releases on the forge, when nothing more specific was found
+    Releases(Url),
+}
+
+impl Changelog {
+    /// The link to the release notes.
+    pub fn url(&self) -> &Url {
+        match self {
+            Changelog::File(url) | Changelog::Release(url) | Changelog::Releases(url) => url,
+        }
+    }
+}
+
+fn forge_of(host: &str) -> Option<Forge> {
+    match host {
+        "github.com" => Some(Forge::GitHub),
+        "gitlab.com" => Some(Forge::GitLab),
+        _ if host.starts_with("gitlab.") => Some(Forge::GitLab),
+        _ => None,
+    }
+}
+
+/// The host and path of an scp-like remote, `[user@]host:path`.
+fn scp_like(url: &str) -> Option<(&str, &str)> {
+    if url.contains("://") {
+        return None;
+    }
+    let (host, path) = url.split_once(':')?;
+    let host = host.rsplit('@').next()?;
+    if host.is_empty() || host.contains('/') || path.is_empty() {
+        return None;
+    }
+    Some((host, path.trim_start_matches('/')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(url: &str) -> (String, String, String, Option<String>) {
+        let repo = Repository::parse(url).unwrap();
+        (repo.host, repo.owner, repo.name, repo.subdir)
+    }
+
+    #[test]
+    fn normalizes_repository_urls() {
+        let serde = (
+            "github.com".to_owned(),
+            "serde-rs".to_owned(),
+            "serde".to_owned(),
+            None,
+        );
+        for url in &[
+            "https://github.com/serde-rs/serde",
+            "https://github.com/serde-rs/serde/",
+            "https://www.github.com/serde-rs/serde.git",
+            "http://GitHub.com/serde-rs/serde",
+            "git+https://github.com/serde-rs/serde.git",
+            "git://github.com/serde-rs/serde.git",
+            "ssh://git@github.com/serde-rs/serde.git",
+            "git@github.com:serde-rs/serde.git",
+            "https://github.com/serde-rs/serde#readme",
+        ] {
+            assert_eq!(parse(url), serde, "{}", url);
+        }
+
+        let (_, owner, name, subdir) =
+            parse("https://github.com/rust-lang/cargo/blob/master/crates/cargo-util/README.md");
+        assert_eq!((owner.as_str(), name.as_str()), ("rust-lang", "cargo"));
+        assert_eq!(subdir.as_deref(), Some("crates/cargo-util"));
+        let (host, owner, name, subdir) =
+            parse("https://gitlab.com/group/subgroup/project/-/tree/main/crates/foo");
+        assert_eq!(host, "gitlab.com");
+        assert_eq!(
+            (owner.as_str(), name.as_str()),
+            ("group/subgroup", "project")
+        );
+        assert_eq!(subdir.as_deref(), Some("crates/foo"));
+
+        assert_eq!(Repository::parse("https://github.com/serde-rs"), None);
+        assert_eq!(Repository::parse("not a url"), None);
+        assert_eq!(Repository::parse(""), None);
+    }
+
+    #[test]
+    fn links_into_known_forges() {
+        let repo =
+            Repository::parse("https://github.com/tokio-rs/tokio/tree/master/tokio").unwrap();
+        assert_eq!(
+            repo.raw_file_url("tokio/CHANGELOG.md").unwrap().as_str(),
+            "https://raw.githubusercontent.com/tokio-rs/tokio/HEAD/tokio/CHANGELOG.md"
+        );
+        assert_eq!(
+            repo.file_url("tokio/CHANGELOG.md").unwrap().as_str(),
+            "https://github.com/tokio-rs/tokio/blob/HEAD/tokio/CHANGELOG.md"
+        );
+        assert_eq!(
+            repo.release_url("tokio-1.0.0").unwrap().as_str(),
+            "https://github.com/tokio-rs/tokio/releases/tag/tokio-1.0.0"
+        );
+        assert_eq!(repo.changelog_paths()[0], "tokio/CHANGELOG.md");
+        assert_eq!(
+            repo.changelog_paths()[CHANGELOG_FILES.len()],
+            "CHANGELOG.md"
+        );
+
+        let repo = Repository::parse("https://gitlab.example.com/a/b").unwrap();
+        assert_eq!(
+            repo.raw_file_url("CHANGELOG.md").unwrap().as_str(),
+            "https://gitlab.example.com/a/b/-/raw/HEAD/CHANGELOG.md"
+        );
+        assert_eq!(
+            repo.releases_url().unwrap().as_str(),
+            "https://gitlab.example.com/a/b/-/releases"
+        );
+
+        let repo = Repository::parse("https://git.sr.ht/~user/project").unwrap();
+        assert_eq!(repo.forge(), None);
+        assert_eq!(repo.raw_file_url("CHANGELOG.md"), None);
+        assert_eq!(repo.url().as_str(), "https://git.sr.ht/~user/project");
+    }
+}
@@ -0,0 +1,176 @@
+//! Retrying HTTP requests which failed for reasons likely to go away, and spacing out requests
+//! to the same host.
+//!
+//! Connection failures, `429 Too Many Requests` and `5xx` answers are retried with exponential
+//! backoff, or after the `Retry-After` the server asks for. A host which asked us to back off is
+//! left alone by every thread until then, so concurrent lookups don't keep hammering it.
+//!
+//! Once the operation is [cancelled](crate::cancel), no further request is sent, and waiting for
+//! one stops.
+use crate::cancel::{check_cancelled, sleep};
+use crate::cargo_flags::ensure_online;
+use crate::errors::*;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// The environment variable cargo reads its `net.retry` setting from.
+const NET_RETRY_ENV: &str = "CARGO_NET_RETRY";
+
+/// How failed requests are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How often a request is retried after the first attempt failed
+    pub retries: u32,
+    /// How long to wait before the first retry; every further retry waits twice as long
+    pub initial_backoff: Duration,
+    /// The longest to wait before a retry, also capping `Retry-After`
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// Retries as often as cargo does, following `CARGO_NET_RETRY` (3 unless set).
+    fn default() -> Self {
+        RetryPolicy {
+            retries: std::env::var(NET_RETRY_ENV)
+                .ok()
+                .and_then(|retries| retries.trim().parse().ok())
+                .unwrap_or(3),
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry.
+    pub fn none() -> Self {
+        RetryPolicy {
+            retries: 0,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// The wait before retry number `retry`, starting at 1.
+    fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .checked_mul(2u32.saturating_pow(retry - 1))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+/// Whether a response is worth retrying.
+fn is_transient(res: &ureq::Response) -> bool {
+    res.synthetic_error().is_some() || matches!(res.status(), 429 | 500 | 502 | 503 | 504)
+}
+
+/// The earliest time the next request to each host may be sent.
+fn not_before() -> &'static Mutex<HashMap<String, Instant>> {
+    static NOT_BEFORE: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+    NOT_BEFORE.get_or_init(Default::default)
+}
+
+/// Wait for our turn to send a request to `host`, keeping requests `min_interval` apart.
+fn wait_for_turn(host: &str, min_interval: Duration) -> Result<()> {
+    let slot = {
+        let mut not_before = not_before()
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = Instant::now();
+        let slot = not_before.get(host).map_or(now, |t| (*t).max(now));
+        not_before.insert(host.to_owned(), slot + min_interval);
+        slot
+    };
+    sleep(slot.saturating_duration_since(Instant::now()))
+}
+
+/// Keep every request to `host` waiting for `delay`.
+fn back_off(host: &str, delay: Duration) {
+    let mut not_before = not_before()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let until = Instant::now() + delay;
+    let entry = not_before.entry(host.to_owned()).or_insert(until);
+    *entry = (*entry).max(until);
+}
+
+/// Send `req` to `url`, retrying transient failures following `policy`, with requests to the same
+/// host at least `min_interval` apart.
+///
+/// The last response is returned as is, so callers handle errors as for a single attempt; only a
+/// cancellation, or the network being off limits (see [`set_offline`]), is an error.
+///
+/// [`set_offline`]: crate::set_offline
+pub(crate) fn call_with_retry(
+    req: &mut ureq::Request,
+    url: &Url,
+    policy: &RetryPolicy,
+    min_interval: Duration,
+) -> Result<ureq::Response> {
+    let host = format!(
+        "{}:{}",
+        url.host_str().unwrap_or_default(),
+        url.port_or_known_default().unwrap_or_default()
+    );
+    ensure_online(url.as_str())?;
+    let _span = span!(DEBUG, "request", %url);
+    let mut retry = 0;
+    loop {
+        wait_for_turn(&host, min_interval)?;
+        let res = req.call();
+        check_cancelled()?;
+        event!(
+            DEBUG,
+            status = res.status(),
+            error = ?res.synthetic_error().as_ref().map(ToString::to_string),
+            "response"
+        );
+        if retry >= policy.retries || !is_transient(&res) {
+            return Ok(res);
+        }
+        retry += 1;
+        let delay = res
+            .header("Retry-After")
+            .and_then(|secs| secs.trim().parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| policy.backoff(retry))
+            .min(policy.max_backoff);
+        event!(WARN, %url, retry, ?delay, "retrying after a transient failure");
+        back_off(&host, delay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_limit() {
+        let policy = RetryPolicy {
+            retries: 10,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+        };
+        let waits: Vec<u64> = (1..=5)
+            .map(|retry| policy.backoff(retry).as_secs())
+            .collect();
+        assert_eq!(waits, [1, 2, 4, 5, 5]);
+        assert_eq!(policy.backoff(40), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn requests_to_a_host_are_spaced_out() {
+        let start = Instant::now();
+        for _ in 0..3 {
+            wait_for_turn("spaced.invalid", Duration::from_millis(50)).unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        back_off("spaced.invalid", Duration::from_millis(100));
+        let start = Instant::now();
+        wait_for_turn("spaced.invalid", Duration::from_millis(0)).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+}
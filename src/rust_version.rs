@@ -0,0 +1,251 @@
+//! The `rust-version` of a package, parsed and compared the way cargo does.
+use crate::errors::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// A minimum supported Rust version, e.g. `1.70` or `1.70.1`.
+///
+/// Like cargo, the minor and patch components are optional, and the precision a version was
+/// written with is kept: `1.70` displays as `1.70`, not `1.70.0`. Versions are ordered
+/// component-wise with a missing component sorting before any present one, so
+/// `1.70 < 1.70.0 < 1.70.1`. Use [`RustVersion::is_compatible_with`] to check a toolchain
+/// against it, which treats missing components as `0`.
+///
+/// Pre-release and build metadata are rejected, as are requirement operators and wildcards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RustVersion {
+    /// The major version, always `1` in practice
+    pub major: u64,
+    /// The minor version, if given
+    pub minor: Option<u64>,
+    /// The patch version, if given
+    pub patch: Option<u64>,
+}
+
+impl RustVersion {
+    /// A version with all components given.
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        RustVersion {
+            major,
+            minor: Some(minor),
+            patch: Some(patch),
+        }
+    }
+
+    /// Whether a toolchain of version `rustc` satisfies this minimum.
+    ///
+    /// Pre-release toolchains, like a nightly, are treated as the release they lead up to.
+    pub fn is_compatible_with(&self, rustc: &semver::Version) -> bool {
+        (rustc.major, rustc.minor, rustc.patch)
+            >= (self.major, self.minor.unwrap_or(0), self.patch.unwrap_or(0))
+    }
+
+    /// The version with missing components filled in with `0`.
+    pub fn to_version(&self) -> semver::Version {
+        semver::Version::new(self.major, self.minor.unwrap_or(0), self.patch.unwrap_or(0))
+    }
+}
+
+impl fmt::Display for RustVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.major)?;
+        if let Some(minor) = self.minor {
+            write!(f, ".{}", minor)?;
+            if let Some(patch) = self.patch {
+                write!(f, ".{}", patch)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for RustVersion {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let invalid = |reason: &str| -> Error {
+            ErrorKind::InvalidRustVersion(input.to_owned(), reason.to_owned()).into()
+        };
+        const EXPECTED: &str = "expected a version like \"1.32\"";
+
+        let trimmed = input.trim();
+        if trimmed.starts_with(|c: char| "=^~<>".contains(c)) || trimmed.contains(['*', 'x', 'X']) {
+            return Err(invalid(&format!(
+                "unexpected version requirement, {}",
+                EXPECTED
+            )));
+        }
+        let (version, build) = match trimmed.split_once('+') {
+            Some((version, build)) => (version, Some(build)),
+            None => (trimmed, None),
+        };
+        let (version, pre) = match version.split_once('-') {
+            Some((version, pre)) => (version, Some(pre)),
+            None => (version, None),
+        };
+        if pre.is_some() {
+            return Err(invalid(&format!(
+                "unexpected prerelease field, {}",
+                EXPECTED
+            )));
+        }
+        if build.is_some() {
+            return Err(invalid(&format!("unexpected build field, {}", EXPECTED)));
+        }
+
+        let mut components = version.split('.');
+        let mut parse = |position: &str| -> Result<Option<u64>> {
+            let part = match components.next() {
+                Some(part) => part,
+                None => return Ok(None),
+            };
+            if part.is_empty() {
+                return Err(invalid(&format!(
+                    "unexpected end of input while parsing {} version number",
+                    position
+                )));
+            }
+            if !part.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid(&format!(
+                    "unexpected character {:?} while parsing {} version number",
+                    part.chars()
+                        .find(|c| !c.is_ascii_digit())
+                        .unwrap_or_default(),
+                    position
+                )));
+            }
+            if part.len() > 1 && part.starts_with('0') {
+                return Err(invalid(&format!(
+                    "invalid leading zero in {} version number",
+                    position
+                )));
+            }
+            part.parse().map(Some).map_err(|_| {
+                invalid(&format!(
+                    "value of {} version number is too large",
+                    position
+                ))
+            })
+        };
+        let major = parse("major")?
+            .ok_or_else(|| invalid("unexpected end of input while parsing major version number"))?;
+        let minor = parse("minor")?;
+        let patch = parse("patch")?;
+        if components.next().is_some() {
+            return Err(invalid(&format!(
+                "unexpected character '.' after patch version number, {}",
+                EXPECTED
+            )));
+        }
+        Ok(RustVersion {
+            major,
+            minor,
+            patch,
+        })
+    }
+}
+
+impl serde::Serialize for RustVersion {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for RustVersion {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> RustVersion {
+        s.parse().unwrap()
+    }
+
+    fn error(s: &str) -> String {
+        match s.parse::<RustVersion>().unwrap_err().kind() {
+            ErrorKind::InvalidRustVersion(_, reason) => reason.clone(),
+            kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for s in &["1", "1.70", "1.70.0", "1.70.1"] {
+            assert_eq!(parse(s).to_string(), *s);
+        }
+    }
+
+    #[test]
+    fn rejects_what_cargo_rejects() {
+        assert_eq!(
+            error("1.70.0-nightly"),
+            "unexpected prerelease field, expected a version like \"1.32\""
+        );
+        assert_eq!(
+            error("1.70.0+abc"),
+            "unexpected build field, expected a version like \"1.32\""
+        );
+        assert_eq!(
+            error("^1.70"),
+            "unexpected version requirement, expected a version like \"1.32\""
+        );
+        assert_eq!(
+            error("1.*"),
+            "unexpected version requirement, expected a version like \"1.32\""
+        );
+        assert_eq!(
+            error("01.70"),
+            "invalid leading zero in major version number"
+        );
+        assert_eq!(
+            error(""),
+            "unexpected end of input while parsing major version number"
+        );
+        assert_eq!(
+            error("1."),
+            "unexpected end of input while parsing minor version number"
+        );
+        assert_eq!(
+            error("1.2.3.4"),
+            "unexpected character '.' after patch version number, expected a version like \"1.32\""
+        );
+        assert_eq!(
+            error("1.a"),
+            "unexpected character 'a' while parsing minor version number"
+        );
+    }
+
+    #[test]
+    fn ordering_and_compatibility() {
+        assert!(parse("1.70") < parse("1.70.0"));
+        assert!(parse("1.70.0") < parse("1.70.1"));
+        assert!(parse("1.69.9") < parse("1.70"));
+        assert!(parse("1") < parse("1.0"));
+
+        let rustc = semver::Version::parse("1.70.0").unwrap();
+        assert!(parse("1.70").is_compatible_with(&rustc));
+        assert!(parse("1.70.0").is_compatible_with(&rustc));
+        assert!(!parse("1.70.1").is_compatible_with(&rustc));
+        assert!(!parse("1.71").is_compatible_with(&rustc));
+        let nightly = semver::Version::parse("1.71.0-nightly").unwrap();
+        assert!(parse("1.71").is_compatible_with(&nightly));
+    }
+
+    #[test]
+    fn serde_as_string() {
+        let version: RustVersion = serde_json::from_str("\"1.56\"").unwrap();
+        assert_eq!(version, parse("1.56"));
+        assert_eq!(serde_json::to_string(&version).unwrap(), "\"1.56\"");
+        assert!(serde_json::from_str::<RustVersion>("\"1.56-beta\"").is_err());
+    }
+}
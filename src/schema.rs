@@ -0,0 +1,403 @@
+//! Validation of custom `[package.metadata]` and `[workspace.metadata]` tables.
+//!
+//! Projects declare the expected shape of their tool configuration under
+//! `[package.metadata.cargo-edit.schema]` (or `[workspace.metadata.cargo-edit.schema]`). Each key
+//! names a metadata table and maps either to an inline schema or to the path of a JSON file
+//! containing one, relative to the manifest:
+//!
+//! ```toml
+//! [package.metadata.cargo-edit.schema]
+//! docs-rs = "schemas/docs-rs.json"
+//! my-tool = { type = "table", required = ["name"], properties = { name = { type = "string" } } }
+//! ```
+//!
+//! The schema language is a subset of JSON schema: `type`, `properties`, `required`, `items`,
+//! `enum` and `additionalProperties` (also spelled `additional-properties`).
+use crate::errors::*;
+use crate::manifest::CARGO_EDIT_KEY;
+use crate::verbosity::verbosity;
+use crate::Manifest;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// The type a metadata value is expected to have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SchemaType {
+    /// A TOML string
+    String,
+    /// A TOML integer
+    Integer,
+    /// A TOML float (integers are accepted too)
+    #[serde(alias = "number")]
+    Float,
+    /// A TOML boolean
+    Boolean,
+    /// A TOML datetime
+    Datetime,
+    /// A TOML array
+    Array,
+    /// A TOML table
+    #[serde(alias = "object")]
+    Table,
+}
+
+impl SchemaType {
+    fn matches(self, value: &toml::Value) -> bool {
+        matches!(
+            (self, value),
+            (SchemaType::String, toml::Value::String(_))
+                | (SchemaType::Integer, toml::Value::Integer(_))
+                | (SchemaType::Float, toml::Value::Float(_))
+                | (SchemaType::Float, toml::Value::Integer(_))
+                | (SchemaType::Boolean, toml::Value::Boolean(_))
+                | (SchemaType::Datetime, toml::Value::Datetime(_))
+                | (SchemaType::Array, toml::Value::Array(_))
+                | (SchemaType::Table, toml::Value::Table(_))
+        )
+    }
+}
+
+/// Description of the expected shape of a metadata value.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Schema {
+    /// Expected type of the value, if constrained.
+    #[serde(rename = "type")]
+    pub kind: Option<SchemaType>,
+    /// Schemas for the keys of a table.
+    #[serde(default)]
+    pub properties: BTreeMap<String, Schema>,
+    /// Keys which must be present in a table.
+    #[serde(default)]
+    pub required: Vec<String>,
+    /// Schema which every element of an array must satisfy.
+    pub items: Option<Box<Schema>>,
+    /// The exhaustive list of allowed values.
+    #[serde(rename = "enum")]
+    pub allowed: Option<Vec<toml::Value>>,
+    /// Whether keys not listed in `properties` are permitted (defaults to `true`).
+    #[serde(rename = "additionalProperties", alias = "additional-properties")]
+    pub additional_properties: Option<bool>,
+    /// Free-form description, ignored by validation.
+    pub description: Option<String>,
+}
+
+/// A single place where metadata does not match its schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// Dotted path to the offending value, e.g. `package.metadata.docs-rs.targets[0]`.
+    pub path: String,
+    /// What is wrong with the value.
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}`: {}", self.path, self.message)
+    }
+}
+
+fn type_name(value: &toml::Value) -> &'static str {
+    match value {
+        toml::Value::String(_) => "string",
+        toml::Value::Integer(_) => "integer",
+        toml::Value::Float(_) => "float",
+        toml::Value::Boolean(_) => "boolean",
+        toml::Value::Datetime(_) => "datetime",
+        toml::Value::Array(_) => "array",
+        toml::Value::Table(_) => "table",
+    }
+}
+
+impl Schema {
+    /// Check `value` against this schema, naming it `path` in any reported violation.
+    pub fn validate(&self, path: &str, value: &toml::Value) -> Vec<SchemaViolation> {
+        let mut violations = Vec::new();
+        self.validate_into(path, value, &mut violations);
+        violations
+    }
+
+    fn validate_into(&self, path: &str, value: &toml::Value, out: &mut Vec<SchemaViolation>) {
+        let violation = |message: String| SchemaViolation {
+            path: path.to_owned(),
+            message,
+        };
+
+        if let Some(kind) = self.kind {
+            if !kind.matches(value) {
+                out.push(violation(format!(
+                    "expected {}, found {}",
+                    format!("{:?}", kind).to_lowercase(),
+                    type_name(value)
+                )));
+                return;
+            }
+        }
+
+        if let Some(allowed) = &self.allowed {
+            if !allowed.contains(value) {
+                let allowed = allowed
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                out.push(violation(format!(
+                    "{} is not one of the allowed values: {}",
+                    value, allowed
+                )));
+            }
+        }
+
+        match value {
+            toml::Value::Table(table) => {
+                for key in &self.required {
+                    if !table.contains_key(key) {
+                        out.push(violation(format!("missing required key `{}`", key)));
+                    }
+                }
+                for (key, child) in table {
+                    let child_path = format!("{}.{}", path, key);
+                    match self.properties.get(key) {
+                        Some(schema) => schema.validate_into(&child_path, child, out),
+                        None if self.additional_properties == Some(false) => {
+                            out.push(SchemaViolation {
+                                path: child_path,
+                                message: "unknown key".to_owned(),
+                            })
+                        }
+                        None => {}
+                    }
+                }
+            }
+            toml::Value::Array(array) => {
+                if let Some(items) = &self.items {
+                    for (index, child) in array.iter().enumerate() {
+                        items.validate_into(&format!("{}[{}]", path, index), child, out);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A schema declaration: either written inline, or a path to a JSON file.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SchemaSource {
+    File(String),
+    Inline(Schema),
+}
+
+fn load_schemas(
+    declared: &toml::Value,
+    manifest_dir: &Path,
+    table: &str,
+) -> Result<BTreeMap<String, Schema>> {
+    let declared = declared
+        .as_table()
+        .chain_err(|| ErrorKind::InvalidMetadataSchema(table.to_owned()))?;
+    let mut schemas = BTreeMap::new();
+    for (key, source) in declared {
+        let name = format!("{}.{}", table, key);
+        let source: SchemaSource = source
+            .clone()
+            .try_into()
+            .chain_err(|| ErrorKind::InvalidMetadataSchema(name.clone()))?;
+        let schema = match source {
+            SchemaSource::Inline(schema) => schema,
+            SchemaSource::File(file) => {
+                let content = std::fs::read_to_string(manifest_dir.join(&file))
+                    .chain_err(|| format!("Failed to read metadata schema `{}`", file))?;
+                serde_json::from_str(&content)
+                    .chain_err(|| ErrorKind::InvalidMetadataSchema(name.clone()))?
+            }
+        };
+        schemas.insert(key.clone(), schema);
+    }
+    Ok(schemas)
+}
+
+/// Validate the `[package.metadata]` and `[workspace.metadata]` tables of a manifest against the
+/// schemas it declares.
+///
+/// `manifest_path` is used to resolve schema files. Manifests without any schema declarations
+/// always validate successfully.
+pub fn validate_metadata(
+    manifest: &Manifest,
+    manifest_path: &Path,
+) -> Result<Vec<SchemaViolation>> {
+    let data: toml::Value =
+        toml::from_str(&manifest.data.to_string()).chain_err(|| ErrorKind::ParseCargoToml)?;
+    let manifest_dir = manifest_path.parent().unwrap_or_else(|| Path::new("."));
+
+    let mut violations = Vec::new();
+    for section in &["package", "workspace"] {
+        let metadata = match data.get(section).and_then(|s| s.get("metadata")) {
+            Some(metadata) => metadata,
+            None => continue,
+        };
+        let declared = match metadata
+            .get(CARGO_EDIT_KEY)
+            .and_then(|cargo_edit| cargo_edit.get("schema"))
+        {
+            Some(declared) => declared,
+            None => continue,
+        };
+        let table = format!("{}.metadata", section);
+        let schemas = load_schemas(
+            declared,
+            manifest_dir,
+            &format!("{}.{}.schema", table, CARGO_EDIT_KEY),
+        )?;
+        for (key, schema) in &schemas {
+            let path = format!("{}.{}", table, key);
+            if let Some(value) = metadata.get(key) {
+                violations.extend(schema.validate(&path, value));
+            }
+        }
+    }
+    Ok(violations)
+}
+
+/// Print a warning for every metadata value that does not match its declared schema.
+///
+/// Used by the editing commands after writing a manifest; schema problems never fail an edit, so
+/// schemas which can't be loaded are warned about too. Nothing is printed when the
+/// [`verbosity`](crate::verbosity()) is quiet.
+pub fn warn_metadata_violations(manifest: &Manifest, manifest_path: &Path) {
+    if verbosity().is_quiet() {
+        return;
+    }
+    match validate_metadata(manifest, manifest_path) {
+        Ok(violations) => {
+            for violation in violations {
+                eprintln!("WARN: {}", violation);
+            }
+        }
+        Err(err) => {
+            let causes: Vec<String> = err.iter().map(ToString::to_string).collect();
+            eprintln!("WARN: Metadata left unchecked: {}", causes.join(": "));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn violations(manifest: &str) -> Vec<String> {
+        let manifest: Manifest = manifest.parse().unwrap();
+        validate_metadata(&manifest, Path::new("Cargo.toml"))
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn no_schema_is_valid() {
+        assert!(violations(
+            r#"
+[package]
+name = "foo"
+version = "0.1.0"
+
+[package.metadata.my-tool]
+anything = 1
+"#
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn reports_type_mismatches() {
+        let found = violations(
+            r#"
+[package]
+name = "foo"
+version = "0.1.0"
+
+[package.metadata.cargo-edit.schema.my-tool]
+type = "table"
+properties = { name = { type = "string" }, targets = { type = "array", items = { type = "string" } } }
+
+[package.metadata.my-tool]
+name = 1
+targets = [64]
+"#,
+        );
+        assert_eq!(
+            found,
+            vec![
+                "`package.metadata.my-tool.name`: expected string, found integer",
+                "`package.metadata.my-tool.targets[0]`: expected string, found integer",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unknown_and_missing_keys() {
+        let found = violations(
+            r#"
+[workspace]
+members = []
+
+[workspace.metadata.cargo-edit.schema.release]
+type = "table"
+required = ["channel"]
+additional-properties = false
+properties = { channel = { enum = ["stable", "beta"] } }
+
+[workspace.metadata.release]
+chanel = "stable"
+"#,
+        );
+        assert_eq!(
+            found,
+            vec![
+                "`workspace.metadata.release`: missing required key `channel`",
+                "`workspace.metadata.release.chanel`: unknown key",
+            ]
+        );
+    }
+
+    #[test]
+    fn enum_values() {
+        let schema: Schema = toml::from_str(r#"enum = ["stable", "beta"]"#).unwrap();
+        assert!(schema
+            .validate("x", &toml::Value::String("beta".into()))
+            .is_empty());
+        assert_eq!(
+            schema.validate("x", &toml::Value::String("nightly".into()))[0].message,
+            r#""nightly" is not one of the allowed values: "stable", "beta""#
+        );
+    }
+
+    #[test]
+    fn schema_from_json_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("schema.json"),
+            r#"{ "type": "object", "additionalProperties": false, "properties": {} }"#,
+        )
+        .unwrap();
+        let manifest: Manifest = r#"
+[package]
+name = "foo"
+version = "0.1.0"
+
+[package.metadata.cargo-edit.schema]
+my-tool = "schema.json"
+
+[package.metadata.my-tool]
+typo = true
+"#
+        .parse()
+        .unwrap();
+        let found = validate_metadata(&manifest, &dir.path().join("Cargo.toml")).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, "package.metadata.my-tool.typo");
+    }
+}
@@ -0,0 +1,635 @@
+//! A long-lived service answering [JSON-RPC 2.0](https://www.jsonrpc.org/specification) requests,
+//! one per line, e.g. over stdin and stdout with `cargo edit serve`.
+//!
+//! Editors and language servers keep one service running instead of spawning a subcommand for
+//! every edit, so registry indices are opened once and stay warm between requests.
+//!
+//! The methods are:
+//!
+//! - `query-latest`: the newest version of `name`, including pre-releases if `allow_prerelease`
+//!   is set
+//! - `add`: add `name` to the `table` of the manifest, at `version` or else the newest version,
+//!   with `features` and `optional` if given
+//! - `remove`: remove `name` from the `table` of the manifest
+//! - `upgrade-preview`: the registry dependencies of the manifest which have a newer version,
+//!   without changing anything
+//! - `shutdown`: stop answering requests
+//!
+//! `query-latest`, `add` and `upgrade-preview` also report the index checksum of the version they
+//! settle on if `checksum` is set, so provenance tooling can cross-check what cargo later
+//! downloads; it is `null` if the index records none, or `add` was given a requirement rather
+//! than an exact version.
+//!
+//! Every method but `shutdown` takes the `manifest_path` to work on, which is found from the
+//! working directory like the subcommands do if it isn't given. `table` is a table path like
+//! `["target", "cfg(unix)", "dependencies"]` and defaults to `["dependencies"]`. Versions are looked
+//! up in the default registry, or in `registry`, which is either the name of a registry configured
+//! for cargo or an index URL.
+use crate::errors::*;
+use crate::fetch::{
+    get_latest_dependency, get_version_checksum, get_version_checksum_from, resolve_registry,
+    select_from_index, update_registry_index_if_stale,
+};
+use crate::index::{open_index, IndexSource};
+use crate::policy::Latest;
+use crate::registry::registry_url;
+use crate::{cache_ttl, find, Dependency, Manifest, ManifestLock, StyleConfig};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use url::Url;
+
+/// The request is not valid JSON.
+const PARSE_ERROR: i64 = -32700;
+/// The request is not a valid JSON-RPC request.
+const INVALID_REQUEST: i64 = -32600;
+/// The requested method does not exist.
+const METHOD_NOT_FOUND: i64 = -32601;
+/// The parameters are missing or of the wrong type.
+const INVALID_PARAMS: i64 = -32602;
+/// The request was understood, but failed, e.g. for an unknown crate.
+const REQUEST_FAILED: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+fn default_table() -> Vec<String> {
+    vec!["dependencies".to_owned()]
+}
+
+#[derive(Debug, Deserialize)]
+struct QueryLatestParams {
+    name: String,
+    manifest_path: Option<PathBuf>,
+    registry: Option<String>,
+    #[serde(default)]
+    allow_prerelease: bool,
+    #[serde(default)]
+    checksum: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct AddParams {
+    name: String,
+    version: Option<String>,
+    manifest_path: Option<PathBuf>,
+    #[serde(default = "default_table")]
+    table: Vec<String>,
+    registry: Option<String>,
+    features: Option<Vec<String>>,
+    #[serde(default)]
+    optional: bool,
+    #[serde(default)]
+    allow_prerelease: bool,
+    #[serde(default)]
+    checksum: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoveParams {
+    name: String,
+    manifest_path: Option<PathBuf>,
+    #[serde(default = "default_table")]
+    table: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpgradePreviewParams {
+    manifest_path: Option<PathBuf>,
+    #[serde(default)]
+    allow_prerelease: bool,
+    #[serde(default)]
+    checksum: bool,
+}
+
+/// Why a request could not be answered.
+enum Failure {
+    /// A JSON-RPC error code, with its message
+    Rpc(i64, String),
+    /// An error from the request itself
+    Edit(Error),
+}
+
+impl From<Error> for Failure {
+    fn from(err: Error) -> Self {
+        Failure::Edit(err)
+    }
+}
+
+fn params<T: serde::de::DeserializeOwned>(params: Value) -> std::result::Result<T, Failure> {
+    serde_json::from_value(params).map_err(|err| Failure::Rpc(INVALID_PARAMS, err.to_string()))
+}
+
+/// Answers JSON-RPC requests, keeping the registry indices it opens.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{CrateVersionInfo, InMemoryIndex, Service};
+///
+///   let mut index = InMemoryIndex::new();
+///   index.insert(CrateVersionInfo::new("serde", semver::Version::new(1, 0, 130)));
+///   let registry = url::Url::parse("https://example.com/index").unwrap();
+///
+///   let mut service = Service::new();
+///   service.insert_index(registry, Box::new(index));
+///   let response = service
+///       .handle(r#"{"jsonrpc": "2.0", "id": 1, "method": "query-latest",
+///                   "params": {"name": "serde", "registry": "https://example.com/index"}}"#)
+///       .unwrap();
+///   assert_eq!(
+///       response,
+///       r#"{"id":1,"jsonrpc":"2.0","result":{"name":"serde","version":"1.0.130"}}"#
+///   );
+/// ```
+#[derive(Default)]
+pub struct Service {
+    /// The open indices by registry, with when they were opened unless they never expire
+    indices: HashMap<Url, (Option<Instant>, Box<dyn IndexSource>)>,
+    offline: bool,
+    shut_down: bool,
+}
+
+impl fmt::Debug for Service {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Service")
+            .field("indices", &self.indices.keys().collect::<Vec<_>>())
+            .field("offline", &self.offline)
+            .field("shut_down", &self.shut_down)
+            .finish()
+    }
+}
+
+impl Service {
+    /// A service which opens registry indices as they are needed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Never update git registry indices, and use them as they are on disk.
+    pub fn set_offline(&mut self, offline: bool) -> &mut Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Answer queries for `registry` from `index`, e.g. one filled by your own fetch layer.
+    ///
+    /// `registry` is either the URL given as `registry` in requests, or the index URL a registry
+    /// name is configured with. Indices inserted this way are kept for the life of the service,
+    /// while those the service opens itself are reopened once the [cache TTL](crate::cache_ttl)
+    /// has passed.
+    pub fn insert_index(&mut self, registry: Url, index: Box<dyn IndexSource>) -> &mut Self {
+        self.indices.insert(registry, (None, index));
+        self
+    }
+
+    /// Whether a `shutdown` request was answered.
+    pub fn is_shut_down(&self) -> bool {
+        self.shut_down
+    }
+
+    /// Answer the requests read from `input`, one per line, writing a response line to `output`
+    /// for each, until the input ends or the service is shut down.
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle(&line) {
+                writeln!(output, "{}", response)?;
+                output.flush()?;
+            }
+            if self.shut_down {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer a single request, returning the response or `None` for a notification, i.e. a
+    /// request without an `id`.
+    pub fn handle(&mut self, request: &str) -> Option<String> {
+        let request = match serde_json::from_str::<Value>(request) {
+            Ok(request) => request,
+            Err(err) => return Some(error_response(Value::Null, PARSE_ERROR, &err.to_string())),
+        };
+        let request: Request = match serde_json::from_value(request) {
+            Ok(request) => request,
+            Err(err) => {
+                return Some(error_response(
+                    Value::Null,
+                    INVALID_REQUEST,
+                    &err.to_string(),
+                ))
+            }
+        };
+
+        let _span = span!(INFO, "request", method = %request.method);
+        let result = self.dispatch(&request.method, request.params);
+        let id = request.id?;
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}).to_string(),
+            Err(Failure::Rpc(code, message)) => error_response(id, code, &message),
+            Err(Failure::Edit(err)) => {
+                let causes: Vec<String> = err.iter().skip(1).map(ToString::to_string).collect();
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": REQUEST_FAILED, "message": err.to_string(), "data": causes},
+                })
+                .to_string()
+            }
+        })
+    }
+
+    fn dispatch(
+        &mut self,
+        method: &str,
+        params_value: Value,
+    ) -> std::result::Result<Value, Failure> {
+        match method {
+            "query-latest" => Ok(self.query_latest(params(params_value)?)?),
+            "add" => Ok(self.add(params(params_value)?)?),
+            "remove" => Ok(self.remove(params(params_value)?)?),
+            "upgrade-preview" => Ok(self.upgrade_preview(params(params_value)?)?),
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Value::Null)
+            }
+            _ => Err(Failure::Rpc(
+                METHOD_NOT_FOUND,
+                format!("Unknown method `{}`", method),
+            )),
+        }
+    }
+
+    fn query_latest(&mut self, params: QueryLatestParams) -> Result<Value> {
+        let manifest_path = manifest_path(&params.manifest_path);
+        let dep = self.latest(
+            &params.name,
+            params.allow_prerelease,
+            &manifest_path,
+            params.registry.as_deref(),
+        )?;
+        let mut result = json!({"name": dep.name, "version": dep.version()});
+        if params.checksum {
+            result["checksum"] =
+                json!(self.checksum(&dep, &manifest_path, params.registry.as_deref())?);
+        }
+        Ok(result)
+    }
+
+    fn add(&mut self, params: AddParams) -> Result<Value> {
+        let manifest_path = find(&params.manifest_path)?;
+        let dep = match params.version {
+            Some(ref version) => Dependency::new(&params.name).set_version(version),
+            None => self.latest(
+                &params.name,
+                params.allow_prerelease,
+                &manifest_path,
+                params.registry.as_deref(),
+            )?,
+        };
+        let mut dep = dep
+            .set_features(params.features)
+            .set_optional(params.optional);
+        if let Some(ref registry) = params.registry {
+            if Url::parse(registry).is_err() {
+                dep = dep.set_registry(registry);
+            }
+        }
+        dep.validate()?;
+
+        let style = StyleConfig::for_manifest(&manifest_path)?;
+        let _lock = ManifestLock::acquire(&manifest_path)?;
+        let mut manifest = Manifest::open(&Some(manifest_path.clone()))?;
+        manifest.insert_into_table_with_style(&params.table, &dep, &style)?;
+        manifest.write_to_path_with_style(&manifest_path, &style)?;
+        let mut result = json!({"name": dep.name, "version": dep.version(), "table": params.table});
+        if params.checksum {
+            result["checksum"] =
+                json!(self.checksum(&dep, &manifest_path, params.registry.as_deref())?);
+        }
+        Ok(result)
+    }
+
+    fn remove(&mut self, params: RemoveParams) -> Result<Value> {
+        let manifest_path = find(&params.manifest_path)?;
+        let style = StyleConfig::for_manifest(&manifest_path)?;
+        let _lock = ManifestLock::acquire(&manifest_path)?;
+        let mut manifest = Manifest::open(&Some(manifest_path.clone()))?;
+        manifest.remove_from_table(&params.table, &params.name)?;
+        manifest.write_to_path_with_style(&manifest_path, &style)?;
+        Ok(json!({"name": params.name, "table": params.table}))
+    }
+
+    fn upgrade_preview(&mut self, params: UpgradePreviewParams) -> Result<Value> {
+        let manifest_path = find(&params.manifest_path)?;
+        let manifest = Manifest::open(&Some(manifest_path.clone()))?;
+        let mut upgrades = Vec::new();
+        for entry in manifest.dependency_entries() {
+            let current = match entry.version_req() {
+                Some(current) if entry.path().is_none() && !entry.is_git() => current.to_owned(),
+                _ => continue,
+            };
+            let table = entry.table.path();
+            match self.latest(
+                entry.name(),
+                params.allow_prerelease,
+                &manifest_path,
+                entry.registry(),
+            ) {
+                Ok(latest) if latest.version() != Some(current.as_str()) => {
+                    let mut upgrade = json!({
+                        "name": entry.name(),
+                        "key": entry.key,
+                        "table": table,
+                        "current": current,
+                        "latest": latest.version(),
+                    });
+                    if params.checksum {
+                        upgrade["checksum"] =
+                            match self.checksum(&latest, &manifest_path, entry.registry()) {
+                                Ok(checksum) => json!(checksum),
+                                Err(err) => json!({"error": err.to_string()}),
+                            };
+                    }
+                    upgrades.push(upgrade)
+                }
+                Ok(_) => {}
+                Err(err) => upgrades.push(json!({
+                    "name": entry.name(),
+                    "key": entry.key,
+                    "table": table,
+                    "current": current,
+                    "error": err.to_string(),
+                })),
+            }
+        }
+        Ok(Value::Array(upgrades))
+    }
+
+    /// The newest version of `name`, looked up in the index of `registry`, which is a registry
+    /// name or an index URL.
+    fn latest(
+        &mut self,
+        name: &str,
+        allow_prerelease: bool,
+        manifest_path: &Path,
+        registry: Option<&str>,
+    ) -> Result<Dependency> {
+        let registry = registry_from_param(manifest_path, registry)?;
+        if std::env::var("CARGO_IS_TEST").is_ok() {
+            return get_latest_dependency(name, allow_prerelease, manifest_path, &registry);
+        }
+
+        let index = self.index(manifest_path, &registry)?;
+        select_from_index(index, name, &Latest { allow_prerelease })
+    }
+
+    /// The index checksum of the version `dep` is at, or `None` if it is a requirement rather than
+    /// a single version.
+    fn checksum(
+        &mut self,
+        dep: &Dependency,
+        manifest_path: &Path,
+        registry: Option<&str>,
+    ) -> Result<Option<String>> {
+        let version = match dep
+            .version()
+            .and_then(|version| semver::Version::parse(version.trim_start_matches('=')).ok())
+        {
+            Some(version) => version,
+            None => return Ok(None),
+        };
+        let registry = registry_from_param(manifest_path, registry)?;
+        if std::env::var("CARGO_IS_TEST").is_ok() {
+            return get_version_checksum(&dep.name, &version, manifest_path, &registry);
+        }
+
+        let index = self.index(manifest_path, &registry)?;
+        get_version_checksum_from(index, &dep.name, &version)
+    }
+
+    /// The index of `registry`, opened and updated if it is not open yet or has expired.
+    fn index(&mut self, manifest_path: &Path, registry: &Option<Url>) -> Result<&dyn IndexSource> {
+        let url = match registry {
+            Some(url) if self.indices.contains_key(url) => url.clone(),
+            _ => resolve_registry(manifest_path, registry)?,
+        };
+        let ttl = cache_ttl();
+        let fresh = self
+            .indices
+            .get(&url)
+            .is_some_and(|(opened, _)| opened.is_none_or(|opened| opened.elapsed() < ttl));
+        if !fresh {
+            if !self.offline {
+                update_registry_index_if_stale(&url, true, ttl)?;
+            }
+            let index = open_index(manifest_path, &url)?;
+            self.indices
+                .insert(url.clone(), (Some(Instant::now()), index));
+        }
+        Ok(&*self.indices[&url].1)
+    }
+}
+
+/// The manifest at `manifest_path`, or the one the working directory belongs to; queries don't
+/// need one to exist, as it is only used to find the cargo configuration.
+fn manifest_path(manifest_path: &Option<PathBuf>) -> PathBuf {
+    find(manifest_path).unwrap_or_else(|_| PathBuf::from("Cargo.toml"))
+}
+
+/// The index URL of `registry` as given in requests, i.e. a registry name or an index URL.
+fn registry_from_param(manifest_path: &Path, registry: Option<&str>) -> Result<Option<Url>> {
+    registry
+        .map(|registry| match Url::parse(registry) {
+            Ok(url) => Ok(url),
+            Err(_) => registry_url(manifest_path, Some(registry)),
+        })
+        .transpose()
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> String {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}}).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CrateVersionInfo, InMemoryIndex};
+
+    const MANIFEST: &str = r#"[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+serde = "1.0.100"
+local = { path = "../local" }
+
+[dev-dependencies]
+regex = "1.4"
+"#;
+
+    fn with_index() -> (Service, Url) {
+        let mut index = InMemoryIndex::new();
+        for (name, version) in &[("serde", "1.0.130"), ("regex", "1.4.0"), ("log", "0.4.14")] {
+            let mut info = CrateVersionInfo::new(name, semver::Version::parse(version).unwrap());
+            if *name == "log" {
+                info.checksum = "a1b2c3".to_owned();
+            }
+            index.insert(info);
+        }
+        let registry = Url::parse("https://example.com/index").unwrap();
+        let mut service = Service::new();
+        service.insert_index(registry.clone(), Box::new(index));
+        (service, registry)
+    }
+
+    fn call(service: &mut Service, method: &str, params: Value) -> Value {
+        let request = json!({"jsonrpc": "2.0", "id": 7, "method": method, "params": params});
+        let response = service.handle(&request.to_string()).unwrap();
+        serde_json::from_str(&response).unwrap()
+    }
+
+    #[test]
+    fn edits_and_previews_manifests() {
+        let (mut service, registry) = with_index();
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("Cargo.toml");
+        std::fs::write(&manifest, MANIFEST).unwrap();
+
+        let response = call(
+            &mut service,
+            "add",
+            json!({"name": "log", "manifest_path": manifest, "registry": registry.as_str()}),
+        );
+        assert_eq!(response["result"]["version"], "0.4.14");
+        let response = call(
+            &mut service,
+            "remove",
+            json!({"name": "regex", "manifest_path": manifest, "table": ["dev-dependencies"]}),
+        );
+        assert_eq!(response["result"]["name"], "regex");
+        let content = std::fs::read_to_string(&manifest).unwrap();
+        assert!(content.contains("log = \"0.4.14\""));
+        assert!(!content.contains("regex"));
+
+        let response = call(
+            &mut service,
+            "remove",
+            json!({"name": "regex", "manifest_path": manifest}),
+        );
+        assert_eq!(response["error"]["code"], REQUEST_FAILED);
+        assert_eq!(
+            response["error"]["message"],
+            "The dependency `regex` could not be found in `dependencies`."
+        );
+
+        // Without a registry, the entries are looked up in the default one, which is crates.io.
+        let crates_io = resolve_registry(&manifest, &None).unwrap();
+        let (mut service, _) = with_index();
+        let index = service.indices.remove(&registry).unwrap().1;
+        service.insert_index(crates_io, index);
+        let response = call(
+            &mut service,
+            "upgrade-preview",
+            json!({"manifest_path": manifest}),
+        );
+        assert_eq!(
+            response["result"],
+            json!([{
+                "name": "serde",
+                "key": "serde",
+                "table": ["dependencies"],
+                "current": "1.0.100",
+                "latest": "1.0.130",
+            }])
+        );
+    }
+
+    #[test]
+    fn reports_checksums_on_request() {
+        let (mut service, registry) = with_index();
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("Cargo.toml");
+        std::fs::write(&manifest, MANIFEST).unwrap();
+
+        let query = |service: &mut Service, name: &str, checksum: bool| {
+            call(
+                service,
+                "query-latest",
+                json!({"name": name, "registry": registry.as_str(), "checksum": checksum}),
+            )["result"]
+                .clone()
+        };
+        assert_eq!(
+            query(&mut service, "log", true),
+            json!({"name": "log", "version": "0.4.14", "checksum": "a1b2c3"})
+        );
+        assert_eq!(
+            query(&mut service, "log", false),
+            json!({"name": "log", "version": "0.4.14"})
+        );
+        assert_eq!(query(&mut service, "regex", true)["checksum"], Value::Null);
+
+        let add =
+            |service: &mut Service, params: Value| call(service, "add", params)["result"].clone();
+        let result = add(
+            &mut service,
+            json!({"name": "log", "version": "=0.4.14", "manifest_path": manifest,
+                   "registry": registry.as_str(), "checksum": true}),
+        );
+        assert_eq!(result["checksum"], "a1b2c3");
+        let result = add(
+            &mut service,
+            json!({"name": "log", "version": "0.4", "manifest_path": manifest,
+                   "registry": registry.as_str(), "checksum": true}),
+        );
+        assert_eq!(result["checksum"], Value::Null);
+    }
+
+    #[test]
+    fn protocol_errors() {
+        let (mut service, _) = with_index();
+        let code = |response: Option<String>| -> Value {
+            serde_json::from_str::<Value>(&response.unwrap()).unwrap()["error"]["code"].clone()
+        };
+        assert_eq!(code(service.handle("{")), PARSE_ERROR);
+        assert_eq!(code(service.handle("{\"id\": 1}")), INVALID_REQUEST);
+        assert_eq!(
+            code(service.handle(r#"{"id": 1, "method": "publish"}"#)),
+            METHOD_NOT_FOUND
+        );
+        assert_eq!(
+            code(service.handle(r#"{"id": 1, "method": "add", "params": {}}"#)),
+            INVALID_PARAMS
+        );
+        assert_eq!(service.handle(r#"{"method": "shutdown"}"#), None);
+        assert!(service.is_shut_down());
+    }
+
+    #[test]
+    fn runs_until_shut_down() {
+        let (mut service, _) = with_index();
+        let input =
+            "\n{\"id\": 1, \"method\": \"shutdown\"}\n{\"id\": 2, \"method\": \"shutdown\"}\n";
+        let mut output = Vec::new();
+        service.run(input.as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"id\":1,\"jsonrpc\":\"2.0\",\"result\":null}\n"
+        );
+    }
+}
@@ -0,0 +1,652 @@
+//! Moving registry index entries to hosts without network access.
+//!
+//! A snapshot holds the index files of a set of crates and their dependencies, as a plain tar
+//! archive which can be inspected before it is carried over: `snapshot.json` lists the registries
+//! and crates in it, and `registries/<n>/` holds the index files of the `n`th registry in the
+//! layout of a registry index.
+//!
+//! Installed snapshots live in `$CARGO_HOME/cargo-edit/snapshots/<registry>`, in the layout of a
+//! `local-registry`, and are used for queries of a registry which can't be reached.
+use crate::crate_name::{validate_crate_name, NamingRules};
+use crate::dependency_table::DependencyKind;
+use crate::errors::*;
+use crate::index::{parse_summaries, summary_raw_path, CrateVersionInfo, IndexSource};
+use crate::registry::snapshot_path_from_url;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+#[cfg(feature = "fetch")]
+use {
+    crate::fetch::resolve_registry,
+    crate::fuzzy::FuzzyNames,
+    crate::index::{open_index, LocalRegistry},
+    crate::manifest::Manifest,
+    crate::registry::registry_url,
+};
+
+/// The file of a snapshot archive listing its content.
+const LISTING: &str = "snapshot.json";
+
+/// The version of the snapshot format.
+const FORMAT_VERSION: u32 = 1;
+
+/// The size of a tar block.
+const BLOCK: usize = 512;
+
+/// The index files of some crates of one or more registries.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::IndexSnapshot;
+///   use url::Url;
+///
+///   let registry = Url::parse("https://github.com/rust-lang/crates.io-index").unwrap();
+///   let index_file = r#"{"name":"serde","vers":"1.0.0","deps":[],"cksum":"","features":{}}"#;
+///   let mut snapshot = IndexSnapshot::new();
+///   snapshot.insert(&registry, "serde", index_file.as_bytes().to_vec());
+///
+///   let mut archive = Vec::new();
+///   snapshot.write_to(&mut archive).unwrap();
+///   let imported = IndexSnapshot::read_from(archive.as_slice()).unwrap();
+///   assert_eq!(imported.crates(&registry), ["serde"]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexSnapshot {
+    /// The index files by crate name, by registry URL.
+    registries: BTreeMap<String, BTreeMap<String, Vec<u8>>>,
+    /// Seconds since the unix epoch.
+    created_at: u64,
+}
+
+impl Default for IndexSnapshot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The content of `snapshot.json`.
+#[derive(Debug, Serialize, Deserialize)]
+struct Listing {
+    version: u32,
+    created_at: u64,
+    registries: Vec<ListedRegistry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ListedRegistry {
+    url: String,
+    crates: Vec<String>,
+}
+
+impl IndexSnapshot {
+    /// An empty snapshot, taken now.
+    pub fn new() -> Self {
+        IndexSnapshot {
+            registries: BTreeMap::new(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+
+    /// When the snapshot was taken.
+    pub fn created_at(&self) -> SystemTime {
+        UNIX_EPOCH + std::time::Duration::from_secs(self.created_at)
+    }
+
+    /// Add the index file `content` of `crate_name` in `registry`.
+    pub fn insert(&mut self, registry: &Url, crate_name: &str, content: Vec<u8>) -> &mut Self {
+        self.registries
+            .entry(registry.to_string())
+            .or_default()
+            .insert(crate_name.to_ascii_lowercase(), content);
+        self
+    }
+
+    /// Add the index files of `crate_names` and the crates they depend on, read from `index`, the
+    /// index of `registry`.
+    ///
+    /// The dependencies followed are the normal and build dependencies of the latest release of
+    /// each crate, as `cargo add` would pick it, which come from the same registry. This fails if
+    /// `index` has no index file for one of `crate_names`.
+    pub fn add_closure(
+        &mut self,
+        index: &dyn IndexSource,
+        registry: &Url,
+        crate_names: &[&str],
+    ) -> Result<&mut Self> {
+        let mut seen: BTreeSet<String> = BTreeSet::new();
+        let mut queue: Vec<(String, bool)> = crate_names
+            .iter()
+            .map(|name| (name.to_ascii_lowercase(), true))
+            .collect();
+        while let Some((name, requested)) = queue.pop() {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            let content = match index.raw_index_file(&name)? {
+                Some(content) => content,
+                None if requested => return Err(ErrorKind::NoCrate(name, vec![]).into()),
+                None => {
+                    event!(DEBUG, crate_name = %name, "dependency not in the index, skipping it");
+                    continue;
+                }
+            };
+            if let Some(latest) = latest_release(&parse_summaries(&content)?) {
+                for dep in &latest.deps {
+                    if dep.registry.is_none() && dep.kind != DependencyKind::Dev {
+                        queue.push((dep.package_name().to_ascii_lowercase(), false));
+                    }
+                }
+            }
+            self.insert(registry, &name, content);
+        }
+        Ok(self)
+    }
+
+    /// Like [`IndexSnapshot::add_closure`], reading from `registry`, or the default registry of
+    /// the manifest at `manifest_path`.
+    #[cfg(feature = "fetch")]
+    pub fn add_registry_closure(
+        &mut self,
+        manifest_path: &Path,
+        registry: &Option<Url>,
+        crate_names: &[&str],
+    ) -> Result<&mut Self> {
+        let registry = resolve_registry(manifest_path, registry)?;
+        let index = open_index(manifest_path, &registry)?;
+        self.add_closure(&*index, &registry, crate_names)
+    }
+
+    /// Add the dependency closures of every registry dependency of the manifest at
+    /// `manifest_path`, from the registry each comes from; git, path and inherited dependencies
+    /// are skipped.
+    #[cfg(feature = "fetch")]
+    pub fn add_manifest_closure(&mut self, manifest_path: &Path) -> Result<&mut Self> {
+        let manifest = Manifest::open(&Some(manifest_path.to_owned()))?;
+        let mut by_registry: BTreeMap<Option<String>, Vec<String>> = BTreeMap::new();
+        for entry in manifest.dependency_entries() {
+            if entry.path().is_some() || entry.is_git() || entry.is_inherited() {
+                continue;
+            }
+            by_registry
+                .entry(entry.registry().map(ToOwned::to_owned))
+                .or_default()
+                .push(entry.name().to_owned());
+        }
+        for (registry, crate_names) in by_registry {
+            let registry = registry_url(manifest_path, registry.as_deref())?;
+            let crate_names: Vec<&str> = crate_names.iter().map(String::as_str).collect();
+            self.add_registry_closure(manifest_path, &Some(registry), &crate_names)?;
+        }
+        Ok(self)
+    }
+
+    /// The registries in the snapshot.
+    pub fn registries(&self) -> Vec<Url> {
+        self.registries
+            .keys()
+            .filter_map(|url| Url::parse(url).ok())
+            .collect()
+    }
+
+    /// The crates of `registry` in the snapshot, by name.
+    pub fn crates(&self, registry: &Url) -> Vec<&str> {
+        self.registries
+            .get(registry.as_str())
+            .map(|crates| crates.keys().map(String::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    /// Write the snapshot as a tar archive.
+    pub fn write_to(&self, mut writer: impl Write) -> Result<()> {
+        let listing = Listing {
+            version: FORMAT_VERSION,
+            created_at: self.created_at,
+            registries: self
+                .registries
+                .iter()
+                .map(|(url, crates)| ListedRegistry {
+                    url: url.clone(),
+                    crates: crates.keys().cloned().collect(),
+                })
+                .collect(),
+        };
+        let listing =
+            serde_json::to_vec_pretty(&listing).expect("snapshot listings are serializable");
+        write_entry(&mut writer, LISTING, &listing, self.created_at)?;
+        for (n, crates) in self.registries.values().enumerate() {
+            for (name, content) in crates {
+                let path = format!("registries/{}/{}", n, index_file_path(name)?);
+                write_entry(&mut writer, &path, content, self.created_at)?;
+            }
+        }
+        writer.write_all(&[0; 2 * BLOCK])?;
+        Ok(())
+    }
+
+    /// Read a snapshot written by [`IndexSnapshot::write_to`].
+    ///
+    /// Every index file has to be listed in `snapshot.json` and be a valid index file of the
+    /// crate it is named after.
+    pub fn read_from(mut reader: impl Read) -> Result<Self> {
+        let mut files = BTreeMap::new();
+        while let Some((path, content)) = read_entry(&mut reader)? {
+            files.insert(path, content);
+        }
+        let listing = files
+            .remove(LISTING)
+            .chain_err(|| invalid(&format!("`{}` is missing", LISTING)))?;
+        let listing: Listing =
+            serde_json::from_slice(&listing).chain_err(|| invalid("unreadable listing"))?;
+        if listing.version != FORMAT_VERSION {
+            return Err(invalid(&format!(
+                "unsupported format version {}",
+                listing.version
+            )));
+        }
+
+        let mut snapshot = IndexSnapshot {
+            registries: BTreeMap::new(),
+            created_at: listing.created_at,
+        };
+        for (n, registry) in listing.registries.iter().enumerate() {
+            let url = Url::parse(&registry.url)
+                .chain_err(|| invalid(&format!("invalid registry `{}`", registry.url)))?;
+            for name in &registry.crates {
+                let path = format!("registries/{}/{}", n, index_file_path(name)?);
+                let content = files
+                    .remove(&path)
+                    .chain_err(|| invalid(&format!("`{}` is missing", path)))?;
+                let versions = parse_summaries(&content)
+                    .chain_err(|| invalid(&format!("invalid `{}`", path)))?;
+                if versions.iter().any(|v| !v.name.eq_ignore_ascii_case(name)) {
+                    return Err(invalid(&format!("`{}` lists another crate", path)));
+                }
+                snapshot.insert(&url, name, content);
+            }
+        }
+        if let Some(path) = files.keys().next() {
+            return Err(invalid(&format!("`{}` is not listed", path)));
+        }
+        Ok(snapshot)
+    }
+
+    /// Install the snapshot in the cargo home directory, for queries of its registries to fall
+    /// back on when they can't be reached, e.g. by `cargo add --offline`.
+    ///
+    /// Index files installed before are replaced by those of the snapshot, and kept otherwise.
+    pub fn install(&self) -> Result<()> {
+        for (url, crates) in &self.registries {
+            let url =
+                Url::parse(url).chain_err(|| invalid(&format!("invalid registry `{}`", url)))?;
+            install_in(&snapshot_path_from_url(&url)?, crates)?;
+        }
+        Ok(())
+    }
+}
+
+/// The snapshot installed for `registry`, if any.
+#[cfg(feature = "fetch")]
+pub(crate) fn installed_snapshot(registry: &Url) -> Result<Option<LocalRegistry>> {
+    let dir = snapshot_path_from_url(registry)?;
+    if dir.join("index").is_dir() {
+        Ok(Some(LocalRegistry::open(&dir)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Whether `err` says a registry couldn't be reached, rather than what it answered.
+#[cfg(feature = "fetch")]
+pub(crate) fn is_unreachable(err: &Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::Network(..) | ErrorKind::Offline(..) | ErrorKind::Git(_)
+    )
+}
+
+/// A registry index, with a snapshot of it to fall back on while it can't be reached.
+#[cfg(feature = "fetch")]
+pub(crate) struct WithSnapshot {
+    index: Box<dyn IndexSource>,
+    snapshot: LocalRegistry,
+}
+
+#[cfg(feature = "fetch")]
+impl WithSnapshot {
+    pub(crate) fn new(index: Box<dyn IndexSource>, snapshot: LocalRegistry) -> Self {
+        WithSnapshot { index, snapshot }
+    }
+
+    fn or_snapshot<T>(
+        &self,
+        result: Result<T>,
+        snapshot: impl FnOnce(&LocalRegistry) -> Result<T>,
+    ) -> Result<T> {
+        match result {
+            Err(ref err) if is_unreachable(err) => {
+                event!(WARN, error = %err, "registry unreachable, using its snapshot");
+                snapshot(&self.snapshot)
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(feature = "fetch")]
+impl IndexSource for WithSnapshot {
+    fn crate_versions(&self, crate_name: &str) -> Result<Option<Vec<CrateVersionInfo>>> {
+        self.or_snapshot(self.index.crate_versions(crate_name), |s| {
+            s.crate_versions(crate_name)
+        })
+    }
+
+    fn refresh_crate_versions(&self, crate_name: &str) -> Result<Option<Vec<CrateVersionInfo>>> {
+        self.or_snapshot(self.index.refresh_crate_versions(crate_name), |s| {
+            s.crate_versions(crate_name)
+        })
+    }
+
+    fn crate_versions_where(
+        &self,
+        crate_name: &str,
+        keep: &dyn Fn(&CrateVersionInfo) -> bool,
+    ) -> Result<Option<Vec<CrateVersionInfo>>> {
+        self.or_snapshot(self.index.crate_versions_where(crate_name, keep), |s| {
+            s.crate_versions_where(crate_name, keep)
+        })
+    }
+
+    fn similar_names(&self, crate_name: &str) -> Result<Vec<String>> {
+        self.or_snapshot(self.index.similar_names(crate_name), |s| {
+            s.similar_names(crate_name)
+        })
+    }
+
+    fn fuzzy_names(&self) -> FuzzyNames {
+        self.index.fuzzy_names()
+    }
+
+    fn raw_index_file(&self, crate_name: &str) -> Result<Option<Vec<u8>>> {
+        self.or_snapshot(self.index.raw_index_file(crate_name), |s| {
+            s.raw_index_file(crate_name)
+        })
+    }
+}
+
+/// Write the index files `crates` to `dir`, in the layout of a `local-registry`.
+fn install_in(dir: &Path, crates: &BTreeMap<String, Vec<u8>>) -> Result<()> {
+    for (name, content) in crates {
+        let path = dir.join("index").join(index_file_path(name)?);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .chain_err(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+        fs::write(&path, content).chain_err(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// The path of the index file of `name` in a registry index.
+///
+/// Snapshots are carried over from elsewhere, so `name` has to be a valid crate name before it
+/// goes anywhere near a path.
+fn index_file_path(name: &str) -> Result<String> {
+    validate_crate_name(name, NamingRules::Cargo)
+        .chain_err(|| invalid(&format!("invalid crate name `{}`", name.escape_debug())))?;
+    Ok(summary_raw_path(name))
+}
+
+/// The release `cargo add` would pick: the latest stable one, or else the latest of any, which
+/// isn't yanked.
+fn latest_release(versions: &[CrateVersionInfo]) -> Option<&CrateVersionInfo> {
+    let available = || versions.iter().filter(|v| !v.yanked);
+    available()
+        .filter(|v| !v.version.is_prerelease())
+        .max_by(|a, b| a.version.cmp(&b.version))
+        .or_else(|| available().max_by(|a, b| a.version.cmp(&b.version)))
+}
+
+fn invalid(reason: &str) -> Error {
+    format!("Invalid index snapshot: {}", reason).into()
+}
+
+/// Write a regular file to a ustar archive.
+fn write_entry(writer: &mut impl Write, path: &str, content: &[u8], mtime: u64) -> Result<()> {
+    if path.len() > 100 {
+        return Err(invalid(&format!("`{}` is too long a path", path)));
+    }
+    let mut header = [0u8; BLOCK];
+    header[..path.len()].copy_from_slice(path.as_bytes());
+    write_octal(&mut header[100..108], 0o644);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    write_octal(&mut header[124..136], content.len() as u64);
+    write_octal(&mut header[136..148], mtime);
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u64 = header.iter().map(|&b| u64::from(b)).sum();
+    write_octal(&mut header[148..155], checksum);
+    writer.write_all(&header)?;
+    writer.write_all(content)?;
+    writer.write_all(&[0; BLOCK][..padding(content.len())])?;
+    Ok(())
+}
+
+/// Read the next regular file of a tar archive; other entries, like directories, are skipped.
+fn read_entry(reader: &mut impl Read) -> Result<Option<(String, Vec<u8>)>> {
+    loop {
+        let mut header = [0u8; BLOCK];
+        reader
+            .read_exact(&mut header)
+            .chain_err(|| invalid("truncated archive"))?;
+        if header.iter().all(|&b| b == 0) {
+            return Ok(None);
+        }
+        let stored = read_octal(&header[148..156])?;
+        let checksum: u64 = header[..148]
+            .iter()
+            .chain(&[b' '; 8])
+            .chain(&header[156..])
+            .map(|&b| u64::from(b))
+            .sum();
+        if stored != checksum {
+            return Err(invalid("corrupted archive"));
+        }
+        let size = read_octal(&header[124..136])? as usize;
+        let mut content = vec![0; size + padding(size)];
+        reader
+            .read_exact(&mut content)
+            .chain_err(|| invalid("truncated archive"))?;
+        content.truncate(size);
+        if header[156] != b'0' && header[156] != 0 {
+            continue;
+        }
+        let mut path = field_str(&header[..100])?;
+        if &header[257..262] == b"ustar" {
+            let prefix = field_str(&header[345..500])?;
+            if !prefix.is_empty() {
+                path = format!("{}/{}", prefix, path);
+            }
+        }
+        return Ok(Some((path, content)));
+    }
+}
+
+fn padding(len: usize) -> usize {
+    (BLOCK - len % BLOCK) % BLOCK
+}
+
+/// Write `value` as a NUL-terminated octal number filling `field`.
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}\0", value, width = field.len() - 1);
+    field.copy_from_slice(digits.as_bytes());
+}
+
+fn read_octal(field: &[u8]) -> Result<u64> {
+    let digits = field_str(field)?;
+    u64::from_str_radix(digits.trim(), 8).chain_err(|| invalid("corrupted archive"))
+}
+
+fn field_str(field: &[u8]) -> Result<String> {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8(field[..end].to_vec()).chain_err(|| invalid("corrupted archive"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::LocalRegistry;
+
+    fn summary(name: &str, version: &str, deps: &[&str]) -> String {
+        let deps: Vec<String> = deps
+            .iter()
+            .map(|dep| format!(r#"{{"name":"{}","req":"1","kind":"normal"}}"#, dep))
+            .collect();
+        format!(
+            r#"{{"name":"{}","vers":"{}","deps":[{}],"cksum":"","features":{{}},"yanked":false}}"#,
+            name,
+            version,
+            deps.join(",")
+        )
+    }
+
+    fn registry_with(files: &[(&str, String)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let mut crates = BTreeMap::new();
+        for (name, content) in files {
+            crates.insert(name.to_string(), content.clone().into_bytes());
+        }
+        install_in(dir.path(), &crates).unwrap();
+        dir
+    }
+
+    #[test]
+    fn captures_the_dependency_closure() {
+        let dir = registry_with(&[
+            (
+                "app-core",
+                summary("app-core", "1.0.0", &["log", "missing"]),
+            ),
+            ("log", summary("log", "0.4.0", &["cfg-if"])),
+            ("cfg-if", summary("cfg-if", "1.0.0", &[])),
+            ("unrelated", summary("unrelated", "1.0.0", &[])),
+        ]);
+        let index = LocalRegistry::open(dir.path()).unwrap();
+        let registry = Url::parse("https://example.com/index").unwrap();
+
+        let mut snapshot = IndexSnapshot::new();
+        snapshot
+            .add_closure(&index, &registry, &["App-Core"])
+            .unwrap();
+        assert_eq!(snapshot.crates(&registry), ["app-core", "cfg-if", "log"]);
+        assert!(snapshot
+            .add_closure(&index, &registry, &["nonexistent"])
+            .is_err());
+
+        let mut archive = Vec::new();
+        snapshot.write_to(&mut archive).unwrap();
+        assert_eq!(archive.len() % BLOCK, 0);
+        let imported = IndexSnapshot::read_from(archive.as_slice()).unwrap();
+        assert_eq!(imported, snapshot);
+
+        let target = tempfile::tempdir().unwrap();
+        install_in(target.path(), &imported.registries[registry.as_str()]).unwrap();
+        let installed = LocalRegistry::open(target.path()).unwrap();
+        assert_eq!(installed.crate_versions("log").unwrap().unwrap().len(), 1);
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn falls_back_on_the_snapshot_while_unreachable() {
+        struct Unreachable;
+
+        impl IndexSource for Unreachable {
+            fn crate_versions(&self, _: &str) -> Result<Option<Vec<CrateVersionInfo>>> {
+                Err(
+                    ErrorKind::Network("https://example.com".to_owned(), "timed out".to_owned())
+                        .into(),
+                )
+            }
+        }
+
+        let dir = registry_with(&[("log", summary("log", "0.4.0", &[]))]);
+        let index = WithSnapshot::new(
+            Box::new(Unreachable),
+            LocalRegistry::open(dir.path()).unwrap(),
+        );
+        assert_eq!(index.crate_versions("log").unwrap().unwrap().len(), 1);
+        assert!(index.crate_versions("serde").unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_tampered_archives() {
+        let registry = Url::parse("https://example.com/index").unwrap();
+        let mut snapshot = IndexSnapshot::new();
+        snapshot.insert(&registry, "log", summary("log", "0.4.0", &[]).into_bytes());
+        let mut archive = Vec::new();
+        snapshot.write_to(&mut archive).unwrap();
+
+        let mut corrupted = archive.clone();
+        corrupted[BLOCK * 3 + 10] ^= 1;
+        assert!(IndexSnapshot::read_from(corrupted.as_slice()).is_err());
+
+        let mut renamed = IndexSnapshot::new();
+        renamed.insert(
+            &registry,
+            "log",
+            summary("other", "0.4.0", &[]).into_bytes(),
+        );
+        let mut archive = Vec::new();
+        renamed.write_to(&mut archive).unwrap();
+        let err = IndexSnapshot::read_from(archive.as_slice()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid index snapshot: `registries/0/3/l/log` lists another crate"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_crate_names() {
+        for (name, path) in &[
+            ("", "registries/0/1/x"),
+            ("éa", "registries/0/3/x/xa"),
+            ("../../x", "registries/0/..//./../../x"),
+        ] {
+            let listing = format!(
+                r#"{{"version":1,"created_at":0,"registries":[{{"url":"https://example.com/index","crates":["{}"]}}]}}"#,
+                name
+            );
+            let mut archive = Vec::new();
+            write_entry(&mut archive, LISTING, listing.as_bytes(), 0).unwrap();
+            write_entry(
+                &mut archive,
+                path,
+                summary(name, "1.0.0", &[]).as_bytes(),
+                0,
+            )
+            .unwrap();
+            archive.extend_from_slice(&[0; 2 * BLOCK]);
+            let err = IndexSnapshot::read_from(archive.as_slice()).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("Invalid index snapshot: invalid crate name `{}`", name),
+            );
+
+            let dir = tempfile::tempdir().unwrap();
+            let target = dir.path().join("cargo-edit").join("snapshots");
+            let mut crates = BTreeMap::new();
+            crates.insert(name.to_string(), summary(name, "1.0.0", &[]).into_bytes());
+            assert!(install_in(&target, &crates).is_err());
+            assert!(!dir.path().join("x").exists());
+        }
+    }
+}
@@ -0,0 +1,405 @@
+//! How new manifest entries are formatted, so edits match a project's existing conventions.
+//!
+//! The style is read from `[package.metadata.cargo-edit.style]`, falling back to
+//! `[workspace.metadata.cargo-edit.style]` in the workspace root:
+//!
+//! ```toml
+//! [workspace.metadata.cargo-edit.style]
+//! table-style = "table"    # or "inline"; by default, like most entries of the manifest
+//! quote-style = "single"   # or "double", the default
+//! trailing-comma = true    # in arrays like `features`
+//! align = true             # align the `=` of entries in dependency tables
+//! ```
+use crate::dependency::Dependency;
+use crate::duplicates::fix_duplicates;
+use crate::errors::*;
+use crate::manifest::{Manifest, CARGO_EDIT_KEY};
+use crate::toml_key::{parse_dotted_key, quote_key};
+use crate::workspace::find_workspace_root;
+use std::path::Path;
+
+/// How dependencies which need more than a version are written.
+///
+/// Entries which already are tables keep their form; this is for new entries, and for plain
+/// versions which need more keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TableStyle {
+    /// `serde = { version = "1.0", features = ["derive"] }`
+    Inline,
+    /// A `[dependencies.serde]` table of its own.
+    Table,
+    /// Like most of the tables already in the manifest, or inline if there are as many of both.
+    Auto,
+}
+
+/// How strings are quoted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuoteStyle {
+    /// `"1.0"`
+    Double,
+    /// `'1.0'`, unless the string itself contains a single quote.
+    Single,
+}
+
+/// The formatting of entries written by cargo-edit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StyleConfig {
+    /// How dependencies which need more than a version are written, `Auto` unless configured
+    pub table_style: TableStyle,
+    /// How strings are quoted
+    pub quote_style: QuoteStyle,
+    /// Whether arrays get a trailing comma
+    pub trailing_comma: bool,
+    /// Whether the `=` of entries in dependency tables are aligned
+    pub align: bool,
+}
+
+impl Default for StyleConfig {
+    fn default() -> Self {
+        StyleConfig {
+            table_style: TableStyle::Auto,
+            quote_style: QuoteStyle::Double,
+            trailing_comma: false,
+            align: false,
+        }
+    }
+}
+
+/// The style as written in a manifest, where every setting is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct PartialStyle {
+    table_style: Option<TableStyle>,
+    quote_style: Option<QuoteStyle>,
+    trailing_comma: Option<bool>,
+    align: Option<bool>,
+}
+
+impl StyleConfig {
+    /// The style for editing the manifest at `manifest_path`.
+    ///
+    /// Settings in the package's own metadata take precedence over those of the workspace root.
+    pub fn for_manifest(manifest_path: &Path) -> Result<Self> {
+        let mut style = StyleConfig::default();
+        let root = find_workspace_root(manifest_path)?;
+        if let Some(partial) = read_style(&root, "workspace")? {
+            style.merge(partial);
+        }
+        if let Some(partial) = read_style(manifest_path, "package")? {
+            style.merge(partial);
+        }
+        Ok(style)
+    }
+
+    /// This style for editing `manifest`, with `TableStyle::Auto` replaced by the form most of
+    /// its table-like dependencies have.
+    pub fn resolve_for(mut self, manifest: &Manifest) -> Self {
+        if self.table_style == TableStyle::Auto {
+            let (tables, inline_tables) =
+                manifest
+                    .dependency_entries()
+                    .fold((0, 0), |(tables, inline_tables), entry| {
+                        (
+                            tables + entry.item.is_table() as usize,
+                            inline_tables + entry.item.is_inline_table() as usize,
+                        )
+                    });
+            self.table_style = if tables > inline_tables {
+                TableStyle::Table
+            } else {
+                TableStyle::Inline
+            };
+        }
+        self
+    }
+
+    fn merge(&mut self, partial: PartialStyle) {
+        self.table_style = partial.table_style.unwrap_or(self.table_style);
+        self.quote_style = partial.quote_style.unwrap_or(self.quote_style);
+        self.trailing_comma = partial.trailing_comma.unwrap_or(self.trailing_comma);
+        self.align = partial.align.unwrap_or(self.align);
+    }
+
+    /// Convert a dependency to TOML in this style.
+    ///
+    /// This is [`Dependency::to_toml`] with strings, arrays and tables re-rendered as configured.
+    /// `TableStyle::Auto` is written inline, unless resolved with [`StyleConfig::resolve_for`].
+    pub fn dependency_to_toml(&self, dep: &Dependency) -> (String, toml_edit::Item) {
+        let (name, item) = dep.to_toml();
+        if self.quote_style == QuoteStyle::Double
+            && !self.trailing_comma
+            && self.table_style != TableStyle::Table
+        {
+            return (name, item);
+        }
+
+        let item = match item.as_value() {
+            Some(toml_edit::Value::InlineTable(table)) => match self.table_style {
+                TableStyle::Inline | TableStyle::Auto => {
+                    let fields: Vec<String> = table
+                        .iter()
+                        .map(|(key, value)| format!("{} = {}", quote_key(key), self.render(value)))
+                        .collect();
+                    parse_value(&format!("{{ {} }}", fields.join(", ")))
+                }
+                TableStyle::Table => {
+                    let mut new_table = toml_edit::Table::new();
+                    for (key, value) in table.iter() {
+                        *new_table.entry(&quote_key(key)) = parse_value(&self.render(value));
+                    }
+                    toml_edit::Item::Table(new_table)
+                }
+            },
+            Some(value) => parse_value(&self.render(value)),
+            None => item,
+        };
+        (name, item)
+    }
+
+    /// Render a value without decoration.
+    fn render(&self, value: &toml_edit::Value) -> String {
+        match value {
+            toml_edit::Value::String(_) => {
+                let s = value.as_str().unwrap_or_default();
+                match self.quote_style {
+                    QuoteStyle::Single if !s.contains('\'') && !s.contains('\n') => {
+                        format!("'{}'", s)
+                    }
+                    _ => toml_edit::Value::from(s).to_string().trim().to_owned(),
+                }
+            }
+            toml_edit::Value::Array(array) => {
+                let items: Vec<String> = array.iter().map(|v| self.render(v)).collect();
+                if items.is_empty() {
+                    "[]".to_owned()
+                } else if self.trailing_comma {
+                    format!("[{},]", items.join(", "))
+                } else {
+                    format!("[{}]", items.join(", "))
+                }
+            }
+            value => value.to_string().trim().to_owned(),
+        }
+    }
+
+    /// Apply the settings which concern whole files, like alignment, to a rendered manifest.
+    pub fn apply_to_document(&self, contents: &str) -> String {
+        if !self.align {
+            return contents.to_owned();
+        }
+        let mut output = Vec::new();
+        let mut run: Vec<(String, String)> = Vec::new();
+        let mut in_dependencies = false;
+        let flush = |run: &mut Vec<(String, String)>, output: &mut Vec<String>| {
+            let width = run
+                .iter()
+                .map(|(key, _)| key.chars().count())
+                .max()
+                .unwrap_or(0);
+            for (key, rest) in run.drain(..) {
+                output.push(format!("{:width$} = {}", key, rest, width = width));
+            }
+        };
+        for line in contents.split('\n') {
+            let trimmed = line.trim();
+            if let Some(header) = trimmed.strip_prefix('[') {
+                flush(&mut run, &mut output);
+                in_dependencies = !header.starts_with('[')
+                    && parse_dotted_key(header)
+                        .and_then(|(path, _)| path.last().cloned())
+                        .is_some_and(|kind| {
+                            ["dependencies", "dev-dependencies", "build-dependencies"]
+                                .contains(&kind.as_str())
+                        });
+                output.push(line.to_owned());
+                continue;
+            }
+            match split_entry(line).filter(|_| in_dependencies) {
+                Some((key, rest)) => run.push((key.to_owned(), rest.to_owned())),
+                None => {
+                    flush(&mut run, &mut output);
+                    output.push(line.to_owned());
+                }
+            }
+        }
+        flush(&mut run, &mut output);
+        output.join("\n")
+    }
+}
+
+/// Split a single-line `key = value` entry with a bare or quoted key, keeping the key as written.
+fn split_entry(line: &str) -> Option<(&str, &str)> {
+    if line.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let (_, rest) = parse_dotted_key(line)?;
+    let key = line[..line.len() - rest.len()].trim_end();
+    let rest = rest.strip_prefix('=')?.trim_start();
+    if key.is_empty() || rest.is_empty() {
+        return None;
+    }
+    Some((key, rest))
+}
+
+fn parse_value(rendered: &str) -> toml_edit::Item {
+    let document: toml_edit::Document = format!("value = {}", rendered)
+        .parse()
+        .expect("rendered values are always valid TOML");
+    document["value"].clone()
+}
+
+fn read_style(manifest_path: &Path, section: &str) -> Result<Option<PartialStyle>> {
+    let content = match std::fs::read_to_string(manifest_path) {
+        Ok(content) => content,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let data: toml::Value =
+        toml::from_str(&fix_duplicates(&content)).chain_err(|| ErrorKind::ParseCargoToml)?;
+    let style = match data
+        .get(section)
+        .and_then(|s| s.get("metadata"))
+        .and_then(|m| m.get(CARGO_EDIT_KEY))
+        .and_then(|c| c.get("style"))
+    {
+        Some(style) => style.clone(),
+        None => return Ok(None),
+    };
+    style
+        .try_into()
+        .map(Some)
+        .chain_err(|| format!("Invalid `{}.metadata.{}.style`", section, CARGO_EDIT_KEY))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dep() -> Dependency {
+        Dependency::new("serde")
+            .set_version("1.0")
+            .set_features(Some(vec!["derive".to_owned(), "rc".to_owned()]))
+    }
+
+    fn render(style: StyleConfig, dep: &Dependency) -> String {
+        let mut manifest: toml_edit::Document = "[dependencies]\n".parse().unwrap();
+        let (name, item) = style.dependency_to_toml(dep);
+        manifest["dependencies"][&name] = item;
+        manifest.to_string()
+    }
+
+    #[test]
+    fn default_style_is_unchanged() {
+        let mut expected: toml_edit::Document = "[dependencies]\n".parse().unwrap();
+        expected["dependencies"]["serde"] = dep().to_toml().1;
+        assert_eq!(render(StyleConfig::default(), &dep()), expected.to_string());
+    }
+
+    #[test]
+    fn quotes_and_trailing_commas() {
+        let style = StyleConfig {
+            quote_style: QuoteStyle::Single,
+            trailing_comma: true,
+            ..StyleConfig::default()
+        };
+        assert_eq!(
+            render(style, &dep()),
+            "[dependencies]\nserde = { version = '1.0', features = ['derive', 'rc',] }\n"
+        );
+        assert_eq!(
+            render(style, &Dependency::new("rand").set_version("0.7")),
+            "[dependencies]\nrand = '0.7'\n"
+        );
+    }
+
+    #[test]
+    fn full_tables() {
+        let style = StyleConfig {
+            table_style: TableStyle::Table,
+            ..StyleConfig::default()
+        };
+        assert_eq!(
+            render(style, &dep()),
+            "[dependencies]\n\n[dependencies.serde]\nversion = \"1.0\"\nfeatures = [\"derive\", \"rc\"]\n"
+        );
+    }
+
+    #[test]
+    fn auto_follows_most_entries() {
+        let manifest: Manifest = "[dependencies]\nrand = { version = \"0.7\", features = [\"small_rng\"] }\n\n[dependencies.serde]\nversion = \"1.0\"\nfeatures = [\"derive\"]\n\n[dev-dependencies.tokio]\nversion = \"1.0\"\nfeatures = [\"full\"]\n"
+            .parse()
+            .unwrap();
+        let style = StyleConfig::default().resolve_for(&manifest);
+        assert_eq!(style.table_style, TableStyle::Table);
+
+        let manifest: Manifest = "[dependencies]\nrand = \"0.7\"\n".parse().unwrap();
+        let style = StyleConfig::default().resolve_for(&manifest);
+        assert_eq!(style.table_style, TableStyle::Inline);
+
+        let configured = StyleConfig {
+            table_style: TableStyle::Inline,
+            ..StyleConfig::default()
+        };
+        let manifest: Manifest = "[dependencies.serde]\nversion = \"1.0\"\n".parse().unwrap();
+        assert_eq!(
+            configured.resolve_for(&manifest).table_style,
+            TableStyle::Inline
+        );
+    }
+
+    #[test]
+    fn aligns_dependency_tables() {
+        let style = StyleConfig {
+            align: true,
+            ..StyleConfig::default()
+        };
+        let manifest = "[package]\nname = \"foo\"\nedition = \"2018\"\n\n[dependencies]\nrand = \"0.7\"\nserde_json = \"1.0\"\n# comment\nlibc = \"0.2\"\n";
+        assert_eq!(
+            style.apply_to_document(manifest),
+            "[package]\nname = \"foo\"\nedition = \"2018\"\n\n[dependencies]\nrand       = \"0.7\"\nserde_json = \"1.0\"\n# comment\nlibc = \"0.2\"\n"
+        );
+    }
+
+    #[test]
+    fn aligns_quoted_keys() {
+        let style = StyleConfig {
+            align: true,
+            ..StyleConfig::default()
+        };
+        let manifest = "[target.'cfg(unix)'.\"dependencies\"]\n\"ünicode\" = \"0.1\"\n'a=b' = \"1\"\nlibc = \"0.2\"\n";
+        assert_eq!(
+            style.apply_to_document(manifest),
+            "[target.'cfg(unix)'.\"dependencies\"]\n\"ünicode\" = \"0.1\"\n'a=b'     = \"1\"\nlibc      = \"0.2\"\n"
+        );
+    }
+
+    #[test]
+    fn workspace_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[workspace]\nmembers = [\"a\"]\n\n[workspace.metadata.cargo-edit.style]\nquote-style = \"single\"\nalign = true\n",
+        )
+        .unwrap();
+        std::fs::create_dir(dir.path().join("a")).unwrap();
+        let member = dir.path().join("a/Cargo.toml");
+        std::fs::write(
+            &member,
+            "[package]\nname = \"a\"\n\n[package.metadata.cargo-edit.style]\nalign = false\n",
+        )
+        .unwrap();
+
+        let style = StyleConfig::for_manifest(&member).unwrap();
+        assert_eq!(style.quote_style, QuoteStyle::Single);
+        assert!(!style.align);
+
+        std::fs::write(
+            &member,
+            "[package]\nname = \"a\"\n\n[package.metadata.cargo-edit.style]\nquote-style = \"backtick\"\n",
+        )
+        .unwrap();
+        assert!(StyleConfig::for_manifest(&member).is_err());
+    }
+}
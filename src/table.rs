@@ -0,0 +1,185 @@
+//! Tables of aligned columns, like the summary `cargo upgrade` prints, fitted to the terminal.
+
+/// The narrowest a column is truncated to, unless its heading is narrower.
+const MIN_COLUMN_WIDTH: usize = 6;
+
+/// The spaces between two columns.
+const COLUMN_GAP: usize = 2;
+
+/// A table of text, rendered with its columns aligned.
+///
+/// When the table is wider than the terminal, the widest column is truncated first, down to its
+/// heading or a few characters, and the cells cut short end with `…`. The first column, which
+/// names the rows, is only truncated once no other column can be.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::Table;
+///
+///   let mut table = Table::new(&["name", "old", "new"]);
+///   table.add_row(vec!["serde".to_owned(), "1.0".to_owned(), "1.0.118".to_owned()]);
+///   table.add_row(vec!["rand".to_owned(), "0.7".to_owned(), "0.8.0".to_owned()]);
+///   assert_eq!(
+///       table.render(None),
+///       "name   old  new\nserde  1.0  1.0.118\nrand   0.7  0.8.0\n"
+///   );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    headings: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// An empty table with a column for each of `headings`.
+    pub fn new(headings: &[&str]) -> Self {
+        Table {
+            headings: headings.iter().map(|&heading| heading.to_owned()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Add a row, with a cell per column; missing cells are left empty and extra ones dropped.
+    pub fn add_row(&mut self, mut cells: Vec<String>) -> &mut Self {
+        cells.resize(self.headings.len(), String::new());
+        self.rows.push(cells);
+        self
+    }
+
+    /// Whether the table has no rows.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The table with its headings, one line per row, fitted to `max_width` characters if given.
+    pub fn render(&self, max_width: Option<usize>) -> String {
+        let mut widths: Vec<usize> = self
+            .headings
+            .iter()
+            .enumerate()
+            .map(|(column, heading)| {
+                self.rows
+                    .iter()
+                    .map(|row| width(&row[column]))
+                    .chain(Some(width(heading)))
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+        if let Some(max_width) = max_width {
+            self.fit(&mut widths, max_width);
+        }
+
+        let mut out = String::new();
+        for row in Some(&self.headings).into_iter().chain(&self.rows) {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, &width)| pad(&truncate(cell, width), width))
+                .collect();
+            out.push_str(cells.join(&" ".repeat(COLUMN_GAP)).trim_end());
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Narrow the widest columns until the table fits in `max_width`, or none can be narrowed.
+    fn fit(&self, widths: &mut [usize], max_width: usize) {
+        let min_widths: Vec<usize> = self
+            .headings
+            .iter()
+            .map(|heading| width(heading).max(MIN_COLUMN_WIDTH))
+            .collect();
+        let gaps = COLUMN_GAP * widths.len().saturating_sub(1);
+        while widths.iter().sum::<usize>() + gaps > max_width {
+            let narrowable = |&(column, &width): &(usize, &usize)| width > min_widths[column];
+            let widest = widths
+                .iter()
+                .enumerate()
+                .skip(1)
+                .filter(narrowable)
+                .max_by_key(|&(column, &width)| (width, std::cmp::Reverse(column)))
+                .or_else(|| widths.iter().enumerate().find(narrowable))
+                .map(|(column, _)| column);
+            match widest {
+                Some(column) => widths[column] -= 1,
+                None => break,
+            }
+        }
+    }
+}
+
+/// The width of `text` on the terminal, counting each character as one column.
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// `text` cut short to `max_width` characters, ending with `…` if it was.
+fn truncate(text: &str, max_width: usize) -> String {
+    if width(text) <= max_width {
+        return text.to_owned();
+    }
+    let mut truncated: String = text.chars().take(max_width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+fn pad(text: &str, column_width: usize) -> String {
+    format!(
+        "{}{}",
+        text,
+        " ".repeat(column_width.saturating_sub(width(text)))
+    )
+}
+
+/// The width of the terminal stdout is written to, or `COLUMNS` if set; `None` when stdout is not
+/// a terminal, as lines are then not wrapped.
+#[cfg(feature = "term_size")]
+pub fn terminal_width() -> Option<usize> {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .or_else(|| term_size::dimensions_stdout().map(|(width, _)| width))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Table {
+        let mut table = Table::new(&["name", "kind", "note"]);
+        table.add_row(vec![
+            "tracing-subscriber".to_owned(),
+            "dev".to_owned(),
+            "incompatible".to_owned(),
+        ]);
+        table.add_row(vec!["log".to_owned(), "normal".to_owned()]);
+        table
+    }
+
+    #[test]
+    fn aligns_the_columns() {
+        assert_eq!(
+            table().render(None),
+            "name                kind    note\n\
+             tracing-subscriber  dev     incompatible\n\
+             log                 normal\n"
+        );
+    }
+
+    #[test]
+    fn truncates_the_widest_columns_to_fit() {
+        assert_eq!(
+            table().render(Some(30)),
+            "name            kind    note\n\
+             tracing-subsc…  dev     incom…\n\
+             log             normal\n"
+        );
+        // Columns are never narrower than their heading or a few characters.
+        assert!(table()
+            .render(Some(10))
+            .lines()
+            .all(|line| line.chars().count() <= 22));
+    }
+}
@@ -0,0 +1,428 @@
+//! Line differences between two versions of a manifest's text, rendered like `git diff`.
+//!
+//! Unlike [`manifest_diff`](crate::manifest_diff), this shows the edit exactly as it is written,
+//! comments and formatting included. Within a changed line, the values which differ are
+//! highlighted, so the new version stands out in a long inline table.
+use crate::errors::*;
+use std::path::Path;
+use termcolor::{Color, ColorSpec, WriteColor};
+
+/// The number of unchanged lines shown around each change.
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Write the difference between `old` and `new`, the text of the manifest at `path` before and
+/// after an edit, as a unified diff. Nothing is written if they are the same.
+///
+/// Colors are only written if `out` supports them, e.g. a `termcolor::StandardStream` created
+/// with `ColorChoice::Auto`.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::write_manifest_diff;
+///   use std::path::Path;
+///
+///   let old = "[dependencies]\nserde = \"1.0\"\n";
+///   let new = "[dependencies]\nserde = \"1.1\"\n";
+///   let mut out = termcolor::Buffer::no_color();
+///   write_manifest_diff(&mut out, Path::new("Cargo.toml"), old, new).unwrap();
+///   assert_eq!(
+///       String::from_utf8(out.into_inner()).unwrap(),
+///       "--- Cargo.toml\n+++ Cargo.toml\n@@ -1,2 +1,2 @@\n [dependencies]\n-serde = \"1.0\"\n+serde = \"1.1\"\n"
+///   );
+/// ```
+pub fn write_manifest_diff(
+    out: &mut impl WriteColor,
+    path: &Path,
+    old: &str,
+    new: &str,
+) -> Result<()> {
+    write_diff(out, path, old, new).chain_err(|| "Failed to write manifest diff")
+}
+
+fn write_diff(out: &mut impl WriteColor, path: &Path, old: &str, new: &str) -> std::io::Result<()> {
+    if old == new {
+        return Ok(());
+    }
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let ops = diff_lines(&old, &new);
+
+    out.set_color(ColorSpec::new().set_bold(true))?;
+    writeln!(out, "--- {}", path.display())?;
+    writeln!(out, "+++ {}", path.display())?;
+    out.reset()?;
+
+    for hunk in hunks(&ops) {
+        write_hunk(out, &old, &new, hunk)?;
+    }
+    Ok(())
+}
+
+fn write_hunk(
+    out: &mut impl WriteColor,
+    old: &[&str],
+    new: &[&str],
+    hunk: &[Op],
+) -> std::io::Result<()> {
+    let (old_start, new_start) = match hunk[0] {
+        Op::Equal(i, j) => (i, j),
+        Op::Delete(i) => (i, insert_position(hunk)),
+        Op::Insert(j) => (delete_position(hunk, old.len()), j),
+    };
+    let old_count = hunk
+        .iter()
+        .filter(|op| !matches!(op, Op::Insert(_)))
+        .count();
+    let new_count = hunk
+        .iter()
+        .filter(|op| !matches!(op, Op::Delete(_)))
+        .count();
+
+    out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+    write!(
+        out,
+        "@@ -{} +{} @@",
+        range(old_start, old_count),
+        range(new_start, new_count)
+    )?;
+    out.reset()?;
+    // Like git names the enclosing function, name the table the hunk starts in.
+    match old[..old_start].iter().rev().find(|line| is_header(line)) {
+        Some(header) => writeln!(out, " {}", header.trim())?,
+        None => writeln!(out)?,
+    }
+
+    let mut i = 0;
+    while i < hunk.len() {
+        match hunk[i] {
+            Op::Equal(index, _) => {
+                writeln!(out, " {}", old[index])?;
+                i += 1;
+            }
+            _ => {
+                let deleted: Vec<usize> = hunk[i..]
+                    .iter()
+                    .map_while(|op| match op {
+                        Op::Delete(index) => Some(*index),
+                        _ => None,
+                    })
+                    .collect();
+                let inserted: Vec<usize> = hunk[i + deleted.len()..]
+                    .iter()
+                    .map_while(|op| match op {
+                        Op::Insert(index) => Some(*index),
+                        _ => None,
+                    })
+                    .collect();
+                i += deleted.len() + inserted.len();
+                // Lines replaced one for one highlight what changed in them.
+                let paired = deleted.len() == inserted.len();
+                for (k, &index) in deleted.iter().enumerate() {
+                    let other = if paired { Some(new[inserted[k]]) } else { None };
+                    write_line(out, '-', Color::Red, old[index], other)?;
+                }
+                for (k, &index) in inserted.iter().enumerate() {
+                    let other = if paired { Some(old[deleted[k]]) } else { None };
+                    write_line(out, '+', Color::Green, new[index], other)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write a removed or added `line`, highlighting where it differs from `other`, the line it
+/// replaces or is replaced by.
+fn write_line(
+    out: &mut impl WriteColor,
+    sign: char,
+    color: Color,
+    line: &str,
+    other: Option<&str>,
+) -> std::io::Result<()> {
+    let plain = ColorSpec::new().set_fg(Some(color)).clone();
+    let highlight = ColorSpec::new()
+        .set_fg(Some(color))
+        .set_bold(true)
+        .set_underline(true)
+        .clone();
+    let tokens = tokenize(line);
+    let (prefix, suffix) = match other {
+        Some(other) => common_ends(&tokens, &tokenize(other)),
+        None => (0, 0),
+    };
+    let changed = prefix..tokens.len() - suffix;
+
+    out.set_color(&plain)?;
+    write!(out, "{}", sign)?;
+    for (k, token) in tokens.iter().enumerate() {
+        if other.is_some() && k == changed.start && !changed.is_empty() {
+            out.set_color(&highlight)?;
+        }
+        write!(out, "{}", token)?;
+        if other.is_some() && k + 1 == changed.end {
+            out.set_color(&plain)?;
+        }
+    }
+    out.reset()?;
+    writeln!(out)
+}
+
+/// The number of tokens `a` and `b` start and end with in common, not overlapping.
+fn common_ends(a: &[&str], b: &[&str]) -> (usize, usize) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let max_suffix = a.len().min(b.len()) - prefix;
+    let suffix = a
+        .iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take(max_suffix)
+        .take_while(|(x, y)| x == y)
+        .count();
+    (prefix, suffix)
+}
+
+/// Split a line of TOML into strings, bare words and numbers, whitespace and punctuation, so that
+/// changed values are highlighted whole.
+fn tokenize(line: &str) -> Vec<&str> {
+    let bytes = line.as_bytes();
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    while start < bytes.len() {
+        let c = bytes[start];
+        let end = match c {
+            b'"' | b'\'' => {
+                let mut end = start + 1;
+                while end < bytes.len() && bytes[end] != c {
+                    // Basic strings escape their quotes.
+                    end += if c == b'"' && bytes[end] == b'\\' {
+                        2
+                    } else {
+                        1
+                    };
+                }
+                (end + 1).min(bytes.len())
+            }
+            _ if c.is_ascii_whitespace() => {
+                start
+                    + bytes[start..]
+                        .iter()
+                        .take_while(|b| b.is_ascii_whitespace())
+                        .count()
+            }
+            _ if is_word(c) => start + bytes[start..].iter().take_while(|b| is_word(**b)).count(),
+            // Anything else is a single character, which may take more than one byte.
+            _ => start + line[start..].chars().next().map_or(1, char::len_utf8),
+        };
+        tokens.push(&line[start..end]);
+        start = end;
+    }
+    tokens
+}
+
+fn is_word(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, b'_' | b'-' | b'.' | b'+')
+}
+
+fn is_header(line: &str) -> bool {
+    line.trim_start().starts_with('[')
+}
+
+/// The `start,count` of a hunk header, which counts lines from 1; an empty range is given by the
+/// line before it.
+fn range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+/// Where the lines inserted at the start of `hunk` go in the new text.
+fn insert_position(hunk: &[Op]) -> usize {
+    hunk.iter()
+        .find_map(|op| match op {
+            Op::Equal(_, j) | Op::Insert(j) => Some(*j),
+            Op::Delete(_) => None,
+        })
+        .unwrap_or(0)
+}
+
+/// Where the lines deleted at the start of `hunk` were in the old text.
+fn delete_position(hunk: &[Op], old_len: usize) -> usize {
+    hunk.iter()
+        .find_map(|op| match op {
+            Op::Equal(i, _) | Op::Delete(i) => Some(*i),
+            Op::Insert(_) => None,
+        })
+        .unwrap_or(old_len)
+}
+
+/// Split `ops` into hunks of changes with up to [`CONTEXT`] unchanged lines around them, merging
+/// changes which are close enough for their context to touch.
+fn hunks(ops: &[Op]) -> Vec<&[Op]> {
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, Op::Equal(..)))
+        .map(|(k, _)| k)
+        .collect();
+    let mut hunks = Vec::new();
+    let mut k = 0;
+    while k < changes.len() {
+        let start = changes[k].saturating_sub(CONTEXT);
+        let mut end = changes[k];
+        while k + 1 < changes.len() && changes[k + 1] - end <= 2 * CONTEXT + 1 {
+            k += 1;
+            end = changes[k];
+        }
+        hunks.push(&ops[start..(end + CONTEXT + 1).min(ops.len())]);
+        k += 1;
+    }
+    hunks
+}
+
+/// The edit turning `old` into `new`, keeping the longest common subsequence of lines.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Op> {
+    // Edits are usually small, so only the lines between the common start and end are compared.
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    // lengths[i][j] is the length of the longest common subsequence of a[i..] and b[j..].
+    let mut lengths = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops: Vec<Op> = (0..prefix).map(|i| Op::Equal(i, i)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            ops.push(Op::Equal(prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lengths[i + 1][j] >= lengths[i][j + 1]) {
+            ops.push(Op::Delete(prefix + i));
+            i += 1;
+        } else {
+            ops.push(Op::Insert(prefix + j));
+            j += 1;
+        }
+    }
+    ops.extend((0..suffix).map(|k| Op::Equal(old.len() - suffix + k, new.len() - suffix + k)));
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(old: &str, new: &str) -> String {
+        let mut out = termcolor::Buffer::no_color();
+        write_manifest_diff(&mut out, Path::new("Cargo.toml"), old, new).unwrap();
+        String::from_utf8(out.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn renders_hunks_with_context() {
+        let old = "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2018\"\n\n\
+                   [dependencies]\nrand = \"0.7\"\nregex = \"1.3\"\nserde = \"1.0\"\n\
+                   # keep in sync\ntokio = \"0.2\"\n";
+        let new = old
+            .replace("version = \"0.1.0\"", "version = \"0.2.0\"")
+            .replace("tokio = \"0.2\"\n", "tokio = \"1.0\"\nurl = \"2.2\"\n");
+        assert_eq!(
+            render(old, &new),
+            "--- Cargo.toml\n+++ Cargo.toml\n\
+             @@ -1,6 +1,6 @@\n [package]\n name = \"app\"\n-version = \"0.1.0\"\n+version = \"0.2.0\"\n \
+             edition = \"2018\"\n \n [dependencies]\n\
+             @@ -8,4 +8,5 @@ [dependencies]\n regex = \"1.3\"\n serde = \"1.0\"\n # keep in sync\n\
+             -tokio = \"0.2\"\n+tokio = \"1.0\"\n+url = \"2.2\"\n"
+        );
+        assert_eq!(render(old, old), "");
+        assert_eq!(
+            render("", "[dependencies]\n"),
+            "--- Cargo.toml\n+++ Cargo.toml\n@@ -0,0 +1,1 @@\n+[dependencies]\n"
+        );
+    }
+
+    #[test]
+    fn highlights_changed_values() {
+        let mut out = termcolor::Buffer::ansi();
+        write_manifest_diff(
+            &mut out,
+            Path::new("Cargo.toml"),
+            "serde = { version = \"1.0\", features = [\"derive\"] }\n",
+            "serde = { version = \"1.0.130\", features = [\"derive\"] }\n",
+        )
+        .unwrap();
+        let out = String::from_utf8(out.into_inner()).unwrap();
+        // Only the version is underlined, in both lines.
+        assert_eq!(out.matches("\x1b[4m").count(), 2);
+        assert!(out.contains("\x1b[4m\x1b[32m\"1.0.130\"\x1b[0m"));
+    }
+
+    #[test]
+    fn tokenizes_toml() {
+        assert_eq!(
+            tokenize(r#"a-b = { version = "1.0", path = 'x"y', k = "q\"r" } # é"#),
+            vec![
+                "a-b",
+                " ",
+                "=",
+                " ",
+                "{",
+                " ",
+                "version",
+                " ",
+                "=",
+                " ",
+                "\"1.0\"",
+                ",",
+                " ",
+                "path",
+                " ",
+                "=",
+                " ",
+                "'x\"y'",
+                ",",
+                " ",
+                "k",
+                " ",
+                "=",
+                " ",
+                r#""q\"r""#,
+                " ",
+                "}",
+                " ",
+                "#",
+                " ",
+                "é"
+            ]
+        );
+    }
+}
@@ -0,0 +1,319 @@
+//! TLS settings for network requests, taken from the cargo config the way cargo takes them.
+//!
+//! `http.cainfo` (or `CARGO_HTTP_CAINFO`) replaces the bundled root certificates with those of a
+//! PEM file, e.g. the CA of a proxy intercepting TLS, for HTTPS requests and git fetches alike.
+//! `http.ssl-version` (or `CARGO_HTTP_SSL_VERSION`) limits the TLS versions of HTTPS requests.
+//! `http.check-revoke` is accepted, but like cargo outside of Windows, revocation isn't checked.
+use crate::errors::*;
+use crate::registry::config_paths;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+/// The TLS settings of the cargo config.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct TlsSettings {
+    /// The PEM file of the root certificates to trust instead of the bundled ones
+    pub(crate) cainfo: Option<PathBuf>,
+    /// The oldest TLS version to use, like `tlsv1.2`
+    pub(crate) min_version: Option<String>,
+    /// The newest TLS version to use, like `tlsv1.3`
+    pub(crate) max_version: Option<String>,
+    /// Whether certificates should be checked for revocation
+    pub(crate) check_revoke: Option<bool>,
+}
+
+impl TlsSettings {
+    /// The settings of the cargo config files of the current directory, overridden by the
+    /// `CARGO_HTTP_*` environment variables.
+    pub(crate) fn from_config() -> Result<Self> {
+        let mut settings = TlsSettings::default();
+        let cwd = std::env::current_dir()?;
+        // `config_paths` looks in the ancestors of a manifest's directory, the most specific
+        // first, so they are read the other way round for it to override the others.
+        for path in config_paths(&cwd.join("Cargo.toml"))?.iter().rev() {
+            let content = std::fs::read(path)?;
+            let config: toml::Value =
+                toml::from_slice(&content).chain_err(|| ErrorKind::InvalidCargoConfig)?;
+            if let Some(http) = config.get("http") {
+                // Relative paths are relative to the directory holding the `.cargo` directory.
+                let base = path.parent().and_then(Path::parent).unwrap_or(&cwd);
+                settings.merge_config(http, base)?;
+            }
+        }
+        if let Some(cainfo) = env_var("CARGO_HTTP_CAINFO") {
+            settings.cainfo = Some(cwd.join(cainfo));
+        }
+        if let Some(version) = env_var("CARGO_HTTP_SSL_VERSION") {
+            settings.min_version = Some(version.clone());
+            settings.max_version = Some(version);
+        }
+        if let Some(check_revoke) = env_var("CARGO_HTTP_CHECK_REVOKE") {
+            settings.check_revoke = Some(parse_bool(&check_revoke)?);
+        }
+        Ok(settings)
+    }
+
+    /// Take the settings of the `[http]` table of a config file over those read so far.
+    fn merge_config(&mut self, http: &toml::Value, base: &Path) -> Result<()> {
+        let invalid = || Error::from(ErrorKind::InvalidCargoConfig);
+        if let Some(cainfo) = http.get("cainfo") {
+            self.cainfo = Some(base.join(cainfo.as_str().ok_or_else(invalid)?));
+        }
+        match http.get("ssl-version") {
+            Some(toml::Value::String(version)) => {
+                self.min_version = Some(version.clone());
+                self.max_version = Some(version.clone());
+            }
+            Some(toml::Value::Table(range)) => {
+                let version = |key| -> Result<Option<String>> {
+                    range
+                        .get(key)
+                        .map(|version| version.as_str().map(str::to_owned).ok_or_else(invalid))
+                        .transpose()
+                };
+                self.min_version = version("min")?;
+                self.max_version = version("max")?;
+            }
+            Some(_) => return Err(invalid()),
+            None => {}
+        }
+        if let Some(check_revoke) = http.get("check-revoke") {
+            self.check_revoke = Some(check_revoke.as_bool().ok_or_else(invalid)?);
+        }
+        Ok(())
+    }
+
+    fn is_default(&self) -> bool {
+        self.cainfo.is_none() && self.min_version.is_none() && self.max_version.is_none()
+    }
+
+    /// The TLS configuration of HTTPS requests following these settings.
+    fn client_config(&self) -> Result<rustls::ClientConfig> {
+        let mut config = rustls::ClientConfig::new();
+        match &self.cainfo {
+            Some(cainfo) => {
+                let mut read = || -> Result<()> {
+                    let file = std::fs::File::open(cainfo)?;
+                    let certs =
+                        rustls::internal::pemfile::certs(&mut std::io::BufReader::new(file))
+                            .map_err(|()| "not a PEM file")?;
+                    if certs.is_empty() {
+                        return Err("no certificates found".into());
+                    }
+                    for cert in certs {
+                        config
+                            .root_store
+                            .add(&cert)
+                            .map_err(|err| format!("invalid certificate: {:?}", err))?;
+                    }
+                    Ok(())
+                };
+                read().chain_err(|| {
+                    format!(
+                        "Failed to read the root certificates of `http.cainfo` from {}",
+                        cainfo.display()
+                    )
+                })?;
+            }
+            None => config
+                .root_store
+                .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS),
+        }
+        if self.check_revoke == Some(true) {
+            event!(
+                DEBUG,
+                "`http.check-revoke` is set, but revocation isn't checked"
+            );
+        }
+        config.versions = tls_versions(self.min_version.as_deref(), self.max_version.as_deref())?;
+        Ok(config)
+    }
+}
+
+/// The TLS versions between `min` and `max`, of those rustls supports.
+fn tls_versions(min: Option<&str>, max: Option<&str>) -> Result<Vec<rustls::ProtocolVersion>> {
+    // In the order of cargo's `http.ssl-version` values; rustls supports TLS 1.2 and later only.
+    const VERSIONS: &[(&str, Option<rustls::ProtocolVersion>)] = &[
+        ("tlsv1", None),
+        ("tlsv1.1", None),
+        ("tlsv1.2", Some(rustls::ProtocolVersion::TLSv1_2)),
+        ("tlsv1.3", Some(rustls::ProtocolVersion::TLSv1_3)),
+    ];
+    let position = |version: Option<&str>, default: usize| match version {
+        None | Some("default") => Ok(default),
+        Some(version) => VERSIONS
+            .iter()
+            .position(|(name, _)| *name == version)
+            .ok_or_else(|| Error::from(format!("Unknown `http.ssl-version` `{}`", version))),
+    };
+    let min = position(min, 0)?;
+    let max = position(max, VERSIONS.len() - 1)?;
+    let versions: Vec<_> = VERSIONS
+        .get(min..=max)
+        .unwrap_or_default()
+        .iter()
+        .rev()
+        .filter_map(|(_, version)| *version)
+        .collect();
+    if versions.is_empty() {
+        return Err(
+            "`http.ssl-version` allows no version of TLS cargo-edit supports, which are \
+                    `tlsv1.2` and `tlsv1.3`"
+                .into(),
+        );
+    }
+    Ok(versions)
+}
+
+/// The TLS configuration of HTTPS requests, or `None` for ureq's own.
+///
+/// The settings are read once per process.
+fn client_config() -> Result<Option<Arc<rustls::ClientConfig>>> {
+    static CONFIG: OnceLock<std::result::Result<Option<Arc<rustls::ClientConfig>>, String>> =
+        OnceLock::new();
+    let config = CONFIG.get_or_init(|| {
+        let settings = TlsSettings::from_config().map_err(|err| error_chain_message(&err))?;
+        if settings.is_default() {
+            return Ok(None);
+        }
+        let config = settings
+            .client_config()
+            .map_err(|err| error_chain_message(&err))?;
+        Ok(Some(Arc::new(config)))
+    });
+    config.clone().map_err(Error::from)
+}
+
+/// `err` with its causes, to keep them in an error which is stored.
+fn error_chain_message(err: &Error) -> String {
+    err.iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+/// Make `req` use the TLS settings of the cargo config.
+pub fn apply_tls(req: &mut ureq::Request) -> Result<()> {
+    if let Some(config) = client_config()? {
+        req.set_tls_config(config);
+    }
+    Ok(())
+}
+
+/// The `-c` options making the `git` command line trust the same root certificates as cargo.
+pub(crate) fn git_tls_args() -> Result<Vec<String>> {
+    Ok(match TlsSettings::from_config()?.cainfo {
+        Some(cainfo) => vec![
+            "-c".to_owned(),
+            format!("http.sslCAInfo={}", cainfo.display()),
+        ],
+        None => Vec::new(),
+    })
+}
+
+/// Make libgit2 trust the root certificates of `http.cainfo`, if set.
+///
+/// git2 has no safe binding for the option.
+#[allow(unsafe_code)]
+pub(crate) fn apply_git_tls() -> Result<()> {
+    let cainfo = match TlsSettings::from_config()?.cainfo {
+        Some(cainfo) => cainfo,
+        None => return Ok(()),
+    };
+    let file = std::ffi::CString::new(cainfo.to_string_lossy().into_owned())
+        .chain_err(|| "`http.cainfo` contains a NUL byte")?;
+    libgit2_sys::init();
+    // SAFETY: both locations are C strings or null as libgit2 expects, and libgit2 copies them.
+    let code = unsafe {
+        libgit2_sys::git_libgit2_opts(
+            libgit2_sys::GIT_OPT_SET_SSL_CERT_LOCATIONS as std::os::raw::c_int,
+            file.as_ptr(),
+            std::ptr::null::<std::os::raw::c_char>(),
+        )
+    };
+    if code < 0 {
+        let err = git2::Error::last_error(code).map_or_else(String::new, |err| err.to_string());
+        return Err(format!(
+            "libgit2 can't use the root certificates of `http.cainfo` ({}); set \
+             `net.git-fetch-with-cli` to fetch with `git` instead",
+            err
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value.trim() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(ErrorKind::InvalidCargoConfig.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_tls_settings_of_a_config_file() {
+        let config: toml::Value = toml::from_str(
+            r#"
+            cainfo = "certs/corporate.pem"
+            check-revoke = false
+            ssl-version = { min = "tlsv1.2" }
+            "#,
+        )
+        .unwrap();
+        let mut settings = TlsSettings::default();
+        settings.merge_config(&config, Path::new("/work")).unwrap();
+        assert_eq!(
+            settings,
+            TlsSettings {
+                cainfo: Some(PathBuf::from("/work/certs/corporate.pem")),
+                min_version: Some("tlsv1.2".to_owned()),
+                max_version: None,
+                check_revoke: Some(false),
+            }
+        );
+    }
+
+    #[test]
+    fn limits_tls_versions() {
+        use rustls::ProtocolVersion::{TLSv1_2, TLSv1_3};
+        assert_eq!(tls_versions(None, None).unwrap(), [TLSv1_3, TLSv1_2]);
+        assert_eq!(
+            tls_versions(Some("tlsv1"), None).unwrap(),
+            [TLSv1_3, TLSv1_2]
+        );
+        assert_eq!(tls_versions(None, Some("tlsv1.2")).unwrap(), [TLSv1_2]);
+        assert_eq!(
+            tls_versions(Some("tlsv1.3"), Some("default")).unwrap(),
+            [TLSv1_3]
+        );
+        assert!(tls_versions(None, Some("tlsv1.1")).is_err());
+        assert!(tls_versions(Some("sslv3"), None).is_err());
+    }
+
+    #[test]
+    fn rejects_files_without_certificates() {
+        let dir = tempfile::tempdir().unwrap();
+        let cainfo = dir.path().join("empty.pem");
+        std::fs::write(&cainfo, "").unwrap();
+        let settings = TlsSettings {
+            cainfo: Some(cainfo),
+            ..TlsSettings::default()
+        };
+        let err = match settings.client_config() {
+            Ok(_) => panic!("an empty file has no certificates"),
+            Err(err) => err,
+        };
+        assert!(err.to_string().contains("http.cainfo"), "{}", err);
+    }
+}
@@ -0,0 +1,198 @@
+//! Keys of manifests written in any of the ways TOML allows: bare, as basic strings with escapes
+//! like `"café"`, or as literal strings like `'serde'`.
+//!
+//! Keys are matched by what they spell, whichever way they are written. Entries already in a
+//! manifest keep their key as it was written, and keys written anew are quoted with
+//! [`quote_key`] only when they need to be.
+use toml_edit::{Item, Value};
+
+/// `key` as it is written in a manifest: bare if it can be, as a basic string otherwise, or as a
+/// literal string if that spares escaping quotes or backslashes.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::quote_key;
+///
+///   assert_eq!(quote_key("serde"), "serde");
+///   assert_eq!(quote_key("cfg(unix)"), r#""cfg(unix)""#);
+///   assert_eq!(quote_key(r#"cfg(target_os = "linux")"#), r#"'cfg(target_os = "linux")'"#);
+///   assert_eq!(quote_key(r#"it's "quoted""#), r#""it's \"quoted\"""#);
+/// ```
+pub fn quote_key(key: &str) -> String {
+    if !key.is_empty() && key.chars().all(is_bare) {
+        return key.to_owned();
+    }
+    let escaped = |c: char| c == '"' || c == '\\' || c.is_control();
+    if key.contains(escaped) && !key.contains(|c: char| c == '\'' || c.is_control()) {
+        return format!("'{}'", key);
+    }
+    let mut quoted = String::with_capacity(key.len() + 2);
+    quoted.push('"');
+    for c in key.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\u{8}' => quoted.push_str("\\b"),
+            '\t' => quoted.push_str("\\t"),
+            '\n' => quoted.push_str("\\n"),
+            '\u{c}' => quoted.push_str("\\f"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Parse the dotted key at the start of `s`, like `target.'cfg(unix)'."dependencies"`, into what
+/// each of its parts spells, and the text after it.
+///
+/// Returns `None` if `s` doesn't start with a key.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::parse_dotted_key;
+///
+///   let (key, rest) = parse_dotted_key(r#"target.'cfg(unix)'."café" = 1"#).unwrap();
+///   assert_eq!(key, ["target", "cfg(unix)", "café"]);
+///   assert_eq!(rest, "= 1");
+/// ```
+pub fn parse_dotted_key(s: &str) -> Option<(Vec<String>, &str)> {
+    let mut segments = Vec::new();
+    let mut s = s;
+    loop {
+        s = s.trim_start_matches([' ', '\t']);
+        let (segment, rest) = if let Some(rest) = s.strip_prefix('"') {
+            parse_basic(rest)?
+        } else if let Some(rest) = s.strip_prefix('\'') {
+            let end = rest.find(['\'', '\n'])?;
+            if !rest[end..].starts_with('\'') {
+                return None;
+            }
+            (rest[..end].to_owned(), &rest[end + 1..])
+        } else {
+            let end = s.find(|c| !is_bare(c)).unwrap_or(s.len());
+            if end == 0 {
+                return None;
+            }
+            (s[..end].to_owned(), &s[end..])
+        };
+        segments.push(segment);
+        let rest = rest.trim_start_matches([' ', '\t']);
+        match rest.strip_prefix('.') {
+            Some(rest) => s = rest,
+            None => return Some((segments, rest)),
+        }
+    }
+}
+
+/// The entry `key` of the table-like `item`, inserted as `Item::None` if missing, like indexing
+/// `item` mutably but with `key` quoted if it needs to be.
+pub(crate) fn entry<'a>(item: &'a mut Item, key: &str) -> &'a mut Item {
+    if item.is_none() {
+        *item = toml_edit::value(toml_edit::InlineTable::default());
+    }
+    if item.is_table() {
+        return item
+            .as_table_mut()
+            .expect("checked above")
+            .entry(&quote_key(key));
+    }
+    if item[key].is_none() {
+        if let Some(table) = item.as_inline_table_mut() {
+            // The placeholder only gives the new entry its key; indexing keeps the key of an
+            // existing entry.
+            table.remove(key);
+            table.get_or_insert(&quote_key(key), Value::from(false));
+            item[key] = Item::None;
+        }
+    }
+    &mut item[key]
+}
+
+fn is_bare(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+/// Parse the rest of a basic string after its opening quote.
+fn parse_basic(s: &str) -> Option<(String, &str)> {
+    let mut parsed = String::new();
+    let mut chars = s.char_indices();
+    loop {
+        match chars.next()? {
+            (i, '"') => return Some((parsed, &s[i + 1..])),
+            (_, '\n') => return None,
+            (_, '\\') => parsed.push(match chars.next()?.1 {
+                'b' => '\u{8}',
+                't' => '\t',
+                'n' => '\n',
+                'f' => '\u{c}',
+                'r' => '\r',
+                '"' => '"',
+                '\\' => '\\',
+                'u' => unicode_escape(&mut chars, 4)?,
+                'U' => unicode_escape(&mut chars, 8)?,
+                _ => return None,
+            }),
+            (_, c) => parsed.push(c),
+        }
+    }
+}
+
+fn unicode_escape(chars: &mut std::str::CharIndices<'_>, digits: usize) -> Option<char> {
+    let hex: String = chars.take(digits).map(|(_, c)| c).collect();
+    if hex.len() != digits {
+        return None;
+    }
+    std::char::from_u32(u32::from_str_radix(&hex, 16).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_kind_of_key() {
+        let parse = |s| parse_dotted_key(s).map(|(key, rest)| (key.join("|"), rest.to_owned()));
+        assert_eq!(parse("serde = 1"), Some(("serde".into(), "= 1".into())));
+        assert_eq!(parse(r"'a\b' = 1"), Some((r"a\b".into(), "= 1".into())));
+        assert_eq!(
+            parse(r#""ü\U0001F600\t\"" ]"#),
+            Some(("ü😀\t\"".into(), "]".into()))
+        );
+        assert_eq!(
+            parse(r#"target . "cfg(unix)".'dependencies']"#),
+            Some(("target|cfg(unix)|dependencies".into(), "]".into()))
+        );
+        assert_eq!(parse(r#""\q" = 1"#), None);
+        assert_eq!(parse(r#""open = 1"#), None);
+        assert_eq!(parse("= 1"), None);
+    }
+
+    #[test]
+    fn quotes_keys_which_round_trip() {
+        for key in &["serde", "café", r#"a"b'c\d"#, "tab\there", "bell\u{7}"] {
+            let quoted = quote_key(key);
+            assert_eq!(parse_dotted_key(&quoted), Some((vec![key.to_string()], "")));
+        }
+    }
+
+    #[test]
+    fn inserts_keys_with_their_quotes() {
+        let mut document: toml_edit::Document =
+            "[dependencies]\n'rand' = \"0.7\"\n".parse().unwrap();
+        *entry(&mut document["dependencies"], "rand") = toml_edit::value("0.8");
+        *entry(&mut document["dependencies"], r"a\b") = toml_edit::value("1");
+        document["target"] = toml_edit::value(toml_edit::InlineTable::default());
+        *entry(&mut document["target"], "cfg(unix)") = toml_edit::value("2");
+        document["target"].as_inline_table_mut().unwrap().fmt();
+        assert_eq!(
+            document.to_string(),
+            "target = { \"cfg(unix)\" = \"2\" }\n\
+             [dependencies]\n'rand' = \"0.8\"\n'a\\b' = \"1\"\n"
+        );
+    }
+}
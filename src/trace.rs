@@ -0,0 +1,132 @@
+//! Spans and events for `tracing` subscribers.
+//!
+//! Without the `tracing` feature, the macros expand to nothing, so instrumenting a function costs
+//! nothing either.
+
+/// Enter a span at `level`, e.g. `DEBUG`, until the returned guard is dropped:
+/// `let _span = span!(DEBUG, "fetch", url = %url);`
+#[cfg(feature = "tracing")]
+macro_rules! span {
+    ($level:ident, $($args:tt)+) => {
+        tracing::span!(tracing::Level::$level, $($args)+).entered()
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! span {
+    ($level:ident, $($args:tt)+) => {
+        $crate::trace::NoSpan
+    };
+}
+
+/// Record an event at `level`, e.g. `DEBUG`: `event!(DEBUG, status, "response");`
+#[cfg(feature = "tracing")]
+macro_rules! event {
+    ($level:ident, $($args:tt)+) => {
+        tracing::event!(tracing::Level::$level, $($args)+)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! event {
+    ($level:ident, $($args:tt)+) => {{}};
+}
+
+/// What [`span!`] returns without the `tracing` feature.
+#[cfg(not(feature = "tracing"))]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct NoSpan;
+
+#[cfg(feature = "tracing")]
+mod json;
+
+/// The environment variable holding the filter of [`init_tracing`].
+#[cfg(feature = "tracing")]
+const LOG_ENV: &str = "CARGO_EDIT_LOG";
+
+/// The environment variable choosing the format of the logs: `text`, the default, or `json`.
+#[cfg(feature = "tracing")]
+const LOG_FORMAT_ENV: &str = "CARGO_EDIT_LOG_FORMAT";
+
+/// The environment variable naming a file to append the logs to, instead of stderr.
+#[cfg(feature = "tracing")]
+const LOG_FILE_ENV: &str = "CARGO_EDIT_LOG_FILE";
+
+/// Log spans and events to stderr as `CARGO_EDIT_LOG` says, like `CARGO_EDIT_LOG=debug` or
+/// `CARGO_EDIT_LOG=cargo_edit::fetch=trace`; the time spent in each span is logged when it
+/// closes.
+///
+/// Without the variable, the [`verbosity`](crate::verbosity()) decides: `Verbose` logs the crates
+/// queried from the index and cache hits, `VeryVerbose` also the HTTP requests and the time spent
+/// in each span, and nothing is logged otherwise. Applications with their own `tracing` subscriber
+/// don't need this; the binaries call it first thing.
+///
+/// `CARGO_EDIT_LOG_FORMAT=json` logs each event as a JSON object on a line of its own, with its
+/// fields and the spans it happened in, and `CARGO_EDIT_LOG_FILE` appends the logs to a file
+/// instead, so that they can be looked at after an automated run failed. Either way they are kept
+/// apart from the output on stdout.
+#[cfg(feature = "tracing")]
+pub fn init_tracing() -> crate::errors::Result<()> {
+    use crate::errors::*;
+    use crate::verbosity::{verbosity, Verbosity};
+    use std::fs::OpenOptions;
+    use std::path::Path;
+    use std::sync::Arc;
+    use tracing_subscriber::fmt::format::FmtSpan;
+    use tracing_subscriber::EnvFilter;
+
+    let (filter, spans) = match std::env::var(LOG_ENV) {
+        Ok(filter) => (filter, FmtSpan::CLOSE),
+        Err(_) => match verbosity() {
+            Verbosity::Quiet | Verbosity::Normal => return Ok(()),
+            Verbosity::Verbose => (VERBOSE_FILTER.to_owned(), FmtSpan::NONE),
+            Verbosity::VeryVerbose => (VERY_VERBOSE_FILTER.to_owned(), FmtSpan::CLOSE),
+        },
+    };
+    let filter = EnvFilter::try_new(&filter)
+        .chain_err(|| format!("Invalid `{}` filter `{}`", LOG_ENV, filter))?;
+    let json = match std::env::var(LOG_FORMAT_ENV).as_deref() {
+        Ok("json") => true,
+        Ok("text") | Err(_) => false,
+        Ok(format) => {
+            return Err(format!("Unknown `{}` format `{}`", LOG_FORMAT_ENV, format).into());
+        }
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(spans);
+    let result = match std::env::var_os(LOG_FILE_ENV) {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .chain_err(|| format!("Failed to open log file {}", Path::new(&path).display()))?;
+            let file = Arc::new(file);
+            if json {
+                builder
+                    .fmt_fields(json::JsonFields)
+                    .event_format(json::JsonFormat)
+                    .with_writer(file)
+                    .try_init()
+            } else {
+                builder.with_writer(file).try_init()
+            }
+        }
+        None if json => builder
+            .fmt_fields(json::JsonFields)
+            .event_format(json::JsonFormat)
+            .with_writer(std::io::stderr)
+            .try_init(),
+        None => builder.with_writer(std::io::stderr).try_init(),
+    };
+    result.map_err(|err| Error::from(err.to_string()))
+}
+
+/// What `-v` logs: index queries and cache hits, but not the requests behind them.
+#[cfg(feature = "tracing")]
+const VERBOSE_FILTER: &str = "cargo_edit=debug,cargo_edit::retry=info";
+
+/// What `-vv` logs: everything, down to the HTTP requests.
+#[cfg(feature = "tracing")]
+const VERY_VERBOSE_FILTER: &str = "cargo_edit=trace";
@@ -0,0 +1,171 @@
+//! Logs as JSON lines, for `CARGO_EDIT_LOG_FORMAT=json`.
+
+use serde_json::{Map, Number, Value};
+use std::fmt::{self, Write};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::time::{FormatTime, SystemTime};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Write each event as an object on a line of its own, like
+/// `{"fields":{"message":"found","versions":12},"level":"DEBUG","spans":[{"crate_name":"serde","name":"query"}],"target":"cargo_edit::fetch","timestamp":"…"}`.
+///
+/// `spans` lists the spans the event happened in, outermost first, each with its fields.
+pub(super) struct JsonFormat;
+
+impl<S> FormatEvent<S, JsonFields> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, JsonFields>,
+        writer: &mut dyn Write,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut timestamp = String::new();
+        SystemTime.format_time(&mut timestamp)?;
+        let metadata = event.metadata();
+        let mut fields = JsonVisitor::default();
+        event.record(&mut fields);
+
+        // The spans of the event, or of the span a `close` event is about.
+        let leaf = match event.parent() {
+            Some(id) => ctx.span(id),
+            None if event.is_contextual() => ctx.lookup_current(),
+            None => None,
+        };
+        let mut spans = Vec::new();
+        for span in leaf.into_iter().flat_map(|leaf| leaf.scope().from_root()) {
+            let mut object = Map::new();
+            object.insert("name".to_owned(), span.name().into());
+            let extensions = span.extensions();
+            if let Some(Value::Object(fields)) = extensions
+                .get::<FormattedFields<JsonFields>>()
+                .and_then(|fields| serde_json::from_str(&fields.fields).ok())
+            {
+                object.extend(fields);
+            }
+            spans.push(Value::Object(object));
+        }
+
+        let mut line = Map::new();
+        line.insert("timestamp".to_owned(), timestamp.into());
+        line.insert("level".to_owned(), metadata.level().to_string().into());
+        line.insert("target".to_owned(), metadata.target().into());
+        line.insert("fields".to_owned(), Value::Object(fields.0));
+        line.insert("spans".to_owned(), Value::Array(spans));
+        writeln!(writer, "{}", Value::Object(line))
+    }
+}
+
+/// Record the fields of spans as a JSON object, for [`JsonFormat`] to pick them up.
+pub(super) struct JsonFields;
+
+impl<'writer> FormatFields<'writer> for JsonFields {
+    fn format_fields<R: RecordFields>(
+        &self,
+        writer: &'writer mut dyn Write,
+        fields: R,
+    ) -> fmt::Result {
+        let mut visitor = JsonVisitor::default();
+        fields.record(&mut visitor);
+        write!(writer, "{}", Value::Object(visitor.0))
+    }
+
+    fn add_fields(
+        &self,
+        current: &'writer mut String,
+        fields: &tracing::span::Record<'_>,
+    ) -> fmt::Result {
+        let mut visitor = JsonVisitor(match serde_json::from_str(current) {
+            Ok(Value::Object(object)) => object,
+            _ => Map::new(),
+        });
+        fields.record(&mut visitor);
+        *current = Value::Object(visitor.0).to_string();
+        Ok(())
+    }
+}
+
+/// The fields of an event or span, with numbers and booleans kept as such.
+#[derive(Default)]
+struct JsonVisitor(Map<String, Value>);
+
+impl Visit for JsonVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        let value = Number::from_f64(value).map_or(Value::Null, Value::Number);
+        self.0.insert(field.name().to_owned(), value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_owned(), format!("{:?}", value).into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_events_with_their_spans() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .fmt_fields(JsonFields)
+            .event_format(JsonFormat)
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("query", crate_name = "serde").entered();
+            tracing::info!(versions = 3u64, yanked = false, "found");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let line: Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["target"], "cargo_edit::trace::json::tests");
+        assert_eq!(
+            line["fields"],
+            serde_json::json!({"message": "found", "versions": 3, "yanked": false})
+        );
+        assert_eq!(
+            line["spans"],
+            serde_json::json!([{"name": "query", "crate_name": "serde"}])
+        );
+    }
+}
@@ -0,0 +1,320 @@
+//! Writing edits to several manifests together, so that either all of them are written or none.
+use crate::cancel::is_cancelled;
+use crate::errors::*;
+use crate::manifest::Manifest;
+use crate::style::StyleConfig;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Edits to any number of manifests, staged to be written together.
+///
+/// Nothing touches the disk until [`ManifestTransaction::commit`], which writes every changed
+/// manifest to a temporary file next to it and then renames it over the original, so no manifest
+/// is ever left half-written. If a manifest can't be written, or the operation is
+/// [cancelled](crate::cancel) before the last one is replaced, those already replaced are restored,
+/// so a workspace is either fully edited or left as it was.
+///
+/// # Examples
+///
+/// ```no_run
+///   use cargo_edit::{Dependency, LocalManifest, Manifest, ManifestTransaction};
+///   use std::path::Path;
+///
+///   let mut transaction = ManifestTransaction::new();
+///   let dependencies = vec!["dependencies".to_owned()];
+///
+///   // Edits are made in memory, and any number of them staged per manifest.
+///   let path = Path::new("a/Cargo.toml");
+///   let mut a = Manifest::open(&Some(path.to_owned()))?;
+///   a.insert_into_table(&dependencies, &Dependency::new("log").set_version("0.4"))?;
+///   a.remove_from_table(&dependencies, "env_logger")?;
+///   transaction.stage(path, &a)?;
+///
+///   let mut b = LocalManifest::try_new(Path::new("b/Cargo.toml"))?;
+///   b.upgrade_in_memory(&Dependency::new("serde").set_version("1.0.130"), false, false)?;
+///   b.stage(&mut transaction)?;
+///
+///   // Nothing was written so far; dropping the transaction instead would discard the edits.
+///   transaction.commit()?;
+///   # Ok::<(), cargo_edit::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct ManifestTransaction {
+    staged: Vec<(PathBuf, String)>,
+}
+
+/// A manifest written to its temporary file, waiting to replace the original.
+struct Pending<'a> {
+    path: &'a Path,
+    temp: PathBuf,
+    original: Vec<u8>,
+}
+
+impl ManifestTransaction {
+    /// Start a transaction with nothing staged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stage `manifest` to be written to `path`, formatted with the style configured for it.
+    ///
+    /// Staging the same path again replaces the earlier contents.
+    pub fn stage(&mut self, path: &Path, manifest: &Manifest) -> Result<()> {
+        self.stage_with_style(path, manifest, &StyleConfig::for_manifest(path)?)
+    }
+
+    /// Stage `manifest` to be written to `path`, formatted according to `style`.
+    pub fn stage_with_style(
+        &mut self,
+        path: &Path,
+        manifest: &Manifest,
+        style: &StyleConfig,
+    ) -> Result<()> {
+        let contents = manifest.to_string_with_style(style)?;
+        match self.staged.iter_mut().find(|(staged, _)| staged == path) {
+            Some((_, staged)) => *staged = contents,
+            None => self.staged.push((path.to_owned(), contents)),
+        }
+        Ok(())
+    }
+
+    /// The paths of the staged manifests, in the order they were first staged.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.staged.iter().map(|(path, _)| path.as_path())
+    }
+
+    /// Whether nothing is staged.
+    pub fn is_empty(&self) -> bool {
+        self.staged.is_empty()
+    }
+
+    /// Write all staged manifests, or none of them if any write fails.
+    ///
+    /// Manifests whose contents did not change are not rewritten.
+    pub fn commit(self) -> Result<()> {
+        self.commit_unless(is_cancelled)
+    }
+
+    /// [`commit`](Self::commit), stopping and restoring the manifests already replaced as soon as
+    /// `cancelled` says so.
+    fn commit_unless(self, cancelled: impl Fn() -> bool) -> Result<()> {
+        let _span = span!(INFO, "commit", manifests = self.staged.len());
+        // Write everything to temporary files first, so most failures happen before any
+        // manifest is touched.
+        let mut pending: Vec<Pending<'_>> = Vec::new();
+        for (path, contents) in &self.staged {
+            match prepare(path, contents) {
+                Ok(Some(prepared)) => pending.push(prepared),
+                Ok(None) => {}
+                Err(err) => {
+                    discard(&pending);
+                    return Err(err);
+                }
+            }
+        }
+
+        for (index, prepared) in pending.iter().enumerate() {
+            if cancelled() {
+                let restored = restore(&pending[..index]);
+                discard(&pending[index..]);
+                return Err(if restored {
+                    ErrorKind::Interrupted.into()
+                } else {
+                    "Interrupted; some manifests were changed and could not be restored".into()
+                });
+            }
+            event!(DEBUG, path = %prepared.path.display(), "writing manifest");
+            if let Err(err) = fs::rename(&prepared.temp, prepared.path) {
+                // Best effort: the error about the failed write matters more.
+                restore(&pending[..index]);
+                discard(&pending[index..]);
+                return Err(Error::from(err)
+                    .chain_err(|| format!("Failed to write {}", prepared.path.display())));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Put back the original contents of manifests already replaced, returning whether all of them
+/// could be.
+fn restore(replaced: &[Pending<'_>]) -> bool {
+    let mut restored = true;
+    for replaced in replaced {
+        restored &= write_atomically(replaced.path, &replaced.original).is_ok();
+    }
+    restored
+}
+
+/// Write `contents` to a temporary file next to `path`, unless they are already its contents.
+fn prepare<'a>(path: &'a Path, contents: &str) -> Result<Option<Pending<'a>>> {
+    let original = fs::read(path).chain_err(|| format!("Failed to read {}", path.display()))?;
+    if original == contents.as_bytes() {
+        return Ok(None);
+    }
+    let temp = temp_path(path);
+    write_temp(path, &temp, contents.as_bytes())?;
+    Ok(Some(Pending {
+        path,
+        temp,
+        original,
+    }))
+}
+
+/// Write `contents` to `temp`, with the permissions of `path` if it exists.
+fn write_temp(path: &Path, temp: &Path, contents: &[u8]) -> Result<()> {
+    let result = fs::write(temp, contents).and_then(|()| match fs::metadata(path) {
+        Ok(metadata) => fs::set_permissions(temp, metadata.permissions()),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(temp);
+    }
+    result.chain_err(|| format!("Failed to write {}", path.display()))
+}
+
+/// Replace `path` with `contents` through a temporary file, or create it, so that readers only
+/// ever see complete files.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let temp = temp_path(path);
+    write_temp(path, &temp, contents)?;
+    let result = fs::rename(&temp, path);
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result.chain_err(|| format!("Failed to write {}", path.display()))
+}
+
+/// Remove the temporary files of manifests which won't be replaced.
+fn discard(pending: &[Pending<'_>]) {
+    for prepared in pending {
+        let _ = fs::remove_file(&prepared.temp);
+    }
+}
+
+/// A temporary file in the directory of `path`, so it can be renamed over it.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n";
+
+    fn manifest(version: &str) -> Manifest {
+        MANIFEST.replace("0.1.0", version).parse().unwrap()
+    }
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut files: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn commits_all_manifests() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.toml");
+        let b = dir.path().join("b.toml");
+        fs::write(&a, MANIFEST).unwrap();
+        fs::write(&b, MANIFEST).unwrap();
+
+        let mut transaction = ManifestTransaction::new();
+        let style = StyleConfig::default();
+        transaction
+            .stage_with_style(&a, &manifest("0.1.1"), &style)
+            .unwrap();
+        transaction
+            .stage_with_style(&b, &manifest("0.2.0"), &style)
+            .unwrap();
+        transaction
+            .stage_with_style(&a, &manifest("0.3.0"), &style)
+            .unwrap();
+        assert_eq!(transaction.paths().count(), 2);
+        transaction.commit().unwrap();
+
+        assert!(fs::read_to_string(&a).unwrap().contains("0.3.0"));
+        assert!(fs::read_to_string(&b).unwrap().contains("0.2.0"));
+        assert_eq!(files(dir.path()), ["a.toml", "b.toml"]);
+    }
+
+    #[test]
+    fn writes_nothing_if_a_manifest_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.toml");
+        fs::write(&a, MANIFEST).unwrap();
+
+        let mut transaction = ManifestTransaction::new();
+        let style = StyleConfig::default();
+        transaction
+            .stage_with_style(&a, &manifest("0.2.0"), &style)
+            .unwrap();
+        transaction
+            .stage_with_style(
+                &dir.path().join("missing/b.toml"),
+                &manifest("0.2.0"),
+                &style,
+            )
+            .unwrap();
+        assert!(transaction.commit().is_err());
+
+        assert_eq!(fs::read_to_string(&a).unwrap(), MANIFEST);
+        assert_eq!(files(dir.path()), ["a.toml"]);
+    }
+
+    #[test]
+    fn restores_manifests_when_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.toml");
+        let b = dir.path().join("b.toml");
+        fs::write(&a, MANIFEST).unwrap();
+        fs::write(&b, MANIFEST).unwrap();
+
+        let mut transaction = ManifestTransaction::new();
+        let style = StyleConfig::default();
+        transaction
+            .stage_with_style(&a, &manifest("0.2.0"), &style)
+            .unwrap();
+        transaction
+            .stage_with_style(&b, &manifest("0.2.0"), &style)
+            .unwrap();
+        // Cancelled after the first manifest was replaced.
+        let checks = std::cell::Cell::new(0);
+        let err = transaction
+            .commit_unless(|| {
+                checks.set(checks.get() + 1);
+                checks.get() > 1
+            })
+            .unwrap_err();
+
+        assert!(matches!(err.kind(), ErrorKind::Interrupted));
+        assert_eq!(fs::read_to_string(&a).unwrap(), MANIFEST);
+        assert_eq!(fs::read_to_string(&b).unwrap(), MANIFEST);
+        assert_eq!(files(dir.path()), ["a.toml", "b.toml"]);
+    }
+
+    #[test]
+    fn writes_new_files_and_cleans_up_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let new = dir.path().join("new");
+        write_atomically(&new, b"new").unwrap();
+        assert_eq!(fs::read(&new).unwrap(), b"new");
+
+        // A file can't be renamed over a directory which isn't empty.
+        let taken = dir.path().join("taken");
+        fs::create_dir(&taken).unwrap();
+        fs::write(taken.join("file"), "").unwrap();
+        assert!(write_atomically(&taken, b"new").is_err());
+        assert_eq!(files(dir.path()), ["new", "taken"]);
+    }
+}
@@ -0,0 +1,51 @@
+//! How much the binaries print, as set by `-q`, `-v` and `-vv`.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How much to print.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    /// Only errors, e.g. for scripts
+    Quiet,
+    /// Progress, warnings and results
+    #[default]
+    Normal,
+    /// Also the crates queried from the index, and cache hits
+    Verbose,
+    /// Also the details of HTTP requests
+    VeryVerbose,
+}
+
+impl Verbosity {
+    /// The verbosity for `-q` and the number of `-v`, as in `-vv`.
+    pub fn new(quiet: bool, verbose: u8) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::VeryVerbose,
+        }
+    }
+
+    /// Whether only errors are printed.
+    pub fn is_quiet(self) -> bool {
+        self == Verbosity::Quiet
+    }
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(1);
+
+/// Set how much is printed, for the rest of the process.
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+/// How much is printed, `Normal` unless set otherwise.
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        2 => Verbosity::Verbose,
+        3 => Verbosity::VeryVerbose,
+        _ => Verbosity::Normal,
+    }
+}
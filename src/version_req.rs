@@ -0,0 +1,560 @@
+//! Computing with version requirements: covering versions, widening and narrowing requirements,
+//! and checking whether two requirements overlap.
+//!
+//! Requirements are read as the range of versions they allow, like cargo reads them: comma
+//! separated comparators (`^`, `~`, `=`, `>`, `>=`, `<`, `<=`, wildcards and bare versions) which
+//! all have to match. Ranges are written back in the shortest of the usual forms, e.g. `1.2.3`
+//! for `>=1.2.3, <2.0.0`.
+use crate::errors::*;
+use semver::Version;
+
+/// How many parts of a version a requirement on it spells out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReqPrecision {
+    /// Only the major version, like `1`
+    Major,
+    /// The major and minor versions, like `1.2`
+    Minor,
+    /// The whole version, like `1.2.3`
+    #[default]
+    Patch,
+}
+
+/// A bare requirement on `version`, spelled out to `precision`.
+///
+/// The parts which decide compatibility are always kept, like the minor version of `0.x`
+/// releases, so the requirement matches the same compatible releases as `version`, and some
+/// earlier ones. Pre-releases are spelled out in full.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::{req_with_precision, ReqPrecision};
+///   use semver::Version;
+///
+///   let version = Version::new(1, 2, 3);
+///   assert_eq!(req_with_precision(&version, ReqPrecision::Major), "1");
+///   assert_eq!(req_with_precision(&version, ReqPrecision::Minor), "1.2");
+///   assert_eq!(req_with_precision(&Version::new(0, 4, 2), ReqPrecision::Major), "0.4");
+/// ```
+pub fn req_with_precision(version: &Version, precision: ReqPrecision) -> String {
+    if version.is_prerelease() || !version.build.is_empty() {
+        return version.to_string();
+    }
+    let precision = match (version.major, version.minor) {
+        (0, 0) => ReqPrecision::Patch,
+        (0, _) if precision == ReqPrecision::Major => ReqPrecision::Minor,
+        _ => precision,
+    };
+    match precision {
+        ReqPrecision::Major => version.major.to_string(),
+        ReqPrecision::Minor => format!("{}.{}", version.major, version.minor),
+        ReqPrecision::Patch => format!("{}.{}.{}", version.major, version.minor, version.patch),
+    }
+}
+
+/// The smallest requirement matching all of `versions`, which ends where a caret requirement on
+/// the highest one does, so that later compatible releases match too.
+///
+/// `None` if there are no versions, or if cargo would not match some of them with any single
+/// range, as happens with pre-releases of different versions.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::covering_req;
+///   use semver::Version;
+///
+///   let versions = [Version::new(1, 2, 3), Version::new(1, 4, 0)];
+///   assert_eq!(covering_req(&versions).as_deref(), Some("1.2.3"));
+///   let versions = [Version::new(1, 2, 3), Version::new(2, 0, 1)];
+///   assert_eq!(covering_req(&versions).as_deref(), Some(">=1.2.3, <3.0.0"));
+/// ```
+pub fn covering_req(versions: &[Version]) -> Option<String> {
+    let lowest = versions.iter().min()?;
+    let highest = versions.iter().max()?;
+    let range = Range {
+        lower: Bound::inclusive(lowest.clone()),
+        upper: Some(Bound::exclusive(caret_upper(highest))),
+    };
+    matching_all(range.to_string(), versions)
+}
+
+/// `req` widened as little as possible to also match `version`: the lower bound moves down to
+/// `version`, or the upper bound up to where a caret requirement on `version` ends.
+///
+/// A requirement already matching `version` is returned as it is. `None` if cargo would not match
+/// `version` with any single range extending `req`, as happens with some pre-releases.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::widen_req;
+///   use semver::Version;
+///
+///   let widened = widen_req("1.2", &Version::new(2, 0, 3)).unwrap();
+///   assert_eq!(widened.as_deref(), Some(">=1.2.0, <3.0.0"));
+///   let widened = widen_req("~1.2.3", &Version::new(1, 1, 0)).unwrap();
+///   assert_eq!(widened.as_deref(), Some(">=1.1.0, <1.3.0"));
+/// ```
+pub fn widen_req(req: &str, version: &Version) -> Result<Option<String>> {
+    if matches(req, version)? {
+        return Ok(Some(req.to_owned()));
+    }
+    let mut range = Range::parse(req)?;
+    if !range.lower.admits_above(version) {
+        range.lower = Bound::inclusive(version.clone());
+    }
+    if let Some(ref upper) = range.upper {
+        if !upper.admits_below(version) {
+            range.upper = Some(Bound::exclusive(caret_upper(version)));
+        }
+    }
+    Ok(matching_all(
+        range.to_string(),
+        std::slice::from_ref(version),
+    ))
+}
+
+/// `req` narrowed to only match `version` and later versions it matches, by raising its lower
+/// bound to `version`; `None` if `req` does not match `version`.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::narrow_req;
+///   use semver::Version;
+///
+///   let narrowed = narrow_req("1.0", &Version::new(1, 4, 2)).unwrap();
+///   assert_eq!(narrowed.as_deref(), Some("1.4.2"));
+///   let narrowed = narrow_req(">=1.0, <1.5", &Version::new(1, 4, 2)).unwrap();
+///   assert_eq!(narrowed.as_deref(), Some(">=1.4.2, <1.5.0"));
+///   assert_eq!(narrow_req("1.0", &Version::new(2, 0, 0)).unwrap(), None);
+/// ```
+pub fn narrow_req(req: &str, version: &Version) -> Result<Option<String>> {
+    if !matches(req, version)? {
+        return Ok(None);
+    }
+    let mut range = Range::parse(req)?;
+    range.lower = Bound::inclusive(version.clone());
+    Ok(matching_all(
+        range.to_string(),
+        std::slice::from_ref(version),
+    ))
+}
+
+/// Whether some version could match both `a` and `b`, so that a single version can be used
+/// where both are required.
+///
+/// Pre-releases are ordered like any other version here, so this may be `true` for requirements
+/// which only overlap in pre-releases neither matches.
+///
+/// # Examples
+///
+/// ```
+///   use cargo_edit::reqs_overlap;
+///
+///   assert!(reqs_overlap("1.2", ">=1.4, <3").unwrap());
+///   assert!(!reqs_overlap("1.2", "2").unwrap());
+///   assert!(!reqs_overlap("=1.2.3", ">1.2.3").unwrap());
+/// ```
+pub fn reqs_overlap(a: &str, b: &str) -> Result<bool> {
+    Ok(!Range::parse(a)?.intersect(Range::parse(b)?).is_empty())
+}
+
+/// Whether cargo matches `version` with `req`: it has to be in range, and a pre-release only
+/// matches if a comparator names a pre-release of the same version.
+fn matches(req: &str, version: &Version) -> Result<bool> {
+    if !Range::parse(req)?.contains(version) {
+        return Ok(false);
+    }
+    if version.pre.is_empty() {
+        return Ok(true);
+    }
+    Ok(comparators(req)
+        .filter_map(|comparator| Partial::parse(operand(comparator).1)?.version)
+        .any(|named| {
+            !named.pre.is_empty()
+                && (named.major, named.minor, named.patch)
+                    == (version.major, version.minor, version.patch)
+        }))
+}
+
+/// `req` if cargo matches all of `versions` with it.
+fn matching_all(req: String, versions: &[Version]) -> Option<String> {
+    let all = versions
+        .iter()
+        .map(|v| matches(&req, v))
+        .collect::<Result<Vec<_>>>()
+        .ok()?;
+    if all.into_iter().all(|m| m) {
+        Some(req)
+    } else {
+        None
+    }
+}
+
+fn comparators(req: &str) -> impl Iterator<Item = &str> {
+    req.split(',').map(str::trim)
+}
+
+/// The operator of `comparator`, with `^` for bare versions, and what follows it.
+fn operand(comparator: &str) -> (&'static str, &str) {
+    let ops = [">=", "<=", ">", "<", "=", "~", "^"];
+    let (op, rest) = ops
+        .iter()
+        .find_map(|op| Some((*op, comparator.strip_prefix(op)?)))
+        .unwrap_or(("^", comparator));
+    (op, rest.trim())
+}
+
+/// Where a caret requirement on `version` ends.
+fn caret_upper(version: &Version) -> Version {
+    if version.major > 0 {
+        Version::new(version.major + 1, 0, 0)
+    } else if version.minor > 0 {
+        Version::new(0, version.minor + 1, 0)
+    } else {
+        Version::new(0, 0, version.patch + 1)
+    }
+}
+
+/// One end of a range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Bound {
+    version: Version,
+    inclusive: bool,
+}
+
+impl Bound {
+    fn inclusive(version: Version) -> Self {
+        Bound {
+            version,
+            inclusive: true,
+        }
+    }
+
+    fn exclusive(version: Version) -> Self {
+        Bound {
+            version,
+            inclusive: false,
+        }
+    }
+
+    /// Whether `version` is above this lower bound.
+    fn admits_above(&self, version: &Version) -> bool {
+        *version > self.version || (self.inclusive && *version == self.version)
+    }
+
+    /// Whether `version` is below this upper bound.
+    fn admits_below(&self, version: &Version) -> bool {
+        *version < self.version || (self.inclusive && *version == self.version)
+    }
+}
+
+/// The versions a requirement allows; without an upper bound, any higher version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Range {
+    lower: Bound,
+    upper: Option<Bound>,
+}
+
+impl Range {
+    fn any() -> Self {
+        Range {
+            lower: Bound::inclusive(Version::new(0, 0, 0)),
+            upper: None,
+        }
+    }
+
+    fn parse(req: &str) -> Result<Self> {
+        let invalid = |reason: &str| -> Error {
+            ErrorKind::InvalidVersionReq(req.to_owned(), reason.to_owned()).into()
+        };
+        if req.contains("||") {
+            return Err(invalid("alternatives with `||` are not supported"));
+        }
+        let mut range = Range::any();
+        for comparator in comparators(req) {
+            if comparator.is_empty() {
+                return Err(invalid("empty comparator"));
+            }
+            let comparator = Range::parse_comparator(comparator)
+                .ok_or_else(|| invalid(&format!("`{}` is not a valid comparator", comparator)))?;
+            range = range.intersect(comparator);
+        }
+        Ok(range)
+    }
+
+    fn parse_comparator(comparator: &str) -> Option<Self> {
+        let (op, rest) = operand(comparator);
+        let partial = Partial::parse(rest)?;
+        let major = match partial.major {
+            Some(major) => major,
+            None if op == "^" || op == "=" => return Some(Range::any()),
+            None => return None,
+        };
+        let lower = partial.lowest();
+        let next = partial.next();
+
+        let range = |lower: Bound, upper: Option<Bound>| Some(Range { lower, upper });
+        let exact = partial.full();
+        let zero = || Bound::inclusive(Version::new(0, 0, 0));
+        match op {
+            "=" => match exact {
+                Some(v) => range(Bound::inclusive(v.clone()), Some(Bound::inclusive(v))),
+                None => range(Bound::inclusive(lower), Some(Bound::exclusive(next))),
+            },
+            ">" => match exact {
+                Some(v) => range(Bound::exclusive(v), None),
+                None => range(Bound::inclusive(next), None),
+            },
+            ">=" => range(Bound::inclusive(lower), None),
+            "<" => range(zero(), Some(Bound::exclusive(lower))),
+            "<=" => match exact {
+                Some(v) => range(zero(), Some(Bound::inclusive(v))),
+                None => range(zero(), Some(Bound::exclusive(next))),
+            },
+            "~" => {
+                let upper = match partial.minor {
+                    Some(minor) => Version::new(major, minor + 1, 0),
+                    None => Version::new(major + 1, 0, 0),
+                };
+                range(Bound::inclusive(lower), Some(Bound::exclusive(upper)))
+            }
+            _ => {
+                let upper = match (major, partial.minor, partial.patch) {
+                    (0, Some(0), Some(patch)) => Version::new(0, 0, patch + 1),
+                    (0, Some(minor), _) => Version::new(0, minor + 1, 0),
+                    (major, _, _) => Version::new(major + 1, 0, 0),
+                };
+                range(Bound::inclusive(lower), Some(Bound::exclusive(upper)))
+            }
+        }
+    }
+
+    fn intersect(self, other: Range) -> Range {
+        // At the same version, an exclusive bound is the tighter one.
+        let lower = if (&other.lower.version, !other.lower.inclusive)
+            > (&self.lower.version, !self.lower.inclusive)
+        {
+            other.lower
+        } else {
+            self.lower
+        };
+        let upper = match (self.upper, other.upper) {
+            (Some(a), Some(b)) => Some(if (&b.version, b.inclusive) < (&a.version, a.inclusive) {
+                b
+            } else {
+                a
+            }),
+            (a, b) => a.or(b),
+        };
+        Range { lower, upper }
+    }
+
+    fn contains(&self, version: &Version) -> bool {
+        self.lower.admits_above(version)
+            && self.upper.as_ref().is_none_or(|u| u.admits_below(version))
+    }
+
+    fn is_empty(&self) -> bool {
+        match self.upper {
+            Some(ref upper) => {
+                upper.version < self.lower.version
+                    || (upper.version == self.lower.version
+                        && !(upper.inclusive && self.lower.inclusive))
+            }
+            None => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Range {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lower = &self.lower;
+        let unbounded_below = lower.inclusive && lower.version == Version::new(0, 0, 0);
+        let upper = match self.upper {
+            Some(ref upper) => upper,
+            None if unbounded_below => return write!(f, "*"),
+            None if lower.inclusive => return write!(f, ">={}", lower.version),
+            None => return write!(f, ">{}", lower.version),
+        };
+        let upper_op = if upper.inclusive { "<=" } else { "<" };
+        if lower.inclusive && upper.inclusive && lower.version == upper.version {
+            write!(f, "={}", lower.version)
+        } else if lower.inclusive
+            && !upper.inclusive
+            && upper.version == caret_upper(&lower.version)
+        {
+            write!(f, "{}", lower.version)
+        } else if unbounded_below {
+            write!(f, "{}{}", upper_op, upper.version)
+        } else {
+            let lower_op = if lower.inclusive { ">=" } else { ">" };
+            write!(
+                f,
+                "{}{}, {}{}",
+                lower_op, lower.version, upper_op, upper.version
+            )
+        }
+    }
+}
+
+/// A version in a comparator, where trailing parts may be missing or wildcards.
+struct Partial {
+    major: Option<u64>,
+    minor: Option<u64>,
+    patch: Option<u64>,
+    /// The whole version, if all parts are given; it may be a pre-release.
+    version: Option<Version>,
+}
+
+impl Partial {
+    fn parse(input: &str) -> Option<Self> {
+        if let Ok(version) = Version::parse(input) {
+            return Some(Partial {
+                major: Some(version.major),
+                minor: Some(version.minor),
+                patch: Some(version.patch),
+                version: Some(version),
+            });
+        }
+        let mut parts = input.split('.');
+        let mut next = || -> Option<Option<u64>> {
+            match parts.next() {
+                None | Some("*") | Some("x") | Some("X") => Some(None),
+                Some(part) => part.parse().ok().map(Some),
+            }
+        };
+        let (major, minor, patch) = (next()?, next()?, next()?);
+        // A wildcard can't be followed by a number, and there are no more than three parts.
+        if parts.next().is_some()
+            || (major.is_none() && minor.is_some())
+            || (minor.is_none() && patch.is_some())
+        {
+            return None;
+        }
+        Some(Partial {
+            major,
+            minor,
+            patch,
+            version: None,
+        })
+    }
+
+    /// The version if all parts are given.
+    fn full(&self) -> Option<Version> {
+        self.version
+            .clone()
+            .or_else(|| Some(Version::new(self.major?, self.minor?, self.patch?)))
+    }
+
+    /// The lowest version matching the given parts.
+    fn lowest(&self) -> Version {
+        self.full().unwrap_or_else(|| {
+            Version::new(
+                self.major.unwrap_or(0),
+                self.minor.unwrap_or(0),
+                self.patch.unwrap_or(0),
+            )
+        })
+    }
+
+    /// The lowest version past those matching the given parts, when some are missing.
+    fn next(&self) -> Version {
+        let major = self.major.unwrap_or(0);
+        match self.minor {
+            None => Version::new(major + 1, 0, 0),
+            Some(minor) => Version::new(major, minor + 1, 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    fn range(req: &str) -> String {
+        Range::parse(req).unwrap().to_string()
+    }
+
+    #[test]
+    fn reads_requirements_as_ranges() {
+        assert_eq!(range("1.2.3"), "1.2.3");
+        assert_eq!(range("^0.2"), "0.2.0");
+        assert_eq!(range("0.0.3"), "0.0.3");
+        assert_eq!(range("~1.2"), ">=1.2.0, <1.3.0");
+        assert_eq!(range("=1.2.3"), "=1.2.3");
+        assert_eq!(range("1.*"), "1.0.0");
+        assert_eq!(range("*"), "*");
+        assert_eq!(range(">1.2"), ">=1.3.0");
+        assert_eq!(range("<=1.2"), "<1.3.0");
+        assert_eq!(range(">=1.2, <1.5, <1.4.2"), ">=1.2.0, <1.4.2");
+        assert!(Range::parse("1.2 || 2").is_err());
+        assert!(Range::parse("1.x.3").is_err());
+    }
+
+    #[test]
+    fn covers_versions() {
+        assert_eq!(covering_req(&[]), None);
+        assert_eq!(covering_req(&[v("0.2.1"), v("0.2.0")]).unwrap(), "0.2.0");
+        assert_eq!(
+            covering_req(&[v("0.2.1"), v("0.3.0")]).unwrap(),
+            ">=0.2.1, <0.4.0"
+        );
+        assert_eq!(covering_req(&[v("1.0.0-rc.1")]).unwrap(), "1.0.0-rc.1");
+        // Cargo only matches pre-releases of the version a comparator names.
+        assert_eq!(covering_req(&[v("1.0.0"), v("1.1.0-rc.1")]), None);
+    }
+
+    #[test]
+    fn widens_and_narrows() {
+        assert_eq!(widen_req("1.2", &v("1.3.0")).unwrap().unwrap(), "1.2");
+        assert_eq!(widen_req("1.2.3", &v("1.1.0")).unwrap().unwrap(), "1.1.0");
+        assert_eq!(widen_req("=1.2.3", &v("1.2.5")).unwrap().unwrap(), "1.2.3");
+        assert_eq!(
+            widen_req("<=1.2.3", &v("1.3.0")).unwrap().unwrap(),
+            "<2.0.0"
+        );
+        assert_eq!(widen_req("1.2", &v("2.0.0-rc.1")).unwrap(), None);
+        assert_eq!(widen_req(">=1.2", &v("0.9.0")).unwrap().unwrap(), ">=0.9.0");
+        assert!(widen_req("latest", &v("1.0.0")).is_err());
+
+        assert_eq!(
+            narrow_req("~1.2", &v("1.2.7")).unwrap().unwrap(),
+            ">=1.2.7, <1.3.0"
+        );
+        assert_eq!(narrow_req("*", &v("1.2.7")).unwrap().unwrap(), ">=1.2.7");
+    }
+
+    #[test]
+    fn spells_out_to_precision() {
+        assert_eq!(
+            req_with_precision(&v("1.2.3"), ReqPrecision::Patch),
+            "1.2.3"
+        );
+        assert_eq!(req_with_precision(&v("0.4.2"), ReqPrecision::Minor), "0.4");
+        assert_eq!(
+            req_with_precision(&v("0.0.3"), ReqPrecision::Major),
+            "0.0.3"
+        );
+        assert_eq!(
+            req_with_precision(&v("2.0.0-rc.1"), ReqPrecision::Major),
+            "2.0.0-rc.1"
+        );
+    }
+
+    #[test]
+    fn overlapping_requirements() {
+        assert!(reqs_overlap("0.2", "0.2.5").unwrap());
+        assert!(!reqs_overlap("0.2", "0.3").unwrap());
+        assert!(reqs_overlap("<=1.2.3", ">=1.2.3").unwrap());
+        assert!(!reqs_overlap("<1.2.3", ">=1.2.3").unwrap());
+    }
+}
@@ -0,0 +1,1277 @@
+//! Workspace discovery, following the rules cargo uses to find the root and the members.
+use crate::dependency_table::DependencyEntry;
+use crate::duplicates::fix_duplicates;
+use crate::errors::{location, *};
+use crate::inheritance::{DependencyField, ResolvedDependency};
+use crate::logical_path::{self, identity, same_file};
+use crate::manifest::Manifest;
+use crate::parallel;
+use crate::style::StyleConfig;
+use crate::toml_key;
+use crate::transaction::ManifestTransaction;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Component, Path, PathBuf};
+
+/// A package belonging to a workspace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceMember {
+    /// The name of the package
+    pub name: String,
+    /// The version of the package, if it declares one, or that of `[workspace.package]` if it
+    /// inherits it with `version = { workspace = true }`
+    pub version: Option<String>,
+    /// Whether the package inherits its version from `[workspace.package]`
+    pub inherits_version: bool,
+    /// The path to the package's `Cargo.toml`
+    pub manifest_path: PathBuf,
+}
+
+/// A manifest which could not be parsed, and was skipped by [`Workspace::load_partial`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenManifest {
+    /// The path to the manifest
+    pub path: PathBuf,
+    /// The 1-based line of the mistake, if known
+    pub line: Option<usize>,
+    /// The 1-based column of the mistake, if known
+    pub column: Option<usize>,
+    /// What is wrong, with a snippet of the manifest pointing at the mistake
+    pub message: String,
+}
+
+impl BrokenManifest {
+    /// The broken manifest an error is about, if it is a parse error naming its manifest.
+    pub fn from_error(err: &Error) -> Option<Self> {
+        err.find_cause(|err| match err.kind() {
+            ErrorKind::ManifestParseAt(path, line, column) => Some(BrokenManifest {
+                path: path.clone(),
+                line: *line,
+                column: *column,
+                message: std::error::Error::source(err)
+                    .map(|cause| cause.to_string().trim().to_owned())
+                    .unwrap_or_default(),
+            }),
+            _ => None,
+        })
+    }
+}
+
+impl std::fmt::Display for BrokenManifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            self.path.display(),
+            location(self.line, self.column)
+        )?;
+        // The location is already given above.
+        let snippet = self
+            .message
+            .lines()
+            .skip_while(|line| line.starts_with("TOML parse error at"));
+        for line in snippet {
+            write!(f, "\n    {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// A cargo workspace, or a single package outside of any workspace.
+///
+/// Members are the packages listed in `workspace.members` (with glob patterns expanded), the root
+/// package if there is one, and every path dependency of a member inside the workspace directory.
+/// Anything listed in `workspace.exclude` is left out, unless also listed in `workspace.members`,
+/// and so are packages belonging to a nested workspace of their own.
+///
+/// The manifests of the root and of the members are parsed once, concurrently, when loading the
+/// workspace, and are kept for [`Workspace::manifest`] and the queries below.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    root_manifest: PathBuf,
+    is_virtual: bool,
+    members: Vec<WorkspaceMember>,
+    manifests: BTreeMap<PathBuf, Manifest>,
+    /// The manifests changed through the `set_dependency_*` methods.
+    changed: BTreeSet<PathBuf>,
+    /// The member manifests skipped by [`Workspace::load_partial`].
+    broken: Vec<BrokenManifest>,
+}
+
+impl Workspace {
+    /// Load the workspace containing the package at `manifest_path`, which may be the path of any
+    /// member's `Cargo.toml` or of the root manifest.
+    ///
+    /// Like cargo, this fails with `NotInWorkspace` for a package under a workspace root which
+    /// neither lists nor excludes it.
+    pub fn load(manifest_path: &Path) -> Result<Self> {
+        Workspace::load_with(manifest_path, None)
+    }
+
+    /// Load the workspace like [`Workspace::load`], but skip the members whose manifests can't be
+    /// parsed, and list them in [`Workspace::broken_manifests`].
+    ///
+    /// This still fails if the root manifest can't be parsed, as it lists the members.
+    pub fn load_partial(manifest_path: &Path) -> Result<Self> {
+        Workspace::load_with(manifest_path, Some(Vec::new()))
+    }
+
+    /// Load the workspace, recording broken manifests into `broken` if given, failing otherwise.
+    fn load_with(manifest_path: &Path, mut broken: Option<Vec<BrokenManifest>>) -> Result<Self> {
+        let _span = span!(DEBUG, "load_workspace", path = ?manifest_path);
+        let manifest_path = absolute(manifest_path)?;
+        let root_manifest = match find_root(&manifest_path) {
+            // Starting from a broken member, its workspace is still found among its ancestors.
+            Err(err)
+                if broken.is_some()
+                    && BrokenManifest::from_error(&err).map(|b| b.path)
+                        == Some(manifest_path.clone()) =>
+            {
+                find_root_above(&manifest_path)?
+            }
+            root => root?,
+        };
+        let root = parse_manifest(&root_manifest)?;
+        let workspace = root.data["workspace"].as_table();
+        let is_virtual = root.data["package"].is_none();
+
+        let member_paths = workspace
+            .map(|workspace| string_list(&workspace["members"]))
+            .unwrap_or_default();
+        let excluded = workspace
+            .map(|workspace| string_list(&workspace["exclude"]))
+            .unwrap_or_default();
+        let in_workspace = workspace.is_some();
+        // Packages with a workspace of their own, like the root of a nested workspace and its
+        // members, are not ours even when they live in our directory.
+        let ours =
+            |path: &Path| -> Result<bool> { Ok(same_file(&find_root(path)?, &root_manifest)) };
+
+        let mut candidates = Vec::new();
+        if !is_virtual {
+            candidates.push(root_manifest.clone());
+        }
+        for pattern in &member_paths {
+            for dir in expand_glob(parent(&root_manifest), pattern)? {
+                let path = dir.join("Cargo.toml");
+                if path.is_file() && skip_broken(&mut broken, ours(&path))? == Some(true) {
+                    candidates.push(path);
+                }
+            }
+        }
+
+        // Manifests are independent files, so each wave of candidates is read and parsed at once;
+        // the path dependencies found in one wave make up the next.
+        let mut manifests = BTreeMap::new();
+        let mut members = BTreeMap::new();
+        // A member reached through symlinks under several paths counts once, under the first.
+        let mut seen = BTreeSet::new();
+        while !candidates.is_empty() {
+            let wave: Vec<PathBuf> = candidates
+                .drain(..)
+                .map(|path| normalize(&path))
+                .filter(|path| seen.insert(identity(path)))
+                .collect();
+            let unparsed: Vec<PathBuf> = wave
+                .iter()
+                .filter(|path| **path != root_manifest)
+                .cloned()
+                .collect();
+            let parsed = parallel::try_map(&unparsed, |path| Ok(parse_manifest(path)))?;
+            for (path, manifest) in unparsed.into_iter().zip(parsed) {
+                if let Some(manifest) = skip_broken(&mut broken, manifest)? {
+                    manifests.insert(path, manifest);
+                }
+            }
+
+            for path in wave {
+                let manifest = if path == root_manifest {
+                    &root
+                } else {
+                    match manifests.get(&path) {
+                        Some(manifest) => manifest,
+                        None => continue,
+                    }
+                };
+                let package = match manifest.data["package"].as_table() {
+                    Some(package) => package,
+                    None => continue,
+                };
+                // Path dependencies are only members if they live in the workspace.
+                for entry in manifest.dependency_entries().filter(|e| !e.table.workspace) {
+                    let dep_path = match entry.path() {
+                        Some(dep_path) => dep_path,
+                        None => continue,
+                    };
+                    let dep_manifest = normalize(&parent(&path).join(dep_path).join("Cargo.toml"));
+                    if in_workspace
+                        && dep_manifest.starts_with(parent(&root_manifest))
+                        && dep_manifest.is_file()
+                        && skip_broken(&mut broken, ours(&dep_manifest))? == Some(true)
+                    {
+                        candidates.push(dep_manifest);
+                    }
+                }
+                let member = WorkspaceMember {
+                    name: package["name"]
+                        .as_str()
+                        .ok_or(ErrorKind::ParseCargoToml)?
+                        .to_owned(),
+                    version: package_version(package, &root),
+                    inherits_version: inherits_version(package),
+                    manifest_path: path.clone(),
+                };
+                members.insert(path, member);
+            }
+        }
+
+        let root_dir = parent(&root_manifest).to_owned();
+        let mut members: Vec<_> = members
+            .into_iter()
+            .filter(|(path, _)| {
+                *path == root_manifest
+                    || !is_excluded(&root_dir, &member_paths, &excluded, parent(path))
+            })
+            .map(|(_, member)| member)
+            .collect();
+        members.sort_by(|a, b| {
+            a.name
+                .cmp(&b.name)
+                .then(a.manifest_path.cmp(&b.manifest_path))
+        });
+
+        let broken = broken.unwrap_or_default();
+        // Like cargo, refuse a package which the root it found neither lists nor excludes; a
+        // broken one can't be told apart.
+        if !same_file(&manifest_path, &root_manifest)
+            && !broken.iter().any(|b| same_file(&b.path, &manifest_path))
+            && !members
+                .iter()
+                .any(|m| same_file(&m.manifest_path, &manifest_path))
+        {
+            return Err(ErrorKind::NotInWorkspace(manifest_path, root_manifest).into());
+        }
+
+        // Only keep the documents later phases can ask for.
+        manifests.retain(|path, _| members.iter().any(|m| m.manifest_path == *path));
+        manifests.insert(root_manifest.clone(), root);
+
+        Ok(Workspace {
+            root_manifest,
+            is_virtual,
+            members,
+            manifests,
+            changed: BTreeSet::new(),
+            broken,
+        })
+    }
+
+    /// The member manifests which could not be parsed, and were skipped by
+    /// [`Workspace::load_partial`].
+    pub fn broken_manifests(&self) -> &[BrokenManifest] {
+        &self.broken
+    }
+
+    /// The path to the root `Cargo.toml`.
+    pub fn root_manifest(&self) -> &Path {
+        &self.root_manifest
+    }
+
+    /// The directory of the root `Cargo.toml`, which also holds the lock file.
+    pub fn root_dir(&self) -> &Path {
+        parent(&self.root_manifest)
+    }
+
+    /// Whether the root manifest has no `[package]` of its own.
+    pub fn is_virtual(&self) -> bool {
+        self.is_virtual
+    }
+
+    /// The members of the workspace, sorted by name.
+    pub fn members(&self) -> &[WorkspaceMember] {
+        &self.members
+    }
+
+    /// The member called `name`, if any.
+    pub fn member(&self, name: &str) -> Option<&WorkspaceMember> {
+        self.members.iter().find(|member| member.name == name)
+    }
+
+    /// The paths of all member manifests.
+    pub fn member_manifests(&self) -> Vec<PathBuf> {
+        self.members
+            .iter()
+            .map(|member| member.manifest_path.clone())
+            .collect()
+    }
+
+    /// The parsed manifest at `path`, if it is the root manifest or a member's manifest.
+    ///
+    /// This is the document as read by [`Workspace::load`]; it is not read again.
+    pub fn manifest(&self, path: &Path) -> Option<&Manifest> {
+        self.manifests.get(&self.manifest_key(path)?)
+    }
+
+    /// The path `path` is kept under in `manifests`, which may be another path to the same
+    /// manifest through symlinks.
+    fn manifest_key(&self, path: &Path) -> Option<PathBuf> {
+        let path = normalize(path);
+        if self.manifests.contains_key(&path) {
+            return Some(path);
+        }
+        self.manifests
+            .keys()
+            .find(|key| same_file(key, &path))
+            .cloned()
+    }
+
+    /// Every member along with its parsed manifest.
+    pub fn member_documents(&self) -> impl Iterator<Item = (&WorkspaceMember, &Manifest)> {
+        self.members
+            .iter()
+            .map(move |member| (member, &self.manifests[&member.manifest_path]))
+    }
+
+    /// Every dependency entry of the root manifest, including `[workspace.dependencies]`, and of
+    /// the members, each with the manifest it is listed in.
+    pub fn dependency_entries(&self) -> Result<Vec<DependencyEntry>> {
+        let mut paths = vec![self.root_manifest.as_path()];
+        for member in &self.members {
+            if !paths.contains(&member.manifest_path.as_path()) {
+                paths.push(&member.manifest_path);
+            }
+        }
+
+        let mut entries = Vec::new();
+        for path in paths {
+            entries.extend(self.manifests[path].dependency_entries().map(|mut entry| {
+                entry.manifest_path = Some(path.to_owned());
+                entry
+            }));
+        }
+        Ok(entries)
+    }
+
+    /// The dependency entries of the root manifest or of a member's manifest, each along with
+    /// the `[workspace.dependencies]` entry it inherits from.
+    pub fn resolved_dependencies(&self, manifest_path: &Path) -> Result<Vec<ResolvedDependency>> {
+        let manifest_path = self.manifest_key(manifest_path).chain_err(|| {
+            format!(
+                "{} is not a manifest of the workspace",
+                manifest_path.display()
+            )
+        })?;
+        let manifest = &self.manifests[&manifest_path];
+        let workspace_entries: Vec<_> = self.manifests[&self.root_manifest]
+            .dependency_entries()
+            .filter(|e| e.table.workspace)
+            .map(|mut entry| {
+                entry.manifest_path = Some(self.root_manifest.clone());
+                entry
+            })
+            .collect();
+        manifest
+            .dependency_entries()
+            .map(|mut entry| {
+                entry.manifest_path = Some(manifest_path.clone());
+                ResolvedDependency::resolve(entry, &workspace_entries)
+            })
+            .collect()
+    }
+
+    /// Set the version requirement of `dep`, in `[workspace.dependencies]` if it is inherited.
+    ///
+    /// Like all `set_dependency_*` methods, this changes the manifest kept by the workspace, which
+    /// [`Workspace::stage_changes`] stages for writing, and returns its path.
+    pub fn set_dependency_version(
+        &mut self,
+        dep: &ResolvedDependency,
+        req: &str,
+    ) -> Result<PathBuf> {
+        let entry = dep.entry_for(DependencyField::Version);
+        let path = self.change(entry)?;
+        self.manifests
+            .get_mut(&path)
+            .expect("changed manifests are loaded")
+            .set_table_entry_version(&entry.table.path(), &entry.key, req)?;
+        Ok(path)
+    }
+
+    /// Add `features` to those of `dep`, in the member's own entry if it is inherited. Features
+    /// already enabled, also through `[workspace.dependencies]`, are skipped.
+    pub fn add_dependency_features(
+        &mut self,
+        dep: &ResolvedDependency,
+        features: &[String],
+    ) -> Result<PathBuf> {
+        let enabled = dep
+            .effective()
+            .and_then(|effective| effective.features)
+            .unwrap_or_default();
+        let entry = dep.entry_for(DependencyField::Features);
+        let path = self.change(entry)?;
+        let item = self.entry_table_mut(&path, entry)?;
+        if item["features"].is_none() {
+            item["features"] = toml_edit::value(toml_edit::Array::default());
+        }
+        let list = item["features"]
+            .as_value_mut()
+            .and_then(toml_edit::Value::as_array_mut)
+            .chain_err(|| format!("`features` of `{}` is not an array", entry.key))?;
+        for feature in features.iter().filter(|f| !enabled.contains(f)) {
+            // Only fails for non-string arrays, which cargo rejects anyway.
+            let _ = list.push(feature.as_str());
+        }
+        format_entry(item);
+        Ok(path)
+    }
+
+    /// Make `dep` optional or not, in the member's own entry if it is inherited.
+    pub fn set_dependency_optional(
+        &mut self,
+        dep: &ResolvedDependency,
+        optional: bool,
+    ) -> Result<PathBuf> {
+        let entry = dep.entry_for(DependencyField::Optional);
+        let path = self.change(entry)?;
+        let item = self.entry_table_mut(&path, entry)?;
+        item["optional"] = if optional {
+            toml_edit::value(true)
+        } else {
+            toml_edit::Item::None
+        };
+        format_entry(item);
+        Ok(path)
+    }
+
+    /// Remove the dependency listed as `entry`, one of [`Workspace::dependency_entries`], along
+    /// with the values of `[features]` which only made sense with it.
+    ///
+    /// Like the `set_dependency_*` methods, this changes the manifest kept by the workspace and
+    /// returns its path.
+    pub fn remove_dependency(&mut self, entry: &DependencyEntry) -> Result<PathBuf> {
+        let path = self.change(entry)?;
+        let manifest = self
+            .manifests
+            .get_mut(&path)
+            .expect("changed manifests are loaded");
+        manifest.remove_from_table(&entry.table.path(), &entry.key)?;
+        if !entry.table.workspace {
+            manifest.remove_feature_references(&entry.key);
+        }
+        Ok(path)
+    }
+
+    /// Stage every manifest changed through the `set_dependency_*` methods, formatted as
+    /// configured for it.
+    pub fn stage_changes(&self, transaction: &mut ManifestTransaction) -> Result<()> {
+        for path in &self.changed {
+            transaction.stage_with_style(
+                path,
+                &self.manifests[path],
+                &StyleConfig::for_manifest(path)?,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Record that the manifest listing `entry` is about to change, and return its path.
+    fn change(&mut self, entry: &DependencyEntry) -> Result<PathBuf> {
+        let path = entry
+            .manifest_path
+            .as_deref()
+            .and_then(|path| self.manifest_key(path))
+            .chain_err(|| {
+                format!(
+                    "`{}` is not listed in a manifest of the workspace",
+                    entry.key
+                )
+            })?;
+        self.changed.insert(path.clone());
+        Ok(path)
+    }
+
+    /// `entry` in the manifest at `path`, turned into a table if it is only a version
+    /// requirement.
+    fn entry_table_mut(
+        &mut self,
+        path: &Path,
+        entry: &DependencyEntry,
+    ) -> Result<&mut toml_edit::Item> {
+        let manifest = self
+            .manifests
+            .get_mut(path)
+            .expect("changed manifests are loaded");
+        let table_path = entry.table.path();
+        let item = toml_key::entry(manifest.get_table(&table_path)?, &entry.key);
+        if let Some(version) = item.as_str().map(String::from) {
+            let mut table = toml_edit::InlineTable::default();
+            table.get_or_insert("version", version);
+            *item = toml_edit::value(toml_edit::Value::InlineTable(table));
+        }
+        if !item.is_table_like() {
+            return Err(
+                ErrorKind::NonExistentDependency(entry.key.clone(), table_path.join(".")).into(),
+            );
+        }
+        Ok(item)
+    }
+
+    /// The dependencies between members: each member's name is mapped to the names of the members
+    /// it depends on, in any dependency table.
+    ///
+    /// A dependency counts if it points to a member by path, or if it names a member and has no
+    /// other source, as with `foo = { workspace = true }`.
+    pub fn dependency_graph(&self) -> Result<BTreeMap<String, BTreeSet<String>>> {
+        let by_path: BTreeMap<PathBuf, &str> = self
+            .members
+            .iter()
+            .map(|member| {
+                (
+                    identity(parent(&member.manifest_path)),
+                    member.name.as_str(),
+                )
+            })
+            .collect();
+        let mut graph = BTreeMap::new();
+        for member in &self.members {
+            let manifest = &self.manifests[&member.manifest_path];
+            let mut edges = BTreeSet::new();
+            // `[workspace.dependencies]` only counts where members inherit from it.
+            for entry in manifest.dependency_entries().filter(|e| !e.table.workspace) {
+                let target = match entry.path() {
+                    Some(path) => by_path
+                        .get(&identity(&normalize(
+                            &parent(&member.manifest_path).join(path),
+                        )))
+                        .copied(),
+                    None if entry.is_inherited() => {
+                        self.member(entry.name()).map(|m| m.name.as_str())
+                    }
+                    None => None,
+                };
+                if let Some(target) = target.filter(|target| *target != member.name) {
+                    edges.insert(target.to_owned());
+                }
+            }
+            graph.insert(member.name.clone(), edges);
+        }
+        Ok(graph)
+    }
+
+    /// The members depending on `name`, directly or transitively.
+    pub fn dependents_of(&self, name: &str) -> Result<BTreeSet<String>> {
+        let graph = self.dependency_graph()?;
+        let mut dependents = BTreeSet::new();
+        let mut queue = vec![name.to_owned()];
+        while let Some(current) = queue.pop() {
+            for (member, deps) in &graph {
+                if deps.contains(&current) && dependents.insert(member.clone()) {
+                    queue.push(member.clone());
+                }
+            }
+        }
+        Ok(dependents)
+    }
+}
+
+/// The value of `result`, or `None` after recording the broken manifest it failed on into
+/// `broken`, if given; other errors are returned.
+fn skip_broken<T>(
+    broken: &mut Option<Vec<BrokenManifest>>,
+    result: Result<T>,
+) -> Result<Option<T>> {
+    match (result, broken) {
+        (Ok(value), _) => Ok(Some(value)),
+        (Err(err), Some(broken)) => match BrokenManifest::from_error(&err) {
+            Some(manifest) => {
+                if !broken.iter().any(|b| b.path == manifest.path) {
+                    broken.push(manifest);
+                }
+                Ok(None)
+            }
+            None => Err(err),
+        },
+        (Err(err), None) => Err(err),
+    }
+}
+
+fn format_entry(item: &mut toml_edit::Item) {
+    if let Some(list) = item["features"]
+        .as_value_mut()
+        .and_then(toml_edit::Value::as_array_mut)
+    {
+        list.fmt();
+    }
+    if let Some(table) = item.as_inline_table_mut() {
+        table.fmt();
+    }
+}
+
+/// Fail with `VirtualManifest`, which lists the members of the workspace to pick from, if the
+/// manifest at `manifest_path` has no `[package]` of its own.
+pub fn ensure_package(manifest_path: &Path) -> Result<()> {
+    let manifest = Manifest::open(&Some(manifest_path.to_owned()))?;
+    if !manifest.data["package"].is_none() || manifest.data["workspace"].is_none() {
+        return Ok(());
+    }
+    let workspace = Workspace::load(manifest_path)?;
+    let members = workspace
+        .members()
+        .iter()
+        .map(|member| member.name.clone())
+        .collect();
+    Err(ErrorKind::VirtualManifest(members).into())
+}
+
+/// Find the root manifest of the workspace containing the package at `manifest_path`.
+///
+/// Like cargo, this honours `package.workspace`, and otherwise picks the closest ancestor with a
+/// `[workspace]` table which does not exclude the package, or which is a member pointing to its
+/// root with `package.workspace`. A package outside of any workspace is its own root.
+pub fn find_workspace_root(manifest_path: &Path) -> Result<PathBuf> {
+    find_root(&absolute(manifest_path)?)
+}
+
+fn find_root(manifest_path: &Path) -> Result<PathBuf> {
+    let data = read_manifest(manifest_path)?;
+    if data.get("workspace").is_some() {
+        return Ok(manifest_path.to_owned());
+    }
+    if let Some(root) = data
+        .get("package")
+        .and_then(|package| package.get("workspace"))
+        .and_then(toml::Value::as_str)
+    {
+        return Ok(normalize(
+            &parent(manifest_path).join(root).join("Cargo.toml"),
+        ));
+    }
+    find_root_above(manifest_path)
+}
+
+/// Find the root manifest of the workspace containing the package at `manifest_path` among its
+/// ancestors, without reading the package's own manifest.
+fn find_root_above(manifest_path: &Path) -> Result<PathBuf> {
+    let package_dir = parent(manifest_path);
+    for dir in package_dir.ancestors().skip(1) {
+        let candidate = dir.join("Cargo.toml");
+        if !candidate.is_file() {
+            continue;
+        }
+        let ancestor = read_manifest(&candidate)?;
+        match ancestor.get("workspace").and_then(toml::Value::as_table) {
+            // An excluded package may still belong to a workspace further up.
+            Some(workspace) => {
+                let members = toml_string_list(workspace.get("members"));
+                let exclude = toml_string_list(workspace.get("exclude"));
+                if !is_excluded(dir, &members, &exclude, package_dir) {
+                    return Ok(candidate);
+                }
+            }
+            // A member of another workspace points to its root, which is then ours too.
+            None => {
+                if let Some(root) = ancestor
+                    .get("package")
+                    .and_then(|package| package.get("workspace"))
+                    .and_then(toml::Value::as_str)
+                {
+                    return Ok(normalize(&dir.join(root).join("Cargo.toml")));
+                }
+            }
+        }
+    }
+    Ok(manifest_path.to_owned())
+}
+
+/// Whether the workspace in `root_dir` excludes the package in `package_dir`, as cargo decides:
+/// it is under one of the `exclude` paths, and not under one of the `members` paths.
+fn is_excluded(
+    root_dir: &Path,
+    members: &[String],
+    exclude: &[String],
+    package_dir: &Path,
+) -> bool {
+    let under = |paths: &[String]| {
+        paths
+            .iter()
+            .any(|path| package_dir.starts_with(normalize(&root_dir.join(path))))
+    };
+    under(exclude) && !under(members)
+}
+
+/// Whether `package` takes its version from `[workspace.package]`.
+fn inherits_version(package: &toml_edit::Table) -> bool {
+    inherits_field(package, "version")
+}
+
+/// Whether `package` takes `field` from `[workspace.package]`.
+fn inherits_field(package: &toml_edit::Table, field: &str) -> bool {
+    package[field]["workspace"].as_bool() == Some(true)
+}
+
+/// The version of `package`, taken from `[workspace.package]` of the `root` manifest if inherited.
+pub(crate) fn package_version(package: &toml_edit::Table, root: &Manifest) -> Option<String> {
+    package_field(package, root, "version")
+}
+
+/// The string `field` of `package`, taken from `[workspace.package]` of the `root` manifest if
+/// inherited.
+pub(crate) fn package_field(
+    package: &toml_edit::Table,
+    root: &Manifest,
+    field: &str,
+) -> Option<String> {
+    let value = if inherits_field(package, field) {
+        &root.data["workspace"]["package"][field]
+    } else {
+        &package[field]
+    };
+    value.as_str().map(String::from)
+}
+
+fn read_manifest(path: &Path) -> Result<toml::Value> {
+    let content = std::fs::read_to_string(path)
+        .chain_err(|| format!("Failed to read manifest {}", path.display()))?;
+    toml::from_str(&fix_duplicates(&content)).map_err(|err| {
+        // The parser of `toml_edit` points at the mistake with a snippet of the manifest.
+        match content.parse::<Manifest>() {
+            Err(parse_err) => parse_err.in_manifest(path),
+            Ok(_) => Error::with_chain(err, format!("Failed to parse manifest {}", path.display())),
+        }
+    })
+}
+
+fn parse_manifest(path: &Path) -> Result<Manifest> {
+    let content = std::fs::read_to_string(path)
+        .chain_err(|| format!("Failed to read manifest {}", path.display()))?;
+    content.parse().map_err(|err: Error| err.in_manifest(path))
+}
+
+fn string_list(item: &toml_edit::Item) -> Vec<String> {
+    item.as_array()
+        .map(|list| {
+            list.iter()
+                .filter_map(|v| v.as_str())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn toml_string_list(value: Option<&toml::Value>) -> Vec<String> {
+    value
+        .and_then(toml::Value::as_array)
+        .map(|list| {
+            list.iter()
+                .filter_map(toml::Value::as_str)
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The directories under `base` matching `pattern`, where `*`, `?` and `[...]` match within a
+/// single path component.
+fn expand_glob(base: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    let mut matches = vec![base.to_owned()];
+    for component in pattern.split(['/', '\\']) {
+        if component.is_empty() || component == "." {
+            continue;
+        }
+        if !component.contains(['*', '?', '[']) {
+            matches = matches.into_iter().map(|dir| dir.join(component)).collect();
+            continue;
+        }
+        let mut next = Vec::new();
+        for dir in matches {
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries {
+                let entry = entry?;
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                if entry.path().is_dir() && wildcard_match(component, &name) {
+                    next.push(entry.path());
+                }
+            }
+        }
+        next.sort();
+        matches = next;
+    }
+    Ok(matches)
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    match_from(&pattern, &name)
+}
+
+fn match_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| match_from(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && match_from(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let close = match pattern.iter().position(|&c| c == ']') {
+                Some(close) => close,
+                None => return name.first() == Some(&'[') && match_from(&pattern[1..], &name[1..]),
+            };
+            let class = &pattern[1..close];
+            let (negated, class) = match class.first() {
+                Some('!') | Some('^') => (true, &class[1..]),
+                _ => (false, class),
+            };
+            let c = match name.first() {
+                Some(&c) => c,
+                None => return false,
+            };
+            let mut found = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    found |= class[i] <= c && c <= class[i + 2];
+                    i += 3;
+                } else {
+                    found |= class[i] == c;
+                    i += 1;
+                }
+            }
+            found != negated && match_from(&pattern[close + 1..], &name[1..])
+        }
+        Some(&c) => name.first() == Some(&c) && match_from(&pattern[1..], &name[1..]),
+    }
+}
+
+fn parent(path: &Path) -> &Path {
+    path.parent().unwrap_or_else(|| Path::new("."))
+}
+
+fn absolute(path: &Path) -> Result<PathBuf> {
+    Ok(normalize(&logical_path::current_dir()?.join(path)))
+}
+
+/// Resolve `.` and `..` without touching the file system, as members may be given through
+/// symlinked paths which should stay as written.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/workspace")
+    }
+
+    #[test]
+    fn discovers_members() {
+        let root = fixture();
+        for start in &["Cargo.toml", "one/Cargo.toml", "implicit/three/Cargo.toml"] {
+            let workspace = Workspace::load(&root.join(start)).unwrap();
+            assert_eq!(workspace.root_manifest(), root.join("Cargo.toml"));
+            assert!(workspace.is_virtual());
+            let names: Vec<_> = workspace.members().iter().map(|m| &m.name).collect();
+            assert_eq!(names, ["four", "one", "three", "two"]);
+        }
+        let three = Workspace::load(&root.join("Cargo.toml"))
+            .unwrap()
+            .member("three")
+            .cloned()
+            .unwrap();
+        assert_eq!(three.manifest_path, root.join("implicit/three/Cargo.toml"));
+        assert_eq!(three.version.as_deref(), Some("0.1.0"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn members_reached_through_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, content: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        // A workspace gathering a crate of another repository, and listing one of its own
+        // under two paths.
+        write(
+            "ws/Cargo.toml",
+            "[workspace]\nmembers = [\"crates/*\", \"shared\"]\n",
+        );
+        write(
+            "ws/shared/Cargo.toml",
+            "[package]\nname = \"shared\"\nversion = \"0.1.0\"\n",
+        );
+        write(
+            "repos/app/Cargo.toml",
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\
+             [dependencies]\nshared = { path = \"../shared\" }\n",
+        );
+        std::fs::create_dir_all(dir.path().join("ws/crates")).unwrap();
+        let symlink = |target: &str, link: &str| {
+            std::os::unix::fs::symlink(dir.path().join(target), dir.path().join(link)).unwrap()
+        };
+        symlink("repos/app", "ws/crates/app");
+        symlink("ws/shared", "ws/crates/shared");
+
+        let root = dir.path().join("ws/Cargo.toml");
+        let workspace = Workspace::load(&root).unwrap();
+        let members: Vec<_> = workspace
+            .members()
+            .iter()
+            .map(|m| (m.name.as_str(), m.manifest_path.clone()))
+            .collect();
+        // Members keep the paths they were found under first.
+        assert_eq!(
+            members,
+            [
+                ("app", dir.path().join("ws/crates/app/Cargo.toml")),
+                ("shared", dir.path().join("ws/crates/shared/Cargo.toml")),
+            ]
+        );
+        assert!(workspace
+            .manifest(&dir.path().join("repos/app/Cargo.toml"))
+            .is_some());
+        let graph = workspace.dependency_graph().unwrap();
+        assert_eq!(graph["app"].iter().collect::<Vec<_>>(), ["shared"]);
+    }
+
+    #[test]
+    fn broken_members() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, content: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write("Cargo.toml", "[workspace]\nmembers = [\"good\", \"bad\"]\n");
+        write(
+            "good/Cargo.toml",
+            "[package]\nname = \"good\"\nversion = \"0.1.0\"\n",
+        );
+        write(
+            "bad/Cargo.toml",
+            "[package]\nname = \"bad\"\nversion = 0.1.0\n",
+        );
+        let root = dir.path().join("Cargo.toml");
+        let bad = dir.path().join("bad/Cargo.toml");
+
+        let err = Workspace::load(&root).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("Unable to parse {}:3:14", bad.display())
+        );
+
+        let workspace = Workspace::load_partial(&root).unwrap();
+        let names: Vec<_> = workspace.members().iter().map(|m| &m.name).collect();
+        assert_eq!(names, ["good"]);
+        let broken = workspace.broken_manifests();
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].path, bad);
+        assert_eq!((broken[0].line, broken[0].column), (Some(3), Some(14)));
+        let shown = broken[0].to_string();
+        assert!(shown.starts_with(&format!("{}:3:14\n", bad.display())));
+        assert!(shown.contains("version = 0.1.0"));
+        // Starting from the broken member still finds the others.
+        assert!(Workspace::load_partial(&bad).is_ok());
+    }
+
+    #[test]
+    fn member_graph() {
+        let workspace = Workspace::load(&fixture().join("Cargo.toml")).unwrap();
+        let graph = workspace.dependency_graph().unwrap();
+        assert_eq!(graph["one"].iter().collect::<Vec<_>>(), ["three"]);
+        assert!(graph["two"].is_empty());
+        assert_eq!(
+            workspace.dependents_of("three").unwrap(),
+            std::iter::once("one".to_owned()).collect()
+        );
+    }
+
+    #[test]
+    fn exclude_and_standalone_packages() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, content: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write(
+            "Cargo.toml",
+            "[package]\nname = \"root\"\nversion = \"1.0.0\"\n\n[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/skip\"]\n",
+        );
+        write("crates/a/Cargo.toml", "[package]\nname = \"a\"\n");
+        write("crates/skip/Cargo.toml", "[package]\nname = \"skip\"\n");
+
+        let workspace = Workspace::load(&dir.path().join("crates/a/Cargo.toml")).unwrap();
+        assert!(!workspace.is_virtual());
+        let names: Vec<_> = workspace.members().iter().map(|m| &m.name).collect();
+        assert_eq!(names, ["a", "root"]);
+
+        let skip = dir.path().join("crates/skip/Cargo.toml");
+        let a = workspace
+            .manifest(&dir.path().join("crates/x/../a/Cargo.toml"))
+            .unwrap();
+        assert_eq!(a.data["package"]["name"].as_str(), Some("a"));
+        assert!(workspace.manifest(&dir.path().join("Cargo.toml")).is_some());
+        assert!(workspace.manifest(&skip).is_none());
+
+        assert_eq!(find_workspace_root(&skip).unwrap(), skip);
+        let standalone = Workspace::load(&skip).unwrap();
+        assert_eq!(standalone.members().len(), 1);
+    }
+
+    #[test]
+    fn inherited_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, content: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"a\", \"b\"]\n\n[workspace.package]\nversion = \"1.2.0\"\n",
+        );
+        write(
+            "a/Cargo.toml",
+            "[package]\nname = \"a\"\nversion = { workspace = true }\n",
+        );
+        write(
+            "b/Cargo.toml",
+            "[package]\nname = \"b\"\nversion = \"0.3.0\"\n",
+        );
+
+        let workspace = Workspace::load(&dir.path().join("Cargo.toml")).unwrap();
+        let a = workspace.member("a").unwrap();
+        assert_eq!(
+            (a.version.as_deref(), a.inherits_version),
+            (Some("1.2.0"), true)
+        );
+        let b = workspace.member("b").unwrap();
+        assert_eq!(
+            (b.version.as_deref(), b.inherits_version),
+            (Some("0.3.0"), false)
+        );
+        assert_eq!(
+            crate::get_crate_version_from_path(dir.path().join("a").to_str().unwrap()).unwrap(),
+            semver::Version::new(1, 2, 0)
+        );
+    }
+
+    #[test]
+    fn inherited_rust_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, content: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"a\", \"b\", \"c\"]\n\n[workspace.package]\nrust-version = \"1.60\"\n",
+        );
+        write(
+            "a/Cargo.toml",
+            "[package]\nname = \"a\"\nrust-version = { workspace = true }\n",
+        );
+        write(
+            "b/Cargo.toml",
+            "[package]\nname = \"b\"\nrust-version = \"1.56\"\n",
+        );
+        write("c/Cargo.toml", "[package]\nname = \"c\"\n");
+
+        let rust_version = |path: &str| {
+            crate::get_rust_version_from_manifest(&dir.path().join(path))
+                .unwrap()
+                .map(|rust_version| rust_version.to_string())
+        };
+        assert_eq!(rust_version("Cargo.toml").as_deref(), Some("1.60"));
+        assert_eq!(rust_version("a/Cargo.toml").as_deref(), Some("1.60"));
+        assert_eq!(rust_version("b/Cargo.toml").as_deref(), Some("1.56"));
+        assert_eq!(rust_version("c/Cargo.toml"), None);
+    }
+
+    #[test]
+    fn nested_workspaces_and_explicit_members() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, content: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"crates/*\", \"vendor/c\"]\nexclude = [\"vendor\"]\n",
+        );
+        write(
+            "crates/a/Cargo.toml",
+            "[package]\nname = \"a\"\n\n[dependencies]\nb = { path = \"../../inner/b\" }\n",
+        );
+        write("inner/Cargo.toml", "[workspace]\nmembers = [\"b\"]\n");
+        write("inner/b/Cargo.toml", "[package]\nname = \"b\"\n");
+        write("vendor/c/Cargo.toml", "[package]\nname = \"c\"\n");
+        write("vendor/d/Cargo.toml", "[package]\nname = \"d\"\n");
+        write("stray/Cargo.toml", "[package]\nname = \"stray\"\n");
+
+        // The path dependency into the inner workspace is not a member of the outer one.
+        let outer = Workspace::load(&dir.path().join("crates/a/Cargo.toml")).unwrap();
+        let names: Vec<_> = outer.members().iter().map(|m| &m.name).collect();
+        assert_eq!(names, ["a", "c"]);
+
+        let b = dir.path().join("inner/b/Cargo.toml");
+        assert_eq!(
+            find_workspace_root(&b).unwrap(),
+            dir.path().join("inner/Cargo.toml")
+        );
+        let names: Vec<_> = Workspace::load(&b)
+            .unwrap()
+            .members()
+            .iter()
+            .map(|m| m.name.clone())
+            .collect();
+        assert_eq!(names, ["b"]);
+
+        let d = dir.path().join("vendor/d/Cargo.toml");
+        assert_eq!(find_workspace_root(&d).unwrap(), d);
+        let c = dir.path().join("vendor/c/Cargo.toml");
+        assert_eq!(
+            find_workspace_root(&c).unwrap(),
+            dir.path().join("Cargo.toml")
+        );
+
+        let err = Workspace::load(&dir.path().join("stray/Cargo.toml")).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NotInWorkspace(..)));
+    }
+
+    #[test]
+    fn edits_go_where_inherited_fields_live() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("Cargo.toml");
+        let app = dir.path().join("app/Cargo.toml");
+        std::fs::create_dir_all(app.parent().unwrap()).unwrap();
+        std::fs::write(
+            &root,
+            "[workspace]\nmembers = [\"app\"]\n\n[workspace.dependencies]\nserde = { version = \"1.0\", features = [\"derive\"] }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &app,
+            "[package]\nname = \"app\"\n\n[dependencies]\nserde = { workspace = true }\nlog = \"0.4\"\nmissing = { workspace = true }\n",
+        )
+        .unwrap();
+
+        let mut workspace = Workspace::load(&app).unwrap();
+        assert_eq!(
+            workspace
+                .resolved_dependencies(&app)
+                .unwrap_err()
+                .to_string(),
+            "The dependency `missing` could not be found in `workspace.dependencies`."
+        );
+        std::fs::write(
+            &app,
+            "[package]\nname = \"app\"\n\n[dependencies]\nserde = { workspace = true }\nlog = \"0.4\"\n",
+        )
+        .unwrap();
+        workspace = Workspace::load(&app).unwrap();
+
+        let deps = workspace.resolved_dependencies(&app).unwrap();
+        let (serde, log) = (&deps[0], &deps[1]);
+        assert!(serde.is_inherited());
+        assert!(!log.is_inherited());
+        assert_eq!(serde.version_req(), Some("1.0"));
+
+        assert_eq!(
+            workspace.set_dependency_version(serde, "1.0.100").unwrap(),
+            root
+        );
+        let features = ["derive".to_owned(), "rc".to_owned()];
+        assert_eq!(
+            workspace.add_dependency_features(serde, &features).unwrap(),
+            app
+        );
+        assert_eq!(workspace.set_dependency_optional(log, true).unwrap(), app);
+
+        let mut transaction = ManifestTransaction::new();
+        workspace.stage_changes(&mut transaction).unwrap();
+        transaction.commit().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&root).unwrap(),
+            "[workspace]\nmembers = [\"app\"]\n\n[workspace.dependencies]\nserde = { version = \"1.0.100\", features = [\"derive\"] }\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&app).unwrap(),
+            "[package]\nname = \"app\"\n\n[dependencies]\nserde = { workspace = true, features = [\"rc\"] }\nlog = { version = \"0.4\", optional = true }\n"
+        );
+    }
+
+    #[test]
+    fn removes_dependencies_from_any_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("Cargo.toml");
+        let app = dir.path().join("app/Cargo.toml");
+        std::fs::create_dir_all(app.parent().unwrap()).unwrap();
+        std::fs::write(
+            &root,
+            "[workspace]\nmembers = [\"app\"]\n\n[workspace.dependencies]\nserde = \"1.0\"\nlog = \"0.4\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &app,
+            "[package]\nname = \"app\"\n\n[dependencies]\nserde = { workspace = true, optional = true }\n\n[features]\njson = [\"serde\"]\n",
+        )
+        .unwrap();
+
+        let mut workspace = Workspace::load(&app).unwrap();
+        let entries = workspace.dependency_entries().unwrap();
+        let find = |key: &str, in_workspace: bool| {
+            entries
+                .iter()
+                .find(|entry| entry.key == key && entry.table.workspace == in_workspace)
+                .unwrap()
+        };
+        assert_eq!(
+            workspace.remove_dependency(find("serde", false)).unwrap(),
+            app
+        );
+        assert_eq!(
+            workspace.remove_dependency(find("log", true)).unwrap(),
+            root
+        );
+
+        let mut transaction = ManifestTransaction::new();
+        workspace.stage_changes(&mut transaction).unwrap();
+        transaction.commit().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&root).unwrap(),
+            "[workspace]\nmembers = [\"app\"]\n\n[workspace.dependencies]\nserde = \"1.0\"\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&app).unwrap(),
+            "[package]\nname = \"app\"\n\n[features]\njson = []\n"
+        );
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(wildcard_match("*", "one"));
+        assert!(wildcard_match("crate-?", "crate-a"));
+        assert!(wildcard_match("[a-c]*", "bar"));
+        assert!(!wildcard_match("[!a-c]*", "bar"));
+        assert!(!wildcard_match("crate-?", "crate-ab"));
+    }
+}
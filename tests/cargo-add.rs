@@ -3,6 +3,8 @@ extern crate pretty_assertions;
 
 use std::process;
 mod utils;
+#[cfg(feature = "fetch")]
+use crate::utils::init_git_repo;
 use crate::utils::{
     clone_out_test, copy_workspace_test, execute_bad_command, execute_command,
     execute_command_for_pkg, get_command_path, get_toml, setup_alt_registry_config,
@@ -36,6 +38,23 @@ fn adds_dependency() {
     assert_eq!(val.as_str().unwrap(), "my-package--CURRENT_VERSION_TEST");
 }
 
+#[test]
+fn adds_dependency_in_configured_style() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    let mut contents = std::fs::read_to_string(&manifest).unwrap();
+    contents.push_str(
+        "\n\n[package.metadata.cargo-edit.style]\nquote-style = \"single\"\nalign = true\n",
+    );
+    std::fs::write(&manifest, contents).unwrap();
+
+    execute_command(&["add", "my-package", "a"], &manifest);
+
+    let contents = std::fs::read_to_string(&manifest).unwrap();
+    assert!(contents.contains(
+        "[dependencies]\na          = 'a--CURRENT_VERSION_TEST'\nmy-package = 'my-package--CURRENT_VERSION_TEST'\n"
+    ));
+}
+
 #[test]
 fn adds_prerelease_dependency() {
     let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
@@ -96,7 +115,7 @@ fn adds_dependency_with_upgrade_bad() {
     let toml = get_toml(&manifest);
     assert!(toml["dependencies"].is_none());
 
-    let upgrade_arg = format!("--upgrade=an_invalid_string",);
+    let upgrade_arg = "--upgrade=an_invalid_string".to_string();
     execute_bad_command(&["add", "my-package", upgrade_arg.as_str()], &manifest);
 }
 
@@ -207,7 +226,7 @@ fn adds_dev_build_dependency() {
 
     // cannot run with both --dev and --build at the same time
     let call = process::Command::new(get_command_path("add").as_str())
-        .args(&["add", BOGUS_CRATE_NAME, "--dev", "--build"])
+        .args(["add", BOGUS_CRATE_NAME, "--dev", "--build"])
         .arg(format!("--manifest-path={}", &manifest))
         .output()
         .unwrap();
@@ -280,7 +299,7 @@ fn adds_specified_version() {
 
     // cannot run with both --dev and --build at the same time
     let call = process::Command::new(get_command_path("add").as_str())
-        .args(&["add", BOGUS_CRATE_NAME, "--vers", "invalid version string"])
+        .args(["add", BOGUS_CRATE_NAME, "--vers", "invalid version string"])
         .arg(format!("--manifest-path={}", &manifest))
         .output()
         .unwrap();
@@ -305,6 +324,28 @@ fn adds_specified_version_with_inline_notation() {
     assert_eq!(val.as_str().expect("not string"), ">=0.1.1");
 }
 
+#[test]
+fn adds_dependencies_from_package_id_specs() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+
+    execute_command(
+        &[
+            "add",
+            "registry+https://github.com/rust-lang/crates.io-index#regex@1.4.3",
+            "git+https://github.com/x/y?branch=dev#git-package@0.1.0",
+        ],
+        &manifest,
+    );
+
+    let toml = get_toml(&manifest);
+    let val = &toml["dependencies"]["regex"];
+    assert_eq!(val.as_str(), Some("1.4.3"));
+    let val = &toml["dependencies"]["git-package"];
+    assert_eq!(val["git"].as_str(), Some("https://github.com/x/y"));
+    assert_eq!(val["branch"].as_str(), Some("dev"));
+    assert!(val["version"].is_none());
+}
+
 #[test]
 fn adds_multiple_dependencies_with_versions() {
     let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
@@ -470,6 +511,116 @@ fn adds_local_source_using_flag() {
     assert_eq!(val["path"].as_str(), Some("/path/to/pkg-dev"));
 }
 
+#[test]
+#[cfg(feature = "fetch")]
+fn adds_git_source_pinned_to_its_commit() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    let upstream = tmpdir.path().join("upstream");
+    let commits = init_git_repo(&upstream, &["0.8.3", "0.8.4"]);
+    let repo = git2::Repository::open(&upstream).unwrap();
+    let first = repo.find_commit(commits[0]).unwrap();
+    repo.branch("stable", &first, false).unwrap();
+    let url = url::Url::from_directory_path(&upstream).unwrap();
+    let add = |args: &[&str]| {
+        let mut command = vec![get_command_path("add"), "add".to_owned()];
+        command.extend(args.iter().map(|arg| arg.to_string()));
+        command.extend(vec!["--manifest-path".to_owned(), manifest.clone()]);
+        assert_cli::Assert::command(&command)
+            .with_env(
+                assert_cli::Environment::inherit()
+                    .insert("CARGO_IS_TEST", "1")
+                    .insert("CARGO_HOME", tmpdir.path().join("cargo-home")),
+            )
+            .succeeds()
+            .unwrap();
+    };
+
+    add(&["docopt", "--git", url.as_str(), "--pin-rev"]);
+    let toml = get_toml(&manifest);
+    let val = &toml["dependencies"]["docopt"];
+    assert_eq!(val["git"].as_str(), Some(url.as_str()));
+    assert_eq!(val["rev"].as_str(), Some(commits[1].to_string().as_str()));
+
+    // The branch is replaced by the commit it points to.
+    add(&[
+        "docopt",
+        "--git",
+        url.as_str(),
+        "--branch",
+        "stable",
+        "--pin-rev",
+    ]);
+    let toml = get_toml(&manifest);
+    let val = &toml["dependencies"]["docopt"];
+    assert_eq!(val["rev"].as_str(), Some(commits[0].to_string().as_str()));
+    assert!(val["branch"].is_none());
+}
+
+#[test]
+#[cfg(feature = "fetch")]
+fn pins_existing_branch_dependency() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    let upstream = tmpdir.path().join("upstream");
+    let commits = init_git_repo(&upstream, &["0.8.3", "0.8.4"]);
+    let url = url::Url::from_directory_path(&upstream).unwrap();
+    let toml = std::fs::read_to_string(&manifest).unwrap();
+    std::fs::write(
+        &manifest,
+        format!(
+            "{}\n[dependencies]\ndocopt = {{ git = \"{}\", branch = \"master\", features = [\"x\"] }}\n",
+            toml, url
+        ),
+    )
+    .unwrap();
+
+    assert_cli::Assert::command(&[
+        get_command_path("add").as_str(),
+        "add",
+        "docopt",
+        "--git",
+        url.as_str(),
+        "--pin-rev",
+        "--manifest-path",
+        &manifest,
+    ])
+    .with_env(
+        assert_cli::Environment::inherit()
+            .insert("CARGO_IS_TEST", "1")
+            .insert("CARGO_HOME", tmpdir.path().join("cargo-home")),
+    )
+    .succeeds()
+    .unwrap();
+
+    let toml = get_toml(&manifest);
+    let val = &toml["dependencies"]["docopt"];
+    assert_eq!(val["git"].as_str(), Some(url.as_str()));
+    assert_eq!(val["rev"].as_str(), Some(commits[1].to_string().as_str()));
+    assert!(val["branch"].is_none());
+    assert_eq!(val["features"].as_array().map(|f| f.len()), Some(1));
+}
+
+#[test]
+fn pin_rev_needs_a_git_source() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+
+    assert_cli::Assert::command(&[
+        get_command_path("add").as_str(),
+        "add",
+        "docopt",
+        "--pin-rev",
+        "--manifest-path",
+        &manifest,
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .fails()
+    .and()
+    .stderr()
+    .contains("Cannot pin `docopt` to a commit, as it is not a git dependency.")
+    .unwrap();
+
+    assert!(get_toml(&manifest)["dependencies"].is_none());
+}
+
 #[test]
 #[cfg(feature = "test-external-apis")]
 fn adds_git_source_without_flag() {
@@ -675,9 +826,9 @@ fn git_and_version_flags_are_mutually_exclusive() {
     let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
 
     let call = process::Command::new(get_command_path("add").as_str())
-        .args(&["add", BOGUS_CRATE_NAME])
-        .args(&["--vers", "0.4.3"])
-        .args(&["--git", "git://git.git"])
+        .args(["add", BOGUS_CRATE_NAME])
+        .args(["--vers", "0.4.3"])
+        .args(["--git", "git://git.git"])
         .arg(format!("--manifest-path={}", &manifest))
         .output()
         .unwrap();
@@ -691,8 +842,8 @@ fn git_flag_and_inline_version_are_mutually_exclusive() {
     let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
 
     let call = process::Command::new(get_command_path("add").as_str())
-        .args(&["add", &format!("{}@0.4.3", BOGUS_CRATE_NAME)])
-        .args(&["--git", "git://git.git"])
+        .args(["add", &format!("{}@0.4.3", BOGUS_CRATE_NAME)])
+        .args(["--git", "git://git.git"])
         .arg(format!("--manifest-path={}", &manifest))
         .output()
         .unwrap();
@@ -706,9 +857,9 @@ fn git_and_path_are_mutually_exclusive() {
     let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
 
     let call = process::Command::new(get_command_path("add").as_str())
-        .args(&["add", BOGUS_CRATE_NAME])
-        .args(&["--git", "git://git.git"])
-        .args(&["--path", "/path/here"])
+        .args(["add", BOGUS_CRATE_NAME])
+        .args(["--git", "git://git.git"])
+        .args(["--path", "/path/here"])
         .arg(format!("--manifest-path={}", &manifest))
         .output()
         .unwrap();
@@ -722,9 +873,9 @@ fn git_and_registry_are_mutually_exclusive() {
     let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
 
     let call = process::Command::new(get_command_path("add").as_str())
-        .args(&["add", BOGUS_CRATE_NAME])
-        .args(&["--git", "git://git.git"])
-        .args(&["--registry", "alternative"])
+        .args(["add", BOGUS_CRATE_NAME])
+        .args(["--git", "git://git.git"])
+        .args(["--registry", "alternative"])
         .arg(format!("--manifest-path={}", &manifest))
         .output()
         .unwrap();
@@ -738,9 +889,9 @@ fn registry_and_path_are_mutually_exclusive() {
     let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
 
     let call = process::Command::new(get_command_path("add").as_str())
-        .args(&["add", BOGUS_CRATE_NAME])
-        .args(&["--registry", "alternative"])
-        .args(&["--path", "/path/here"])
+        .args(["add", BOGUS_CRATE_NAME])
+        .args(["--registry", "alternative"])
+        .args(["--path", "/path/here"])
         .arg(format!("--manifest-path={}", &manifest))
         .output()
         .unwrap();
@@ -1053,6 +1204,20 @@ fn forbids_multiple_crates_with_features_option() {
     .unwrap();
 }
 
+#[test]
+fn adds_features_per_crate() {
+    overwrite_dependency_test(
+        &["add", "my-package"],
+        &["add", "your-face+nose", "my-package2+foo,bar", "my-package"],
+        r#"
+[dependencies]
+my-package = "my-package--CURRENT_VERSION_TEST"
+my-package2 = { version = "my-package2--CURRENT_VERSION_TEST", features = ["foo", "bar"] }
+your-face = { version = "your-face--CURRENT_VERSION_TEST", features = ["nose"] }
+"#,
+    )
+}
+
 #[test]
 fn adds_dependency_with_custom_target() {
     let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
@@ -1265,6 +1430,18 @@ a2 = { version = "0.2", package = "a" }
     )
 }
 
+#[test]
+fn overwrite_renamed_by_key() {
+    overwrite_dependency_test(
+        &["add", "a", "--vers", "0.1", "--rename", "a1"],
+        &["add", "a1"],
+        r#"
+[dependencies]
+a1 = { version = "a--CURRENT_VERSION_TEST", package = "a" }
+"#,
+    )
+}
+
 #[test]
 fn overwrite_previously_renamed() {
     overwrite_dependency_test(
@@ -1307,6 +1484,55 @@ versioned-package = "versioned-package--CURRENT_VERSION_TEST"
     )
 }
 
+#[test]
+fn invalid_crate_names() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    setup_alt_registry_config(tmpdir.path());
+
+    assert_cli::Assert::command(&[
+        get_command_path("add").as_str(),
+        "add",
+        "std",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .fails_with(1)
+    .and()
+    .stderr()
+    .contains("Invalid crate name `std`: it is reserved for a crate shipped with Rust")
+    .unwrap();
+
+    assert_cli::Assert::command(&[
+        get_command_path("add").as_str(),
+        "add",
+        "2d@1.0",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .fails_with(1)
+    .and()
+    .stderr()
+    .contains("Invalid crate name `2d`: it must not start with a digit")
+    .unwrap();
+
+    // Other registries only have to follow cargo's rules.
+    execute_command(
+        &[
+            "add",
+            "_private",
+            "--vers",
+            "0.1",
+            "--registry",
+            "alternative",
+        ],
+        &manifest,
+    );
+    let toml = get_toml(&manifest);
+    assert_eq!(
+        toml["dependencies"]["_private"]["registry"].as_str(),
+        Some("alternative")
+    );
+}
+
 #[test]
 fn no_argument() {
     assert_cli::Assert::command(&[get_command_path("add").as_str(), "add"])
@@ -1351,7 +1577,7 @@ fn add_prints_message() {
         "--vers=0.6.0",
         &format!("--manifest-path={}", manifest),
     ])
-    .with_env(&[("CARGO_IS_TEST", "1")])
+    .with_env([("CARGO_IS_TEST", "1")])
     .succeeds()
     .and()
     .stdout()
@@ -1372,7 +1598,7 @@ fn add_prints_message_with_section() {
         "--vers=0.1.0",
         &format!("--manifest-path={}", manifest),
     ])
-    .with_env(&[("CARGO_IS_TEST", "1")])
+    .with_env([("CARGO_IS_TEST", "1")])
     .succeeds()
     .and()
     .stdout()
@@ -1393,7 +1619,7 @@ fn add_prints_message_for_dev_deps() {
         "0.8.0",
         &format!("--manifest-path={}", manifest),
     ])
-    .with_env(&[("CARGO_IS_TEST", "1")])
+    .with_env([("CARGO_IS_TEST", "1")])
     .succeeds()
     .and()
     .stdout()
@@ -1414,7 +1640,7 @@ fn add_prints_message_for_build_deps() {
         "0.1.0",
         &format!("--manifest-path={}", manifest),
     ])
-    .with_env(&[("CARGO_IS_TEST", "1")])
+    .with_env([("CARGO_IS_TEST", "1")])
     .succeeds()
     .and()
     .stdout()
@@ -1529,6 +1755,55 @@ fn add_dependency_to_workspace_member() {
         "toml--CURRENT_VERSION_TEST",
     );
 }
+#[test]
+fn add_inherits_workspace_dependencies() {
+    let (tmpdir, root_manifest, _workspace_manifests) = copy_workspace_test();
+    let root = std::fs::read_to_string(&root_manifest).unwrap();
+    std::fs::write(
+        &root_manifest,
+        format!("{}\n\n[workspace.dependencies]\ntoml = \"0.5\"\n", root),
+    )
+    .unwrap();
+    let one = tmpdir.path().join("one/Cargo.toml");
+    let one = one.to_str().unwrap();
+
+    assert_cli::Assert::command(&[
+        "target/debug/cargo-add",
+        "add",
+        "toml+preserve_order",
+        &format!("--manifest-path={}", one),
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .succeeds()
+    .and()
+    .stdout()
+    .contains(r#"Adding toml from the workspace to dependencies with features: ["preserve_order"]"#)
+    .unwrap();
+    let toml = &get_toml(one)["dependencies"]["toml"];
+    assert_eq!(toml["workspace"].as_bool(), Some(true));
+    assert_eq!(toml["features"].as_array().unwrap().len(), 1);
+    assert!(toml["version"].is_none());
+
+    // Crates with a version of their own, or missing from the workspace, are looked up.
+    execute_command(&["add", "toml@0.4"], one);
+    execute_command(&["add", "serde"], one);
+    let manifest = get_toml(one);
+    assert_eq!(
+        manifest["dependencies"]["toml"]["version"].as_str(),
+        Some("0.4")
+    );
+    assert!(manifest["dependencies"]["toml"]["workspace"].is_none());
+    assert_eq!(
+        manifest["dependencies"]["serde"].as_str(),
+        Some("serde--CURRENT_VERSION_TEST")
+    );
+
+    execute_command(&["add", "toml", "--no-workspace-inherit"], one);
+    let toml = &get_toml(one)["dependencies"]["toml"];
+    assert_eq!(toml["version"].as_str(), Some("toml--CURRENT_VERSION_TEST"));
+    assert!(toml["workspace"].is_none());
+}
+
 #[test]
 fn add_prints_message_for_features_deps() {
     let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
@@ -1549,3 +1824,403 @@ fn add_prints_message_for_features_deps() {
     .contains(r#"Adding hello-world v0.1.0 to dependencies with features: ["jui"]"#)
     .unwrap();
 }
+
+#[test]
+fn adds_dependency_to_equivalent_target_cfg() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+
+    execute_command(
+        &["add", "--target", "cfg(any(unix,all(unix)))", "my-package1"],
+        &manifest,
+    );
+    // An existing table for the same platform is reused, however it is spelled.
+    execute_command(
+        &["add", "--target", "cfg( unix )", "my-package2"],
+        &manifest,
+    );
+
+    let toml = get_toml(&manifest);
+    let target = toml["target"].as_table().unwrap();
+    assert_eq!(target.iter().count(), 1);
+    let dependencies = &target["cfg(unix)"]["dependencies"];
+    assert!(dependencies["my-package1"].as_str().is_some());
+    assert!(dependencies["my-package2"].as_str().is_some());
+}
+
+#[test]
+fn adds_dependency_to_target_cfg_with_single_quotes() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+
+    execute_command(
+        &[
+            "add",
+            "--target",
+            r#"cfg(target_os = "linux")"#,
+            "my-package1",
+        ],
+        &manifest,
+    );
+    execute_command(
+        &["add", "--target", "cfg(target_os='linux')", "my-package2"],
+        &manifest,
+    );
+
+    let contents = std::fs::read_to_string(&manifest).unwrap();
+    assert!(!contents.contains("[target]"));
+    let toml = get_toml(&manifest);
+    let target = toml["target"].as_table().unwrap();
+    assert_eq!(target.iter().count(), 1);
+    let dependencies = &target[r#"cfg(target_os = "linux")"#]["dependencies"];
+    assert!(dependencies["my-package1"].as_str().is_some());
+    assert!(dependencies["my-package2"].as_str().is_some());
+}
+
+#[test]
+fn fails_to_add_dependency_with_invalid_target_cfg() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+
+    execute_bad_command(&["add", "--target", "cfg(unix", "my-package1"], &manifest);
+}
+
+#[test]
+fn sorts_dependencies_as_configured_for_the_workspace() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.unsorted");
+    let mut contents = std::fs::read_to_string(&manifest).unwrap();
+    contents.push_str("\n[workspace.metadata.cargo-edit]\nsort = true\n");
+    std::fs::write(&manifest, contents).unwrap();
+
+    execute_command(&["add", "toml"], &manifest);
+
+    let toml = get_toml(&manifest);
+    let names: Vec<&str> = toml["dependencies"]
+        .as_table()
+        .unwrap()
+        .iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(names, ["atty", "toml", "toml_edit"]);
+}
+
+#[test]
+fn adds_versions_as_configured_for_the_workspace() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    let mut contents = std::fs::read_to_string(&manifest).unwrap();
+    contents.push_str(
+        "\n[workspace.metadata.cargo-edit]\nprecision = \"major\"\n\
+         prerelease-crates = [\"my-package1\"]\n",
+    );
+    std::fs::write(&manifest, contents).unwrap();
+
+    execute_command(&["add", "test_breaking", "my-package1"], &manifest);
+
+    let toml = get_toml(&manifest);
+    assert_eq!(toml["dependencies"]["test_breaking"].as_str(), Some("0.2"));
+    assert_eq!(
+        toml["dependencies"]["my-package1"].as_str(),
+        Some("my-package1--PRERELEASE_VERSION_TEST")
+    );
+}
+
+#[test]
+fn looks_crates_up_in_the_configured_registries() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    setup_alt_registry_config(tmpdir.path());
+    let mut contents = std::fs::read_to_string(&manifest).unwrap();
+    contents.push_str(
+        "\n[workspace.metadata.cargo-edit]\nregistries = [\"alternative\", \"crates-io\"]\n\
+         crate-registries = { my-package2 = [\"crates-io\"] }\n",
+    );
+    std::fs::write(&manifest, contents).unwrap();
+
+    assert_cli::Assert::command(&[
+        get_command_path("add").as_str(),
+        "add",
+        "my-package1",
+        "my-package2",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .succeeds()
+    .and()
+    .stdout()
+    .contains("Adding my-package1 v")
+    .stdout()
+    .contains("from registry `alternative` to dependencies")
+    .unwrap();
+
+    let toml = get_toml(&manifest);
+    assert_eq!(
+        toml["dependencies"]["my-package1"]["registry"].as_str(),
+        Some("alternative")
+    );
+    assert!(toml["dependencies"]["my-package2"].as_str().is_some());
+}
+
+#[test]
+fn refuses_crates_on_several_registries() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    setup_alt_registry_config(tmpdir.path());
+
+    // The test stubs have every crate on every registry.
+    assert_cli::Assert::command(&[
+        get_command_path("add").as_str(),
+        "add",
+        "my-package1",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .fails_with(1)
+    .and()
+    .stderr()
+    .contains(
+        "The crate `my-package1` is on several registries: \
+         `crates-io` (my-package1--CURRENT_VERSION_TEST), \
+         `alternative` (my-package1--CURRENT_VERSION_TEST). Pick one with `--registry`",
+    )
+    .unwrap();
+    assert!(get_toml(&manifest)["dependencies"].is_none());
+
+    execute_command(
+        &["add", "my-package1", "--registry", "alternative"],
+        &manifest,
+    );
+    assert_eq!(
+        get_toml(&manifest)["dependencies"]["my-package1"]["registry"].as_str(),
+        Some("alternative")
+    );
+}
+
+#[test]
+fn merges_duplicate_dependencies_when_asked() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.duplicates");
+
+    assert_cli::Assert::command(&[
+        get_command_path("add").as_str(),
+        "add",
+        "rand",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .fails_with(6)
+    .and()
+    .stderr()
+    .contains("Dependencies listed more than once: `docopt` in `dependencies` (lines 9, 12)")
+    .unwrap();
+
+    execute_command(&["add", "rand", "--fix-duplicates"], &manifest);
+    let contents = std::fs::read_to_string(&manifest).unwrap();
+    assert!(contents.contains("docopt = { version = \"0.8\", features = [\"serde\"] }\n"));
+    assert!(!contents.contains("[dependencies.docopt]"));
+    assert!(get_toml(&manifest)["dependencies"]["rand"].is_value());
+}
+
+#[test]
+fn keeps_the_quoting_of_keys() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.quoted_keys");
+
+    execute_command(&["add", "docopt", "--features", "serde"], &manifest);
+    execute_command(&["add", "semver", "--vers", "0.2"], &manifest);
+    execute_command(
+        &["add", "unicode-xid", "--rename", "ünicode", "--vers", "0.2"],
+        &manifest,
+    );
+    execute_command(
+        &["add", "libc", "--target", "cfg(unix)", "--vers", "0.3"],
+        &manifest,
+    );
+    execute_command(&["add", "unicode-width", "--rename", "ünicode2"], &manifest);
+
+    let contents = std::fs::read_to_string(&manifest).unwrap();
+    for entry in &[
+        r#""docopt" = { version = "docopt--CURRENT_VERSION_TEST", features = ["serde"] }"#,
+        r#"'semver' = "0.2""#,
+        r#""ünicode" = { version = "0.2", package = "unicode-xid" }"#,
+        r#""ünicode2" = { version = "unicode-width--CURRENT_VERSION_TEST", package = "unicode-width" }"#,
+        "[target.'cfg(unix)'.\"dependencies\"]\nlibc = \"0.3\"",
+    ] {
+        assert!(contents.contains(entry), "{} not in:\n{}", entry, contents);
+    }
+}
+
+#[test]
+fn edits_the_original_of_generated_manifests() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.generated");
+    let original = tmpdir.path().join("Cargo.toml.orig");
+    std::fs::copy("tests/fixtures/add/Cargo.toml.sample", &original).unwrap();
+
+    assert_cli::Assert::command(&[
+        get_command_path("add").as_str(),
+        "add",
+        "docopt",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .succeeds()
+    .and()
+    .stderr()
+    .contains("Cargo.toml was generated by cargo when its crate was packaged")
+    .unwrap();
+    assert!(!get_toml(&manifest)["dependencies"]["docopt"].is_none());
+
+    execute_command(&["add", "rand", "--orig"], &manifest);
+    assert!(get_toml(&manifest)["dependencies"]["rand"].is_none());
+    let original = get_toml(original.to_str().unwrap());
+    assert!(original["dependencies"]["rand"].is_value());
+    assert!(original["dependencies"]["docopt"].is_none());
+}
+
+#[test]
+fn logs_json_lines_to_a_file() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    let log = tmpdir.path().join("cargo-edit.log");
+
+    assert_cli::Assert::command(&[
+        get_command_path("add").as_str(),
+        "add",
+        "docopt",
+        "--manifest-path",
+        &manifest,
+    ])
+    .with_env(
+        assert_cli::Environment::inherit()
+            .insert("CARGO_IS_TEST", "1")
+            .insert("CARGO_EDIT_LOG", "cargo_edit::transaction=debug")
+            .insert("CARGO_EDIT_LOG_FORMAT", "json")
+            .insert("CARGO_EDIT_LOG_FILE", log.to_str().unwrap()),
+    )
+    .succeeds()
+    .and()
+    .stderr()
+    .doesnt_contain("writing manifest")
+    .unwrap();
+
+    let log = std::fs::read_to_string(log).unwrap();
+    let line = log
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .find(|line| line["fields"]["message"] == "writing manifest")
+        .expect(&log);
+    assert_eq!(line["level"], "DEBUG");
+    assert_eq!(line["target"], "cargo_edit::transaction");
+    assert_eq!(line["spans"][0]["name"], "commit");
+}
+
+#[test]
+fn exit_code_reports_unchanged_manifest() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    execute_command(&["add", "my-package", "--vers", "0.1"], &manifest);
+
+    assert_cli::Assert::command(&[
+        get_command_path("add").as_str(),
+        "add",
+        "my-package",
+        "--vers",
+        "0.1",
+        "--exit-code",
+        "--manifest-path",
+        &manifest,
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .fails_with(2)
+    .unwrap();
+}
+
+#[test]
+fn dry_run_prints_diff() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    let original = std::fs::read_to_string(&manifest).unwrap();
+
+    assert_cli::Assert::command(&[
+        get_command_path("add").as_str(),
+        "add",
+        "my-package",
+        "--features",
+        "derive",
+        "--dry-run",
+        "--exit-code",
+        "--manifest-path",
+        &manifest,
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .fails_with(3)
+    .and()
+    .stdout()
+    .contains("Starting dry run. Changes will not be saved.")
+    .and()
+    .stdout()
+    .contains("+[dependencies]")
+    .and()
+    .stdout()
+    .contains(
+        r#"+my-package = { version = "my-package--CURRENT_VERSION_TEST", features = ["derive"] }"#,
+    )
+    .unwrap();
+
+    assert_eq!(std::fs::read_to_string(&manifest).unwrap(), original);
+}
+
+#[test]
+fn interactive_without_terminal_adds_features_as_given() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+
+    assert_cli::Assert::command(&[
+        get_command_path("add").as_str(),
+        "add",
+        "my-package",
+        "--features",
+        "derive",
+        "--interactive",
+        "--manifest-path",
+        &manifest,
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .stdin("")
+    .succeeds()
+    .and()
+    .stderr()
+    .contains("WARN: `--interactive` needs a terminal; adding the features as given")
+    .unwrap();
+
+    let toml = get_toml(&manifest);
+    let features = toml["dependencies"]["my-package"]["features"]
+        .as_array()
+        .unwrap();
+    assert_eq!(features.get(0).and_then(|f| f.as_str()), Some("derive"));
+}
+
+#[test]
+fn json_request_adds_dependencies() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    let request = serde_json::json!({
+        "crates": ["my-package"],
+        "vers": "0.1",
+        "dev": true,
+        "manifest_path": manifest,
+    });
+
+    assert_cli::Assert::command(&[get_command_path("add").as_str(), "add", "--json-request"])
+        .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+        .stdin(request.to_string())
+        .succeeds()
+        .and()
+        .stdout()
+        .contains(r#"{"kind":"dependency-added","name":"my-package","table":"dev-dependencies","version":"0.1"}"#)
+        .and()
+        .stderr()
+        .is("")
+        .unwrap();
+
+    let toml = get_toml(&manifest);
+    assert_eq!(toml["dev-dependencies"]["my-package"].as_str(), Some("0.1"));
+}
+
+#[test]
+fn json_request_reports_invalid_requests() {
+    assert_cli::Assert::command(&[get_command_path("add").as_str(), "add", "--json-request"])
+        .stdin(r#"{"crates": ["my-package"], "man": true}"#)
+        .fails_with(1)
+        .and()
+        .stdout()
+        .is(r#"{"command":"add","error":{"causes":[],"exit_code":1,"message":"Invalid request: The argument '--man' cannot be used with '--json-request'"}}"#)
+        .unwrap();
+}
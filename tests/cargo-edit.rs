@@ -0,0 +1,173 @@
+mod utils;
+use crate::utils::{clone_out_test, get_command_path, get_toml};
+
+#[test]
+fn serve_answers_requests_until_shutdown() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/rm/Cargo.toml.sample");
+    let requests = [
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "query-latest", "params": {"name": "serde"}}"#
+            .to_owned(),
+        format!(
+            r#"{{"jsonrpc": "2.0", "id": 1, "method": "add", "params": {{"name": "my-package", "version": "0.4", "manifest_path": "{}"}}}}"#,
+            manifest
+        ),
+        format!(
+            r#"{{"jsonrpc": "2.0", "id": 2, "method": "remove", "params": {{"name": "docopt", "manifest_path": "{}"}}}}"#,
+            manifest
+        ),
+        r#"{"jsonrpc": "2.0", "id": 3, "method": "shutdown"}"#.to_owned(),
+        r#"{"jsonrpc": "2.0", "id": 4, "method": "query-latest", "params": {"name": "log"}}"#
+            .to_owned(),
+    ];
+
+    assert_cli::Assert::command(&[get_command_path("edit").as_str(), "edit", "serve"])
+        .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+        .stdin(requests.join("\n"))
+        .succeeds()
+        .and()
+        .stdout()
+        .is(r#"{"id":1,"jsonrpc":"2.0","result":{"name":"serde","version":"serde--CURRENT_VERSION_TEST"}}
+{"id":1,"jsonrpc":"2.0","result":{"name":"my-package","table":["dependencies"],"version":"0.4"}}
+{"id":2,"jsonrpc":"2.0","result":{"name":"docopt","table":["dependencies"]}}
+{"id":3,"jsonrpc":"2.0","result":null}"#)
+        .unwrap();
+
+    let toml = get_toml(&manifest);
+    let val = &toml["dependencies"]["my-package"];
+    assert_eq!(val.as_str(), Some("0.4"));
+    assert!(toml["dependencies"]["docopt"].is_none());
+}
+
+#[test]
+fn serve_reports_failed_requests() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/rm/Cargo.toml.sample");
+    let request = format!(
+        r#"{{"jsonrpc": "2.0", "id": "a", "method": "remove", "params": {{"name": "missing", "manifest_path": "{}"}}}}"#,
+        manifest
+    );
+
+    assert_cli::Assert::command(&[get_command_path("edit").as_str(), "edit", "serve"])
+        .stdin(request)
+        .succeeds()
+        .and()
+        .stdout()
+        .is(r#"{"error":{"code":-32000,"data":[],"message":"The dependency `missing` could not be found in `dependencies`."},"id":"a","jsonrpc":"2.0"}"#)
+        .unwrap();
+}
+
+#[test]
+fn serve_logs_to_stderr() {
+    assert_cli::Assert::command(&[get_command_path("edit").as_str(), "edit", "serve"])
+        .with_env(assert_cli::Environment::inherit().insert("CARGO_EDIT_LOG", "info"))
+        .stdin(r#"{"jsonrpc": "2.0", "id": 1, "method": "shutdown"}"#)
+        .succeeds()
+        .and()
+        .stdout()
+        .is(r#"{"id":1,"jsonrpc":"2.0","result":null}"#)
+        .and()
+        .stderr()
+        .contains("request{method=shutdown}")
+        .unwrap();
+}
+
+#[test]
+fn invalid_log_filter() {
+    assert_cli::Assert::command(&[get_command_path("edit").as_str(), "edit", "serve"])
+        .with_env(assert_cli::Environment::inherit().insert("CARGO_EDIT_LOG", "cargo_edit=["))
+        .stdin("")
+        .fails_with(1)
+        .and()
+        .stderr()
+        .contains("Invalid `CARGO_EDIT_LOG` filter `cargo_edit=[`")
+        .unwrap();
+}
+
+#[test]
+fn snapshot_carries_index_entries_over() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    let index = tmpdir.path().join("registry").join("index");
+    for (path, entry) in &[
+        (
+            "3/l/log",
+            r#"{"name":"log","vers":"0.4.0","deps":[{"name":"cfg-if","req":"1"}],"cksum":"","features":{},"yanked":false}"#,
+        ),
+        (
+            "cf/g-/cfg-if",
+            r#"{"name":"cfg-if","vers":"1.0.0","deps":[],"cksum":"","features":{},"yanked":false}"#,
+        ),
+    ] {
+        let file = index.join(path);
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(file, entry).unwrap();
+    }
+    std::fs::create_dir(tmpdir.path().join(".cargo")).unwrap();
+    std::fs::write(
+        tmpdir.path().join(".cargo").join("config.toml"),
+        "[source.crates-io]\nreplace-with = 'local'\n[source.local]\nlocal-registry = 'registry'\n",
+    )
+    .unwrap();
+    let archive = tmpdir.path().join("snapshot.tar");
+    let cargo_home = tmpdir.path().join("home");
+
+    assert_cli::Assert::command(&[
+        get_command_path("edit").as_str(),
+        "edit",
+        "snapshot",
+        "export",
+        "log",
+        "--offline",
+        "-o",
+        archive.to_str().unwrap(),
+        &format!("--manifest-path={}", manifest),
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_HOME", &cargo_home))
+    .succeeds()
+    .and()
+    .stdout()
+    .contains("Exported 2 crates of file://")
+    .unwrap();
+
+    assert_cli::Assert::command(&[
+        get_command_path("edit").as_str(),
+        "edit",
+        "snapshot",
+        "import",
+        archive.to_str().unwrap(),
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_HOME", &cargo_home))
+    .succeeds()
+    .and()
+    .stdout()
+    .contains("Imported 2 crates of file://")
+    .unwrap();
+
+    let snapshots = cargo_home.join("cargo-edit").join("snapshots");
+    let installed = std::fs::read_dir(&snapshots)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    assert!(installed.join("index/cf/g-/cfg-if").is_file());
+}
+
+#[test]
+fn complete_lists_dependencies() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/rm/Cargo.toml.sample");
+
+    assert_cli::Assert::command(&[
+        get_command_path("edit").as_str(),
+        "edit",
+        "complete",
+        "dependencies",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .succeeds()
+    .and()
+    .stdout()
+    .contains("clippy\ndocopt\n")
+    .and()
+    .stdout()
+    .contains("semver\n")
+    .unwrap();
+}
@@ -0,0 +1,142 @@
+mod utils;
+use crate::utils::{
+    clone_out_test, copy_workspace_test, execute_command, get_command_path, get_toml,
+};
+
+/// Copy the `upgrade` lock file fixture next to a manifest.
+fn setup_lockfile(tmpdir: &tempfile::TempDir) {
+    std::fs::copy(
+        "tests/fixtures/upgrade/Cargo.lock",
+        tmpdir.path().join("Cargo.lock"),
+    )
+    .unwrap_or_else(|err| panic!("could not copy test lock file: {}", err));
+}
+
+#[test]
+fn freeze_to_lockfile() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/upgrade/Cargo.toml.lockfile_source");
+    setup_lockfile(&tmpdir);
+
+    execute_command(&["freeze"], &manifest);
+
+    let toml = get_toml(&manifest);
+    assert_eq!(toml["dependencies"]["rand"].as_str(), Some("=0.3.10"));
+    assert_eq!(
+        toml["dependencies"]["libc"]["version"].as_str(),
+        Some("=0.2.65")
+    );
+    assert_eq!(
+        toml["dependencies"]["libc"]["optional"].as_bool(),
+        Some(true)
+    );
+}
+
+#[test]
+fn freeze_specified_only() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/upgrade/Cargo.toml.lockfile_source");
+    setup_lockfile(&tmpdir);
+
+    execute_command(&["freeze", "rand"], &manifest);
+
+    let toml = get_toml(&manifest);
+    assert_eq!(toml["dependencies"]["rand"].as_str(), Some("=0.3.10"));
+    assert_eq!(
+        toml["dependencies"]["libc"]["version"].as_str(),
+        Some("0.2.28")
+    );
+}
+
+#[test]
+fn freeze_with_exclude() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/upgrade/Cargo.toml.lockfile_source");
+    setup_lockfile(&tmpdir);
+
+    execute_command(&["freeze", "--exclude", "rand"], &manifest);
+
+    let toml = get_toml(&manifest);
+    assert_eq!(toml["dependencies"]["rand"].as_str(), Some("0.3"));
+    assert_eq!(
+        toml["dependencies"]["libc"]["version"].as_str(),
+        Some("=0.2.65")
+    );
+}
+
+#[test]
+fn freeze_dry_run() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/upgrade/Cargo.toml.lockfile_source");
+    setup_lockfile(&tmpdir);
+
+    assert_cli::Assert::command(&[
+        get_command_path("freeze").as_str(),
+        "freeze",
+        "--dry-run",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .succeeds()
+    .and()
+    .stdout()
+    .contains("-rand = \"0.3\"\n")
+    .and()
+    .stdout()
+    .contains("+rand = \"=0.3.10\"\n")
+    .and()
+    .stdout()
+    .contains("Pinned 2 dependency requirement(s)")
+    .unwrap();
+
+    let toml = get_toml(&manifest);
+    assert_eq!(toml["dependencies"]["rand"].as_str(), Some("0.3"));
+}
+
+#[test]
+fn freeze_workspace() {
+    let (tmpdir, root_manifest, _workspace_manifests) = copy_workspace_test();
+
+    execute_command(&["freeze", "--workspace"], &root_manifest);
+
+    // The members one and two both request different, semver incompatible versions of rand.
+    let one = get_toml(tmpdir.path().join("one/Cargo.toml").to_str().unwrap());
+    assert_eq!(one["dependencies"]["rand"].as_str(), Some("=0.3.23"));
+    assert_eq!(one["dependencies"]["libc"].as_str(), Some("=0.2.62"));
+    // Path dependencies are left alone.
+    assert_eq!(
+        one["dependencies"]["three"]["path"].as_str(),
+        Some("../implicit/three")
+    );
+
+    let two = get_toml(tmpdir.path().join("two/Cargo.toml").to_str().unwrap());
+    assert_eq!(two["dependencies"]["rand"].as_str(), Some("=0.2.1"));
+}
+
+#[test]
+fn freeze_without_lockfile() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/upgrade/Cargo.toml.lockfile_source");
+
+    assert_cli::Assert::command(&[
+        get_command_path("freeze").as_str(),
+        "freeze",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .fails_with(1)
+    .and()
+    .stderr()
+    .contains("Run `cargo generate-lockfile` first.")
+    .unwrap();
+}
+
+#[test]
+fn freeze_records_reason() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/upgrade/Cargo.toml.lockfile_source");
+    setup_lockfile(&tmpdir);
+
+    execute_command(
+        &["freeze", "rand", "--reason", "RUSTSEC-2020-0001"],
+        &manifest,
+    );
+
+    let toml = get_toml(&manifest);
+    assert_eq!(toml["dependencies"]["rand"].as_str(), Some("=0.3.10"));
+    let pins = &toml["package"]["metadata"]["cargo-edit"]["pins"];
+    assert_eq!(pins["rand"].as_str(), Some("RUSTSEC-2020-0001"));
+    assert!(pins["libc"].is_none());
+}
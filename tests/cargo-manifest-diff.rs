@@ -0,0 +1,50 @@
+mod utils;
+use crate::utils::get_command_path;
+
+#[test]
+fn lists_changes() {
+    assert_cli::Assert::command(&[
+        get_command_path("manifest-diff").as_str(),
+        "manifest-diff",
+        "tests/fixtures/manifest-diff/Cargo.toml.old",
+        "tests/fixtures/manifest-diff/Cargo.toml.new",
+    ])
+    .succeeds()
+    .and()
+    .stdout()
+    .is("dependencies: removed docopt 0.8
+dependencies: serde 1.0 -> 1.0.118
+dependencies: serde feature `rc` enabled
+dev-dependencies: added pretty_assertions 0.6
+package.edition: \"2015\" -> \"2018\"")
+    .unwrap();
+}
+
+#[test]
+fn exit_code_reports_differences() {
+    assert_cli::Assert::command(&[
+        get_command_path("manifest-diff").as_str(),
+        "manifest-diff",
+        "--exit-code",
+        "tests/fixtures/manifest-diff/Cargo.toml.old",
+        "tests/fixtures/manifest-diff/Cargo.toml.new",
+    ])
+    .fails_with(3)
+    .and()
+    .stderr()
+    .contains("Found 5 change(s)")
+    .unwrap();
+
+    assert_cli::Assert::command(&[
+        get_command_path("manifest-diff").as_str(),
+        "manifest-diff",
+        "--exit-code",
+        "tests/fixtures/manifest-diff/Cargo.toml.old",
+        "tests/fixtures/manifest-diff/Cargo.toml.old",
+    ])
+    .succeeds()
+    .and()
+    .stdout()
+    .is("")
+    .unwrap();
+}
@@ -74,6 +74,28 @@ fn remove_section_after_removed_last_dependency() {
     assert!(toml["dev-dependencies"].is_none());
 }
 
+#[test]
+fn remove_feature_references_to_removed_dependency() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/rm/Cargo.toml.sample");
+    let mut contents = std::fs::read_to_string(&manifest).unwrap();
+    contents.push_str(
+        r#"
+[features]
+lint = ["dep:clippy", "toml?/preserve_order"]
+strict = ["clippy/strict", "lint"]
+"#,
+    );
+    std::fs::write(&manifest, contents).unwrap();
+
+    execute_command(&["rm", "clippy"], &manifest);
+
+    let toml = get_toml(&manifest);
+    assert_eq!(
+        toml["features"].as_table().unwrap().to_string(),
+        "lint = [\"toml?/preserve_order\"]\nstrict = [\"lint\"]\n"
+    );
+}
+
 // https://github.com/killercup/cargo-edit/issues/32
 #[test]
 fn issue_32() {
@@ -97,6 +119,23 @@ fn issue_32() {
     assert!(toml["dependencies"]["bar"].is_none());
 }
 
+#[test]
+fn rm_dependency_by_package_id_spec() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/rm/Cargo.toml.sample");
+
+    execute_command(&["add", "bar@1.0.7"], &manifest);
+    execute_command(
+        &[
+            "rm",
+            "registry+https://github.com/rust-lang/crates.io-index#bar@1.0.7",
+        ],
+        &manifest,
+    );
+
+    let toml = get_toml(&manifest);
+    assert!(toml["dependencies"]["bar"].is_none());
+}
+
 #[test]
 fn invalid_dependency() {
     let (_tmpdir, manifest) = clone_out_test("tests/fixtures/rm/Cargo.toml.sample");
@@ -161,6 +200,50 @@ fn invalid_dependency_in_section() {
     .unwrap();
 }
 
+#[test]
+fn warns_about_dependents() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/rm/Cargo.toml.sample");
+    let content = std::fs::read_to_string(&manifest).unwrap();
+    std::fs::write(
+        &manifest,
+        content.replace("cargo-rm-test-fixture", "test_popular"),
+    )
+    .unwrap();
+
+    assert_cli::Assert::command(&[
+        get_command_path("rm").as_str(),
+        "rm",
+        "docopt",
+        "--check-dependents",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .with_env([("CARGO_IS_TEST", "1")])
+    .succeeds()
+    .and()
+    .stderr()
+    .contains(
+        "WARN: `test_popular` is used by 3 crate(s) on crates.io, including dependent_a, \
+         dependent_b.",
+    )
+    .unwrap();
+
+    // Dev-dependencies are not part of the public API.
+    assert_cli::Assert::command(&[
+        get_command_path("rm").as_str(),
+        "rm",
+        "regex",
+        "--dev",
+        "--check-dependents",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .with_env([("CARGO_IS_TEST", "1")])
+    .succeeds()
+    .and()
+    .stderr()
+    .doesnt_contain("WARN")
+    .unwrap();
+}
+
 #[test]
 fn no_argument() {
     assert_cli::Assert::command(&[get_command_path("rm").as_str(), "rm"])
@@ -211,6 +294,27 @@ fn rm_prints_message() {
     .unwrap();
 }
 
+#[test]
+fn rm_renamed_dependency_by_package() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/upgrade/Cargo.toml.renamed_dep");
+
+    assert_cli::Assert::command(&[
+        get_command_path("rm").as_str(),
+        "rm",
+        "regex",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .succeeds()
+    .and()
+    .stdout()
+    .is("Removing rx (package regex) from dependencies")
+    .unwrap();
+
+    let toml = get_toml(&manifest);
+    assert!(toml["dependencies"]["rx"].is_none());
+    assert!(!toml["dependencies"]["te"].is_none());
+}
+
 #[test]
 fn rm_prints_messages_for_multiple() {
     let (_tmpdir, manifest) = clone_out_test("tests/fixtures/rm/Cargo.toml.sample");
@@ -229,6 +333,48 @@ fn rm_prints_messages_for_multiple() {
     .unwrap();
 }
 
+#[test]
+fn rm_reports_json() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/rm/Cargo.toml.sample");
+
+    assert_cli::Assert::command(&[
+        get_command_path("rm").as_str(),
+        "rm",
+        "semver",
+        "--output=json",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .succeeds()
+    .and()
+    .stdout()
+    .contains(
+        r#"{"kind":"dependency-removed","name":"semver","table":"dependencies","version":"0.1"}"#,
+    )
+    .and()
+    .stdout()
+    .doesnt_contain("Removing")
+    .unwrap();
+    assert!(get_toml(&manifest)["dependencies"]["semver"].is_none());
+}
+
+#[test]
+fn rm_prints_completions() {
+    assert_cli::Assert::command(&[
+        get_command_path("rm").as_str(),
+        "rm",
+        "--completions",
+        "bash",
+    ])
+    .succeeds()
+    .and()
+    .stdout()
+    .contains("complete -F _cargo_edit_rm_complete -o bashdefault -o default cargo")
+    .and()
+    .stdout()
+    .contains("$(cargo edit complete dependencies 2>/dev/null)")
+    .unwrap();
+}
+
 #[test]
 fn rm_dependency_from_workspace_member() {
     let (tmpdir, _root_manifest, workspace_manifests) = copy_workspace_test();
@@ -242,3 +388,141 @@ fn rm_dependency_from_workspace_member() {
 
     assert!(one["dependencies"]["libc"].as_str().is_none());
 }
+
+#[test]
+fn rm_target_dependency() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/rm/Cargo.toml.sample");
+    execute_command(
+        &["add", "--target", "cfg(all(unix, not(windows)))", "docopt"],
+        &manifest,
+    );
+
+    execute_command(
+        &[
+            "rm",
+            "docopt",
+            "--target",
+            "cfg(all(all(unix), not(not(not(windows)))))",
+        ],
+        &manifest,
+    );
+    let toml = get_toml(&manifest);
+    assert!(toml["dependencies"]["docopt"].as_str().is_some());
+    assert!(toml["target"]["cfg(all(unix, not(windows)))"]["dependencies"]["docopt"].is_none());
+}
+
+#[test]
+fn json_request_reports_errors() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/rm/Cargo.toml.sample");
+    let request = serde_json::json!({"crates": ["missing"], "manifest_path": manifest});
+
+    assert_cli::Assert::command(&[get_command_path("rm").as_str(), "rm", "--json-request"])
+        .stdin(request.to_string())
+        .fails_with(1)
+        .and()
+        .stdout()
+        .is(r#"{"command":"rm","error":{"causes":[],"exit_code":1,"message":"The dependency `missing` could not be found in `dependencies`."}}"#)
+        .and()
+        .stderr()
+        .is("")
+        .unwrap();
+}
+
+#[test]
+fn rm_from_virtual_manifest_asks_for_a_package() {
+    let (_tmpdir, root_manifest, _workspace_manifests) = copy_workspace_test();
+
+    assert_cli::Assert::command(&[
+        get_command_path("rm").as_str(),
+        "rm",
+        "libc",
+        "--manifest-path",
+        &root_manifest,
+    ])
+    .fails_with(1)
+    .and()
+    .stderr()
+    .contains(
+        "Found virtual manifest, but this command requires running against an actual package in \
+         this workspace. Pick one with `--package`: four, one, three, two.",
+    )
+    .unwrap();
+}
+
+#[test]
+fn rm_from_every_workspace_member() {
+    let (_tmpdir, root_manifest, workspace_manifests) = copy_workspace_test();
+
+    assert_cli::Assert::command(&[
+        get_command_path("rm").as_str(),
+        "rm",
+        "rand",
+        "--workspace",
+        "--manifest-path",
+        &root_manifest,
+    ])
+    .succeeds()
+    .and()
+    .stdout()
+    .contains("Removing rand from dependencies of `one`")
+    .and()
+    .stdout()
+    .contains("Removing rand from dependencies of `two`")
+    .unwrap();
+
+    for manifest in &workspace_manifests {
+        let toml = get_toml(manifest);
+        assert!(toml["dependencies"]["rand"].is_none());
+        assert!(toml["dependencies"]["libc"].as_str().is_some());
+    }
+
+    assert_cli::Assert::command(&[
+        get_command_path("rm").as_str(),
+        "rm",
+        "rand",
+        "--workspace",
+        "--manifest-path",
+        &root_manifest,
+    ])
+    .fails_with(1)
+    .and()
+    .stderr()
+    .contains("The dependency `rand` could not be found in `dependencies` of any workspace member.")
+    .unwrap();
+}
+
+#[test]
+fn rm_from_workspace_dependencies_once_unused() {
+    let (_tmpdir, root_manifest, workspace_manifests) = copy_workspace_test();
+    let root = std::fs::read_to_string(&root_manifest).unwrap();
+    std::fs::write(
+        &root_manifest,
+        format!(
+            "{}\n\n[workspace.dependencies]\nlibc = \"0.2.28\"\nrand = \"0.3\"\n",
+            root
+        ),
+    )
+    .unwrap();
+    for manifest in &workspace_manifests {
+        let toml = std::fs::read_to_string(manifest).unwrap();
+        let mut toml = toml
+            .replace("libc = \"0.2.28\"", "libc = { workspace = true }")
+            .replace("rand = \"0.3\"", "rand = { workspace = true }");
+        if toml.contains("name = \"three\"") {
+            toml.push_str("\n[dev-dependencies]\nlibc = { workspace = true }\n");
+        }
+        std::fs::write(manifest, toml).unwrap();
+    }
+
+    execute_command(&["rm", "--workspace", "rand", "libc"], &root_manifest);
+
+    let root = get_toml(&root_manifest);
+    assert!(root["workspace"]["dependencies"]["rand"].is_none());
+    // Still inherited by the dev-dependencies of `three`.
+    assert!(root["workspace"]["dependencies"]["libc"].as_str().is_some());
+    for manifest in &workspace_manifests {
+        let toml = get_toml(manifest);
+        assert!(toml["dependencies"]["rand"].is_none());
+        assert!(toml["dependencies"]["libc"].is_none());
+    }
+}
@@ -0,0 +1,100 @@
+mod utils;
+use crate::utils::{clone_out_test, get_command_path};
+
+#[test]
+fn stale_sorted_by_risk() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/stale/Cargo.toml.sample");
+
+    let output = std::process::Command::new(get_command_path("stale"))
+        .args(["stale", &format!("--manifest-path={}", manifest)])
+        .env("CARGO_IS_TEST", "1")
+        .output()
+        .expect("failed to run cargo-stale");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+
+    assert_eq!(lines.len(), 4, "{}", stdout);
+    assert!(lines[0].contains("test_unmaintained v0.1.0 (released 2015-05-15): unmaintained (RUSTSEC-0000-0000), no release in"));
+    assert!(lines[1].contains(
+        "test_archived v0.1.0 (released 2020-01-01): repository archived (https://github.com/test/archived)"
+    ));
+    assert!(lines[2].contains("test_outdated v0.1.0 (released 2015-05-15): no release in"));
+    assert_eq!(lines[3], "Found 3 stale dependencies out of 4");
+}
+
+#[test]
+fn stale_with_skipped_checks() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/stale/Cargo.toml.sample");
+
+    assert_cli::Assert::command(&[
+        get_command_path("stale").as_str(),
+        "stale",
+        "--skip-advisories",
+        "--skip-repository",
+        "--exclude",
+        "test_outdated",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .with_env([("CARGO_IS_TEST", "1")])
+    .succeeds()
+    .and()
+    .stdout()
+    .contains("test_unmaintained v0.1.0 (released 2015-05-15): no release in")
+    .and()
+    .stdout()
+    .contains("test_archived v0.1.0 (released 2020-01-01): no release in")
+    .and()
+    .stdout()
+    .doesnt_contain("repository archived")
+    .and()
+    .stdout()
+    .contains("Found 2 stale dependencies out of 3")
+    .unwrap();
+}
+
+#[test]
+fn stale_max_age() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/stale/Cargo.toml.sample");
+
+    // Nothing is older than 100000 days, so only the archived and unmaintained crates are listed.
+    assert_cli::Assert::command(&[
+        get_command_path("stale").as_str(),
+        "stale",
+        "--max-age=100000",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .with_env([("CARGO_IS_TEST", "1")])
+    .succeeds()
+    .and()
+    .stdout()
+    .doesnt_contain("no release in")
+    .and()
+    .stdout()
+    .contains("Found 2 stale dependencies out of 4")
+    .unwrap();
+}
+
+#[test]
+fn stale_offline_without_cached_data() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/stale/Cargo.toml.sample");
+
+    // Without the test stubs, nothing can be looked up without the network.
+    let output = std::process::Command::new(get_command_path("stale"))
+        .args([
+            "stale",
+            "--offline",
+            &format!("--manifest-path={}", manifest),
+        ])
+        .env("CARGO_HOME", tmpdir.path().join("cargo-home"))
+        .env_remove("CARGO_IS_TEST")
+        .output()
+        .expect("failed to run cargo-stale");
+    assert_eq!(output.status.code(), Some(5));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("as the network may not be accessed"),
+        "{}",
+        stderr
+    );
+}
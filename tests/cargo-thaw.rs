@@ -0,0 +1,105 @@
+mod utils;
+use crate::utils::{clone_out_test, execute_command, get_command_path, get_toml};
+
+#[test]
+fn thaw_all_pins() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/thaw/Cargo.toml.sample");
+
+    execute_command(&["thaw"], &manifest);
+
+    let toml = get_toml(&manifest);
+    assert_eq!(toml["dependencies"]["rand"].as_str(), Some("0.3.23"));
+    assert_eq!(
+        toml["dependencies"]["libc"]["version"].as_str(),
+        Some("0.2.65")
+    );
+    assert_eq!(
+        toml["dependencies"]["libc"]["optional"].as_bool(),
+        Some(true)
+    );
+    assert_eq!(toml["dependencies"]["regex"].as_str(), Some("1.3"));
+    assert_eq!(toml["dependencies"]["toml"].as_str(), Some("0.5.6"));
+    assert_eq!(toml["dev-dependencies"]["serde"].as_str(), Some("1.0.116"));
+    // The reason no longer applies once the pin is gone.
+    assert!(toml["package"]["metadata"]["cargo-edit"]["pins"].is_none());
+}
+
+#[test]
+fn thaw_with_precision() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/thaw/Cargo.toml.sample");
+
+    execute_command(
+        &["thaw", "--precision", "minor", "rand", "serde"],
+        &manifest,
+    );
+
+    let toml = get_toml(&manifest);
+    assert_eq!(toml["dependencies"]["rand"].as_str(), Some("0.3"));
+    assert_eq!(toml["dev-dependencies"]["serde"].as_str(), Some("1.0"));
+    assert_eq!(
+        toml["dependencies"]["libc"]["version"].as_str(),
+        Some("=0.2.65")
+    );
+}
+
+#[test]
+fn thaw_keeps_prerelease_versions() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/thaw/Cargo.toml.sample");
+    let content = std::fs::read_to_string(&manifest).unwrap().replace(
+        "[dependencies]\n",
+        "[dependencies]\nnom = \"=7.0.0-alpha.3\"\n",
+    );
+    std::fs::write(&manifest, content).unwrap();
+
+    execute_command(&["thaw", "--precision", "major", "nom", "rand"], &manifest);
+
+    let toml = get_toml(&manifest);
+    // `7` would no longer match the locked pre-release.
+    assert_eq!(toml["dependencies"]["nom"].as_str(), Some("7.0.0-alpha.3"));
+    assert_eq!(toml["dependencies"]["rand"].as_str(), Some("0"));
+}
+
+#[test]
+fn thaw_keeps_reasoned_pins() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/thaw/Cargo.toml.sample");
+
+    execute_command(&["thaw", "--keep-reasoned", "--exclude", "libc"], &manifest);
+
+    let toml = get_toml(&manifest);
+    assert_eq!(toml["dependencies"]["rand"].as_str(), Some("0.3.23"));
+    assert_eq!(
+        toml["dependencies"]["libc"]["version"].as_str(),
+        Some("=0.2.65")
+    );
+    assert_eq!(toml["dev-dependencies"]["serde"].as_str(), Some("=1.0.116"));
+    assert_eq!(
+        toml["package"]["metadata"]["cargo-edit"]["pins"]["serde"].as_str(),
+        Some("Needs the same version as the build server")
+    );
+}
+
+#[test]
+fn thaw_dry_run() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/thaw/Cargo.toml.sample");
+
+    assert_cli::Assert::command(&[
+        get_command_path("thaw").as_str(),
+        "thaw",
+        "--dry-run",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .succeeds()
+    .and()
+    .stdout()
+    .contains("-rand = \"=0.3.23\"\n")
+    .and()
+    .stdout()
+    .contains("+rand = \"0.3.23\"\n")
+    .and()
+    .stdout()
+    .contains("Relaxed 4 dependency requirement(s)")
+    .unwrap();
+
+    let toml = get_toml(&manifest);
+    assert_eq!(toml["dependencies"]["rand"].as_str(), Some("=0.3.23"));
+}
@@ -2,6 +2,8 @@
 extern crate pretty_assertions;
 
 mod utils;
+#[cfg(feature = "fetch")]
+use crate::utils::init_git_repo;
 use crate::utils::{
     clone_out_test, copy_workspace_test, execute_command, execute_command_for_pkg,
     execute_command_in_dir, get_command_path, get_toml, setup_alt_registry_config,
@@ -20,6 +22,19 @@ fn upgrade_as_expected() {
     assert_eq!(target.to_string(), upgraded.to_string());
 }
 
+#[test]
+fn upgrade_frozen_as_expected() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/upgrade/Cargo.toml.source");
+
+    // The test stubs need no network, and `cargo metadata` is run with `--offline --locked`.
+    execute_command(&["upgrade", "--frozen"], &manifest);
+
+    let upgraded = get_toml(&manifest);
+    let target = get_toml("tests/fixtures/upgrade/Cargo.toml.target");
+    assert_eq!(target.to_string(), upgraded.to_string());
+    assert!(!tmpdir.path().join("Cargo.lock").exists());
+}
+
 #[test]
 fn upgrade_all() {
     let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
@@ -128,6 +143,87 @@ fn upgrade_all_allow_prerelease_dry_run() {
     );
 }
 
+#[test]
+fn upgrade_dry_run_reports_markdown() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    execute_command(&["add", "docopt", "--vers", "0.8"], &manifest);
+
+    assert_cli::Assert::command(&[
+        get_command_path("upgrade").as_str(),
+        "upgrade",
+        "--dry-run",
+        "--output",
+        "markdown",
+        "--manifest-path",
+        &manifest,
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .succeeds()
+    .and()
+    .stdout()
+    .contains("| dependencies | docopt | 0.8 → docopt--CURRENT_VERSION_TEST |")
+    .and()
+    .stdout()
+    .contains("_Dry run: nothing was written._")
+    .and()
+    .stdout()
+    .doesnt_contain("Upgrading")
+    .unwrap();
+
+    // Verify that `docopt` has not been updated.
+    assert_eq!(
+        get_toml(&manifest)["dependencies"]["docopt"].as_str(),
+        Some("0.8")
+    );
+}
+
+#[test]
+fn upgrade_quiet_prints_nothing() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    execute_command(&["add", "docopt", "--vers", "0.8"], &manifest);
+
+    assert_cli::Assert::command(&[
+        get_command_path("upgrade").as_str(),
+        "upgrade",
+        "--dry-run",
+        "--quiet",
+        "--manifest-path",
+        &manifest,
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .succeeds()
+    .and()
+    .stdout()
+    .is("")
+    .and()
+    .stderr()
+    .is("")
+    .unwrap();
+}
+
+#[test]
+fn upgrade_verbose_conflicts_with_quiet() {
+    assert_cli::Assert::command(&[get_command_path("upgrade").as_str(), "upgrade", "-q", "-v"])
+        .fails()
+        .and()
+        .stderr()
+        .contains("The argument '--quiet' cannot be used with '--verbose'")
+        .unwrap();
+}
+
+#[test]
+fn upgrade_prints_man_page() {
+    assert_cli::Assert::command(&[get_command_path("upgrade").as_str(), "upgrade", "--man"])
+        .succeeds()
+        .and()
+        .stdout()
+        .contains(".TH \"CARGO-UPGRADE\" \"1\"")
+        .and()
+        .stdout()
+        .contains(".TP\n\\fB\\-\\-dry\\-run\\fR\nPrint changes to be made without making them\n")
+        .unwrap();
+}
+
 #[test]
 fn upgrade_specified_only() {
     let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
@@ -274,6 +370,34 @@ fn upgrade_renamed_dependency_table_specified_only() {
     assert_eq!(dep["version"].as_str(), Some("regex--CURRENT_VERSION_TEST"));
 }
 
+#[test]
+fn upgrade_renamed_dependency_by_key() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/upgrade/Cargo.toml.renamed_dep");
+
+    assert_cli::Assert::command(&[
+        get_command_path("upgrade").as_str(),
+        "upgrade",
+        "rx",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .succeeds()
+    .and()
+    .stdout()
+    .contains("rx (package regex)  normal  0.2  regex--CURRENT_VERSION_TEST")
+    .unwrap();
+
+    let toml = get_toml(&manifest);
+    assert_eq!(
+        toml["dependencies"]["rx"]["version"].as_str(),
+        Some("regex--CURRENT_VERSION_TEST")
+    );
+    assert_eq!(
+        toml["dependencies"]["te"]["version"].as_str(),
+        Some("0.1.5")
+    );
+}
+
 #[test]
 fn upgrade_alt_registry_dependency_all() {
     let (tmpdir, manifest) = clone_out_test("tests/fixtures/upgrade/Cargo.toml.alt_registry");
@@ -447,20 +571,23 @@ fn invalid_manifest() {
         "--manifest-path",
         &manifest,
     ])
-    .with_env(&[("CARGO_IS_TEST", "1")])
-    .fails_with(1)
+    .with_env([("CARGO_IS_TEST", "1")])
+    .fails_with(6)
     .and()
     .stderr()
-    .is("\
-Command failed due to unhandled error: Unable to parse Cargo.toml
+    .is(format!(
+        "\
+Command failed due to unhandled error: Unable to parse {}:1:6
 
-Caused by: Manifest not valid TOML
 Caused by: TOML parse error at line 1, column 6
   |
 1 | This is clearly not a valid Cargo.toml.
   |      ^
 Unexpected `i`
-Expected `=`")
+Expected `=`",
+        manifest
+    )
+    .as_str())
     .unwrap();
 }
 
@@ -475,7 +602,7 @@ fn invalid_root_manifest_all() {
         "--manifest-path",
         &manifest,
     ])
-    .with_env(&[("CARGO_IS_TEST", "1")])
+    .with_env([("CARGO_IS_TEST", "1")])
     .fails_with(1)
     .and()
     .stderr()
@@ -494,7 +621,7 @@ fn invalid_root_manifest_workspace() {
         "--manifest-path",
         &manifest,
     ])
-    .with_env(&[("CARGO_IS_TEST", "1")])
+    .with_env([("CARGO_IS_TEST", "1")])
     .fails_with(1)
     .and()
     .stderr()
@@ -510,7 +637,7 @@ fn unknown_flags() {
         "foo",
         "--flag",
     ])
-    .with_env(&[("CARGO_IS_TEST", "1")])
+    .with_env([("CARGO_IS_TEST", "1")])
     .fails_with(1)
     .and()
     .stderr()
@@ -579,6 +706,25 @@ fn upgrade_workspace_to_lockfile_workspace() {
     assert_eq!(two_target.to_string(), two_upgraded.to_string());
 }
 
+#[test]
+fn upgrade_warns_about_yanked_versions() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    execute_command(&["add", "test_yanked", "--vers", "0.1"], &manifest);
+    execute_command(&["add", "docopt", "--vers", "0.8"], &manifest);
+
+    assert_cli::Assert::command(&[
+        get_command_path("upgrade").as_str(),
+        "upgrade",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .succeeds()
+    .and()
+    .stderr()
+    .is("WARN: The current version of `test_yanked` (v0.1.0) has been yanked")
+    .unwrap();
+}
+
 #[test]
 #[cfg(feature = "test-external-apis")]
 fn upgrade_prints_messages() {
@@ -593,6 +739,541 @@ fn upgrade_prints_messages() {
     .succeeds()
     .and()
     .stdout()
-    .contains("docopt v0.8 -> v")
+    .contains("-docopt = \"0.8\"\n+docopt = \"")
+    .unwrap();
+}
+
+#[test]
+fn upgrade_with_exclude_from_environment() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    execute_command(&["add", "docopt", "--vers", "0.8"], &manifest);
+
+    assert_cli::Assert::command(&[
+        get_command_path("upgrade").as_str(),
+        "upgrade",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .with_env(
+        assert_cli::Environment::inherit()
+            .insert("CARGO_IS_TEST", "1")
+            .insert("CARGO_EDIT_UPGRADE_EXCLUDE", r#"["docopt"]"#),
+    )
+    .succeeds()
+    .unwrap();
+
+    assert_eq!(
+        get_toml(&manifest)["dependencies"]["docopt"].as_str(),
+        Some("0.8")
+    );
+}
+
+#[test]
+fn upgrade_leaves_pinned_crates_unless_named() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    execute_command(&["add", "docopt", "--vers", "0.8"], &manifest);
+    execute_command(&["add", "test_breaking", "--vers", "0.1"], &manifest);
+    let mut contents = std::fs::read_to_string(&manifest).unwrap();
+    contents.push_str("\n[workspace.metadata.cargo-edit]\npinned = [\"docopt\"]\n");
+    std::fs::write(&manifest, contents).unwrap();
+
+    execute_command(&["upgrade"], &manifest);
+    let toml = get_toml(&manifest);
+    assert_eq!(toml["dependencies"]["docopt"].as_str(), Some("0.8"));
+    assert_eq!(
+        toml["dependencies"]["test_breaking"].as_str(),
+        Some("0.2.0")
+    );
+
+    execute_command(&["upgrade", "docopt"], &manifest);
+    assert_eq!(
+        get_toml(&manifest)["dependencies"]["docopt"].as_str(),
+        Some("docopt--CURRENT_VERSION_TEST")
+    );
+}
+
+#[test]
+fn upgrade_downgrades_with_yes() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    execute_command(&["add", "docopt", "--vers", "0.8"], &manifest);
+
+    execute_command(&["upgrade", "docopt@0.6", "--yes"], &manifest);
+
+    assert_eq!(
+        get_toml(&manifest)["dependencies"]["docopt"].as_str(),
+        Some("0.6")
+    );
+}
+
+#[test]
+fn upgrade_exit_code_tells_pending_upgrades_apart() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    execute_command(&["add", "docopt", "--vers", "0.8"], &manifest);
+
+    assert_cli::Assert::command(&[
+        get_command_path("upgrade").as_str(),
+        "upgrade",
+        "--dry-run",
+        "--exit-code",
+        "--manifest-path",
+        &manifest,
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .fails_with(3)
+    .unwrap();
+
+    // `docopt` is already at the requested version.
+    assert_cli::Assert::command(&[
+        get_command_path("upgrade").as_str(),
+        "upgrade",
+        "docopt@0.8",
+        "--exit-code",
+        "--manifest-path",
+        &manifest,
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .fails_with(2)
+    .unwrap();
+}
+
+#[test]
+fn upgrade_prints_summary_columns() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    execute_command(&["add", "docopt", "--vers", "0.8"], &manifest);
+
+    assert_cli::Assert::command(&[
+        get_command_path("upgrade").as_str(),
+        "upgrade",
+        "docopt@0.6",
+        "--dry-run",
+        "--columns",
+        "name,old,new,note",
+        "--manifest-path",
+        &manifest,
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .succeeds()
+    .and()
+    .stdout()
+    .contains("name    old  new  note\ndocopt  0.8  0.6  downgrade\n")
+    .unwrap();
+}
+
+#[test]
+fn upgrade_offline_without_an_index_is_a_network_failure() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    execute_command(&["add", "docopt", "--vers", "0.8"], &manifest);
+
+    assert_cli::Assert::command(&[
+        get_command_path("upgrade").as_str(),
+        "upgrade",
+        "--offline",
+        "--manifest-path",
+        &manifest,
+    ])
+    .with_env(
+        assert_cli::Environment::inherit().insert("CARGO_HOME", tmpdir.path().join("cargo-home")),
+    )
+    .fails_with(5)
+    .and()
+    .stderr()
+    .contains("as the network may not be accessed")
+    .unwrap();
+}
+
+#[test]
+fn upgrade_explains_passed_over_versions() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    execute_command(&["add", "log", "--vers", "0.3"], &manifest);
+    execute_command(&["add", "tokio", "--vers", "1.0"], &manifest);
+    let index = tmpdir.path().join("registry").join("index");
+    std::fs::create_dir_all(index.join("3").join("l")).unwrap();
+    std::fs::write(
+        index.join("3").join("l").join("log"),
+        r#"{"name":"log","vers":"0.4.0","deps":[],"cksum":"","features":{},"yanked":false}
+{"name":"log","vers":"0.4.1","deps":[],"cksum":"","features":{},"yanked":true}
+{"name":"log","vers":"0.5.0-alpha.1","deps":[],"cksum":"","features":{},"yanked":false}
+"#,
+    )
+    .unwrap();
+    std::fs::create_dir(tmpdir.path().join(".cargo")).unwrap();
+    std::fs::write(
+        tmpdir.path().join(".cargo").join("config.toml"),
+        "[source.crates-io]\nreplace-with = 'local'\n[source.local]\nlocal-registry = 'registry'\n",
+    )
+    .unwrap();
+
+    assert_cli::Assert::command(&[
+        get_command_path("upgrade").as_str(),
+        "upgrade",
+        "--explain",
+        "--dry-run",
+        "--offline",
+        "--exclude",
+        "tokio",
+        "--manifest-path",
+        &manifest,
+    ])
+    .succeeds()
+    .and()
+    .stdout()
+    .contains(
+        "log: 0.4.0, the newest version allowed\n    \
+         0.5.0-alpha.1  pre-release\n    \
+         0.4.1          yanked\n\
+         tokio: left alone, excluded\n",
+    )
+    .unwrap();
+}
+
+#[test]
+fn upgrade_reports_dependency_statuses_as_json() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    execute_command(&["add", "log", "--vers", "0.3"], &manifest);
+    execute_command(&["add", "rand", "--vers", "0.4.0"], &manifest);
+    execute_command(&["add", "tokio", "--vers", "1.0"], &manifest);
+    let index = tmpdir.path().join("registry").join("index");
+    std::fs::create_dir_all(index.join("3").join("l")).unwrap();
+    std::fs::write(
+        index.join("3").join("l").join("log"),
+        r#"{"name":"log","vers":"0.3.9","deps":[],"cksum":"","features":{},"yanked":false}
+{"name":"log","vers":"0.4.0","deps":[],"cksum":"","features":{},"yanked":false}
+{"name":"log","vers":"0.4.1","deps":[],"cksum":"","features":{},"yanked":true}
+"#,
+    )
+    .unwrap();
+    std::fs::create_dir_all(index.join("ra").join("nd")).unwrap();
+    std::fs::write(
+        index.join("ra").join("nd").join("rand"),
+        r#"{"name":"rand","vers":"0.4.0","deps":[],"cksum":"","features":{},"yanked":false}
+{"name":"rand","vers":"0.5.0","deps":[],"cksum":"","features":{},"yanked":true}
+"#,
+    )
+    .unwrap();
+    std::fs::create_dir(tmpdir.path().join(".cargo")).unwrap();
+    std::fs::write(
+        tmpdir.path().join(".cargo").join("config.toml"),
+        "[source.crates-io]\nreplace-with = 'local'\n[source.local]\nlocal-registry = 'registry'\n",
+    )
+    .unwrap();
+
+    let output = std::process::Command::new(get_command_path("upgrade"))
+        .args([
+            "upgrade",
+            "--dry-run",
+            "--offline",
+            "--output",
+            "json",
+            "--exclude",
+            "tokio",
+            "--manifest-path",
+            &manifest,
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let dependencies = &report["manifests"][0]["dependencies"];
+    let status = |name: &str| {
+        dependencies
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|dep| dep["name"] == name)
+            .unwrap()
+            .clone()
+    };
+    assert_eq!(
+        status("log"),
+        serde_json::json!({
+            "table": "dependencies", "name": "log", "package": "log", "current": "0.3",
+            "new": "0.4.0", "latest_compatible": "0.3.9", "latest": "0.4.0", "skipped": null,
+        })
+    );
+    assert_eq!(status("rand")["new"], "0.4.0");
+    assert_eq!(status("rand")["latest"], "0.4.0");
+    assert_eq!(status("rand")["skipped"], "yanked");
+    assert_eq!(status("tokio")["latest"], serde_json::Value::Null);
+    assert_eq!(status("tokio")["skipped"], "excluded");
+}
+
+#[test]
+fn upgrade_reports_every_requirement_as_json() {
+    let (tmpdir, root_manifest, _workspace_manifests) = copy_workspace_test();
+    let index = tmpdir.path().join("registry").join("index");
+    std::fs::create_dir_all(index.join("ra").join("nd")).unwrap();
+    std::fs::write(
+        index.join("ra").join("nd").join("rand"),
+        r#"{"name":"rand","vers":"0.2.1","deps":[],"cksum":"","features":{},"yanked":false}
+{"name":"rand","vers":"0.2.9","deps":[],"cksum":"","features":{},"yanked":false}
+{"name":"rand","vers":"0.3.23","deps":[],"cksum":"","features":{},"yanked":false}
+{"name":"rand","vers":"0.3.30","deps":[],"cksum":"","features":{},"yanked":false}
+{"name":"rand","vers":"0.4.0","deps":[],"cksum":"","features":{},"yanked":false}
+"#,
+    )
     .unwrap();
+    std::fs::create_dir(tmpdir.path().join(".cargo")).unwrap();
+    std::fs::write(
+        tmpdir.path().join(".cargo").join("config.toml"),
+        "[source.crates-io]\nreplace-with = 'local'\n[source.local]\nlocal-registry = 'registry'\n",
+    )
+    .unwrap();
+
+    // `one` requires `rand = "0.3"` and `two` `rand = "0.2"`, with or without a Rust version.
+    for extra in [&[][..], &["--rust-version", "1.70"][..]] {
+        let output = std::process::Command::new(get_command_path("upgrade"))
+            .args([
+                "upgrade",
+                "--workspace",
+                "--dry-run",
+                "--offline",
+                "--output",
+                "json",
+                "--exclude",
+                "libc",
+                "--manifest-path",
+                &root_manifest,
+            ])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let rand = |package: &str| {
+            let manifest = report["manifests"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|manifest| manifest["package"] == package)
+                .unwrap();
+            manifest["dependencies"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|dep| dep["name"] == "rand")
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(rand("one")["current"], "0.3");
+        assert_eq!(rand("one")["latest_compatible"], "0.3.30");
+        assert_eq!(rand("one")["latest"], "0.4.0");
+        assert_eq!(rand("two")["current"], "0.2");
+        assert_eq!(rand("two")["latest_compatible"], "0.2.9");
+        assert_eq!(rand("two")["latest"], "0.4.0");
+    }
+}
+
+#[test]
+fn upgrade_keeps_supporting_rust_version() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    let content = std::fs::read_to_string(&manifest).unwrap();
+    std::fs::write(
+        &manifest,
+        content.replace(
+            "version = \"0.0.0\"",
+            "version = \"0.0.0\"\nrust-version = \"1.60\"",
+        ),
+    )
+    .unwrap();
+    execute_command(&["add", "log", "--vers", "0.3"], &manifest);
+    execute_command(&["add", "rand", "--vers", "0.4.0"], &manifest);
+    let index = tmpdir.path().join("registry").join("index");
+    std::fs::create_dir_all(index.join("3").join("l")).unwrap();
+    std::fs::write(
+        index.join("3").join("l").join("log"),
+        r#"{"name":"log","vers":"0.3.9","deps":[],"cksum":"","features":{},"yanked":false,"rust_version":"1.40"}
+{"name":"log","vers":"0.4.0","deps":[],"cksum":"","features":{},"yanked":false,"rust_version":"1.56"}
+{"name":"log","vers":"0.4.1","deps":[],"cksum":"","features":{},"yanked":false,"rust_version":"1.70"}
+"#,
+    )
+    .unwrap();
+    std::fs::create_dir_all(index.join("ra").join("nd")).unwrap();
+    std::fs::write(
+        index.join("ra").join("nd").join("rand"),
+        r#"{"name":"rand","vers":"0.4.0","deps":[],"cksum":"","features":{},"yanked":false}
+{"name":"rand","vers":"0.5.0","deps":[],"cksum":"","features":{},"yanked":false,"rust_version":"1.80"}
+"#,
+    )
+    .unwrap();
+    std::fs::create_dir(tmpdir.path().join(".cargo")).unwrap();
+    std::fs::write(
+        tmpdir.path().join(".cargo").join("config.toml"),
+        "[source.crates-io]\nreplace-with = 'local'\n[source.local]\nlocal-registry = 'registry'\n",
+    )
+    .unwrap();
+
+    let upgrade = |rust_version: &str, strict: bool| {
+        let mut command = std::process::Command::new(get_command_path("upgrade"));
+        command.args([
+            "upgrade",
+            "--dry-run",
+            "--offline",
+            "--output",
+            "json",
+            "--rust-version",
+            rust_version,
+            "--manifest-path",
+            &manifest,
+        ]);
+        if strict {
+            command.arg("--msrv-strict");
+        }
+        let output = command.output().unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let dependencies = report["manifests"][0]["dependencies"].clone();
+        let status = move |name: &str| {
+            dependencies
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|dep| dep["name"] == name)
+                .unwrap()
+                .clone()
+        };
+        (status, String::from_utf8(output.stderr).unwrap())
+    };
+
+    // The `rust-version` of the package caps log at 0.4.0, and leaves rand alone.
+    let (status, _) = upgrade("auto", false);
+    assert_eq!(status("log")["new"], "0.4.0");
+    assert_eq!(status("log")["latest"], "0.4.1");
+    assert_eq!(status("log")["skipped"], serde_json::Value::Null);
+    assert_eq!(status("rand")["new"], "0.4.0");
+    assert_eq!(status("rand")["skipped"], "rust-version");
+
+    // Versions which don't declare their Rust version don't count with `--msrv-strict`.
+    let (status, stderr) = upgrade("1.50", true);
+    assert_eq!(status("log")["new"], "0.3.9");
+    assert_eq!(status("rand")["skipped"], "rust-version");
+    assert!(
+        stderr.contains("Not upgrading `rand`, as no newer version supports Rust 1.50"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn upgrade_workspace_dependencies_of_virtual_manifest() {
+    let (tmpdir, root_manifest, _workspace_manifests) = copy_workspace_test();
+    let root = std::fs::read_to_string(&root_manifest).unwrap();
+    std::fs::write(
+        &root_manifest,
+        format!("{}\n\n[workspace.dependencies]\nlibc = \"0.2.28\"\n", root),
+    )
+    .unwrap();
+    let one_manifest = tmpdir.path().join("one/Cargo.toml");
+    let one = std::fs::read_to_string(&one_manifest).unwrap();
+    std::fs::write(
+        &one_manifest,
+        one.replace("libc = \"0.2.28\"", "libc = { workspace = true }"),
+    )
+    .unwrap();
+
+    execute_command(&["upgrade", "--workspace"], &root_manifest);
+
+    assert_eq!(
+        get_toml(&root_manifest)["workspace"]["dependencies"]["libc"].as_str(),
+        Some("libc--CURRENT_VERSION_TEST")
+    );
+    // The member still inherits the requirement.
+    let one = get_toml(one_manifest.to_str().unwrap());
+    assert_eq!(
+        one["dependencies"]["libc"]["workspace"].as_bool(),
+        Some(true)
+    );
+    assert_eq!(
+        one["dependencies"]["rand"].as_str(),
+        Some("rand--CURRENT_VERSION_TEST")
+    );
+}
+
+#[test]
+fn upgrade_leaves_patched_dependencies_alone() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    execute_command(&["add", "docopt", "--vers", "0.8"], &manifest);
+    let patched = tmpdir.path().join("docopt");
+    std::fs::create_dir_all(patched.join("src")).unwrap();
+    std::fs::write(
+        patched.join("Cargo.toml"),
+        "[package]\nname = \"docopt\"\nversion = \"0.8.3\"\n",
+    )
+    .unwrap();
+    std::fs::write(patched.join("src").join("lib.rs"), "").unwrap();
+    let toml = std::fs::read_to_string(&manifest).unwrap();
+    std::fs::write(
+        &manifest,
+        format!(
+            "{}\n[patch.crates-io]\ndocopt = {{ path = \"docopt\" }}\n",
+            toml
+        ),
+    )
+    .unwrap();
+
+    assert_cli::Assert::command(&[
+        get_command_path("upgrade").as_str(),
+        "upgrade",
+        "--manifest-path",
+        &manifest,
+    ])
+    .with_env(assert_cli::Environment::inherit().insert("CARGO_IS_TEST", "1"))
+    .succeeds()
+    .stderr()
+    .contains("WARN: Not upgrading `docopt`, which is patched in `[patch.crates-io]`")
+    .unwrap();
+
+    assert_eq!(
+        get_toml(&manifest)["dependencies"]["docopt"].as_str(),
+        Some("0.8")
+    );
+
+    // Named, it is upgraded anyway.
+    execute_command(&["upgrade", "docopt"], &manifest);
+    assert_eq!(
+        get_toml(&manifest)["dependencies"]["docopt"].as_str(),
+        Some("docopt--CURRENT_VERSION_TEST")
+    );
+}
+
+#[test]
+#[cfg(feature = "fetch")]
+fn upgrade_patches_to_newest_commit() {
+    let (tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    execute_command(&["add", "docopt", "--vers", "0.8"], &manifest);
+    let upstream = tmpdir.path().join("upstream");
+    let commits = init_git_repo(&upstream, &["0.8.3", "0.8.4"]);
+    let url = url::Url::from_directory_path(&upstream).unwrap();
+    let toml = std::fs::read_to_string(&manifest).unwrap();
+    std::fs::write(
+        &manifest,
+        format!(
+            "{}\n[patch.crates-io]\ndocopt = {{ git = \"{}\", rev = \"{}\" }} # until 0.9\n",
+            toml, url, commits[0]
+        ),
+    )
+    .unwrap();
+
+    assert_cli::Assert::command(&[
+        get_command_path("upgrade").as_str(),
+        "upgrade",
+        "--upgrade-patches",
+        "--manifest-path",
+        &manifest,
+    ])
+    .with_env(
+        assert_cli::Environment::inherit()
+            .insert("CARGO_IS_TEST", "1")
+            .insert("CARGO_HOME", tmpdir.path().join("cargo-home")),
+    )
+    .succeeds()
+    .unwrap();
+
+    let toml = get_toml(&manifest);
+    assert_eq!(toml["dependencies"]["docopt"].as_str(), Some("0.8"));
+    assert_eq!(
+        toml["patch"]["crates-io"]["docopt"]["rev"].as_str(),
+        Some(commits[1].to_string().as_str())
+    );
+    assert!(std::fs::read_to_string(&manifest)
+        .unwrap()
+        .contains("} # until 0.9"));
 }
@@ -0,0 +1,129 @@
+mod utils;
+use crate::utils::{clone_out_test, copy_workspace_test, execute_command, get_command_path};
+
+#[test]
+fn verifies_valid_metadata() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/verify-manifest/Cargo.toml.valid");
+
+    execute_command(&["verify-manifest"], &manifest);
+}
+
+#[test]
+fn reports_invalid_metadata() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/verify-manifest/Cargo.toml.invalid");
+
+    assert_cli::Assert::command(&[
+        get_command_path("verify-manifest").as_str(),
+        "verify-manifest",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .fails_with(4)
+    .and()
+    .stderr()
+    .contains("`package.metadata.release`: missing required key `channel`")
+    .and()
+    .stderr()
+    .contains("`package.metadata.release.chanel`: unknown key")
+    .and()
+    .stderr()
+    .contains("`package.metadata.release.targets[0]`: expected string, found integer")
+    .and()
+    .stderr()
+    .contains("Found 3 metadata schema violation(s)")
+    .unwrap();
+}
+
+#[test]
+fn add_warns_about_invalid_metadata() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/verify-manifest/Cargo.toml.invalid");
+
+    assert_cli::Assert::command(&[
+        get_command_path("add").as_str(),
+        "add",
+        "my-package",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .with_env([("CARGO_IS_TEST", "1")])
+    .succeeds()
+    .and()
+    .stderr()
+    .contains("WARN: `package.metadata.release.chanel`: unknown key")
+    .unwrap();
+}
+
+#[test]
+fn add_warns_about_missing_schemas() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/add/Cargo.toml.sample");
+    let mut contents = std::fs::read_to_string(&manifest).unwrap();
+    contents.push_str("\n[package.metadata.cargo-edit.schema]\nrelease = \"missing.json\"\n");
+    std::fs::write(&manifest, contents).unwrap();
+
+    assert_cli::Assert::command(&[
+        get_command_path("add").as_str(),
+        "add",
+        "my-package",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .with_env([("CARGO_IS_TEST", "1")])
+    .succeeds()
+    .and()
+    .stderr()
+    .contains("WARN: Metadata left unchecked: Failed to read metadata schema `missing.json`")
+    .unwrap();
+
+    let contents = std::fs::read_to_string(&manifest).unwrap();
+    assert!(contents.contains("my-package = \"my-package--CURRENT_VERSION_TEST\""));
+}
+
+#[test]
+fn reports_invalid_target_tables() {
+    let (_tmpdir, manifest) = clone_out_test("tests/fixtures/verify-manifest/Cargo.toml.valid");
+    let mut contents = std::fs::read_to_string(&manifest).unwrap();
+    contents.push_str("\n[target.'cfg(unix'.dependencies]\nlibc = \"0.2\"\n");
+    std::fs::write(&manifest, contents).unwrap();
+
+    assert_cli::Assert::command(&[
+        get_command_path("verify-manifest").as_str(),
+        "verify-manifest",
+        &format!("--manifest-path={}", manifest),
+    ])
+    .fails_with(4)
+    .and()
+    .stderr()
+    .contains("Invalid target `cfg(unix`: missing `)`")
+    .and()
+    .stderr()
+    .contains("Found 1 invalid target table(s)")
+    .unwrap();
+}
+
+#[test]
+fn skips_broken_workspace_members() {
+    let (_tmpdir, root_manifest, workspace_manifests) = copy_workspace_test();
+    let broken = &workspace_manifests[1];
+    std::fs::write(broken, "[package]\nname = \"two\"\nversion = 0.1.0\n").unwrap();
+
+    assert_cli::Assert::command(&[
+        get_command_path("verify-manifest").as_str(),
+        "verify-manifest",
+        "--workspace",
+        &format!("--manifest-path={}", root_manifest),
+    ])
+    .fails_with(6)
+    .and()
+    .stdout()
+    .contains(workspace_manifests[0].as_str())
+    .and()
+    .stderr()
+    .contains(
+        format!(
+            "Skipped a manifest which could not be parsed:\n{}:3:14",
+            broken
+        )
+        .as_str(),
+    )
+    .and()
+    .stderr()
+    .contains("version = 0.1.0")
+    .unwrap();
+}
@@ -9,13 +9,12 @@ fn invalid_manifest() {
         "foo",
         "--manifest-path=tests/fixtures/manifest-invalid/Cargo.toml.sample",
     ])
-    .fails_with(1)
+    .fails_with(6)
     .and()
     .stderr()
     .is(
-        r#"Command failed due to unhandled error: Unable to parse Cargo.toml
+        r#"Command failed due to unhandled error: Unable to parse tests/fixtures/manifest-invalid/Cargo.toml.sample:6:7
 
-Caused by: Manifest not valid TOML
 Caused by: TOML parse error at line 6, column 7
   |
 6 | key = invalid-value
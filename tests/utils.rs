@@ -84,7 +84,7 @@ where
 {
     let subcommand_name = &command[0].as_ref();
 
-    let call = process::Command::new(&get_command_path(subcommand_name))
+    let call = process::Command::new(get_command_path(subcommand_name))
         .args(command)
         .arg(format!("--manifest-path={}", manifest))
         .env("CARGO_IS_TEST", "1")
@@ -111,11 +111,11 @@ where
     let subcommand_name = &command[0].as_ref();
     let cwd = cwd.as_ref();
 
-    let call = process::Command::new(&get_command_path(subcommand_name))
+    let call = process::Command::new(get_command_path(subcommand_name))
         .args(command)
         .arg("--package")
         .arg(pkgid)
-        .current_dir(&cwd)
+        .current_dir(cwd)
         .env("CARGO_IS_TEST", "1")
         .output()
         .expect("call to test command failed");
@@ -138,7 +138,7 @@ where
 {
     let subcommand_name = &command[0].as_ref();
 
-    let call = process::Command::new(&get_command_path(subcommand_name))
+    let call = process::Command::new(get_command_path(subcommand_name))
         .args(command)
         .arg(format!("--manifest-path={}", manifest))
         .env("CARGO_IS_TEST", "1")
@@ -163,7 +163,7 @@ where
 {
     let subcommand_name = &command[0].as_ref();
 
-    let call = process::Command::new(&get_command_path(subcommand_name))
+    let call = process::Command::new(get_command_path(subcommand_name))
         .args(command)
         .env("CARGO_IS_TEST", "1")
         .current_dir(dir)
@@ -181,6 +181,39 @@ where
     }
 }
 
+/// Create a git repository at `dir` with a commit of a `docopt` manifest for each of `versions`,
+/// returning the commits.
+#[cfg(feature = "fetch")]
+pub fn init_git_repo(dir: &Path, versions: &[&str]) -> Vec<git2::Oid> {
+    let repo = git2::Repository::init(dir).unwrap();
+    let signature = git2::Signature::now("test", "test@example.com").unwrap();
+    let mut commits: Vec<git2::Oid> = Vec::new();
+    for version in versions {
+        fs::write(
+            dir.join("Cargo.toml"),
+            format!("[package]\nname = \"docopt\"\nversion = \"{}\"\n", version),
+        )
+        .unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("Cargo.toml")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = commits.last().map(|id| repo.find_commit(*id).unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        let commit = repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                version,
+                &tree,
+                &parents,
+            )
+            .unwrap();
+        commits.push(commit);
+    }
+    commits
+}
+
 /// Parse a manifest file as TOML
 pub fn get_toml(manifest_path: &str) -> toml_edit::Document {
     let mut f = fs::File::open(manifest_path).unwrap();